@@ -0,0 +1,250 @@
+//! A snapshot of the activated environment. Running the activation starts a shell that sources the
+//! activation scripts of the packages and of the project, which costs more than everything else
+//! `pixi run` does when the environment is up to date. The environment variables that the
+//! activation adds or changes are stored in the environment in a small binary file, together with
+//! a hash of everything that influences them: the installed packages, the activation scripts, the
+//! environment the activation starts from and the version of pixi. As long as the hash matches, the
+//! snapshot is used instead of running the activation.
+//!
+//! Set `PIXI_NO_ACTIVATION_CACHE=1` to always run the activation.
+
+use crate::consts;
+use rattler_conda_types::Platform;
+use rattler_digest::{digest::Digest, Sha256};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The environment variable that disables the snapshot.
+pub const NO_ACTIVATION_CACHE_ENV: &str = "PIXI_NO_ACTIVATION_CACHE";
+
+/// The file in the `.pixi` directory of the environment that stores the snapshot.
+const SNAPSHOT_FILE: &str = "activation.bin";
+
+/// The variables of the environment the activation starts from that are left out of the hash,
+/// because shells change them all the time while they don't influence the activation.
+const VOLATILE_VARIABLES: &[&str] = &["_", "OLDPWD", "SHLVL"];
+
+/// The first bytes of a snapshot, the version changes when the format changes.
+const MAGIC: &[u8; 8] = b"PIXIACT1";
+
+/// Returns true if activation snapshots are used.
+pub fn is_enabled() -> bool {
+    !std::env::var(NO_ACTIVATION_CACHE_ENV)
+        .is_ok_and(|value| matches!(value.as_str(), "1" | "true"))
+}
+
+/// Adds the names of the files in `dir` for which `include` returns true to the hash, in a
+/// deterministic order, and their content if `with_content` is set.
+fn hash_dir(
+    hasher: &mut Sha256,
+    dir: &Path,
+    include: impl Fn(&Path) -> bool,
+    with_content: bool,
+) -> std::io::Result<()> {
+    let mut entries = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    entries.sort();
+    for entry in entries.into_iter().filter(|entry| include(entry)) {
+        hasher.update(entry.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        if with_content && entry.is_file() {
+            hasher.update(fs::read(&entry)?);
+            hasher.update([0]);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the hash of everything the activation of the environment at `prefix` depends on. The
+/// installed packages are hashed by the names of their records in `conda-meta`, which contain the
+/// version and build of every package. The activation scripts can read any variable of the
+/// environment `env` the activation starts from, so all of them are hashed.
+pub fn activation_hash(
+    prefix: &Path,
+    platform: Platform,
+    scripts: &[PathBuf],
+    package_scripts: bool,
+    clean_env: bool,
+    env: impl IntoIterator<Item = (OsString, OsString)>,
+) -> std::io::Result<String> {
+    let mut hasher = Sha256::default();
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update([0]);
+    hasher.update(prefix.to_string_lossy().as_bytes());
+    hasher.update([0]);
+    hasher.update(platform.as_str());
+    hasher.update([u8::from(package_scripts), u8::from(clean_env)]);
+    let mut env = env
+        .into_iter()
+        .filter(|(key, _)| !VOLATILE_VARIABLES.iter().any(|volatile| key == volatile))
+        .collect::<Vec<_>>();
+    env.sort();
+    for (key, value) in env {
+        hasher.update(key.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(value.to_string_lossy().as_bytes());
+        hasher.update([0]);
+    }
+    let is_record = |path: &Path| path.extension().map_or(false, |ext| ext == "json");
+    hash_dir(&mut hasher, &prefix.join("conda-meta"), is_record, false)?;
+    if package_scripts {
+        hash_dir(
+            &mut hasher,
+            &prefix.join("etc/conda/activate.d"),
+            |_| true,
+            true,
+        )?;
+    }
+    for script in scripts {
+        hasher.update(script.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(fs::read(script)?);
+        hasher.update([0]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Appends a length prefixed string to `buf`.
+fn write_str(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Reads a little endian `u32` from the start of `buf` and advances it.
+fn read_u32(buf: &mut &[u8]) -> Option<u32> {
+    let bytes = buf.get(..4)?.try_into().ok()?;
+    *buf = &buf[4..];
+    Some(u32::from_le_bytes(bytes))
+}
+
+/// Reads a length prefixed string from the start of `buf` and advances it.
+fn read_str<'a>(buf: &mut &'a [u8]) -> Option<&'a str> {
+    let len = read_u32(buf)? as usize;
+    let value = buf.get(..len)?;
+    *buf = &buf[len..];
+    std::str::from_utf8(value).ok()
+}
+
+/// Encodes the environment variables of an activation with the given hash.
+fn encode(hash: &str, env: &HashMap<String, String>) -> Vec<u8> {
+    let mut buf = MAGIC.to_vec();
+    write_str(&mut buf, hash);
+    buf.extend_from_slice(&(env.len() as u32).to_le_bytes());
+    for (key, value) in env {
+        write_str(&mut buf, key);
+        write_str(&mut buf, value);
+    }
+    buf
+}
+
+/// Decodes a snapshot, returns `None` if it is not a valid snapshot.
+fn decode(buf: &[u8]) -> Option<(String, HashMap<String, String>)> {
+    let mut buf = buf.strip_prefix(MAGIC.as_slice())?;
+    let hash = read_str(&mut buf)?.to_string();
+    let len = read_u32(&mut buf)? as usize;
+    let mut env = HashMap::with_capacity(len.min(4096));
+    for _ in 0..len {
+        let key = read_str(&mut buf)?;
+        let value = read_str(&mut buf)?;
+        env.insert(key.to_string(), value.to_string());
+    }
+    buf.is_empty().then_some((hash, env))
+}
+
+/// Returns the environment variables of the activation of the environment at `prefix` if a
+/// snapshot with the given hash was stored.
+pub fn load(prefix: &Path, hash: &str) -> Option<HashMap<String, String>> {
+    let buf = fs::read(prefix.join(consts::PIXI_DIR).join(SNAPSHOT_FILE)).ok()?;
+    let (stored_hash, env) = decode(&buf)?;
+    (stored_hash == hash).then_some(env)
+}
+
+/// Stores the environment variables that the activation of the environment at `prefix` added or
+/// changed.
+pub fn store(prefix: &Path, hash: &str, env: &HashMap<String, String>) -> std::io::Result<()> {
+    let state_dir = prefix.join(consts::PIXI_DIR);
+    fs::create_dir_all(&state_dir)?;
+    // Written to a temporary file first so concurrent runs never read a partial snapshot
+    let temp = tempfile::NamedTempFile::new_in(&state_dir)?;
+    fs::write(temp.path(), encode(hash, env))?;
+    temp.persist(state_dir.join(SNAPSHOT_FILE))
+        .map_err(|err| err.error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode() {
+        let env = HashMap::from([
+            (String::from("CONDA_PREFIX"), String::from("/env")),
+            (String::from("EMPTY"), String::new()),
+            (String::from("UNICODE"), String::from("ünïcödé")),
+        ]);
+        let buf = encode("abc", &env);
+        assert_eq!(decode(&buf), Some((String::from("abc"), env)));
+        assert_eq!(decode(&buf[..buf.len() - 1]), None);
+        assert_eq!(decode(b"PIXIACT0"), None);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let prefix = dir.path();
+        fs::create_dir_all(prefix.join("conda-meta")).unwrap();
+        fs::write(prefix.join("conda-meta/python-3.12.1-0.json"), "{}").unwrap();
+        let script = prefix.join("activate.sh");
+        fs::write(&script, "export FOO=1").unwrap();
+        let hash_with_env = |package_scripts, env: &[(&str, &str)]| {
+            activation_hash(
+                prefix,
+                Platform::Linux64,
+                &[script.clone()],
+                package_scripts,
+                false,
+                env.iter()
+                    .map(|(key, value)| (OsString::from(key), OsString::from(value))),
+            )
+            .unwrap()
+        };
+        let hash = |package_scripts| {
+            hash_with_env(package_scripts, &[("PATH", "/usr/bin"), ("SHLVL", "1")])
+        };
+
+        let env = HashMap::from([(String::from("FOO"), String::from("1"))]);
+        let first = hash(true);
+        assert_eq!(load(prefix, &first), None);
+        store(prefix, &first, &env).unwrap();
+        assert_eq!(hash(true), first);
+        assert_eq!(load(prefix, &first), Some(env));
+
+        // The hash changes with the scripts and the installed packages
+        assert_ne!(hash(false), first);
+        fs::write(&script, "export FOO=2").unwrap();
+        let second = hash(true);
+        assert_ne!(second, first);
+        fs::write(prefix.join("conda-meta/python-3.12.2-0.json"), "{}").unwrap();
+        let third = hash(true);
+        assert_ne!(third, second);
+        assert_eq!(load(prefix, &second), None);
+
+        // And with the variables the activation scripts can read, except the volatile ones
+        assert_ne!(
+            hash_with_env(true, &[("PATH", "/usr/bin"), ("CUDA_HOME", "/opt/cuda")]),
+            third
+        );
+        assert_eq!(
+            hash_with_env(true, &[("SHLVL", "2"), ("PATH", "/usr/bin")]),
+            third
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! Aliases for pixi commands, like the aliases of cargo and git. An alias maps a name to the
+//! arguments it stands for, e.g. `t = "run test"` makes `pixi t --verbose` run `pixi run test
+//! --verbose`. Aliases are defined in the `[alias]` table of the global configuration, of the
+//! configuration of a project or of the manifest, and are expanded before the command line is
+//! parsed. The commands of pixi always take precedence over aliases with the same name.
+
+use crate::config::Config;
+use crate::consts;
+use crate::project::find_project_root;
+use indexmap::IndexMap;
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Deserializer};
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// The arguments an alias expands to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias(pub Vec<String>);
+
+impl<'de> Deserialize<'de> for Alias {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        serde_untagged::UntaggedEnumVisitor::new()
+            .string(|command| {
+                shlex::split(command)
+                    .map(Alias)
+                    .ok_or_else(|| serde::de::Error::custom("the alias is not quoted correctly"))
+            })
+            .seq(|seq| seq.deserialize().map(Alias))
+            .expecting("a command or a list of arguments")
+            .deserialize(deserializer)
+    }
+}
+
+/// The aliases of a manifest, the rest of the manifest is ignored so a manifest that has errors
+/// elsewhere does not prevent the expansion.
+#[derive(Deserialize)]
+struct ManifestAliases {
+    #[serde(default)]
+    alias: IndexMap<String, Alias>,
+}
+
+/// Returns the manifest of the project in which pixi runs, without reading it.
+fn manifest_path() -> Option<PathBuf> {
+    let env_manifest_path = std::env::var_os(consts::PROJECT_MANIFEST_ENV)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from);
+    match env_manifest_path {
+        Some(path) if path.is_dir() => Some(path.join(consts::PROJECT_MANIFEST)),
+        Some(path) => Some(path),
+        None => find_project_root().map(|root| root.join(consts::PROJECT_MANIFEST)),
+    }
+}
+
+/// Loads the aliases of the global configuration and of the project in which pixi runs. Aliases of
+/// the project configuration take precedence over global ones, and aliases of the manifest over
+/// both.
+pub fn load_aliases() -> miette::Result<IndexMap<String, Alias>> {
+    let manifest_path = manifest_path().filter(|path| path.is_file());
+    let config = match manifest_path.as_ref().and_then(|path| path.parent()) {
+        Some(root) => Config::load(root)?,
+        None => Config::load_global()?,
+    };
+    let mut aliases = config.alias;
+    if let Some(manifest_path) = manifest_path {
+        let contents = std::fs::read_to_string(&manifest_path).into_diagnostic()?;
+        let manifest: ManifestAliases = toml_edit::de::from_str(&contents)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                format!(
+                    "failed to read the aliases of '{}'",
+                    manifest_path.display()
+                )
+            })?;
+        aliases.extend(manifest.alias);
+    }
+    Ok(aliases)
+}
+
+/// Returns the position of the command in `args`, which start with the name of the program. The
+/// options before the command are skipped, `takes_value` tells whether an option is followed by
+/// its value.
+pub fn command_position(args: &[OsString], takes_value: impl Fn(&str) -> bool) -> Option<usize> {
+    let mut position = 1;
+    while let Some(arg) = args.get(position) {
+        let arg = arg.to_str()?;
+        if arg == "--" {
+            return None;
+        }
+        if !arg.starts_with('-') {
+            return Some(position);
+        }
+        position += if !arg.contains('=') && takes_value(arg) {
+            2
+        } else {
+            1
+        };
+    }
+    None
+}
+
+/// Replaces the alias in place of the command in `args` by the arguments it stands for. An alias
+/// may refer to another alias. Names for which `is_command` returns true are never expanded.
+pub fn expand_aliases(
+    mut args: Vec<OsString>,
+    aliases: &IndexMap<String, Alias>,
+    is_command: impl Fn(&str) -> bool,
+    takes_value: impl Fn(&str) -> bool,
+) -> miette::Result<Vec<OsString>> {
+    let Some(position) = command_position(&args, takes_value) else {
+        return Ok(args);
+    };
+    let mut expanded: Vec<String> = Vec::new();
+    loop {
+        let Some(name) = args[position].to_str().map(ToOwned::to_owned) else {
+            return Ok(args);
+        };
+        if is_command(&name) {
+            return Ok(args);
+        }
+        let Some(alias) = aliases.get(&name) else {
+            return Ok(args);
+        };
+        if expanded.contains(&name) {
+            miette::bail!(
+                "the alias `{name}` refers to itself through `{}`",
+                expanded.join("` -> `")
+            );
+        }
+        if alias.0.is_empty() {
+            miette::bail!("the alias `{name}` is empty");
+        }
+        args.splice(position..=position, alias.0.iter().map(OsString::from));
+        expanded.push(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    fn aliases(contents: &str) -> IndexMap<String, Alias> {
+        toml_edit::de::from_str::<ManifestAliases>(contents)
+            .unwrap()
+            .alias
+    }
+
+    fn expand(
+        arguments: &[&str],
+        aliases: &IndexMap<String, Alias>,
+    ) -> miette::Result<Vec<String>> {
+        let is_command = |name: &str| matches!(name, "run" | "update" | "install");
+        let takes_value = |option: &str| option == "--color";
+        Ok(
+            expand_aliases(args(arguments), aliases, is_command, takes_value)?
+                .into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_aliases() {
+        let aliases = aliases(
+            r#"
+            [alias]
+            t = "run test --filter 'slow tests'"
+            up = ["update", "--dry-run"]
+            "#,
+        );
+        assert_eq!(
+            aliases["t"],
+            Alias(vec![
+                String::from("run"),
+                String::from("test"),
+                String::from("--filter"),
+                String::from("slow tests")
+            ])
+        );
+        assert_eq!(aliases["up"].0, vec!["update", "--dry-run"]);
+        assert!(
+            toml_edit::de::from_str::<ManifestAliases>("alias = { t = \"run 'test\" }").is_err()
+        );
+    }
+
+    #[test]
+    fn test_expand_aliases() {
+        let aliases = aliases(
+            r#"
+            [alias]
+            t = "run test"
+            tv = "t --verbose"
+            run = "install"
+            loop = "again"
+            again = "loop"
+            "#,
+        );
+        assert_eq!(
+            expand(&["pixi", "--color", "never", "tv", "-x"], &aliases).unwrap(),
+            vec!["pixi", "--color", "never", "run", "test", "--verbose", "-x"]
+        );
+        // Commands of pixi are not expanded
+        assert_eq!(
+            expand(&["pixi", "run", "t"], &aliases).unwrap(),
+            vec!["pixi", "run", "t"]
+        );
+        assert_eq!(
+            expand(&["pixi", "unknown"], &aliases).unwrap(),
+            vec!["pixi", "unknown"]
+        );
+        assert!(expand(&["pixi", "loop"], &aliases).is_err());
+    }
+}
@@ -0,0 +1,456 @@
+//! Resolution of the credentials used to access private channels and package indexes.
+//!
+//! Credentials are looked up from the following sources, the first source that provides
+//! credentials for a host wins:
+//!
+//! 1. Environment variables: `PIXI_TOKEN_<HOST>` (bearer token), `PIXI_CONDA_TOKEN_<HOST>` (conda
+//!    token) or `PIXI_USERNAME_<HOST>` together with `PIXI_PASSWORD_<HOST>` (basic HTTP).
+//!    `<HOST>` is the uppercased host with all non-alphanumeric characters replaced by `_`.
+//! 2. The keyring (or its fallback file) in which `pixi auth login` stores credentials.
+//! 3. The `~/.netrc` file, or the file pointed to by the `NETRC` environment variable.
+//!
+//! For environment variables and the keyring the parent domains of a host are also tried, e.g.
+//! credentials for `prefix.dev` also apply to `repo.prefix.dev`.
+
+use crate::config::Config;
+use crate::{default_authenticated_client, default_client};
+use base64::Engine;
+use miette::IntoDiagnostic;
+use once_cell::sync::{Lazy, OnceCell};
+use rattler_networking::{AuthenticatedClient, Authentication, AuthenticationStorage};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use url::Url;
+
+/// The source from which credentials were resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthenticationSource {
+    /// From the `PIXI_*_<HOST>` environment variables.
+    Environment,
+    /// From the keyring used by `pixi auth login`.
+    Keyring,
+    /// From a netrc file.
+    Netrc,
+}
+
+impl Display for AuthenticationSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthenticationSource::Environment => write!(f, "environment"),
+            AuthenticationSource::Keyring => write!(f, "keyring"),
+            AuthenticationSource::Netrc => write!(f, "netrc"),
+        }
+    }
+}
+
+/// Returns the host itself followed by all its parent domains that consist of at least two
+/// labels. (e.g. `repo.prefix.dev`, `prefix.dev`)
+fn host_candidates(host: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(host), |host| {
+        host.split_once('.')
+            .map(|(_, parent)| parent)
+            .filter(|parent| parent.contains('.'))
+    })
+}
+
+/// Converts a host to the suffix used in environment variable names.
+fn env_var_suffix(host: &str) -> String {
+    host.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Reads credentials for the host from the `PIXI_*_<HOST>` environment variables.
+fn from_environment(host: &str, env: &dyn Fn(&str) -> Option<String>) -> Option<Authentication> {
+    let suffix = env_var_suffix(host);
+    let var = |name: &str| env(&format!("PIXI_{name}_{suffix}"));
+    if let Some(token) = var("TOKEN") {
+        Some(Authentication::BearerToken(token))
+    } else if let Some(token) = var("CONDA_TOKEN") {
+        Some(Authentication::CondaToken(token))
+    } else {
+        Some(Authentication::BasicHTTP {
+            username: var("USERNAME")?,
+            password: var("PASSWORD")?,
+        })
+    }
+}
+
+/// Reads credentials for the host from the keyring used by `pixi auth login`.
+fn from_keyring(host: &str) -> Option<Authentication> {
+    let storage = AuthenticationStorage::default();
+    let wildcard = host
+        .split_once('.')
+        .map(|(_, parent)| format!("*.{parent}"));
+    std::iter::once(host.to_string())
+        .chain(wildcard)
+        .find_map(|host| match storage.get(&host) {
+            Ok(auth) => auth,
+            Err(e) => {
+                tracing::debug!("failed to read credentials for {host} from the keyring: {e}");
+                None
+            }
+        })
+}
+
+/// Returns the path of the netrc file.
+fn netrc_path(env: &dyn Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    match env("NETRC") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => {
+            dirs::home_dir().map(|home| home.join(if cfg!(windows) { "_netrc" } else { ".netrc" }))
+        }
+    }
+}
+
+/// Parses the contents of a netrc file and returns the login and password for the given host. A
+/// `default` entry is used if there is no entry for the host.
+fn parse_netrc(contents: &str, host: &str) -> Option<(String, String)> {
+    let mut tokens = contents.split_whitespace();
+    let mut current_matches = false;
+    let mut found = None;
+    let mut default = None;
+    let mut login = None;
+    let mut password = None;
+    let mut is_default = false;
+
+    let mut finish = |matches: bool,
+                      is_default: bool,
+                      login: &mut Option<String>,
+                      password: &mut Option<String>| {
+        if let (Some(l), Some(p)) = (login.take(), password.take()) {
+            if matches && found.is_none() {
+                found = Some((l, p));
+            } else if is_default && default.is_none() {
+                default = Some((l, p));
+            }
+        }
+    };
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "machine" => {
+                finish(current_matches, is_default, &mut login, &mut password);
+                current_matches = tokens.next() == Some(host);
+                is_default = false;
+            }
+            "default" => {
+                finish(current_matches, is_default, &mut login, &mut password);
+                current_matches = false;
+                is_default = true;
+            }
+            "login" => login = tokens.next().map(ToOwned::to_owned),
+            "password" => password = tokens.next().map(ToOwned::to_owned),
+            "account" => {
+                tokens.next();
+            }
+            "macdef" => {
+                // Macros are not supported, skip until the end of the file to be safe.
+                break;
+            }
+            _ => {}
+        }
+    }
+    finish(current_matches, is_default, &mut login, &mut password);
+
+    found.or(default)
+}
+
+/// Resolves credentials from the environment, the keyring and the netrc file. The netrc file is
+/// read once and keyring lookups are remembered per host, so the sources are not hit again for
+/// every request.
+struct Credentials {
+    /// Looks up an environment variable.
+    env: Box<dyn Fn(&str) -> Option<String> + Send + Sync>,
+    /// The contents of the netrc file, if any.
+    netrc: OnceCell<Option<String>>,
+    /// The credentials that were read from the keyring per host.
+    keyring: Mutex<HashMap<String, Option<Authentication>>>,
+}
+
+impl Credentials {
+    /// Constructs credentials that read the environment variables of the process.
+    fn from_process_env() -> Self {
+        Self::new(|name| std::env::var(name).ok())
+    }
+
+    /// Constructs credentials that look up environment variables through `env`.
+    fn new(env: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Self {
+        Self {
+            env: Box::new(env),
+            netrc: OnceCell::new(),
+            keyring: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads credentials for the host from the keyring, only the first lookup for a host hits it.
+    fn keyring_credentials(&self, host: &str) -> Option<Authentication> {
+        let mut keyring = self.keyring.lock().unwrap_or_else(|e| e.into_inner());
+        keyring
+            .entry(host.to_string())
+            .or_insert_with(|| from_keyring(host))
+            .clone()
+    }
+
+    /// Reads credentials for the host from the netrc file.
+    fn netrc_credentials(&self, host: &str) -> Option<Authentication> {
+        let contents = self.netrc.get_or_init(|| {
+            netrc_path(&self.env).and_then(|path| std::fs::read_to_string(path).ok())
+        });
+        parse_netrc(contents.as_deref()?, host)
+            .map(|(username, password)| Authentication::BasicHTTP { username, password })
+    }
+
+    /// Resolves the credentials for the given host from all supported sources in order of
+    /// precedence. See the module documentation for the order.
+    fn resolve(&self, host: &str) -> Option<(Authentication, AuthenticationSource)> {
+        host_candidates(host)
+            .find_map(|host| from_environment(host, &self.env))
+            .map(|auth| (auth, AuthenticationSource::Environment))
+            .or_else(|| {
+                self.keyring_credentials(host)
+                    .map(|auth| (auth, AuthenticationSource::Keyring))
+            })
+            .or_else(|| {
+                self.netrc_credentials(host)
+                    .map(|auth| (auth, AuthenticationSource::Netrc))
+            })
+    }
+
+    /// Returns the `Authorization` header with the bearer token or basic HTTP credentials from
+    /// the environment or a netrc file for requests to the url. Credentials from the keyring are
+    /// applied by the authenticated client itself.
+    fn authorization_header(&self, url: &Url) -> Option<HeaderValue> {
+        let value = match url.host_str().and_then(|host| self.resolve(host))? {
+            (_, AuthenticationSource::Keyring) => return None,
+            (Authentication::BearerToken(token), _) => format!("Bearer {token}"),
+            (Authentication::BasicHTTP { username, password }, _) => format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"))
+            ),
+            (Authentication::CondaToken(_), _) => return None,
+        };
+        let mut value = HeaderValue::from_str(&value).ok()?;
+        value.set_sensitive(true);
+        Some(value)
+    }
+}
+
+/// The credentials of this process.
+static CREDENTIALS: Lazy<Credentials> = Lazy::new(Credentials::from_process_env);
+
+/// Resolves the credentials for the given host from all supported sources in order of
+/// precedence. See the module documentation for the order.
+pub fn resolve_authentication(host: &str) -> Option<(Authentication, AuthenticationSource)> {
+    CREDENTIALS.resolve(host)
+}
+
+/// Embeds credentials from the environment or a netrc file into the url.
+///
+/// Credentials from the keyring are applied by the authenticated client itself, so this only
+/// handles the sources that it does not know about. Basic HTTP credentials are added as the user
+/// info of the url and conda tokens are added as a `/t/<token>` path prefix. Bearer tokens cannot
+/// be represented in a url and are left to the caller.
+pub fn authenticate_url(url: &Url) -> Url {
+    let Some(host) = url.host_str() else {
+        return url.clone();
+    };
+    if !url.username().is_empty() {
+        return url.clone();
+    }
+
+    match resolve_authentication(host) {
+        Some((Authentication::BasicHTTP { username, password }, source))
+            if source != AuthenticationSource::Keyring =>
+        {
+            let mut url = url.clone();
+            if url.set_username(&username).is_err() || url.set_password(Some(&password)).is_err() {
+                return url;
+            }
+            url
+        }
+        Some((Authentication::CondaToken(token), AuthenticationSource::Environment))
+            if !url.path().starts_with("/t/") =>
+        {
+            let mut url = url.clone();
+            let path = format!("/t/{token}{}", url.path());
+            url.set_path(&path);
+            url
+        }
+        _ => url.clone(),
+    }
+}
+
+/// Returns the `Authorization` header for requests to the url, see
+/// [`Credentials::authorization_header`].
+fn authorization_header(url: &Url) -> Option<HeaderValue> {
+    CREDENTIALS.authorization_header(url)
+}
+
+/// Returns a client for requests to the host of the url that sends the credentials of the host
+/// from the environment or a netrc file. Unlike [`authenticate_url`] this keeps the credentials out
+/// of the urls, which end up in the lock file for package indexes, and supports bearer tokens. The
+/// credentials are not sent along when a request is redirected to another host.
+pub fn client_for_url(url: &Url) -> Client {
+    let Some(header) = authorization_header(url) else {
+        return default_client();
+    };
+    Config::load_global()
+        .and_then(|config| config.http_client_builder())
+        .and_then(|builder| {
+            builder
+                .default_headers(HeaderMap::from_iter([(AUTHORIZATION, header)]))
+                .build()
+                .into_diagnostic()
+        })
+        .unwrap_or_else(|err| {
+            tracing::warn!("failed to construct the client for '{url}': {err:?}");
+            default_client()
+        })
+}
+
+/// Returns the authenticated client for requests to the host of the url, with the credentials of
+/// the keyring and, like [`client_for_url`], those from the environment or a netrc file.
+pub fn authenticated_client_for_url(url: &Url) -> AuthenticatedClient {
+    if authorization_header(url).is_none() {
+        return default_authenticated_client();
+    }
+    AuthenticatedClient::from_client(client_for_url(url), Default::default())
+}
+
+/// Adds credentials from the environment or a netrc file to a request to the given url.
+///
+/// Like [`authenticate_url`] this only handles the sources the authenticated client does not know
+/// about, but it also supports bearer tokens by adding them as a header.
+pub fn authenticate_request(
+    request: reqwest::RequestBuilder,
+    url: &Url,
+) -> reqwest::RequestBuilder {
+    match url.host_str().and_then(resolve_authentication) {
+        Some((Authentication::BearerToken(token), source))
+            if source != AuthenticationSource::Keyring =>
+        {
+            request.bearer_auth(token)
+        }
+        Some((Authentication::BasicHTTP { username, password }, source))
+            if source != AuthenticationSource::Keyring =>
+        {
+            request.basic_auth(username, Some(password))
+        }
+        _ => request,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn test_host_candidates() {
+        assert_eq!(
+            host_candidates("repo.prefix.dev").collect_vec(),
+            vec!["repo.prefix.dev", "prefix.dev"]
+        );
+        assert_eq!(
+            host_candidates("localhost").collect_vec(),
+            vec!["localhost"]
+        );
+        assert_eq!(env_var_suffix("repo.prefix.dev"), "REPO_PREFIX_DEV");
+    }
+
+    #[test]
+    fn test_parse_netrc() {
+        let contents = r#"
+            machine example.com login foo password bar
+            machine other.com
+                login baz
+                password qux
+            default login anonymous password secret
+        "#;
+        assert_eq!(
+            parse_netrc(contents, "other.com"),
+            Some((String::from("baz"), String::from("qux")))
+        );
+        assert_eq!(
+            parse_netrc(contents, "example.com"),
+            Some((String::from("foo"), String::from("bar")))
+        );
+        assert_eq!(
+            parse_netrc(contents, "unknown.com"),
+            Some((String::from("anonymous"), String::from("secret")))
+        );
+        assert_eq!(parse_netrc("", "example.com"), None);
+    }
+
+    /// Credentials that only see the given environment variables.
+    fn credentials(vars: &[(&str, &str)]) -> Credentials {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Credentials::new(move |name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_authorization_header() {
+        let credentials = credentials(&[
+            ("PIXI_TOKEN_BEARER_PIXI_TEST_INVALID", "secret-token"),
+            ("PIXI_USERNAME_BASIC_PIXI_TEST_INVALID", "user"),
+            ("PIXI_PASSWORD_BASIC_PIXI_TEST_INVALID", "pass"),
+        ]);
+
+        let header = credentials
+            .authorization_header(
+                &Url::parse("https://bearer.pixi-test.invalid/channel/linux-64/").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(header.to_str().unwrap(), "Bearer secret-token");
+        assert!(header.is_sensitive());
+        let header = credentials
+            .authorization_header(&Url::parse("https://basic.pixi-test.invalid/simple/").unwrap())
+            .unwrap();
+        assert_eq!(header.to_str().unwrap(), "Basic dXNlcjpwYXNz");
+        assert!(credentials
+            .authorization_header(&Url::parse("file:///channel").unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn test_netrc_is_read_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let netrc = dir.path().join("netrc");
+        std::fs::write(
+            &netrc,
+            "machine netrc.pixi-test.invalid login foo password bar",
+        )
+        .unwrap();
+        let credentials = credentials(&[("NETRC", netrc.to_str().unwrap())]);
+
+        let expected = Authentication::BasicHTTP {
+            username: String::from("foo"),
+            password: String::from("bar"),
+        };
+        assert_eq!(
+            credentials.resolve("netrc.pixi-test.invalid"),
+            Some((expected.clone(), AuthenticationSource::Netrc))
+        );
+
+        // Later lookups use the contents that were read before.
+        std::fs::remove_file(&netrc).unwrap();
+        assert_eq!(
+            credentials.resolve("netrc.pixi-test.invalid"),
+            Some((expected, AuthenticationSource::Netrc))
+        );
+    }
+}
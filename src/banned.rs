@@ -0,0 +1,239 @@
+//! Packages that a project must not depend on, e.g. because of their license or a known
+//! vulnerability. They are listed in the `[banned-dependencies]` table of the manifest, and a
+//! solve that locks one of them fails, whether the project depends on it directly or through
+//! another package.
+
+use crate::lock_file::{DependencyGraph, NodeKind};
+use crate::utils::spec_format::normalize_pypi_name;
+use crate::Project;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use rattler_conda_types::{PackageName, Platform, Version, VersionSpec};
+use rattler_lock::{CondaLock, LockedDependencyKind};
+use serde::{Deserialize, Deserializer};
+use serde_with::{serde_as, DisplayFromStr};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The versions of a package that are banned, with the reason why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BannedDependency {
+    /// The banned versions, every version is banned if this is `None`.
+    pub versions: Option<VersionSpec>,
+
+    /// Why the package is banned, e.g. its license.
+    pub reason: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for BannedDependency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[serde_as]
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct VersionAndReason {
+            #[serde_as(as = "Option<DisplayFromStr>")]
+            #[serde(default)]
+            version: Option<VersionSpec>,
+            #[serde(default)]
+            reason: Option<String>,
+        }
+
+        serde_untagged::UntaggedEnumVisitor::new()
+            .string(|version| {
+                VersionSpec::from_str(version)
+                    .map(|versions| BannedDependency {
+                        versions: Some(versions),
+                        reason: None,
+                    })
+                    .map_err(serde::de::Error::custom)
+            })
+            .map(|map| {
+                let VersionAndReason { version, reason } = map.deserialize()?;
+                Ok(BannedDependency {
+                    versions: version,
+                    reason,
+                })
+            })
+            .expecting("a version spec or a mapping with `version` and `reason`")
+            .deserialize(deserializer)
+    }
+}
+
+impl BannedDependency {
+    /// Returns true if the given version is banned.
+    fn bans(&self, version: &str) -> bool {
+        match &self.versions {
+            None => true,
+            // A version that can't be parsed, e.g. of a pypi package, is banned unless it is
+            // certain that the spec doesn't match it.
+            Some(versions) => Version::from_str(version).map_or(true, |v| versions.matches(&v)),
+        }
+    }
+}
+
+/// A locked package that is banned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BannedPackage {
+    pub name: String,
+    pub version: String,
+    pub platform: Platform,
+    pub pypi: bool,
+    pub reason: Option<String>,
+    /// The packages that depend on the banned package, `the project` if it is a direct dependency.
+    pub required_by: Vec<String>,
+}
+
+impl Display for BannedPackage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.version)?;
+        if self.pypi {
+            write!(f, " (pypi)")?;
+        }
+        write!(f, " for {}", self.platform)?;
+        if !self.required_by.is_empty() {
+            write!(f, ", required by {}", self.required_by.join(", "))?;
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, ": {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the locked packages of `platform` that are banned by `banned`.
+fn find_banned(
+    lock_file: &CondaLock,
+    platform: Platform,
+    banned: &IndexMap<PackageName, BannedDependency>,
+) -> Vec<BannedPackage> {
+    let banned: HashMap<String, &BannedDependency> = banned
+        .iter()
+        .map(|(name, banned)| (normalize_pypi_name(name.as_normalized()), banned))
+        .collect();
+    lock_file
+        .get_packages_by_platform(platform)
+        .filter_map(|package| {
+            let banned = banned.get(&normalize_pypi_name(&package.name))?;
+            banned.bans(&package.version).then(|| BannedPackage {
+                name: package.name.clone(),
+                version: package.version.clone(),
+                platform,
+                pypi: matches!(package.kind, LockedDependencyKind::Pypi(_)),
+                reason: banned.reason.clone(),
+                required_by: Vec::new(),
+            })
+        })
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .collect()
+}
+
+/// Returns the locked packages of the given platforms that are banned by the project, with the
+/// packages that depend on them.
+pub fn find_banned_packages(
+    project: &Project,
+    lock_file: &CondaLock,
+    platforms: &[Platform],
+) -> Vec<BannedPackage> {
+    let banned = project.banned_dependencies();
+    if banned.is_empty() {
+        return Vec::new();
+    }
+    let mut found = Vec::new();
+    for platform in platforms.iter().copied() {
+        let mut packages = find_banned(lock_file, platform, banned);
+        if packages.is_empty() {
+            continue;
+        }
+        let graph = DependencyGraph::from_project(project, lock_file, platform);
+        for package in packages.iter_mut() {
+            let kind = if package.pypi {
+                NodeKind::Pypi
+            } else {
+                NodeKind::Conda
+            };
+            package.required_by = graph
+                .edges
+                .iter()
+                .filter(|edge| {
+                    edge.kind == kind
+                        && graph
+                            .nodes
+                            .iter()
+                            .any(|node| node.id == edge.to && node.name == package.name)
+                })
+                .filter_map(|edge| graph.nodes.iter().find(|node| node.id == edge.from))
+                .map(|node| match node.kind {
+                    NodeKind::Project => String::from("the project"),
+                    _ => node.name.clone(),
+                })
+                .unique()
+                .collect();
+        }
+        found.extend(packages);
+    }
+    found
+}
+
+/// Returns an error that lists the banned packages in the solution of the given platforms, if
+/// there are any.
+pub fn check_banned_packages(
+    project: &Project,
+    lock_file: &CondaLock,
+    platforms: &[Platform],
+) -> miette::Result<()> {
+    let found = find_banned_packages(project, lock_file, platforms);
+    if found.is_empty() {
+        return Ok(());
+    }
+    Err(miette::miette!(
+        help = "the packages are listed in the [banned-dependencies] table of the manifest, remove or constrain the dependencies that require them",
+        "the solution contains {} banned package{}:\n{}",
+        found.len(),
+        if found.len() == 1 { "" } else { "s" },
+        found.iter().map(|package| format!("  - {package}")).join("\n")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize_banned_dependency() {
+        let banned: IndexMap<PackageName, BannedDependency> = toml_edit::de::from_str(
+            r#"
+            log4j = "*"
+            openssl = { version = "<3", reason = "end-of-life" }
+            pyqt = { reason = "GPL licensed, use pyside6" }
+            "#,
+        )
+        .unwrap();
+        let banned = banned.values().collect_vec();
+        assert!(banned[0].bans("2.17.0"));
+        assert!(banned[1].bans("1.1.1w"));
+        assert!(!banned[1].bans("3.2.0"));
+        assert_eq!(banned[1].reason.as_deref(), Some("end-of-life"));
+        assert_eq!(banned[2].versions, None);
+        assert!(banned[2].bans("5.15.9"));
+    }
+
+    #[test]
+    fn test_display_banned_package() {
+        let package = BannedPackage {
+            name: String::from("pyqt"),
+            version: String::from("5.15.9"),
+            platform: Platform::Linux64,
+            pypi: false,
+            reason: Some(String::from("GPL licensed")),
+            required_by: vec![String::from("the project"), String::from("matplotlib")],
+        };
+        assert_eq!(
+            package.to_string(),
+            "pyqt 5.15.9 for linux-64, required by the project, matplotlib: GPL licensed"
+        );
+    }
+}
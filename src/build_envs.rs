@@ -0,0 +1,218 @@
+//! The environments in which python source distributions are built. They live in
+//! `.pixi/build-envs/<environment>/<name>-<version>` and each one records the distribution it was
+//! created for, so the build environments of distributions that are no longer locked can be
+//! pruned. A build environment that is dropped before its build finished is removed right away,
+//! which keeps failed builds from leaking directories.
+
+use crate::utils::spec_format::normalize_pypi_name;
+use crate::{consts, Project};
+use miette::IntoDiagnostic;
+use rattler_lock::CondaLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The file in a build environment that records the distribution it was created for.
+const RECORD_FILE: &str = "pixi-build-env.json";
+
+/// The distribution a build environment was created for.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+struct BuildEnvironmentRecord {
+    /// The normalized name of the distribution.
+    name: String,
+    /// The version of the distribution.
+    version: String,
+}
+
+/// Returns the directory with the build environments of all environments of the project.
+pub fn build_envs_root(project: &Project) -> PathBuf {
+    project.pixi_dir().join(consts::BUILD_ENVS_DIR)
+}
+
+/// Returns the directory with the build environments of the selected environment of the project.
+fn build_envs_dir(project: &Project) -> PathBuf {
+    build_envs_root(project).join(project.environment_name().unwrap_or("default"))
+}
+
+/// A build environment of a source distribution. The directory is removed when this is dropped,
+/// unless the build finished and [`BuildEnvironment::keep`] was called.
+#[derive(Debug)]
+pub struct BuildEnvironment {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl BuildEnvironment {
+    /// Creates the build environment of a distribution, replacing the one of a previous build.
+    pub fn create(project: &Project, name: &str, version: &str) -> miette::Result<Self> {
+        let record = BuildEnvironmentRecord {
+            name: normalize_pypi_name(name),
+            version: version.to_string(),
+        };
+        let path = build_envs_dir(project).join(format!("{}-{}", record.name, record.version));
+        remove_dir(&path)?;
+        std::fs::create_dir_all(&path).into_diagnostic()?;
+        let build_env = Self { path, keep: false };
+        std::fs::write(
+            build_env.path.join(RECORD_FILE),
+            serde_json::to_string(&record).into_diagnostic()?,
+        )
+        .into_diagnostic()?;
+        Ok(build_env)
+    }
+
+    /// Returns the directory of the build environment.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keeps the build environment after a successful build, so a following build of the same
+    /// distribution can reuse it. Returns its directory.
+    pub fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        std::mem::take(&mut self.path)
+    }
+}
+
+impl Drop for BuildEnvironment {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        if let Err(err) = remove_dir(&self.path) {
+            tracing::warn!(
+                "failed to remove the build environment {}: {err:?}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+/// Removes a directory, it is not an error if it does not exist.
+fn remove_dir(path: &Path) -> miette::Result<()> {
+    match std::fs::remove_dir_all(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err).into_diagnostic(),
+        _ => Ok(()),
+    }
+}
+
+/// Reads the record of a build environment, `None` if it has none, e.g. because pixi was killed
+/// while it was created.
+fn read_record(path: &Path) -> Option<BuildEnvironmentRecord> {
+    serde_json::from_str(&std::fs::read_to_string(path.join(RECORD_FILE)).ok()?).ok()
+}
+
+/// Removes the build environments of the selected environment of the project whose distribution
+/// is not locked in `lock_file`, and those without a record. Returns the removed directories.
+pub fn prune_build_environments(
+    project: &Project,
+    lock_file: &CondaLock,
+) -> miette::Result<Vec<PathBuf>> {
+    let locked = lock_file
+        .package
+        .iter()
+        .filter(|package| package.as_pypi().is_some())
+        .map(|package| BuildEnvironmentRecord {
+            name: normalize_pypi_name(&package.name),
+            version: package.version.clone(),
+        })
+        .collect::<HashSet<_>>();
+
+    let entries = match std::fs::read_dir(build_envs_dir(project)) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).into_diagnostic(),
+    };
+    let mut pruned = Vec::new();
+    for entry in entries {
+        let path = entry.into_diagnostic()?.path();
+        if !path.is_dir() {
+            continue;
+        }
+        if read_record(&path).map_or(false, |record| locked.contains(&record)) {
+            continue;
+        }
+        remove_dir(&path)?;
+        pruned.push(path);
+    }
+    Ok(pruned)
+}
+
+/// Removes the build environments of all environments of the project. Returns the number of
+/// build environments that were removed.
+pub fn clean_build_environments(project: &Project) -> miette::Result<usize> {
+    let root = build_envs_root(project);
+    let count = std::fs::read_dir(&root)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .flat_map(|environment| std::fs::read_dir(environment.path()).into_iter().flatten())
+        .count();
+    remove_dir(&root)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::manifest::Manifest;
+
+    fn project(root: &Path) -> Project {
+        Project::from_manifest(
+            Manifest::from_str(
+                root,
+                r#"
+                [project]
+                name = "foo"
+                channels = ["conda-forge"]
+                platforms = ["linux-64"]
+                "#,
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_prune_build_environments() {
+        let root = tempfile::tempdir().unwrap();
+        let project = project(root.path());
+
+        // The lock file of the `pypi` example locks flask 3.0.0.
+        let lock_file = crate::lock_file::read_lock_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/pypi/pixi.lock"),
+        )
+        .unwrap();
+
+        let locked = BuildEnvironment::create(&project, "Flask", "3.0.0")
+            .unwrap()
+            .keep();
+        let stale = BuildEnvironment::create(&project, "flask", "2.3.0")
+            .unwrap()
+            .keep();
+        let interrupted = build_envs_dir(&project).join("werkzeug-3.0.1");
+        std::fs::create_dir_all(&interrupted).unwrap();
+
+        let mut pruned = prune_build_environments(&project, &lock_file).unwrap();
+        pruned.sort();
+        assert_eq!(pruned, vec![stale.clone(), interrupted.clone()]);
+        assert!(locked.is_dir());
+        assert!(!stale.exists());
+        assert!(!interrupted.exists());
+
+        assert_eq!(clean_build_environments(&project).unwrap(), 1);
+        assert!(!build_envs_root(&project).exists());
+    }
+
+    #[test]
+    fn test_failed_build_is_removed() {
+        let root = tempfile::tempdir().unwrap();
+        let project = project(root.path());
+
+        let build_env = BuildEnvironment::create(&project, "flask", "3.0.0").unwrap();
+        let path = build_env.path().to_path_buf();
+        assert!(path.join(RECORD_FILE).is_file());
+        drop(build_env);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,469 @@
+//! Builds conda packages from source when the channels of a project have no build of a dependency
+//! for one of its platforms. The fallback is opt-in through the `[build-fallback]` table of the
+//! manifest. The recipe of such a package is built with `rattler-build` and the result is stored
+//! in a local channel in `.pixi/build-fallback`, which is used by every following solve so a
+//! package is only built once per platform. The lock file refers to the local channel with
+//! [`LOCAL_CHANNEL_URL`] instead of its absolute path, so it is the same on every machine.
+
+use crate::{auth, consts, default_authenticated_client, Project};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::{Channel, ChannelConfig, PackageName, Platform, RepoDataRecord};
+use rattler_lock::{CondaLock, LockedDependencyKind};
+use rattler_repodata_gateway::sparse::SparseRepoData;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tokio::process::Command;
+use url::Url;
+
+/// The name of the recipe files of rattler-build.
+const RECIPE_FILE: &str = "recipe.yaml";
+
+/// The name of the recipe files of conda-build, which most conda-forge feedstocks still use.
+const CONDA_BUILD_RECIPE_FILE: &str = "meta.yaml";
+
+/// The url of the local channel in the lock file, it is replaced by the url of the local channel
+/// of the project when the lock file is installed.
+pub const LOCAL_CHANNEL_URL: &str = "pixi-build-fallback:///";
+
+/// Only one package is built at a time, the builds of all platforms share the local channel.
+static BUILD_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// How to build the packages that are not available in the channels of the project.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BuildFallback {
+    /// The recipes of packages, either a path relative to the project or the url of a `.tar.gz`
+    /// archive that contains a `recipe.yaml`.
+    #[serde(default)]
+    pub recipes: IndexMap<PackageName, String>,
+
+    /// Whether the recipe of the conda-forge feedstock is used for packages without a recipe.
+    #[serde(default = "default_conda_forge_feedstocks")]
+    pub conda_forge_feedstocks: bool,
+}
+
+fn default_conda_forge_feedstocks() -> bool {
+    true
+}
+
+/// Returns the directory of the local channel with the packages that were built from source.
+pub fn local_channel_dir(project: &Project) -> PathBuf {
+    project.pixi_dir().join(consts::BUILD_FALLBACK_DIR)
+}
+
+/// Returns the `file://` url of the local channel of the project, with a trailing slash.
+fn local_channel_url(project: &Project) -> Option<Url> {
+    Url::from_directory_path(local_channel_dir(project)).ok()
+}
+
+/// Returns the url of a package in the local channel as it is written to the lock file, relative
+/// to [`LOCAL_CHANNEL_URL`]. Urls of other channels are returned as is.
+pub fn portable_url(project: &Project, url: &Url) -> Url {
+    local_channel_url(project)
+        .and_then(|local| {
+            url.as_str()
+                .strip_prefix(local.as_str())
+                .map(str::to_string)
+        })
+        .and_then(|relative| Url::parse(&format!("{LOCAL_CHANNEL_URL}{relative}")).ok())
+        .unwrap_or_else(|| url.clone())
+}
+
+/// Returns the url of a package in the lock file, with [`LOCAL_CHANNEL_URL`] replaced by the url of
+/// the local channel of the project.
+pub fn resolve_url(project: &Project, url: &Url) -> Url {
+    url.as_str()
+        .strip_prefix(LOCAL_CHANNEL_URL)
+        .zip(local_channel_url(project))
+        .and_then(|(relative, local)| local.join(relative).ok())
+        .unwrap_or_else(|| url.clone())
+}
+
+/// Returns a copy of the lock file in which the packages of the local channel refer to the local
+/// channel of the project.
+pub fn resolve_lock_file_urls(project: &Project, lock_file: &CondaLock) -> CondaLock {
+    let mut lock_file = lock_file.clone();
+    for package in lock_file.package.iter_mut() {
+        if let LockedDependencyKind::Conda(conda) = &mut package.kind {
+            conda.url = resolve_url(project, &conda.url);
+        }
+    }
+    lock_file
+}
+
+/// Returns the local channel with the packages that were built from source.
+fn local_channel(project: &Project) -> miette::Result<Channel> {
+    Channel::from_str(
+        local_channel_dir(project).to_string_lossy(),
+        &ChannelConfig::default(),
+    )
+    .into_diagnostic()
+}
+
+/// Loads the repodata of the local channel for the `platforms` and noarch. Subdirectories without
+/// packages are skipped.
+pub fn load_local_repodata(
+    project: &Project,
+    platforms: &[Platform],
+) -> miette::Result<Vec<SparseRepoData>> {
+    let channel = local_channel(project)?;
+    let dir = local_channel_dir(project);
+    platforms
+        .iter()
+        .chain([&Platform::NoArch])
+        .unique()
+        .map(|platform| (platform, dir.join(platform.as_str()).join("repodata.json")))
+        .filter(|(_, path)| path.is_file())
+        .map(|(platform, path)| {
+            SparseRepoData::new(channel.clone(), platform.to_string(), &path, None)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to read '{}'", path.display()))
+        })
+        .collect()
+}
+
+/// Returns the packages for which none of the `available` records exist, of the `names` and of
+/// the dependencies of the available packages. A dependency only counts when every record of the
+/// package depends on it, so a package that can still be installed without it is not built.
+pub fn missing_packages(
+    names: impl IntoIterator<Item = PackageName>,
+    available: &[Vec<RepoDataRecord>],
+) -> Vec<PackageName> {
+    let available_names = available
+        .iter()
+        .flatten()
+        .map(|record| &record.package_record.name)
+        .collect::<HashSet<_>>();
+
+    let required_dependencies = available
+        .iter()
+        .flatten()
+        .into_group_map_by(|record| record.package_record.name.clone())
+        .into_values()
+        .flat_map(|records| {
+            records
+                .iter()
+                .map(|record| dependency_names(record))
+                .reduce(|common, names| common.intersection(&names).cloned().collect())
+                .unwrap_or_default()
+        })
+        .sorted_by(|a, b| a.as_normalized().cmp(b.as_normalized()))
+        .collect_vec();
+
+    names
+        .into_iter()
+        .chain(required_dependencies)
+        .unique()
+        .filter(|name| !name.as_normalized().starts_with("__") && !available_names.contains(name))
+        .collect()
+}
+
+/// Returns the names of the dependencies of a record.
+fn dependency_names(record: &RepoDataRecord) -> HashSet<PackageName> {
+    record
+        .package_record
+        .depends
+        .iter()
+        .filter_map(|spec| spec.split_whitespace().next())
+        .filter_map(|name| PackageName::from_str(name).ok())
+        .collect()
+}
+
+/// Returns the `recipe.yaml`, or else the `meta.yaml`, in `dir`, in its `recipe` directory, or in
+/// one of its subdirectories, which is where it ends up when an archive of a repository is
+/// extracted.
+pub fn find_recipe(dir: &Path) -> Option<PathBuf> {
+    let candidates = |dir: &Path| {
+        [RECIPE_FILE, CONDA_BUILD_RECIPE_FILE].map(|file| {
+            [dir.join(file), dir.join("recipe").join(file)]
+                .into_iter()
+                .find(|path| path.is_file())
+        })
+    };
+    candidates(dir)
+        .into_iter()
+        .chain(
+            std::fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .sorted_by_key(|entry| entry.file_name())
+                .flat_map(|entry| candidates(&entry.path())),
+        )
+        .flatten()
+        .next()
+}
+
+/// Returns the url of the archive of the conda-forge feedstock of a package.
+fn feedstock_url(name: &PackageName) -> Url {
+    Url::parse(&format!(
+        "https://github.com/conda-forge/{}-feedstock/archive/HEAD.tar.gz",
+        name.as_normalized()
+    ))
+    .expect("the url of a feedstock is valid")
+}
+
+/// Downloads and extracts the archive at `url` into `dir`.
+async fn download_recipe_archive(url: &Url, dir: &Path) -> miette::Result<()> {
+    let bytes = auth::authenticate_request(default_authenticated_client().get(url.clone()), url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to download the recipe from {url}"))?
+        .bytes()
+        .await
+        .into_diagnostic()?;
+
+    if dir.exists() {
+        std::fs::remove_dir_all(dir).into_diagnostic()?;
+    }
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        tar::Archive::new(flate2::read::GzDecoder::new(std::io::Cursor::new(bytes))).unpack(&dir)
+    })
+    .await
+    .into_diagnostic()?
+    .into_diagnostic()
+    .wrap_err_with(|| format!("failed to extract the recipe downloaded from {url}"))
+}
+
+/// Returns the recipe of a package, downloading it first if it is not a local path.
+async fn fetch_recipe(
+    project: &Project,
+    build_fallback: &BuildFallback,
+    name: &PackageName,
+) -> miette::Result<PathBuf> {
+    let url = match build_fallback.recipes.get(name) {
+        Some(source) => match Url::parse(source) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => url,
+            _ => {
+                let path = project.root().join(source);
+                let recipe = if path.is_file() {
+                    Some(path)
+                } else {
+                    find_recipe(&path)
+                };
+                return recipe.ok_or_else(|| {
+                    miette::miette!(
+                        "the recipe of {} was not found at '{source}'",
+                        name.as_source()
+                    )
+                });
+            }
+        },
+        None if build_fallback.conda_forge_feedstocks => feedstock_url(name),
+        None => miette::bail!(
+            help = "add a recipe for it to the `[build-fallback.recipes]` table of the manifest",
+            "there is no recipe to build {} from source",
+            name.as_source()
+        ),
+    };
+
+    let dir = local_channel_dir(project)
+        .join("recipes")
+        .join(name.as_normalized());
+    download_recipe_archive(&url, &dir).await?;
+    find_recipe(&dir).ok_or_else(|| {
+        miette::miette!(
+            "the source downloaded from {url} contains no {RECIPE_FILE} or {CONDA_BUILD_RECIPE_FILE}"
+        )
+    })
+}
+
+/// Builds a recipe for `platform` into the local channel, a `recipe.yaml` with `rattler-build` and
+/// a `meta.yaml` with `conda-build`. The output of the build is written to a log file in the
+/// `logs` directory of the local channel.
+async fn build_recipe(
+    project: &Project,
+    name: &PackageName,
+    recipe: &Path,
+    platform: Platform,
+) -> miette::Result<()> {
+    let output_dir = local_channel_dir(project);
+    let log_dir = output_dir.join("logs");
+    std::fs::create_dir_all(&log_dir).into_diagnostic()?;
+    let log_path = log_dir.join(format!("{}-{platform}.log", name.as_normalized()));
+    let log = std::fs::File::create(&log_path).into_diagnostic()?;
+
+    let is_conda_build = recipe
+        .file_name()
+        .map_or(false, |file| file == CONDA_BUILD_RECIPE_FILE);
+    let (tool, mut command) = if is_conda_build {
+        // conda-build only builds for the platform it runs on.
+        if platform != Platform::current() && platform != Platform::NoArch {
+            miette::bail!(
+                help = "add a `recipe.yaml` for it to the `[build-fallback.recipes]` table of the manifest",
+                "the recipe of {} is a {CONDA_BUILD_RECIPE_FILE}, which can't be built for {platform} on {}",
+                name.as_source(),
+                Platform::current()
+            );
+        }
+        let mut command = Command::new("conda-build");
+        command
+            .arg(recipe.parent().unwrap_or(recipe))
+            .arg("--output-folder")
+            .arg(&output_dir)
+            .arg("--no-anaconda-upload");
+        ("conda-build", command)
+    } else {
+        let mut command = Command::new("rattler-build");
+        command
+            .arg("build")
+            .arg("--recipe")
+            .arg(recipe)
+            .arg("--target-platform")
+            .arg(platform.as_str())
+            .arg("--output-dir")
+            .arg(&output_dir);
+        ("rattler-build", command)
+    };
+    command
+        .arg("--channel")
+        .arg(output_dir.to_string_lossy().as_ref());
+    for channel in project.channels() {
+        command.arg("--channel").arg(channel.base_url().as_str());
+    }
+    let status = match command
+        .stdout(log.try_clone().into_diagnostic()?)
+        .stderr(log)
+        .status()
+        .await
+    {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => miette::bail!(
+            help = format!("install it with `pixi global install {tool}`"),
+            "building {} from source requires `{tool}`",
+            name.as_source()
+        ),
+        result => result
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to run `{tool}`"))?,
+    };
+    if !status.success() {
+        miette::bail!(
+            help = format!("the output of the build is in '{}'", log_path.display()),
+            "failed to build {} for {platform} from source",
+            name.as_source()
+        );
+    }
+    Ok(())
+}
+
+/// Builds the `missing` packages for `platform` from source and returns the repodata of the local
+/// channel for the platform.
+pub async fn build_missing_packages(
+    project: &Project,
+    build_fallback: &BuildFallback,
+    platform: Platform,
+    missing: &[PackageName],
+) -> miette::Result<Vec<SparseRepoData>> {
+    let _guard = BUILD_LOCK.lock().await;
+    for name in missing {
+        tracing::info!(
+            "building {} for {platform} from source, no channel provides it",
+            name.as_source()
+        );
+        let recipe = fetch_recipe(project, build_fallback, name).await?;
+        build_recipe(project, name, &recipe, platform).await?;
+    }
+    load_local_repodata(project, &[platform])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{repodata_record, CONDA_FORGE};
+
+    #[test]
+    fn test_find_recipe() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(find_recipe(dir.path()), None);
+
+        let recipe = dir
+            .path()
+            .join("foo-feedstock-main")
+            .join("recipe")
+            .join(RECIPE_FILE);
+        std::fs::create_dir_all(recipe.parent().unwrap()).unwrap();
+        std::fs::write(&recipe, "").unwrap();
+        assert_eq!(find_recipe(dir.path()), Some(recipe));
+
+        let meta = dir.path().join(CONDA_BUILD_RECIPE_FILE);
+        std::fs::write(&meta, "").unwrap();
+        assert_eq!(find_recipe(dir.path()), Some(meta));
+
+        // A `recipe.yaml` is preferred over a `meta.yaml`.
+        let recipe = dir.path().join(RECIPE_FILE);
+        std::fs::write(&recipe, "").unwrap();
+        assert_eq!(find_recipe(dir.path()), Some(recipe));
+    }
+
+    #[test]
+    fn test_feedstock_url() {
+        assert_eq!(
+            feedstock_url(&PackageName::new_unchecked("libfoo")).as_str(),
+            "https://github.com/conda-forge/libfoo-feedstock/archive/HEAD.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_portable_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = Project::from_str(
+            dir.path(),
+            "[project]\nname = \"foo\"\nchannels = [\"conda-forge\"]\nplatforms = [\"linux-64\"]\n",
+        )
+        .unwrap();
+        let local = Url::from_directory_path(local_channel_dir(&project))
+            .unwrap()
+            .join("linux-64/libfoo-1.0-0.conda")
+            .unwrap();
+        let portable = portable_url(&project, &local);
+        assert_eq!(
+            portable.as_str(),
+            "pixi-build-fallback:///linux-64/libfoo-1.0-0.conda"
+        );
+        assert_eq!(resolve_url(&project, &portable), local);
+
+        // Packages of other channels are kept as is.
+        let remote =
+            Url::parse("https://conda.anaconda.org/conda-forge/linux-64/python-3.12.0-0.conda")
+                .unwrap();
+        assert_eq!(portable_url(&project, &remote), remote);
+        assert_eq!(resolve_url(&project, &remote), remote);
+    }
+
+    fn record(name: &str, version: &str, depends: &[&str]) -> RepoDataRecord {
+        let mut record = repodata_record(name, version, CONDA_FORGE);
+        record.package_record.depends = depends.iter().map(|spec| spec.to_string()).collect();
+        record
+    }
+
+    #[test]
+    fn test_missing_packages() {
+        let names = ["python", "libfoo", "__glibc"].map(PackageName::new_unchecked);
+        assert_eq!(
+            missing_packages(names, &[]),
+            vec![
+                PackageName::new_unchecked("python"),
+                PackageName::new_unchecked("libfoo")
+            ]
+        );
+
+        // Dependencies of the available packages are missing too, when every record of the
+        // package needs them.
+        let available = vec![vec![
+            record("app", "1.0", &["libfoo >=1.0", "libold", "__glibc >=2.17"]),
+            record("app", "2.0", &["libfoo >=2.0", "libbar"]),
+        ]];
+        assert_eq!(
+            missing_packages([PackageName::new_unchecked("app")], &available),
+            vec![PackageName::new_unchecked("libfoo")]
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! A report of the channels of a project for `pixi info --channels`: the order in which they are
+//! used, the url every channel resolves to, the platforms each of them provides and the channel
+//! every locked direct dependency comes from. Combinations that usually lead to solve failures or
+//! surprising results are reported as conflicts.
+
+use crate::config::Config;
+use crate::lock_file::conda_channel_url;
+use crate::repodata::friendly_channel_name;
+use crate::{auth, default_authenticated_client, Project};
+use futures::future::join_all;
+use itertools::Itertools;
+use rattler_conda_types::{Channel, PackageName, Platform};
+use rattler_lock::{CondaLock, LockedDependencyKind};
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use url::Url;
+
+/// A channel of the project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChannelInfo {
+    /// The position of the channel, channels with a lower priority number are preferred.
+    pub priority: usize,
+    pub name: String,
+    /// The url of the channel after the channel alias is applied.
+    pub url: Url,
+    /// The url the packages are downloaded from, if a `url-rewrites` entry of the configuration
+    /// applies to the channel.
+    pub mirror: Option<Url>,
+    /// The platforms of the project for which the channel has packages.
+    pub platforms: Vec<Platform>,
+    /// The platforms of the project for which the channel has no packages.
+    pub missing_platforms: Vec<Platform>,
+}
+
+/// The channel a direct dependency of the project is locked from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LockedChannel {
+    pub package: String,
+    pub version: String,
+    /// The name of the channel, or its url if it is not a channel of the project.
+    pub channel: String,
+}
+
+/// A combination of channels and locked packages that needs attention.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum ChannelConflict {
+    /// Two channels of the project resolve to the same url.
+    DuplicateChannel { first: String, second: String },
+    /// None of the channels provides packages for a platform of the project.
+    UnsupportedPlatform { platform: Platform },
+    /// A direct dependency is locked from a channel that is not a channel of the project.
+    UnknownChannel { package: String, channel: String },
+}
+
+impl Display for ChannelConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelConflict::DuplicateChannel { first, second } => {
+                write!(f, "{second} resolves to the same url as {first}")
+            }
+            ChannelConflict::UnsupportedPlatform { platform } => {
+                write!(f, "none of the channels provides packages for {platform}")
+            }
+            ChannelConflict::UnknownChannel { package, channel } => write!(
+                f,
+                "{package} is locked from {channel}, which is not a channel of the project"
+            ),
+        }
+    }
+}
+
+/// The channels of a project and the channels its direct dependencies are locked from.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelReport {
+    pub channels: Vec<ChannelInfo>,
+    /// The platform of the locked dependencies.
+    pub platform: Platform,
+    pub locked_dependencies: Vec<LockedChannel>,
+    pub conflicts: Vec<ChannelConflict>,
+}
+
+/// Returns true if the channel at `url` has a `repodata.json` for `platform`.
+async fn has_subdir(url: &Url, platform: Platform) -> bool {
+    let Ok(repodata) = url.join(&format!("{}/repodata.json", platform.as_str())) else {
+        return false;
+    };
+    if repodata.scheme() == "file" {
+        return repodata.to_file_path().map_or(false, |path| path.is_file());
+    }
+    auth::authenticate_request(
+        default_authenticated_client().head(repodata.clone()),
+        &repodata,
+    )
+    .send()
+    .await
+    .map_or(false, |response| response.status().is_success())
+}
+
+/// Describes a channel, checking which of the `platforms` it provides.
+async fn channel_info(
+    priority: usize,
+    channel: &Channel,
+    platforms: &[Platform],
+    config: &Config,
+) -> ChannelInfo {
+    let url = channel.base_url().clone();
+    let mirror = Some(config.rewrite_url(&url)).filter(|mirror| mirror != &url);
+    let available = join_all(
+        platforms
+            .iter()
+            .map(|platform| has_subdir(mirror.as_ref().unwrap_or(&url), *platform)),
+    )
+    .await;
+    let (platforms, missing_platforms) =
+        platforms
+            .iter()
+            .zip(available)
+            .partition_map(|(platform, available)| {
+                if available {
+                    itertools::Either::Left(*platform)
+                } else {
+                    itertools::Either::Right(*platform)
+                }
+            });
+    ChannelInfo {
+        priority,
+        name: friendly_channel_name(channel),
+        url,
+        mirror,
+        platforms,
+        missing_platforms,
+    }
+}
+
+/// Returns the channel every conda package in `names` is locked from for `platform`. Packages that
+/// are not from one of the `channels` are reported by their channel url.
+pub fn locked_channels(
+    lock_file: &CondaLock,
+    platform: Platform,
+    names: &[PackageName],
+    channels: &[ChannelInfo],
+) -> Vec<LockedChannel> {
+    lock_file
+        .get_packages_by_platform(platform)
+        .filter(|package| {
+            names
+                .iter()
+                .any(|name| name.as_normalized() == package.name)
+        })
+        .filter_map(|package| match &package.kind {
+            LockedDependencyKind::Conda(conda) => Some((package, conda_channel_url(&conda.url)?)),
+            LockedDependencyKind::Pypi(_) => None,
+        })
+        .map(|(package, url)| LockedChannel {
+            package: package.name.clone(),
+            version: package.version.clone(),
+            channel: channels
+                .iter()
+                .find(|channel| channel.url == url)
+                .map_or_else(|| url.to_string(), |channel| channel.name.clone()),
+        })
+        .sorted_by(|a, b| a.package.cmp(&b.package))
+        .collect()
+}
+
+/// Returns the conflicts between the `channels` of a project with the `platforms` and its
+/// `locked` direct dependencies.
+pub fn find_conflicts(
+    channels: &[ChannelInfo],
+    platforms: &[Platform],
+    locked: &[LockedChannel],
+) -> Vec<ChannelConflict> {
+    let duplicates = channels
+        .iter()
+        .tuple_combinations()
+        .filter(|(first, second)| first.url == second.url)
+        .map(|(first, second)| ChannelConflict::DuplicateChannel {
+            first: first.name.clone(),
+            second: second.name.clone(),
+        });
+    let unsupported = platforms
+        .iter()
+        .filter(|platform| {
+            !channels
+                .iter()
+                .any(|channel| channel.platforms.contains(platform))
+        })
+        .map(|platform| ChannelConflict::UnsupportedPlatform {
+            platform: *platform,
+        });
+    let unknown = locked
+        .iter()
+        .filter(|locked| {
+            !channels
+                .iter()
+                .any(|channel| channel.name == locked.channel)
+        })
+        .map(|locked| ChannelConflict::UnknownChannel {
+            package: locked.package.clone(),
+            channel: locked.channel.clone(),
+        });
+    duplicates.chain(unsupported).chain(unknown).collect()
+}
+
+/// Creates the channel report of the project. The channels are queried for the platforms of the
+/// project, the locked dependencies are those of `platform`.
+pub async fn channel_report(
+    project: &Project,
+    lock_file: &CondaLock,
+    platform: Platform,
+) -> ChannelReport {
+    let platforms = project.platforms().to_vec();
+    let channels = join_all(
+        project
+            .channels()
+            .iter()
+            .enumerate()
+            .map(|(index, channel)| channel_info(index + 1, channel, &platforms, project.config())),
+    )
+    .await;
+
+    let names = project.all_dependencies(platform).into_keys().collect_vec();
+    let locked_dependencies = locked_channels(lock_file, platform, &names, &channels);
+    let conflicts = find_conflicts(&channels, &platforms, &locked_dependencies);
+    ChannelReport {
+        channels,
+        platform,
+        locked_dependencies,
+        conflicts,
+    }
+}
+
+impl Display for ChannelReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let join = |platforms: &[Platform]| platforms.iter().join(", ");
+        writeln!(f, "Channels\n------------\n")?;
+        for channel in &self.channels {
+            writeln!(f, "{:>3}. {}", channel.priority, channel.name)?;
+            writeln!(f, "{:20}: {}", "     Url", channel.url)?;
+            if let Some(mirror) = &channel.mirror {
+                writeln!(f, "{:20}: {}", "     Mirror", mirror)?;
+            }
+            if !channel.platforms.is_empty() {
+                writeln!(f, "{:20}: {}", "     Platforms", join(&channel.platforms))?;
+            }
+            if !channel.missing_platforms.is_empty() {
+                writeln!(
+                    f,
+                    "{:20}: {}",
+                    "     Missing platforms",
+                    join(&channel.missing_platforms)
+                )?;
+            }
+        }
+
+        if !self.locked_dependencies.is_empty() {
+            writeln!(
+                f,
+                "\nLocked direct dependencies ({})\n------------\n",
+                self.platform
+            )?;
+            for locked in &self.locked_dependencies {
+                writeln!(
+                    f,
+                    "{:20}: {} from {}",
+                    locked.package, locked.version, locked.channel
+                )?;
+            }
+        }
+
+        if !self.conflicts.is_empty() {
+            writeln!(f, "\nConflicts\n------------\n")?;
+            for conflict in &self.conflicts {
+                writeln!(f, "{} {conflict}", console::style("!").yellow().bold())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn channel(priority: usize, name: &str, url: &str, platforms: &[Platform]) -> ChannelInfo {
+        ChannelInfo {
+            priority,
+            name: name.to_string(),
+            url: Url::parse(url).unwrap(),
+            mirror: None,
+            platforms: platforms.to_vec(),
+            missing_platforms: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_locked_channels() {
+        let lock_file = crate::lock_file::read_lock_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/cpp-sdl/pixi.lock"),
+        )
+        .unwrap();
+        let names = [PackageName::new_unchecked("sdl2")];
+
+        let channels = [channel(
+            1,
+            "conda-forge",
+            "https://conda.anaconda.org/conda-forge/",
+            &[Platform::Linux64],
+        )];
+        let locked = locked_channels(&lock_file, Platform::Linux64, &names, &channels);
+        assert_eq!(locked.len(), 1);
+        assert_eq!(locked[0].package, "sdl2");
+        assert_eq!(locked[0].channel, "conda-forge");
+        assert!(find_conflicts(&channels, &[Platform::Linux64], &locked).is_empty());
+
+        let locked = locked_channels(&lock_file, Platform::Linux64, &names, &[]);
+        assert_eq!(locked[0].channel, "https://conda.anaconda.org/conda-forge/");
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let channels = [
+            channel(
+                1,
+                "conda-forge",
+                "https://conda.anaconda.org/conda-forge/",
+                &[Platform::Linux64],
+            ),
+            channel(
+                2,
+                "https://conda.anaconda.org/conda-forge/",
+                "https://conda.anaconda.org/conda-forge/",
+                &[Platform::Linux64],
+            ),
+        ];
+        let locked = [LockedChannel {
+            package: String::from("python"),
+            version: String::from("3.12.1"),
+            channel: String::from("https://repo.example.com/internal/"),
+        }];
+        assert_eq!(
+            find_conflicts(&channels, &[Platform::Linux64, Platform::Win64], &locked),
+            vec![
+                ChannelConflict::DuplicateChannel {
+                    first: String::from("conda-forge"),
+                    second: String::from("https://conda.anaconda.org/conda-forge/"),
+                },
+                ChannelConflict::UnsupportedPlatform {
+                    platform: Platform::Win64
+                },
+                ChannelConflict::UnknownChannel {
+                    package: String::from("python"),
+                    channel: String::from("https://repo.example.com/internal/"),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+//! Writes the spans of a pixi command to a file in the Chrome trace event format with
+//! `--trace-file`, so slow solves, downloads and installs can be profiled in
+//! [Perfetto](https://ui.perfetto.dev) or `chrome://tracing` and attached to bug reports.
+//!
+//! When pixi runs as part of a larger traced job, e.g. a CI pipeline that sets the W3C
+//! `TRACEPARENT` environment variable, the trace id and parent span id are stored in the metadata
+//! of the file so the trace can be correlated with the rest of the job.
+
+use miette::{Context, IntoDiagnostic};
+use serde_json::{json, Map, Value};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context as LayerContext;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// The environment variable with the W3C trace context of the parent of the process.
+const TRACEPARENT_ENV: &str = "TRACEPARENT";
+
+/// A W3C trace context, parsed from a `traceparent` value like
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub parent_id: String,
+}
+
+impl TraceContext {
+    /// Parses a `traceparent` value, returns `None` if it is not valid.
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let is_hex = |value: &str, len: usize| {
+            value.len() == len && value.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+        };
+        let mut parts = traceparent.trim().split('-');
+        let (version, trace_id, parent_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        // Only version 00 is defined, it has no other parts
+        if version != "00" || parts.next().is_some() {
+            return None;
+        }
+        if !is_hex(trace_id, 32) || !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+            return None;
+        }
+        // An id of all zeros is invalid
+        if trace_id.chars().all(|c| c == '0') || parent_id.chars().all(|c| c == '0') {
+            return None;
+        }
+        Some(Self {
+            trace_id: trace_id.to_string(),
+            parent_id: parent_id.to_string(),
+        })
+    }
+
+    /// Returns the trace context of the parent of this process, if any.
+    pub fn from_env() -> Option<Self> {
+        std::env::var(TRACEPARENT_ENV)
+            .ok()
+            .and_then(|value| Self::parse(&value))
+    }
+}
+
+/// The file the trace events are written to.
+struct TraceWriter {
+    file: File,
+    events: usize,
+}
+
+impl TraceWriter {
+    /// Writes a single event. Every event is written immediately, so the trace is usable even when
+    /// the process exits without flushing.
+    fn write(&mut self, event: &Value) {
+        let separator = if self.events == 0 { "" } else { ",\n" };
+        if let Err(err) = write!(self.file, "{separator}{event}") {
+            tracing::debug!("failed to write to the trace file: {err}");
+        }
+        self.events += 1;
+    }
+}
+
+/// The start and the fields of a span, stored in the extensions of the span.
+struct SpanTiming {
+    start: Instant,
+    fields: Map<String, Value>,
+}
+
+/// Collects the fields of a span as json values.
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), json!(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), json!(format!("{value:?}")));
+    }
+}
+
+/// A [`Layer`] that writes every span as a pair of async begin and end events, so spans of
+/// concurrent work (e.g. downloads) each get their own track.
+pub struct ChromeTraceLayer {
+    writer: Arc<Mutex<TraceWriter>>,
+    start: Instant,
+    pid: u32,
+}
+
+/// Closes the trace file when it is dropped.
+pub struct ChromeTraceGuard {
+    writer: Arc<Mutex<TraceWriter>>,
+}
+
+impl Drop for ChromeTraceGuard {
+    fn drop(&mut self) {
+        if let Ok(mut writer) = self.writer.lock() {
+            let _ = writeln!(writer.file, "\n]");
+        }
+    }
+}
+
+impl ChromeTraceLayer {
+    /// Creates the trace file at `path`. The file is completed when the returned guard is dropped,
+    /// without it the file can still be opened because the closing bracket of the format is
+    /// optional.
+    pub fn create(path: &Path) -> miette::Result<(Self, ChromeTraceGuard)> {
+        let mut file = File::create(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to create the trace file '{}'", path.display()))?;
+        writeln!(file, "[").into_diagnostic()?;
+
+        let pid = std::process::id();
+        let mut writer = TraceWriter { file, events: 0 };
+        writer.write(&json!({
+            "name": "process_name",
+            "ph": "M",
+            "pid": pid,
+            "args": { "name": "pixi" },
+        }));
+        if let Some(context) = TraceContext::from_env() {
+            let labels = format!(
+                "trace_id={},parent_id={}",
+                context.trace_id, context.parent_id
+            );
+            writer.write(&json!({
+                "name": "process_labels",
+                "ph": "M",
+                "pid": pid,
+                "args": {
+                    "labels": labels,
+                    "trace_id": context.trace_id,
+                    "parent_id": context.parent_id,
+                },
+            }));
+        }
+
+        let writer = Arc::new(Mutex::new(writer));
+        Ok((
+            Self {
+                writer: writer.clone(),
+                start: Instant::now(),
+                pid,
+            },
+            ChromeTraceGuard { writer },
+        ))
+    }
+
+    /// Returns the number of microseconds between the start of the trace and `instant`.
+    fn timestamp(&self, instant: Instant) -> f64 {
+        instant.saturating_duration_since(self.start).as_nanos() as f64 / 1000.0
+    }
+}
+
+impl<S> Layer<S> for ChromeTraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        let mut fields = Map::new();
+        attrs.record(&mut JsonVisitor(&mut fields));
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanTiming {
+                start: Instant::now(),
+                fields,
+            });
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: LayerContext<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                values.record(&mut JsonVisitor(&mut timing.fields));
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: LayerContext<'_, S>) {
+        let end = Instant::now();
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(timing) = span.extensions_mut().remove::<SpanTiming>() else {
+            return;
+        };
+        let metadata = span.metadata();
+        let event = |phase: &str, ts: f64, args: Value| {
+            json!({
+                "name": metadata.name(),
+                "cat": metadata.target(),
+                "ph": phase,
+                "id": format!("{:#x}", id.into_u64()),
+                "pid": self.pid,
+                "tid": 0,
+                "ts": ts,
+                "args": args,
+            })
+        };
+        let begin = event(
+            "b",
+            self.timestamp(timing.start),
+            Value::Object(timing.fields),
+        );
+        let end = event("e", self.timestamp(end), json!({}));
+        if let Ok(mut writer) = self.writer.lock() {
+            writer.write(&begin);
+            writer.write(&end);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_parse_traceparent() {
+        assert_eq!(
+            TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            Some(TraceContext {
+                trace_id: String::from("4bf92f3577b34da6a3ce929d0e0e4736"),
+                parent_id: String::from("00f067aa0ba902b7"),
+            })
+        );
+        for traceparent in [
+            "",
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-00",
+        ] {
+            assert_eq!(TraceContext::parse(traceparent), None, "{traceparent}");
+        }
+    }
+
+    #[test]
+    fn test_write_trace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.json");
+        let (layer, guard) = ChromeTraceLayer::create(&path).unwrap();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let solve = tracing::info_span!("solve", platform = "linux-64");
+            let _enter = solve.enter();
+            tracing::info_span!("fetch_repodata").in_scope(|| {});
+        });
+        drop(guard);
+
+        let events: Vec<Value> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let spans = events
+            .iter()
+            .filter(|event| event["ph"] == "b")
+            .map(|event| event["name"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec!["fetch_repodata", "solve"]);
+        let solve = events
+            .iter()
+            .find(|event| event["ph"] == "b" && event["name"] == "solve")
+            .unwrap();
+        assert_eq!(solve["args"]["platform"], "linux-64");
+        assert_eq!(events.iter().filter(|event| event["ph"] == "e").count(), 2);
+    }
+}
@@ -0,0 +1,114 @@
+//! Detection of continuous integration environments. On CI nobody watches the output of pixi or
+//! answers its questions, so progress bars are hidden and commands that would prompt either use
+//! the default answer or fail with an explanation instead of waiting for input.
+//!
+//! CI is detected from the environment variables that the common CI providers set. `--ci` forces
+//! the behavior locally, `PIXI_CI=1` does the same and `PIXI_CI=0` disables the detection.
+
+use once_cell::sync::OnceCell;
+
+/// The environment variable that forces (`1`) or disables (`0`) the CI behavior.
+pub const PIXI_CI_ENV: &str = "PIXI_CI";
+
+/// The environment variables that CI providers set, with the name of the provider.
+const CI_PROVIDERS: &[(&str, &str)] = &[
+    ("GITHUB_ACTIONS", "GitHub Actions"),
+    ("GITLAB_CI", "GitLab CI"),
+    ("TF_BUILD", "Azure Pipelines"),
+    ("CIRCLECI", "CircleCI"),
+    ("BUILDKITE", "Buildkite"),
+    ("TRAVIS", "Travis CI"),
+    ("JENKINS_URL", "Jenkins"),
+    ("TEAMCITY_VERSION", "TeamCity"),
+    ("BITBUCKET_BUILD_NUMBER", "Bitbucket Pipelines"),
+    ("APPVEYOR", "AppVeyor"),
+    ("DRONE", "Drone"),
+    ("CODEBUILD_BUILD_ID", "AWS CodeBuild"),
+    // Set by most providers, including those that are not listed above
+    ("CI", "CI"),
+];
+
+static CI_PROVIDER: OnceCell<Option<&'static str>> = OnceCell::new();
+
+/// Returns false for the values that commonly mean "no", e.g. `CI=false`.
+fn is_truthy(value: &str) -> bool {
+    !matches!(
+        value.trim().to_lowercase().as_str(),
+        "" | "0" | "false" | "no" | "off"
+    )
+}
+
+/// Returns the name of the CI provider that the environment variables, looked up with `var`,
+/// belong to. `None` if this is not a CI environment or the detection is disabled.
+pub fn detect_ci(var: impl Fn(&str) -> Option<String>) -> Option<&'static str> {
+    if let Some(value) = var(PIXI_CI_ENV).filter(|value| !value.trim().is_empty()) {
+        return is_truthy(&value).then_some(PIXI_CI_ENV);
+    }
+    CI_PROVIDERS
+        .iter()
+        .find(|(name, _)| var(name).map_or(false, |value| is_truthy(&value)))
+        .map(|(_, provider)| *provider)
+}
+
+/// Sets whether pixi runs on CI for the rest of the process, `force` is set by `--ci`. Has no
+/// effect if it was already determined.
+pub fn init(force: bool) {
+    CI_PROVIDER.get_or_init(|| {
+        if force {
+            Some("--ci")
+        } else {
+            detect_ci(|name| std::env::var(name).ok())
+        }
+    });
+}
+
+/// Returns the name of the CI provider pixi runs on, if any.
+pub fn provider() -> Option<&'static str> {
+    *CI_PROVIDER.get_or_init(|| detect_ci(|name| std::env::var(name).ok()))
+}
+
+/// Returns true if pixi runs on CI.
+pub fn is_ci() -> bool {
+    provider().is_some()
+}
+
+/// Returns true if the user can be asked questions: pixi is not running on CI and stderr is a
+/// terminal.
+pub fn is_interactive() -> bool {
+    !is_ci() && console::Term::stderr().is_term()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn detect(vars: &[(&str, &str)]) -> Option<&'static str> {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        detect_ci(|name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn test_detect_ci() {
+        assert_eq!(detect(&[]), None);
+        assert_eq!(
+            detect(&[("GITHUB_ACTIONS", "true"), ("CI", "true")]),
+            Some("GitHub Actions")
+        );
+        assert_eq!(detect(&[("TF_BUILD", "True")]), Some("Azure Pipelines"));
+        assert_eq!(
+            detect(&[("JENKINS_URL", "https://jenkins.example.com/")]),
+            Some("Jenkins")
+        );
+        assert_eq!(detect(&[("CI", "1")]), Some("CI"));
+        assert_eq!(detect(&[("CI", "false")]), None);
+
+        // PIXI_CI forces or disables the detection
+        assert_eq!(detect(&[("PIXI_CI", "1")]), Some(PIXI_CI_ENV));
+        assert_eq!(detect(&[("PIXI_CI", "0"), ("GITLAB_CI", "true")]), None);
+        assert_eq!(
+            detect(&[("PIXI_CI", ""), ("GITLAB_CI", "true")]),
+            Some("GitLab CI")
+        );
+    }
+}
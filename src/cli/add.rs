@@ -1,9 +1,18 @@
 use crate::{
-    consts,
+    ci, concurrency, consts, default_client,
+    drift::{find_project_drift, remove_drifted_distributions, DriftedPackage, DriftedPackageKind},
     environment::{get_up_to_date_prefix, verify_prefix_location_unchanged, LockFileUsage},
-    project::{manifest::PyPiRequirement, DependencyType, Project, SpecType},
+    lock_file::conda_pypi_name_mapping,
+    prefix::Prefix,
+    project::{
+        manifest::{PyPiRequirement, TargetSelector},
+        DependencyType, Project, SpecType,
+    },
+    repodata::friendly_channel_name,
+    utils::spec_format::{self, normalize_pypi_name},
 };
 use clap::Parser;
+use futures::{stream, StreamExt};
 use indexmap::IndexMap;
 use itertools::Itertools;
 
@@ -18,6 +27,7 @@ use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
     str::FromStr,
+    time::Duration,
 };
 
 /// Adds a dependency to the project
@@ -42,6 +52,14 @@ pub struct Args {
     ///
     /// - `pixi add python pytest`: This will add both `python` and `pytest` to the project's dependencies.
     ///
+    /// All dependencies are solved together, and either all of them are added or none of them.
+    ///
+    /// The version range sugar of other package managers is translated to conda ranges:
+    ///
+    /// - `pixi add "requests~=2.31"`: adds `requests >=2.31,<3`.
+    ///
+    /// - `pixi add "numpy^1.26.2"` or `pixi add "numpy~1.26.2"`: adds `numpy >=1.26.2,<2` or `numpy >=1.26.2,<1.27`.
+    ///
     /// The `--platform` and `--build/--host` flags make the dependency target specific.
     ///
     /// - `pixi add python --platform linux-64 --platform osx-arm64`: Will add the latest version of python for linux-64 and osx-arm64 platforms.
@@ -54,10 +72,10 @@ pub struct Args {
     /// - `pixi add --pypi boto3`
     /// - `pixi add --pypi "boto3==version"
     ///
-    #[arg(required = true)]
+    #[arg(required_unless_present = "from_prefix")]
     pub specs: Vec<String>,
 
-    /// The path to 'pixi.toml'
+    /// The path to 'pixi.toml' or the directory that contains it
     #[arg(long)]
     pub manifest_path: Option<PathBuf>,
 
@@ -84,6 +102,26 @@ pub struct Args {
     /// The platform(s) for which the dependency should be added
     #[arg(long, short)]
     pub platform: Vec<Platform>,
+
+    /// When the dependencies cannot be solved, propose constraints that can be relaxed and apply
+    /// the chosen one to the manifest
+    #[arg(long, short, conflicts_with = "pypi")]
+    pub interactive: bool,
+
+    /// Allow pre-release versions of the added packages. PyPI dependencies are added with
+    /// `allow-prerelease = true`, conda packages are added to the `prerelease-packages` of the
+    /// project so their versions can come from the pre-release labels of the channels
+    #[arg(long)]
+    pub prerelease: bool,
+
+    /// Add the packages that are installed in the environment but not locked, e.g. with
+    /// `pip install`. Python packages are added as conda dependencies when the channels of the
+    /// project have them, and as pypi dependencies otherwise
+    #[arg(
+        long,
+        conflicts_with_all = ["specs", "host", "build", "pypi", "platform", "interactive", "prerelease"]
+    )]
+    pub from_prefix: bool,
 }
 
 impl DependencyType {
@@ -116,15 +154,41 @@ pub async fn execute(args: Args) -> miette::Result<()> {
         .collect::<Vec<Platform>>();
     project.manifest.add_platforms(platforms_to_add.iter())?;
 
-    match dependency_type {
+    let added = match dependency_type {
+        _ if args.from_prefix => {
+            let drifted = find_project_drift(&project).await?;
+            if drifted.is_empty() {
+                eprintln!(
+                    "{}The environment of {} only contains locked packages",
+                    console::style(console::Emoji("✔ ", "")).green(),
+                    console::style(project.name()).bold()
+                );
+                return Ok(());
+            }
+            adopt_drifted_packages(
+                &mut project,
+                &drifted,
+                args.no_install,
+                args.no_lockfile_update,
+            )
+            .await
+        }
         DependencyType::CondaDependency(spec_type) => {
             let specs = args
                 .specs
-                .clone()
-                .into_iter()
-                .map(|s| MatchSpec::from_str(&s))
-                .collect::<Result<Vec<_>, _>>()
-                .into_diagnostic()?;
+                .iter()
+                .map(|s| {
+                    let expanded = expand_version_sugar(s)?;
+                    MatchSpec::from_str(&expanded)
+                        .into_diagnostic()
+                        .wrap_err_with(|| format!("invalid spec '{s}'"))
+                })
+                .collect::<miette::Result<Vec<_>>>()?;
+            if args.prerelease {
+                project
+                    .manifest
+                    .add_prerelease_packages(specs.iter().filter_map(|spec| spec.name.clone()))?;
+            }
             add_conda_specs_to_project(
                 &mut project,
                 specs,
@@ -132,6 +196,7 @@ pub async fn execute(args: Args) -> miette::Result<()> {
                 args.no_install,
                 args.no_lockfile_update,
                 spec_platforms,
+                args.interactive,
             )
             .await
         }
@@ -149,7 +214,8 @@ pub async fn execute(args: Args) -> miette::Result<()> {
                 .into_iter()
                 .map(|req| {
                     let name = rip::types::PackageName::from_str(req.name.as_str())?;
-                    let requirement = PyPiRequirement::from(req);
+                    let mut requirement = PyPiRequirement::from(req);
+                    requirement.allow_prerelease = args.prerelease;
                     Ok((name, requirement))
                 })
                 .collect::<Result<Vec<_>, rip::types::ParsePackageNameError>>()
@@ -166,11 +232,11 @@ pub async fn execute(args: Args) -> miette::Result<()> {
         }
     }?;
 
-    for package in args.specs {
+    for spec in added {
         eprintln!(
             "{}Added {}",
             console::style(console::Emoji("✔ ", "")).green(),
-            console::style(package).bold(),
+            console::style(spec).bold(),
         );
     }
 
@@ -202,8 +268,28 @@ pub async fn add_pypi_specs_to_project(
     specs_platforms: &Vec<Platform>,
     no_update_lockfile: bool,
     no_install: bool,
-) -> miette::Result<()> {
-    for (name, spec) in &specs {
+) -> miette::Result<Vec<String>> {
+    let added = add_pypi_specs_to_manifest(project, &specs, specs_platforms)?;
+
+    update_prefix_or_restore_lock_file(
+        project,
+        lock_file_usage(no_update_lockfile),
+        no_install,
+        None,
+    )
+    .await?;
+    project.save()?;
+
+    Ok(added)
+}
+
+/// Adds the pypi specs to the manifest in memory, the manifest is not saved.
+fn add_pypi_specs_to_manifest(
+    project: &mut Project,
+    specs: &[(rip::types::PackageName, PyPiRequirement)],
+    specs_platforms: &Vec<Platform>,
+) -> miette::Result<Vec<String>> {
+    for (name, spec) in specs {
         // TODO: Get best version
         // Add the dependency to the project
         if specs_platforms.is_empty() {
@@ -216,29 +302,70 @@ pub async fn add_pypi_specs_to_project(
             }
         }
     }
-    let lock_file_usage = if no_update_lockfile {
+
+    Ok(specs
+        .iter()
+        .map(|(name, spec)| {
+            spec_format::pypi_requirement(name, &spec_format::normalize_pypi_requirement(spec))
+        })
+        .collect())
+}
+
+/// Returns how the lock file is used when dependencies are added.
+fn lock_file_usage(no_update_lockfile: bool) -> LockFileUsage {
+    if no_update_lockfile {
         LockFileUsage::Frozen
     } else {
         LockFileUsage::Update
-    };
+    }
+}
+
+pub async fn add_conda_specs_to_project(
+    project: &mut Project,
+    specs: Vec<MatchSpec>,
+    spec_type: SpecType,
+    no_install: bool,
+    no_update_lockfile: bool,
+    specs_platforms: &Vec<Platform>,
+    interactive: bool,
+) -> miette::Result<Vec<String>> {
+    // Fetch the repodata for the project
+    let sparse_repo_data = project.fetch_sparse_repodata().await?;
 
-    get_up_to_date_prefix(project, lock_file_usage, no_install, None).await?;
+    let added = add_conda_specs_to_manifest(
+        project,
+        specs,
+        spec_type,
+        specs_platforms,
+        interactive,
+        &sparse_repo_data,
+    )
+    .await?;
 
+    update_prefix_or_restore_lock_file(
+        project,
+        lock_file_usage(no_update_lockfile),
+        no_install,
+        Some(sparse_repo_data),
+    )
+    .await?;
     project.save()?;
 
-    Ok(())
+    Ok(added)
 }
 
-pub async fn add_conda_specs_to_project(
+/// Adds the conda specs to the manifest in memory with the best available versions, the manifest
+/// is not saved.
+async fn add_conda_specs_to_manifest(
     project: &mut Project,
     specs: Vec<MatchSpec>,
     spec_type: SpecType,
-    no_install: bool,
-    no_update_lockfile: bool,
     specs_platforms: &Vec<Platform>,
-) -> miette::Result<()> {
+    interactive: bool,
+    sparse_repo_data: &[SparseRepoData],
+) -> miette::Result<Vec<String>> {
     // Split the specs into package name and version specifier
-    let new_specs = specs
+    let mut new_specs = specs
         .into_iter()
         .map(|spec| match &spec.name {
             Some(name) => Ok((name.clone(), spec.into())),
@@ -246,10 +373,8 @@ pub async fn add_conda_specs_to_project(
         })
         .collect::<miette::Result<HashMap<PackageName, NamelessMatchSpec>>>()?;
 
-    // Get the current specs
-
-    // Fetch the repodata for the project
-    let sparse_repo_data = project.fetch_sparse_repodata().await?;
+    // Fail early with suggestions if a package does not exist in any of the channels
+    check_packages_available(project, new_specs.keys(), sparse_repo_data).await?;
 
     // Determine the best version per platform
     let mut package_versions = HashMap::<PackageName, HashSet<Version>>::new();
@@ -268,23 +393,39 @@ pub async fn add_conda_specs_to_project(
         //     SpecType::Build => project.build_dependencies(platform)?,
         //     SpecType::Run => project.dependencies(platform)?,
         // };
-        let current_specs = project.all_dependencies(platform);
+        // Solve the environment with the new specs added. In interactive mode the user can relax
+        // constraints until the environment can be solved.
+        let solved_versions = loop {
+            let current_specs = project.all_dependencies(platform);
+            let err = match determine_best_version(
+                project,
+                &new_specs,
+                &current_specs,
+                sparse_repo_data,
+                platform,
+            ) {
+                Ok(versions) => break versions,
+                Err(err) => err,
+            };
 
-        // Solve the environment with the new specs added
-        let solved_versions = match determine_best_version(
-            project,
-            &new_specs,
-            &current_specs,
-            &sparse_repo_data,
-            platform,
-        ) {
-            Ok(versions) => versions,
-            Err(err) => {
-                return Err(err).wrap_err_with(||miette::miette!(
-                        "could not determine any available versions for {} on {platform}. Either the package could not be found or version constraints on other dependencies result in a conflict.",
-                        new_specs.keys().map(|s| s.as_source()).join(", ")
-                    ));
+            if interactive {
+                let relaxations = propose_relaxations(
+                    project,
+                    &new_specs,
+                    &current_specs,
+                    sparse_repo_data,
+                    platform,
+                );
+                if let Some(relaxation) = choose_relaxation(&relaxations, platform)? {
+                    apply_relaxation(project, &mut new_specs, relaxation)?;
+                    continue;
+                }
             }
+
+            return Err(err).wrap_err_with(||miette::miette!(
+                    "could not determine any available versions for {} on {platform}. Either the package could not be found or version constraints on other dependencies result in a conflict.",
+                    new_specs.keys().map(|s| s.as_source()).join(", ")
+                ));
         };
 
         // Collect all the versions seen.
@@ -294,6 +435,7 @@ pub async fn add_conda_specs_to_project(
     }
 
     // Update the specs passed on the command line with the best available versions.
+    let mut added = Vec::new();
     for (name, spec) in new_specs {
         let versions_seen = package_versions
             .get(&name)
@@ -306,6 +448,7 @@ pub async fn add_conda_specs_to_project(
         } else {
             spec
         };
+        added.push(spec_format::conda_spec(&name, &updated_spec));
         let spec = MatchSpec::from_nameless(updated_spec, Some(name));
 
         // Add the dependency to the project
@@ -319,15 +462,390 @@ pub async fn add_conda_specs_to_project(
             }
         }
     }
-    let lock_file_usage = if no_update_lockfile {
-        LockFileUsage::Frozen
-    } else {
-        LockFileUsage::Update
+
+    added.sort();
+    Ok(added)
+}
+
+/// Adds packages that are installed in the environment but not locked to the dependencies of the
+/// project, with a constraint that allows their installed version. Python distributions become
+/// conda dependencies when the channels of the project have a package for them, which replaces the
+/// distribution on install, and pypi dependencies otherwise.
+pub async fn adopt_drifted_packages(
+    project: &mut Project,
+    drifted: &[DriftedPackage],
+    no_install: bool,
+    no_update_lockfile: bool,
+) -> miette::Result<Vec<String>> {
+    let sparse_repo_data = project.fetch_sparse_repodata().await?;
+    let conda_names = sparse_repo_data
+        .iter()
+        .flat_map(|repo| repo.package_names())
+        .collect::<HashSet<_>>();
+    let name_mapping = match conda_pypi_name_mapping().await {
+        Ok(mapping) => Some(mapping),
+        Err(err) => {
+            tracing::debug!("failed to load the conda names of pypi packages: {err}");
+            None
+        }
     };
-    get_up_to_date_prefix(project, lock_file_usage, no_install, Some(sparse_repo_data)).await?;
+
+    let AdoptedSpecs {
+        conda: conda_specs,
+        pypi: pypi_specs,
+        replaced_distributions,
+    } = adopted_specs(drifted, &conda_names, name_mapping)?;
+
+    // Add all the dependencies to the manifest before the lock file is updated and the manifest
+    // is saved, so either all of them are added or none of them.
+    let mut added = Vec::new();
+    if !conda_specs.is_empty() {
+        added.extend(
+            add_conda_specs_to_manifest(
+                project,
+                conda_specs,
+                SpecType::Run,
+                &Vec::new(),
+                false,
+                &sparse_repo_data,
+            )
+            .await?,
+        );
+    }
+    if !pypi_specs.is_empty() {
+        added.extend(add_pypi_specs_to_manifest(
+            project,
+            &pypi_specs,
+            &Vec::new(),
+        )?);
+    }
+
+    // The environment is installed once the replaced distributions are removed.
+    update_prefix_or_restore_lock_file(
+        project,
+        lock_file_usage(no_update_lockfile),
+        true,
+        Some(sparse_repo_data),
+    )
+    .await?;
     project.save()?;
 
-    Ok(())
+    if !no_install && !no_update_lockfile {
+        let prefix = Prefix::new(project.environment_dir())?;
+        remove_drifted_distributions(&prefix, &replaced_distributions)?;
+        get_up_to_date_prefix(project, LockFileUsage::Update, false, None).await?;
+    }
+    Ok(added)
+}
+
+/// The dependencies that adopt the drifted packages of an environment.
+#[derive(Debug, Default)]
+struct AdoptedSpecs {
+    conda: Vec<MatchSpec>,
+    pypi: Vec<(rip::types::PackageName, PyPiRequirement)>,
+    /// The python distributions that are replaced by the added conda packages.
+    replaced_distributions: Vec<DriftedPackage>,
+}
+
+/// Determines the dependencies that adopt the drifted packages, all of them allow the installed
+/// version. `conda_names` are the packages in the channels of the project and `name_mapping` maps
+/// conda names to pypi names, a python distribution becomes a conda dependency when it is found
+/// in the channels through the mapping or by its own name.
+fn adopted_specs(
+    drifted: &[DriftedPackage],
+    conda_names: &HashSet<&str>,
+    name_mapping: Option<&HashMap<String, String>>,
+) -> miette::Result<AdoptedSpecs> {
+    let mut specs = AdoptedSpecs::default();
+    for package in drifted {
+        let version = Version::from_str(&package.version).ok();
+        let version_spec = version
+            .as_ref()
+            .and_then(|version| determine_version_constraint([version]));
+        match &package.kind {
+            DriftedPackageKind::Conda => {
+                let name = PackageName::from_str(&package.name).into_diagnostic()?;
+                specs.conda.push(MatchSpec::from_nameless(
+                    NamelessMatchSpec {
+                        version: version_spec,
+                        ..NamelessMatchSpec::default()
+                    },
+                    Some(name),
+                ));
+            }
+            DriftedPackageKind::Pypi { .. } => {
+                let pypi_name = normalize_pypi_name(&package.name);
+                let conda_name = name_mapping
+                    .into_iter()
+                    .flatten()
+                    .find(|(_, mapped)| normalize_pypi_name(mapped) == pypi_name)
+                    .map(|(conda_name, _)| conda_name.as_str())
+                    .filter(|name| conda_names.contains(name))
+                    .or_else(|| conda_names.get(pypi_name.as_str()).copied());
+                if let Some(conda_name) = conda_name {
+                    // The conda package replaces the distribution, so it keeps the installed
+                    // version like the other adopted packages.
+                    specs.conda.push(MatchSpec::from_nameless(
+                        NamelessMatchSpec {
+                            version: version_spec,
+                            ..NamelessMatchSpec::default()
+                        },
+                        Some(PackageName::from_str(conda_name).into_diagnostic()?),
+                    ));
+                    specs.replaced_distributions.push(package.clone());
+                } else {
+                    let requirement = match &version_spec {
+                        Some(spec) => PyPiRequirement::from_str(&spec.to_string()),
+                        None => PyPiRequirement::from_str("*"),
+                    }
+                    .into_diagnostic()?;
+                    specs.pypi.push((
+                        rip::types::PackageName::from_str(&package.name).into_diagnostic()?,
+                        requirement,
+                    ));
+                }
+            }
+        }
+    }
+    Ok(specs)
+}
+
+/// Updates the lock file and the environment after the specs were added to the manifest in memory.
+/// The manifest is only saved when this succeeds, and on failure the previous lock file is
+/// restored, so either all specs are added or none of them.
+async fn update_prefix_or_restore_lock_file(
+    project: &Project,
+    lock_file_usage: LockFileUsage,
+    no_install: bool,
+    sparse_repo_data: Option<Vec<SparseRepoData>>,
+) -> miette::Result<()> {
+    let lock_file_path = project.lock_file_path();
+    let previous_lock_file = std::fs::read(&lock_file_path).ok();
+    let result =
+        get_up_to_date_prefix(project, lock_file_usage, no_install, sparse_repo_data).await;
+    if result.is_err() {
+        let restored = match previous_lock_file {
+            Some(contents) => std::fs::write(&lock_file_path, contents),
+            None if lock_file_path.exists() => std::fs::remove_file(&lock_file_path),
+            None => Ok(()),
+        };
+        if let Err(err) = restored {
+            tracing::warn!(
+                "failed to restore the lock file '{}': {err}",
+                lock_file_path.display()
+            );
+        }
+    }
+    result.map(|_| ())
+}
+
+/// Translates the version range sugar of other package managers in a conda spec to the equivalent
+/// conda ranges:
+///
+/// - `~=1.2.3`, a compatible release: `>=1.2.3,<1.3`
+/// - `^1.2.3`, a caret range: `>=1.2.3,<2`, or `>=0.2.3,<0.3` for a `0.x` version
+/// - `~1.2.3`, a tilde range: `>=1.2.3,<1.3`
+/// - `==1.2.*`: `1.2.*`
+///
+/// Specs without sugar are returned as is.
+fn expand_version_sugar(spec: &str) -> miette::Result<String> {
+    let Some(start) = spec.find(|c: char| "<>=!~^ ".contains(c)) else {
+        return Ok(spec.to_string());
+    };
+    let (name, constraints) = spec.split_at(start);
+    let (constraints, build) =
+        constraints.split_at(constraints.find('[').unwrap_or(constraints.len()));
+    let constraints = constraints.trim();
+
+    // Leave specs alone that are not a list of version constraints, e.g. `numpy 1.26 py311*`.
+    let has_sugar = constraints.split(',').any(|constraint| {
+        constraint.starts_with(['^', '~'])
+            || (constraint.starts_with("==") && constraint.ends_with(".*"))
+    });
+    if !has_sugar || constraints.contains(|c: char| c.is_whitespace() || c == '|') {
+        return Ok(spec.to_string());
+    }
+
+    let expanded = constraints
+        .split(',')
+        .map(expand_version_constraint)
+        .collect::<miette::Result<Vec<_>>>()?;
+    Ok(format!("{} {}{build}", name.trim(), expanded.join(",")))
+}
+
+/// Translates a single version constraint, see [`expand_version_sugar`].
+fn expand_version_constraint(constraint: &str) -> miette::Result<String> {
+    let components = |version: &str| {
+        version
+            .split('.')
+            .map(|component| component.parse::<u64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| {
+                miette::miette!(
+                    "'{constraint}' can only be translated to a conda range for versions that consist of numbers"
+                )
+            })
+    };
+    let range = |lower: &str, mut upper: Vec<u64>| {
+        *upper.last_mut().expect("upper bound has a component") += 1;
+        format!(">={lower},<{}", upper.iter().join("."))
+    };
+
+    if let Some(version) = constraint.strip_prefix("~=") {
+        let parts = components(version)?;
+        if parts.len() < 2 {
+            miette::bail!("'{constraint}' must have at least two version components");
+        }
+        Ok(range(version, parts[..parts.len() - 1].to_vec()))
+    } else if let Some(version) = constraint.strip_prefix('^') {
+        let parts = components(version)?;
+        let significant = parts
+            .iter()
+            .position(|part| *part != 0)
+            .unwrap_or(parts.len() - 1);
+        Ok(range(version, parts[..=significant].to_vec()))
+    } else if let Some(version) = constraint.strip_prefix('~') {
+        let parts = components(version)?;
+        Ok(range(version, parts[..parts.len().min(2)].to_vec()))
+    } else if let Some(glob) = constraint
+        .strip_prefix("==")
+        .filter(|version| version.ends_with(".*"))
+    {
+        Ok(glob.to_string())
+    } else {
+        Ok(constraint.to_string())
+    }
+}
+
+/// Returns an error when one of the packages is not available in the channels of the project. The
+/// error suggests packages with a similar name and whether the package can be added from PyPI.
+async fn check_packages_available(
+    project: &Project,
+    names: impl IntoIterator<Item = &PackageName>,
+    sparse_repo_data: &[SparseRepoData],
+) -> miette::Result<()> {
+    let missing = names
+        .into_iter()
+        // Virtual packages are never part of the repodata
+        .filter(|name| !name.as_normalized().starts_with("__"))
+        .filter(|name| {
+            !sparse_repo_data
+                .iter()
+                .any(|repo| repo.package_names().any(|n| n == name.as_normalized()))
+        })
+        .collect_vec();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let on_pypi = names_on_pypi(missing.iter().map(|name| name.as_normalized())).await;
+    let mut hints = Vec::new();
+    for name in &missing {
+        let similar = similar_package_names(
+            name.as_normalized(),
+            sparse_repo_data
+                .iter()
+                .flat_map(|repo| repo.package_names()),
+        );
+        if !similar.is_empty() {
+            hints.push(format!(
+                "did you mean {} instead of '{}'?",
+                similar.iter().map(|n| format!("'{n}'")).join(" or "),
+                name.as_source()
+            ));
+        }
+        if on_pypi.contains(name.as_normalized()) {
+            hints.push(format!(
+                "'{}' is available on PyPI, add it with `pixi add --pypi {}`",
+                name.as_source(),
+                name.as_source()
+            ));
+        }
+    }
+
+    let message = format!(
+        "{} could not be found in the channels of the project ({})",
+        missing
+            .iter()
+            .map(|name| format!("'{}'", name.as_source()))
+            .join(", "),
+        project
+            .channels()
+            .iter()
+            .map(friendly_channel_name)
+            .join(", ")
+    );
+    if hints.is_empty() {
+        miette::bail!("{message}");
+    }
+    Err(miette::miette!(help = hints.join("\n"), "{message}"))
+}
+
+/// Returns the names of the `candidates` that are most likely meant by `name`. Conda packages of
+/// python libraries are often prefixed with `py-` or `python-`, those are matched too.
+fn similar_package_names<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let variants = [
+        format!("py-{name}"),
+        format!("python-{name}"),
+        name.trim_start_matches("py-").to_string(),
+        name.trim_start_matches("python-").to_string(),
+        name.replace('_', "-"),
+    ];
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .filter_map(|candidate| {
+            let score = if variants.iter().any(|variant| variant == candidate) {
+                1.0
+            } else {
+                strsim::jaro(name, candidate)
+            };
+            (score >= 0.9).then(|| (score, candidate.to_string()))
+        })
+        .sorted_by(|(a, _), (b, _)| b.total_cmp(a))
+        .map(|(_, candidate)| candidate)
+        .unique()
+        .take(3)
+        .collect()
+}
+
+/// Returns the names that exist on PyPI. The lookups run concurrently, and as soon as one of them
+/// cannot reach PyPI (e.g. when working offline) the remaining lookups are skipped.
+async fn names_on_pypi<'a>(names: impl IntoIterator<Item = &'a str>) -> HashSet<&'a str> {
+    let mut lookups = stream::iter(names)
+        .map(|name| async move { (name, exists_on_pypi(name).await) })
+        .buffer_unordered(concurrency::limits().downloads);
+
+    let mut found = HashSet::new();
+    while let Some((name, result)) = lookups.next().await {
+        match result {
+            Ok(true) => {
+                found.insert(name);
+            }
+            Ok(false) => {}
+            Err(err) if err.is_connect() || err.is_timeout() => {
+                tracing::debug!("PyPI is unreachable, skipping the remaining lookups: {err}");
+                break;
+            }
+            Err(err) => {
+                tracing::debug!("failed to check whether '{name}' exists on PyPI: {err}");
+            }
+        }
+    }
+    found
+}
+
+/// Returns true if a package with the given name exists on PyPI.
+async fn exists_on_pypi(name: &str) -> reqwest::Result<bool> {
+    let response = default_client()
+        .get(format!("https://pypi.org/pypi/{name}/json"))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?;
+    Ok(response.status().is_success())
 }
 
 /// Given several specs determines the highest installable version for them.
@@ -344,6 +862,22 @@ pub fn determine_best_version(
         .map(|(name, spec)| (name.clone(), spec.clone()))
         .collect::<HashMap<_, _>>();
 
+    // Determine the versions of the new packages
+    Ok(
+        solve_versions(project, &combined_specs, sparse_repo_data, platform)?
+            .into_iter()
+            .filter(|(name, _)| new_specs.contains_key(name))
+            .collect(),
+    )
+}
+
+/// Solves the given specs and returns the versions of all the packages in the solution.
+fn solve_versions(
+    project: &Project,
+    combined_specs: &HashMap<PackageName, NamelessMatchSpec>,
+    sparse_repo_data: &[SparseRepoData],
+    platform: Platform,
+) -> miette::Result<HashMap<PackageName, Version>> {
     // Extract the package names from all the dependencies
     let package_names = combined_specs.keys().cloned().collect_vec();
 
@@ -353,12 +887,13 @@ pub fn determine_best_version(
     });
 
     // Load only records we need for this platform
-    let available_packages = SparseRepoData::load_records_recursive(
+    let mut available_packages = SparseRepoData::load_records_recursive(
         platform_sparse_repo_data,
         package_names.iter().cloned(),
         None,
     )
     .into_diagnostic()?;
+    project.retain_allowed_prereleases(&mut available_packages);
 
     // Construct a solver task to start solving.
     let task = rattler_solve::SolverTask {
@@ -379,10 +914,8 @@ pub fn determine_best_version(
 
     let records = resolvo::Solver.solve(task).into_diagnostic()?;
 
-    // Determine the versions of the new packages
     Ok(records
         .into_iter()
-        .filter(|record| new_specs.contains_key(&record.package_record.name))
         .map(|record| {
             (
                 record.package_record.name,
@@ -392,6 +925,189 @@ pub fn determine_best_version(
         .collect())
 }
 
+/// A manifest edit that makes an infeasible environment solvable again by relaxing the version
+/// constraint of a single package.
+struct Relaxation {
+    /// The package whose constraint is relaxed.
+    name: PackageName,
+
+    /// The constraint that currently prevents a solution.
+    current: NamelessMatchSpec,
+
+    /// The version of the package that is selected when the constraint is relaxed.
+    resolved: Version,
+
+    /// Where the constraint is defined in the manifest, `None` if it is one of the specs that is
+    /// being added.
+    location: Option<(SpecType, Option<Platform>)>,
+}
+
+impl Relaxation {
+    /// Returns the spec that replaces the current constraint.
+    fn relaxed_spec(&self) -> NamelessMatchSpec {
+        let mut spec = self.current.clone();
+        spec.version = determine_version_constraint([&self.resolved]);
+        spec
+    }
+}
+
+impl std::fmt::Display for Relaxation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let version = self
+            .current
+            .version
+            .as_ref()
+            .map_or_else(|| String::from("*"), ToString::to_string);
+        write!(
+            f,
+            "allow {} {} \u{2192} {}",
+            self.name.as_source(),
+            version,
+            self.resolved
+        )?;
+        match self.location {
+            None => write!(f, " (requested)"),
+            Some((spec_type, None)) => write!(f, " (in [{}])", spec_type.name()),
+            Some((spec_type, Some(platform))) => {
+                write!(f, " (in [target.{platform}.{}])", spec_type.name())
+            }
+        }
+    }
+}
+
+/// Returns where the dependency on `name` that applies to `platform` is defined in the manifest.
+/// Platform specific definitions take precedence over the default ones.
+fn find_dependency_location(
+    project: &Project,
+    name: &PackageName,
+    platform: Platform,
+) -> Option<(SpecType, Option<Platform>)> {
+    project
+        .manifest
+        .default_feature()
+        .targets
+        .iter()
+        .filter(|(_, selector)| selector.map_or(true, |selector| selector.matches(platform)))
+        .sorted_by_key(|(_, selector)| selector.is_none())
+        .find_map(|(target, selector)| {
+            [SpecType::Build, SpecType::Host, SpecType::Run]
+                .into_iter()
+                .find(|spec_type| {
+                    target
+                        .dependencies
+                        .get(spec_type)
+                        .map_or(false, |deps| deps.contains_key(name))
+                })
+                .map(|spec_type| {
+                    (
+                        spec_type,
+                        selector.map(|selector| match selector {
+                            TargetSelector::Platform(platform) => *platform,
+                        }),
+                    )
+                })
+        })
+}
+
+/// Determines which version constraints can be dropped individually to make the environment
+/// solvable again.
+fn propose_relaxations(
+    project: &Project,
+    new_specs: &HashMap<PackageName, NamelessMatchSpec>,
+    current_specs: &IndexMap<PackageName, NamelessMatchSpec>,
+    sparse_repo_data: &[SparseRepoData],
+    platform: Platform,
+) -> Vec<Relaxation> {
+    let combined_specs = current_specs
+        .iter()
+        .chain(new_specs.iter())
+        .map(|(name, spec)| (name.clone(), spec.clone()))
+        .collect::<HashMap<_, _>>();
+
+    combined_specs
+        .iter()
+        .filter(|(_, spec)| spec.version.is_some())
+        .sorted_by_key(|(name, _)| name.as_normalized().to_string())
+        .filter_map(|(name, spec)| {
+            let mut relaxed_specs = combined_specs.clone();
+            relaxed_specs.insert(
+                name.clone(),
+                NamelessMatchSpec {
+                    version: None,
+                    ..spec.clone()
+                },
+            );
+            let versions =
+                solve_versions(project, &relaxed_specs, sparse_repo_data, platform).ok()?;
+            let location = if new_specs.contains_key(name) {
+                None
+            } else {
+                Some(find_dependency_location(project, name, platform)?)
+            };
+            Some(Relaxation {
+                name: name.clone(),
+                current: spec.clone(),
+                resolved: versions.get(name)?.clone(),
+                location,
+            })
+        })
+        .collect()
+}
+
+/// Asks the user which of the relaxations to apply. Returns `None` if there is nothing to choose
+/// from, the user declined or pixi can't prompt, e.g. on CI.
+fn choose_relaxation(
+    relaxations: &[Relaxation],
+    platform: Platform,
+) -> miette::Result<Option<&Relaxation>> {
+    let term = console::Term::stderr();
+    if relaxations.is_empty() || !ci::is_interactive() {
+        return Ok(None);
+    }
+
+    eprintln!(
+        "{}The dependencies cannot be solved for {platform}, relaxing one of these constraints resolves the conflict:",
+        console::style(console::Emoji("⚠ ", "")).yellow(),
+    );
+    for (idx, relaxation) in relaxations.iter().enumerate() {
+        eprintln!("  {}) {relaxation}", idx + 1);
+    }
+    eprint!(
+        "Choose a constraint to relax [1-{}], or press enter to abort: ",
+        relaxations.len()
+    );
+
+    let answer = term.read_line().into_diagnostic()?;
+    Ok(answer
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|choice| choice.checked_sub(1))
+        .and_then(|idx| relaxations.get(idx)))
+}
+
+/// Applies a relaxation to the manifest or to the specs that are being added.
+fn apply_relaxation(
+    project: &mut Project,
+    new_specs: &mut HashMap<PackageName, NamelessMatchSpec>,
+    relaxation: &Relaxation,
+) -> miette::Result<()> {
+    let spec = relaxation.relaxed_spec();
+    match relaxation.location {
+        None => {
+            new_specs.insert(relaxation.name.clone(), spec);
+        }
+        Some((spec_type, platform)) => {
+            project.manifest.add_dependency(
+                &MatchSpec::from_nameless(spec, Some(relaxation.name.clone())),
+                spec_type,
+                platform,
+            )?;
+        }
+    }
+    Ok(())
+}
+
 /// Given a set of versions, determines the best version constraint to use that captures all of them.
 fn determine_version_constraint<'a>(
     versions: impl IntoIterator<Item = &'a Version>,
@@ -426,4 +1142,116 @@ mod test {
             .unwrap()
             .to_string(), @">=1.2.0,<1.4");
     }
+
+    #[test]
+    fn test_expand_version_sugar() {
+        let expand = |spec: &str| expand_version_sugar(spec).unwrap();
+        assert_eq!(expand("numpy>=1.26,<2"), "numpy>=1.26,<2");
+        assert_eq!(expand("python=3.9"), "python=3.9");
+        assert_eq!(expand("numpy 1.26 py311*"), "numpy 1.26 py311*");
+        assert_eq!(expand("pandas==2.1.*"), "pandas 2.1.*");
+        assert_eq!(expand("requests~=2.31"), "requests >=2.31,<3");
+        assert_eq!(expand("requests~=2.31.0"), "requests >=2.31.0,<2.32");
+        assert_eq!(expand("numpy^1.26.2"), "numpy >=1.26.2,<2");
+        assert_eq!(expand("numpy ^0.4.1"), "numpy >=0.4.1,<0.5");
+        assert_eq!(expand("numpy^0.0.3"), "numpy >=0.0.3,<0.0.4");
+        assert_eq!(expand("numpy~1.26.2"), "numpy >=1.26.2,<1.27");
+        assert_eq!(expand("numpy~1"), "numpy >=1,<2");
+        assert_eq!(expand("numpy^1.2,!=1.2.5"), "numpy >=1.2,<2,!=1.2.5");
+        assert!(expand_version_sugar("numpy~=1").is_err());
+        assert!(expand_version_sugar("numpy^1.2a").is_err());
+
+        for spec in ["numpy^1.26.2", "pandas==2.1.*", "requests~=2.31"] {
+            MatchSpec::from_str(&expand(spec)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_similar_package_names() {
+        let candidates = [
+            "opencv",
+            "py-opencv",
+            "matplotlib",
+            "python-dateutil",
+            "pandas",
+        ];
+        assert_eq!(
+            similar_package_names("py-opencv", candidates),
+            vec!["opencv".to_string()]
+        );
+        assert_eq!(
+            similar_package_names("dateutil", candidates),
+            vec!["python-dateutil".to_string()]
+        );
+        assert_eq!(
+            similar_package_names("matplotlb", candidates),
+            vec!["matplotlib".to_string()]
+        );
+        assert!(similar_package_names("requests", candidates).is_empty());
+    }
+
+    #[test]
+    fn test_from_prefix_args() {
+        let args = Args::try_parse_from(["add", "--from-prefix"]).unwrap();
+        assert!(args.from_prefix);
+        assert!(args.specs.is_empty());
+        assert!(Args::try_parse_from(["add", "--from-prefix", "numpy"]).is_err());
+        assert!(Args::try_parse_from(["add", "--from-prefix", "--pypi"]).is_err());
+        assert!(Args::try_parse_from(["add", "--no-install"]).is_err());
+    }
+
+    #[test]
+    fn test_adopted_specs() {
+        let pypi = |name: &str, version: &str| DriftedPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            kind: DriftedPackageKind::Pypi {
+                site_packages: PathBuf::from("lib/python3.12/site-packages"),
+                dist_info: PathBuf::from(format!("{name}-{version}.dist-info")),
+                installer: Some(String::from("pip")),
+            },
+        };
+        let drifted = [
+            DriftedPackage {
+                name: String::from("libfoo"),
+                version: String::from("1.2.3"),
+                kind: DriftedPackageKind::Conda,
+            },
+            pypi("Pillow", "10.1.0"),
+            pypi("torch", "2.1.0"),
+            pypi("some_tool", "0.3.1"),
+        ];
+        let conda_names = HashSet::from(["libfoo", "pillow", "pytorch"]);
+        let name_mapping = HashMap::from([(String::from("pytorch"), String::from("torch"))]);
+        let specs = adopted_specs(&drifted, &conda_names, Some(&name_mapping)).unwrap();
+
+        // The conda packages, also those that replace a distribution, allow the installed version.
+        let conda = specs
+            .conda
+            .iter()
+            .map(|spec| (spec.name.as_ref().unwrap().as_normalized(), spec))
+            .collect_vec();
+        assert_eq!(
+            conda.iter().map(|(name, _)| *name).collect_vec(),
+            ["libfoo", "pillow", "pytorch"]
+        );
+        for ((_, spec), version) in conda.iter().zip(["1.2.3", "10.1.0", "2.1.0"]) {
+            let version_spec = spec.version.as_ref().unwrap();
+            assert!(version_spec.matches(&Version::from_str(version).unwrap()));
+        }
+        assert_eq!(
+            specs
+                .replaced_distributions
+                .iter()
+                .map(|package| package.name.as_str())
+                .collect_vec(),
+            ["Pillow", "torch"]
+        );
+
+        // A distribution without a conda package becomes a pypi dependency.
+        assert_eq!(specs.pypi.len(), 1);
+        let (name, requirement) = &specs.pypi[0];
+        assert_eq!(name.as_source_str(), "some_tool");
+        assert!(requirement.version.is_some());
+    }
 }
@@ -0,0 +1,264 @@
+use crate::build_fallback;
+use crate::cli::LockFileUsageArgs;
+use crate::environment::get_up_to_date_prefix;
+use crate::lock_file::{self, download_locked_artifacts, signature_path};
+use crate::progress::await_in_progress;
+use crate::Project;
+use clap::Parser;
+use flate2::{write::GzEncoder, Compression};
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::Platform;
+use rattler_digest::{compute_file_digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// The name of the file in the archive with the sha256 of all other files, in the format of
+/// `sha256sum` so it can be checked with `sha256sum -c SHA256SUMS`.
+const HASHES_FILE: &str = "SHA256SUMS";
+
+/// The environment variable that sets the modification time of the files in the archive, see
+/// <https://reproducible-builds.org/specs/source-date-epoch/>.
+const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Create a reproducible archive of the project
+///
+/// The archive contains the manifest and the files it includes, the lock file and its signature if
+/// it is signed, and a `SHA256SUMS` file with the hashes of all files. Archiving the same project
+/// twice results in the same archive, byte for byte.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// The path of the created archive, defaults to `<name>-<version>.tar.gz`
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+
+    /// Also add the artifacts of the locked packages, so the environment can be recreated without
+    /// access to the channels
+    #[arg(long)]
+    pub with_packages: bool,
+
+    /// The platforms of the added packages, defaults to all platforms of the project
+    #[arg(long, short, requires = "with_packages")]
+    pub platform: Vec<Platform>,
+
+    #[clap(flatten)]
+    pub lock_file_usage: LockFileUsageArgs,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+
+    // The lock file is what makes the archive reproducible, make sure it is up to date.
+    get_up_to_date_prefix(&project, args.lock_file_usage.into(), true, None).await?;
+    let lock_file_path = project.lock_file_path();
+    if !lock_file_path.is_file() {
+        miette::bail!(
+            help = "create it with `pixi install`",
+            "the project has no lock file"
+        );
+    }
+
+    let name = match project.version() {
+        Some(version) => format!("{}-{version}", project.name()),
+        None => project.name().to_string(),
+    };
+    let output = args
+        .output
+        .unwrap_or_else(|| PathBuf::from(format!("{name}.tar.gz")));
+
+    let mut files = vec![
+        (
+            archive_path(project.root(), &project.manifest_path()),
+            project.manifest_path(),
+        ),
+        (
+            archive_path(project.root(), &lock_file_path),
+            lock_file_path.clone(),
+        ),
+    ];
+    files.extend(
+        project
+            .manifest
+            .included_files
+            .iter()
+            .map(|path| (archive_path(project.root(), path), path.clone())),
+    );
+    let signature = signature_path(&lock_file_path);
+    if signature.is_file() {
+        files.push((archive_path(project.root(), &signature), signature));
+    }
+
+    // The artifacts are downloaded to a temporary directory first, so they are verified against
+    // the lock file before they are added.
+    let packages_dir = tempfile::tempdir().into_diagnostic()?;
+    if args.with_packages {
+        let platforms = if args.platform.is_empty() {
+            project.platforms().to_vec()
+        } else {
+            args.platform
+        };
+        let lock_file = build_fallback::resolve_lock_file_urls(
+            &project,
+            &lock_file::load_lock_file(&project).await?,
+        );
+        let packages = download_locked_artifacts(
+            &lock_file,
+            &platforms,
+            project.config(),
+            packages_dir.path(),
+        )
+        .await?;
+        files.extend(packages.into_iter().map(|path| {
+            (
+                Path::new("packages").join(&path),
+                packages_dir.path().join(path),
+            )
+        }));
+    }
+
+    let mtime = std::env::var(SOURCE_DATE_EPOCH)
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .unwrap_or(0);
+    let output_path = output.clone();
+    await_in_progress(
+        "creating archive",
+        tokio::task::spawn_blocking(move || write_archive(&output_path, &name, files, mtime)),
+    )
+    .await
+    .into_diagnostic()?
+    .into_diagnostic()
+    .wrap_err_with(|| format!("failed to write the archive to '{}'", output.display()))?;
+
+    eprintln!(
+        "{}Created archive {}",
+        console::style(console::Emoji("✔ ", "")).green(),
+        console::style(output.display()).bold()
+    );
+    Ok(())
+}
+
+/// Returns the path of a file of the project in the archive, relative to the root of the project.
+/// Files outside of the project, e.g. the lock file of a workspace, are added to the root of the
+/// archive.
+fn archive_path(project_root: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(project_root)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| PathBuf::from(path.file_name().unwrap_or_default()))
+}
+
+/// Writes the `files`, pairs of the path in the archive and the path on disk, and a `SHA256SUMS`
+/// file with their hashes to a gzipped tarball in a directory called `name`. The files are sorted
+/// and get the same owner, permissions and modification time, so the archive only depends on the
+/// contents of the files.
+fn write_archive(
+    output: &Path,
+    name: &str,
+    mut files: Vec<(PathBuf, PathBuf)>,
+    mtime: u64,
+) -> std::io::Result<()> {
+    files.sort();
+    files.dedup_by(|a, b| a.0 == b.0);
+
+    let file = std::fs::File::create(output)?;
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    let mut append = |path: &Path, contents: &mut dyn std::io::Read, size: u64| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_entry_type(tar::EntryType::Regular);
+        builder.append_data(&mut header, Path::new(name).join(path), contents)
+    };
+
+    let mut hashes = String::new();
+    for (path, source) in &files {
+        let hash = compute_file_digest::<Sha256>(source)?;
+        let archive_path = path.to_string_lossy().replace('\\', "/");
+        hashes.push_str(&format!("{hash:x}  {archive_path}\n"));
+
+        let mut contents = std::fs::File::open(source)?;
+        let size = contents.metadata()?.len();
+        append(path, &mut contents, size)?;
+    }
+    append(
+        Path::new(HASHES_FILE),
+        &mut hashes.as_bytes(),
+        hashes.len() as u64,
+    )?;
+
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_archive_path() {
+        let root = Path::new("/project");
+        assert_eq!(
+            archive_path(root, Path::new("/project/.pixi/locks/default.lock")),
+            Path::new(".pixi/locks/default.lock")
+        );
+        assert_eq!(
+            archive_path(root, Path::new("/monorepo/pixi.lock")),
+            Path::new("pixi.lock")
+        );
+    }
+
+    #[test]
+    fn test_write_archive_is_reproducible() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("pixi.toml");
+        let lock_file = dir.path().join("pixi.lock");
+        std::fs::write(&manifest, "[project]\nname = \"test\"\n").unwrap();
+        std::fs::write(&lock_file, "version: 1\n").unwrap();
+        let files = vec![
+            (PathBuf::from("pixi.toml"), manifest.clone()),
+            (PathBuf::from("pixi.lock"), lock_file.clone()),
+        ];
+
+        let first = dir.path().join("first.tar.gz");
+        write_archive(&first, "test", files.clone(), 0).unwrap();
+        // The order of the files and their metadata don't change the archive.
+        std::fs::write(&lock_file, "version: 1\n").unwrap();
+        let second = dir.path().join("second.tar.gz");
+        write_archive(&second, "test", files.into_iter().rev().collect(), 0).unwrap();
+        assert_eq!(
+            std::fs::read(&first).unwrap(),
+            std::fs::read(&second).unwrap()
+        );
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(
+            std::fs::File::open(&first).unwrap(),
+        ));
+        let mut entries = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents).unwrap();
+            entries.push((entry.path().unwrap().to_path_buf(), contents));
+        }
+        let paths = entries
+            .iter()
+            .map(|(path, _)| path.as_path())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                Path::new("test/pixi.lock"),
+                Path::new("test/pixi.toml"),
+                Path::new("test/SHA256SUMS")
+            ]
+        );
+        let hash = compute_file_digest::<Sha256>(&lock_file).unwrap();
+        assert!(entries[2].1.starts_with(&format!("{hash:x}  pixi.lock\n")));
+    }
+}
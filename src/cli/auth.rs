@@ -1,6 +1,10 @@
+use crate::auth;
+use crate::token_expiry::{self, Expiry, TokenMetadata, TokenMetadataStore};
+use chrono::{DateTime, Utc};
 use clap::Parser;
 use miette::IntoDiagnostic;
 use rattler_networking::{Authentication, AuthenticationStorage};
+use url::Url;
 
 #[derive(Parser, Debug)]
 pub struct LoginArgs {
@@ -22,6 +26,15 @@ pub struct LoginArgs {
     /// The token to use on anaconda.org / quetz authentication
     #[clap(long)]
     conda_token: Option<String>,
+
+    /// When the token expires, as a date (2024-06-30) or a RFC 3339 timestamp. Pixi warns when the
+    /// token is about to expire
+    #[clap(long, value_parser = token_expiry::parse_expiry)]
+    expires_at: Option<DateTime<Utc>>,
+
+    /// The url that issues a new token for `pixi auth rotate`
+    #[clap(long)]
+    rotate_url: Option<Url>,
 }
 
 #[derive(Parser, Debug)]
@@ -30,12 +43,36 @@ struct LogoutArgs {
     host: String,
 }
 
+#[derive(Parser, Debug)]
+struct StatusArgs {
+    /// The host to show the authentication status for
+    host: String,
+}
+
+#[derive(Parser, Debug)]
+struct RotateArgs {
+    /// The host to rotate the token for
+    host: String,
+
+    /// The new token, requested from the rotation url of the host if it is not given
+    #[clap(long)]
+    token: Option<String>,
+
+    /// When the new token expires, overrides the expiry reported by the rotation url
+    #[clap(long, value_parser = token_expiry::parse_expiry)]
+    expires_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Parser, Debug)]
 enum Subcommand {
     /// Store authentication information for a given host
     Login(LoginArgs),
     /// Remove authentication information for a given host
     Logout(LogoutArgs),
+    /// Show from which source the credentials for a given host are resolved
+    Status(StatusArgs),
+    /// Replace the token for a given host, with a given token or one from its rotation url
+    Rotate(RotateArgs),
 }
 
 /// Login to prefix.dev or anaconda.org servers to access private channels
@@ -95,10 +132,26 @@ fn login(args: LoginArgs, storage: AuthenticationStorage) -> miette::Result<()>
         miette::bail!("Authentication with anaconda.org requires a conda token. Use `--conda-token` to provide one.");
     }
 
+    if matches!(auth, Authentication::BasicHTTP { .. })
+        && (args.expires_at.is_some() || args.rotate_url.is_some())
+    {
+        miette::bail!("`--expires-at` and `--rotate-url` can only be used with a token");
+    }
+
     storage
         .store(&host, &auth)
         .map_err(|e| miette::miette!(e.to_string()))?;
-    Ok(())
+
+    // The metadata of a previous token does not apply to the new one.
+    let mut metadata_store = TokenMetadataStore::load()?;
+    metadata_store.set(
+        host,
+        TokenMetadata {
+            expires_at: args.expires_at,
+            rotate_url: args.rotate_url,
+        },
+    );
+    metadata_store.save()
 }
 
 fn logout(args: LogoutArgs, storage: AuthenticationStorage) -> miette::Result<()> {
@@ -109,6 +162,106 @@ fn logout(args: LogoutArgs, storage: AuthenticationStorage) -> miette::Result<()
     storage
         .delete(&host)
         .map_err(|e| miette::miette!(e.to_string()))?;
+
+    let mut metadata_store = TokenMetadataStore::load()?;
+    if metadata_store.remove(&host) {
+        metadata_store.save()?;
+    }
+    Ok(())
+}
+
+async fn rotate(args: RotateArgs, storage: AuthenticationStorage) -> miette::Result<()> {
+    let host = get_url(&args.host)?;
+    let Some(auth) = storage
+        .get(&host)
+        .map_err(|e| miette::miette!(e.to_string()))?
+    else {
+        miette::bail!(
+            help = format!("use `pixi auth login {host}` to store a token"),
+            "there is no token stored for {host}"
+        );
+    };
+
+    let mut metadata_store = TokenMetadataStore::load()?;
+    let mut metadata = metadata_store.get(&host).cloned().unwrap_or_default();
+    let (token, expires_at) = match (args.token, &metadata.rotate_url) {
+        (Some(token), _) => (token, args.expires_at),
+        (None, Some(rotate_url)) => {
+            let rotated = token_expiry::rotate_token(rotate_url, &auth).await?;
+            (rotated.token, args.expires_at.or(rotated.expires_at))
+        }
+        (None, None) => miette::bail!(
+            help = format!("pass the new token with `--token`, or store the rotation url of the service with `pixi auth login {host} --rotate-url <URL>`"),
+            "pixi doesn't know how to request a new token for {host}"
+        ),
+    };
+
+    let auth = match auth {
+        Authentication::BearerToken(_) => Authentication::BearerToken(token),
+        Authentication::CondaToken(_) => Authentication::CondaToken(token),
+        Authentication::BasicHTTP { .. } => {
+            miette::bail!("only tokens can be rotated, {host} uses basic HTTP credentials")
+        }
+    };
+    storage
+        .store(&host, &auth)
+        .map_err(|e| miette::miette!(e.to_string()))?;
+
+    metadata.expires_at = expires_at;
+    metadata_store.set(host.clone(), metadata);
+    metadata_store.save()?;
+
+    match expires_at {
+        Some(expires_at) => println!(
+            "Rotated the token for {host}, it expires on {}",
+            expires_at.format("%Y-%m-%d %H:%M UTC")
+        ),
+        None => println!("Rotated the token for {host}"),
+    }
+    Ok(())
+}
+
+fn status(args: StatusArgs) -> miette::Result<()> {
+    // Resolve against the plain host, the resolution itself takes care of wildcards.
+    let host = if args.host.contains("://") {
+        let url = url::Url::parse(&args.host).into_diagnostic()?;
+        url.host_str()
+            .ok_or_else(|| miette::miette!("the url '{url}' has no host"))?
+            .to_string()
+    } else {
+        args.host
+    };
+
+    match auth::resolve_authentication(&host) {
+        Some((auth, source)) => {
+            let kind = match auth {
+                Authentication::BearerToken(_) => "bearer token",
+                Authentication::BasicHTTP { .. } => "basic HTTP",
+                Authentication::CondaToken(_) => "conda token",
+            };
+            println!("Using {kind} credentials for {host} from the {source}");
+
+            // Only the tokens stored by `pixi auth login` have metadata.
+            let metadata_store = TokenMetadataStore::load()?;
+            let metadata = metadata_store
+                .lookup(&host)
+                .filter(|_| source == auth::AuthenticationSource::Keyring);
+            if let Some((_, metadata)) = metadata {
+                if let Some(expires_at) = metadata.expires_at {
+                    let state = if metadata.expiry(Utc::now()) == Expiry::Expired {
+                        "expired"
+                    } else {
+                        "expires"
+                    };
+                    println!(
+                        "The token {state} on {}",
+                        expires_at.format("%Y-%m-%d %H:%M UTC")
+                    );
+                }
+            }
+        }
+        None => println!("No credentials found for {host}"),
+    }
     Ok(())
 }
 
@@ -118,5 +271,7 @@ pub async fn execute(args: Args) -> miette::Result<()> {
     match args.subcommand {
         Subcommand::Login(args) => login(args, storage),
         Subcommand::Logout(args) => logout(args, storage),
+        Subcommand::Status(args) => status(args),
+        Subcommand::Rotate(args) => rotate(args, storage).await,
     }
 }
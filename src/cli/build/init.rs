@@ -0,0 +1,334 @@
+use crate::{project::SpecType, Project};
+use clap::Parser;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use minijinja::{context, Environment};
+use rattler_conda_types::{NamelessMatchSpec, PackageName};
+use std::path::{Path, PathBuf};
+
+/// The name of the task that is invoked by the build script of the recipe.
+const BUILD_TASK: &str = "build";
+
+/// Generate a starter `recipe.yaml` for rattler-build from the project manifest.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// Where to write the recipe (defaults to `recipe/recipe.yaml` in the project root)
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+
+    /// Overwrite the recipe if it already exists
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// The recipe.yaml template. All values are passed in as quoted YAML scalars.
+const RECIPE_TEMPLATE: &str = r#"# Generated by `pixi build init`, adjust it to your needs.
+# See https://prefix-dev.github.io/rattler-build for the documentation of this format.
+package:
+  name: {{ name }}
+  version: {{ version }}
+
+source:
+  path: {{ source_path }}
+
+build:
+  number: 0
+  script:
+{%- for line in script %}
+    - {{ line }}
+{%- endfor %}
+
+requirements:
+{%- for kind, deps in requirements %}
+  {{ kind }}:
+{%- for dep in deps %}
+    - {{ dep }}
+{%- endfor %}
+{%- endfor %}
+{%- if about %}
+
+about:
+{%- for key, value in about %}
+  {{ key }}: {{ value }}
+{%- endfor %}
+{%- endif %}
+"#;
+
+/// Quotes a value as a YAML scalar. JSON strings are valid YAML, so characters that have a
+/// meaning in YAML (e.g. `: `, `#` or a leading `*`) are kept as they are.
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).expect("strings can always be serialized")
+}
+
+/// Formats dependencies as a list of quoted match specs.
+fn format_dependencies(deps: &IndexMap<PackageName, NamelessMatchSpec>) -> Vec<String> {
+    deps.iter()
+        .map(|(name, spec)| {
+            let spec = spec.to_string();
+            if spec == "*" {
+                quote(name.as_source())
+            } else {
+                quote(&format!("{} {}", name.as_source(), spec))
+            }
+        })
+        .collect()
+}
+
+/// Returns the lines of the build script, which invoke the command of the build task. pixi is not
+/// available when the recipe is built, so the command is inlined and tasks whose behavior depends
+/// on pixi (dependencies on other tasks, a working directory or conditions) are rejected.
+fn build_script(project: &Project) -> miette::Result<Vec<String>> {
+    let Some(task) = project.task_opt(BUILD_TASK, None) else {
+        tracing::warn!(
+            "the project does not define a '{BUILD_TASK}' task, the build script of the recipe needs to be filled in"
+        );
+        return Ok(vec![quote("echo \"TODO: add a build script\"")]);
+    };
+
+    let unsupported = if !task.depends_on().is_empty() {
+        Some("depends on other tasks")
+    } else if task.working_directory().is_some() {
+        Some("has a working directory")
+    } else if task.as_execute().map_or(false, |execute| {
+        execute.skip_if.is_some() || execute.only_if.is_some()
+    }) {
+        Some("has a condition")
+    } else {
+        None
+    };
+    let command = match (unsupported, task.as_single_command()) {
+        (None, Some(command)) => command,
+        (Some(reason), _) => miette::bail!(
+            help = "write the build script of the recipe by hand, or make '{BUILD_TASK}' a single command",
+            "the '{BUILD_TASK}' task {reason}, which cannot be represented in the build script of the recipe"
+        ),
+        (None, None) => miette::bail!(
+            help = "write the build script of the recipe by hand, or make '{BUILD_TASK}' a single command",
+            "the '{BUILD_TASK}' task is an alias, which cannot be represented in the build script of the recipe"
+        ),
+    };
+    Ok(vec![quote(&command)])
+}
+
+/// Renders the recipe for the project. `source_path` is the path of the project root relative to
+/// the recipe.
+pub fn render_recipe(project: &Project, source_path: &str) -> miette::Result<String> {
+    let default_target = project.manifest.default_feature().targets.default();
+
+    // Only the dependencies of the default target are added, target specific dependencies need
+    // selectors which are left to the user.
+    let requirements = [
+        ("build", SpecType::Build),
+        ("host", SpecType::Host),
+        ("run", SpecType::Run),
+    ]
+    .into_iter()
+    .filter_map(|(kind, spec_type)| {
+        let deps = format_dependencies(default_target.dependencies.get(&spec_type)?);
+        (!deps.is_empty()).then_some((kind, deps))
+    })
+    .collect_vec();
+
+    // Invoke the command of the build task.
+    let script = build_script(project)?;
+
+    let metadata = &project.manifest.parsed.project;
+    let about = [
+        (
+            "homepage",
+            metadata.homepage.as_ref().map(ToString::to_string),
+        ),
+        (
+            "repository",
+            metadata.repository.as_ref().map(ToString::to_string),
+        ),
+        (
+            "documentation",
+            metadata.documentation.as_ref().map(ToString::to_string),
+        ),
+        ("license", metadata.license.clone()),
+        (
+            "license_file",
+            metadata
+                .license_file
+                .as_ref()
+                .map(|p| p.display().to_string()),
+        ),
+        ("summary", metadata.description.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| Some((key, serde_json::to_string(&value?).ok()?)))
+    .collect_vec();
+
+    let version = quote(
+        &project
+            .version()
+            .as_ref()
+            .map_or_else(|| String::from("0.1.0"), ToString::to_string),
+    );
+
+    Environment::new()
+        .render_named_str(
+            "recipe.yaml",
+            RECIPE_TEMPLATE,
+            context! {
+                name => quote(project.name()),
+                version,
+                source_path => quote(source_path),
+                script,
+                requirements,
+                about,
+            },
+        )
+        .into_diagnostic()
+}
+
+pub async fn execute(project: Project, args: Args) -> miette::Result<()> {
+    let output = match args.output {
+        Some(output) => std::env::current_dir().into_diagnostic()?.join(output),
+        None => project.root().join("recipe").join("recipe.yaml"),
+    };
+    if output.exists() && !args.force {
+        miette::bail!(
+            "{} already exists, use `--force` to overwrite it",
+            output.display()
+        );
+    }
+
+    // Determine the path of the project relative to the recipe.
+    let recipe_dir = output
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| project.root().to_path_buf());
+    let source_path = pathdiff(&recipe_dir, project.root());
+
+    let recipe = render_recipe(&project, &source_path)?;
+    std::fs::create_dir_all(&recipe_dir).into_diagnostic()?;
+    std::fs::write(&output, recipe).into_diagnostic()?;
+
+    eprintln!(
+        "{}Created recipe at {}",
+        console::style(console::Emoji("✔ ", "")).green(),
+        output.display()
+    );
+
+    Ok(())
+}
+
+/// Returns the relative path from `from` to `to` using forward slashes. Both paths are expected
+/// to be absolute.
+fn pathdiff(from: &Path, to: &Path) -> String {
+    let from = from.components().collect_vec();
+    let to = to.components().collect_vec();
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let relative = std::iter::repeat("..".to_string())
+        .take(from.len() - common)
+        .chain(
+            to[common..]
+                .iter()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+        )
+        .join("/");
+    if relative.is_empty() {
+        String::from(".")
+    } else {
+        relative
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::manifest::Manifest;
+
+    #[test]
+    fn test_render_recipe() {
+        let manifest = Manifest::from_str(
+            Path::new(""),
+            r#"
+            [project]
+            name = "foo"
+            version = "1.2.3"
+            description = "A foo package"
+            channels = []
+            platforms = ["linux-64"]
+
+            [tasks]
+            build = "cargo build --release"
+
+            [dependencies]
+            python = ">=3.8"
+            numpy = "*"
+
+            [host-dependencies]
+            pip = "*"
+            "#,
+        )
+        .unwrap();
+        let project = Project::from_manifest(manifest);
+
+        let recipe = render_recipe(&project, "..").unwrap();
+        assert!(recipe.contains("  name: \"foo\"\n  version: \"1.2.3\"\n"));
+        assert!(recipe.contains("  path: \"..\"\n"));
+        assert!(recipe.contains("  script:\n    - \"cargo build --release\"\n"));
+        assert!(recipe.contains("  host:\n    - \"pip\"\n"));
+        assert!(recipe.contains("  run:\n    - \"python >=3.8\"\n    - \"numpy\"\n"));
+        assert!(recipe.contains("  summary: \"A foo package\""));
+    }
+
+    /// Returns a project with the given tasks.
+    fn project_with_tasks(tasks: &str) -> Project {
+        let manifest = Manifest::from_str(
+            Path::new(""),
+            &format!(
+                r#"
+                [project]
+                name = "foo"
+                channels = []
+                platforms = ["linux-64"]
+
+                [tasks]
+                {tasks}
+                "#
+            ),
+        )
+        .unwrap();
+        Project::from_manifest(manifest)
+    }
+
+    #[test]
+    fn test_build_script_is_quoted() {
+        let project = project_with_tasks(r#"build = "echo 'key: value' # comment""#);
+        let recipe = render_recipe(&project, ".").unwrap();
+        assert!(recipe.contains("  script:\n    - \"echo 'key: value' # comment\"\n"));
+    }
+
+    #[test]
+    fn test_build_script_rejects_pixi_only_tasks() {
+        for tasks in [
+            r#"
+            compile = "make"
+            build = { cmd = "make install", depends_on = ["compile"] }
+            "#,
+            r#"build = { cmd = "make", cwd = "src" }"#,
+            r#"
+            compile = "make"
+            build = { depends_on = ["compile"] }
+            "#,
+        ] {
+            let project = project_with_tasks(tasks);
+            assert!(render_recipe(&project, ".").is_err(), "{tasks}");
+        }
+    }
+
+    #[test]
+    fn test_pathdiff() {
+        assert_eq!(pathdiff(Path::new("/a/b/recipe"), Path::new("/a/b")), "..");
+        assert_eq!(pathdiff(Path::new("/a/b"), Path::new("/a/b")), ".");
+    }
+}
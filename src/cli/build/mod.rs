@@ -0,0 +1,38 @@
+use crate::Project;
+use clap::Parser;
+use std::path::PathBuf;
+
+pub mod init;
+pub mod test;
+
+/// Commands to build the project into a conda package.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[clap(long, global = true)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// The subcommand to execute
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Parser, Debug)]
+pub enum Command {
+    /// Generate a starter rattler-build recipe from the project manifest.
+    Init(init::Args),
+
+    /// Run the tests of a built package in a clean environment.
+    Test(test::Args),
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+
+    match args.command {
+        Command::Init(args) => init::execute(project, args).await?,
+        Command::Test(args) => test::execute(project, args).await?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,320 @@
+use crate::{
+    cli::run::run_activation, default_authenticated_client, install::execute_transaction,
+    prefix::Prefix, progress::await_in_progress, repodata::fetch_sparse_repodata, Project,
+};
+use clap::Parser;
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use rattler::install::Transaction;
+use rattler_conda_types::{
+    package::{IndexJson, PackageFile},
+    Channel, ChannelConfig, MatchSpec, PackageRecord, Platform, PrefixRecord, RepoDataRecord,
+};
+use rattler_repodata_gateway::sparse::SparseRepoData;
+use rattler_solve::{resolvo, SolverImpl};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+use url::Url;
+
+/// The directory inside a package that contains the test files.
+const TEST_DIR: &str = "info/test";
+
+/// The file that lists the additional requirements of the tests.
+const TEST_DEPENDENCIES_FILE: &str = "test_time_dependencies.json";
+
+/// Run the tests of a built package in a clean environment.
+///
+/// The environment only contains the package itself, its run dependencies and the requirements of
+/// the tests, so missing run dependencies are caught before the package is uploaded.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The package file (`.conda` or `.tar.bz2`) to test
+    pub package_file: PathBuf,
+
+    /// The channels to resolve the dependencies from (defaults to the channels of the project)
+    #[arg(short, long = "channel", id = "channel")]
+    pub channels: Option<Vec<String>>,
+}
+
+/// A test script shipped inside a package.
+enum TestScript {
+    /// A shell script (`run_test.sh`) or batch file (`run_test.bat`)
+    Shell(PathBuf),
+    /// A python script (`run_test.py`)
+    Python(PathBuf),
+}
+
+/// Returns the test scripts of an extracted package that can run on the current platform.
+fn test_scripts(test_dir: &Path) -> Vec<TestScript> {
+    let shell_script = if cfg!(windows) {
+        "run_test.bat"
+    } else {
+        "run_test.sh"
+    };
+    [
+        TestScript::Shell(test_dir.join(shell_script)),
+        TestScript::Python(test_dir.join("run_test.py")),
+    ]
+    .into_iter()
+    .filter(|script| match script {
+        TestScript::Shell(path) | TestScript::Python(path) => path.is_file(),
+    })
+    .collect()
+}
+
+/// Reads the additional requirements of the tests from an extracted package.
+fn test_dependencies(test_dir: &Path) -> miette::Result<Vec<MatchSpec>> {
+    let path = test_dir.join(TEST_DEPENDENCIES_FILE);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(&path).into_diagnostic()?;
+    let specs: Vec<String> = serde_json::from_str(&contents)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to parse {}", path.display()))?;
+    specs
+        .iter()
+        .map(|spec| MatchSpec::from_str(spec).into_diagnostic())
+        .collect()
+}
+
+/// Constructs a [`RepoDataRecord`] for a local package file so it can be passed to the solver.
+fn local_repodata_record(
+    package_file: &Path,
+    extracted: &Path,
+    sha256: rattler_digest::Sha256Hash,
+    md5: rattler_digest::Md5Hash,
+) -> miette::Result<RepoDataRecord> {
+    let package_file = package_file.canonicalize().into_diagnostic()?;
+    let size = package_file.metadata().into_diagnostic()?.len();
+    let index_json = IndexJson::from_package_directory(extracted).into_diagnostic()?;
+    let package_record =
+        PackageRecord::from_index_json(index_json, Some(size), Some(sha256), Some(md5))
+            .into_diagnostic()?;
+
+    let url = Url::from_file_path(&package_file)
+        .map_err(|_| miette::miette!("invalid package path {}", package_file.display()))?;
+    let channel = package_file
+        .parent()
+        .and_then(|dir| Url::from_directory_path(dir).ok())
+        .map(|url| url.to_string())
+        .unwrap_or_default();
+    let file_name = package_file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    Ok(RepoDataRecord {
+        package_record,
+        file_name,
+        url,
+        channel,
+    })
+}
+
+/// Runs a single test script with the activated environment.
+fn run_test_script(
+    script: &TestScript,
+    test_dir: &Path,
+    prefix: &Prefix,
+    activation_env: &HashMap<String, String>,
+) -> miette::Result<()> {
+    let (mut command, path) = match script {
+        TestScript::Shell(path) if cfg!(windows) => {
+            let mut command = Command::new("cmd.exe");
+            command.arg("/d").arg("/c").arg(path);
+            (command, path)
+        }
+        TestScript::Shell(path) => {
+            let mut command = Command::new("bash");
+            command.arg("-e").arg(path);
+            (command, path)
+        }
+        TestScript::Python(path) => {
+            let python = if cfg!(windows) {
+                prefix.root().join("python.exe")
+            } else {
+                prefix.root().join("bin").join("python")
+            };
+            let mut command = Command::new(python);
+            command.arg(path);
+            (command, path)
+        }
+    };
+
+    eprintln!(
+        "{}Running {}",
+        console::style(console::Emoji("🧪 ", "")).bold(),
+        path.display()
+    );
+    let status = command
+        .current_dir(test_dir)
+        .envs(activation_env)
+        .env("PREFIX", prefix.root())
+        .status()
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to run {}", path.display()))?;
+
+    if !status.success() {
+        miette::bail!("{} failed with {}", path.display(), status);
+    }
+    Ok(())
+}
+
+pub async fn execute(project: Project, args: Args) -> miette::Result<()> {
+    // Extract the package to read its metadata and tests.
+    let extracted = tempfile::tempdir().into_diagnostic()?;
+    let extract_result =
+        rattler_package_streaming::fs::extract(&args.package_file, extracted.path())
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to extract {}", args.package_file.display()))?;
+    let record = local_repodata_record(
+        &args.package_file,
+        extracted.path(),
+        extract_result.sha256,
+        extract_result.md5,
+    )?;
+    let package_record = &record.package_record;
+
+    // The tests have to run on the current platform.
+    let platform = Platform::current();
+    if package_record.subdir != platform.as_str() && package_record.subdir != "noarch" {
+        miette::bail!(
+            "cannot test a package built for '{}' on '{}'",
+            package_record.subdir,
+            platform
+        );
+    }
+
+    // Determine the channels to resolve the dependencies from.
+    let channels = match args.channels {
+        Some(channels) => {
+            let channel_config = ChannelConfig::default();
+            channels
+                .iter()
+                .map(|c| Channel::from_str(c, &channel_config))
+                .collect::<Result<Vec<_>, _>>()
+                .into_diagnostic()?
+        }
+        None => project.channels().to_vec(),
+    };
+
+    // The environment consists of exactly this package and the requirements of its tests.
+    let test_dir = extracted.path().join(TEST_DIR);
+    let scripts = test_scripts(&test_dir);
+    let mut specs = test_dependencies(&test_dir)?;
+    if scripts
+        .iter()
+        .any(|script| matches!(script, TestScript::Python(_)))
+        && !specs
+            .iter()
+            .any(|spec| spec.name.as_ref().map(|n| n.as_normalized()) == Some("python"))
+    {
+        specs.push(MatchSpec::from_str("python").into_diagnostic()?);
+    }
+    let package_spec = MatchSpec::from_str(&format!(
+        "{} =={} {}",
+        package_record.name.as_normalized(),
+        package_record.version,
+        package_record.build
+    ))
+    .into_diagnostic()?;
+
+    // Load the records of all the dependencies and add the local package.
+    let dependency_names = package_record
+        .depends
+        .iter()
+        .map(|dep| MatchSpec::from_str(dep).into_diagnostic())
+        .chain(specs.iter().cloned().map(Ok))
+        .filter_map_ok(|spec| spec.name)
+        .collect::<miette::Result<Vec<_>>>()?;
+    let sparse_repodata = fetch_sparse_repodata(&channels, &[platform]).await?;
+    let mut available_packages =
+        SparseRepoData::load_records_recursive(sparse_repodata.iter(), dependency_names, None)
+            .into_diagnostic()?;
+    available_packages.push(vec![record.clone()]);
+
+    let task = rattler_solve::SolverTask {
+        specs: std::iter::once(package_spec).chain(specs).collect(),
+        available_packages: &available_packages,
+        virtual_packages: rattler_virtual_packages::VirtualPackage::current()
+            .into_diagnostic()?
+            .iter()
+            .cloned()
+            .map(Into::into)
+            .collect(),
+        locked_packages: vec![],
+        pinned_packages: vec![],
+    };
+    let records = resolvo::Solver
+        .solve(task)
+        .into_diagnostic()
+        .wrap_err("failed to create the test environment")?;
+
+    // Create the test environment in a temporary directory.
+    let env_dir = tempfile::tempdir().into_diagnostic()?;
+    let prefix = Prefix::new(env_dir.path())?;
+    let transaction =
+        Transaction::from_current_and_desired(Vec::<PrefixRecord>::new(), records, platform)
+            .into_diagnostic()?;
+    await_in_progress(
+        "creating test environment",
+        execute_transaction(
+            &transaction,
+            prefix.root().to_path_buf(),
+            rattler::default_cache_dir()
+                .map_err(|_| miette::miette!("could not determine default cache directory"))?,
+            default_authenticated_client(),
+        ),
+    )
+    .await?;
+
+    if scripts.is_empty() {
+        tracing::warn!(
+            "{} does not contain any tests, only the installation was verified",
+            args.package_file.display()
+        );
+    }
+
+    let activation_env = run_activation(prefix.clone(), vec![], true).await?;
+    for script in &scripts {
+        run_test_script(script, &test_dir, &prefix, &activation_env)?;
+    }
+
+    eprintln!(
+        "{}All tests of {} {} {} passed",
+        console::style(console::Emoji("✔ ", "")).green(),
+        console::style(package_record.name.as_source()).bold(),
+        console::style(&package_record.version).bold(),
+        console::style(&package_record.build).bold(),
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_test_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(test_dependencies(dir.path()).unwrap().is_empty());
+
+        std::fs::write(
+            dir.path().join(TEST_DEPENDENCIES_FILE),
+            r#"["pytest >=7", "numpy"]"#,
+        )
+        .unwrap();
+        let specs = test_dependencies(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|spec| spec.to_string())
+            .collect_vec();
+        assert_eq!(specs, vec!["pytest >=7", "numpy"]);
+    }
+}
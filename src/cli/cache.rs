@@ -0,0 +1,233 @@
+use crate::config::Config;
+use crate::repodata::fetch_sparse_repodata;
+use crate::{ci, Project};
+use clap::Parser;
+use miette::IntoDiagnostic;
+use rattler_conda_types::{Channel, Platform};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+/// Fetch the repodata of channels ahead of time, so solves start with an up to date cache
+#[derive(Parser, Debug)]
+pub struct WarmArgs {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Additional channels to warm, besides those of the project and the configuration
+    #[arg(long, short)]
+    pub channel: Vec<String>,
+
+    /// The platforms of the additional channels, defaults to the current platform
+    #[arg(long, short)]
+    pub platform: Vec<Platform>,
+
+    /// Keep running and warm the cache again after this interval, e.g. `30m` or `1h`
+    #[arg(long, value_parser = parse_interval)]
+    pub interval: Option<Duration>,
+}
+
+#[derive(Parser, Debug)]
+enum Subcommand {
+    /// Fetch the repodata of the channels of the project and the configuration
+    Warm(WarmArgs),
+}
+
+/// Manage the caches of pixi
+#[derive(Parser, Debug)]
+pub struct Args {
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+/// Parses an interval like `90s`, `30m`, `1h` or `1d`.
+fn parse_interval(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| format!("'{value}' does not start with a number"))?;
+    let seconds: u64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("'{value}' must end with a unit: s, m, h or d")),
+    };
+    if amount == 0 {
+        return Err(String::from("the interval must be longer than zero"));
+    }
+    amount
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("the interval '{value}' is too long"))
+}
+
+/// The channels and platforms whose repodata is warmed.
+struct WarmTargets {
+    /// The channels of the project, with the platforms of the project.
+    project: Option<(Vec<Channel>, Vec<Platform>)>,
+    /// The channels of the configuration and the command line, with their platforms.
+    additional: (Vec<Channel>, Vec<Platform>),
+}
+
+/// Determines what to warm from the project, if there is one, the configuration and the command
+/// line.
+fn warm_targets(
+    project: Option<&Project>,
+    config: &Config,
+    args: &WarmArgs,
+) -> miette::Result<WarmTargets> {
+    let warming = config.repodata_warming.clone().unwrap_or_default();
+    let channel_config = config.channel_config();
+    let channels = warming
+        .channels
+        .iter()
+        .chain(args.channel.iter())
+        .map(|channel| Channel::from_str(channel, &channel_config))
+        .collect::<Result<Vec<_>, _>>()
+        .into_diagnostic()?;
+    let mut platforms = if args.platform.is_empty() {
+        warming.platforms
+    } else {
+        args.platform.clone()
+    };
+    if platforms.is_empty() {
+        platforms.push(Platform::current());
+    }
+
+    let project = project.map(|project| {
+        let mut channels = project.channels().to_vec();
+        channels.extend(project.prerelease_channels());
+        (channels, project.platforms().to_vec())
+    });
+    Ok(WarmTargets {
+        project,
+        additional: (channels, platforms),
+    })
+}
+
+/// Fetches the repodata of the targets, the cache of rattler skips what is still up to date.
+async fn warm(targets: &WarmTargets) -> miette::Result<()> {
+    let mut fetched = 0;
+    for (channels, platforms) in targets.project.iter().chain([&targets.additional]) {
+        fetched += fetch_sparse_repodata(channels, platforms).await?.len();
+    }
+    eprintln!(
+        "{}Warmed the repodata of {fetched} channel subdirectories",
+        console::style(console::Emoji("✔ ", "")).green(),
+    );
+    Ok(())
+}
+
+async fn execute_warm(args: WarmArgs) -> miette::Result<()> {
+    // Outside of a project only the channels of the configuration and the command line are warmed.
+    let project = match &args.manifest_path {
+        Some(manifest_path) => Some(Project::load_or_else_discover(Some(manifest_path))?),
+        None => Project::load_or_else_discover(None).ok(),
+    };
+    let config = match &project {
+        Some(project) => project.config().clone(),
+        None => Config::load_global()?,
+    };
+    let targets = warm_targets(project.as_ref(), &config, &args)?;
+    if targets.project.is_none() && targets.additional.0.is_empty() {
+        miette::bail!(
+            help = "run the command in a project, pass `--channel` or add channels to `repodata-warming` in the configuration",
+            "there are no channels to warm"
+        );
+    }
+
+    let Some(interval) = args.interval else {
+        return warm(&targets).await;
+    };
+    loop {
+        // A failure, e.g. because the network is down, is retried at the next interval.
+        if let Err(err) = warm(&targets).await {
+            tracing::warn!("failed to warm the repodata: {err:?}");
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Warms the repodata of the project in a background process if `on-project-open` is enabled in
+/// the configuration. The process runs detached, its output is discarded.
+pub fn warm_in_background(project: &Project) {
+    let enabled = project
+        .config()
+        .repodata_warming
+        .as_ref()
+        .map_or(false, |warming| warming.on_project_open);
+    if !enabled || ci::is_ci() {
+        return;
+    }
+    if let Err(err) = spawn_warm(&project.manifest_path()) {
+        tracing::debug!("failed to warm the repodata in the background: {err}");
+    }
+}
+
+fn spawn_warm(manifest_path: &Path) -> std::io::Result<()> {
+    std::process::Command::new(std::env::current_exe()?)
+        .args(["cache", "warm", "--manifest-path"])
+        .arg(manifest_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    match args.subcommand {
+        Subcommand::Warm(args) => execute_warm(args).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_interval("30m").unwrap(), Duration::from_secs(30 * 60));
+        assert_eq!(parse_interval("1d").unwrap(), Duration::from_secs(86400));
+        assert!(parse_interval("1").is_err());
+        assert!(parse_interval("0h").is_err());
+        assert!(parse_interval("h").is_err());
+        assert!(parse_interval(&format!("{}d", u64::MAX / 2)).is_err());
+    }
+
+    #[test]
+    fn test_warm_targets() {
+        let config = Config::from_toml(
+            r#"
+            [repodata-warming]
+            channels = ["bioconda"]
+            "#,
+        )
+        .unwrap();
+        let args = WarmArgs::parse_from(["warm", "-c", "pytorch", "-p", "linux-64"]);
+        let targets = warm_targets(None, &config, &args).unwrap();
+        assert!(targets.project.is_none());
+        assert_eq!(
+            targets
+                .additional
+                .0
+                .iter()
+                .map(|channel| channel.name())
+                .collect_vec(),
+            vec!["bioconda", "pytorch"]
+        );
+        assert_eq!(targets.additional.1, vec![Platform::Linux64]);
+
+        let args = WarmArgs::parse_from(["warm"]);
+        let targets = warm_targets(None, &Config::default(), &args).unwrap();
+        assert!(targets.additional.0.is_empty());
+        assert_eq!(targets.additional.1, vec![Platform::current()]);
+    }
+}
@@ -0,0 +1,32 @@
+use crate::local_channel::index_channel;
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Generate the `repodata.json` of every platform directory of a channel from the packages in it.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The directory of the channel, with a subdirectory for every platform, e.g. `linux-64`
+    pub dir: PathBuf,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let dir = args.dir.clone();
+    let indexed = tokio::task::spawn_blocking(move || index_channel(&dir))
+        .await
+        .map_err(|_| miette::miette!("the operation was cancelled"))??;
+
+    for subdir in &indexed {
+        eprintln!(
+            "{}Indexed {} packages for {}",
+            console::style(console::Emoji("✔ ", "")).green(),
+            subdir.packages,
+            console::style(&subdir.subdir).bold()
+        );
+    }
+    let dir = dunce::canonicalize(&args.dir).unwrap_or(args.dir);
+    eprintln!(
+        "Use the channel with `pixi project channel add {}`",
+        dir.display()
+    );
+    Ok(())
+}
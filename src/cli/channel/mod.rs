@@ -0,0 +1,27 @@
+pub mod index;
+pub mod serve;
+
+use clap::Parser;
+
+/// Commands to create and share channels in a local directory.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The subcommand to execute
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Parser, Debug)]
+pub enum Command {
+    /// Generate the repodata of a channel in a local directory.
+    Index(index::Args),
+    /// Serve a channel in a local directory over HTTP.
+    Serve(serve::Args),
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    match args.command {
+        Command::Index(args) => index::execute(args).await,
+        Command::Serve(args) => serve::execute(args).await,
+    }
+}
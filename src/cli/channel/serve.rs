@@ -0,0 +1,61 @@
+use crate::local_channel::{index_channel, serve_channel};
+use clap::Parser;
+use miette::{Context, IntoDiagnostic};
+use std::net::TcpListener;
+use std::path::PathBuf;
+
+/// Serve a channel in a local directory over HTTP, so other machines can use its packages.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The directory of the channel
+    #[arg(default_value = ".")]
+    pub dir: PathBuf,
+
+    /// The address to listen on, use `0.0.0.0` to accept connections from other machines
+    #[arg(long, default_value = "127.0.0.1")]
+    pub host: String,
+
+    /// The port to listen on
+    #[arg(long, short, default_value_t = 8000)]
+    pub port: u16,
+
+    /// Generate the repodata of the channel before serving it
+    #[arg(long)]
+    pub index: bool,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let dir = dunce::canonicalize(&args.dir)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("'{}' is not a directory", args.dir.display()))?;
+    if args.index {
+        index_channel(&dir)?;
+    } else if !dir.join("noarch").join("repodata.json").is_file() {
+        miette::bail!(
+            help = format!(
+                "generate it with `pixi channel index {}`",
+                args.dir.display()
+            ),
+            "'{}' is not a channel, it has no repodata",
+            dir.display()
+        );
+    }
+
+    let listener = TcpListener::bind((args.host.as_str(), args.port))
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to listen on {}:{}", args.host, args.port))?;
+    let address = listener.local_addr().into_diagnostic()?;
+    eprintln!(
+        "{}Serving {} at {}",
+        console::style(console::Emoji("✔ ", "")).green(),
+        dir.display(),
+        console::style(format!("http://{address}/")).bold()
+    );
+    eprintln!(
+        "Use the channel with `pixi project channel add http://{address}/`, press Ctrl+C to stop"
+    );
+
+    tokio::task::spawn_blocking(move || serve_channel(&dir, listener))
+        .await
+        .map_err(|_| miette::miette!("the operation was cancelled"))?
+}
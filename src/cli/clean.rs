@@ -0,0 +1,41 @@
+use crate::{build_envs, Project};
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Remove the build environments of the source distributions of the project
+#[derive(Parser, Debug)]
+pub struct BuildEnvsArgs {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+}
+
+#[derive(Parser, Debug)]
+enum Subcommand {
+    /// Remove the build environments of the source distributions of the project
+    BuildEnvs(BuildEnvsArgs),
+}
+
+/// Remove what pixi stored for the project and can recreate
+#[derive(Parser, Debug)]
+pub struct Args {
+    #[clap(subcommand)]
+    subcommand: Subcommand,
+}
+
+fn execute_build_envs(args: BuildEnvsArgs) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+    let removed = build_envs::clean_build_environments(&project)?;
+    eprintln!(
+        "{}Removed {removed} build environments from {}",
+        console::style(console::Emoji("✔ ", "")).green(),
+        build_envs::build_envs_root(&project).display()
+    );
+    Ok(())
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    match args.subcommand {
+        Subcommand::BuildEnvs(args) => execute_build_envs(args),
+    }
+}
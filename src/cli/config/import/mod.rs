@@ -0,0 +1,23 @@
+pub mod condarc;
+
+use clap::Parser;
+
+/// Commands to import settings from the configuration of other tools.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The subcommand to execute
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Parser, Debug)]
+pub enum Command {
+    /// Import the channels, proxies and ssl settings of a conda `.condarc`.
+    Condarc(condarc::Args),
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    match args.command {
+        Command::Condarc(args) => condarc::execute(args).await,
+    }
+}
@@ -0,0 +1,23 @@
+pub mod import;
+
+use clap::Parser;
+
+/// Commands to manage the global configuration of pixi.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The subcommand to execute
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Parser, Debug)]
+pub enum Command {
+    /// Import settings from the configuration of other tools.
+    Import(import::Args),
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    match args.command {
+        Command::Import(args) => import::execute(args).await,
+    }
+}
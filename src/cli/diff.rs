@@ -0,0 +1,147 @@
+use crate::cli::update::{diff_lock_files, PackageUpdate};
+use crate::cli::OutputFormat;
+use crate::lock_file::read_lock_file;
+use crate::utils::spec_format;
+use crate::Project;
+use clap::Parser;
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use std::path::PathBuf;
+
+/// Show the changes of the locked packages between two lock files.
+///
+/// This is useful in CI to summarize the environment changes of a pull request, e.g. by comparing
+/// against the lock file of the target branch:
+/// `git show main:pixi.lock > base.lock && pixi diff --before base.lock --format markdown`
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// The lock file before the changes
+    #[arg(long)]
+    pub before: PathBuf,
+
+    /// The lock file after the changes, defaults to the lock file of the project
+    #[arg(long)]
+    pub after: Option<PathBuf>,
+
+    /// The format of the output
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let after = match args.after {
+        Some(after) => after,
+        None => Project::load_or_else_discover(args.manifest_path.as_deref())?.lock_file_path(),
+    };
+    let changes = diff_lock_files(&read_lock_file(&args.before)?, &read_lock_file(&after)?);
+
+    match args.format {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&changes).into_diagnostic()?
+        ),
+        OutputFormat::Markdown => print!("{}", markdown_summary(&changes)),
+        OutputFormat::Table => {
+            if changes.is_empty() {
+                eprintln!(
+                    "{}The locked packages are identical",
+                    console::style(console::Emoji("✔ ", "")).green()
+                );
+            }
+            for change in &changes {
+                println!(
+                    "{}: {}",
+                    spec_format::locked_package(&change.name, change.kind, change.platform),
+                    spec_format::version_change(change.from.as_deref(), change.to.as_deref()),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders the changes as GitHub flavored markdown, the changes of every platform are a separate
+/// table so they can be collapsed.
+fn markdown_summary(changes: &[PackageUpdate]) -> String {
+    if changes.is_empty() {
+        return String::from("### Environment changes\n\nNo locked packages changed.\n");
+    }
+    let added = changes
+        .iter()
+        .filter(|change| change.from.is_none())
+        .count();
+    let removed = changes.iter().filter(|change| change.to.is_none()).count();
+    let mut markdown = format!(
+        "### Environment changes\n\n{added} added, {removed} removed, {} updated\n",
+        changes.len() - added - removed
+    );
+
+    for (platform, changes) in &changes
+        .iter()
+        .sorted_by_key(|change| change.platform.as_str())
+        .group_by(|change| change.platform)
+    {
+        let changes = changes.collect_vec();
+        markdown.push_str(&format!(
+            "\n<details>\n<summary>{platform} ({} changes)</summary>\n\n",
+            changes.len()
+        ));
+        markdown.push_str("| Package | Kind | Before | After |\n| --- | --- | --- | --- |\n");
+        for change in changes {
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {} |\n",
+                change.name,
+                change.kind,
+                change.from.as_deref().unwrap_or("-"),
+                change.to.as_deref().unwrap_or("-"),
+            ));
+        }
+        markdown.push_str("\n</details>\n");
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rattler_conda_types::Platform;
+
+    #[test]
+    fn test_markdown_summary() {
+        let change = |name: &str, platform, from: Option<&str>, to: Option<&str>| PackageUpdate {
+            name: name.to_string(),
+            kind: "conda",
+            platform,
+            from: from.map(ToString::to_string),
+            to: to.map(ToString::to_string),
+            changelog_url: None,
+        };
+        let changes = [
+            change("numpy", Platform::Linux64, Some("1.26.1"), Some("1.26.2")),
+            change("numpy", Platform::Osx64, Some("1.26.1"), Some("1.26.2")),
+            change("scipy", Platform::Linux64, None, Some("1.11.4")),
+        ];
+        assert_eq!(
+            markdown_summary(&changes),
+            "### Environment changes\n\n1 added, 0 removed, 2 updated\n\
+             \n<details>\n<summary>linux-64 (2 changes)</summary>\n\n\
+             | Package | Kind | Before | After |\n| --- | --- | --- | --- |\n\
+             | numpy | conda | 1.26.1 | 1.26.2 |\n\
+             | scipy | conda | - | 1.11.4 |\n\
+             \n</details>\n\
+             \n<details>\n<summary>osx-64 (1 changes)</summary>\n\n\
+             | Package | Kind | Before | After |\n| --- | --- | --- | --- |\n\
+             | numpy | conda | 1.26.1 | 1.26.2 |\n\
+             \n</details>\n"
+        );
+        assert_eq!(
+            markdown_summary(&[]),
+            "### Environment changes\n\nNo locked packages changed.\n"
+        );
+    }
+}
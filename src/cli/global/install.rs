@@ -1,15 +1,20 @@
-use crate::install::execute_transaction;
+use crate::config::{Config, ShadowingPolicy};
+use crate::install::{
+    execute_transaction, recover_interrupted_transaction, report_post_link_outputs,
+    run_post_link_scripts, PostLinkScriptPolicy,
+};
 use crate::repodata::friendly_channel_name;
 use crate::{
-    default_authenticated_client, prefix::Prefix, progress::await_in_progress,
+    default_authenticated_client, menuinst, prefix::Prefix, progress::await_in_progress,
     repodata::fetch_sparse_repodata,
 };
 use clap::Parser;
 use dirs::home_dir;
+use indexmap::IndexMap;
 use itertools::Itertools;
 use miette::IntoDiagnostic;
 use rattler::install::Transaction;
-use rattler_conda_types::{Channel, ChannelConfig, MatchSpec, PackageName, Platform, PrefixRecord};
+use rattler_conda_types::{Channel, MatchSpec, PackageName, Platform, PrefixRecord};
 use rattler_repodata_gateway::sparse::SparseRepoData;
 use rattler_shell::{
     activation::{ActivationVariables, Activator, PathModificationBehavior},
@@ -17,6 +22,7 @@ use rattler_shell::{
     shell::ShellEnum,
 };
 use rattler_solve::{resolvo, SolverImpl};
+use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
 use std::{
     path::{Path, PathBuf},
@@ -25,6 +31,14 @@ use std::{
 
 const BIN_DIR: &str = ".pixi/bin";
 const BIN_ENVS_DIR: &str = ".pixi/envs";
+const GLOBAL_MANIFEST_FILE_NAME: &str = "pixi-global.json";
+
+/// Binaries that other tools of the system rely on. Shadowing them with the version of a globally
+/// installed package can break the system in subtle ways.
+const CRITICAL_SYSTEM_BINARIES: &[&str] = &[
+    "bash", "cc", "curl", "gcc", "git", "gpg", "gzip", "ld", "make", "openssl", "perl", "pip",
+    "pip3", "python", "python3", "sh", "sqlite3", "ssh", "sudo", "tar", "wget", "zsh",
+];
 
 /// Installs the defined package in a global accessible location.
 #[derive(Parser, Debug)]
@@ -33,6 +47,13 @@ pub struct Args {
     /// Specifies the package that is to be installed.
     package: String,
 
+    /// Additional packages that are installed into the environment of the package, e.g. plugins.
+    /// Their executables are not exposed globally. Can be specified multiple times.
+    ///
+    /// For example: `pixi global install jupyterlab --with jupyterlab-git --with ipywidgets`.
+    #[clap(long)]
+    with: Vec<String>,
+
     /// Represents the channels from which the package will be installed.
     /// Multiple channels can be specified by using this field multiple times.
     ///
@@ -40,9 +61,23 @@ pub struct Args {
     /// depends on the `conda-forge` channel.
     /// For example: `pixi global install --channel conda-forge --channel bioconda`.
     ///
-    /// By default, if no channel is provided, `conda-forge` is used.
-    #[clap(short, long, default_values = ["conda-forge"])]
+    /// By default, if no channel is provided, the `default-channels` of the global configuration
+    /// are used, which default to `conda-forge`.
+    #[clap(short, long)]
     channel: Vec<String>,
+
+    /// Create desktop or start-menu shortcuts for the menu items defined by the package.
+    #[clap(long)]
+    shortcuts: bool,
+
+    /// Whether the post-link scripts of the installed packages are executed.
+    #[clap(long, value_enum, default_value_t = PostLinkScriptPolicy::Deny)]
+    post_link_scripts: PostLinkScriptPolicy,
+
+    /// What to do when an executable of the package shadows a critical system binary like `git`.
+    /// Overrides the `system-binary-shadowing` configuration, which defaults to `warn`.
+    #[clap(long, value_enum)]
+    system_binary_shadowing: Option<ShadowingPolicy>,
 }
 
 pub(crate) struct BinDir(pub PathBuf);
@@ -111,6 +146,52 @@ impl BinEnvDir {
     }
 }
 
+/// Records what was requested for a globally installed package. It is stored in the environment of
+/// the package so reinstalling the package keeps the injected packages.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct GlobalManifest {
+    /// The packages that were injected into the environment with `--with`.
+    #[serde(default)]
+    pub with: Vec<String>,
+}
+
+impl GlobalManifest {
+    /// Reads the manifest from the environment at `prefix`, returns the default if there is none.
+    pub async fn from_prefix(prefix: &Path) -> miette::Result<Self> {
+        match tokio::fs::read_to_string(prefix.join(GLOBAL_MANIFEST_FILE_NAME)).await {
+            Ok(contents) => serde_json::from_str(&contents).into_diagnostic(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).into_diagnostic(),
+        }
+    }
+
+    /// Writes the manifest to the environment at `prefix`.
+    pub async fn to_prefix(&self, prefix: &Path) -> miette::Result<()> {
+        let contents = serde_json::to_string_pretty(self).into_diagnostic()?;
+        tokio::fs::write(prefix.join(GLOBAL_MANIFEST_FILE_NAME), contents)
+            .await
+            .into_diagnostic()
+    }
+}
+
+/// Combines the previously injected packages with the newly requested ones, a newly requested spec
+/// replaces a previous spec for the same package.
+fn merge_injected_specs(
+    previous: &[String],
+    requested: &[String],
+) -> miette::Result<Vec<MatchSpec>> {
+    let mut specs = IndexMap::new();
+    for spec in previous.iter().chain(requested) {
+        let spec = MatchSpec::from_str(spec).into_diagnostic()?;
+        let name = spec
+            .name
+            .clone()
+            .ok_or_else(|| miette::miette!("could not find package name in MatchSpec {spec}"))?;
+        specs.insert(name, spec);
+    }
+    Ok(specs.into_values().collect())
+}
+
 /// Binary environments are installed in ~/.pixi/envs
 pub(crate) fn bin_env_dir() -> miette::Result<PathBuf> {
     Ok(home_dir()
@@ -188,7 +269,10 @@ fn is_executable(prefix: &Prefix, relative_path: &Path) -> bool {
 }
 
 /// Find the executable scripts within the specified package installed in this conda prefix.
-fn find_executables<'a>(prefix: &Prefix, prefix_package: &'a PrefixRecord) -> Vec<&'a Path> {
+pub(crate) fn find_executables<'a>(
+    prefix: &Prefix,
+    prefix_package: &'a PrefixRecord,
+) -> Vec<&'a Path> {
     prefix_package
         .files
         .iter()
@@ -276,6 +360,30 @@ pub(crate) async fn find_and_map_executable_scripts<'a>(
     map_executables_to_global_bin_scripts(&executables, bin_dir).await
 }
 
+/// Returns the path of the critical system binary that would be shadowed by the global binary script
+/// at `global_binary_path`, if any. The pixi bin directory itself is not searched.
+fn shadowed_system_binary(global_binary_path: &Path, bin_dir: &Path) -> Option<PathBuf> {
+    let name = global_binary_path.file_stem()?.to_str()?;
+    if !CRITICAL_SYSTEM_BINARIES.contains(&name) {
+        return None;
+    }
+
+    let candidates = if cfg!(windows) {
+        vec![
+            format!("{name}.exe"),
+            format!("{name}.bat"),
+            format!("{name}.cmd"),
+        ]
+    } else {
+        vec![name.to_string()]
+    };
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .filter(|dir| dir != bin_dir)
+        .flat_map(|dir| candidates.iter().map(move |candidate| dir.join(candidate)))
+        .find(|candidate| is_executable::is_executable(candidate))
+}
+
 /// Create the executable scripts by modifying the activation script
 /// to activate the environment and run the executable.
 pub(crate) async fn create_executable_scripts(
@@ -319,9 +427,14 @@ pub(crate) async fn create_executable_scripts(
 /// Install a global command
 pub async fn execute(args: Args) -> miette::Result<()> {
     // Figure out what channels we are using
-    let channel_config = ChannelConfig::default();
-    let channels = args
-        .channel
+    let config = Config::load_global()?;
+    let channel_config = config.channel_config();
+    let channel_names = if args.channel.is_empty() {
+        config.default_channels()
+    } else {
+        args.channel.clone()
+    };
+    let channels = channel_names
         .iter()
         .map(|c| Channel::from_str(c, &channel_config))
         .collect::<Result<Vec<Channel>, _>>()
@@ -337,12 +450,21 @@ pub async fn execute(args: Args) -> miette::Result<()> {
     })?;
     let platform = Platform::current();
 
+    // The packages injected by a previous installation are kept.
+    let previous_manifest = match BinEnvDir::from_existing(&package_name).await {
+        Ok(BinEnvDir(bin_prefix)) => GlobalManifest::from_prefix(&bin_prefix).await?,
+        Err(_) => GlobalManifest::default(),
+    };
+    let injected_specs = merge_injected_specs(&previous_manifest.with, &args.with)?;
+
     // Fetch sparse repodata
     let platform_sparse_repodata = fetch_sparse_repodata(&channels, &[platform]).await?;
 
     let available_packages = SparseRepoData::load_records_recursive(
         platform_sparse_repodata.iter(),
-        vec![package_name.clone()],
+        std::iter::once(package_name.clone())
+            .chain(injected_specs.iter().filter_map(|spec| spec.name.clone()))
+            .collect_vec(),
         None,
     )
     .into_diagnostic()?;
@@ -350,7 +472,9 @@ pub async fn execute(args: Args) -> miette::Result<()> {
     // Solve for environment
     // Construct a solver task that we can start solving.
     let task = rattler_solve::SolverTask {
-        specs: vec![package_matchspec],
+        specs: std::iter::once(package_matchspec)
+            .chain(injected_specs.iter().cloned())
+            .collect(),
         available_packages: &available_packages,
 
         virtual_packages: rattler_virtual_packages::VirtualPackage::current()
@@ -370,6 +494,7 @@ pub async fn execute(args: Args) -> miette::Result<()> {
     // Create the binary environment prefix where we install or update the package
     let BinEnvDir(bin_prefix) = BinEnvDir::create(&package_name).await?;
     let prefix = Prefix::new(bin_prefix)?;
+    recover_interrupted_transaction(prefix.root())?;
     let prefix_records = prefix.find_installed_packages(None).await?;
 
     // Create the transaction that we need
@@ -391,10 +516,40 @@ pub async fn execute(args: Args) -> miette::Result<()> {
             ),
         )
         .await?;
+
+        // Run the post-link scripts of the newly installed packages.
+        let installed_records = prefix
+            .find_installed_packages(None)
+            .await?
+            .into_iter()
+            .filter(|record| {
+                transaction
+                    .operations
+                    .iter()
+                    .filter_map(|op| op.record_to_install())
+                    .any(|r| r.package_record.name == record.repodata_record.package_record.name)
+            })
+            .collect_vec();
+        let outputs =
+            run_post_link_scripts(prefix.root(), &installed_records, args.post_link_scripts)?;
+        report_post_link_outputs(&outputs);
     }
 
+    // Record the injected packages so they are kept when the package is reinstalled.
+    GlobalManifest {
+        with: injected_specs.iter().map(ToString::to_string).collect(),
+    }
+    .to_prefix(prefix.root())
+    .await?;
+
     // Find the installed package in the environment
     let prefix_package = find_designated_package(&prefix, &package_name).await?;
+
+    // Create the shortcuts defined by the package if requested.
+    if args.shortcuts {
+        menuinst::install_shortcuts(&prefix, std::slice::from_ref(&prefix_package), platform)?;
+    }
+
     let channel = Channel::from_str(&prefix_package.repodata_record.channel, &channel_config)
         .map(|ch| friendly_channel_name(&ch))
         .unwrap_or_else(|_| prefix_package.repodata_record.channel.clone());
@@ -410,8 +565,56 @@ pub async fn execute(args: Args) -> miette::Result<()> {
     // for each executable added by the package to the environment.
     let activation_script = create_activation_script(&prefix, shell.clone())?;
     let bin_dir = BinDir::create().await?;
-    let script_mapping =
-        find_and_map_executable_scripts(&prefix, &prefix_package, &bin_dir).await?;
+
+    // Don't silently shadow the binaries the system relies on.
+    let shadowing_policy = match args.system_binary_shadowing {
+        Some(policy) => policy,
+        None => config.system_binary_shadowing(),
+    };
+    let mut script_mapping = Vec::new();
+    let mut denied = Vec::new();
+    for mapping in find_and_map_executable_scripts(&prefix, &prefix_package, &bin_dir).await? {
+        let shadowed = match shadowing_policy {
+            ShadowingPolicy::Allow => None,
+            ShadowingPolicy::Warn | ShadowingPolicy::Deny => {
+                shadowed_system_binary(&mapping.global_binary_path, &bin_dir.0)
+            }
+        };
+        let Some(system_binary) = shadowed else {
+            script_mapping.push(mapping);
+            continue;
+        };
+
+        let name = mapping
+            .global_binary_path
+            .file_stem()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if shadowing_policy == ShadowingPolicy::Deny {
+            eprintln!(
+                "{} '{name}' is not exposed because it would shadow '{}', use `pixi global run {} -- {name}` to run it",
+                console::style("!").yellow().bold(),
+                system_binary.display(),
+                package_name.as_source(),
+            );
+            denied.push(name);
+        } else {
+            eprintln!(
+                "{} '{name}' shadows '{}', set `system-binary-shadowing = \"deny\"` in the global configuration to prevent this",
+                console::style("!").yellow().bold(),
+                system_binary.display(),
+            );
+            script_mapping.push(mapping);
+        }
+    }
+    if script_mapping.is_empty() && !denied.is_empty() {
+        miette::bail!(
+            "all executables of {} would shadow system binaries ({}), use `--system-binary-shadowing allow` to expose them anyway",
+            package_name.as_source(),
+            denied.join(", ")
+        );
+    }
+
     create_executable_scripts(&script_mapping, &prefix, &shell, activation_script).await?;
 
     let scripts: Vec<_> = script_mapping
@@ -450,6 +653,15 @@ pub async fn execute(args: Args) -> miette::Result<()> {
             console::style(prefix_package.repodata_record.package_record.build).bold(),
             channel,
         );
+        if !injected_specs.is_empty() {
+            eprintln!(
+                "{whitespace}Together with: {}",
+                injected_specs
+                    .iter()
+                    .map(|spec| console::style(spec).bold())
+                    .join(", ")
+            );
+        }
 
         let BinDir(bin_dir) = BinDir::from_existing().await?;
         let script_names = scripts
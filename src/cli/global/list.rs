@@ -9,7 +9,7 @@ use rattler_conda_types::PackageName;
 
 use crate::cli::global::install::{
     bin_env_dir, find_and_map_executable_scripts, find_designated_package, BinDir, BinEnvDir,
-    BinScriptMapping,
+    BinScriptMapping, GlobalManifest,
 };
 use crate::prefix::Prefix;
 
@@ -21,6 +21,7 @@ pub struct Args {}
 struct InstalledPackageInfo {
     name: PackageName,
     binaries: Vec<String>,
+    injected: Vec<String>,
 }
 
 impl Display for InstalledPackageInfo {
@@ -29,6 +30,11 @@ impl Display for InstalledPackageInfo {
             .binaries
             .iter()
             .map(|name| format!("[bin] {}", console::style(name).bold()))
+            .chain(
+                self.injected
+                    .iter()
+                    .map(|spec| format!("[with] {}", console::style(spec).bold())),
+            )
             .join("\n     -  ");
         write!(
             f,
@@ -68,6 +74,7 @@ pub async fn execute(_args: Args) -> miette::Result<()> {
             return Ok(());
         };
         let prefix = Prefix::new(bin_env_prefix)?;
+        let manifest = GlobalManifest::from_prefix(prefix.root()).await?;
 
         let Ok(bin_prefix) = BinDir::from_existing().await else {
             print_no_packages_found_message();
@@ -101,6 +108,7 @@ pub async fn execute(_args: Args) -> miette::Result<()> {
         package_info.push(InstalledPackageInfo {
             name: package_name,
             binaries,
+            injected: manifest.with,
         });
     }
 
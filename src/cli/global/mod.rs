@@ -1,7 +1,9 @@
 use clap::Parser;
-mod install;
+pub(crate) mod install;
 mod list;
 mod remove;
+mod run;
+mod shell;
 
 #[derive(Debug, Parser)]
 pub enum Command {
@@ -11,6 +13,8 @@ pub enum Command {
     Remove(remove::Args),
     #[clap(alias = "ls")]
     List(list::Args),
+    Run(run::Args),
+    Shell(shell::Args),
 }
 
 /// Global is the main entry point for the part of pixi that executes on the global(system) level.
@@ -27,6 +31,8 @@ pub async fn execute(cmd: Args) -> miette::Result<()> {
         Command::Install(args) => install::execute(args).await?,
         Command::Remove(args) => remove::execute(args).await?,
         Command::List(args) => list::execute(args).await?,
+        Command::Run(args) => run::execute(args).await?,
+        Command::Shell(args) => shell::execute(args).await?,
     };
     Ok(())
 }
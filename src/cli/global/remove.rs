@@ -5,12 +5,12 @@ use clap::Parser;
 use clap_verbosity_flag::{Level, Verbosity};
 use itertools::Itertools;
 use miette::IntoDiagnostic;
-use rattler_conda_types::MatchSpec;
+use rattler_conda_types::{MatchSpec, Platform};
 
 use crate::cli::global::install::{
     find_and_map_executable_scripts, find_designated_package, BinDir, BinEnvDir, BinScriptMapping,
 };
-use crate::prefix::Prefix;
+use crate::{menuinst, prefix::Prefix};
 
 /// Removes a package previously installed into a globally accessible location via `pixi global install`.
 #[derive(Parser, Debug)]
@@ -56,6 +56,9 @@ pub async fn execute(args: Args) -> miette::Result<()> {
 
     let dirs_to_remove: Vec<_> = vec![bin_prefix];
 
+    // Remove any shortcuts that were created for the package, this is a no-op if there are none.
+    menuinst::remove_shortcuts(&prefix, &[&prefix_package], Platform::current())?;
+
     if args.verbose.log_level().unwrap_or(Level::Error) >= Level::Warn {
         let whitespace = console::Emoji("  ", "").to_string();
         let names_to_remove = dirs_to_remove
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use clap::Parser;
+use miette::IntoDiagnostic;
+use rattler_conda_types::{MatchSpec, PackageName};
+
+use crate::cli::global::install::BinEnvDir;
+use crate::cli::run::run_activation;
+use crate::prefix::Prefix;
+
+/// Runs a command in the environment of a package previously installed via `pixi global install`.
+#[derive(Parser, Debug)]
+#[clap(trailing_var_arg = true, arg_required_else_help = true)]
+pub struct Args {
+    /// Specifies the package in whose environment the command is run.
+    package: String,
+
+    /// The command to run, e.g. `pixi global run jupyterlab -- jupyter lab --port 8888`.
+    #[arg(required = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+}
+
+/// Returns the name of the globally installed `package` and the environment variables that
+/// activate its environment.
+pub(crate) async fn global_env(
+    package: &str,
+) -> miette::Result<(PackageName, HashMap<String, String>)> {
+    let package_matchspec = MatchSpec::from_str(package).into_diagnostic()?;
+    let package_name = package_matchspec.name.clone().ok_or_else(|| {
+        miette::miette!(
+            "could not find package name in MatchSpec {}",
+            package_matchspec
+        )
+    })?;
+    let BinEnvDir(bin_prefix) = BinEnvDir::from_existing(&package_name).await?;
+    let prefix = Prefix::new(bin_prefix)?;
+
+    let mut env = run_activation(prefix, vec![], true).await?;
+    env.insert(
+        "CONDA_DEFAULT_ENV".to_string(),
+        package_name.as_normalized().to_string(),
+    );
+    Ok((package_name, env))
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let (_, env) = global_env(&args.package).await?;
+
+    let (executable, arguments) = args
+        .command
+        .split_first()
+        .expect("clap requires at least one argument");
+    let status = std::process::Command::new(executable)
+        .args(arguments)
+        .envs(env)
+        .status()
+        .map_err(|e| miette::miette!("failed to run '{executable}': {e}"))?;
+
+    std::process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,28 @@
+use clap::Parser;
+use rattler_conda_types::Platform;
+
+use crate::cli::global::run::global_env;
+use crate::cli::shell::{start_shell, ShellOptions};
+use crate::config::Config;
+
+/// Starts a shell in the environment of a package previously installed via `pixi global install`.
+#[derive(Parser, Debug)]
+#[clap(arg_required_else_help = true)]
+pub struct Args {
+    /// Specifies the package in whose environment the shell is started.
+    package: String,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let (package_name, env) = global_env(&args.package).await?;
+    tracing::debug!("Pixi environment activation:\n{:?}", env);
+
+    let options = ShellOptions {
+        default_shell: Config::load_global()?
+            .default_shell
+            .and_then(|shell| shell.for_platform(Platform::current())),
+        ..ShellOptions::default()
+    };
+    let code = start_shell(&env, package_name.as_source(), &options).await?;
+    std::process::exit(code)
+}
@@ -10,8 +10,10 @@ use serde_with::serde_as;
 use serde_with::DisplayFromStr;
 use tokio::task::spawn_blocking;
 
+use crate::channel_report::channel_report;
+use crate::host::{self, LibcFlavor, Wsl};
 use crate::progress::await_in_progress;
-use crate::Project;
+use crate::{lock_file, Project};
 
 /// Information about the system and project
 #[derive(Parser, Debug)]
@@ -24,9 +26,27 @@ pub struct Args {
     #[arg(long)]
     json: bool,
 
-    /// The path to 'pixi.toml'
+    /// The path to 'pixi.toml' or the directory that contains it
     #[arg(long)]
     pub manifest_path: Option<PathBuf>,
+
+    /// Only print a key to cache the environment in CI, which changes whenever the lock file
+    /// changes
+    #[arg(long, conflicts_with_all = ["extended", "json"])]
+    cache_key: bool,
+
+    /// The environment of the cache key, one for every variant of the dependencies of the project
+    #[arg(long, short, requires = "cache_key")]
+    environment: Option<String>,
+
+    /// The platform of the cache key, defaults to the platform of this machine
+    #[arg(long, requires = "cache_key")]
+    platform: Option<Platform>,
+
+    /// Show the channels of the project in order of priority, the platforms they provide, the
+    /// channel every locked direct dependency comes from and conflicts between them
+    #[arg(long, conflicts_with_all = ["extended", "cache_key"])]
+    channels: bool,
 }
 
 #[derive(Serialize)]
@@ -43,6 +63,8 @@ pub struct ProjectInfo {
 #[derive(Serialize)]
 pub struct Info {
     platform: String,
+    libc: Option<LibcFlavor>,
+    wsl: Option<Wsl>,
     #[serde_as(as = "Vec<DisplayFromStr>")]
     virtual_packages: Vec<GenericVirtualPackage>,
     version: String,
@@ -61,6 +83,12 @@ impl Display for Info {
 
         writeln!(f, "pixi {}\n", self.version)?;
         writeln!(f, "{:20}: {}", "Platform", self.platform)?;
+        if let Some(libc) = &self.libc {
+            writeln!(f, "{:20}: {}", "Libc", libc)?;
+        }
+        if let Some(wsl) = &self.wsl {
+            writeln!(f, "{:20}: {}", "WSL", wsl)?;
+        }
 
         for (i, p) in self.virtual_packages.iter().enumerate() {
             if i == 0 {
@@ -172,6 +200,33 @@ fn dependency_count(project: &Project) -> miette::Result<u64> {
 }
 
 pub async fn execute(args: Args) -> miette::Result<()> {
+    if args.cache_key {
+        let project = Project::load_or_else_discover(args.manifest_path.as_deref())?
+            .with_environment(args.environment.as_deref())?;
+        let platform = args.platform.unwrap_or_else(|| project.platform());
+        println!("{}", lock_file::cache_key(&project, platform)?);
+        return Ok(());
+    }
+
+    if args.channels {
+        let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+        let lock_file = lock_file::load_lock_file(&project).await?;
+        let report = await_in_progress(
+            "querying channels",
+            channel_report(&project, &lock_file, project.platform()),
+        )
+        .await;
+        if args.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).into_diagnostic()?
+            );
+        } else {
+            print!("{report}");
+        }
+        return Ok(());
+    }
+
     let project = Project::load_or_else_discover(args.manifest_path.as_deref()).ok();
 
     let cache_dir = rattler::default_cache_dir()
@@ -216,6 +271,8 @@ pub async fn execute(args: Args) -> miette::Result<()> {
 
     let info = Info {
         platform: Platform::current().to_string(),
+        libc: host::libc_flavor(),
+        wsl: host::wsl(),
         virtual_packages,
         version: env!("CARGO_PKG_VERSION").to_string(),
         cache_dir: Some(cache_dir),
@@ -1,10 +1,22 @@
-use crate::{config::get_default_author, consts};
-use clap::Parser;
-use miette::IntoDiagnostic;
+use crate::{
+    ci,
+    config::{get_default_author, Config},
+    consts, gitignore,
+    project::{
+        manifest::{Manifest, PyPiRequirement},
+        SpecType,
+    },
+    task::{CmdArgs, Execute, Task},
+};
+use clap::{Parser, ValueEnum};
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
 use minijinja::{context, Environment};
-use rattler_conda_types::Platform;
+use rattler_conda_types::{MatchSpec, Platform};
+use serde::Deserialize;
 use std::io::{Error, ErrorKind, Write};
 use std::path::Path;
+use std::str::FromStr;
 use std::{fs, path::PathBuf};
 
 /// Creates a new project
@@ -18,13 +30,39 @@ pub struct Args {
     #[arg(short, long = "channel", id = "channel")]
     pub channels: Option<Vec<String>>,
 
-    /// Platforms that the project supports.
-    #[arg(short, long = "platform", id = "platform")]
+    /// Platforms that the project supports, or presets of platforms: `current`, `all-desktop`
+    /// (linux-64, osx-64, osx-arm64 and win-64) or `ci-default` (linux-64, osx-arm64 and win-64).
+    /// Defaults to `default-platforms` of the global configuration, or the current platform.
+    #[arg(short, long = "platform", id = "platform", value_delimiter = ',')]
     pub platforms: Vec<String>,
+
+    /// Ask for the name, channels, platforms, template, whether the lock file is committed and the
+    /// files to import
+    #[arg(short, long)]
+    pub interactive: bool,
+
+    /// Start from a template with the dependencies and tasks of a language
+    #[arg(long, value_enum)]
+    pub template: Option<ProjectTemplate>,
+
+    /// Import the dependencies of an `environment.yml` or `requirements.txt` file
+    #[arg(long = "import", value_name = "FILE")]
+    pub imports: Vec<PathBuf>,
+
+    /// Add `pixi.lock` to the `.gitignore`, e.g. for a library whose users solve their own
+    /// environments. By default the lock file is committed
+    #[arg(long)]
+    pub ignore_lock_file: bool,
 }
 
-/// The default channels to use for a new project.
-const DEFAULT_CHANNELS: &[&str] = &["conda-forge"];
+/// A template with the dependencies and tasks to get started with a language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProjectTemplate {
+    /// Python with a `main.py`
+    Python,
+    /// C++ built with CMake and Ninja
+    Cpp,
+}
 
 /// The pixi.toml template
 ///
@@ -45,21 +83,67 @@ platforms = ["{{ platforms|join("\", \"") }}"]
 
 "#;
 
-const GITIGNORE_TEMPLATE: &str = r#"# pixi environments
-.pixi
+const GITATTRIBUTES_TEMPLATE: &str = r#"# GitHub syntax highlighting
+pixi.lock linguist-language=YAML
+
+"#;
+
+const PYTHON_MAIN: &str = r#"def main():
+    print("Hello from pixi!")
+
 
+if __name__ == "__main__":
+    main()
 "#;
 
-const GITATTRIBUTES_TEMPLATE: &str = r#"# GitHub syntax highlighting
-pixi.lock linguist-language=YAML
+const CMAKE_LISTS: &str = r#"cmake_minimum_required(VERSION 3.20)
+project(main CXX)
+
+add_executable(main src/main.cpp)
+"#;
+
+const CPP_MAIN: &str = r#"#include <iostream>
 
+int main() {
+    std::cout << "Hello from pixi!" << std::endl;
+    return 0;
+}
 "#;
 
+/// The files that `--interactive` offers to import when they exist in the project directory.
+const IMPORTABLE_FILES: [&str; 3] = ["environment.yml", "environment.yaml", "requirements.txt"];
+
+/// The presets of platforms that can be used instead of the name of a platform.
+const PLATFORM_PRESETS: [(&str, &[Platform]); 2] = [
+    (
+        "all-desktop",
+        &[
+            Platform::Linux64,
+            Platform::Osx64,
+            Platform::OsxArm64,
+            Platform::Win64,
+        ],
+    ),
+    (
+        "ci-default",
+        &[Platform::Linux64, Platform::OsxArm64, Platform::Win64],
+    ),
+];
+
+/// The choices for the project, from the arguments and the answers of the user.
+struct InitOptions {
+    name: String,
+    channels: Vec<String>,
+    platforms: Vec<String>,
+    template: Option<ProjectTemplate>,
+    imports: Vec<PathBuf>,
+    ignore_lock_file: bool,
+}
+
 pub async fn execute(args: Args) -> miette::Result<()> {
     let env = Environment::new();
     let dir = get_dir(args.path).into_diagnostic()?;
     let manifest_path = dir.join(consts::PROJECT_MANIFEST);
-    let gitignore_path = dir.join(".gitignore");
     let gitattributes_path = dir.join(".gitattributes");
 
     // Check if the project file doesn't already exist. We don't want to overwrite it.
@@ -79,25 +163,53 @@ pub async fn execute(args: Args) -> miette::Result<()> {
                 dir.to_string_lossy()
             )
         })?
-        .to_string_lossy();
+        .to_string_lossy()
+        .into_owned();
     let version = "0.1.0";
     let author = get_default_author();
     let channels = if let Some(channels) = args.channels {
         channels
     } else {
-        DEFAULT_CHANNELS
-            .iter()
-            .copied()
-            .map(ToOwned::to_owned)
-            .collect()
+        Config::load_global()?.default_channels()
     };
 
     let platforms = if args.platforms.is_empty() {
-        vec![Platform::current().to_string()]
+        expand_platforms(&Config::load_global()?.default_platforms())
+            .wrap_err("invalid `default-platforms` in the global configuration")?
     } else {
-        args.platforms
+        expand_platforms(&args.platforms)?
+    };
+
+    let mut options = InitOptions {
+        name,
+        channels,
+        platforms,
+        template: args.template,
+        imports: args.imports,
+        ignore_lock_file: args.ignore_lock_file,
     };
+    if args.interactive {
+        prompt_options(&dir, &mut options)?;
+    }
 
+    // Read the files to import before anything is written.
+    let imported = options
+        .imports
+        .iter()
+        .map(|path| import_dependencies(path))
+        .collect::<miette::Result<Vec<_>>>()?;
+    for channel in imported.iter().flat_map(|imported| &imported.channels) {
+        if !options.channels.contains(channel) {
+            options.channels.push(channel.clone());
+        }
+    }
+
+    let InitOptions {
+        name,
+        channels,
+        platforms,
+        ..
+    } = &options;
     let rv = env
         .render_named_str(
             consts::PROJECT_MANIFEST,
@@ -113,22 +225,43 @@ pub async fn execute(args: Args) -> miette::Result<()> {
         .unwrap();
     fs::write(&manifest_path, rv).into_diagnostic()?;
 
-    // create a .gitignore if one is missing
-    if let Err(e) = create_or_append_file(&gitignore_path, GITIGNORE_TEMPLATE) {
-        tracing::warn!(
-            "Warning, couldn't update '{}' because of: {}",
-            gitignore_path.to_string_lossy(),
-            e
-        );
+    // Add the dependencies and tasks of the template and the imported files.
+    if options.template.is_some() || !imported.is_empty() {
+        let mut manifest = Manifest::from_path(&manifest_path)?;
+        if let Some(template) = options.template {
+            apply_template(&mut manifest, &dir, template)?;
+        }
+        for imported in &imported {
+            for spec in &imported.conda {
+                manifest.add_dependency(spec, SpecType::Run, None)?;
+            }
+            for (name, requirement) in &imported.pypi {
+                manifest.add_pypi_dependency(name, requirement, None)?;
+            }
+        }
+        manifest.save()?;
     }
 
-    // create a .gitattributes if one is missing
-    if let Err(e) = create_or_append_file(&gitattributes_path, GITATTRIBUTES_TEMPLATE) {
-        tracing::warn!(
-            "Warning, couldn't update '{}' because of: {}",
-            gitattributes_path.to_string_lossy(),
-            e
-        );
+    // Only a project in a git repository gets the git files, `pixi project gitignore sync` adds
+    // them after the repository is created.
+    let git_root = gitignore::find_git_root(&dir);
+    if git_root.is_some() {
+        if let Err(e) = gitignore::sync_file(&dir, options.ignore_lock_file) {
+            tracing::warn!(
+                "Warning, couldn't update '{}' because of: {}",
+                dir.join(".gitignore").to_string_lossy(),
+                e
+            );
+        }
+
+        // create a .gitattributes if one is missing
+        if let Err(e) = create_or_append_file(&gitattributes_path, GITATTRIBUTES_TEMPLATE) {
+            tracing::warn!(
+                "Warning, couldn't update '{}' because of: {}",
+                gitattributes_path.to_string_lossy(),
+                e
+            );
+        }
     }
 
     // Emit success
@@ -137,10 +270,257 @@ pub async fn execute(args: Args) -> miette::Result<()> {
         console::style(console::Emoji("✔ ", "")).green(),
         dir.display()
     );
+    if options.template.is_some() {
+        eprintln!("Run `pixi run start` to try it out");
+    }
+    if git_root.is_none() {
+        eprintln!(
+            "Run `pixi project gitignore sync` after `git init` to ignore the environments of pixi"
+        );
+    }
+
+    Ok(())
+}
+
+/// Asks the user for the options of the project, the current options are the defaults.
+fn prompt_options(dir: &Path, options: &mut InitOptions) -> miette::Result<()> {
+    let term = console::Term::stderr();
+    if !ci::is_interactive() {
+        miette::bail!("--interactive requires an interactive terminal and can't be used on CI");
+    }
+    let ask = |question: &str, default: &str| -> miette::Result<String> {
+        eprint!("{} [{}]: ", console::style(question).bold(), default);
+        let answer = term.read_line().into_diagnostic()?;
+        let answer = answer.trim();
+        Ok(if answer.is_empty() {
+            default.to_string()
+        } else {
+            answer.to_string()
+        })
+    };
+    let split = |answer: String| {
+        answer
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect_vec()
+    };
 
+    options.name = ask("Project name", &options.name)?;
+    options.channels = split(ask(
+        "Channels (comma separated)",
+        &options.channels.join(", "),
+    )?);
+    loop {
+        let platforms = split(ask(
+            "Platforms or presets (comma separated)",
+            &options.platforms.join(", "),
+        )?);
+        match expand_platforms(&platforms) {
+            Ok(platforms) => {
+                options.platforms = platforms;
+                break;
+            }
+            Err(err) => eprintln!("{err}"),
+        }
+    }
+    loop {
+        let default = options
+            .template
+            .and_then(|template| template.to_possible_value())
+            .map_or_else(
+                || String::from("none"),
+                |value| value.get_name().to_string(),
+            );
+        let answer = ask("Template (none, python, cpp)", &default)?;
+        if answer == "none" {
+            options.template = None;
+            break;
+        }
+        match ProjectTemplate::from_str(&answer, true) {
+            Ok(template) => {
+                options.template = Some(template);
+                break;
+            }
+            Err(_) => eprintln!("'{answer}' is not a template"),
+        }
+    }
+    options.ignore_lock_file = !ask(
+        "Commit the lock file to git? (y/n)",
+        if options.ignore_lock_file { "n" } else { "y" },
+    )?
+    .to_lowercase()
+    .starts_with('y');
+    for file in IMPORTABLE_FILES {
+        let path = dir.join(file);
+        if !path.is_file() || options.imports.contains(&path) {
+            continue;
+        }
+        if ask(&format!("Import the dependencies of {file}? (y/n)"), "y")?
+            .to_lowercase()
+            .starts_with('y')
+        {
+            options.imports.push(path);
+        }
+    }
     Ok(())
 }
 
+/// Adds the dependencies and tasks of a template to the manifest, and creates the source files of
+/// the template that don't exist yet.
+fn apply_template(
+    manifest: &mut Manifest,
+    dir: &Path,
+    template: ProjectTemplate,
+) -> miette::Result<()> {
+    let (dependencies, tasks, files): (&[&str], Vec<(&str, Task)>, &[(&str, &str)]) = match template
+    {
+        ProjectTemplate::Python => (
+            &["python 3.12.*"],
+            vec![("start", Task::Plain(String::from("python main.py")))],
+            &[("main.py", PYTHON_MAIN)],
+        ),
+        ProjectTemplate::Cpp => {
+            let task = |cmd: &str, depends_on: &str| {
+                Task::Execute(Execute {
+                    cmd: CmdArgs::Single(cmd.to_string()),
+                    depends_on: vec![depends_on.to_string()],
+                    cwd: None,
+                    inputs: Vec::new(),
+                    outputs: Vec::new(),
+                    cache_env: Vec::new(),
+                    interactive: false,
+                    expose: false,
+                    skip_if: None,
+                    only_if: None,
+                })
+            };
+            (
+                &["cmake 3.*", "ninja *", "cxx-compiler *"],
+                vec![
+                    (
+                        "configure",
+                        Task::Plain(String::from("cmake -G Ninja -S . -B .build")),
+                    ),
+                    ("build", task("cmake --build .build", "configure")),
+                    ("start", task("./.build/main", "build")),
+                ],
+                &[("CMakeLists.txt", CMAKE_LISTS), ("src/main.cpp", CPP_MAIN)],
+            )
+        }
+    };
+
+    for dependency in dependencies {
+        let spec = MatchSpec::from_str(dependency).into_diagnostic()?;
+        manifest.add_dependency(&spec, SpecType::Run, None)?;
+    }
+    for (name, task) in tasks {
+        manifest.add_task(name, task, None)?;
+    }
+    for (file, contents) in files {
+        let path = dir.join(file);
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).into_diagnostic()?;
+            }
+            fs::write(&path, contents).into_diagnostic()?;
+        }
+    }
+    Ok(())
+}
+
+/// The dependencies of an existing `environment.yml` or `requirements.txt` file.
+#[derive(Debug, Default)]
+struct ImportedDependencies {
+    channels: Vec<String>,
+    conda: Vec<MatchSpec>,
+    pypi: Vec<(rip::types::PackageName, PyPiRequirement)>,
+}
+
+/// The parts of a conda `environment.yml` file that are imported.
+#[derive(Deserialize)]
+struct EnvironmentYml {
+    #[serde(default)]
+    channels: Vec<String>,
+    #[serde(default)]
+    dependencies: Vec<EnvironmentYmlDependency>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EnvironmentYmlDependency {
+    Conda(String),
+    Pip { pip: Vec<String> },
+}
+
+/// Reads the dependencies of an `environment.yml` or a `requirements.txt` file.
+fn import_dependencies(path: &Path) -> miette::Result<ImportedDependencies> {
+    let contents = fs::read_to_string(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read '{}'", path.display()))?;
+    let is_environment_yml = path
+        .extension()
+        .map_or(false, |ext| ext == "yml" || ext == "yaml");
+    if is_environment_yml {
+        parse_environment_yml(&contents)
+    } else {
+        parse_requirements_txt(&contents)
+    }
+    .wrap_err_with(|| format!("failed to import '{}'", path.display()))
+}
+
+fn parse_environment_yml(contents: &str) -> miette::Result<ImportedDependencies> {
+    let environment: EnvironmentYml = serde_yaml::from_str(contents).into_diagnostic()?;
+    let mut imported = ImportedDependencies {
+        // `defaults` and `nodefaults` are conda configuration, not channels.
+        channels: environment
+            .channels
+            .into_iter()
+            .filter(|channel| channel != "defaults" && channel != "nodefaults")
+            .collect(),
+        ..Default::default()
+    };
+    for dependency in environment.dependencies {
+        match dependency {
+            EnvironmentYmlDependency::Conda(spec) => {
+                // Drop the channel of `channel::package` specs, the channels are imported instead.
+                let spec = spec.rsplit("::").next().unwrap_or(&spec);
+                match MatchSpec::from_str(spec) {
+                    Ok(spec) => imported.conda.push(spec),
+                    Err(err) => tracing::warn!("skipping the dependency '{spec}': {err}"),
+                }
+            }
+            EnvironmentYmlDependency::Pip { pip } => {
+                let requirements = parse_requirements_txt(&pip.join("\n"))?;
+                imported.pypi.extend(requirements.pypi);
+            }
+        }
+    }
+    Ok(imported)
+}
+
+fn parse_requirements_txt(contents: &str) -> miette::Result<ImportedDependencies> {
+    let mut imported = ImportedDependencies::default();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('-') {
+            tracing::warn!("skipping the pip option '{line}'");
+            continue;
+        }
+        let requirement = pep508_rs::Requirement::from_str(line)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("invalid requirement '{line}'"))?;
+        let name = rip::types::PackageName::from_str(&requirement.name).into_diagnostic()?;
+        imported
+            .pypi
+            .push((name, PyPiRequirement::from(requirement)));
+    }
+    Ok(imported)
+}
+
 // When the specific template is not in the file or the file does not exist.
 // Make the file and append the template to the file.
 fn create_or_append_file(path: &Path, template: &str) -> std::io::Result<()> {
@@ -176,6 +556,39 @@ fn get_dir(path: PathBuf) -> Result<PathBuf, Error> {
     }
 }
 
+/// Replaces the presets in `platforms` with the platforms they stand for, every platform is only
+/// returned once.
+fn expand_platforms(platforms: &[String]) -> miette::Result<Vec<String>> {
+    let mut expanded = Vec::new();
+    for platform in platforms {
+        let preset = PLATFORM_PRESETS
+            .iter()
+            .find(|(name, _)| *name == platform.as_str());
+        let platforms = if platform == "current" {
+            vec![Platform::current()]
+        } else if let Some((_, platforms)) = preset {
+            platforms.to_vec()
+        } else {
+            vec![Platform::from_str(platform).map_err(|_| {
+                miette::miette!(
+                    help = format!(
+                        "use the name of a platform, or one of the presets current, {}",
+                        PLATFORM_PRESETS.iter().map(|(name, _)| name).join(", ")
+                    ),
+                    "'{platform}' is not a known platform"
+                )
+            })?]
+        };
+        for platform in platforms {
+            let platform = platform.to_string();
+            if !expanded.contains(&platform) {
+                expanded.push(platform);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -240,4 +653,71 @@ mod tests {
 
         dir.close().unwrap();
     }
+
+    #[test]
+    fn test_parse_environment_yml() {
+        let imported = parse_environment_yml(
+            r#"
+name: example
+channels:
+  - conda-forge
+  - defaults
+dependencies:
+  - python=3.11
+  - conda-forge::numpy>=1.26
+  - pip
+  - pip:
+    - requests[socks]>=2.31
+"#,
+        )
+        .unwrap();
+        assert_eq!(imported.channels, vec!["conda-forge"]);
+        assert_eq!(
+            imported
+                .conda
+                .iter()
+                .map(|spec| spec.name.as_ref().unwrap().as_normalized().to_string())
+                .collect_vec(),
+            vec!["python", "numpy", "pip"]
+        );
+        assert_eq!(imported.pypi.len(), 1);
+        assert_eq!(imported.pypi[0].0.as_str(), "requests");
+    }
+
+    #[test]
+    fn test_parse_requirements_txt() {
+        let imported = parse_requirements_txt(
+            "# comment\n--index-url https://example.com/simple\nflask>=3.0 # web\n\npytest\n",
+        )
+        .unwrap();
+        assert_eq!(
+            imported
+                .pypi
+                .iter()
+                .map(|(name, requirement)| format!("{} = {requirement}", name.as_str()))
+                .collect_vec(),
+            vec!["flask = \">=3.0\"", "pytest = \"*\""]
+        );
+        assert!(parse_requirements_txt("not a requirement!").is_err());
+    }
+
+    #[test]
+    fn test_expand_platforms() {
+        let platforms = |values: &[&str]| {
+            expand_platforms(&values.iter().map(ToString::to_string).collect_vec())
+        };
+        assert_eq!(
+            platforms(&["ci-default", "linux-aarch64", "linux-64"]).unwrap(),
+            vec!["linux-64", "osx-arm64", "win-64", "linux-aarch64"]
+        );
+        assert_eq!(
+            platforms(&["all-desktop"]).unwrap(),
+            vec!["linux-64", "osx-64", "osx-arm64", "win-64"]
+        );
+        assert_eq!(
+            platforms(&["current"]).unwrap(),
+            vec![Platform::current().to_string()]
+        );
+        assert!(platforms(&["all-mobile"]).is_err());
+    }
 }
@@ -1,29 +1,188 @@
-use crate::environment::get_up_to_date_prefix;
+use crate::environment::{get_filtered_prefix, InstallFilter, LockFileUsage};
+use crate::progress::{default_progress_style, global_multi_progress, ProgressBarMessageFormatter};
 use crate::Project;
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use indicatif::ProgressBar;
+use itertools::Itertools;
+use miette::WrapErr;
+use rattler_conda_types::Platform;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Install all dependencies
 #[derive(Parser, Debug)]
 pub struct Args {
-    /// The path to 'pixi.toml'
+    /// The path to 'pixi.toml' or the directory that contains it
     #[arg(long)]
     pub manifest_path: Option<PathBuf>,
 
+    /// The environment to use, one for every variant of the dependencies of the project or every
+    /// environment of the `[environments]` table
+    #[arg(long, short)]
+    pub environment: Option<String>,
+
+    /// Install all environments of the project at the same time
+    #[arg(long, conflicts_with = "environment")]
+    pub all: bool,
+
+    /// The platform to install the environment for, e.g. `osx-64` to run it through Rosetta on
+    /// Apple Silicon. Defaults to the platform of this machine
+    #[arg(long)]
+    pub platform: Option<Platform>,
+
+    /// Only install these groups of dependencies, the other groups are left as they are
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "skip")]
+    pub only: Vec<DependencyGroup>,
+
+    /// Don't install these groups of dependencies, they are left as they are
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub skip: Vec<DependencyGroup>,
+
     #[clap(flatten)]
     pub lock_file_usage: super::LockFileUsageArgs,
 }
 
+/// A group of dependencies that can be installed separately.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyGroup {
+    /// The conda packages of the `dependencies` tables
+    #[value(alias = "dependencies")]
+    Conda,
+
+    /// The packages of the `pypi-dependencies` tables
+    #[value(alias = "pypi-dependencies")]
+    Pypi,
+}
+
+/// Returns which groups of dependencies are installed for `--only` and `--skip`.
+fn install_filter(only: &[DependencyGroup], skip: &[DependencyGroup]) -> InstallFilter {
+    let installs = |group| (only.is_empty() || only.contains(&group)) && !skip.contains(&group);
+    InstallFilter {
+        conda: installs(DependencyGroup::Conda),
+        pypi: installs(DependencyGroup::Pypi),
+    }
+}
+
+/// Installs all environments of the project at the same time, with a progress bar that shows the
+/// environments that are still being installed. The installs share the package cache, so packages
+/// that several environments need are only downloaded and extracted once.
+async fn install_all(
+    project: &Project,
+    usage: LockFileUsage,
+    filter: InstallFilter,
+) -> miette::Result<()> {
+    let environments = project.environments();
+    let pb = global_multi_progress().add(
+        ProgressBar::new(environments.len() as u64)
+            .with_style(default_progress_style())
+            .with_prefix("environments"),
+    );
+    pb.enable_steady_tick(Duration::from_millis(100));
+    let formatter = ProgressBarMessageFormatter::new(pb.clone());
+
+    let installs = environments.iter().map(|name| {
+        let formatter = &formatter;
+        let pb = &pb;
+        async move {
+            let project = project.clone().with_environment(Some(name))?;
+            formatter
+                .wrap(
+                    name.clone(),
+                    get_filtered_prefix(&project, usage, false, filter, None),
+                )
+                .await
+                .wrap_err_with(|| format!("failed to install the environment '{name}'"))?;
+            pb.inc(1);
+            Ok::<_, miette::Report>(())
+        }
+    });
+
+    // Every install runs to completion, cancelling one halfway would only leave a transaction that
+    // has to be rolled back.
+    let results = futures::future::join_all(installs).await;
+    pb.finish_and_clear();
+    results.into_iter().collect()
+}
+
 pub async fn execute(args: Args) -> miette::Result<()> {
-    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?
+        .with_platform(args.platform)?;
 
-    get_up_to_date_prefix(&project, args.lock_file_usage.into(), false, None).await?;
+    let filter = install_filter(&args.only, &args.skip);
+    let all = args.all && !project.environments().is_empty();
+    if all {
+        install_all(&project, args.lock_file_usage.into(), filter).await?;
+    } else {
+        let project = project.with_environment(args.environment.as_deref())?;
+        get_filtered_prefix(&project, args.lock_file_usage.into(), false, filter, None).await?;
+    }
 
     // Emit success
-    eprintln!(
-        "{}Project in {} is ready to use!",
-        console::style(console::Emoji("✔ ", "")).green(),
-        project.root().display()
-    );
+    if filter == InstallFilter::default() {
+        if all {
+            eprintln!(
+                "{}The {} environments of the project in {} are ready to use!",
+                console::style(console::Emoji("✔ ", "")).green(),
+                project.environments().len(),
+                project.root().display()
+            );
+        } else {
+            eprintln!(
+                "{}Project in {} is ready to use!",
+                console::style(console::Emoji("✔ ", "")).green(),
+                project.root().display()
+            );
+        }
+    } else {
+        let groups = [(filter.conda, "conda"), (filter.pypi, "pypi")]
+            .into_iter()
+            .filter_map(|(installed, name)| installed.then_some(name))
+            .collect_vec();
+        eprintln!(
+            "{}Installed the {} dependencies of the project in {}",
+            console::style(console::Emoji("✔ ", "")).green(),
+            if groups.is_empty() {
+                String::from("no")
+            } else {
+                groups.join(" and ")
+            },
+            project.root().display()
+        );
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_filter() {
+        assert_eq!(install_filter(&[], &[]), InstallFilter::default());
+        assert_eq!(
+            install_filter(&[DependencyGroup::Conda], &[]),
+            InstallFilter {
+                conda: true,
+                pypi: false
+            }
+        );
+        assert_eq!(
+            install_filter(&[], &[DependencyGroup::Pypi]),
+            InstallFilter {
+                conda: true,
+                pypi: false
+            }
+        );
+        assert_eq!(
+            install_filter(&[DependencyGroup::Pypi], &[]),
+            InstallFilter {
+                conda: false,
+                pypi: true
+            }
+        );
+        assert_eq!(
+            DependencyGroup::from_str("dependencies", false),
+            Ok(DependencyGroup::Conda)
+        );
+    }
+}
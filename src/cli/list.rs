@@ -0,0 +1,194 @@
+use crate::cli::OutputFormat;
+use crate::lock_file::load_lock_file;
+use crate::utils::spec_format::{locked_name, normalize_pypi_name, package_kind};
+use crate::Project;
+use clap::Parser;
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use rattler_conda_types::Platform;
+use rattler_lock::LockedDependencyKind;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+/// List the locked packages of the project environment.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// The platform to list the packages for, defaults to the current platform
+    #[arg(long, short)]
+    pub platform: Option<Platform>,
+
+    /// The format of the output
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+/// A locked package of the environment.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct ListedPackage {
+    /// The name of the package.
+    pub name: String,
+
+    /// The locked version of the package.
+    pub version: String,
+
+    /// The build string of conda packages.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<String>,
+
+    /// Either `conda` or `pypi`.
+    pub kind: &'static str,
+
+    /// Whether the package is a dependency in the manifest, instead of a dependency of one.
+    pub explicit: bool,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+    let platform = args.platform.unwrap_or_else(Platform::current);
+    if !project.platforms().contains(&platform) {
+        miette::bail!("the project does not support the platform {platform}");
+    }
+    let lock_file = load_lock_file(&project).await?;
+
+    let explicit = project
+        .all_dependencies(platform)
+        .keys()
+        .map(|name| ("conda", name.as_normalized().to_string()))
+        .chain(
+            project
+                .pypi_dependencies(platform)
+                .keys()
+                .map(|name| ("pypi", normalize_pypi_name(name.as_source_str()))),
+        )
+        .collect::<HashSet<_>>();
+
+    let packages = lock_file
+        .package
+        .iter()
+        .filter(|package| package.platform == platform)
+        .map(|package| ListedPackage {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            build: match &package.kind {
+                LockedDependencyKind::Conda(conda) => conda.build.clone(),
+                LockedDependencyKind::Pypi(_) => None,
+            },
+            kind: package_kind(package),
+            explicit: explicit.contains(&(package_kind(package), locked_name(package))),
+        })
+        .sorted_by(|a, b| (&a.name, a.kind).cmp(&(&b.name, b.kind)))
+        .collect_vec();
+
+    match args.format {
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&packages).into_diagnostic()?
+        ),
+        OutputFormat::Markdown => print!("{}", markdown_table(project.name(), platform, &packages)),
+        OutputFormat::Table => {
+            if packages.is_empty() {
+                eprintln!(
+                    "No packages are locked for {platform}, run `pixi install` to lock the environment"
+                );
+            }
+            print_table(&packages);
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the packages as aligned columns, the explicit dependencies are highlighted.
+fn print_table(packages: &[ListedPackage]) {
+    let name_width = packages
+        .iter()
+        .map(|p| p.name.len())
+        .max()
+        .unwrap_or_default();
+    let version_width = packages
+        .iter()
+        .map(|p| p.version.len())
+        .max()
+        .unwrap_or_default();
+    let build_width = packages
+        .iter()
+        .map(|p| p.build.as_deref().unwrap_or_default().len())
+        .max()
+        .unwrap_or_default();
+
+    for package in packages {
+        let name = format!("{:name_width$}", package.name);
+        println!(
+            "{} {:version_width$} {:build_width$} {}",
+            if package.explicit {
+                console::style(name).bold()
+            } else {
+                console::style(name)
+            },
+            package.version,
+            package.build.as_deref().unwrap_or_default(),
+            package.kind,
+        );
+    }
+}
+
+/// Renders the packages as a GitHub flavored markdown table, the explicit dependencies are bold.
+fn markdown_table(project_name: &str, platform: Platform, packages: &[ListedPackage]) -> String {
+    let mut markdown = format!(
+        "### Packages of `{project_name}` ({platform})\n\n{} packages, {} explicit dependencies\n\n",
+        packages.len(),
+        packages.iter().filter(|package| package.explicit).count()
+    );
+    markdown.push_str("| Package | Version | Build | Kind |\n| --- | --- | --- | --- |\n");
+    for package in packages {
+        let name = if package.explicit {
+            format!("**{}**", package.name)
+        } else {
+            package.name.clone()
+        };
+        markdown.push_str(&format!(
+            "| {name} | {} | {} | {} |\n",
+            package.version,
+            package.build.as_deref().unwrap_or_default(),
+            package.kind
+        ));
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_table() {
+        let packages = [
+            ListedPackage {
+                name: String::from("numpy"),
+                version: String::from("1.26.2"),
+                build: Some(String::from("py311h64a7726_0")),
+                kind: "conda",
+                explicit: true,
+            },
+            ListedPackage {
+                name: String::from("requests"),
+                version: String::from("2.31.0"),
+                build: None,
+                kind: "pypi",
+                explicit: false,
+            },
+        ];
+        assert_eq!(
+            markdown_table("foo", Platform::Linux64, &packages),
+            "### Packages of `foo` (linux-64)\n\n2 packages, 1 explicit dependencies\n\n\
+             | Package | Version | Build | Kind |\n| --- | --- | --- | --- |\n\
+             | **numpy** | 1.26.2 | py311h64a7726_0 | conda |\n\
+             | requests | 2.31.0 |  | pypi |\n"
+        );
+    }
+}
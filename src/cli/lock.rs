@@ -0,0 +1,167 @@
+use crate::lock_file::{self, load_lock_file, merge_lock_files, ResolveReport};
+use crate::Project;
+use clap::Parser;
+use itertools::Itertools;
+use rattler_conda_types::Platform;
+use std::path::PathBuf;
+
+/// Solve the dependencies of the project and update the lock file without installing anything.
+///
+/// Platforms that cannot be solved on this machine can be solved on another machine with
+/// `--platform` and `--output`, and merged into the lock file afterwards with `--merge`.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// The environment to use, one for every variant of the dependencies of the project or every
+    /// environment of the `[environments]` table
+    #[arg(long, short)]
+    pub environment: Option<String>,
+
+    /// Only solve the given platforms, the packages of the other platforms are kept
+    #[arg(long, short, conflicts_with = "merge")]
+    pub platform: Vec<Platform>,
+
+    /// Write the solved platforms to this file instead of updating the lock file of the project
+    #[arg(long, short, conflicts_with = "merge")]
+    pub output: Option<PathBuf>,
+
+    /// Merge lock files that were created with `--platform` and `--output` into the lock file of
+    /// the project
+    #[arg(long, num_args = 1..)]
+    pub merge: Vec<PathBuf>,
+
+    /// Print the inputs that influenced the solve of every platform of the lock file, like the
+    /// repodata, the virtual packages and the solver, without solving again
+    #[arg(long, conflicts_with_all = ["platform", "output", "merge"])]
+    pub explain: bool,
+
+    /// Sign the lock file with a minisign secret key, the signature is written next to it, e.g.
+    /// `pixi.lock.minisig`
+    #[arg(long, conflicts_with = "explain")]
+    pub sign: bool,
+
+    /// The minisign secret key to sign the lock file with, defaults to
+    /// `~/.minisign/minisign.key`. The password of an encrypted key is read from
+    /// `PIXI_SIGNING_KEY_PASSWORD` or asked for
+    #[arg(long, requires = "sign")]
+    pub secret_key: Option<PathBuf>,
+}
+
+/// Prints the resolve report of the lock file of the project.
+fn explain(project: &Project) -> miette::Result<()> {
+    let lock_file_path = project.lock_file_path();
+    if !lock_file_path.is_file() {
+        miette::bail!(
+            help = "run `pixi lock` to create it",
+            "'{}' does not exist",
+            lock_file_path.display()
+        );
+    }
+    match ResolveReport::read(&lock_file::read_lock_file(&lock_file_path)?) {
+        Some(report) => print!("{report}"),
+        None => eprintln!(
+            "{} contains no resolve report, it was created by an older version of pixi. Run `pixi lock` to record one.",
+            lock_file_path.display()
+        ),
+    }
+    Ok(())
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?
+        .with_environment(args.environment.as_deref())?;
+    if args.explain {
+        return explain(&project);
+    }
+    let existing_lock_file = load_lock_file(&project).await?;
+
+    let (lock_file, output, platforms) = if args.merge.is_empty() {
+        let platforms = if args.platform.is_empty() {
+            project.platforms().to_vec()
+        } else {
+            args.platform
+        };
+        if let Some(platform) = platforms
+            .iter()
+            .find(|platform| !project.platforms().contains(platform))
+        {
+            miette::bail!("'{platform}' is not a platform of the project");
+        }
+
+        // Solve the requested platforms.
+        let (mut solved, member_locks) = lock_file::solve_conda_with_members(
+            &project,
+            &existing_lock_file,
+            None,
+            &platforms,
+            &[],
+        )
+        .await?;
+        if project.has_pypi_dependencies() {
+            solved = lock_file::solve_pypi(&project, solved, &platforms).await?;
+        }
+
+        match args.output {
+            // Only the solved platforms are written to the output.
+            Some(output) => (solved, output, platforms),
+            None => {
+                // The other environments of the solve group are kept at the same versions.
+                lock_file::write_member_lock_files(&member_locks)?;
+                let merged = merge_lock_files(
+                    &project,
+                    existing_lock_file,
+                    vec![(project.lock_file_path(), solved)],
+                )?;
+                (merged, project.lock_file_path(), platforms)
+            }
+        }
+    } else {
+        let partial = args
+            .merge
+            .into_iter()
+            .map(|path| {
+                let lock_file = lock_file::read_lock_file(&path)?;
+                Ok((path, lock_file))
+            })
+            .collect::<miette::Result<Vec<_>>>()?;
+        let platforms = partial
+            .iter()
+            .flat_map(|(_, lock_file)| lock_file.metadata.platforms.iter().copied())
+            .unique()
+            .collect_vec();
+        let merged = merge_lock_files(&project, existing_lock_file, partial)?;
+        (merged, project.lock_file_path(), platforms)
+    };
+
+    lock_file::write_lock_file(&project, &lock_file, &output)?;
+
+    eprintln!(
+        "{}Locked {} in {}",
+        console::style(console::Emoji("✔ ", "")).green(),
+        platforms
+            .iter()
+            .map(|platform| console::style(platform).bold())
+            .join(", "),
+        output.display()
+    );
+
+    if args.sign {
+        let Some(secret_key) = args.secret_key.or_else(lock_file::default_secret_key_path) else {
+            miette::bail!(
+                "could not determine the home directory, pass the key with `--secret-key`"
+            );
+        };
+        let signature = lock_file::sign_lock_file(&output, &secret_key)?;
+        eprintln!(
+            "{}Signed {} in {}",
+            console::style(console::Emoji("✔ ", "")).green(),
+            output.display(),
+            signature.display()
+        );
+    }
+
+    Ok(())
+}
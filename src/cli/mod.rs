@@ -1,26 +1,52 @@
 use super::util::IndicatifWriter;
-use crate::progress;
-use clap::Parser;
+use crate::chrome_trace::ChromeTraceLayer;
+use crate::concurrency::{self, ConcurrencyOverrides};
+use crate::{alias, ci, progress, warnings};
+use clap::{CommandFactory, Parser};
 use clap_complete;
 use clap_verbosity_flag::Verbosity;
 use miette::IntoDiagnostic;
+use std::ffi::OsString;
 use std::io::IsTerminal;
-use tracing_subscriber::{filter::LevelFilter, util::SubscriberInitExt, EnvFilter};
+use std::path::PathBuf;
+use tracing_subscriber::{
+    filter::{filter_fn, LevelFilter},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
 
 pub mod add;
+pub mod archive;
 pub mod auth;
+pub mod build;
+pub mod cache;
+pub mod channel;
+pub mod clean;
 pub mod completion;
+pub mod config;
+pub mod diff;
 pub mod global;
 pub mod info;
 pub mod init;
 pub mod install;
+pub mod list;
+pub mod lock;
+pub mod outdated;
+mod pipeline;
 pub mod project;
 pub mod remove;
 pub mod run;
 pub mod search;
 pub mod shell;
+pub mod snapshot;
+pub mod start;
 pub mod task;
+pub mod tree;
+pub mod trust;
+pub mod update;
 pub mod upload;
+pub mod verify;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -37,6 +63,33 @@ struct Args {
     /// Whether the log needs to be colored.
     #[clap(long, default_value = "auto", global = true)]
     color: ColorOutput,
+
+    /// Fail the command if any warning was emitted, except for the warnings that the manifest
+    /// allows.
+    #[clap(long, global = true)]
+    deny_warnings: bool,
+
+    /// The number of platforms that are solved at the same time
+    #[clap(long, global = true)]
+    max_concurrent_solves: Option<usize>,
+
+    /// The number of packages that are downloaded at the same time
+    #[clap(long, global = true)]
+    max_concurrent_downloads: Option<usize>,
+
+    /// The number of packages that are linked into an environment at the same time
+    #[clap(long, global = true)]
+    max_concurrent_link_jobs: Option<usize>,
+
+    /// Write a trace of the command to this file in the Chrome trace event format, which can be
+    /// opened in Perfetto or `chrome://tracing`
+    #[clap(long, global = true)]
+    trace_file: Option<PathBuf>,
+
+    /// Behave like on CI: hide progress bars and never prompt. Enabled automatically when a CI
+    /// environment is detected, e.g. on GitHub Actions or GitLab CI
+    #[clap(long, global = true)]
+    ci: bool,
 }
 
 /// Generates a completion script for a shell.
@@ -55,18 +108,34 @@ pub enum Command {
     Add(add::Args),
     #[clap(alias = "r")]
     Run(run::Args),
+    Start(start::Args),
     #[clap(alias = "s")]
     Shell(shell::Args),
     #[clap(alias = "g")]
     Global(global::Args),
     Auth(auth::Args),
+    Build(build::Args),
+    Cache(cache::Args),
+    Channel(channel::Args),
+    Clean(clean::Args),
     #[clap(alias = "i")]
     Install(install::Args),
+    Lock(lock::Args),
+    List(list::Args),
+    Tree(tree::Args),
+    Diff(diff::Args),
     Task(task::Args),
+    Update(update::Args),
+    Outdated(outdated::Args),
+    Snapshot(snapshot::Args),
     Info(info::Args),
     Upload(upload::Args),
     Search(search::Args),
     Project(project::Args),
+    Config(config::Args),
+    Trust(trust::Args),
+    Verify(verify::Args),
+    Archive(archive::Args),
     #[clap(alias = "rm")]
     Remove(remove::Args),
 }
@@ -95,10 +164,35 @@ impl From<LockFileUsageArgs> for crate::environment::LockFileUsage {
     }
 }
 
+/// Returns the arguments of the process with an alias in place of the command expanded.
+fn args_with_aliases() -> miette::Result<Vec<OsString>> {
+    let args = std::env::args_os().collect::<Vec<_>>();
+    let command = Args::command();
+    let is_command = |name: &str| name == "help" || command.find_subcommand(name).is_some();
+    let takes_value = |option: &str| {
+        command.get_arguments().any(|arg| {
+            let long = arg.get_long().map(|long| format!("--{long}"));
+            let short = arg.get_short().map(|short| format!("-{short}"));
+            arg.get_action().takes_values()
+                && (long.as_deref() == Some(option) || short.as_deref() == Some(option))
+        })
+    };
+    let maybe_alias = alias::command_position(&args, takes_value)
+        .and_then(|position| args[position].to_str())
+        .map_or(false, |name| !is_command(name));
+    if !maybe_alias {
+        return Ok(args);
+    }
+    alias::expand_aliases(args, &alias::load_aliases()?, is_command, takes_value)
+}
+
 pub async fn execute() -> miette::Result<()> {
-    let args = Args::parse();
+    let args = Args::parse_from(args_with_aliases()?);
     let use_colors = use_color_output(&args);
 
+    // Must happen before the progress bars are created
+    ci::init(args.ci);
+
     // Setup the default miette handler based on whether or not we want colors or not.
     miette::set_hook(Box::new(move |_| {
         Box::new(
@@ -134,18 +228,49 @@ pub async fn execute() -> miette::Result<()> {
                 .into_diagnostic()?,
         );
 
+    // Only spans are written to the trace file, the verbosity of the log does not change which
+    // spans are recorded.
+    let (trace_layer, _trace_guard) = match &args.trace_file {
+        Some(path) => {
+            let (layer, guard) = ChromeTraceLayer::create(path)?;
+            let layer = layer.with_filter(filter_fn(|metadata| {
+                metadata.is_span() && *metadata.level() <= tracing::Level::DEBUG
+            }));
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+
     // Setup the tracing subscriber
-    tracing_subscriber::fmt()
-        .with_ansi(use_colors)
-        .with_env_filter(env_filter)
-        .with_writer(IndicatifWriter::new(progress::global_multi_progress()))
-        .without_time()
-        .finish()
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(use_colors)
+                .with_writer(IndicatifWriter::new(progress::global_multi_progress()))
+                .without_time()
+                .with_filter(env_filter),
+        )
+        .with(trace_layer)
         .try_init()
         .into_diagnostic()?;
 
+    if let Some(provider) = ci::provider() {
+        tracing::info!("running on CI ({provider}), progress bars and prompts are disabled");
+    }
+
+    concurrency::init(ConcurrencyOverrides {
+        solves: args.max_concurrent_solves,
+        downloads: args.max_concurrent_downloads,
+        link_jobs: args.max_concurrent_link_jobs,
+    });
+
     // Execute the command
-    execute_command(args.command).await
+    execute_command(args.command).await?;
+
+    if args.deny_warnings {
+        warnings::deny_warnings()?;
+    }
+    Ok(())
 }
 
 /// Execute the actual command
@@ -155,15 +280,31 @@ pub async fn execute_command(command: Command) -> miette::Result<()> {
         Command::Init(cmd) => init::execute(cmd).await,
         Command::Add(cmd) => add::execute(cmd).await,
         Command::Run(cmd) => run::execute(cmd).await,
+        Command::Start(cmd) => start::execute(cmd).await,
         Command::Global(cmd) => global::execute(cmd).await,
         Command::Auth(cmd) => auth::execute(cmd).await,
+        Command::Build(cmd) => build::execute(cmd).await,
+        Command::Cache(cmd) => cache::execute(cmd).await,
+        Command::Channel(cmd) => channel::execute(cmd).await,
+        Command::Clean(cmd) => clean::execute(cmd).await,
         Command::Install(cmd) => install::execute(cmd).await,
+        Command::Lock(cmd) => lock::execute(cmd).await,
+        Command::List(cmd) => list::execute(cmd).await,
+        Command::Tree(cmd) => tree::execute(cmd).await,
+        Command::Diff(cmd) => diff::execute(cmd).await,
         Command::Shell(cmd) => shell::execute(cmd).await,
         Command::Task(cmd) => task::execute(cmd),
+        Command::Update(cmd) => update::execute(cmd).await,
+        Command::Outdated(cmd) => outdated::execute(cmd).await,
+        Command::Snapshot(cmd) => snapshot::execute(cmd).await,
         Command::Info(cmd) => info::execute(cmd).await,
         Command::Upload(cmd) => upload::execute(cmd).await,
         Command::Search(cmd) => search::execute(cmd).await,
         Command::Project(cmd) => project::execute(cmd).await,
+        Command::Config(cmd) => config::execute(cmd).await,
+        Command::Trust(cmd) => trust::execute(cmd).await,
+        Command::Verify(cmd) => verify::execute(cmd).await,
+        Command::Archive(cmd) => archive::execute(cmd).await,
         Command::Remove(cmd) => remove::execute(cmd).await,
     }
 }
@@ -180,6 +321,20 @@ pub enum ColorOutput {
     Auto,
 }
 
+/// The format in which commands print information about the environment.
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A table that is readable in the terminal.
+    #[default]
+    Table,
+
+    /// JSON, to be processed by other tools.
+    Json,
+
+    /// A GitHub flavored markdown table, e.g. to post as a comment on a pull request.
+    Markdown,
+}
+
 /// Returns true if the output is considered to be a terminal.
 fn is_terminal() -> bool {
     std::io::stderr().is_terminal()
@@ -0,0 +1,82 @@
+use crate::cli::update::{diff_lock_files, solve_updates, PackageUpdate};
+use crate::end_of_life::{end_of_life_list, find_end_of_life_packages};
+use crate::lock_file::load_lock_file;
+use crate::utils::spec_format;
+use crate::Project;
+use clap::Parser;
+use miette::IntoDiagnostic;
+use std::path::PathBuf;
+
+/// Show the locked packages for which newer versions are allowed by the manifest, or with `--eol`
+/// the locked packages that are end-of-life.
+///
+/// Neither the lock file nor the environment is modified.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Show the locked packages that are end-of-life or deprecated according to the channels and
+    /// the `[end-of-life]` table of the manifest
+    #[arg(long)]
+    pub eol: bool,
+
+    /// Output the packages as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+    let lock_file = load_lock_file(&project).await?;
+
+    if args.eol {
+        let packages = find_end_of_life_packages(&lock_file, &end_of_life_list(&project).await);
+        if args.json {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&packages).into_diagnostic()?
+            );
+        } else if packages.is_empty() {
+            eprintln!(
+                "{}No locked package is end-of-life",
+                console::style(console::Emoji("✔ ", "")).green()
+            );
+        } else {
+            for package in &packages {
+                println!("{} {package}", console::style("!").yellow().bold());
+            }
+        }
+        return Ok(());
+    }
+
+    let (updated_lock_file, _) = solve_updates(&project, &lock_file, &[], &[]).await?;
+    let outdated: Vec<PackageUpdate> = diff_lock_files(&lock_file, &updated_lock_file)
+        .into_iter()
+        .filter(|update| update.from.is_some() && update.to.is_some())
+        .collect();
+
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&outdated).into_diagnostic()?
+        );
+    } else if outdated.is_empty() {
+        eprintln!(
+            "{}All packages are up to date",
+            console::style(console::Emoji("✔ ", "")).green()
+        );
+    } else {
+        for update in &outdated {
+            println!(
+                "{}: {}",
+                spec_format::locked_package(&update.name, update.kind, update.platform),
+                spec_format::version_change(update.from.as_deref(), update.to.as_deref()),
+            );
+        }
+        eprintln!("Run `pixi update` to update the lock file");
+    }
+
+    Ok(())
+}
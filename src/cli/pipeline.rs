@@ -0,0 +1,244 @@
+//! Runs the pipelines of the project with `pixi run pipeline <name>`.
+
+use crate::cli::run::run_task_args;
+use crate::environment::LockFileUsage;
+use crate::project::manifest::{Stage, MATRIX_ENVIRONMENT_KEY};
+use crate::Project;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use std::collections::HashMap;
+use std::path::{Component, Path};
+
+/// The directory inside `.pixi` that stores the artifacts of the pipelines.
+const PIPELINES_DIR: &str = "pipelines";
+
+/// Runs the stages of a pipeline in order, every stage runs its task once for every combination of
+/// its matrix. Exits the process with the exit code of the task if a stage fails.
+pub async fn execute(
+    project: &Project,
+    args: &[String],
+    lock_file_usage: LockFileUsage,
+    pty: bool,
+) -> miette::Result<()> {
+    let [name] = args else {
+        let pipelines = project.pipelines();
+        return Err(miette::miette!(
+            help = if pipelines.is_empty() {
+                String::from("add a pipeline to the [pipelines] table of the manifest")
+            } else {
+                format!(
+                    "the pipelines of the project are:\n{}",
+                    pipelines
+                        .iter()
+                        .map(|(name, pipeline)| match &pipeline.description {
+                            Some(description) => format!("  {name}: {description}"),
+                            None => format!("  {name}"),
+                        })
+                        .join("\n")
+                )
+            },
+            "expected the name of a single pipeline, e.g. `pixi run pipeline ci`"
+        ));
+    };
+    let Some(pipeline) = project.pipelines().get(name) else {
+        miette::bail!("the project has no pipeline named '{name}'");
+    };
+    let stages = pipeline
+        .stage_order()
+        .map_err(|err| miette::miette!("invalid pipeline '{name}': {err}"))?;
+
+    let artifacts_dir = project.pixi_dir().join(PIPELINES_DIR).join(name);
+    for stage_name in stages {
+        let stage = &pipeline.stages[stage_name];
+        for matrix in stage.matrix_combinations() {
+            let label = stage_label(stage_name, &matrix);
+            eprintln!(
+                "{}{}",
+                console::style("🚀 Pixi stage: ").bold(),
+                console::style(&label).cyan()
+            );
+
+            let environment = matrix
+                .get(MATRIX_ENVIRONMENT_KEY)
+                .copied()
+                .or(stage.environment.as_deref());
+            let stage_project = match environment {
+                Some(environment) => project.clone().with_environment(Some(environment))?,
+                None => project.clone(),
+            };
+            let task_args = shlex::split(&stage.task).ok_or_else(|| {
+                miette::miette!("could not split the task of the stage '{stage_name}'")
+            })?;
+            let env = stage_env(name, stage_name, &matrix, &artifacts_dir);
+
+            let code = run_task_args(&stage_project, task_args, lock_file_usage, pty, env).await?;
+            if code != 0 {
+                eprintln!(
+                    "{} The stage {} of the pipeline '{name}' failed with exit code {code}",
+                    console::style("✘").red().bold(),
+                    console::style(&label).bold()
+                );
+                std::process::exit(code);
+            }
+
+            let stage_artifacts_dir = artifacts_dir.join(artifacts_dir_name(stage_name, &matrix));
+            collect_artifacts(project.root(), stage, &stage_artifacts_dir)?;
+        }
+    }
+
+    eprintln!(
+        "{}The pipeline '{name}' succeeded",
+        console::style(console::Emoji("✔ ", "")).green()
+    );
+    if pipeline
+        .stages
+        .values()
+        .any(|stage| !stage.artifacts.is_empty())
+    {
+        eprintln!("The artifacts are stored in {}", artifacts_dir.display());
+    }
+    Ok(())
+}
+
+/// Returns the name of a stage with the values of its matrix, e.g. `test (cpu, 3.11)`.
+fn stage_label(stage_name: &str, matrix: &IndexMap<&str, &str>) -> String {
+    if matrix.is_empty() {
+        stage_name.to_string()
+    } else {
+        format!("{stage_name} ({})", matrix.values().join(", "))
+    }
+}
+
+/// Returns the name of the directory with the artifacts of a stage, e.g. `test-cpu-3.11`.
+fn artifacts_dir_name(stage_name: &str, matrix: &IndexMap<&str, &str>) -> String {
+    std::iter::once(stage_name)
+        .chain(matrix.values().copied())
+        .join("-")
+}
+
+/// Returns the variables that describe the stage to its task. The values of the matrix are
+/// available as `PIXI_MATRIX_<KEY>`.
+fn stage_env(
+    pipeline_name: &str,
+    stage_name: &str,
+    matrix: &IndexMap<&str, &str>,
+    artifacts_dir: &Path,
+) -> HashMap<String, String> {
+    let mut env = HashMap::from([
+        (String::from("PIXI_PIPELINE"), pipeline_name.to_string()),
+        (String::from("PIXI_STAGE"), stage_name.to_string()),
+        (
+            String::from("PIXI_ARTIFACTS_DIR"),
+            artifacts_dir.to_string_lossy().to_string(),
+        ),
+    ]);
+    for (key, value) in matrix {
+        let key = key.to_uppercase().replace(['-', '.'], "_");
+        env.insert(format!("PIXI_MATRIX_{key}"), value.to_string());
+    }
+    env
+}
+
+/// Copies the artifacts of a stage into its directory, the artifacts of a previous run are removed.
+fn collect_artifacts(root: &Path, stage: &Stage, destination: &Path) -> miette::Result<()> {
+    if destination.exists() {
+        std::fs::remove_dir_all(destination).into_diagnostic()?;
+    }
+    for artifact in &stage.artifacts {
+        // The artifact is stored at the same path in the destination, so a path outside of the
+        // project would also be copied to a path outside of the artifacts directory.
+        if artifact
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            miette::bail!(
+                help = "use a path relative to the root of the project",
+                "the artifact '{}' is not inside the project",
+                artifact.display()
+            );
+        }
+        let source = root.join(artifact);
+        if !source.exists() {
+            miette::bail!(
+                "the artifact '{}' does not exist after the stage ran",
+                artifact.display()
+            );
+        }
+        copy_recursively(&source, &destination.join(artifact))
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to store the artifact '{}'", artifact.display()))?;
+    }
+    Ok(())
+}
+
+/// Copies a file, or a directory with all its contents.
+fn copy_recursively(source: &Path, destination: &Path) -> std::io::Result<()> {
+    if source.is_dir() {
+        std::fs::create_dir_all(destination)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+    } else {
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(source, destination)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_env() {
+        let matrix = IndexMap::from_iter([("environment", "cpu"), ("python-version", "3.11")]);
+        assert_eq!(stage_label("test", &matrix), "test (cpu, 3.11)");
+        assert_eq!(artifacts_dir_name("test", &matrix), "test-cpu-3.11");
+        assert_eq!(stage_label("lint", &IndexMap::new()), "lint");
+
+        let env = stage_env(
+            "ci",
+            "test",
+            &matrix,
+            Path::new("/project/.pixi/pipelines/ci"),
+        );
+        assert_eq!(env["PIXI_PIPELINE"], "ci");
+        assert_eq!(env["PIXI_STAGE"], "test");
+        assert_eq!(env["PIXI_MATRIX_ENVIRONMENT"], "cpu");
+        assert_eq!(env["PIXI_MATRIX_PYTHON_VERSION"], "3.11");
+    }
+
+    #[test]
+    fn test_collect_artifacts() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("dist/wheels")).unwrap();
+        std::fs::write(root.path().join("dist/wheels/foo.whl"), "wheel").unwrap();
+        std::fs::write(root.path().join("report.xml"), "report").unwrap();
+
+        let stage: Stage = toml_edit::de::from_str(
+            r#"
+            task = "build"
+            artifacts = ["dist", "report.xml"]
+            "#,
+        )
+        .unwrap();
+        let destination = root.path().join(".pixi/pipelines/ci/build");
+        collect_artifacts(root.path(), &stage, &destination).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(destination.join("dist/wheels/foo.whl")).unwrap(),
+            "wheel"
+        );
+        assert!(destination.join("report.xml").is_file());
+
+        for artifact in ["../outside", "/etc/passwd", "dist/../../outside"] {
+            let stage: Stage =
+                toml_edit::de::from_str(&format!("task = \"build\"\nartifacts = [\"{artifact}\"]"))
+                    .unwrap();
+            assert!(collect_artifacts(root.path(), &stage, &destination).is_err());
+        }
+    }
+}
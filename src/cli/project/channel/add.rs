@@ -1,11 +1,12 @@
 use crate::environment::{get_up_to_date_prefix, LockFileUsage};
 use crate::lock_file::load_lock_file;
 
+use crate::project::manifest::{is_relative_channel_path, parse_channel};
 use crate::Project;
 use clap::Parser;
 use itertools::Itertools;
 use miette::IntoDiagnostic;
-use rattler_conda_types::{Channel, ChannelConfig};
+use std::path::Path;
 
 #[derive(Parser, Debug, Default)]
 pub struct Args {
@@ -20,12 +21,14 @@ pub struct Args {
 
 pub async fn execute(mut project: Project, args: Args) -> miette::Result<()> {
     // Determine which channels are missing
-    let channel_config = ChannelConfig::default();
+    let channel_config = project.config().channel_config();
     let channels = args
         .channel
         .into_iter()
+        .map(|channel_str| project_relative_channel(&project, channel_str))
         .map(|channel_str| {
-            Channel::from_str(&channel_str, &channel_config).map(|channel| (channel_str, channel))
+            parse_channel(&channel_str, project.root(), &channel_config)
+                .map(|channel| (channel_str, channel))
         })
         .collect::<Result<Vec<_>, _>>()
         .into_diagnostic()?;
@@ -65,3 +68,29 @@ pub async fn execute(mut project: Project, args: Args) -> miette::Result<()> {
 
     Ok(())
 }
+
+/// Channels in the manifest that are relative paths are relative to the project, so a path that is
+/// relative to the current directory is rewritten to be relative to the root of the project, or
+/// absolute if the channel is outside of the project.
+fn project_relative_channel(project: &Project, channel: String) -> String {
+    if !is_relative_channel_path(&channel) {
+        return channel;
+    }
+    let Ok(current_dir) = std::env::current_dir() else {
+        return channel;
+    };
+    let path = current_dir.join(&channel);
+    let path = dunce::canonicalize(&path).unwrap_or(path);
+    let root = dunce::canonicalize(project.root()).unwrap_or(project.root().to_path_buf());
+    match path.strip_prefix(&root) {
+        Ok(relative) if relative == Path::new("") => String::from("."),
+        Ok(relative) => format!(
+            "./{}",
+            relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .join("/")
+        ),
+        Err(_) => path.to_string_lossy().to_string(),
+    }
+}
@@ -1,10 +1,10 @@
 use crate::environment::{get_up_to_date_prefix, LockFileUsage};
 
+use crate::project::manifest::parse_channel;
 use crate::Project;
 use clap::Parser;
 use itertools::Itertools;
 use miette::IntoDiagnostic;
-use rattler_conda_types::{Channel, ChannelConfig};
 
 #[derive(Parser, Debug, Default)]
 pub struct Args {
@@ -19,12 +19,13 @@ pub struct Args {
 
 pub async fn execute(mut project: Project, args: Args) -> miette::Result<()> {
     // Determine which channels to remove
-    let channel_config = ChannelConfig::default();
+    let channel_config = project.config().channel_config();
     let channels = args
         .channel
         .into_iter()
         .map(|channel_str| {
-            Channel::from_str(&channel_str, &channel_config).map(|channel| (channel_str, channel))
+            parse_channel(&channel_str, project.root(), &channel_config)
+                .map(|channel| (channel_str, channel))
         })
         .collect::<Result<Vec<_>, _>>()
         .into_diagnostic()?;
@@ -0,0 +1,134 @@
+use crate::cli::add::adopt_drifted_packages;
+use crate::drift::{find_project_drift, remove_drifted_distributions};
+use crate::environment::{get_up_to_date_prefix, LockFileUsage};
+use crate::prefix::Prefix;
+use crate::project::manifest::Manifest;
+use crate::unused_dependencies::{default_analyzers, find_unused_dependencies};
+use crate::Project;
+use clap::Parser;
+use itertools::Itertools;
+use std::path::PathBuf;
+
+/// Check the project manifest for errors.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Treat unknown keys in the manifest as errors, even if `strict` is not set in the manifest
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Report the declared dependencies that nothing in the project appears to use, based on the
+    /// imports of python files, the packages CMake files look for and the commands of the tasks
+    #[arg(long)]
+    pub unused_deps: bool,
+
+    /// Report the packages that are installed in the environment but not locked, e.g. because
+    /// they were installed with `pip install` inside `pixi shell`
+    #[arg(long)]
+    pub drift: bool,
+
+    /// Add the packages found by `--drift` to the dependencies of the project
+    #[arg(long, requires = "drift", conflicts_with = "remove")]
+    pub adopt: bool,
+
+    /// Remove the packages found by `--drift` from the environment
+    #[arg(long, requires = "drift")]
+    pub remove: bool,
+
+    /// The environment to check with `--drift`
+    #[arg(long, short)]
+    pub environment: Option<String>,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?
+        .with_environment(args.environment.as_deref())?;
+    if args.strict {
+        Manifest::from_str_with_strictness(
+            project.root(),
+            project.manifest.contents.clone(),
+            true,
+        )?;
+    }
+
+    eprintln!(
+        "{}The manifest of {} is valid",
+        console::style(console::Emoji("✔ ", "")).green(),
+        console::style(project.name()).bold()
+    );
+
+    if args.unused_deps {
+        let unused = find_unused_dependencies(&project, &default_analyzers()).await?;
+        if !unused.is_empty() {
+            return Err(miette::miette!(
+                help = "remove them with `pixi remove`, unless they are used in a way that can't be detected, e.g. by a compiler",
+                "the project declares dependencies that nothing appears to use:\n{}",
+                unused
+                    .iter()
+                    .map(|dep| format!("  - {} ({})", dep.name, dep.table))
+                    .join("\n")
+            ));
+        }
+        eprintln!(
+            "{}All dependencies of {} are used",
+            console::style(console::Emoji("✔ ", "")).green(),
+            console::style(project.name()).bold()
+        );
+    }
+
+    if args.drift {
+        check_drift(project, args.adopt, args.remove).await?;
+    }
+    Ok(())
+}
+
+/// Reports the packages of the environment that are not locked, or adopts or removes them.
+async fn check_drift(mut project: Project, adopt: bool, remove: bool) -> miette::Result<()> {
+    let drifted = find_project_drift(&project).await?;
+    if drifted.is_empty() {
+        eprintln!(
+            "{}The environment of {} only contains locked packages",
+            console::style(console::Emoji("✔ ", "")).green(),
+            console::style(project.name()).bold()
+        );
+        return Ok(());
+    }
+
+    if adopt {
+        for spec in adopt_drifted_packages(&mut project, &drifted, false, false).await? {
+            eprintln!(
+                "{}Added {}",
+                console::style(console::Emoji("✔ ", "")).green(),
+                console::style(spec).bold(),
+            );
+        }
+    } else if remove {
+        let prefix = Prefix::new(project.environment_dir())?;
+        remove_drifted_distributions(&prefix, &drifted)?;
+        // Installing the locked environment removes the conda packages that are not locked.
+        if drifted.iter().any(|package| !package.is_pypi()) {
+            get_up_to_date_prefix(&project, LockFileUsage::Frozen, false, None).await?;
+        }
+        eprintln!(
+            "{}Removed {} package{} that {} not locked",
+            console::style(console::Emoji("✔ ", "")).green(),
+            drifted.len(),
+            if drifted.len() == 1 { "" } else { "s" },
+            if drifted.len() == 1 { "is" } else { "are" },
+        );
+    } else {
+        miette::bail!(
+            help = "add them to the dependencies of the project with `pixi add --from-prefix`, or remove them with `pixi project check --drift --remove`",
+            "the environment of {} contains packages that are not locked:\n{}",
+            project.name(),
+            drifted
+                .iter()
+                .map(|package| format!("  - {package}"))
+                .join("\n")
+        );
+    }
+    Ok(())
+}
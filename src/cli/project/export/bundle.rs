@@ -0,0 +1,338 @@
+use crate::cli::LockFileUsageArgs;
+use crate::environment::get_up_to_date_prefix;
+use crate::progress::await_in_progress;
+use crate::{consts, Project};
+use clap::Parser;
+use flate2::{write::GzEncoder, Compression};
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::Platform;
+use rattler_digest::{compute_bytes_digest, Sha256};
+use rattler_shell::activation::{ActivationVariables, Activator, PathModificationBehavior};
+use rattler_shell::shell::Bash;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The line of the launcher after which the compressed payload starts.
+const PAYLOAD_MARKER: &str = "__PIXI_BUNDLE_PAYLOAD__";
+
+/// Export the project as a single self-extracting executable that starts an entry point of the
+/// environment.
+///
+/// By default the installed environment is embedded in the executable. On the first run it is
+/// extracted to `$PIXI_BUNDLE_DIR`, which defaults to `~/.cache/pixi-bundles`.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The executable of the environment that is started by the bundle.
+    #[arg(long)]
+    pub entry_point: String,
+
+    /// The path of the created executable, defaults to the name of the entry point.
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+
+    /// Don't embed the environment, instead download pixi and install the locked environment on
+    /// the first run. The entry point can also be a task of the project in this case.
+    #[arg(long)]
+    pub download: bool,
+
+    #[clap(flatten)]
+    pub lock_file_usage: LockFileUsageArgs,
+}
+
+/// What is stored in the bundle after the launcher script.
+enum Payload {
+    /// The installed environment at the given path.
+    Environment(PathBuf),
+
+    /// The manifest, the files it includes by their path relative to the root of the project, and
+    /// the lock file of the project.
+    Project {
+        manifest: PathBuf,
+        included_files: Vec<(PathBuf, PathBuf)>,
+        lock_file: PathBuf,
+    },
+}
+
+pub async fn execute(project: Project, args: Args) -> miette::Result<()> {
+    if Platform::current().is_windows() {
+        miette::bail!("bundles are shell scripts and can only be created on unix platforms");
+    }
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(&args.entry_point));
+
+    // Make sure the lock file and the environment are up to date, the lock file also identifies
+    // the bundle. The environment is only needed when it is embedded.
+    let prefix =
+        get_up_to_date_prefix(&project, args.lock_file_usage.into(), args.download, None).await?;
+    let lock_file = std::fs::read(project.lock_file_path()).into_diagnostic()?;
+    let bundle_id = bundle_id(project.name(), &args.entry_point, args.download, &lock_file);
+
+    let (install, run, payload) = if args.download {
+        let install = download_install_snippet();
+        let run = format!(
+            "exec \"$TARGET/pixi\" run --frozen --manifest-path \"$TARGET/{}\" {} \"$@\"",
+            consts::PROJECT_MANIFEST,
+            shlex::quote(&args.entry_point)
+        );
+        let payload = Payload::Project {
+            manifest: project.manifest_path(),
+            included_files: project.included_files()?,
+            lock_file: project.lock_file_path(),
+        };
+        (install, run, payload)
+    } else {
+        if !prefix.root().join("bin").join(&args.entry_point).is_file() {
+            miette::bail!(
+                "the environment does not contain an executable called '{}'",
+                args.entry_point
+            );
+        }
+
+        let prefix_root = prefix.root().to_path_buf();
+        let (text_files, binary_files) = await_in_progress(
+            "searching for files to relocate",
+            tokio::task::spawn_blocking(move || find_prefix_references(&prefix_root)),
+        )
+        .await
+        .into_diagnostic()?
+        .into_diagnostic()?;
+        if binary_files > 0 {
+            eprintln!(
+                "{} {binary_files} binary files contain the path of the environment, they are not relocated and might not work on other machines",
+                console::style("!").yellow().bold(),
+            );
+        }
+
+        let install = relocate_snippet(prefix.root(), &text_files);
+        let run = format!(
+            "{}\nexec {} \"$@\"",
+            activation_snippet(prefix.root(), project.package_activation_scripts_enabled())?,
+            shlex::quote(&args.entry_point)
+        );
+        (
+            install,
+            run,
+            Payload::Environment(prefix.root().to_path_buf()),
+        )
+    };
+
+    let launcher = launcher_script(project.name(), &bundle_id, &install, &run);
+    let output_path = output.clone();
+    await_in_progress(
+        "creating bundle",
+        tokio::task::spawn_blocking(move || write_bundle(&output_path, &launcher, &payload)),
+    )
+    .await
+    .into_diagnostic()?
+    .into_diagnostic()
+    .wrap_err_with(|| format!("failed to write the bundle to '{}'", output.display()))?;
+
+    eprintln!(
+        "{}Created bundle {}",
+        console::style(console::Emoji("✔ ", "")).green(),
+        console::style(output.display()).bold()
+    );
+
+    Ok(())
+}
+
+/// Returns the name of the directory the bundle is extracted to. It changes whenever the locked
+/// packages change so a new version of the bundle never reuses an outdated extraction.
+fn bundle_id(project_name: &str, entry_point: &str, download: bool, lock_file: &[u8]) -> String {
+    let mut contents = format!("{entry_point}\n{download}\n").into_bytes();
+    contents.extend_from_slice(lock_file);
+    let hash = format!("{:x}", compute_bytes_digest::<Sha256>(&contents));
+    let name = project_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect::<String>();
+    format!("{name}-{}", &hash[..16])
+}
+
+/// Constructs the shell script at the start of the bundle. On the first run the payload is
+/// extracted and `install` is executed, `run` is executed on every run.
+fn launcher_script(project_name: &str, bundle_id: &str, install: &str, run: &str) -> String {
+    format!(
+        r#"#!/bin/sh
+# Bundle of the pixi project '{project_name}', created with `pixi project export bundle`.
+set -e
+TARGET="${{PIXI_BUNDLE_DIR:-${{XDG_CACHE_HOME:-$HOME/.cache}}/pixi-bundles}}/{bundle_id}"
+if [ ! -f "$TARGET/.pixi-bundle-complete" ]; then
+    rm -rf "$TARGET"
+    mkdir -p "$TARGET"
+    PAYLOAD_START=$(awk '/^{PAYLOAD_MARKER}$/ {{ print NR + 1; exit 0 }}' "$0")
+    tail -n +"$PAYLOAD_START" "$0" | gzip -dc | tar -xf - -C "$TARGET"
+{install}
+    touch "$TARGET/.pixi-bundle-complete"
+fi
+{run}
+exit 1
+{PAYLOAD_MARKER}
+"#
+    )
+}
+
+/// Constructs the part of the launcher that replaces the original path of the environment with the
+/// path it was extracted to.
+fn relocate_snippet(prefix: &Path, text_files: &[PathBuf]) -> String {
+    if text_files.is_empty() {
+        return String::new();
+    }
+    let files = text_files
+        .iter()
+        .map(|path| path.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        r#"    ORIGINAL_PREFIX={}
+    while IFS= read -r file; do
+        sed -i.pixi-bundle "s|$ORIGINAL_PREFIX|$TARGET|g" "$TARGET/$file"
+        rm -f "$TARGET/$file.pixi-bundle"
+    done <<'PIXI_BUNDLE_FILES'
+{files}
+PIXI_BUNDLE_FILES"#,
+        shlex::quote(&prefix.to_string_lossy())
+    )
+}
+
+/// Constructs the part of the launcher that downloads the pixi version that created the bundle and
+/// installs the locked environment.
+fn download_install_snippet() -> String {
+    format!(
+        r#"    case "$(uname -s)-$(uname -m)" in
+        Linux-x86_64) TRIPLE=x86_64-unknown-linux-musl ;;
+        Linux-aarch64|Linux-arm64) TRIPLE=aarch64-unknown-linux-musl ;;
+        Darwin-x86_64) TRIPLE=x86_64-apple-darwin ;;
+        Darwin-arm64) TRIPLE=aarch64-apple-darwin ;;
+        *) echo "unsupported platform $(uname -s)-$(uname -m)" >&2; exit 1 ;;
+    esac
+    curl -fsSL "{}/pixi-$TRIPLE.tar.gz" | tar -xzf - -C "$TARGET"
+    "$TARGET/pixi" install --frozen --manifest-path "$TARGET/{}""#,
+        super::release_url(),
+        consts::PROJECT_MANIFEST
+    )
+}
+
+/// Constructs the part of the launcher that activates the extracted environment, `package_scripts`
+/// determines whether the activation scripts of the packages are sourced.
+fn activation_snippet(prefix: &Path, package_scripts: bool) -> miette::Result<String> {
+    let mut activator =
+        Activator::from_path(prefix, Bash, Platform::current()).into_diagnostic()?;
+    if !package_scripts {
+        activator.activation_scripts.clear();
+    }
+    let activation = activator
+        .activation(ActivationVariables {
+            conda_prefix: None,
+            path: None,
+            path_modification_behavior: PathModificationBehavior::Prepend,
+        })
+        .into_diagnostic()?;
+    Ok(activation
+        .script
+        .replace(prefix.to_string_lossy().as_ref(), "$TARGET"))
+}
+
+/// Returns the paths, relative to `prefix`, of the text files that contain the path of the prefix
+/// and the number of binary files that contain it. Only text files are relocated by the launcher.
+fn find_prefix_references(prefix: &Path) -> std::io::Result<(Vec<PathBuf>, usize)> {
+    let needle = prefix.to_string_lossy().into_owned().into_bytes();
+    let mut text_files = Vec::new();
+    let mut binary_files = 0;
+    let mut directories = vec![prefix.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in std::fs::read_dir(directory)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                directories.push(entry.path());
+                continue;
+            } else if !file_type.is_file() {
+                continue;
+            }
+
+            let contents = std::fs::read(entry.path())?;
+            if !contents
+                .windows(needle.len())
+                .any(|window| window == needle)
+            {
+                continue;
+            }
+            if contents.contains(&0) {
+                binary_files += 1;
+            } else {
+                text_files.push(
+                    entry
+                        .path()
+                        .strip_prefix(prefix)
+                        .expect("the entry was found in the prefix")
+                        .to_path_buf(),
+                );
+            }
+        }
+    }
+    text_files.sort();
+    Ok((text_files, binary_files))
+}
+
+/// Writes the launcher followed by the gzipped tarball of the payload to `output` and makes it
+/// executable.
+fn write_bundle(output: &Path, launcher: &str, payload: &Payload) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(output)?;
+    file.write_all(launcher.as_bytes())?;
+
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    builder.follow_symlinks(false);
+    match payload {
+        Payload::Environment(prefix) => builder.append_dir_all(".", prefix)?,
+        Payload::Project {
+            manifest,
+            included_files,
+            lock_file,
+        } => {
+            builder.append_path_with_name(manifest, consts::PROJECT_MANIFEST)?;
+            for (relative, path) in included_files {
+                builder.append_path_with_name(path, relative)?;
+            }
+            builder.append_path_with_name(lock_file, consts::PROJECT_LOCK_FILE)?;
+        }
+    }
+    builder.into_inner()?.finish()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(output, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_prefix_references() {
+        let prefix = tempfile::tempdir().unwrap();
+        let prefix_path = prefix.path().to_string_lossy().to_string();
+        std::fs::create_dir_all(prefix.path().join("bin")).unwrap();
+        std::fs::write(
+            prefix.path().join("bin").join("tool"),
+            format!("#!{prefix_path}/bin/python\n"),
+        )
+        .unwrap();
+        std::fs::write(
+            prefix.path().join("lib.so"),
+            [prefix_path.as_bytes(), &[0, 1, 2]].concat(),
+        )
+        .unwrap();
+        std::fs::write(prefix.path().join("README"), "no references").unwrap();
+
+        let (text_files, binary_files) = find_prefix_references(prefix.path()).unwrap();
+        assert_eq!(text_files, vec![Path::new("bin").join("tool")]);
+        assert_eq!(binary_files, 1);
+    }
+}
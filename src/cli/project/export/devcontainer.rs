@@ -0,0 +1,209 @@
+use crate::Project;
+use clap::Parser;
+use miette::{Context, IntoDiagnostic};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+/// The base image of the container, it has a non-root user and the tools a dev container needs.
+const DEFAULT_BASE_IMAGE: &str = "mcr.microsoft.com/devcontainers/base:jammy";
+
+/// Export the project as a dev container for VS Code and GitHub Codespaces.
+///
+/// Creates a `devcontainer.json` and a `Dockerfile` that installs this release of pixi. When the
+/// container is created the locked environment is installed in a volume, so it survives rebuilds of the
+/// container and is not shared with the environment of the host.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The task that is run after the environment is installed in a new container.
+    #[arg(long)]
+    pub on_create: Option<String>,
+
+    /// The environment to install, one for every variant of the dependencies of the project.
+    #[arg(long, short)]
+    pub environment: Option<String>,
+
+    /// The image the container is based on.
+    #[arg(long, default_value = DEFAULT_BASE_IMAGE)]
+    pub base_image: String,
+
+    /// The directory the files are written to, defaults to `.devcontainer` in the project.
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+
+    /// Overwrite the files if they already exist.
+    #[arg(long)]
+    pub force: bool,
+}
+
+pub async fn execute(project: Project, args: Args) -> miette::Result<()> {
+    let project = project.with_environment(args.environment.as_deref())?;
+    if let Some(task) = &args.on_create {
+        if project.task_opt(task, None).is_none() {
+            miette::bail!("the project has no task called '{task}'");
+        }
+    }
+    if !project.lock_file_path().is_file() {
+        eprintln!(
+            "{} the project has no lock file, run `pixi install` and commit the lock file so the container installs the locked environment",
+            console::style("!").yellow().bold(),
+        );
+    }
+
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| project.root().join(".devcontainer"));
+    let files = [
+        (
+            output.join("devcontainer.json"),
+            serde_json::to_string_pretty(&devcontainer_json(
+                project.name(),
+                &relative_path(&output, project.root()),
+                project.environment_name(),
+                &environment_dir(&project),
+                args.on_create.as_deref(),
+            ))
+            .into_diagnostic()?
+                + "\n",
+        ),
+        (output.join("Dockerfile"), dockerfile(&args.base_image)),
+    ];
+    if !args.force {
+        if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+            miette::bail!(
+                help = "use --force to overwrite it",
+                "'{}' already exists",
+                path.display()
+            );
+        }
+    }
+
+    std::fs::create_dir_all(&output).into_diagnostic()?;
+    for (path, contents) in &files {
+        std::fs::write(path, contents)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to write '{}'", path.display()))?;
+    }
+
+    eprintln!(
+        "{}Created the dev container in {}",
+        console::style(console::Emoji("✔ ", "")).green(),
+        console::style(output.display()).bold()
+    );
+    Ok(())
+}
+
+/// Returns the directory of the environment relative to the root of the project, with forward
+/// slashes as it is used in the container.
+fn environment_dir(project: &Project) -> String {
+    let environment_dir = project.environment_dir();
+    let relative = environment_dir
+        .strip_prefix(project.root())
+        .unwrap_or(&environment_dir);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns the path of `root` relative to `dir`, the build context of the container is the root of
+/// the project.
+fn relative_path(dir: &Path, root: &Path) -> String {
+    match dir.strip_prefix(root) {
+        Ok(relative) => relative
+            .components()
+            .map(|_| "..")
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => root.to_string_lossy().into_owned(),
+    }
+}
+
+/// Constructs the `devcontainer.json`. The environment is installed in a volume that is mounted on
+/// its directory, `environment_dir` relative to the root of the project. The rest of the `.pixi`
+/// directory, e.g. the configuration of the project, comes from the workspace.
+fn devcontainer_json(
+    project_name: &str,
+    context: &str,
+    environment: Option<&str>,
+    environment_dir: &str,
+    on_create: Option<&str>,
+) -> serde_json::Value {
+    let environment_arg = environment
+        .map(|environment| format!(" --environment {}", shlex::quote(environment)))
+        .unwrap_or_default();
+    let mut on_create_command = format!(
+        "sudo chown \"$(id -un)\" {} && pixi install --frozen{environment_arg}",
+        shlex::quote(environment_dir)
+    );
+    if let Some(task) = on_create {
+        on_create_command.push_str(&format!(
+            " && pixi run --frozen{environment_arg} {}",
+            shlex::quote(task)
+        ));
+    }
+
+    json!({
+        "name": project_name,
+        "build": {
+            "dockerfile": "Dockerfile",
+            "context": if context.is_empty() { "." } else { context },
+        },
+        "mounts": [
+            format!("source=${{localWorkspaceFolderBasename}}-pixi-{},target=${{containerWorkspaceFolder}}/{environment_dir},type=volume", environment.unwrap_or("default"))
+        ],
+        "onCreateCommand": on_create_command,
+    })
+}
+
+/// Constructs the `Dockerfile`, which installs the release of pixi that created it.
+fn dockerfile(base_image: &str) -> String {
+    format!(
+        r#"FROM {base_image}
+
+ARG PIXI_RELEASE={}
+RUN curl -fsSL "${{PIXI_RELEASE}}/pixi-$(uname -m)-unknown-linux-musl.tar.gz" \
+    | tar -xzf - -C /usr/local/bin \
+    && chmod +x /usr/local/bin/pixi
+"#,
+        super::release_url()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_devcontainer_json() {
+        let root = Path::new("/project");
+        assert_eq!(relative_path(&root.join(".devcontainer"), root), "..");
+
+        let json = devcontainer_json("foo", "..", Some("test"), ".pixi/envs/test", Some("setup"));
+        assert_eq!(json["build"]["context"], "..");
+        assert_eq!(
+            json["onCreateCommand"],
+            "sudo chown \"$(id -un)\" .pixi/envs/test && pixi install --frozen --environment test && pixi run --frozen --environment test setup"
+        );
+        assert_eq!(
+            json["mounts"][0],
+            "source=${localWorkspaceFolderBasename}-pixi-test,target=${containerWorkspaceFolder}/.pixi/envs/test,type=volume"
+        );
+
+        let json = devcontainer_json("foo", "..", None, ".pixi/env", None);
+        assert_eq!(
+            json["onCreateCommand"],
+            "sudo chown \"$(id -un)\" .pixi/env && pixi install --frozen"
+        );
+    }
+
+    #[test]
+    fn test_dockerfile() {
+        let dockerfile = dockerfile(DEFAULT_BASE_IMAGE);
+        assert!(dockerfile.contains(&format!(
+            "ARG PIXI_RELEASE=https://github.com/marcelotrevisani/pixi/releases/download/v{}\n",
+            env!("CARGO_PKG_VERSION")
+        )));
+    }
+}
@@ -0,0 +1,253 @@
+use crate::Project;
+use clap::Parser;
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::Platform;
+use std::path::PathBuf;
+
+/// Export a GitHub Actions workflow that installs the locked environment of the project.
+///
+/// The workflow has a job for every platform of the project that GitHub hosts runners for, and for
+/// every environment. The environment is cached with the key of `pixi info --cache-key`, so it is
+/// only installed again when the lock file changes.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The task that every job runs after the environment is installed.
+    #[arg(long)]
+    pub task: Option<String>,
+
+    /// The path of the workflow, defaults to `.github/workflows/pixi.yml` in the project.
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+
+    /// Overwrite the workflow if it already exists.
+    #[arg(long)]
+    pub force: bool,
+}
+
+/// A job of the matrix of the workflow.
+#[derive(Debug, PartialEq, Eq)]
+struct MatrixEntry<'a> {
+    runner: &'static str,
+    platform: Platform,
+    environment: Option<&'a str>,
+}
+
+pub async fn execute(project: Project, args: Args) -> miette::Result<()> {
+    if let Some(task) = &args.task {
+        if project.task_opt(task, None).is_none() {
+            miette::bail!("the project has no task called '{task}'");
+        }
+    }
+
+    let unsupported = project
+        .platforms()
+        .iter()
+        .filter(|platform| runner(**platform).is_none())
+        .collect_vec();
+    if !unsupported.is_empty() {
+        eprintln!(
+            "{} GitHub has no hosted runners for {}, they are not part of the workflow",
+            console::style("!").yellow().bold(),
+            unsupported.iter().join(", ")
+        );
+    }
+    let environments = project.environments();
+    let matrix = matrix(project.platforms(), &environments);
+    if matrix.is_empty() {
+        miette::bail!("GitHub has no hosted runners for any platform of the project");
+    }
+
+    let output = args.output.clone().unwrap_or_else(|| {
+        project
+            .root()
+            .join(".github")
+            .join("workflows")
+            .join("pixi.yml")
+    });
+    if output.exists() && !args.force {
+        miette::bail!(
+            help = "use --force to overwrite it",
+            "'{}' already exists",
+            output.display()
+        );
+    }
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent).into_diagnostic()?;
+    }
+    std::fs::write(&output, workflow(&matrix, args.task.as_deref()))
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write '{}'", output.display()))?;
+
+    eprintln!(
+        "{}Created the workflow {}",
+        console::style(console::Emoji("✔ ", "")).green(),
+        console::style(output.display()).bold()
+    );
+    Ok(())
+}
+
+/// Returns the GitHub hosted runner for a platform.
+fn runner(platform: Platform) -> Option<&'static str> {
+    match platform {
+        Platform::Linux64 => Some("ubuntu-latest"),
+        Platform::LinuxAarch64 => Some("ubuntu-24.04-arm"),
+        Platform::Osx64 => Some("macos-13"),
+        Platform::OsxArm64 => Some("macos-latest"),
+        Platform::Win64 => Some("windows-latest"),
+        _ => None,
+    }
+}
+
+/// Returns the name of the release asset of pixi for the platform of a GitHub hosted runner.
+fn release_asset(platform: Platform) -> Option<&'static str> {
+    match platform {
+        Platform::Linux64 => Some("pixi-x86_64-unknown-linux-musl.tar.gz"),
+        Platform::LinuxAarch64 => Some("pixi-aarch64-unknown-linux-musl.tar.gz"),
+        Platform::Osx64 => Some("pixi-x86_64-apple-darwin.tar.gz"),
+        Platform::OsxArm64 => Some("pixi-aarch64-apple-darwin.tar.gz"),
+        Platform::Win64 => Some("pixi-x86_64-pc-windows-msvc.zip"),
+        _ => None,
+    }
+}
+
+/// Returns a job for every combination of platform and environment.
+fn matrix<'a>(platforms: &[Platform], environments: &'a [String]) -> Vec<MatrixEntry<'a>> {
+    let environments = if environments.is_empty() {
+        vec![None]
+    } else {
+        environments
+            .iter()
+            .map(|name| Some(name.as_str()))
+            .collect()
+    };
+    platforms
+        .iter()
+        .filter_map(|platform| Some((*platform, runner(*platform)?)))
+        .cartesian_product(environments)
+        .map(|((platform, runner), environment)| MatrixEntry {
+            runner,
+            platform,
+            environment,
+        })
+        .collect()
+}
+
+/// Constructs the workflow, it installs the release of pixi that created it.
+fn workflow(matrix: &[MatrixEntry], task: Option<&str>) -> String {
+    let has_environments = matrix.iter().any(|entry| entry.environment.is_some());
+    let include = matrix
+        .iter()
+        .map(|entry| {
+            let mut item = format!(
+                "          - os: {}\n            platform: {}",
+                entry.runner, entry.platform
+            );
+            if let Some(asset) = release_asset(entry.platform) {
+                item.push_str(&format!("\n            asset: {asset}"));
+            }
+            if let Some(environment) = entry.environment {
+                item.push_str(&format!("\n            environment: {environment}"));
+            }
+            item
+        })
+        .join("\n");
+    let environment_arg = if has_environments {
+        " --environment ${{ matrix.environment }}"
+    } else {
+        ""
+    };
+    let name = if has_environments {
+        "${{ matrix.platform }} (${{ matrix.environment }})"
+    } else {
+        "${{ matrix.platform }}"
+    };
+    let run_task = task
+        .map(|task| {
+            format!(
+                "      - name: Run {task}\n        run: pixi run --frozen{environment_arg} {task}\n"
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        r#"# Created with `pixi project export github-actions`.
+name: pixi
+
+on:
+  push:
+    branches: [main]
+  pull_request:
+
+jobs:
+  pixi:
+    name: {name}
+    runs-on: ${{{{ matrix.os }}}}
+    strategy:
+      fail-fast: false
+      matrix:
+        include:
+{include}
+    steps:
+      - uses: actions/checkout@v4
+      - name: Install pixi
+        shell: bash
+        run: |
+          mkdir -p "$RUNNER_TEMP/pixi"
+          curl -fsSL -o "$RUNNER_TEMP/${{{{ matrix.asset }}}}" "{release_url}/${{{{ matrix.asset }}}}"
+          case "${{{{ matrix.asset }}}}" in
+            *.zip) unzip -q "$RUNNER_TEMP/${{{{ matrix.asset }}}}" -d "$RUNNER_TEMP/pixi" ;;
+            *) tar -xzf "$RUNNER_TEMP/${{{{ matrix.asset }}}}" -C "$RUNNER_TEMP/pixi" ;;
+          esac
+          echo "$RUNNER_TEMP/pixi" >> "$GITHUB_PATH"
+      - name: Compute the cache key
+        id: cache-key
+        shell: bash
+        run: echo "key=$(pixi info --cache-key --platform ${{{{ matrix.platform }}}}{environment_arg})" >> "$GITHUB_OUTPUT"
+      - uses: actions/cache@v4
+        with:
+          path: .pixi
+          key: ${{{{ steps.cache-key.outputs.key }}}}
+      - name: Install the environment
+        run: pixi install --frozen{environment_arg}
+{run_task}"#,
+        release_url = super::release_url(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix() {
+        let environments = vec![String::from("cpu"), String::from("cuda")];
+        let matrix = matrix(&[Platform::Linux64, Platform::LinuxPpc64le], &environments);
+        assert_eq!(
+            matrix,
+            vec![
+                MatrixEntry {
+                    runner: "ubuntu-latest",
+                    platform: Platform::Linux64,
+                    environment: Some("cpu"),
+                },
+                MatrixEntry {
+                    runner: "ubuntu-latest",
+                    platform: Platform::Linux64,
+                    environment: Some("cuda"),
+                },
+            ]
+        );
+
+        let workflow = workflow(&matrix, Some("test"));
+        assert!(workflow.contains("          - os: ubuntu-latest\n            platform: linux-64\n            asset: pixi-x86_64-unknown-linux-musl.tar.gz\n            environment: cuda\n"));
+        assert!(workflow.contains(&format!(
+            "\"https://github.com/marcelotrevisani/pixi/releases/download/v{}/${{{{ matrix.asset }}}}\"",
+            env!("CARGO_PKG_VERSION")
+        )));
+        assert!(!workflow.contains("setup-pixi"));
+        assert!(workflow
+            .contains("run: pixi run --frozen --environment ${{ matrix.environment }} test\n"));
+        assert!(workflow.contains("runs-on: ${{ matrix.os }}\n"));
+    }
+}
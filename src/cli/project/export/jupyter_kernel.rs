@@ -0,0 +1,249 @@
+use crate::Project;
+use clap::{Parser, ValueEnum};
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::PackageName;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Register the environment as a Jupyter kernel, so notebooks outside of the project can use it.
+///
+/// The kernel starts through `pixi run`, so it runs in the activated environment of the project and
+/// the environment is installed when it is missing.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The name of the kernel, defaults to `pixi-<project name>`.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// The name of the kernel that is shown in Jupyter, defaults to the name of the project.
+    #[arg(long)]
+    pub display_name: Option<String>,
+
+    /// The language of the kernel.
+    #[arg(long, value_enum, default_value_t = KernelLanguage::Python)]
+    pub language: KernelLanguage,
+
+    /// The environment of the kernel, one for every variant of the dependencies of the project or
+    /// every environment of the `[environments]` table
+    #[arg(long, short)]
+    pub environment: Option<String>,
+
+    /// The directory the kernel is written to, defaults to the `kernels` directory in the Jupyter
+    /// data directory of the user.
+    #[arg(long)]
+    pub kernels_dir: Option<PathBuf>,
+}
+
+/// The languages a kernel can be registered for.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KernelLanguage {
+    /// Requires `ipykernel` in the environment
+    Python,
+    /// Requires `r-irkernel` in the environment
+    R,
+}
+
+impl KernelLanguage {
+    /// The package in the environment that implements the kernel.
+    fn kernel_package(self) -> &'static str {
+        match self {
+            KernelLanguage::Python => "ipykernel",
+            KernelLanguage::R => "r-irkernel",
+        }
+    }
+
+    /// The command that starts the kernel in the environment, Jupyter replaces
+    /// `{connection_file}`.
+    fn kernel_command(self) -> &'static [&'static str] {
+        match self {
+            KernelLanguage::Python => &[
+                "python",
+                "-m",
+                "ipykernel_launcher",
+                "-f",
+                "{connection_file}",
+            ],
+            KernelLanguage::R => &[
+                "R",
+                "--slave",
+                "-e",
+                "IRkernel::main()",
+                "--args",
+                "{connection_file}",
+            ],
+        }
+    }
+
+    /// The name of the language in the kernelspec.
+    fn name(self) -> &'static str {
+        match self {
+            KernelLanguage::Python => "python",
+            KernelLanguage::R => "R",
+        }
+    }
+}
+
+pub async fn execute(project: Project, args: Args) -> miette::Result<()> {
+    let project = project.with_environment(args.environment.as_deref())?;
+    if !declares_dependency(&project, args.language.kernel_package()) {
+        eprintln!(
+            "{} the project doesn't depend on '{package}', add it with `pixi add {package}` so the kernel can start",
+            console::style("!").yellow().bold(),
+            package = args.language.kernel_package()
+        );
+    }
+
+    let name = kernel_name(
+        args.name
+            .as_deref()
+            .unwrap_or(&format!("pixi-{}", project.name())),
+    );
+    let display_name = match (args.display_name, project.environment_name()) {
+        (Some(display_name), _) => display_name,
+        (None, Some(environment)) => format!("{} ({environment})", project.name()),
+        (None, None) => project.name().to_string(),
+    };
+    let kernels_dir = match args.kernels_dir {
+        Some(dir) => dir,
+        None => jupyter_data_dir()
+            .ok_or_else(|| miette::miette!("could not determine the Jupyter data directory"))?
+            .join("kernels"),
+    };
+
+    let pixi = std::env::current_exe().into_diagnostic()?;
+    let spec = kernel_spec(
+        &pixi,
+        &project.manifest_path(),
+        project.environment_name(),
+        args.language,
+        &display_name,
+    );
+    let kernel_dir = kernels_dir.join(&name);
+    std::fs::create_dir_all(&kernel_dir)
+        .and_then(|_| {
+            std::fs::write(
+                kernel_dir.join("kernel.json"),
+                serde_json::to_string_pretty(&spec)? + "\n",
+            )
+        })
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write the kernel to '{}'", kernel_dir.display()))?;
+
+    eprintln!(
+        "{}Registered the Jupyter kernel {} ({})",
+        console::style(console::Emoji("✔ ", "")).green(),
+        console::style(&display_name).bold(),
+        kernel_dir.display()
+    );
+    Ok(())
+}
+
+/// Returns true if the environment declares a conda or pypi dependency on the package.
+fn declares_dependency(project: &Project, package: &str) -> bool {
+    let platform = project.platform();
+    let conda = PackageName::from_str(package).map_or(false, |name| {
+        project.all_dependencies(platform).contains_key(&name)
+    });
+    let pypi = rip::types::PackageName::from_str(package).map_or(false, |name| {
+        project.pypi_dependencies(platform).contains_key(&name)
+    });
+    conda || pypi
+}
+
+/// Replaces the characters that Jupyter doesn't allow in the name of a kernel.
+fn kernel_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Returns the Jupyter data directory of the user, the `JUPYTER_DATA_DIR` environment variable
+/// overrides the default of the platform.
+fn jupyter_data_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("JUPYTER_DATA_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(target_os = "macos") {
+        dirs::home_dir().map(|home| home.join("Library").join("Jupyter"))
+    } else {
+        // `~/.local/share/jupyter` on linux and `%APPDATA%\jupyter` on windows.
+        dirs::data_dir().map(|dir| dir.join("jupyter"))
+    }
+}
+
+/// Constructs the `kernel.json` of a kernel that starts in the environment through `pixi run`.
+fn kernel_spec(
+    pixi: &Path,
+    manifest_path: &Path,
+    environment: Option<&str>,
+    language: KernelLanguage,
+    display_name: &str,
+) -> serde_json::Value {
+    let mut argv = vec![
+        pixi.to_string_lossy().into_owned(),
+        String::from("run"),
+        String::from("--manifest-path"),
+        manifest_path.to_string_lossy().into_owned(),
+    ];
+    if let Some(environment) = environment {
+        argv.extend([String::from("--environment"), environment.to_string()]);
+    }
+    argv.extend(language.kernel_command().iter().map(|arg| arg.to_string()));
+
+    serde_json::json!({
+        "argv": argv,
+        "display_name": display_name,
+        "language": language.name(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_name() {
+        assert_eq!(kernel_name("pixi-My Project"), "pixi-my-project");
+        assert_eq!(kernel_name("pixi-data_science.v2"), "pixi-data_science.v2");
+    }
+
+    #[test]
+    fn test_kernel_spec() {
+        let spec = kernel_spec(
+            Path::new("/usr/bin/pixi"),
+            Path::new("/project/pixi.toml"),
+            Some("cuda"),
+            KernelLanguage::Python,
+            "project (cuda)",
+        );
+        assert_eq!(
+            spec,
+            serde_json::json!({
+                "argv": [
+                    "/usr/bin/pixi", "run", "--manifest-path", "/project/pixi.toml",
+                    "--environment", "cuda",
+                    "python", "-m", "ipykernel_launcher", "-f", "{connection_file}"
+                ],
+                "display_name": "project (cuda)",
+                "language": "python",
+            })
+        );
+
+        let spec = kernel_spec(
+            Path::new("/usr/bin/pixi"),
+            Path::new("/project/pixi.toml"),
+            None,
+            KernelLanguage::R,
+            "project",
+        );
+        assert_eq!(spec["language"], "R");
+        assert_eq!(spec["argv"][4], "R");
+        assert_eq!(spec["argv"][9], "{connection_file}");
+    }
+}
@@ -0,0 +1,61 @@
+pub mod bundle;
+pub mod devcontainer;
+pub mod github_actions;
+pub mod jupyter_kernel;
+pub mod shell_aliases;
+
+use crate::Project;
+use clap::Parser;
+use std::path::PathBuf;
+
+/// The releases of this build of pixi. The exported files install the release that exported them,
+/// pixi of other builds doesn't know all tables of the manifest.
+const RELEASES_URL: &str = "https://github.com/marcelotrevisani/pixi/releases/download";
+
+/// Returns the url of the release that the exported files install, the assets are
+/// `pixi-<target triple>.tar.gz`.
+fn release_url() -> String {
+    format!("{RELEASES_URL}/v{}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Commands to export the project to other formats.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[clap(long, global = true)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// The subcommand to execute
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Parser, Debug)]
+pub enum Command {
+    /// Export the project as a single executable that runs without pixi being installed.
+    Bundle(bundle::Args),
+
+    /// Export the project as a dev container for VS Code and GitHub Codespaces.
+    Devcontainer(devcontainer::Args),
+
+    /// Export a GitHub Actions workflow that installs and caches the locked environment.
+    GithubActions(github_actions::Args),
+
+    /// Register the environment as a Jupyter kernel, so notebooks outside of the project can use it.
+    JupyterKernel(jupyter_kernel::Args),
+
+    /// Export shell aliases that run the tasks of the project.
+    ShellAliases(shell_aliases::Args),
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+
+    match args.command {
+        Command::Bundle(args) => bundle::execute(project, args).await,
+        Command::Devcontainer(args) => devcontainer::execute(project, args).await,
+        Command::GithubActions(args) => github_actions::execute(project, args).await,
+        Command::JupyterKernel(args) => jupyter_kernel::execute(project, args).await,
+        Command::ShellAliases(args) => shell_aliases::execute(project, args).await,
+    }
+}
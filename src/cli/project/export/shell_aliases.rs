@@ -0,0 +1,123 @@
+use crate::Project;
+use clap::{Parser, ValueEnum};
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use std::path::PathBuf;
+
+/// Export shell aliases that run the tasks of the project, e.g. `alias test='pixi run test'`.
+///
+/// Source the output in the configuration of your shell, e.g.
+/// `eval "$(pixi project export shell-aliases)"` in `~/.bashrc`.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The shell to create the aliases for.
+    #[arg(long, short, value_enum, default_value_t = AliasShell::Bash)]
+    pub shell: AliasShell,
+
+    /// A prefix for the names of the aliases, e.g. `px-` for `px-test`, so they don't shadow other
+    /// commands.
+    #[arg(long)]
+    pub prefix: Option<String>,
+
+    /// Run the tasks of this project from any directory, instead of the project in the current
+    /// directory.
+    #[arg(long)]
+    pub pin_project: bool,
+
+    /// Write the aliases to this file instead of stdout.
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+}
+
+/// The shells aliases can be created for.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasShell {
+    Bash,
+    Zsh,
+    /// Abbreviations, which expand to the command when they are typed
+    Fish,
+    Powershell,
+}
+
+pub async fn execute(project: Project, args: Args) -> miette::Result<()> {
+    let command = if args.pin_project {
+        format!(
+            "pixi run --manifest-path {}",
+            quote(args.shell, &project.manifest_path().to_string_lossy())
+        )
+    } else {
+        String::from("pixi run")
+    };
+    let prefix = args.prefix.as_deref().unwrap_or_default();
+    let aliases = project
+        .tasks(Some(project.platform()))
+        .into_keys()
+        .sorted()
+        .map(|task| {
+            alias(
+                args.shell,
+                &format!("{prefix}{}", alias_name(task)),
+                &format!("{command} {}", quote(args.shell, task)),
+            )
+        })
+        .join("\n");
+
+    match args.output {
+        Some(path) => std::fs::write(path, aliases + "\n").into_diagnostic()?,
+        None if aliases.is_empty() => eprintln!("The project has no tasks"),
+        None => println!("{aliases}"),
+    }
+    Ok(())
+}
+
+/// Replaces the characters that are not allowed in the name of an alias.
+fn alias_name(task: &str) -> String {
+    task.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Quotes a word for the shell.
+fn quote(shell: AliasShell, word: &str) -> String {
+    match shell {
+        AliasShell::Powershell => format!("'{}'", word.replace('\'', "''")),
+        _ => shlex::quote(word).into_owned(),
+    }
+}
+
+/// Returns the definition of an alias that runs `command`.
+fn alias(shell: AliasShell, name: &str, command: &str) -> String {
+    match shell {
+        AliasShell::Bash | AliasShell::Zsh => format!("alias {name}={}", quote(shell, command)),
+        AliasShell::Fish => format!("abbr --add {name} {}", quote(shell, command)),
+        AliasShell::Powershell => format!("function {name} {{ {command} @args }}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alias() {
+        assert_eq!(alias_name("build:docs"), "build-docs");
+        assert_eq!(
+            alias(AliasShell::Bash, "t", "pixi run test"),
+            "alias t='pixi run test'"
+        );
+        assert_eq!(
+            alias(AliasShell::Fish, "t", "pixi run test"),
+            "abbr --add t 'pixi run test'"
+        );
+        assert_eq!(
+            alias(AliasShell::Powershell, "t", "pixi run test"),
+            "function t { pixi run test @args }"
+        );
+    }
+}
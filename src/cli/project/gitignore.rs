@@ -0,0 +1,80 @@
+use crate::{consts, gitignore, Project};
+use clap::Parser;
+use miette::IntoDiagnostic;
+use std::path::PathBuf;
+
+/// Update the patterns that pixi manages in the `.gitignore` of the project.
+#[derive(Parser, Debug)]
+pub struct SyncArgs {
+    /// Add `pixi.lock` to the ignored files
+    #[arg(long, conflicts_with = "commit_lock_file")]
+    pub ignore_lock_file: bool,
+
+    /// Remove `pixi.lock` from the ignored files. Without either flag the current choice is kept
+    #[arg(long)]
+    pub commit_lock_file: bool,
+}
+
+#[derive(Parser, Debug)]
+pub enum Command {
+    /// Add or update the patterns that pixi manages in the `.gitignore`, e.g. after pixi started to
+    /// write new files into the `.pixi` directory.
+    Sync(SyncArgs),
+}
+
+/// Commands to manage the `.gitignore` of the project.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[clap(long, global = true)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// The subcommand to execute
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+fn execute_sync(project: &Project, args: SyncArgs) -> miette::Result<()> {
+    if gitignore::find_git_root(project.root()).is_none() {
+        tracing::warn!("the project is not in a git repository, the .gitignore has no effect");
+    }
+
+    let path = project.root().join(".gitignore");
+    let current = std::fs::read_to_string(&path).unwrap_or_default();
+    let ignore_lock_file = if args.ignore_lock_file {
+        true
+    } else if args.commit_lock_file {
+        false
+    } else {
+        gitignore::ignores_lock_file(&current).unwrap_or(false)
+    };
+
+    if gitignore::sync_file(project.root(), ignore_lock_file).into_diagnostic()? {
+        eprintln!(
+            "{}Updated {}, {} is {}",
+            console::style(console::Emoji("✔ ", "")).green(),
+            path.display(),
+            consts::PROJECT_LOCK_FILE,
+            if ignore_lock_file {
+                "ignored"
+            } else {
+                "committed"
+            }
+        );
+    } else {
+        eprintln!(
+            "{}{} is up to date",
+            console::style(console::Emoji("✔ ", "")).green(),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+
+    match args.command {
+        Command::Sync(sync_args) => execute_sync(&project, sync_args),
+    }
+}
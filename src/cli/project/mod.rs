@@ -2,14 +2,20 @@ use clap::Parser;
 use std::path::PathBuf;
 
 pub mod channel;
+pub mod check;
 pub mod description;
+pub mod export;
+pub mod gitignore;
 pub mod platform;
 pub mod version;
 
 #[derive(Debug, Parser)]
 pub enum Command {
     Channel(channel::Args),
+    Check(check::Args),
     Description(description::Args),
+    Export(export::Args),
+    Gitignore(gitignore::Args),
     Platform(platform::Args),
     Version(version::Args),
 }
@@ -19,7 +25,7 @@ pub enum Command {
 pub struct Args {
     #[command(subcommand)]
     command: Command,
-    /// The path to 'pixi.toml'
+    /// The path to 'pixi.toml' or the directory that contains it
     #[arg(long)]
     pub manifest_path: Option<PathBuf>,
 }
@@ -27,7 +33,10 @@ pub struct Args {
 pub async fn execute(cmd: Args) -> miette::Result<()> {
     match cmd.command {
         Command::Channel(args) => channel::execute(args).await?,
+        Command::Check(args) => check::execute(args).await?,
         Command::Description(args) => description::execute(args).await?,
+        Command::Export(args) => export::execute(args).await?,
+        Command::Gitignore(args) => gitignore::execute(args).await?,
         Command::Platform(args) => platform::execute(args).await?,
         Command::Version(args) => version::execute(args).await?,
     };
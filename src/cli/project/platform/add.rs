@@ -1,11 +1,8 @@
-use std::str::FromStr;
-
 use crate::environment::{get_up_to_date_prefix, LockFileUsage};
+use crate::project::manifest::parse_platform;
 use crate::Project;
 use clap::Parser;
 use itertools::Itertools;
-use miette::IntoDiagnostic;
-use rattler_conda_types::Platform;
 
 #[derive(Parser, Debug, Default)]
 pub struct Args {
@@ -23,9 +20,9 @@ pub async fn execute(mut project: Project, args: Args) -> miette::Result<()> {
     let platforms = args
         .platform
         .into_iter()
-        .map(|platform_str| Platform::from_str(&platform_str))
+        .map(|platform_str| parse_platform(&platform_str))
         .collect::<Result<Vec<_>, _>>()
-        .into_diagnostic()?;
+        .map_err(|err| miette::miette!("{err}"))?;
 
     let missing_platforms = platforms
         .into_iter()
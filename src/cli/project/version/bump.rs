@@ -0,0 +1,150 @@
+use crate::Project;
+use clap::Parser;
+use itertools::Itertools;
+
+#[derive(Parser, Debug, Default)]
+pub struct Args {}
+
+/// The part of the version that is bumped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionBump {
+    Major,
+    Minor,
+    Patch,
+}
+
+impl VersionBump {
+    /// The index of the bumped segment in the version.
+    fn segment(self) -> usize {
+        match self {
+            VersionBump::Major => 0,
+            VersionBump::Minor => 1,
+            VersionBump::Patch => 2,
+        }
+    }
+}
+
+pub async fn execute(mut project: Project, bump: VersionBump) -> miette::Result<()> {
+    let Some(version) = written_version(&project.manifest.document) else {
+        miette::bail!(
+            help = "set it with `pixi project version set <VERSION>`",
+            "the project has no version"
+        );
+    };
+    let new_version = bump_version(&version, bump)?;
+
+    // Set the version and save the manifest on disk
+    project.manifest.set_version(&new_version)?;
+    project.save()?;
+
+    // Report back to the user
+    eprintln!(
+        "{}Updated project version from '{}' to '{}'.",
+        console::style(console::Emoji("✔ ", "")).green(),
+        version,
+        new_version
+    );
+
+    Ok(())
+}
+
+/// Returns the version as it is written in the manifest, not its normalized form, so that is what
+/// is bumped.
+fn written_version(document: &toml_edit::Document) -> Option<String> {
+    document
+        .get("project")
+        .and_then(|project| project.get("version"))
+        .and_then(|version| version.as_str())
+        .map(str::to_string)
+}
+
+/// Increments a segment of a version of numbers separated by dots, e.g. `1.2.3`, and resets the
+/// segments after it to zero. Missing segments up to the bumped one are added, so the patch
+/// version of `1.2` is bumped to `1.2.1`. Segments keep their zero padding, so calendar versions
+/// like `2024.01` are bumped to `2024.02`.
+fn bump_version(version: &str, bump: VersionBump) -> miette::Result<String> {
+    // The value of every segment together with its width.
+    let mut segments = version
+        .split('.')
+        .map(|segment| {
+            let digits = segment.bytes().all(|b| b.is_ascii_digit());
+            Some((segment.parse::<u64>().ok().filter(|_| digits)?, segment.len()))
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| {
+            miette::miette!(
+                help = "set the new version with `pixi project version set <VERSION>`",
+                "the version '{version}' can't be bumped, only versions of numbers separated by dots are supported"
+            )
+        })?;
+
+    let index = bump.segment();
+    if segments.len() <= index {
+        segments.resize(index + 1, (0, 1));
+    }
+    segments[index].0 += 1;
+    for (segment, _) in segments.iter_mut().skip(index + 1) {
+        *segment = 0;
+    }
+    Ok(segments
+        .iter()
+        .map(|(segment, width)| format!("{segment:0width$}"))
+        .join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_written_version() {
+        let document = "[project]\nname = \"foo\"\nversion = \"1.02\"\n"
+            .parse()
+            .unwrap();
+        assert_eq!(written_version(&document).as_deref(), Some("1.02"));
+        let document = "[project]\nname = \"foo\"\n".parse().unwrap();
+        assert_eq!(written_version(&document), None);
+        assert_eq!(written_version(&"".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_bump_version() {
+        assert_eq!(bump_version("1.2.3", VersionBump::Major).unwrap(), "2.0.0");
+        assert_eq!(bump_version("1.2.3", VersionBump::Minor).unwrap(), "1.3.0");
+        assert_eq!(bump_version("1.2.3", VersionBump::Patch).unwrap(), "1.2.4");
+        assert_eq!(bump_version("0.9", VersionBump::Minor).unwrap(), "0.10");
+        assert_eq!(bump_version("1.2", VersionBump::Patch).unwrap(), "1.2.1");
+        assert_eq!(bump_version("1", VersionBump::Minor).unwrap(), "1.1");
+        assert_eq!(
+            bump_version("2023.10.1.4", VersionBump::Minor).unwrap(),
+            "2023.11.0.0"
+        );
+        assert!(bump_version("1.2.3rc1", VersionBump::Patch).is_err());
+        assert!(bump_version("1.+2", VersionBump::Patch).is_err());
+        assert!(bump_version("1!2.0", VersionBump::Major).is_err());
+    }
+
+    #[test]
+    fn test_bump_calendar_version() {
+        assert_eq!(
+            bump_version("2024.01", VersionBump::Minor).unwrap(),
+            "2024.02"
+        );
+        assert_eq!(
+            bump_version("2024.09", VersionBump::Minor).unwrap(),
+            "2024.10"
+        );
+        assert_eq!(
+            bump_version("2024.01.05", VersionBump::Minor).unwrap(),
+            "2024.02.00"
+        );
+        assert_eq!(
+            bump_version("2024.12.31", VersionBump::Major).unwrap(),
+            "2025.00.00"
+        );
+        assert_eq!(
+            bump_version("2024.01", VersionBump::Patch).unwrap(),
+            "2024.01.1"
+        );
+    }
+}
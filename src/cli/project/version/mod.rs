@@ -1,14 +1,16 @@
+pub mod bump;
 pub mod get;
 pub mod set;
 
 use crate::Project;
+use bump::VersionBump;
 use clap::Parser;
 use std::path::PathBuf;
 
-/// Commands to manage project description.
+/// Commands to manage project version.
 #[derive(Parser, Debug)]
 pub struct Args {
-    /// The path to 'pixi.toml'
+    /// The path to 'pixi.toml' or the directory that contains it
     #[clap(long, global = true)]
     pub manifest_path: Option<PathBuf>,
 
@@ -23,6 +25,12 @@ pub enum Command {
     Get(get::Args),
     /// Set the project version.
     Set(set::Args),
+    /// Bump the major version of the project, e.g. `1.2.3` to `2.0.0`.
+    Major(bump::Args),
+    /// Bump the minor version of the project, e.g. `1.2.3` to `1.3.0`.
+    Minor(bump::Args),
+    /// Bump the patch version of the project, e.g. `1.2.3` to `1.2.4`.
+    Patch(bump::Args),
 }
 
 pub async fn execute(args: Args) -> miette::Result<()> {
@@ -31,6 +39,9 @@ pub async fn execute(args: Args) -> miette::Result<()> {
     match args.command {
         Command::Get(args) => get::execute(project, args).await?,
         Command::Set(args) => set::execute(project, args).await?,
+        Command::Major(_) => bump::execute(project, VersionBump::Major).await?,
+        Command::Minor(_) => bump::execute(project, VersionBump::Minor).await?,
+        Command::Patch(_) => bump::execute(project, VersionBump::Patch).await?,
     }
 
     Ok(())
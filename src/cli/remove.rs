@@ -15,7 +15,7 @@ pub struct Args {
     #[arg(required = true)]
     pub deps: Vec<String>,
 
-    /// The path to 'pixi.toml'
+    /// The path to 'pixi.toml' or the directory that contains it
     #[arg(long)]
     pub manifest_path: Option<PathBuf>,
 
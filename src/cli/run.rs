@@ -1,16 +1,30 @@
-use std::{collections::HashMap, path::PathBuf, string::String};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::{str::FromStr, string::String, sync::Arc};
 
 use clap::Parser;
 use itertools::Itertools;
 use miette::{miette, Context, Diagnostic, IntoDiagnostic};
-use rattler_conda_types::Platform;
+use rattler_conda_types::{PackageName, Platform};
 
+use super::global::install::{find_designated_package, find_executables};
+use super::pipeline;
 use crate::environment::LockFileUsage;
+use crate::lock_file::{self, load_lock_file, lock_file_satisfies_project};
+use crate::remote::RemoteHost;
 use crate::task::{
-    ExecutableTask, FailedToParseShellScript, InvalidWorkingDirectory, TraversalError,
+    self, quote_arguments, ExecutableTask, FailedToParseShellScript, InvalidTemplate,
+    InvalidWorkingDirectory, ProcessGroup, RemoteTaskCache, Signals, Task, TaskCache,
+    TemplateVariables, TraversalError,
 };
+use crate::warnings::{self, WarningCode};
 use crate::{
-    environment::get_up_to_date_prefix, prefix::Prefix, progress::await_in_progress, Project,
+    activation_cache,
+    environment::{get_up_to_date_prefix, install_new_prefix},
+    interrupt,
+    prefix::Prefix,
+    progress::await_in_progress,
+    trust, Project,
 };
 use rattler_shell::{
     activation::{ActivationVariables, Activator, PathModificationBehavior},
@@ -19,31 +33,112 @@ use rattler_shell::{
 use thiserror::Error;
 use tracing::Level;
 
+/// The first argument of `pixi run` that runs a pipeline instead of a task.
+const PIPELINE_COMMAND: &str = "pipeline";
+
 /// Runs task in project.
 #[derive(Parser, Debug, Default)]
-#[clap(trailing_var_arg = true, arg_required_else_help = true)]
+#[clap(trailing_var_arg = true)]
 pub struct Args {
-    /// The task you want to run in the projects environment.
+    /// The task you want to run in the projects environment, defaults to the `default-task` of
+    /// the project.
     pub task: Vec<String>,
 
-    /// The path to 'pixi.toml'
+    /// The path to 'pixi.toml' or the directory that contains it
     #[arg(long)]
     pub manifest_path: Option<PathBuf>,
 
+    /// The environment to use, one for every variant of the dependencies of the project or every
+    /// environment of the `[environments]` table
+    #[arg(long, short)]
+    pub environment: Option<String>,
+
+    /// The platform to install the environment for, e.g. `osx-64` to run it through Rosetta on
+    /// Apple Silicon. Defaults to the platform of this machine
+    #[arg(long)]
+    pub platform: Option<Platform>,
+
     #[clap(flatten)]
     pub lock_file_usage: super::LockFileUsageArgs,
+
+    /// Run the tasks in a pseudo-terminal, as if the `interactive` option was set on all of them
+    #[arg(long)]
+    pub pty: bool,
+
+    /// Run the tasks in a clean environment, with only the environment variables of the host that
+    /// the `passthrough-env` of the project lists, e.g. `["HOME", "AWS_*"]`
+    #[arg(long, conflicts_with = "host")]
+    pub clean_env: bool,
+
+    /// Run the task on another machine over SSH, e.g. `user@server`. The manifest, the files it
+    /// includes and the lock file are copied to the host and pixi on the host installs the
+    /// environment and runs the task
+    #[arg(long, value_name = "DESTINATION")]
+    pub host: Option<String>,
+
+    /// The directory of the project on the host, defaults to the same path relative to the home
+    /// directory as on this machine
+    #[arg(long, requires = "host")]
+    pub remote_dir: Option<String>,
+
+    /// Save the command as a task with this name in the manifest if it succeeds, e.g.
+    /// `pixi run --save-as lint -- ruff check .`
+    #[arg(long, value_name = "NAME", conflicts_with = "host")]
+    pub save_as: Option<String>,
+
+    /// Run the task in an ephemeral environment with the lowest versions of the dependencies that
+    /// the manifest allows, to test the minimum supported versions. The lock file and the
+    /// environment of the project are not changed
+    #[arg(long, conflicts_with_all = ["host", "save_as"])]
+    pub with_lowest_deps: bool,
+
+    /// Run a binary of this locked conda package, e.g. `pixi run --package gcc gcc --version`.
+    /// Only the activation scripts of the package run instead of the activation of the whole
+    /// environment, which starts the binary faster
+    #[arg(long, value_name = "PACKAGE", conflicts_with_all = ["host", "save_as", "with_lowest_deps"])]
+    pub package: Option<String>,
 }
 
 /// CLI entry point for `pixi run`
-/// When running the sigints are ignored and child can react to them. As it pleases.
+/// When running, signals are forwarded to the processes of the task and they can react to them as
+/// they please.
 pub async fn execute(args: Args) -> miette::Result<()> {
-    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+    let mut project = Project::load_or_else_discover(args.manifest_path.as_deref())?
+        .with_environment(args.environment.as_deref())?
+        .with_platform(args.platform)?
+        .with_clean_env(args.clean_env);
+
+    if let Some(name) = args.save_as {
+        return execute_and_save(
+            &mut project,
+            &name,
+            args.task,
+            args.lock_file_usage.into(),
+            args.pty,
+        )
+        .await;
+    }
+
+    // The task is passed on as is, pixi on the host determines the default task of its platform.
+    if let Some(host) = args.host {
+        let host = RemoteHost::new(host, args.remote_dir, &project)?;
+        return execute_remote(
+            &project,
+            &host,
+            &args.task,
+            args.lock_file_usage.into(),
+            args.pty,
+        )
+        .await;
+    }
 
     // Split 'task' into arguments if it's a single string, supporting commands like:
     // `"test 1 == 0 || echo failed"` or `"echo foo && echo bar"` or `"echo 'Hello World'"`
     // This prevents shell interpretation of pixi run inputs.
     // Use as-is if 'task' already contains multiple elements.
-    let task_args = if args.task.len() == 1 {
+    let task_args = if args.task.is_empty() {
+        vec![default_task(&project)?]
+    } else if args.task.len() == 1 {
         shlex::split(args.task[0].as_str())
             .ok_or(miette!("Could not split task, assuming non valid task"))?
     } else {
@@ -51,30 +146,430 @@ pub async fn execute(args: Args) -> miette::Result<()> {
     };
     tracing::debug!("Task parsed from run command: {:?}", task_args);
 
+    if args.with_lowest_deps {
+        return execute_with_lowest_deps(&project, task_args, args.pty).await;
+    }
+
+    if let Some(package) = args.package {
+        return execute_package_binary(&project, &package, task_args, args.lock_file_usage.into())
+            .await;
+    }
+
+    // `pixi run pipeline <name>` runs a pipeline, unless the project has a task named `pipeline`.
+    if task_args.first().map(String::as_str) == Some(PIPELINE_COMMAND)
+        && project
+            .task_opt(PIPELINE_COMMAND, Some(project.platform()))
+            .is_none()
+    {
+        return pipeline::execute(
+            &project,
+            &task_args[1..],
+            args.lock_file_usage.into(),
+            args.pty,
+        )
+        .await;
+    }
+
+    execute_task_args(&project, task_args, args.lock_file_usage.into(), args.pty).await
+}
+
+/// Runs an ad-hoc command and, if it succeeds, saves it as a task called `name` in the manifest.
+/// Exits the process if the command fails with a non-zero exit code, nothing is saved then.
+async fn execute_and_save(
+    project: &mut Project,
+    name: &str,
+    command: Vec<String>,
+    lock_file_usage: LockFileUsage,
+    pty: bool,
+) -> miette::Result<()> {
+    if command.is_empty() {
+        miette::bail!("`--save-as` requires the command to run, e.g. `pixi run --save-as lint -- ruff check .`");
+    }
+    let tasks = project.manifest.tasks(None);
+    if tasks.contains_key(name) {
+        miette::bail!(
+            help = "remove it with `pixi task remove` first",
+            "the task '{name}' already exists"
+        );
+    }
+    // A task that is saved as the command of another task would not run as a task.
+    if let Some(first) = command
+        .first()
+        .filter(|first| tasks.contains_key(first.as_str()))
+    {
+        miette::bail!(
+            help = format!("create an alias with `pixi task alias {name} {first}` instead"),
+            "'{first}' is a task of the project, only commands can be saved"
+        );
+    }
+
+    // A single argument is saved as is, so operators like `&&` keep their meaning.
+    let cmd = if command.len() == 1 {
+        command[0].clone()
+    } else {
+        quote_arguments(command.iter().map(String::as_str))
+    };
+    let task_args =
+        shlex::split(&cmd).ok_or(miette!("Could not split task, assuming non valid task"))?;
+
+    let code = run_task_args(project, task_args, lock_file_usage, pty, HashMap::new()).await?;
+    if code != 0 {
+        eprintln!(
+            "{} the command failed, the task '{name}' is not saved",
+            console::style("!").yellow().bold(),
+        );
+        std::process::exit(code);
+    }
+
+    let task = Task::Plain(cmd);
+    project.manifest.add_task(name, task.clone(), None)?;
+    project.save()?;
+    eprintln!(
+        "{}Added task {}: {}",
+        console::style(console::Emoji("✔ ", "+")).green(),
+        console::style(name).bold(),
+        task,
+    );
+    Ok(())
+}
+
+/// Returns the name of the default task of the project, it must be available on the current
+/// platform.
+pub(crate) fn default_task(project: &Project) -> miette::Result<String> {
+    let Some(name) = project.default_task() else {
+        let tasks = project.task_names(Some(project.platform()));
+        return Err(miette!(
+            help = if tasks.is_empty() {
+                String::from("add a task with `pixi task add` and set it as the `default-task` in the [project] table")
+            } else {
+                format!(
+                    "specify one of the tasks {} or set the `default-task` in the [project] table",
+                    tasks
+                        .iter()
+                        .sorted()
+                        .map(|name| format!("'{name}'"))
+                        .join(", ")
+                )
+            },
+            "no task specified and the project has no default task"
+        ));
+    };
+    if project.task_opt(name, Some(project.platform())).is_none() {
+        miette::bail!(
+            "the default task '{name}' is not available on {}",
+            project.platform()
+        );
+    }
+    Ok(name.to_string())
+}
+
+/// Runs the task (or command) described by `task_args` and its dependencies in the environment of
+/// the project. Exits the process if the task fails with a non-zero exit code.
+pub(crate) async fn execute_task_args(
+    project: &Project,
+    task_args: Vec<String>,
+    lock_file_usage: LockFileUsage,
+    pty: bool,
+) -> miette::Result<()> {
+    match run_task_args(project, task_args, lock_file_usage, pty, HashMap::new()).await? {
+        0 => Ok(()),
+        // If one of the tasks failed with a non-zero exit code, we exit this parent process with
+        // the same code.
+        code => std::process::exit(code),
+    }
+}
+
+/// Runs the task (or command) described by `task_args` and its dependencies in the environment of
+/// the project, with `extra_env` added to the environment variables. Returns the exit code of the
+/// task that failed, or zero if all of them succeeded.
+pub(crate) async fn run_task_args(
+    project: &Project,
+    task_args: Vec<String>,
+    lock_file_usage: LockFileUsage,
+    pty: bool,
+    extra_env: HashMap<String, String>,
+) -> miette::Result<i32> {
+    // Get the environment to run the commands in.
+    let mut command_env = get_task_env(project, lock_file_usage).await?;
+    command_env.extend(extra_env);
+
+    // The versions of the locked packages can be used in the commands of the tasks.
+    let lock_file = load_lock_file(project).await?;
+    let variables = TemplateVariables::from_lock_file(&lock_file, project.platform());
+
+    run_tasks(project, task_args, &command_env, variables, pty).await
+}
+
+/// Runs the task (or command) described by `task_args` and its dependencies with the environment
+/// variables `command_env`. Returns the exit code of the task that failed, or zero if all of them
+/// succeeded.
+async fn run_tasks(
+    project: &Project,
+    task_args: Vec<String>,
+    command_env: &HashMap<String, String>,
+    variables: TemplateVariables,
+    pty: bool,
+) -> miette::Result<i32> {
     // Get the task to execute
     let executable_task =
-        ExecutableTask::from_cmd_args(&project, task_args, Some(Platform::current()));
+        ExecutableTask::from_cmd_args(project, task_args, Some(project.platform()))
+            .with_variables(Arc::new(variables));
 
-    // Get the environment to run the commands in.
-    let command_env = get_task_env(&project, args.lock_file_usage.into()).await?;
+    // Run the tasks in a process group of their own so signals can be forwarded to all the
+    // processes they spawn.
+    let process_group = ProcessGroup::enter();
 
     // Traverse the task and its dependencies. Execute each task in order.
-    match executable_task
+    let result = executable_task
         .traverse(
             (),
-            |_, task| execute_task(task, &command_env),
-            |_, _task| async { true },
+            |_, task| execute_task(task, command_env, &process_group, pty),
+            |_, task| {
+                let run = task.conditions_hold(command_env);
+                if !run && tracing::enabled!(Level::WARN) {
+                    eprintln!(
+                        "{}{} {}",
+                        console::style("⏭ Pixi task (skipped): ").bold(),
+                        task.display_command(),
+                        console::style("its conditions do not hold").dim(),
+                    );
+                }
+                async move { run }
+            },
         )
+        .await;
+
+    // Restore the process group before returning, the caller might exit the process which does not
+    // run destructors.
+    drop(process_group);
+
+    match result {
+        Ok(_) => Ok(0),
+        Err(TaskExecutionError::NonZeroExitCode(code)) => Ok(code),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Runs the task in an ephemeral environment with the lowest versions of the direct dependencies
+/// that can be solved. The environment is removed afterwards. Exits the process if the task fails
+/// with a non-zero exit code.
+async fn execute_with_lowest_deps(
+    project: &Project,
+    task_args: Vec<String>,
+    pty: bool,
+) -> miette::Result<()> {
+    let platform = project.platform();
+    let (lock_file, pins) = lock_file::solve_lowest(project, platform).await?;
+    for pin in &pins {
+        eprintln!(
+            "{}Using {}",
+            console::style(console::Emoji("⬇ ", "")).cyan(),
+            console::style(pin).bold()
+        );
+    }
+
+    // The environment lives next to the environment of the project, so packages can be hard
+    // linked from the cache.
+    std::fs::create_dir_all(project.pixi_dir()).into_diagnostic()?;
+    let dir = tempfile::Builder::new()
+        .prefix("lowest-deps-")
+        .tempdir_in(project.pixi_dir())
+        .into_diagnostic()?;
+    let prefix = install_new_prefix(project, &lock_file, &dir.path().join("env")).await?;
+
+    let command_env = project
+        .host_env()
+        .into_iter()
+        .chain(run_activation_async(project, prefix).await?)
+        .chain(project.get_metadata_env())
+        .collect::<HashMap<_, _>>();
+    let variables = TemplateVariables::from_lock_file(&lock_file, platform);
+    let code = run_tasks(project, task_args, &command_env, variables, pty).await?;
+
+    // Remove the environment before exiting, exiting the process doesn't run destructors.
+    drop(dir);
+    match code {
+        0 => Ok(()),
+        code => std::process::exit(code),
+    }
+}
+
+/// Runs a binary of a locked conda package with only the activation of that package: the
+/// environment variables of its activation scripts and the directories of the environment in
+/// `PATH`. The activation scripts of the project and of the other packages don't run. Exits the
+/// process with the exit code of the binary.
+async fn execute_package_binary(
+    project: &Project,
+    package: &str,
+    args: Vec<String>,
+    lock_file_usage: LockFileUsage,
+) -> miette::Result<()> {
+    let package_name = PackageName::from_str(package).into_diagnostic()?;
+    let Some((binary, args)) = args.split_first() else {
+        miette::bail!(
+            "`--package` requires the binary to run, e.g. `pixi run --package {package} {package}`"
+        );
+    };
+
+    let prefix = get_up_to_date_prefix(project, lock_file_usage, false, None).await?;
+    let record = find_designated_package(&prefix, &package_name)
         .await
+        .map_err(|_| {
+            miette!(
+                help = "add it with `pixi add`, or run the binary without `--package`",
+                "'{}' is not a conda package of the environment for {}",
+                package_name.as_source(),
+                project.platform()
+            )
+        })?;
+
+    let executables = find_executables(&prefix, &record);
+    let Some(executable) = find_package_binary(&executables, binary) else {
+        let names = executables
+            .iter()
+            .filter_map(|path| path.file_stem())
+            .map(|name| name.to_string_lossy())
+            .sorted()
+            .dedup()
+            .join(", ");
+        return Err(miette!(
+            help = if names.is_empty() {
+                String::from("the package has no binaries")
+            } else {
+                format!("the binaries of the package are {names}")
+            },
+            "'{binary}' is not a binary of '{}'",
+            package_name.as_source()
+        ));
+    };
+    let executable = prefix.root().join(executable);
+
+    let package_files = record
+        .files
+        .iter()
+        .map(|file| prefix.root().join(file))
+        .collect::<HashSet<_>>();
+    let command_env = project
+        .host_env()
+        .into_iter()
+        .chain(run_package_activation(prefix, package_files).await?)
+        .chain(project.get_metadata_env())
+        .collect::<HashMap<_, _>>();
+
+    let mut command = std::process::Command::new(&executable);
+    command.args(args).env_clear().envs(command_env);
+
+    // On unix pixi is replaced by the binary, so signals go to it directly.
+    #[cfg(unix)]
+    let err = std::os::unix::process::CommandExt::exec(&mut command);
+    #[cfg(not(unix))]
+    let err = match command.status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(err) => err,
+    };
+    Err(err)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to run '{}'", executable.display()))
+}
+
+/// Returns the executable of a package with the given name, with or without its extension, e.g.
+/// `python` for `python.exe` on Windows.
+fn find_package_binary<'a>(executables: &[&'a Path], name: &str) -> Option<&'a Path> {
+    let name = Path::new(name);
+    executables
+        .iter()
+        .find(|path| path.file_name() == name.file_name())
+        .or_else(|| {
+            executables
+                .iter()
+                .find(|path| path.file_stem() == name.file_name())
+        })
+        .copied()
+}
+
+/// Runs the activation of a single package: the directories of the environment are added to
+/// `PATH` and only the activation scripts in `package_files` run. Without activation scripts no
+/// shell is started.
+async fn run_package_activation(
+    prefix: Prefix,
+    package_files: HashSet<PathBuf>,
+) -> miette::Result<HashMap<String, String>> {
+    tokio::task::spawn_blocking(move || {
+        let mut activator =
+            Activator::from_path(prefix.root(), ShellEnum::default(), Platform::current())
+                .into_diagnostic()?;
+        activator
+            .activation_scripts
+            .retain(|script| package_files.contains(script));
+
+        if activator.activation_scripts.is_empty() {
+            let path = std::env::var_os("PATH").unwrap_or_default();
+            let path = std::env::join_paths(
+                activator
+                    .paths
+                    .iter()
+                    .cloned()
+                    .chain(std::env::split_paths(&path)),
+            )
+            .into_diagnostic()?;
+            return Ok(HashMap::from([
+                (String::from("PATH"), path.to_string_lossy().into_owned()),
+                (
+                    String::from("CONDA_PREFIX"),
+                    prefix.root().to_string_lossy().into_owned(),
+                ),
+            ]));
+        }
+
+        activator
+            .run_activation(ActivationVariables {
+                path: Default::default(),
+                conda_prefix: None,
+                path_modification_behavior: PathModificationBehavior::Prepend,
+            })
+            .into_diagnostic()
+    })
+    .await
+    .into_diagnostic()?
+}
+
+/// Runs the task on a remote host with the environment of the lock file of this machine. The lock
+/// file is updated first if needed, but nothing is installed on this machine. Exits the process
+/// with the exit code of the task if it fails.
+async fn execute_remote(
+    project: &Project,
+    host: &RemoteHost,
+    task_args: &[String],
+    lock_file_usage: LockFileUsage,
+    pty: bool,
+) -> miette::Result<()> {
+    // Only the lock file is updated, the project doesn't have to support the platform of this
+    // machine.
+    let lock_file = load_lock_file(project).await?;
+    if lock_file_usage.should_check_if_out_of_date()
+        && !lock_file_satisfies_project(project, &lock_file)?
     {
-        Ok(_) => Ok(()),
-        Err(TaskExecutionError::NonZeroExitCode(code)) => {
-            // If one of the tasks failed with a non-zero exit code, we exit this parent process
-            // with the same code.
-            std::process::exit(code);
+        if !lock_file_usage.allows_lock_file_updates() {
+            miette::bail!("lockfile not up-to-date with the project");
         }
-        Err(err) => Err(err.into()),
+        let lock_file = lock_file::update_lock_file_conda(project, lock_file, None).await?;
+        if project.has_pypi_dependencies() {
+            lock_file::update_lock_file_for_pypi(project, lock_file).await?;
+        }
+    }
+
+    eprintln!(
+        "{}Syncing the project to {}",
+        console::style(console::Emoji("🔄 ", "")).bold(),
+        console::style(host.destination()).bold()
+    );
+    host.sync(project)?;
+
+    let code = host.run(task_args, project.environment_name(), pty)?;
+    if code != 0 {
+        std::process::exit(code);
     }
+    Ok(())
 }
 
 #[derive(Debug, Error, Diagnostic)]
@@ -88,8 +583,71 @@ enum TaskExecutionError {
     #[error(transparent)]
     InvalidWorkingDirectory(#[from] InvalidWorkingDirectory),
 
+    #[error(transparent)]
+    InvalidTemplate(#[from] InvalidTemplate),
+
     #[error(transparent)]
     TraverseError(#[from] TraversalError),
+
+    #[error("failed to install the signal handlers")]
+    SignalHandler(#[source] std::io::Error),
+
+    #[error("failed to run the task in a pseudo-terminal")]
+    Pty(#[source] std::io::Error),
+}
+
+impl From<task::TaskExecutionError> for TaskExecutionError {
+    fn from(err: task::TaskExecutionError) -> Self {
+        match err {
+            task::TaskExecutionError::InvalidWorkingDirectory(err) => err.into(),
+            task::TaskExecutionError::FailedToParseShellScript(err) => err.into(),
+            task::TaskExecutionError::InvalidTemplate(err) => err.into(),
+        }
+    }
+}
+
+/// Returns true if tasks can be executed in a pseudo-terminal. This requires pixi itself to be
+/// connected to a terminal, pseudo-terminals are not supported on Windows.
+fn pty_available() -> bool {
+    if cfg!(unix) && console::Term::stdout().is_term() && console::Term::stderr().is_term() {
+        return true;
+    }
+    static WARNED: std::sync::Once = std::sync::Once::new();
+    WARNED.call_once(|| {
+        tracing::warn!("a pseudo-terminal is not available, running the task without one");
+    });
+    false
+}
+
+/// Executes a command with the system shell in a pseudo-terminal and returns its exit code.
+#[cfg(unix)]
+async fn execute_in_pty(
+    command: String,
+    command_env: HashMap<String, String>,
+    cwd: PathBuf,
+) -> std::io::Result<i32> {
+    tokio::task::spawn_blocking(move || {
+        let mut process = std::process::Command::new("sh");
+        process
+            .arg("-c")
+            .arg(command)
+            .env_clear()
+            .envs(command_env)
+            .current_dir(cwd);
+        let mut session = crate::unix::PtySession::new(process)?;
+        Ok(session.interact()?.unwrap_or(1))
+    })
+    .await
+    .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?
+}
+
+#[cfg(not(unix))]
+async fn execute_in_pty(
+    _command: String,
+    _command_env: HashMap<String, String>,
+    _cwd: PathBuf,
+) -> std::io::Result<i32> {
+    unreachable!("pseudo-terminals are only available on unix")
 }
 
 /// Called to execute a single command.
@@ -98,18 +656,81 @@ enum TaskExecutionError {
 async fn execute_task<'p>(
     task: ExecutableTask<'p>,
     command_env: &HashMap<String, String>,
+    process_group: &ProcessGroup,
+    pty: bool,
 ) -> Result<(), TaskExecutionError> {
     let Some(script) = task.as_deno_script()? else {
         return Ok(());
     };
     let cwd = task.working_directory()?;
 
-    // Ignore CTRL+C
-    // Specifically so that the child is responsible for its own signal handling
-    // NOTE: one CTRL+C is registered it will always stay registered for the rest of the runtime of the program
-    // which is fine when using run in isolation, however if we start to use run in conjunction with
-    // some other command we might want to revaluate this.
-    let ctrl_c = tokio::spawn(async { while tokio::signal::ctrl_c().await.is_ok() {} });
+    // Skip the task if nothing that influences its result changed since the last successful run.
+    let cache = TaskCache::new(task.project().root());
+    let remote_cache = task
+        .project()
+        .config()
+        .task_cache
+        .as_ref()
+        .map(RemoteTaskCache::new);
+    let cached = match (task.name(), task.cache_key(command_env)) {
+        (Some(name), Ok(Some(key))) => Some((name, key)),
+        (_, Err(err)) => {
+            tracing::warn!("failed to compute the cache key of the task: {err}");
+            None
+        }
+        _ => None,
+    };
+    if let Some((name, key)) = &cached {
+        let outputs = task
+            .task()
+            .as_execute()
+            .map(|execute| execute.outputs.as_slice())
+            .unwrap_or_default();
+        if cache.is_up_to_date(name, key, &cwd, outputs) {
+            if tracing::enabled!(Level::WARN) {
+                eprintln!(
+                    "{}{} {}",
+                    console::style("✨ Pixi task (cached): ").bold(),
+                    task.display_command(),
+                    console::style("is up to date").dim(),
+                );
+            }
+            return Ok(());
+        }
+
+        // Try to download the outputs of the task from the remote cache.
+        if let Some(remote) = &remote_cache {
+            if !outputs.is_empty() {
+                match remote.download(key, &cwd).await {
+                    Ok(true) => {
+                        if let Err(err) = cache.store(name, key) {
+                            tracing::warn!("failed to store the cache key of the task: {err}");
+                        }
+                        if tracing::enabled!(Level::WARN) {
+                            eprintln!(
+                                "{}{} {}",
+                                console::style("✨ Pixi task (remote cache): ").bold(),
+                                task.display_command(),
+                                console::style("outputs downloaded").dim(),
+                            );
+                        }
+                        return Ok(());
+                    }
+                    Ok(false) => {}
+                    Err(err) => tracing::warn!("failed to query the remote task cache: {err:?}"),
+                }
+            }
+        }
+    }
+
+    // Listen for signals so they are forwarded to the processes of the task instead of terminating
+    // pixi, the processes are responsible for their own signal handling.
+    // NOTE: once the signals are registered they will always stay registered for the rest of the
+    // runtime of the program which is fine when using run in isolation, however if we start to use
+    // run in conjunction with some other command we might want to revaluate this.
+    let mut signals = Signals::new().map_err(TaskExecutionError::SignalHandler)?;
+    // Keep pixi alive when the task is interrupted with Ctrl+C, the task decides how to react.
+    let _interrupt_guard = interrupt::guard();
 
     // Showing which command is being run if the level and type allows it.
     if tracing::enabled!(Level::WARN) && !task.task().is_custom() {
@@ -120,18 +741,31 @@ async fn execute_task<'p>(
         );
     }
 
-    let execute_future =
-        deno_task_shell::execute(script, command_env.clone(), &cwd, Default::default());
-    let status_code = tokio::select! {
-        code = execute_future => code,
-        // This should never exit
-        _ = ctrl_c => { unreachable!("Ctrl+C should not be triggered") }
+    let status_code = if (pty || task.task().is_interactive()) && pty_available() {
+        // The command is executed in a pseudo-terminal, input (including Ctrl+C) is passed on to
+        // the process through the terminal.
+        let command = task.full_command()?.unwrap_or_default();
+        execute_in_pty(command, command_env.clone(), cwd.clone())
+            .await
+            .map_err(TaskExecutionError::Pty)?
+    } else {
+        let execute_future =
+            deno_task_shell::execute(script, command_env.clone(), &cwd, Default::default());
+        tokio::pin!(execute_future);
+        loop {
+            tokio::select! {
+                code = &mut execute_future => break code,
+                signal = process_group.forward_signal(&mut signals) => {
+                    tracing::debug!("forwarded signal {signal} to the task");
+                }
+            }
+        }
     };
     if status_code == 127 {
         let available_tasks = task
             .project()
             .manifest
-            .tasks(Some(Platform::current()))
+            .tasks(Some(task.project().platform()))
             .into_keys()
             .sorted()
             .collect_vec();
@@ -150,6 +784,25 @@ async fn execute_task<'p>(
         return Err(TaskExecutionError::NonZeroExitCode(status_code));
     }
 
+    // Remember the cache key of this successful run and share the outputs through the remote cache.
+    if let Some((name, key)) = cached {
+        if let Err(err) = cache.store(name, &key) {
+            tracing::warn!("failed to store the cache key of the task: {err}");
+        }
+        let outputs = task
+            .task()
+            .as_execute()
+            .map(|execute| execute.outputs.as_slice())
+            .unwrap_or_default();
+        if let Some(remote) = remote_cache.filter(|remote| remote.upload_enabled()) {
+            if !outputs.is_empty() {
+                if let Err(err) = remote.upload(&key, &cwd, outputs).await {
+                    tracing::warn!("failed to upload the outputs of the task: {err:?}");
+                }
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -171,7 +824,9 @@ pub async fn get_task_env(
     let manifest_env = project.get_metadata_env();
 
     // Construct command environment by concatenating the environments
-    Ok(std::env::vars()
+    Ok(project
+        .host_env()
+        .into_iter()
         .chain(activation_env.into_iter())
         .chain(manifest_env.into_iter())
         .collect())
@@ -182,38 +837,106 @@ pub async fn run_activation_async(
     project: &Project,
     prefix: Prefix,
 ) -> miette::Result<HashMap<String, String>> {
-    let platform = Platform::current();
+    let platform = project.platform();
     let additional_activation_scripts = project.activation_scripts(platform)?;
 
     // Check if the platform and activation script extension match. For Platform::Windows the extension should be .bat and for All other platforms it should be .sh or .bash.
     for script in additional_activation_scripts.iter() {
         let extension = script.extension().unwrap_or_default();
         if platform.is_windows() && extension != "bat" {
-            tracing::warn!("The activation script '{}' does not have the correct extension for the platform '{}'. The extension should be '.bat'.", script.display(), platform);
+            warnings::emit(WarningCode::ActivationScriptExtension, format!("The activation script '{}' does not have the correct extension for the platform '{}'. The extension should be '.bat'.", script.display(), platform));
         } else if !platform.is_windows() && extension != "sh" && extension != "bash" {
-            tracing::warn!("The activation script '{}' does not have the correct extension for the platform '{}'. The extension should be '.sh' or '.bash'.", script.display(), platform);
+            warnings::emit(WarningCode::ActivationScriptExtension, format!("The activation script '{}' does not have the correct extension for the platform '{}'. The extension should be '.sh' or '.bash'.", script.display(), platform));
         }
     }
 
-    await_in_progress(
+    // The activation scripts of the project run arbitrary code, only run them when it is trusted.
+    trust::ensure_trusted(project, &trust::project_scripts(project)?)?;
+
+    // Use the snapshot of a previous activation if nothing it depends on changed.
+    let package_scripts = project.package_activation_scripts_enabled();
+    let snapshot_hash = activation_cache::is_enabled()
+        .then(|| {
+            activation_cache::activation_hash(
+                prefix.root(),
+                platform,
+                &additional_activation_scripts,
+                package_scripts,
+                project.clean_env(),
+                std::env::vars_os(),
+            )
+            .map_err(|err| tracing::debug!("failed to hash the activation inputs: {err}"))
+            .ok()
+        })
+        .flatten();
+    if let Some(hash) = &snapshot_hash {
+        if let Some(env) = activation_cache::load(prefix.root(), hash) {
+            tracing::debug!("using the snapshot of the activated environment");
+            return Ok(env);
+        }
+    }
+
+    let root = prefix.root().to_path_buf();
+    let env = await_in_progress(
         "activating environment",
-        run_activation(prefix, additional_activation_scripts.into_iter().collect()),
+        run_activation(
+            prefix,
+            additional_activation_scripts.into_iter().collect(),
+            package_scripts,
+        ),
     )
     .await
-    .wrap_err("failed to activate environment")
+    .wrap_err("failed to activate environment")?;
+
+    // The activation starts from the environment of pixi and returns all variables, only keep the
+    // ones it changed so the variables of the host only reach the tasks through `host_env`.
+    let env = activation_changes(env);
+
+    if let Some(hash) = &snapshot_hash {
+        if let Err(err) = activation_cache::store(&root, hash, &env) {
+            tracing::debug!("failed to store the snapshot of the activated environment: {err}");
+        }
+    }
+    Ok(env)
+}
+
+/// Returns the variables of the activation that are not set to the same value in the environment
+/// of pixi, the variables the activation added or changed.
+fn activation_changes(env: HashMap<String, String>) -> HashMap<String, String> {
+    env.into_iter()
+        .filter(|(key, value)| {
+            std::env::var_os(key).as_deref() != Some(std::ffi::OsStr::new(value.as_str()))
+        })
+        .collect()
 }
 
 /// Runs and caches the activation script.
-async fn run_activation(
+///
+/// Like conda, the activation runs the scripts that the installed packages ship in
+/// `etc/conda/activate.d` (e.g. compilers setting `CC`) unless `package_scripts` is false. The
+/// `additional_activation_scripts` run after them.
+pub(crate) async fn run_activation(
     prefix: Prefix,
     additional_activation_scripts: Vec<PathBuf>,
+    package_scripts: bool,
 ) -> miette::Result<HashMap<String, String>> {
     let activator_result = tokio::task::spawn_blocking(move || {
         // Run and cache the activation script
         let shell: ShellEnum = ShellEnum::default();
 
-        // Construct an activator for the script
+        // Construct an activator for the script, it collects the activation scripts of the
+        // packages from the prefix.
         let mut activator = Activator::from_path(prefix.root(), shell, Platform::current())?;
+        if package_scripts {
+            for script in &activator.activation_scripts {
+                tracing::debug!(
+                    "running the package activation script '{}'",
+                    script.display()
+                );
+            }
+        } else {
+            activator.activation_scripts.clear();
+        }
         activator
             .activation_scripts
             .extend(additional_activation_scripts);
@@ -236,3 +959,76 @@ async fn run_activation(
 
     Ok(activator_result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_package_activation_scripts() {
+        // A compiler package that sets `CC` when the environment is activated.
+        let prefix_dir = tempfile::tempdir().unwrap();
+        let activate_d = prefix_dir.path().join("etc/conda/activate.d");
+        std::fs::create_dir_all(&activate_d).unwrap();
+        std::fs::write(
+            activate_d.join("activate-gcc.sh"),
+            "export CC=x86_64-conda-linux-gnu-cc\n",
+        )
+        .unwrap();
+
+        let prefix = Prefix::new(prefix_dir.path()).unwrap();
+        let env = run_activation(prefix.clone(), vec![], true).await.unwrap();
+        assert_eq!(env.get("CC").unwrap(), "x86_64-conda-linux-gnu-cc");
+
+        let env = run_activation(prefix, vec![], false).await.unwrap();
+        assert!(env.get("CC").is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_package_activation() {
+        // Two packages that set environment variables, only those of gcc are set.
+        let prefix_dir = tempfile::tempdir().unwrap();
+        let activate_d = prefix_dir.path().join("etc/conda/activate.d");
+        std::fs::create_dir_all(&activate_d).unwrap();
+        std::fs::write(activate_d.join("activate-gcc.sh"), "export CC=gcc\n").unwrap();
+        std::fs::write(activate_d.join("activate-gxx.sh"), "export CXX=g++\n").unwrap();
+
+        let prefix = Prefix::new(prefix_dir.path()).unwrap();
+        let package_files = HashSet::from([activate_d.join("activate-gcc.sh")]);
+        let env = run_package_activation(prefix.clone(), package_files)
+            .await
+            .unwrap();
+        assert_eq!(env.get("CC").unwrap(), "gcc");
+        assert!(env.get("CXX").is_none());
+
+        let env = run_package_activation(prefix, HashSet::new())
+            .await
+            .unwrap();
+        assert!(env.get("CC").is_none());
+        assert!(env["PATH"].starts_with(&prefix_dir.path().join("bin").display().to_string()));
+    }
+
+    #[test]
+    fn test_find_package_binary() {
+        let executables = [
+            Path::new("bin/python3.12"),
+            Path::new("bin/python"),
+            Path::new("Scripts/pip.exe"),
+        ];
+        assert_eq!(
+            find_package_binary(&executables, "python"),
+            Some(Path::new("bin/python"))
+        );
+        assert_eq!(
+            find_package_binary(&executables, "pip"),
+            Some(Path::new("Scripts/pip.exe"))
+        );
+        assert_eq!(
+            find_package_binary(&executables, "pip.exe"),
+            Some(Path::new("Scripts/pip.exe"))
+        );
+        assert_eq!(find_package_binary(&executables, "ruff"), None);
+    }
+}
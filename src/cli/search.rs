@@ -3,13 +3,15 @@ use std::{cmp::Ordering, path::PathBuf};
 use clap::Parser;
 use itertools::Itertools;
 use miette::IntoDiagnostic;
-use rattler_conda_types::{Channel, ChannelConfig, PackageName, Platform, RepoDataRecord};
+use rattler_conda_types::{Channel, PackageName, Platform, RepoDataRecord};
 use rattler_repodata_gateway::sparse::SparseRepoData;
 use regex::Regex;
 use strsim::jaro;
 use tokio::task::spawn_blocking;
 
-use crate::{progress::await_in_progress, repodata::fetch_sparse_repodata, Project};
+use crate::{
+    config::Config, progress::await_in_progress, repodata::fetch_sparse_repodata, Project,
+};
 
 /// Search a package, output will list the latest version of package
 #[derive(Debug, Parser)]
@@ -24,7 +26,7 @@ pub struct Args {
     #[clap(short, long)]
     channel: Option<Vec<String>>,
 
-    /// The path to 'pixi.toml'
+    /// The path to 'pixi.toml' or the directory that contains it
     #[arg(long)]
     pub manifest_path: Option<PathBuf>,
 
@@ -79,7 +81,8 @@ where
 pub async fn execute(args: Args) -> miette::Result<()> {
     let project = Project::load_or_else_discover(args.manifest_path.as_deref()).ok();
 
-    let channel_config = ChannelConfig::default();
+    let config = Config::load_global()?;
+    let channel_config = config.channel_config();
 
     let channels = match (args.channel, project) {
         // if user passes channels through the channel flag
@@ -91,7 +94,12 @@ pub async fn execute(args: Args) -> miette::Result<()> {
         // if user doesn't pass channels and we are in a project
         (None, Some(p)) => p.channels().to_owned(),
         // if user doesn't pass channels and we are not in project
-        (None, None) => vec![Channel::from_str("conda-forge", &channel_config).into_diagnostic()?],
+        (None, None) => config
+            .default_channels()
+            .iter()
+            .map(|c| Channel::from_str(c, &channel_config))
+            .collect::<Result<Vec<Channel>, _>>()
+            .into_diagnostic()?,
     };
 
     let package_name_filter = args.package;
@@ -1,12 +1,14 @@
-use crate::{prompt, Project};
+use crate::config::ShellName;
+use crate::{prompt, trust, Project};
 use clap::Parser;
+use itertools::Itertools;
 use miette::IntoDiagnostic;
 use rattler_conda_types::Platform;
 use rattler_shell::activation::PathModificationBehavior;
 use rattler_shell::shell::{PowerShell, Shell, ShellEnum, ShellScript};
 use std::collections::HashMap;
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[cfg(target_family = "unix")]
 use crate::unix::PtySession;
@@ -22,18 +24,96 @@ use super::run::run_activation_async;
 /// Start a shell in the pixi environment of the project
 #[derive(Parser, Debug)]
 pub struct Args {
-    /// The path to 'pixi.toml'
+    /// The path to 'pixi.toml' or the directory that contains it
     #[arg(long)]
     manifest_path: Option<PathBuf>,
 
+    /// The environment to use, one for every variant of the dependencies of the project or every
+    /// environment of the `[environments]` table
+    #[arg(long, short)]
+    environment: Option<String>,
+
+    /// The platform to install the environment for, e.g. `osx-64` to run it through Rosetta on
+    /// Apple Silicon. Defaults to the platform of this machine
+    #[arg(long)]
+    platform: Option<Platform>,
+
+    /// Don't load the rc files of the user, e.g. `~/.bashrc`, when starting the shell
+    #[arg(long)]
+    no_rc: bool,
+
+    /// Store the history of the shell in this file instead of the history of the user. Without a
+    /// value the history is stored in `.pixi/shell_history` of the project
+    #[arg(long, value_name = "FILE")]
+    history_file: Option<Option<PathBuf>>,
+
+    /// Run the command in the activated shell and exit with its exit code
+    #[arg(long, short)]
+    command: Option<String>,
+
+    /// The shell to start, instead of the shell from which pixi is started
+    #[arg(long, value_enum)]
+    shell: Option<ShellName>,
+
     #[clap(flatten)]
     lock_file_usage: LockFileUsageArgs,
 }
 
+/// The name of the file in the `.pixi` directory that stores the history of `pixi shell
+/// --history-file` without a value.
+const SHELL_HISTORY_FILE: &str = "shell_history";
+
+/// Options of the interactive shell that is started.
+#[derive(Debug, Default, Clone)]
+pub struct ShellOptions {
+    /// Don't load the rc files of the user
+    pub no_rc: bool,
+    /// The file that stores the history of the shell
+    pub history_file: Option<PathBuf>,
+    /// A command that is run in the shell, after which the shell exits
+    pub command: Option<String>,
+    /// The shell to start, instead of the shell of the user
+    pub shell: Option<ShellName>,
+    /// The shell to start if the shell of the user can't be detected, see `default-shell` in the
+    /// configuration
+    pub default_shell: Option<ShellName>,
+    /// The scripts that run when the shell exits
+    pub deactivation: DeactivationScripts,
+}
+
+/// Copies of the deactivation scripts that are made before the shell starts, so the scripts that
+/// run when it exits are the ones that were trusted even if they change in the meantime.
+#[derive(Debug, Default, Clone)]
+pub struct DeactivationScripts {
+    /// The scripts of the project, they run in every shell.
+    pub project: Vec<PathBuf>,
+    /// The scripts of the packages, only those for the started shell run.
+    pub packages: Vec<PathBuf>,
+}
+
+impl DeactivationScripts {
+    /// Returns the scripts that run when `shell` exits: the scripts of the project followed by
+    /// those of the packages that are written for the shell.
+    fn for_shell(&self, shell: &ShellEnum) -> Vec<PathBuf> {
+        let extension = package_script_extension(shell);
+        self.project
+            .iter()
+            .chain(
+                self.packages
+                    .iter()
+                    .filter(|script| script.extension().map_or(false, |ext| ext == extension)),
+            )
+            .cloned()
+            .collect()
+    }
+}
+
 fn start_powershell(
     pwsh: PowerShell,
     env: &HashMap<String, String>,
     prompt: String,
+    deactivation: Option<String>,
+    options: &ShellOptions,
 ) -> miette::Result<Option<i32>> {
     // create a tempfile for activation
     let mut temp_file = tempfile::Builder::new()
@@ -52,12 +132,32 @@ fn start_powershell(
     // Write custom prompt to the env file
     temp_file.write(prompt.as_bytes()).into_diagnostic()?;
 
+    if let Some(history_file) = &options.history_file {
+        writeln!(
+            temp_file,
+            "\nSet-PSReadLineOption -HistorySavePath '{}'",
+            history_file.display().to_string().replace('\'', "''")
+        )
+        .into_diagnostic()?;
+    }
+    if let Some(deactivation) = deactivation {
+        writeln!(temp_file, "\n{deactivation}").into_diagnostic()?;
+    }
+    if let Some(cmd) = &options.command {
+        writeln!(temp_file, "\n{cmd}\nexit $LASTEXITCODE").into_diagnostic()?;
+    }
+
     // close the file handle, but keep the path (needed for Windows)
     let temp_path = temp_file.into_temp_path();
 
     let mut command = std::process::Command::new(pwsh.executable());
     command.arg("-NoLogo");
-    command.arg("-NoExit");
+    if options.no_rc {
+        command.arg("-NoProfile");
+    }
+    if options.command.is_none() {
+        command.arg("-NoExit");
+    }
     command.arg("-File");
     command.arg(&temp_path);
 
@@ -70,6 +170,7 @@ fn start_cmdexe(
     cmdexe: CmdExe,
     env: &HashMap<String, String>,
     prompt: String,
+    options: &ShellOptions,
 ) -> miette::Result<Option<i32>> {
     // create a tempfile for activation
     let mut temp_file = tempfile::Builder::new()
@@ -89,8 +190,22 @@ fn start_cmdexe(
     // Write custom prompt to the env file
     temp_file.write(prompt.as_bytes()).into_diagnostic()?;
 
+    if options.history_file.is_some() {
+        tracing::warn!("cmd.exe doesn't store its history in a file, ignoring --history-file");
+    }
+    if let Some(cmd) = &options.command {
+        writeln!(temp_file, "\r\n{cmd}").into_diagnostic()?;
+    }
+
     let mut command = std::process::Command::new(cmdexe.executable());
-    command.arg("/K");
+    if options.no_rc {
+        command.arg("/D");
+    }
+    command.arg(if options.command.is_some() {
+        "/C"
+    } else {
+        "/K"
+    });
     command.arg(temp_file.path());
 
     let mut process = command.spawn().into_diagnostic()?;
@@ -102,12 +217,18 @@ fn start_cmdexe(
 /// - `shell`: The type of shell to start. Must implement the `Shell` and `Copy` traits.
 /// - `args`: A vector of arguments to pass to the shell.
 /// - `env`: A HashMap containing environment variables to set in the shell.
+/// - `history`: Commands that make the shell use the history file of the options.
+/// - `deactivation`: Commands that make the shell run the deactivation scripts when it exits.
+/// - `options`: The options of the shell, the rc files are already handled by `args`.
 #[cfg(target_family = "unix")]
 async fn start_unix_shell<T: Shell + Copy>(
     shell: T,
     args: Vec<&str>,
     env: &HashMap<String, String>,
     prompt: String,
+    history: Option<String>,
+    deactivation: Option<String>,
+    options: &ShellOptions,
 ) -> miette::Result<Option<i32>> {
     // create a tempfile for activation
     let mut temp_file = tempfile::Builder::new()
@@ -129,6 +250,13 @@ async fn start_unix_shell<T: Shell + Copy>(
     // Write custom prompt to the env file
     temp_file.write(prompt.as_bytes()).into_diagnostic()?;
 
+    if let Some(history) = history {
+        writeln!(temp_file, "\n{history}").into_diagnostic()?;
+    }
+    if let Some(deactivation) = deactivation {
+        writeln!(temp_file, "\n{deactivation}").into_diagnostic()?;
+    }
+
     let mut command = std::process::Command::new(shell.executable());
     command.args(args);
 
@@ -147,6 +275,14 @@ async fn start_unix_shell<T: Shell + Copy>(
     let mut process = PtySession::new(command).into_diagnostic()?;
     process.send_line(source_command).into_diagnostic()?;
 
+    // The command and the exit are sent as a single line, otherwise the exit would be read as
+    // input by the command.
+    if let Some(cmd) = &options.command {
+        process
+            .send_line(&format!("{cmd}; exit"))
+            .into_diagnostic()?;
+    }
+
     process.interact().into_diagnostic()
 }
 
@@ -158,6 +294,7 @@ async fn start_nu_shell(
     shell: rattler_shell::shell::NuShell,
     env: &HashMap<String, String>,
     prompt: String,
+    options: &ShellOptions,
 ) -> miette::Result<Option<i32>> {
     // create a tempfile for activation
     let mut temp_file = tempfile::Builder::new()
@@ -185,9 +322,24 @@ async fn start_nu_shell(
     // Write custom prompt to the env file
     temp_file.write(prompt.as_bytes()).into_diagnostic()?;
 
+    if options.history_file.is_some() {
+        tracing::warn!("nushell doesn't support another history file, ignoring --history-file");
+    }
+
     let mut command = std::process::Command::new(shell.executable());
-    command.arg("--execute");
-    command.arg(format!("source {}", temp_file.path().display()));
+    if options.no_rc {
+        command.arg("--no-config-file");
+    }
+    match &options.command {
+        Some(cmd) => {
+            command.arg("--commands");
+            command.arg(format!("source {}; {cmd}", temp_file.path().display()));
+        }
+        None => {
+            command.arg("--execute");
+            command.arg(format!("source {}", temp_file.path().display()));
+        }
+    }
 
     let mut process = command.spawn().into_diagnostic()?;
     Ok(process.wait().into_diagnostic()?.code())
@@ -223,76 +375,628 @@ pub async fn get_shell_env(
 }
 
 pub async fn execute(args: Args) -> miette::Result<()> {
-    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?
+        .with_environment(args.environment.as_deref())?
+        .with_platform(args.platform)?;
 
     // Get the environment variables we need to set activate the project in the shell.
     let env = get_shell_env(&project, args.lock_file_usage.into()).await?;
     tracing::debug!("Pixi environment activation:\n{:?}", env);
 
+    // Opening the project is a good moment to refresh the repodata for the next solve. This only
+    // starts once the environment is up to date, so it doesn't fetch the same repodata as the
+    // solve above at the same time.
+    super::cache::warm_in_background(&project);
+
+    let history_file = match args.history_file {
+        Some(path) => {
+            let path = match path {
+                Some(path) => std::env::current_dir().into_diagnostic()?.join(path),
+                None => project.pixi_dir().join(SHELL_HISTORY_FILE),
+            };
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).into_diagnostic()?;
+            }
+            Some(path)
+        }
+        None => None,
+    };
+    // The deactivation scripts unwind the activation when the shell exits, e.g. to stop services
+    // that an activation script started.
+    let deactivation_dir = tempfile::tempdir().into_diagnostic()?;
+    let options = ShellOptions {
+        no_rc: args.no_rc,
+        history_file,
+        command: args.command,
+        shell: args.shell,
+        default_shell: project
+            .config()
+            .default_shell
+            .as_ref()
+            .and_then(|shell| shell.for_platform(Platform::current())),
+        deactivation: copy_deactivation_scripts(&project, &env, deactivation_dir.path())?,
+    };
+
+    let code = start_shell(&env, project.name(), &options).await?;
+
+    // `exit` doesn't run destructors, remove the copies of the deactivation scripts first.
+    drop(deactivation_dir);
+    std::process::exit(code)
+}
+
+/// The extensions of the deactivation scripts of packages that pixi runs, the scripts for bash,
+/// cmd.exe, fish and PowerShell.
+const PACKAGE_SCRIPT_EXTENSIONS: [&str; 4] = ["sh", "bat", "fish", "ps1"];
+
+/// Returns the deactivation scripts that the packages in the environment at `prefix` ship in
+/// `etc/conda/deactivate.d`. Like conda, they run in the reverse order of the activation scripts.
+fn package_deactivation_scripts(prefix: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(prefix.join("etc/conda/deactivate.d")) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path.extension().map_or(false, |ext| {
+                    PACKAGE_SCRIPT_EXTENSIONS
+                        .iter()
+                        .any(|extension| ext == *extension)
+                })
+        })
+        .sorted()
+        .rev()
+        .collect()
+}
+
+/// Returns the extension of the deactivation scripts of packages that run when `shell` exits.
+fn package_script_extension(shell: &ShellEnum) -> &'static str {
+    match shell {
+        ShellEnum::Fish(_) => "fish",
+        ShellEnum::PowerShell(_) => "ps1",
+        _ if Platform::current().is_windows() => "bat",
+        _ => "sh",
+    }
+}
+
+/// Copies the deactivation scripts of the project followed by those of the packages in the
+/// activated environment `env` to `dir`. The scripts of the project are checked against the
+/// trusted scripts when they are read.
+fn copy_deactivation_scripts(
+    project: &Project,
+    env: &HashMap<String, String>,
+    dir: &Path,
+) -> miette::Result<DeactivationScripts> {
+    let mut scripts = Vec::new();
+    let trusted = trust::read_trusted_scripts(project)?;
+    for script in project.deactivation_scripts(project.platform())? {
+        if let Some((path, contents)) = trusted.iter().find(|(path, _)| *path == script) {
+            scripts.push((false, path.clone(), contents.clone()));
+        }
+    }
+    if project.package_activation_scripts_enabled() {
+        if let Some(prefix) = env.get("CONDA_PREFIX") {
+            for script in package_deactivation_scripts(Path::new(prefix)) {
+                let contents = std::fs::read(&script).into_diagnostic()?;
+                scripts.push((true, script, contents));
+            }
+        }
+    }
+
+    // The copies are numbered, scripts in different directories can have the same name.
+    let mut copies = DeactivationScripts::default();
+    for (index, (is_package, script, contents)) in scripts.into_iter().enumerate() {
+        let copy = dir.join(format!(
+            "{index}-{}",
+            script.file_name().unwrap_or_default().to_string_lossy()
+        ));
+        std::fs::write(&copy, contents).into_diagnostic()?;
+        if is_package {
+            copies.packages.push(copy);
+        } else {
+            copies.project.push(copy);
+        }
+    }
+    Ok(copies)
+}
+
+/// Returns the command and the arguments that run a script that is not written for the started
+/// shell, based on its extension.
+fn script_interpreter(script: &Path) -> Vec<String> {
+    match script.extension().and_then(|ext| ext.to_str()) {
+        Some("bat") => vec![
+            String::from("cmd.exe"),
+            String::from("/D"),
+            String::from("/C"),
+        ],
+        Some("ps1") => vec![
+            PowerShell::default().executable().to_string(),
+            String::from("-NoProfile"),
+            String::from("-File"),
+        ],
+        Some("fish") => vec![String::from("fish")],
+        _ => vec![String::from("bash")],
+    }
+}
+
+fn posix_quote(value: &str) -> String {
+    shlex::quote(value).into_owned()
+}
+
+fn powershell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Returns the line that runs `script` in a shell. A script with the `native` extension of the
+/// shell is sourced with `source`, so it can use the state of the shell, other scripts run with
+/// their own interpreter, which the shell calls with `call`.
+fn script_command(
+    script: &Path,
+    native: &str,
+    source: &str,
+    call: &str,
+    quote: fn(&str) -> String,
+) -> String {
+    let path = quote(&script.to_string_lossy());
+    if script.extension().map_or(false, |ext| ext == native) {
+        format!("{source} {path}")
+    } else {
+        let interpreter = script_interpreter(script)
+            .iter()
+            .map(|arg| quote(arg))
+            .join(" ");
+        format!("{call}{interpreter} {path}")
+    }
+}
+
+/// Returns the code that makes `shell` run the deactivation `scripts` when it exits, or `None` if
+/// the shell has no exit hook. Then pixi runs the scripts after the shell exited.
+fn deactivation_hook(shell: &ShellEnum, scripts: &[PathBuf]) -> Option<String> {
+    let body = |native: &str, source: &str, call: &str, quote: fn(&str) -> String| {
+        scripts
+            .iter()
+            .map(|script| {
+                format!(
+                    "    {}",
+                    script_command(script, native, source, call, quote)
+                )
+            })
+            .join("\n")
+    };
+    match shell {
+        ShellEnum::Bash(_) | ShellEnum::Zsh(_) => Some(format!(
+            "__pixi_deactivate() {{\n{}\n}}\ntrap __pixi_deactivate EXIT",
+            body("sh", ".", "", posix_quote)
+        )),
+        ShellEnum::Fish(_) => Some(format!(
+            "function __pixi_deactivate --on-event fish_exit\n{}\nend",
+            body("fish", "source", "", posix_quote)
+        )),
+        ShellEnum::PowerShell(_) => Some(format!(
+            "Register-EngineEvent -SourceIdentifier PowerShell.Exiting -Action {{\n{}\n}} | Out-Null",
+            body("ps1", ".", "& ", powershell_quote)
+        )),
+        _ => None,
+    }
+}
+
+/// Runs the deactivation `scripts` in the activated environment `env`, for the shells without an
+/// exit hook. A script that fails only emits a warning, the shell has already exited.
+fn run_deactivation_scripts(scripts: &[PathBuf], env: &HashMap<String, String>) {
+    for script in scripts {
+        tracing::debug!("running the deactivation script '{}'", script.display());
+        let interpreter = script_interpreter(script);
+        let status = std::process::Command::new(&interpreter[0])
+            .args(&interpreter[1..])
+            .arg(script)
+            .envs(env)
+            .status();
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) => tracing::warn!(
+                "the deactivation script '{}' failed with {status}",
+                script.display()
+            ),
+            Err(err) => tracing::warn!(
+                "failed to run the deactivation script '{}': {err}",
+                script.display()
+            ),
+        }
+    }
+}
+
+/// Returns the commands that make a unix shell store its history in `history_file`, or `None` if
+/// the shell doesn't support it. Fish only stores history in its own data directory, so the name
+/// of the file is used as the name of the history session.
+#[cfg(target_family = "unix")]
+fn unix_history_script(shell: &ShellEnum, history_file: &std::path::Path) -> Option<String> {
+    let path = shlex::quote(history_file.to_str()?);
+    match shell {
+        ShellEnum::Bash(_) => Some(format!("export HISTFILE={path}\nhistory -c\nhistory -r")),
+        ShellEnum::Zsh(_) => Some(format!("fc -p {path}")),
+        ShellEnum::Fish(_) => {
+            let name = history_file
+                .file_name()?
+                .to_string_lossy()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>();
+            Some(format!("set -g fish_history pixi_{name}"))
+        }
+        ShellEnum::Xonsh(_) => Some(format!("$XONSH_HISTORY_FILE = {path}")),
+        _ => None,
+    }
+}
+
+/// Returns the shells that `pixi shell` can start on the given platform.
+fn supported_shells(platform: Platform) -> &'static [ShellName] {
+    if platform.is_windows() {
+        &[ShellName::Nu, ShellName::Powershell, ShellName::Cmd]
+    } else {
+        &[
+            ShellName::Bash,
+            ShellName::Zsh,
+            ShellName::Fish,
+            ShellName::Xonsh,
+            ShellName::Nu,
+            ShellName::Powershell,
+        ]
+    }
+}
+
+/// Returns the name of a detected shell.
+fn shell_name(shell: &ShellEnum) -> Option<ShellName> {
+    match shell {
+        ShellEnum::Bash(_) => Some(ShellName::Bash),
+        ShellEnum::Zsh(_) => Some(ShellName::Zsh),
+        ShellEnum::Fish(_) => Some(ShellName::Fish),
+        ShellEnum::Xonsh(_) => Some(ShellName::Xonsh),
+        ShellEnum::NuShell(_) => Some(ShellName::Nu),
+        ShellEnum::PowerShell(_) => Some(ShellName::Powershell),
+        ShellEnum::CmdExe(_) => Some(ShellName::Cmd),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }
+}
+
+fn shell_from_name(name: ShellName) -> ShellEnum {
+    match name {
+        ShellName::Bash => rattler_shell::shell::Bash.into(),
+        ShellName::Zsh => rattler_shell::shell::Zsh.into(),
+        ShellName::Fish => rattler_shell::shell::Fish.into(),
+        ShellName::Xonsh => rattler_shell::shell::Xonsh.into(),
+        ShellName::Nu => rattler_shell::shell::NuShell.into(),
+        ShellName::Powershell => PowerShell::default().into(),
+        ShellName::Cmd => rattler_shell::shell::CmdExe.into(),
+    }
+}
+
+/// Selects the shell to start: the shell requested with `--shell`, otherwise the detected shell of
+/// the user if it is supported, otherwise the configured default shell, otherwise the
+/// [`builtin_default_shell`].
+fn select_shell(
+    platform: Platform,
+    options: &ShellOptions,
+    detected: Option<ShellEnum>,
+) -> miette::Result<ShellEnum> {
+    let supported = supported_shells(platform);
+    if let Some(shell) = options.shell {
+        if !supported.contains(&shell) {
+            miette::bail!(
+                help = format!(
+                    "the shells that can be started on {platform} are {}",
+                    supported.iter().join(", ")
+                ),
+                "'{shell}' can't be started on {platform}"
+            );
+        }
+        return Ok(shell_from_name(shell));
+    }
+
+    match detected {
+        Some(detected) if shell_name(&detected).map_or(false, |name| supported.contains(&name)) => {
+            return Ok(detected)
+        }
+        Some(detected) => {
+            tracing::info!(
+                "the detected shell {detected:?} is not supported, starting the default shell"
+            )
+        }
+        None => tracing::info!("could not detect the shell, starting the default shell"),
+    }
+    let fallback = match options.default_shell {
+        Some(shell) if supported.contains(&shell) => shell,
+        Some(shell) => {
+            tracing::warn!(
+                "the default shell '{shell}' can't be started on {platform}, ignoring it"
+            );
+            builtin_default_shell(platform)
+        }
+        None => builtin_default_shell(platform),
+    };
+    Ok(shell_from_name(fallback))
+}
+
+/// The shell that is started if neither the shell of the user nor a default shell is known: bash on
+/// unix and PowerShell on Windows, which both come with line editing and history.
+fn builtin_default_shell(platform: Platform) -> ShellName {
+    if platform.is_windows() {
+        ShellName::Powershell
+    } else {
+        ShellName::Bash
+    }
+}
+
+/// Starts the interactive shell of the user with the given environment variables and returns the
+/// exit code of the shell. The `env_name` is shown in the prompt.
+pub(crate) async fn start_shell(
+    env: &HashMap<String, String>,
+    env_name: &str,
+    options: &ShellOptions,
+) -> miette::Result<i32> {
     // Start the shell as the last part of the activation script based on the default shell.
-    let interactive_shell: ShellEnum = ShellEnum::from_parent_process()
-        .or_else(ShellEnum::from_env)
-        .unwrap_or_default();
+    let interactive_shell = select_shell(
+        Platform::current(),
+        options,
+        ShellEnum::from_parent_process().or_else(ShellEnum::from_env),
+    )?;
+
+    let deactivation_scripts = options.deactivation.for_shell(&interactive_shell);
+    let deactivation = if deactivation_scripts.is_empty() {
+        None
+    } else {
+        deactivation_hook(&interactive_shell, &deactivation_scripts)
+    };
+    let run_deactivation = !deactivation_scripts.is_empty() && deactivation.is_none();
 
     #[cfg(target_family = "windows")]
     let res = match interactive_shell {
         ShellEnum::NuShell(nushell) => {
-            start_nu_shell(nushell, &env, prompt::get_nu_prompt(project.name())).await
-        }
-        ShellEnum::PowerShell(pwsh) => {
-            start_powershell(pwsh, &env, prompt::get_powershell_prompt(project.name()))
+            start_nu_shell(nushell, env, prompt::get_nu_prompt(env_name), options).await
         }
+        ShellEnum::PowerShell(pwsh) => start_powershell(
+            pwsh,
+            env,
+            prompt::get_powershell_prompt(env_name),
+            deactivation,
+            options,
+        ),
         ShellEnum::CmdExe(cmdexe) => {
-            start_cmdexe(cmdexe, &env, prompt::get_cmd_prompt(project.name()))
+            start_cmdexe(cmdexe, env, prompt::get_cmd_prompt(env_name), options)
         }
         _ => {
             miette::bail!("Unsupported shell: {:?}", interactive_shell);
         }
     };
 
+    #[cfg(target_family = "unix")]
+    let history = options
+        .history_file
+        .as_deref()
+        .and_then(|history_file| unix_history_script(&interactive_shell, history_file));
+
     #[cfg(target_family = "unix")]
     let res = match interactive_shell {
         ShellEnum::NuShell(nushell) => {
-            start_nu_shell(nushell, &env, prompt::get_nu_prompt(project.name())).await
-        }
-        ShellEnum::PowerShell(pwsh) => {
-            start_powershell(pwsh, &env, prompt::get_powershell_prompt(project.name()))
+            start_nu_shell(nushell, env, prompt::get_nu_prompt(env_name), options).await
         }
+        ShellEnum::PowerShell(pwsh) => start_powershell(
+            pwsh,
+            env,
+            prompt::get_powershell_prompt(env_name),
+            deactivation,
+            options,
+        ),
         ShellEnum::Bash(bash) => {
+            let args = if options.no_rc {
+                vec!["--noprofile", "--norc", "-i"]
+            } else {
+                vec!["-l", "-i"]
+            };
             start_unix_shell(
                 bash,
-                vec!["-l", "-i"],
-                &env,
-                prompt::get_bash_prompt(project.name()),
+                args,
+                env,
+                prompt::get_bash_prompt(env_name),
+                history,
+                deactivation,
+                options,
             )
             .await
         }
         ShellEnum::Zsh(zsh) => {
+            let args = if options.no_rc {
+                vec!["-f", "-i"]
+            } else {
+                vec!["-l", "-i"]
+            };
             start_unix_shell(
                 zsh,
-                vec!["-l", "-i"],
-                &env,
-                prompt::get_zsh_prompt(project.name()),
+                args,
+                env,
+                prompt::get_zsh_prompt(env_name),
+                history,
+                deactivation,
+                options,
             )
             .await
         }
         ShellEnum::Fish(fish) => {
-            start_unix_shell(fish, vec![], &env, prompt::get_fish_prompt(project.name())).await
+            let args = if options.no_rc {
+                vec!["--no-config"]
+            } else {
+                vec![]
+            };
+            start_unix_shell(
+                fish,
+                args,
+                env,
+                prompt::get_fish_prompt(env_name),
+                history,
+                deactivation,
+                options,
+            )
+            .await
         }
         ShellEnum::Xonsh(xonsh) => {
-            start_unix_shell(xonsh, vec![], &env, prompt::get_xonsh_prompt()).await
+            let args = if options.no_rc {
+                vec!["--no-rc"]
+            } else {
+                vec![]
+            };
+            start_unix_shell(
+                xonsh,
+                args,
+                env,
+                prompt::get_xonsh_prompt(),
+                history,
+                None,
+                options,
+            )
+            .await
         }
         _ => {
             miette::bail!("Unsupported shell: {:?}", interactive_shell)
         }
     };
 
+    if run_deactivation {
+        run_deactivation_scripts(&deactivation_scripts, env);
+    }
+
     match res {
-        Ok(Some(code)) => std::process::exit(code),
-        Ok(None) => std::process::exit(0),
+        Ok(code) => Ok(code.unwrap_or(0)),
         Err(e) => {
             eprintln!("Error starting shell: {}", e);
-            std::process::exit(1);
+            Ok(1)
+        }
+    }
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use super::*;
+    use rattler_shell::shell::{Bash, Fish};
+    use std::path::Path;
+
+    #[test]
+    fn test_unix_history_script() {
+        let path = Path::new("/project/.pixi/shell history");
+        assert_eq!(
+            unix_history_script(&ShellEnum::Bash(Bash), path).unwrap(),
+            "export HISTFILE='/project/.pixi/shell history'\nhistory -c\nhistory -r"
+        );
+        assert_eq!(
+            unix_history_script(&ShellEnum::Fish(Fish), path).unwrap(),
+            "set -g fish_history pixi_shell_history"
+        );
+    }
+
+    #[test]
+    fn test_select_shell() {
+        let name = |shell: ShellEnum| shell_name(&shell).unwrap();
+        let options = ShellOptions::default();
+        let zsh = Some(ShellEnum::Zsh(rattler_shell::shell::Zsh));
+        assert_eq!(
+            name(select_shell(Platform::Linux64, &options, zsh.clone()).unwrap()),
+            ShellName::Zsh
+        );
+        assert_eq!(
+            name(select_shell(Platform::Linux64, &options, None).unwrap()),
+            ShellName::Bash
+        );
+        assert_eq!(
+            name(select_shell(Platform::Win64, &options, zsh.clone()).unwrap()),
+            ShellName::Powershell
+        );
+
+        let options = ShellOptions {
+            default_shell: Some(ShellName::Fish),
+            ..ShellOptions::default()
+        };
+        assert_eq!(
+            name(select_shell(Platform::Linux64, &options, None).unwrap()),
+            ShellName::Fish
+        );
+        assert_eq!(
+            name(select_shell(Platform::Win64, &options, None).unwrap()),
+            ShellName::Powershell
+        );
+
+        let options = ShellOptions {
+            shell: Some(ShellName::Nu),
+            ..ShellOptions::default()
+        };
+        assert_eq!(
+            name(select_shell(Platform::Linux64, &options, zsh).unwrap()),
+            ShellName::Nu
+        );
+        let options = ShellOptions {
+            shell: Some(ShellName::Cmd),
+            ..ShellOptions::default()
+        };
+        assert!(select_shell(Platform::Linux64, &options, None).is_err());
+    }
+
+    #[test]
+    fn test_package_deactivation_scripts() {
+        let prefix = tempfile::tempdir().unwrap();
+        let deactivate_d = prefix.path().join("etc/conda/deactivate.d");
+        std::fs::create_dir_all(&deactivate_d).unwrap();
+        for name in [
+            "gcc_deactivate.sh",
+            "cmake_deactivate.sh",
+            "gcc_deactivate.fish",
+            "gcc_deactivate.csh",
+        ] {
+            std::fs::write(deactivate_d.join(name), "").unwrap();
         }
+
+        let scripts = package_deactivation_scripts(prefix.path());
+        assert_eq!(
+            scripts,
+            vec![
+                deactivate_d.join("gcc_deactivate.sh"),
+                deactivate_d.join("gcc_deactivate.fish"),
+                deactivate_d.join("cmake_deactivate.sh")
+            ]
+        );
+        assert!(package_deactivation_scripts(&prefix.path().join("missing")).is_empty());
+
+        let deactivation = DeactivationScripts {
+            project: vec![PathBuf::from("/tmp/0-teardown.sh")],
+            packages: scripts,
+        };
+        assert_eq!(
+            deactivation.for_shell(&ShellEnum::Fish(Fish)),
+            vec![
+                PathBuf::from("/tmp/0-teardown.sh"),
+                deactivate_d.join("gcc_deactivate.fish")
+            ]
+        );
+        assert_eq!(deactivation.for_shell(&ShellEnum::Bash(Bash)).len(), 3);
+    }
+
+    #[test]
+    fn test_deactivation_hook() {
+        let scripts = [
+            PathBuf::from("/tmp/0-teardown.sh"),
+            PathBuf::from("/tmp/1-gcc deactivate.fish"),
+        ];
+        assert_eq!(
+            deactivation_hook(&ShellEnum::Bash(Bash), &scripts).unwrap(),
+            "__pixi_deactivate() {\n    . /tmp/0-teardown.sh\n    fish '/tmp/1-gcc deactivate.fish'\n}\ntrap __pixi_deactivate EXIT"
+        );
+        assert_eq!(
+            deactivation_hook(&ShellEnum::Fish(Fish), &scripts).unwrap(),
+            "function __pixi_deactivate --on-event fish_exit\n    bash /tmp/0-teardown.sh\n    source '/tmp/1-gcc deactivate.fish'\nend"
+        );
+        let hook =
+            deactivation_hook(&ShellEnum::PowerShell(PowerShell::default()), &scripts).unwrap();
+        assert!(hook.starts_with("Register-EngineEvent -SourceIdentifier PowerShell.Exiting"));
+        assert!(hook.contains("    & 'bash' '/tmp/0-teardown.sh'\n"));
+        assert!(
+            deactivation_hook(&ShellEnum::Xonsh(rattler_shell::shell::Xonsh), &scripts).is_none()
+        );
     }
 }
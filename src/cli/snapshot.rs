@@ -0,0 +1,129 @@
+use crate::snapshot::{create_snapshot, list_snapshots, remove_snapshot, restore_snapshot};
+use crate::Project;
+use clap::Parser;
+use miette::IntoDiagnostic;
+use std::path::PathBuf;
+
+/// Save the state of an environment and roll back to it, e.g. before trying a risky update
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long, global = true)]
+    pub manifest_path: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Parser, Debug)]
+pub enum Command {
+    /// Save the manifest, the lock file and the installed packages of an environment
+    Create(CreateArgs),
+
+    /// List the snapshots of the project
+    #[clap(alias = "ls")]
+    List(ListArgs),
+
+    /// Restore the manifest and lock file of a snapshot and reinstall the environment
+    Restore(RestoreArgs),
+
+    /// Remove a snapshot
+    #[clap(alias = "rm")]
+    Remove(RemoveArgs),
+}
+
+#[derive(Parser, Debug)]
+pub struct CreateArgs {
+    /// The name of the snapshot
+    pub name: String,
+
+    /// The environment to snapshot, one for every variant of the dependencies of the project
+    #[arg(long, short)]
+    pub environment: Option<String>,
+
+    /// Replace an existing snapshot with the same name
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct ListArgs {
+    /// Output the snapshots as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct RestoreArgs {
+    /// The name of the snapshot
+    pub name: String,
+
+    /// Only restore the manifest and lock file, don't reinstall the environment
+    #[arg(long)]
+    pub no_install: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct RemoveArgs {
+    /// The name of the snapshot
+    pub name: String,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+    match args.command {
+        Command::Create(args) => {
+            let project = project.with_environment(args.environment.as_deref())?;
+            let snapshot = create_snapshot(&project, &args.name, args.force).await?;
+            eprintln!(
+                "{}Created the snapshot '{}' of {} packages",
+                console::style(console::Emoji("✔ ", "")).green(),
+                snapshot.name,
+                snapshot.packages.len()
+            );
+        }
+        Command::List(args) => {
+            let snapshots = list_snapshots(&project)?;
+            if args.json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&snapshots).into_diagnostic()?
+                );
+            } else if snapshots.is_empty() {
+                eprintln!("The project has no snapshots");
+            } else {
+                for snapshot in &snapshots {
+                    println!(
+                        "{}: {} ({}, {} packages)",
+                        console::style(&snapshot.name).bold(),
+                        snapshot.created_local().map_or_else(
+                            || snapshot.created.clone(),
+                            |created| created.format("%Y-%m-%d %H:%M:%S").to_string()
+                        ),
+                        snapshot.environment.as_deref().unwrap_or("default"),
+                        snapshot.packages.len()
+                    );
+                }
+            }
+        }
+        Command::Restore(args) => {
+            let (snapshot, project) =
+                restore_snapshot(&project, &args.name, args.no_install).await?;
+            eprintln!(
+                "{}Restored the snapshot '{}' in {}",
+                console::style(console::Emoji("✔ ", "")).green(),
+                snapshot.name,
+                project.root().display()
+            );
+        }
+        Command::Remove(args) => {
+            remove_snapshot(&project, &args.name)?;
+            eprintln!(
+                "{}Removed the snapshot '{}'",
+                console::style(console::Emoji("✔ ", "")).green(),
+                args.name
+            );
+        }
+    }
+    Ok(())
+}
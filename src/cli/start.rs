@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use rattler_conda_types::Platform;
+
+use crate::cli::run::{default_task, execute_task_args};
+use crate::Project;
+
+/// Runs the default task of the project, set with `default-task` in the [project] table.
+#[derive(Parser, Debug, Default)]
+#[clap(trailing_var_arg = true)]
+pub struct Args {
+    /// Additional arguments that are passed to the default task.
+    pub args: Vec<String>,
+
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// The environment to use, one for every variant of the dependencies of the project or every
+    /// environment of the `[environments]` table
+    #[arg(long, short)]
+    pub environment: Option<String>,
+
+    /// The platform to install the environment for, e.g. `osx-64` to run it through Rosetta on
+    /// Apple Silicon. Defaults to the platform of this machine
+    #[arg(long)]
+    pub platform: Option<Platform>,
+
+    #[clap(flatten)]
+    pub lock_file_usage: super::LockFileUsageArgs,
+
+    /// Run the tasks in a pseudo-terminal, as if the `interactive` option was set on all of them
+    #[arg(long)]
+    pub pty: bool,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?
+        .with_environment(args.environment.as_deref())?
+        .with_platform(args.platform)?;
+
+    let mut task_args = vec![default_task(&project)?];
+    task_args.extend(args.args);
+    tracing::debug!("Task parsed from start command: {:?}", task_args);
+
+    execute_task_args(&project, task_args, args.lock_file_usage.into(), args.pty).await
+}
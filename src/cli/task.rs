@@ -1,8 +1,11 @@
-use crate::task::{quote, Alias, CmdArgs, Execute, Task};
+use crate::task::{quote, Alias, CmdArgs, Condition, Execute, Task};
 use crate::Project;
 use clap::Parser;
 use itertools::Itertools;
+use miette::IntoDiagnostic;
 use rattler_conda_types::Platform;
+use serde::Serialize;
+use std::fmt::Display;
 use std::path::PathBuf;
 use toml_edit::{Array, Item, Table, Value};
 
@@ -23,6 +26,10 @@ pub enum Operation {
     /// List all tasks
     #[clap(alias = "l")]
     List(ListArgs),
+
+    /// Show the resolved definition of a task
+    #[clap(alias = "i")]
+    Info(InfoArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -58,6 +65,21 @@ pub struct AddArgs {
     /// The working directory relative to the root of the project
     #[arg(long)]
     pub cwd: Option<PathBuf>,
+
+    /// Install the task as an executable in the environment, so it can be called without
+    /// `pixi run`
+    #[arg(long)]
+    pub expose: bool,
+
+    /// Skip the task and the tasks it depends on when this condition holds, e.g.
+    /// `"platform == 'win-64'"`
+    #[arg(long)]
+    pub skip_if: Option<Condition>,
+
+    /// Only run the task and the tasks it depends on when this condition holds, e.g.
+    /// `"env.CI == 'true'"`
+    #[arg(long)]
+    pub only_if: Option<Condition>,
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -81,6 +103,154 @@ pub struct ListArgs {
     pub summary: bool,
 }
 
+#[derive(Parser, Debug, Clone)]
+#[clap(arg_required_else_help = true)]
+pub struct InfoArgs {
+    /// Task name
+    pub name: String,
+
+    /// The platform for which the task should be resolved (defaults to the current platform)
+    #[arg(long, short)]
+    pub platform: Option<Platform>,
+
+    /// Whether to show the output as JSON or not
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// The resolved definition of a task as it is executed on a specific platform.
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TaskInfo {
+    name: String,
+    platform: Platform,
+    feature: String,
+    target: String,
+    kind: &'static str,
+    cmd: Option<String>,
+    cwd: Option<PathBuf>,
+    depends_on: Vec<String>,
+    inputs: Vec<PathBuf>,
+    outputs: Vec<PathBuf>,
+    cache_env: Vec<String>,
+    interactive: bool,
+    expose: bool,
+    skip_if: Option<String>,
+    only_if: Option<String>,
+}
+
+impl TaskInfo {
+    /// Resolves the task with the given name for a platform. Returns `None` if no such task exists.
+    fn resolve(project: &Project, name: &str, platform: Platform) -> Option<Self> {
+        // Platform specific tasks take precedence over the default ones.
+        let (task, selector) = project
+            .manifest
+            .default_feature()
+            .targets
+            .iter()
+            .filter(|(_, selector)| selector.map_or(true, |selector| selector.matches(platform)))
+            .sorted_by_key(|(_, selector)| selector.is_none())
+            .find_map(|(target, selector)| target.tasks.get(name).map(|task| (task, selector)))?;
+
+        let execute = task.as_execute();
+        Some(Self {
+            name: name.to_string(),
+            platform,
+            feature: project
+                .manifest
+                .default_feature()
+                .name()
+                .unwrap_or("default")
+                .to_string(),
+            target: selector.map_or_else(|| String::from("default"), ToString::to_string),
+            kind: match task {
+                Task::Plain(_) => "plain",
+                Task::Execute(_) => "execute",
+                Task::Alias(_) => "alias",
+                Task::Custom(_) => "custom",
+            },
+            cmd: task.as_single_command().map(|cmd| cmd.into_owned()),
+            cwd: task
+                .is_executable()
+                .then(|| match task.working_directory() {
+                    Some(cwd) => project.root().join(cwd),
+                    None => project.root().to_path_buf(),
+                }),
+            depends_on: task.depends_on().to_vec(),
+            inputs: execute.map(|e| e.inputs.clone()).unwrap_or_default(),
+            outputs: execute.map(|e| e.outputs.clone()).unwrap_or_default(),
+            cache_env: execute.map(|e| e.cache_env.clone()).unwrap_or_default(),
+            interactive: task.is_interactive(),
+            expose: task.is_exposed(),
+            skip_if: execute.and_then(|e| e.skip_if.as_ref().map(ToString::to_string)),
+            only_if: execute.and_then(|e| e.only_if.as_ref().map(ToString::to_string)),
+        })
+    }
+}
+
+impl Display for TaskInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bold = console::Style::new().bold();
+        let paths = |paths: &[PathBuf]| paths.iter().map(|p| p.display()).join(", ");
+        writeln!(f, "{:>12}: {}", bold.apply_to("Task"), self.name)?;
+        writeln!(f, "{:>12}: {}", bold.apply_to("Platform"), self.platform)?;
+        writeln!(f, "{:>12}: {}", bold.apply_to("Feature"), self.feature)?;
+        writeln!(f, "{:>12}: {}", bold.apply_to("Target"), self.target)?;
+        writeln!(f, "{:>12}: {}", bold.apply_to("Kind"), self.kind)?;
+        if let Some(cmd) = &self.cmd {
+            writeln!(f, "{:>12}: {}", bold.apply_to("Command"), cmd)?;
+        }
+        if let Some(cwd) = &self.cwd {
+            writeln!(f, "{:>12}: {}", bold.apply_to("Working dir"), cwd.display())?;
+        }
+        if !self.depends_on.is_empty() {
+            writeln!(
+                f,
+                "{:>12}: {}",
+                bold.apply_to("Depends on"),
+                self.depends_on.join(", ")
+            )?;
+        }
+        if !self.inputs.is_empty() {
+            writeln!(
+                f,
+                "{:>12}: {}",
+                bold.apply_to("Inputs"),
+                paths(&self.inputs)
+            )?;
+        }
+        if !self.outputs.is_empty() {
+            writeln!(
+                f,
+                "{:>12}: {}",
+                bold.apply_to("Outputs"),
+                paths(&self.outputs)
+            )?;
+        }
+        if !self.cache_env.is_empty() {
+            writeln!(
+                f,
+                "{:>12}: {}",
+                bold.apply_to("Cache env"),
+                self.cache_env.join(", ")
+            )?;
+        }
+        if self.interactive {
+            writeln!(f, "{:>12}: yes", bold.apply_to("Interactive"))?;
+        }
+        if self.expose {
+            writeln!(f, "{:>12}: yes", bold.apply_to("Exposed"))?;
+        }
+        if let Some(skip_if) = &self.skip_if {
+            writeln!(f, "{:>12}: {}", bold.apply_to("Skip if"), skip_if)?;
+        }
+        if let Some(only_if) = &self.only_if {
+            writeln!(f, "{:>12}: {}", bold.apply_to("Only if"), only_if)?;
+        }
+        Ok(())
+    }
+}
+
 impl From<AddArgs> for Task {
     fn from(value: AddArgs) -> Self {
         let depends_on = value.depends_on.unwrap_or_default();
@@ -101,13 +271,25 @@ impl From<AddArgs> for Task {
         // complex, or alias command.
         if cmd_args.trim().is_empty() && !depends_on.is_empty() {
             Self::Alias(Alias { depends_on })
-        } else if depends_on.is_empty() && value.cwd.is_none() {
+        } else if depends_on.is_empty()
+            && value.cwd.is_none()
+            && !value.expose
+            && value.skip_if.is_none()
+            && value.only_if.is_none()
+        {
             Self::Plain(cmd_args)
         } else {
             Self::Execute(Execute {
                 cmd: CmdArgs::Single(cmd_args),
                 depends_on,
                 cwd: value.cwd,
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                cache_env: Vec::new(),
+                interactive: false,
+                expose: value.expose,
+                skip_if: value.skip_if,
+                only_if: value.only_if,
             })
         }
     }
@@ -129,7 +311,7 @@ pub struct Args {
     #[clap(subcommand)]
     pub operation: Operation,
 
-    /// The path to 'pixi.toml'
+    /// The path to 'pixi.toml' or the directory that contains it
     #[arg(long)]
     pub manifest_path: Option<PathBuf>,
 }
@@ -239,6 +421,17 @@ pub fn execute(args: Args) -> miette::Result<()> {
                 println!("{}", formatted);
             }
         }
+        Operation::Info(args) => {
+            let platform = args.platform.unwrap_or_else(Platform::current);
+            let Some(info) = TaskInfo::resolve(&project, &args.name, platform) else {
+                miette::bail!("task '{}' does not exist on {}", args.name, platform);
+            };
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&info).into_diagnostic()?);
+            } else {
+                print!("{}", info);
+            }
+        }
     };
 
     Ok(())
@@ -267,6 +460,46 @@ impl From<Task> for Item {
                 if let Some(cwd) = process.cwd {
                     table.insert("cwd", cwd.to_string_lossy().to_string().into());
                 }
+                if !process.inputs.is_empty() {
+                    table.insert(
+                        "inputs",
+                        Value::Array(Array::from_iter(
+                            process
+                                .inputs
+                                .iter()
+                                .map(|path| path.to_string_lossy().to_string()),
+                        )),
+                    );
+                }
+                if !process.outputs.is_empty() {
+                    table.insert(
+                        "outputs",
+                        Value::Array(Array::from_iter(
+                            process
+                                .outputs
+                                .iter()
+                                .map(|path| path.to_string_lossy().to_string()),
+                        )),
+                    );
+                }
+                if !process.cache_env.is_empty() {
+                    table.insert(
+                        "cache-env",
+                        Value::Array(Array::from_iter(process.cache_env)),
+                    );
+                }
+                if process.interactive {
+                    table.insert("interactive", true.into());
+                }
+                if process.expose {
+                    table.insert("expose", true.into());
+                }
+                if let Some(skip_if) = process.skip_if {
+                    table.insert("skip-if", skip_if.to_string().into());
+                }
+                if let Some(only_if) = process.only_if {
+                    table.insert("only-if", only_if.to_string().into());
+                }
                 Item::Value(Value::InlineTable(table))
             }
             Task::Alias(alias) => {
@@ -281,3 +514,45 @@ impl From<Task> for Item {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::manifest::Manifest;
+    use std::path::Path;
+
+    #[test]
+    fn test_resolve_task_info() {
+        let contents = r#"
+            [project]
+            name = "foo"
+            channels = []
+            platforms = ["linux-64", "win-64"]
+
+            [tasks]
+            build = { cmd = "make", cwd = "src", inputs = ["src"], outputs = ["out"], cache-env = ["CC"] }
+            test = { cmd = "make test", depends_on = ["build"] }
+
+            [target.win-64.tasks]
+            build = "nmake"
+        "#;
+        let project = Project::from_manifest(Manifest::from_str(Path::new(""), contents).unwrap());
+
+        let linux = TaskInfo::resolve(&project, "build", Platform::Linux64).unwrap();
+        assert_eq!(linux.target, "default");
+        assert_eq!(linux.kind, "execute");
+        assert_eq!(linux.cmd.as_deref(), Some("make"));
+        assert_eq!(linux.cwd, Some(PathBuf::from("src")));
+        assert_eq!(linux.outputs, vec![PathBuf::from("out")]);
+        assert_eq!(linux.cache_env, vec![String::from("CC")]);
+
+        let windows = TaskInfo::resolve(&project, "build", Platform::Win64).unwrap();
+        assert_eq!(windows.target, "win-64");
+        assert_eq!(windows.kind, "plain");
+        assert_eq!(windows.cmd.as_deref(), Some("nmake"));
+
+        let test = TaskInfo::resolve(&project, "test", Platform::Win64).unwrap();
+        assert_eq!(test.depends_on, vec![String::from("build")]);
+        assert!(TaskInfo::resolve(&project, "missing", Platform::Linux64).is_none());
+    }
+}
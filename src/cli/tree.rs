@@ -0,0 +1,61 @@
+use crate::lock_file::{load_lock_file, DependencyGraph};
+use crate::Project;
+use clap::{Parser, ValueEnum};
+use miette::IntoDiagnostic;
+use rattler_conda_types::Platform;
+use std::path::PathBuf;
+
+/// The format of the dependency graph.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TreeFormat {
+    /// A tree below the project that is readable in the terminal.
+    #[default]
+    Tree,
+
+    /// The DOT language of Graphviz, e.g. `pixi tree --format dot | dot -Tsvg > deps.svg`.
+    Dot,
+
+    /// JSON with the nodes and the edges of the graph, to be processed by other tools.
+    Json,
+}
+
+/// Show the resolved dependency graph of the project.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// The platform to show the graph for, defaults to the current platform
+    #[arg(long, short)]
+    pub platform: Option<Platform>,
+
+    /// The format of the output
+    #[arg(long, value_enum, default_value_t)]
+    pub format: TreeFormat,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+    let platform = args.platform.unwrap_or_else(Platform::current);
+    if !project.platforms().contains(&platform) {
+        miette::bail!("the project does not support the platform {platform}");
+    }
+    let lock_file = load_lock_file(&project).await?;
+    let graph = DependencyGraph::from_project(&project, &lock_file, platform);
+    if graph.edges.is_empty() {
+        eprintln!(
+            "No packages are locked for {platform}, run `pixi install` to lock the environment"
+        );
+    }
+
+    match args.format {
+        TreeFormat::Tree => print!("{}", graph.to_tree()),
+        TreeFormat::Dot => print!("{}", graph.to_dot()),
+        TreeFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&graph).into_diagnostic()?
+        ),
+    }
+    Ok(())
+}
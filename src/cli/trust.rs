@@ -0,0 +1,70 @@
+use crate::trust::{decide, project_key, TrustStore};
+use crate::Project;
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Trust the activation scripts of the project, so `pixi run` and `pixi shell` run them.
+///
+/// Activation scripts run arbitrary code, so pixi asks before it runs them for the first time or
+/// after they changed.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Don't run the activation scripts of the project
+    #[arg(long, conflicts_with_all = ["revoke", "list"])]
+    pub deny: bool,
+
+    /// Forget the decision for the project, pixi asks again the next time the scripts run
+    #[arg(long, conflicts_with = "list")]
+    pub revoke: bool,
+
+    /// List the decisions for all projects
+    #[arg(long)]
+    pub list: bool,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    if args.list {
+        let store = TrustStore::load()?;
+        for decision in store.decisions() {
+            println!(
+                "{} {}",
+                if decision.trusted {
+                    console::style("trusted").green()
+                } else {
+                    console::style("denied ").red()
+                },
+                decision.manifest_path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+    if args.revoke {
+        let mut store = TrustStore::load()?;
+        if store.forget(&project_key(&project)) {
+            store.save()?;
+            eprintln!(
+                "{}Forgot the decision for {}",
+                console::style(console::Emoji("✔ ", "")).green(),
+                console::style(project.name()).bold()
+            );
+        } else {
+            eprintln!("There is no decision for {}", project.name());
+        }
+        return Ok(());
+    }
+
+    decide(&project, !args.deny)?;
+    eprintln!(
+        "{}{} the activation scripts of {}",
+        console::style(console::Emoji("✔ ", "")).green(),
+        if args.deny { "Denied" } else { "Trusted" },
+        console::style(project.name()).bold()
+    );
+    Ok(())
+}
@@ -0,0 +1,347 @@
+use crate::lock_file::{self, load_lock_file, merge_lock_files};
+use crate::utils::spec_format::{self, package_kind};
+use crate::{default_client, end_of_life, Project};
+use clap::Parser;
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::{MatchSpec, PackageName, Platform};
+use rattler_lock::{CondaLock, LockedDependency};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Update the locked packages to the newest versions that are allowed by the manifest.
+///
+/// Only the lock file is updated, run `pixi install` to update the environment.
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// The packages to update, all packages are updated if none are specified
+    pub packages: Vec<String>,
+
+    /// Update a single package to exactly the given version, e.g. `numpy@1.26.2`
+    #[arg(long, value_name = "PACKAGE@VERSION", conflicts_with = "packages")]
+    pub only: Option<String>,
+
+    /// Allow pre-release versions of the updated packages. The opt-in is written to the manifest,
+    /// so later solves keep them
+    #[arg(long)]
+    pub prerelease: bool,
+
+    /// Only determine the updates, don't modify the lock file
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Output the updates as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// A change of a locked package.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct PackageUpdate {
+    /// The name of the package.
+    pub name: String,
+
+    /// Either `conda` or `pypi`.
+    pub kind: &'static str,
+
+    /// The platform for which the package changes.
+    pub platform: Platform,
+
+    /// The previously locked version, `None` if the package is added.
+    pub from: Option<String>,
+
+    /// The newly locked version, `None` if the package is removed.
+    pub to: Option<String>,
+
+    /// A url of the changelog of the package, if it is known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changelog_url: Option<String>,
+}
+
+/// Determines the packages whose version differs between two lock files.
+pub fn diff_lock_files(previous: &CondaLock, updated: &CondaLock) -> Vec<PackageUpdate> {
+    let versions = |lock_file: &CondaLock| {
+        lock_file
+            .package
+            .iter()
+            .map(|package| {
+                (
+                    (
+                        package.platform,
+                        package_kind(package),
+                        package.name.clone(),
+                    ),
+                    package.version.clone(),
+                )
+            })
+            .collect::<HashMap<_, _>>()
+    };
+    let mut previous = versions(previous);
+    let updated = versions(updated);
+
+    let mut updates = Vec::new();
+    for ((platform, kind, name), to) in updated {
+        let from = previous.remove(&(platform, kind, name.clone()));
+        if from.as_ref() != Some(&to) {
+            updates.push(PackageUpdate {
+                name,
+                kind,
+                platform,
+                from,
+                to: Some(to),
+                changelog_url: None,
+            });
+        }
+    }
+    for ((platform, kind, name), from) in previous {
+        updates.push(PackageUpdate {
+            name,
+            kind,
+            platform,
+            from: Some(from),
+            to: None,
+            changelog_url: None,
+        });
+    }
+
+    updates.sort_by(|a, b| {
+        (&a.name, a.kind, a.platform.as_str()).cmp(&(&b.name, b.kind, b.platform.as_str()))
+    });
+    updates
+}
+
+/// Looks up the changelog of a PyPI package from the project urls on PyPI.
+async fn pypi_changelog_url(name: &str, version: &str) -> Option<String> {
+    let response = default_client()
+        .get(format!("https://pypi.org/pypi/{name}/{version}/json"))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    let json: serde_json::Value = response.json().await.ok()?;
+    let project_urls = json.get("info")?.get("project_urls")?.as_object()?;
+    project_urls
+        .iter()
+        .find(|(label, _)| {
+            let label = label.to_lowercase().replace([' ', '-', '_'], "");
+            [
+                "changelog",
+                "changes",
+                "releasenotes",
+                "history",
+                "whatsnew",
+            ]
+            .contains(&label.as_str())
+        })
+        .and_then(|(_, url)| url.as_str().map(ToString::to_string))
+}
+
+/// Parses the argument of `--only` into the name of the package and a spec that selects exactly the
+/// requested version.
+fn parse_only(only: &str) -> miette::Result<(String, MatchSpec)> {
+    let Some((name, version)) = only.split_once('@') else {
+        miette::bail!("expected '<package>@<version>' but got '{only}'");
+    };
+    let spec = MatchSpec::from_str(&format!("{name} =={version}"))
+        .into_diagnostic()
+        .wrap_err_with(|| format!("invalid package or version in '{only}'"))?;
+    Ok((name.to_lowercase(), spec))
+}
+
+/// Allows pre-release versions of the packages in `names`. PyPI dependencies get
+/// `allow-prerelease`, all other packages are added to the `prerelease-packages` of the project
+/// unless they are only locked as PyPI packages.
+fn allow_prereleases(
+    project: &mut Project,
+    lock_file: &CondaLock,
+    names: &[String],
+) -> miette::Result<()> {
+    if names.is_empty() {
+        miette::bail!(
+            help = "e.g. `pixi update --prerelease numpy`",
+            "`--prerelease` requires the packages that may be updated to a pre-release"
+        );
+    }
+    let mut conda_names = Vec::new();
+    for name in names {
+        let pypi_name = rip::types::PackageName::from_str(name).into_diagnostic()?;
+        if project.manifest.allow_pypi_prerelease(&pypi_name)? {
+            continue;
+        }
+        let is_conda = lock_file.package.iter().any(|package| {
+            package.name.to_lowercase() == *name && package_kind(package) == "conda"
+        });
+        if !is_conda {
+            miette::bail!(
+                "pre-releases of '{name}' can't be allowed, it is neither a conda package nor a pypi dependency of the project"
+            );
+        }
+        conda_names.push(PackageName::from_str(name).into_diagnostic()?);
+    }
+    project.manifest.add_prerelease_packages(conda_names)
+}
+
+/// Solves the project again without favoring the locked versions of the packages in `names`, or of
+/// all packages if `names` is empty. Returns the updated lock file and those of the other
+/// environments of the solve group of the project without writing them to disk.
+pub(crate) async fn solve_updates(
+    project: &Project,
+    existing_lock_file: &CondaLock,
+    names: &[String],
+    additional_specs: &[MatchSpec],
+) -> miette::Result<(CondaLock, Vec<(Project, CondaLock)>)> {
+    let is_updated = |package: &LockedDependency| {
+        names.is_empty() || names.contains(&package.name.to_lowercase())
+    };
+
+    // Only the platforms that contain the packages have to be solved again.
+    let platforms = project
+        .platforms()
+        .iter()
+        .copied()
+        .filter(|platform| {
+            names.is_empty()
+                || existing_lock_file
+                    .package
+                    .iter()
+                    .any(|package| package.platform == *platform && is_updated(package))
+        })
+        .collect_vec();
+
+    // Solve without favoring the locked versions of the packages that are updated.
+    let unlocked = CondaLock {
+        metadata: existing_lock_file.metadata.clone(),
+        package: existing_lock_file
+            .package
+            .iter()
+            .filter(|package| !is_updated(package))
+            .cloned()
+            .collect(),
+    };
+    let (mut solved, member_locks) =
+        lock_file::solve_conda_with_members(project, &unlocked, None, &platforms, additional_specs)
+            .await?;
+    if project.has_pypi_dependencies() {
+        solved = lock_file::solve_pypi(project, solved, &platforms).await?;
+    }
+    let lock_file = merge_lock_files(
+        project,
+        existing_lock_file.clone(),
+        vec![(project.lock_file_path(), solved)],
+    )?;
+    Ok((lock_file, member_locks))
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let mut project = Project::load_or_else_discover(args.manifest_path.as_deref())?;
+    let existing_lock_file = load_lock_file(&project).await?;
+
+    // Determine the packages that are updated and any exact version that is requested.
+    let (names, additional_specs) = match &args.only {
+        Some(only) => {
+            let (name, spec) = parse_only(only)?;
+            (vec![name], vec![spec])
+        }
+        None => (
+            args.packages
+                .iter()
+                .map(|name| name.to_lowercase())
+                .collect(),
+            vec![],
+        ),
+    };
+    if let Some(name) = names.iter().find(|name| {
+        !existing_lock_file
+            .package
+            .iter()
+            .any(|package| package.name.to_lowercase() == **name)
+    }) {
+        miette::bail!("'{name}' is not a locked package of the project");
+    }
+    if args.prerelease {
+        allow_prereleases(&mut project, &existing_lock_file, &names)?;
+    }
+
+    let (updated_lock_file, member_locks) =
+        solve_updates(&project, &existing_lock_file, &names, &additional_specs)
+            .await
+            .wrap_err_with(|| match &args.only {
+                Some(only) => format!(
+                "could not update to '{only}', the version might not be allowed by the manifest"
+            ),
+                None => String::from("could not solve the updated environment"),
+            })?;
+
+    end_of_life::warn_end_of_life_packages(&project, &updated_lock_file).await;
+
+    let mut updates = diff_lock_files(&existing_lock_file, &updated_lock_file);
+    for update in updates.iter_mut().filter(|update| update.kind == "pypi") {
+        if let Some(to) = &update.to {
+            update.changelog_url = pypi_changelog_url(&update.name, to).await;
+        }
+    }
+
+    if !args.dry_run {
+        lock_file::write_lock_file(&project, &updated_lock_file, &project.lock_file_path())?;
+        lock_file::write_member_lock_files(&member_locks)?;
+        if args.prerelease {
+            project.save()?;
+        }
+    }
+
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&updates).into_diagnostic()?
+        );
+    } else if updates.is_empty() {
+        eprintln!(
+            "{}All packages are up to date",
+            console::style(console::Emoji("✔ ", "")).green()
+        );
+    } else {
+        for update in &updates {
+            println!(
+                "{}: {}{}",
+                spec_format::locked_package(&update.name, update.kind, update.platform),
+                spec_format::version_change(update.from.as_deref(), update.to.as_deref()),
+                update
+                    .changelog_url
+                    .as_ref()
+                    .map(|url| format!(" ({url})"))
+                    .unwrap_or_default()
+            );
+        }
+        if args.dry_run {
+            eprintln!("The lock file was not modified (--dry-run)");
+        } else {
+            eprintln!(
+                "{}Updated {}, run `pixi install` to update the environment",
+                console::style(console::Emoji("✔ ", "")).green(),
+                project.lock_file_path().display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_only() {
+        let (name, spec) = parse_only("NumPy@1.26.2").unwrap();
+        assert_eq!(name, "numpy");
+        assert_eq!(spec.to_string(), "numpy ==1.26.2");
+        assert!(parse_only("numpy").is_err());
+    }
+}
@@ -9,7 +9,8 @@ use rattler_digest::{compute_file_digest, Sha256};
 use tokio::fs::File;
 use tokio_util::io::ReaderStream;
 
-use crate::{default_authenticated_client, progress};
+use crate::{auth, default_authenticated_client, progress};
+use url::Url;
 
 /// Upload a package to a prefix.dev channel
 #[derive(Parser, Debug)]
@@ -48,9 +49,11 @@ pub async fn execute(args: Args) -> miette::Result<()> {
 
     let file = File::open(&args.package_file).await.into_diagnostic()?;
 
-    let progress_bar = indicatif::ProgressBar::new(filesize)
-        .with_prefix("Uploading")
-        .with_style(progress::default_bytes_style());
+    let progress_bar = progress::global_multi_progress().add(
+        indicatif::ProgressBar::new(filesize)
+            .with_prefix("Uploading")
+            .with_style(progress::default_bytes_style()),
+    );
 
     let reader_stream = ReaderStream::new(file)
         .inspect_ok(move |bytes| {
@@ -62,8 +65,12 @@ pub async fn execute(args: Args) -> miette::Result<()> {
 
     let body = reqwest::Body::wrap_stream(reader_stream);
 
-    let response = client
-        .post(args.host.clone())
+    // The authenticated client only knows about the keyring, apply credentials from the other
+    // sources ourselves.
+    let url = Url::parse(&args.host).into_diagnostic()?;
+    let request = auth::authenticate_request(client.post(args.host.clone()), &url);
+
+    let response = request
         .header("X-File-Sha256", sha256sum)
         .header("X-File-Name", filename)
         .header("Content-Length", filesize)
@@ -0,0 +1,83 @@
+use crate::build_fallback;
+use crate::lock_file::{self, check_locked_provenance, verify_artifacts};
+use crate::Project;
+use clap::Parser;
+use itertools::Itertools;
+use rattler_conda_types::Platform;
+use std::path::PathBuf;
+
+/// Verify the locked packages of the project
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// The path to 'pixi.toml' or the directory that contains it
+    #[arg(long)]
+    pub manifest_path: Option<PathBuf>,
+
+    /// Verify that every locked package has a sha256 and that the conda packages are from the
+    /// channels of the project, then download the artifacts and compare them with the locked
+    /// sha256
+    #[arg(long, required = true)]
+    pub provenance: bool,
+
+    /// Only check what is recorded in the lock file, without downloading the artifacts
+    #[arg(long, requires = "provenance")]
+    pub no_download: bool,
+
+    /// The platforms to verify, defaults to the current platform
+    #[arg(long, short)]
+    pub platform: Vec<Platform>,
+
+    /// The environment to verify, one for every variant of the dependencies of the project
+    #[arg(long, short)]
+    pub environment: Option<String>,
+}
+
+pub async fn execute(args: Args) -> miette::Result<()> {
+    let project = Project::load_or_else_discover(args.manifest_path.as_deref())?
+        .with_environment(args.environment.as_deref())?;
+    if !project.lock_file_path().is_file() && !project.root_lock_file_path().is_file() {
+        miette::bail!(
+            help = "create it with `pixi install`",
+            "the project has no lock file"
+        );
+    }
+    let lock_file = lock_file::load_lock_file(&project).await?;
+    let platforms = if args.platform.is_empty() {
+        vec![project.platform()]
+    } else {
+        args.platform
+    };
+    let channels = project
+        .channels()
+        .iter()
+        .map(|channel| channel.base_url().clone())
+        .collect_vec();
+
+    // The packages that were built from source are verified against the local channel.
+    let resolved_lock_file = build_fallback::resolve_lock_file_urls(&project, &lock_file);
+    let mut findings = Vec::new();
+    for platform in &platforms {
+        findings.extend(check_locked_provenance(&lock_file, *platform, &channels));
+        if !args.no_download {
+            findings
+                .extend(verify_artifacts(&resolved_lock_file, *platform, project.config()).await);
+        }
+    }
+
+    if !findings.is_empty() {
+        miette::bail!(
+            help = "update the lock file with `pixi update` if the packages were rebuilt or moved on purpose, otherwise don't install the environment",
+            "the provenance of the locked packages could not be verified:\n{}",
+            findings
+                .iter()
+                .map(|finding| format!("  - {finding}"))
+                .join("\n")
+        );
+    }
+    eprintln!(
+        "{}Verified the provenance of the locked packages for {}",
+        console::style(console::Emoji("✔ ", "")).green(),
+        platforms.iter().join(", ")
+    );
+    Ok(())
+}
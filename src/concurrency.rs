@@ -0,0 +1,148 @@
+//! Limits on the number of solves, downloads and link jobs that run at the same time. A limit is
+//! taken from the command line, from the global configuration, or defaults to a number based on
+//! the CPUs of the machine, so small containers are not overwhelmed and large CI machines are not
+//! left idle.
+
+use crate::config::Config;
+use once_cell::sync::OnceCell;
+
+/// The number of jobs of every kind that run at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConcurrencyLimits {
+    /// The platforms that are solved at the same time.
+    pub solves: usize,
+    /// The packages and repodata files that are downloaded at the same time.
+    pub downloads: usize,
+    /// The packages that are linked into an environment at the same time.
+    pub link_jobs: usize,
+}
+
+impl ConcurrencyLimits {
+    /// Returns the default limits for a machine with `cpus` CPUs. Solving is bound by the CPU,
+    /// downloading by the network and linking by the disk.
+    pub fn for_cpus(cpus: usize) -> Self {
+        Self {
+            solves: (cpus / 2).clamp(1, 4),
+            downloads: (cpus * 4).clamp(8, 50),
+            link_jobs: (cpus * 2).clamp(4, 32),
+        }
+    }
+
+    /// Returns the default limits for this machine.
+    pub fn for_current_machine() -> Self {
+        Self::for_cpus(
+            std::thread::available_parallelism()
+                .map(|cpus| cpus.get())
+                .unwrap_or(1),
+        )
+    }
+}
+
+/// Limits that override the configuration, e.g. from the command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConcurrencyOverrides {
+    pub solves: Option<usize>,
+    pub downloads: Option<usize>,
+    pub link_jobs: Option<usize>,
+}
+
+static LIMITS: OnceCell<ConcurrencyLimits> = OnceCell::new();
+
+/// Resolves the limits from the `overrides`, the `config` and the `defaults`. A limit is at least
+/// one.
+fn resolve_limits(
+    overrides: ConcurrencyOverrides,
+    config: &Config,
+    defaults: ConcurrencyLimits,
+) -> ConcurrencyLimits {
+    ConcurrencyLimits {
+        solves: overrides
+            .solves
+            .or(config.max_concurrent_solves)
+            .unwrap_or(defaults.solves)
+            .max(1),
+        downloads: overrides
+            .downloads
+            .or(config.max_concurrent_downloads)
+            .unwrap_or(defaults.downloads)
+            .max(1),
+        link_jobs: overrides
+            .link_jobs
+            .or(config.max_concurrent_link_jobs)
+            .unwrap_or(defaults.link_jobs)
+            .max(1),
+    }
+}
+
+/// Sets the limits for the rest of the process. Has no effect if the limits were already used.
+pub fn init(overrides: ConcurrencyOverrides) {
+    let config = Config::load_global().unwrap_or_else(|err| {
+        tracing::warn!("failed to load the configuration: {err:?}");
+        Config::default()
+    });
+    let _ = LIMITS.set(resolve_limits(
+        overrides,
+        &config,
+        ConcurrencyLimits::for_current_machine(),
+    ));
+}
+
+/// Returns the limits of the process, without overrides if [`init`] was not called.
+pub fn limits() -> ConcurrencyLimits {
+    *LIMITS.get_or_init(|| {
+        let config = Config::load_global().unwrap_or_default();
+        resolve_limits(
+            ConcurrencyOverrides::default(),
+            &config,
+            ConcurrencyLimits::for_current_machine(),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_limits() {
+        assert_eq!(
+            ConcurrencyLimits::for_cpus(1),
+            ConcurrencyLimits {
+                solves: 1,
+                downloads: 8,
+                link_jobs: 4
+            }
+        );
+        assert_eq!(
+            ConcurrencyLimits::for_cpus(64),
+            ConcurrencyLimits {
+                solves: 4,
+                downloads: 50,
+                link_jobs: 32
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_limits() {
+        let config = Config::from_toml(
+            r#"
+            max-concurrent-solves = 2
+            max-concurrent-downloads = 0
+            "#,
+        )
+        .unwrap();
+        let overrides = ConcurrencyOverrides {
+            solves: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            resolve_limits(overrides, &config, ConcurrencyLimits::for_cpus(8)),
+            ConcurrencyLimits {
+                solves: 3,
+                downloads: 1,
+                link_jobs: 16
+            }
+        );
+    }
+}
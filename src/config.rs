@@ -1,4 +1,13 @@
+use crate::alias::Alias;
+use crate::consts;
+use indexmap::IndexMap;
+use miette::{IntoDiagnostic, WrapErr};
+use rattler_conda_types::{ChannelConfig, Platform};
+use reqwest::{Certificate, Client, ClientBuilder, Proxy};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use url::Url;
 
 /// Determines the default author based on the default git author. Both the name and the email
 /// address of the author are returned.
@@ -28,3 +37,698 @@ pub fn get_default_author() -> Option<(String, String)> {
 
     Some((name?, email.unwrap_or_else(|| "".into())))
 }
+
+/// User specific configuration of pixi. In contrast to the project manifest, this configuration
+/// is not meant to be shared with others but describes the machine pixi runs on.
+///
+/// The configuration is read from `~/.pixi/config.toml` and from `.pixi/config.toml` in the root
+/// of a project. Values from the project configuration take precedence.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// Rewrites the urls of locked packages before they are downloaded. The keys are url prefixes
+    /// that are replaced by the corresponding values. This allows using an artifact proxy without
+    /// modifying the lock file.
+    #[serde(default)]
+    pub url_rewrites: IndexMap<String, String>,
+
+    /// Whether environments with identical lock content share a single prefix in
+    /// `~/.pixi/shared-envs` instead of each project installing its own copy.
+    #[serde(default)]
+    pub shared_environments: Option<bool>,
+
+    /// A remote cache from which the outputs of tasks are downloaded instead of running the task.
+    #[serde(default)]
+    pub task_cache: Option<RemoteTaskCacheConfig>,
+
+    /// What to do when a globally installed package exposes an executable that shadows a critical
+    /// system binary like `git` or `python`.
+    #[serde(default)]
+    pub system_binary_shadowing: Option<ShadowingPolicy>,
+
+    /// The channels that are used when none are specified, e.g. by `pixi init` and
+    /// `pixi global install`.
+    #[serde(default)]
+    pub default_channels: Option<Vec<String>>,
+
+    /// The platforms that `pixi init` adds to a new project when none are specified, either names
+    /// of platforms or presets like `all-desktop`.
+    #[serde(default)]
+    pub default_platforms: Option<Vec<String>>,
+
+    /// The url against which channel names are resolved, defaults to
+    /// `https://conda.anaconda.org/`.
+    #[serde(default)]
+    pub channel_alias: Option<Url>,
+
+    /// The proxies through which requests are sent. The keys are either a scheme (`http` or
+    /// `https`) or a url prefix, the values are the urls of the proxies.
+    #[serde(default)]
+    pub proxies: IndexMap<String, Url>,
+
+    /// Whether the TLS certificates of servers are verified. Disabling this is insecure.
+    #[serde(default)]
+    pub ssl_verify: Option<bool>,
+
+    /// A file with additional root certificates in PEM format, e.g. of a corporate proxy.
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+
+    /// Whether the environments of projects are installed for a platform that runs through
+    /// emulation, e.g. `osx-64` under Rosetta on Apple Silicon.
+    #[serde(default)]
+    pub platform_preference: Option<PlatformPreference>,
+
+    /// Whether the activation scripts of every project run without asking the user to trust the
+    /// project first, e.g. on CI machines. Only read from the global configuration.
+    #[serde(default)]
+    pub trust_all_projects: Option<bool>,
+
+    /// Whether environments are only installed from lock files that are signed by one of the
+    /// `lock-signing-keys`.
+    #[serde(default)]
+    pub require_signed_lock: Option<bool>,
+
+    /// The minisign public keys, or paths of public key files, that are trusted to sign lock
+    /// files. Only read from the global configuration.
+    #[serde(default)]
+    pub lock_signing_keys: Vec<String>,
+
+    /// The number of platforms that are solved at the same time.
+    #[serde(default)]
+    pub max_concurrent_solves: Option<usize>,
+
+    /// The number of packages and repodata files that are downloaded at the same time.
+    #[serde(default)]
+    pub max_concurrent_downloads: Option<usize>,
+
+    /// The number of packages that are linked into an environment at the same time.
+    #[serde(default)]
+    pub max_concurrent_link_jobs: Option<usize>,
+
+    /// Names for pixi commands, e.g. `t = "run test"`.
+    #[serde(default)]
+    pub alias: IndexMap<String, Alias>,
+
+    /// The repodata that `pixi cache warm` fetches ahead of time.
+    #[serde(default)]
+    pub repodata_warming: Option<RepodataWarmingConfig>,
+
+    /// The shell that `pixi shell` starts when the shell of the user can't be detected, e.g. when
+    /// it is started from a GUI or on CI.
+    #[serde(default)]
+    pub default_shell: Option<DefaultShell>,
+}
+
+/// The channels that are used when neither the user nor the configuration specifies any.
+pub const DEFAULT_CHANNELS: &[&str] = &["conda-forge"];
+
+/// Determines what happens when an executable installed by pixi would shadow a critical binary of
+/// the system.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShadowingPolicy {
+    /// Expose the executable without a warning.
+    Allow,
+    /// Expose the executable but emit a warning.
+    #[default]
+    Warn,
+    /// Don't expose the executable, it is only available in the environment of the package.
+    Deny,
+}
+
+/// Determines for which platform the environments of projects are installed on a machine that can
+/// also run another platform through emulation, e.g. `osx-64` under Rosetta on Apple Silicon.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlatformPreference {
+    /// Only use the native platform, the emulated platform has to be requested with `--platform`.
+    #[default]
+    Native,
+    /// Use the emulated platform if the project doesn't support the native platform.
+    AllowEmulation,
+    /// Use the emulated platform if the project supports it, even if it supports the native
+    /// platform.
+    Emulation,
+}
+
+/// The shells that `pixi shell` can start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShellName {
+    Bash,
+    Zsh,
+    Fish,
+    Xonsh,
+    #[serde(alias = "nushell")]
+    #[value(alias = "nushell")]
+    Nu,
+    #[serde(alias = "pwsh")]
+    #[value(alias = "pwsh")]
+    Powershell,
+    Cmd,
+}
+
+impl std::fmt::Display for ShellName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = clap::ValueEnum::to_possible_value(self).expect("no variant is skipped");
+        f.write_str(value.get_name())
+    }
+}
+
+/// The shell that is started when the shell of the user can't be detected, either one for every
+/// platform or a table with a shell by platform selector: the name of a platform like `win-64`,
+/// `linux`, `osx`, `win` or `unix`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DefaultShell {
+    All(ShellName),
+    BySelector(IndexMap<String, ShellName>),
+}
+
+impl<'de> Deserialize<'de> for DefaultShell {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        serde_untagged::UntaggedEnumVisitor::new()
+            .string(|name| {
+                ShellName::deserialize(serde::de::value::StrDeserializer::new(name))
+                    .map(DefaultShell::All)
+            })
+            .map(|map| {
+                let shells: IndexMap<String, ShellName> = map.deserialize()?;
+                if let Some(selector) = shells.keys().find(|selector| {
+                    !SHELL_SELECTORS.contains(&selector.as_str())
+                        && selector.parse::<Platform>().is_err()
+                }) {
+                    return Err(serde::de::Error::custom(format!(
+                        "'{selector}' is not a platform or one of {}",
+                        SHELL_SELECTORS.join(", ")
+                    )));
+                }
+                Ok(DefaultShell::BySelector(shells))
+            })
+            .expecting("the name of a shell or a table with a shell by platform")
+            .deserialize(deserializer)
+    }
+}
+
+/// The selectors of a group of platforms in the `default-shell` table.
+const SHELL_SELECTORS: &[&str] = &["linux", "osx", "win", "unix"];
+
+impl DefaultShell {
+    /// Returns the shell for the given platform. The name of the platform takes precedence over
+    /// the name of its operating system, which takes precedence over `unix`.
+    pub fn for_platform(&self, platform: Platform) -> Option<ShellName> {
+        let shells = match self {
+            DefaultShell::All(shell) => return Some(*shell),
+            DefaultShell::BySelector(shells) => shells,
+        };
+        let os = if platform.is_linux() {
+            Some("linux")
+        } else if platform.is_osx() {
+            Some("osx")
+        } else if platform.is_windows() {
+            Some("win")
+        } else {
+            None
+        };
+        let unix = platform.is_unix().then_some("unix");
+        let platform = platform.to_string();
+        [Some(platform.as_str()), os, unix]
+            .into_iter()
+            .flatten()
+            .find_map(|selector| shells.get(selector).copied())
+    }
+}
+
+/// The configuration of a remote cache for the outputs of tasks.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RemoteTaskCacheConfig {
+    /// The base url of the cache. The outputs of a task are stored as a tarball at
+    /// `<url>/<platform>/<cache key>.tar`.
+    pub url: Url,
+
+    /// Whether the outputs of tasks that are executed on this machine are uploaded to the cache.
+    #[serde(default)]
+    pub upload: bool,
+}
+
+/// The repodata that is fetched ahead of time, so solves start with an up to date cache.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RepodataWarmingConfig {
+    /// Channels that are warmed in addition to the channels of the project.
+    #[serde(default)]
+    pub channels: Vec<String>,
+
+    /// The platforms of these channels that are warmed, defaults to the current platform.
+    #[serde(default)]
+    pub platforms: Vec<Platform>,
+
+    /// Whether `pixi shell` warms the repodata of the project in the background.
+    #[serde(default)]
+    pub on_project_open: bool,
+}
+
+impl Config {
+    /// Parses the configuration from the given TOML string.
+    pub fn from_toml(contents: &str) -> miette::Result<Self> {
+        toml_edit::de::from_str(contents).into_diagnostic()
+    }
+
+    /// Loads the configuration from the given path. Returns the default configuration if the file
+    /// does not exist.
+    pub fn from_path(path: &Path) -> miette::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Self::from_toml(&contents)
+                .wrap_err_with(|| format!("failed to parse config file '{}'", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).into_diagnostic(),
+        }
+    }
+
+    /// Loads the global configuration from `~/.pixi/config.toml`.
+    pub fn load_global() -> miette::Result<Self> {
+        match global_config_path() {
+            Some(path) => Self::from_path(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Loads the global configuration merged with the configuration of the project located at
+    /// `project_root`.
+    pub fn load(project_root: &Path) -> miette::Result<Self> {
+        let project_config = Self::from_path(
+            &project_root
+                .join(consts::PIXI_DIR)
+                .join(consts::CONFIG_FILE),
+        )?;
+        Ok(Self::load_global()?.merge(project_config))
+    }
+
+    /// Merges the `other` configuration into this one, values of `other` take precedence.
+    pub fn merge(mut self, other: Self) -> Self {
+        self.url_rewrites.extend(other.url_rewrites);
+        self.shared_environments = other.shared_environments.or(self.shared_environments);
+        self.task_cache = other.task_cache.or(self.task_cache);
+        self.system_binary_shadowing = other
+            .system_binary_shadowing
+            .or(self.system_binary_shadowing);
+        self.default_channels = other.default_channels.or(self.default_channels);
+        self.default_platforms = other.default_platforms.or(self.default_platforms);
+        self.channel_alias = other.channel_alias.or(self.channel_alias);
+        self.proxies.extend(other.proxies);
+        self.ssl_verify = other.ssl_verify.or(self.ssl_verify);
+        self.ca_bundle = other.ca_bundle.or(self.ca_bundle);
+        self.platform_preference = other.platform_preference.or(self.platform_preference);
+        self.trust_all_projects = other.trust_all_projects.or(self.trust_all_projects);
+        // A project can require a signed lock file, but not lift the requirement of the machine.
+        // The keys are only trusted from the global configuration, so they are not merged.
+        self.require_signed_lock = match (self.require_signed_lock, other.require_signed_lock) {
+            (Some(required), Some(other)) => Some(required || other),
+            (required, other) => other.or(required),
+        };
+        self.max_concurrent_solves = other.max_concurrent_solves.or(self.max_concurrent_solves);
+        self.max_concurrent_downloads = other
+            .max_concurrent_downloads
+            .or(self.max_concurrent_downloads);
+        self.max_concurrent_link_jobs = other
+            .max_concurrent_link_jobs
+            .or(self.max_concurrent_link_jobs);
+        self.alias.extend(other.alias);
+        self.repodata_warming = other.repodata_warming.or(self.repodata_warming);
+        self.default_shell = other.default_shell.or(self.default_shell);
+        self
+    }
+
+    /// Returns true if environments with identical lock content should share a prefix.
+    pub fn shared_environments(&self) -> bool {
+        self.shared_environments.unwrap_or(false)
+    }
+
+    /// Returns what to do when an executable would shadow a critical system binary.
+    pub fn system_binary_shadowing(&self) -> ShadowingPolicy {
+        self.system_binary_shadowing.unwrap_or_default()
+    }
+
+    /// Returns whether environments are installed for a platform that runs through emulation.
+    pub fn platform_preference(&self) -> PlatformPreference {
+        self.platform_preference.unwrap_or_default()
+    }
+
+    /// Returns true if the activation scripts of projects run without asking for trust.
+    pub fn trust_all_projects(&self) -> bool {
+        self.trust_all_projects.unwrap_or(false)
+    }
+
+    /// Returns true if environments are only installed from signed lock files.
+    pub fn require_signed_lock(&self) -> bool {
+        self.require_signed_lock.unwrap_or(false)
+    }
+
+    /// Returns the channels that are used when none are specified.
+    pub fn default_channels(&self) -> Vec<String> {
+        self.default_channels.clone().unwrap_or_else(|| {
+            DEFAULT_CHANNELS
+                .iter()
+                .copied()
+                .map(ToOwned::to_owned)
+                .collect()
+        })
+    }
+
+    /// Returns the platforms, or presets of platforms, that a new project supports when none are
+    /// specified. Defaults to the current platform.
+    pub fn default_platforms(&self) -> Vec<String> {
+        self.default_platforms
+            .clone()
+            .unwrap_or_else(|| vec![String::from("current")])
+    }
+
+    /// Returns the configuration that is used to resolve channel names to urls.
+    pub fn channel_config(&self) -> ChannelConfig {
+        match &self.channel_alias {
+            Some(channel_alias) => ChannelConfig {
+                channel_alias: channel_alias.clone(),
+            },
+            None => ChannelConfig::default(),
+        }
+    }
+
+    /// Constructs the http client with the proxies and TLS settings of this configuration.
+    pub fn http_client(&self) -> miette::Result<Client> {
+        self.http_client_builder()?.build().into_diagnostic()
+    }
+
+    /// Returns a builder of an http client with the proxies and TLS settings of this
+    /// configuration, so more settings can be added.
+    pub fn http_client_builder(&self) -> miette::Result<ClientBuilder> {
+        let mut builder = Client::builder();
+        for (target, proxy_url) in &self.proxies {
+            let proxy = match target.as_str() {
+                "http" => Proxy::http(proxy_url.as_str()),
+                "https" => Proxy::https(proxy_url.as_str()),
+                "all" => Proxy::all(proxy_url.as_str()),
+                prefix => {
+                    let (prefix, proxy_url) = (prefix.to_string(), proxy_url.clone());
+                    Ok(Proxy::custom(move |url| {
+                        url.as_str().starts_with(&prefix).then(|| proxy_url.clone())
+                    }))
+                }
+            }
+            .into_diagnostic()
+            .wrap_err_with(|| format!("invalid proxy '{proxy_url}' for '{target}'"))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(ca_bundle) = &self.ca_bundle {
+            let pem = std::fs::read_to_string(ca_bundle)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to read '{}'", ca_bundle.display()))?;
+            let certificates = pem_certificates(&pem);
+            if certificates.is_empty() {
+                miette::bail!("'{}' contains no certificates", ca_bundle.display());
+            }
+            for certificate in certificates {
+                let certificate = Certificate::from_pem(certificate.as_bytes())
+                    .into_diagnostic()
+                    .wrap_err_with(|| {
+                        format!("invalid certificate in '{}'", ca_bundle.display())
+                    })?;
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        if self.ssl_verify == Some(false) {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        Ok(builder)
+    }
+
+    /// Rewrites the given url according to the `url-rewrites` configuration. The longest matching
+    /// prefix is used.
+    pub fn rewrite_url(&self, url: &Url) -> Url {
+        let url_str = url.as_str();
+        self.url_rewrites
+            .iter()
+            .filter(|(prefix, _)| url_str.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .and_then(|(prefix, replacement)| {
+                Url::parse(&format!("{replacement}{}", &url_str[prefix.len()..])).ok()
+            })
+            .unwrap_or_else(|| url.clone())
+    }
+}
+
+/// Returns the path to the global configuration file.
+pub fn global_config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(consts::PIXI_DIR).join(consts::CONFIG_FILE))
+}
+
+/// Splits a bundle of PEM encoded certificates into the individual certificates, anything outside
+/// of the certificates, e.g. comments, is skipped.
+fn pem_certificates(pem: &str) -> Vec<&str> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+    let mut certificates = Vec::new();
+    let mut rest = pem;
+    while let Some(start) = rest.find(BEGIN) {
+        let Some(end) = rest[start..].find(END) else {
+            break;
+        };
+        let end = start + end + END.len();
+        certificates.push(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    certificates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rewrite_url() {
+        let config = Config::from_toml(
+            r#"
+            [url-rewrites]
+            "https://conda.anaconda.org/" = "https://artifactory.example.com/api/conda/"
+            "https://conda.anaconda.org/conda-forge/" = "https://proxy.example.com/conda-forge/"
+            "#,
+        )
+        .unwrap();
+
+        let rewrite = |url: &str| config.rewrite_url(&Url::parse(url).unwrap()).to_string();
+        assert_eq!(
+            rewrite("https://conda.anaconda.org/conda-forge/noarch/foo-1.0-0.conda"),
+            "https://proxy.example.com/conda-forge/noarch/foo-1.0-0.conda"
+        );
+        assert_eq!(
+            rewrite("https://conda.anaconda.org/bioconda/noarch/bar-1.0-0.conda"),
+            "https://artifactory.example.com/api/conda/bioconda/noarch/bar-1.0-0.conda"
+        );
+        assert_eq!(
+            rewrite("https://repo.prefix.dev/foo/noarch/baz-1.0-0.conda"),
+            "https://repo.prefix.dev/foo/noarch/baz-1.0-0.conda"
+        );
+    }
+
+    #[test]
+    fn test_task_cache_config() {
+        let global = Config::from_toml(
+            r#"
+            [task-cache]
+            url = "https://cache.example.com/pixi/"
+            "#,
+        )
+        .unwrap();
+        let task_cache = global.task_cache.as_ref().unwrap();
+        assert_eq!(task_cache.url.as_str(), "https://cache.example.com/pixi/");
+        assert!(!task_cache.upload);
+
+        let project = Config::from_toml(
+            r#"
+            [task-cache]
+            url = "https://ci-cache.example.com/"
+            upload = true
+            "#,
+        )
+        .unwrap();
+        let merged = global.merge(project);
+        assert!(merged.task_cache.unwrap().upload);
+    }
+
+    #[test]
+    fn test_pem_certificates() {
+        let pem = "# First\n-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n\
+            # Second\n-----BEGIN CERTIFICATE-----\nMIIC\n-----END CERTIFICATE-----\n\
+            -----BEGIN CERTIFICATE-----\ntruncated";
+        assert_eq!(
+            pem_certificates(pem),
+            vec![
+                "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----",
+                "-----BEGIN CERTIFICATE-----\nMIIC\n-----END CERTIFICATE-----",
+            ]
+        );
+        assert!(pem_certificates("").is_empty());
+    }
+
+    #[test]
+    fn test_channel_config() {
+        let config = Config::default();
+        assert_eq!(config.default_channels(), vec!["conda-forge"]);
+        assert_eq!(
+            config.channel_config().channel_alias,
+            ChannelConfig::default().channel_alias
+        );
+
+        let global = Config::from_toml(
+            r#"
+            default-channels = ["conda-forge", "bioconda"]
+            channel-alias = "https://artifactory.example.com/api/conda/"
+            "#,
+        )
+        .unwrap();
+        let merged =
+            global.merge(Config::from_toml(r#"default-channels = ["robostack"]"#).unwrap());
+        assert_eq!(merged.default_channels(), vec!["robostack"]);
+        assert_eq!(
+            merged.channel_config().channel_alias.as_str(),
+            "https://artifactory.example.com/api/conda/"
+        );
+    }
+
+    #[test]
+    fn test_alias_config() {
+        let global = Config::from_toml(
+            r#"
+            [alias]
+            t = "run test"
+            up = "update"
+            "#,
+        )
+        .unwrap();
+        let merged =
+            global.merge(Config::from_toml(r#"alias = { t = ["run", "check"] }"#).unwrap());
+        assert_eq!(merged.alias["t"].0, vec!["run", "check"]);
+        assert_eq!(merged.alias["up"].0, vec!["update"]);
+    }
+
+    #[test]
+    fn test_repodata_warming_config() {
+        assert!(Config::default().repodata_warming.is_none());
+
+        let config = Config::from_toml(
+            r#"
+            [repodata-warming]
+            channels = ["bioconda"]
+            platforms = ["linux-64", "noarch"]
+            on-project-open = true
+            "#,
+        )
+        .unwrap();
+        let warming = config.repodata_warming.unwrap();
+        assert_eq!(warming.channels, vec!["bioconda"]);
+        assert_eq!(warming.platforms, vec![Platform::Linux64, Platform::NoArch]);
+        assert!(warming.on_project_open);
+
+        assert!(Config::from_toml("repodata-warming = { interval = \"1h\" }").is_err());
+    }
+
+    #[test]
+    fn test_default_platforms_config() {
+        assert_eq!(Config::default().default_platforms(), vec!["current"]);
+
+        let global = Config::from_toml(r#"default-platforms = ["all-desktop"]"#).unwrap();
+        assert_eq!(global.default_platforms(), vec!["all-desktop"]);
+        let merged = global.merge(Config::default());
+        assert_eq!(merged.default_platforms(), vec!["all-desktop"]);
+    }
+
+    #[test]
+    fn test_system_binary_shadowing_config() {
+        assert_eq!(
+            Config::default().system_binary_shadowing(),
+            ShadowingPolicy::Warn
+        );
+
+        let global = Config::from_toml(r#"system-binary-shadowing = "deny""#).unwrap();
+        assert_eq!(global.system_binary_shadowing(), ShadowingPolicy::Deny);
+
+        let project = Config::from_toml(r#"system-binary-shadowing = "allow""#).unwrap();
+        assert_eq!(
+            global.merge(project).system_binary_shadowing(),
+            ShadowingPolicy::Allow
+        );
+    }
+
+    #[test]
+    fn test_require_signed_lock_config() {
+        assert!(!Config::default().require_signed_lock());
+
+        let global = Config::from_toml(
+            r#"
+            require-signed-lock = true
+            lock-signing-keys = ["RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3"]
+            "#,
+        )
+        .unwrap();
+
+        // A project can't lift the requirement or trust other keys.
+        let project = Config::from_toml(
+            r#"
+            require-signed-lock = false
+            lock-signing-keys = ["/tmp/other.pub"]
+            "#,
+        )
+        .unwrap();
+        let merged = global.merge(project);
+        assert!(merged.require_signed_lock());
+        assert_eq!(merged.lock_signing_keys.len(), 1);
+
+        let project = Config::from_toml("require-signed-lock = true").unwrap();
+        assert!(Config::default().merge(project).require_signed_lock());
+    }
+
+    #[test]
+    fn test_default_shell_config() {
+        let config = Config::from_toml(r#"default-shell = "zsh""#).unwrap();
+        let default_shell = config.default_shell.unwrap();
+        assert_eq!(
+            default_shell.for_platform(Platform::Win64),
+            Some(ShellName::Zsh)
+        );
+
+        let config = Config::from_toml(
+            r#"
+            [default-shell]
+            unix = "fish"
+            osx = "zsh"
+            win = "pwsh"
+            win-arm64 = "cmd"
+            "#,
+        )
+        .unwrap();
+        let default_shell = config.default_shell.unwrap();
+        assert_eq!(
+            default_shell.for_platform(Platform::Linux64),
+            Some(ShellName::Fish)
+        );
+        assert_eq!(
+            default_shell.for_platform(Platform::OsxArm64),
+            Some(ShellName::Zsh)
+        );
+        assert_eq!(
+            default_shell.for_platform(Platform::Win64),
+            Some(ShellName::Powershell)
+        );
+        assert_eq!(
+            default_shell.for_platform(Platform::WinArm64),
+            Some(ShellName::Cmd)
+        );
+        assert_eq!(default_shell.for_platform(Platform::EmscriptenWasm32), None);
+
+        assert!(Config::from_toml(r#"default-shell = "tcsh""#).is_err());
+        assert!(Config::from_toml("default-shell = { windows = \"cmd\" }").is_err());
+    }
+}
@@ -1,6 +1,16 @@
 pub const PROJECT_MANIFEST: &str = "pixi.toml";
+pub const PROJECT_MANIFEST_ENV: &str = "PIXI_PROJECT_MANIFEST";
+pub const PROJECT_ROOT_MARKER: &str = ".pixi-root";
+pub const NO_PARENT_DISCOVERY_ENV: &str = "PIXI_NO_PARENT_DISCOVERY";
 pub const PROJECT_LOCK_FILE: &str = "pixi.lock";
 pub const PIXI_DIR: &str = ".pixi";
 pub const PREFIX_FILE_NAME: &str = "prefix";
 pub const ENVIRONMENT_DIR: &str = "env";
+pub const ENVIRONMENTS_DIR: &str = "envs";
+pub const LOCKS_DIR: &str = "locks";
+pub const SNAPSHOTS_DIR: &str = "snapshots";
 pub const PYPI_DEPENDENCIES: &str = "pypi-dependencies";
+pub const CONFIG_FILE: &str = "config.toml";
+pub const CHANNEL_END_OF_LIFE_FILE: &str = "eol.json";
+pub const BUILD_FALLBACK_DIR: &str = "build-fallback";
+pub const BUILD_ENVS_DIR: &str = "build-envs";
@@ -0,0 +1,270 @@
+//! Finds the packages in the environment of a project that are not part of the lock file, e.g.
+//! because someone ran `pip install` inside `pixi shell`. Such packages are not reproduced on other
+//! machines, so the project either has to adopt them as dependencies or they have to be removed.
+
+use crate::prefix::Prefix;
+use crate::utils::spec_format::normalize_pypi_name;
+use crate::{lock_file, Project};
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::Platform;
+use rattler_lock::CondaLock;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// A package that is installed in the environment but not locked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DriftedPackage {
+    pub name: String,
+    pub version: String,
+    pub kind: DriftedPackageKind,
+}
+
+/// How a drifted package was installed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DriftedPackageKind {
+    /// A conda package, e.g. installed with `conda install --prefix`.
+    Conda,
+    /// A python distribution that no conda package installed, e.g. with `pip install`.
+    Pypi {
+        /// The `site-packages` directory, relative to the prefix.
+        site_packages: PathBuf,
+        /// The `.dist-info` directory, relative to `site_packages`.
+        dist_info: PathBuf,
+        /// The tool that installed the distribution, from its `INSTALLER` file.
+        installer: Option<String>,
+    },
+}
+
+impl DriftedPackage {
+    pub fn is_pypi(&self) -> bool {
+        matches!(self.kind, DriftedPackageKind::Pypi { .. })
+    }
+}
+
+impl Display for DriftedPackage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            DriftedPackageKind::Conda => write!(f, "{} {} (conda)", self.name, self.version),
+            DriftedPackageKind::Pypi {
+                installer: Some(installer),
+                ..
+            } => write!(
+                f,
+                "{} {} (pypi, installed by {installer})",
+                self.name, self.version
+            ),
+            DriftedPackageKind::Pypi {
+                installer: None, ..
+            } => {
+                write!(f, "{} {} (pypi)", self.name, self.version)
+            }
+        }
+    }
+}
+
+/// Returns the packages in the environment of the project that are not part of its lock file.
+pub async fn find_project_drift(project: &Project) -> miette::Result<Vec<DriftedPackage>> {
+    let prefix = Prefix::new(project.environment_dir())?;
+    if !prefix.root().join("conda-meta").is_dir() {
+        miette::bail!(
+            help = "install it with `pixi install`",
+            "the environment of {} is not installed",
+            project.name()
+        );
+    }
+    if !project.lock_file_path().is_file() && !project.root_lock_file_path().is_file() {
+        miette::bail!(
+            help = "create it with `pixi install`",
+            "the project has no lock file"
+        );
+    }
+    let lock_file = lock_file::load_lock_file(project).await?;
+    find_drift(&prefix, &lock_file, project.platform()).await
+}
+
+/// Returns the packages in `prefix` that are not part of the lock file for `platform`.
+pub async fn find_drift(
+    prefix: &Prefix,
+    lock_file: &CondaLock,
+    platform: Platform,
+) -> miette::Result<Vec<DriftedPackage>> {
+    let locked = lock_file.get_packages_by_platform(platform).collect_vec();
+    let locked_conda = locked
+        .iter()
+        .filter(|package| !package.is_pypi())
+        .map(|package| package.name.to_lowercase())
+        .collect::<HashSet<_>>();
+    let locked_pypi = locked
+        .iter()
+        .filter(|package| package.is_pypi())
+        .map(|package| normalize_pypi_name(&package.name))
+        .collect::<HashSet<_>>();
+
+    let installed = prefix.find_installed_packages(None).await?;
+    let mut drifted = installed
+        .iter()
+        .filter(|record| {
+            !locked_conda.contains(record.repodata_record.package_record.name.as_normalized())
+        })
+        .map(|record| DriftedPackage {
+            name: record
+                .repodata_record
+                .package_record
+                .name
+                .as_source()
+                .to_string(),
+            version: record.repodata_record.package_record.version.to_string(),
+            kind: DriftedPackageKind::Conda,
+        })
+        .collect_vec();
+
+    // The python distributions of conda packages are part of the files of those packages.
+    let conda_files = installed
+        .iter()
+        .flat_map(|record| record.files.iter().cloned())
+        .collect::<HashSet<_>>();
+    drifted.extend(find_drifted_distributions(
+        prefix.root(),
+        &conda_files,
+        &locked_pypi,
+    ));
+    Ok(drifted
+        .into_iter()
+        .sorted_by(|a, b| a.name.cmp(&b.name))
+        .collect())
+}
+
+/// Returns the python distributions in the `site-packages` directories of the prefix that are
+/// neither installed by a conda package nor locked as pypi packages.
+fn find_drifted_distributions(
+    prefix: &Path,
+    conda_files: &HashSet<PathBuf>,
+    locked_pypi: &HashSet<String>,
+) -> Vec<DriftedPackage> {
+    let site_packages_dirs = std::fs::read_dir(prefix.join("lib"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("python"))
+        .map(|entry| {
+            Path::new("lib")
+                .join(entry.file_name())
+                .join("site-packages")
+        })
+        .chain([Path::new("Lib").join("site-packages")]);
+
+    let mut drifted = Vec::new();
+    for site_packages in site_packages_dirs {
+        for entry in std::fs::read_dir(prefix.join(&site_packages))
+            .into_iter()
+            .flatten()
+            .flatten()
+        {
+            let dir_name = entry.file_name().to_string_lossy().to_string();
+            let Some((name, version)) = dir_name
+                .strip_suffix(".dist-info")
+                .and_then(|stem| stem.split_once('-'))
+            else {
+                continue;
+            };
+            if conda_files.contains(&site_packages.join(&dir_name).join("METADATA"))
+                || locked_pypi.contains(&normalize_pypi_name(name))
+            {
+                continue;
+            }
+            let installer = std::fs::read_to_string(entry.path().join("INSTALLER"))
+                .ok()
+                .map(|installer| installer.trim().to_string())
+                .filter(|installer| !installer.is_empty());
+            drifted.push(DriftedPackage {
+                name: name.to_string(),
+                version: version.to_string(),
+                kind: DriftedPackageKind::Pypi {
+                    site_packages: site_packages.clone(),
+                    dist_info: PathBuf::from(&dir_name),
+                    installer,
+                },
+            });
+        }
+    }
+    drifted
+}
+
+/// Uninstalls the drifted python distributions from the prefix. Drifted conda packages are removed
+/// by installing the locked environment again.
+pub fn remove_drifted_distributions(
+    prefix: &Prefix,
+    packages: &[DriftedPackage],
+) -> miette::Result<()> {
+    for package in packages {
+        let DriftedPackageKind::Pypi {
+            site_packages,
+            dist_info,
+            ..
+        } = &package.kind
+        else {
+            continue;
+        };
+        tracing::info!(
+            "uninstalling python package {}-{}",
+            package.name,
+            package.version
+        );
+        rip::python_env::uninstall_distribution(&prefix.root().join(site_packages), dist_info)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                format!(
+                    "could not uninstall python package {}-{}",
+                    package.name, package.version
+                )
+            })?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_drifted_distributions() {
+        let prefix = tempfile::tempdir().unwrap();
+        let site_packages = Path::new("lib").join("python3.11").join("site-packages");
+        for (dist_info, installer) in [
+            ("numpy-1.26.2.dist-info", None),
+            ("Flask-3.0.0.dist-info", Some("pixi")),
+            ("rich-13.7.0.dist-info", Some("pip")),
+        ] {
+            let dir = prefix.path().join(&site_packages).join(dist_info);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("METADATA"), "").unwrap();
+            if let Some(installer) = installer {
+                std::fs::write(dir.join("INSTALLER"), format!("{installer}\n")).unwrap();
+            }
+        }
+
+        let conda_files = HashSet::from([site_packages
+            .join("numpy-1.26.2.dist-info")
+            .join("METADATA")]);
+        let locked_pypi = HashSet::from([String::from("flask")]);
+        let drifted = find_drifted_distributions(prefix.path(), &conda_files, &locked_pypi);
+        assert_eq!(
+            drifted,
+            vec![DriftedPackage {
+                name: String::from("rich"),
+                version: String::from("13.7.0"),
+                kind: DriftedPackageKind::Pypi {
+                    site_packages,
+                    dist_info: PathBuf::from("rich-13.7.0.dist-info"),
+                    installer: Some(String::from("pip")),
+                },
+            }]
+        );
+        assert_eq!(
+            drifted[0].to_string(),
+            "rich 13.7.0 (pypi, installed by pip)"
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Support for installing and running environments of a platform that this machine only runs
+//! through emulation. Currently this is `osx-64` on Apple Silicon, which runs under Rosetta 2.
+
+use crate::config::PlatformPreference;
+use rattler_conda_types::Platform;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The runtime of Rosetta 2, it only exists if Rosetta is installed.
+const ROSETTA_RUNTIME: &str = "/Library/Apple/usr/libexec/oah/libRosettaRuntime";
+
+/// Returns the platform that the given native platform can run through emulation.
+pub fn emulated_platform(native: Platform) -> Option<Platform> {
+    match native {
+        Platform::OsxArm64 => Some(Platform::Osx64),
+        _ => None,
+    }
+}
+
+/// Returns true if the emulation of the platform is available on this machine.
+pub fn emulation_available(platform: Platform) -> bool {
+    match platform {
+        Platform::Osx64 => Path::new(ROSETTA_RUNTIME).exists(),
+        _ => false,
+    }
+}
+
+/// Selects the platform for which the environment of a project is installed, based on the
+/// platforms the project supports and the preference of the user. Falls back to the native
+/// platform, even if the project doesn't support it, so the error refers to the native platform.
+pub fn select_platform(
+    native: Platform,
+    supported: &[Platform],
+    preference: PlatformPreference,
+) -> Platform {
+    let Some(emulated) = emulated_platform(native).filter(|p| supported.contains(p)) else {
+        return native;
+    };
+    match preference {
+        PlatformPreference::Native => native,
+        PlatformPreference::AllowEmulation if supported.contains(&native) => native,
+        PlatformPreference::AllowEmulation | PlatformPreference::Emulation => emulated,
+    }
+}
+
+/// Verifies that the requested platform can run on this machine, either natively or through
+/// emulation.
+pub fn verify_platform(native: Platform, requested: Platform) -> miette::Result<()> {
+    if requested == native {
+        return Ok(());
+    }
+    if emulated_platform(native) != Some(requested) {
+        miette::bail!("'{requested}' environments cannot run on this machine ({native})");
+    }
+    if !emulation_available(requested) {
+        return Err(miette::miette!(
+            help = "install Rosetta with `softwareupdate --install-rosetta`",
+            "'{requested}' environments require Rosetta, which is not installed"
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the environment variables that make the tools in an emulated environment behave as if
+/// they run on that platform, e.g. conda installs packages of the emulated platform and `arch`
+/// prefers the emulated architecture of universal binaries. Empty for the native platform.
+pub fn emulation_env(native: Platform, platform: Platform) -> HashMap<String, String> {
+    if platform == native {
+        return HashMap::new();
+    }
+    let mut env = HashMap::from([(String::from("CONDA_SUBDIR"), platform.to_string())]);
+    if platform == Platform::Osx64 {
+        env.insert(String::from("ARCHPREFERENCE"), String::from("x86_64,arm64"));
+    }
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_platform() {
+        let both = [Platform::Osx64, Platform::OsxArm64];
+        let intel = [Platform::Osx64];
+        let native = Platform::OsxArm64;
+
+        assert_eq!(
+            select_platform(native, &intel, PlatformPreference::Native),
+            Platform::OsxArm64
+        );
+        assert_eq!(
+            select_platform(native, &intel, PlatformPreference::AllowEmulation),
+            Platform::Osx64
+        );
+        assert_eq!(
+            select_platform(native, &both, PlatformPreference::AllowEmulation),
+            Platform::OsxArm64
+        );
+        assert_eq!(
+            select_platform(native, &both, PlatformPreference::Emulation),
+            Platform::Osx64
+        );
+        assert_eq!(
+            select_platform(native, &[Platform::OsxArm64], PlatformPreference::Emulation),
+            Platform::OsxArm64
+        );
+        assert_eq!(
+            select_platform(Platform::Linux64, &intel, PlatformPreference::Emulation),
+            Platform::Linux64
+        );
+    }
+
+    #[test]
+    fn test_emulation_env() {
+        assert!(emulation_env(Platform::OsxArm64, Platform::OsxArm64).is_empty());
+        let env = emulation_env(Platform::OsxArm64, Platform::Osx64);
+        assert_eq!(env["CONDA_SUBDIR"], "osx-64");
+        assert!(verify_platform(Platform::Linux64, Platform::Osx64).is_err());
+        assert!(verify_platform(Platform::Linux64, Platform::Linux64).is_ok());
+    }
+}
@@ -0,0 +1,195 @@
+//! Deprecation and end-of-life metadata of packages. A channel can list the package versions that
+//! are end-of-life in an `eol.json` file in its root, and a project can add its own entries in the
+//! `[end-of-life]` table of the manifest. Pixi warns when such a version is locked.
+
+use crate::consts::CHANNEL_END_OF_LIFE_FILE;
+use crate::warnings::{self, WarningCode};
+use crate::{auth, default_authenticated_client, Project};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use rattler_conda_types::{Channel, PackageName, Platform, Version, VersionSpec};
+use rattler_lock::{CondaLock, LockedDependencyKind};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// The versions of a package that are end-of-life or deprecated.
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndOfLife {
+    /// The versions that are end-of-life, e.g. `<3.8` for python.
+    #[serde_as(as = "DisplayFromStr")]
+    pub versions: VersionSpec,
+
+    /// Why the versions are end-of-life or what to use instead.
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// A locked package whose version is end-of-life.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct EndOfLifePackage {
+    pub name: String,
+    pub version: String,
+    /// The platforms for which the version is locked.
+    pub platforms: Vec<Platform>,
+    pub message: Option<String>,
+}
+
+impl Display for EndOfLifePackage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} is end-of-life ({})",
+            self.name,
+            self.version,
+            self.platforms.iter().join(", ")
+        )?;
+        if let Some(message) = &self.message {
+            write!(f, ": {message}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Fetches the end-of-life metadata of a channel. Most channels don't provide the metadata, so any
+/// failure results in an empty list.
+async fn fetch_channel_end_of_life(channel: &Channel) -> IndexMap<PackageName, EndOfLife> {
+    let Ok(url) = channel.base_url().join(CHANNEL_END_OF_LIFE_FILE) else {
+        return IndexMap::new();
+    };
+    let request = auth::authenticate_request(default_authenticated_client().get(url.clone()), &url);
+    let response = match request.send().await.and_then(|r| r.error_for_status()) {
+        Ok(response) => response,
+        Err(err) => {
+            tracing::debug!("no end-of-life metadata at {url}: {err}");
+            return IndexMap::new();
+        }
+    };
+    response.json().await.unwrap_or_else(|err| {
+        tracing::warn!("ignoring the invalid end-of-life metadata at {url}: {err}");
+        IndexMap::new()
+    })
+}
+
+/// Returns the end-of-life metadata that applies to the project. The entries of the manifest take
+/// precedence over those of the channels, and earlier channels over later ones.
+pub async fn end_of_life_list(project: &Project) -> IndexMap<PackageName, EndOfLife> {
+    let channel_lists =
+        futures::future::join_all(project.channels().iter().map(fetch_channel_end_of_life)).await;
+    let mut list = IndexMap::new();
+    for (name, end_of_life) in channel_lists.into_iter().flatten() {
+        list.entry(name).or_insert(end_of_life);
+    }
+    list.extend(
+        project
+            .end_of_life()
+            .iter()
+            .map(|(name, end_of_life)| (name.clone(), end_of_life.clone())),
+    );
+    list
+}
+
+/// Returns the locked conda packages whose version is end-of-life according to `list`.
+pub fn find_end_of_life_packages(
+    lock_file: &CondaLock,
+    list: &IndexMap<PackageName, EndOfLife>,
+) -> Vec<EndOfLifePackage> {
+    find_end_of_life(
+        lock_file
+            .package
+            .iter()
+            .filter(|package| matches!(package.kind, LockedDependencyKind::Conda(_)))
+            .map(|package| {
+                (
+                    package.platform,
+                    package.name.as_str(),
+                    package.version.as_str(),
+                )
+            }),
+        list,
+    )
+}
+
+/// Returns the packages, given as platform, name and version, whose version is end-of-life.
+fn find_end_of_life<'a>(
+    packages: impl IntoIterator<Item = (Platform, &'a str, &'a str)>,
+    list: &IndexMap<PackageName, EndOfLife>,
+) -> Vec<EndOfLifePackage> {
+    let list: HashMap<&str, &EndOfLife> = list
+        .iter()
+        .map(|(name, end_of_life)| (name.as_normalized(), end_of_life))
+        .collect();
+
+    let mut found: IndexMap<(&str, &str), EndOfLifePackage> = IndexMap::new();
+    for (platform, name, version) in packages {
+        let Some(end_of_life) = list.get(name.to_lowercase().as_str()) else {
+            continue;
+        };
+        let Ok(parsed_version) = Version::from_str(version) else {
+            continue;
+        };
+        if !end_of_life.versions.matches(&parsed_version) {
+            continue;
+        }
+        found
+            .entry((name, version))
+            .or_insert_with(|| EndOfLifePackage {
+                name: name.to_string(),
+                version: version.to_string(),
+                platforms: Vec::new(),
+                message: end_of_life.message.clone(),
+            })
+            .platforms
+            .push(platform);
+    }
+    found
+        .into_values()
+        .sorted_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)))
+        .collect()
+}
+
+/// Emits a warning for every locked package whose version is end-of-life.
+pub async fn warn_end_of_life_packages(project: &Project, lock_file: &CondaLock) {
+    let list = end_of_life_list(project).await;
+    if list.is_empty() {
+        return;
+    }
+    for package in find_end_of_life_packages(lock_file, &list) {
+        warnings::emit(WarningCode::EndOfLife, package.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_end_of_life() {
+        let list: IndexMap<PackageName, EndOfLife> = serde_json::from_str(
+            r#"{
+                "python": { "versions": "<3.8", "message": "use python 3.8 or newer" },
+                "numpy": { "versions": "<1.20" }
+            }"#,
+        )
+        .unwrap();
+
+        let packages = find_end_of_life(
+            [
+                (Platform::Linux64, "python", "3.7.12"),
+                (Platform::Win64, "python", "3.7.12"),
+                (Platform::Linux64, "numpy", "1.26.2"),
+                (Platform::Linux64, "pip", "23.3.1"),
+            ],
+            &list,
+        );
+        assert_eq!(packages.len(), 1);
+        assert_eq!(
+            packages[0].to_string(),
+            "python 3.7.12 is end-of-life (linux-64, win-64): use python 3.8 or newer"
+        );
+    }
+}
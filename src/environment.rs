@@ -1,16 +1,25 @@
 use crate::{
-    consts, default_authenticated_client, install, install_pypi, lock_file, prefix::Prefix,
-    progress, virtual_packages::verify_current_platform_has_required_virtual_packages, Project,
+    build_envs, consts, default_authenticated_client, emulation, install, install_pypi, lock_file,
+    menuinst, overlay,
+    prefix::Prefix,
+    progress,
+    shared_prefix::{self, SharedPrefixStore},
+    shebang, task,
+    virtual_packages::verify_current_platform_has_required_virtual_packages,
+    warnings::{self, WarningCode},
+    Project,
 };
+use itertools::Itertools;
 use miette::{Context, IntoDiagnostic, LabeledSpan};
 
+use crate::install::PostLinkScriptPolicy;
 use crate::lock_file::lock_file_satisfies_project;
 use rattler::install::{PythonInfo, Transaction};
 use rattler_conda_types::{Platform, PrefixRecord, RepoDataRecord};
 use rattler_lock::CondaLock;
 use rattler_repodata_gateway::sparse::SparseRepoData;
 use rip::index::PackageDb;
-use std::{io::ErrorKind, path::Path};
+use std::{collections::HashSet, io::ErrorKind, path::Path};
 
 /// Verify the location of the prefix folder is not changed so the applied prefix path is still valid.
 /// Errors when there is a file system error or the path does not align with the defined prefix.
@@ -60,14 +69,21 @@ pub fn sanity_check_project(project: &Project) -> miette::Result<()> {
     // Sanity check of prefix location
     verify_prefix_location_unchanged(project.pixi_dir().join(consts::PREFIX_FILE_NAME).as_path())?;
 
-    // Make sure the project supports the current platform
-    let platform = Platform::current();
+    // Make sure the project supports the platform of the environment
+    let platform = project.platform();
     if !project.platforms().contains(&platform) {
         let span = project.manifest.parsed.project.platforms.span();
-        return Err(miette::miette!(
-            help = format!(
+        let help = match emulation::emulated_platform(platform) {
+            Some(emulated) if project.platforms().contains(&emulated) => format!(
+                "The project supports {emulated}, which can run through emulation on your platform ({platform}). \
+                Use `--platform {emulated}` or set `platform-preference = \"allow-emulation\"` in the pixi configuration."
+            ),
+            _ => format!(
                 "The project needs to be configured to support your platform ({platform})."
             ),
+        };
+        return Err(miette::miette!(
+            help = help,
             labels = vec![LabeledSpan::at(
                 span.unwrap_or_default(),
                 format!("add '{platform}' here"),
@@ -76,6 +92,12 @@ pub fn sanity_check_project(project: &Project) -> miette::Result<()> {
         )
         .with_source_code(project.manifest_named_source()));
     }
+    if project.is_emulated() {
+        tracing::info!(
+            "using the {platform} environment through emulation on {}",
+            Platform::current()
+        );
+    }
 
     // Make sure the system requirements are met
     verify_current_platform_has_required_virtual_packages(project)?;
@@ -83,6 +105,38 @@ pub fn sanity_check_project(project: &Project) -> miette::Result<()> {
     Ok(())
 }
 
+/// Emits a warning if the environment contains packages of another platform than the one it is
+/// installed for, e.g. when it was installed natively before and is now installed for a platform
+/// that runs through emulation. The packages of the other platform are replaced.
+fn warn_mixed_platforms(installed_packages: &[PrefixRecord], platform: Platform) {
+    let other_platforms = installed_packages
+        .iter()
+        .map(|record| record.repodata_record.package_record.subdir.as_str())
+        .filter(|subdir| *subdir != platform.as_str() && *subdir != Platform::NoArch.as_str())
+        .unique()
+        .collect_vec();
+    if !other_platforms.is_empty() {
+        warnings::emit(
+            WarningCode::MixedPlatforms,
+            format!(
+                "the environment contains packages for {}, they are replaced by the packages for {platform}",
+                other_platforms.join(", ")
+            ),
+        );
+    }
+}
+
+/// Loads the lock-file of the project after verifying its signature against the keys the
+/// configuration trusts. The signature is verified over the same contents that are parsed.
+fn load_signed_lock_file(project: &Project) -> miette::Result<CondaLock> {
+    let lock_file_path = if project.lock_file_path().is_file() {
+        project.lock_file_path()
+    } else {
+        project.root_lock_file_path()
+    };
+    lock_file::read_signed_lock_file(&lock_file_path, &project.config().lock_signing_keys)
+}
+
 /// Specifies how the lock-file should be updated.
 #[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
 pub enum LockFileUsage {
@@ -113,6 +167,26 @@ impl LockFileUsage {
     }
 }
 
+/// Selects the groups of packages of the lock-file that are installed in the environment, e.g. to
+/// only install the conda packages in a container image that adds the pypi packages in a separate
+/// layer. The packages of a group that is not installed are left untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstallFilter {
+    /// Install the conda packages
+    pub conda: bool,
+    /// Install the pypi packages
+    pub pypi: bool,
+}
+
+impl Default for InstallFilter {
+    fn default() -> Self {
+        Self {
+            conda: true,
+            pypi: true,
+        }
+    }
+}
+
 /// Returns the prefix associated with the given environment. If the prefix doesn't exist or is not
 /// up to date it is updated.
 ///
@@ -125,24 +199,79 @@ pub async fn get_up_to_date_prefix(
     usage: LockFileUsage,
     no_install: bool,
     sparse_repo_data: Option<Vec<SparseRepoData>>,
+) -> miette::Result<Prefix> {
+    get_filtered_prefix(
+        project,
+        usage,
+        no_install,
+        InstallFilter::default(),
+        sparse_repo_data,
+    )
+    .await
+}
+
+/// Same as [`get_up_to_date_prefix`] but only installs the groups of packages selected by the
+/// `filter`. The lock-file is always updated completely.
+pub async fn get_filtered_prefix(
+    project: &Project,
+    usage: LockFileUsage,
+    no_install: bool,
+    filter: InstallFilter,
+    sparse_repo_data: Option<Vec<SparseRepoData>>,
 ) -> miette::Result<Prefix> {
     // Make sure the project is in a sane state
     sanity_check_project(project)?;
 
-    // Start loading the installed packages in the background
+    // If the environment used to be shared but sharing has been disabled, detach it from the
+    // shared prefix so the shared prefix is not modified. A partial environment is never shared,
+    // the shared prefixes are identified by the complete content of the lock-file.
+    let shared_environments =
+        project.config().shared_environments() && filter == InstallFilter::default();
+    if !shared_environments && project.environment_dir().is_symlink() {
+        SharedPrefixStore::open()?.detach(&project.environment_dir())?;
+    }
+
+    // Roll back any update of the environment that was interrupted before loading the installed
+    // packages.
     let prefix = Prefix::new(project.environment_dir())?;
+    install::recover_interrupted_transaction(prefix.root())?;
+
+    // Start loading the installed packages in the background
     let installed_packages_future = {
         let prefix = prefix.clone();
         tokio::spawn(async move { prefix.find_installed_packages(None).await })
     };
 
     // If there is no lock-file and we are also not allowed to update it, we can bail immediately.
-    if !project.lock_file_path().is_file() && !usage.allows_lock_file_updates() {
+    if !project.lock_file_path().is_file()
+        && !project.root_lock_file_path().is_file()
+        && !usage.allows_lock_file_updates()
+    {
         miette::bail!("no lockfile available, can't do a frozen installation.");
     }
 
-    // Load the lock-file into memory.
-    let mut lock_file = lock_file::load_lock_file(project).await?;
+    // Load the lock-file into memory. A signed lock-file is installed as it was signed, it can't be
+    // updated on this machine.
+    let (usage, mut lock_file) = if project.config().require_signed_lock() {
+        let usage = match usage {
+            LockFileUsage::Update => LockFileUsage::Locked,
+            usage => usage,
+        };
+        (usage, load_signed_lock_file(project)?)
+    } else {
+        (usage, lock_file::load_lock_file(project).await?)
+    };
+
+    // Remove what the project no longer requires from the lock-file, e.g. after a platform or a
+    // dependency was removed from the manifest.
+    if usage.allows_lock_file_updates() {
+        let (pruned_lock_file, pruned) = lock_file::prune_lock_file(project, lock_file);
+        lock_file = pruned_lock_file;
+        if !pruned.is_empty() {
+            lock_file::write_lock_file(project, &lock_file, &project.lock_file_path())?;
+            lock_file::report_pruned_packages(&pruned);
+        }
+    }
 
     // Check if the lock-file is up to date, but only if the current usage allows it.
     let update_lock_file = if usage.should_check_if_out_of_date()
@@ -156,20 +285,82 @@ pub async fn get_up_to_date_prefix(
         false
     };
 
+    // A frozen lock-file is installed as is, but the user should know that it is outdated.
+    if usage == LockFileUsage::Frozen
+        && !lock_file_satisfies_project(project, &lock_file).unwrap_or(true)
+    {
+        warnings::emit(
+            WarningCode::OutdatedLockFile,
+            "the lock file is not up to date with the manifest, it is installed as is because of --frozen",
+        );
+    }
+
     // First lock and install the conda environment
     // After which we should have a usable prefix to use for pypi resolution.
     if update_lock_file {
         lock_file = lock_file::update_lock_file_conda(project, lock_file, sparse_repo_data).await?;
     }
 
-    let python_status = if !no_install {
+    // Rewrite the urls of the locked packages through any configured artifact proxy. This only
+    // affects what is installed, the lock-file on disk is left untouched.
+    let mut install_lock_file = lock_file::rewrite_lock_file_urls(&lock_file, project);
+
+    // When environments are shared, link the environment to the prefix in the shared store that
+    // matches the content of the lock-file. If an identical environment was installed before,
+    // there is nothing left to install.
+    let (prefix, installed_packages) = if shared_environments && !no_install {
+        let platform = project.platform();
+        let hash = shared_prefix::lock_content_hash(
+            &lock_file,
+            platform,
+            project
+                .pypi_dependencies(platform)
+                .into_iter()
+                .map(|(name, requirement)| requirement.as_pep508(&name).to_string()),
+            overlay::overlay_fingerprints(project.root(), project.overlays())?,
+        );
+        let prefix =
+            Prefix::new(SharedPrefixStore::open()?.acquire(&hash, &project.environment_dir())?)?;
+        install::recover_interrupted_transaction(prefix.root())?;
+        let installed_packages = prefix.find_installed_packages(None).await?;
+        (prefix, installed_packages)
+    } else {
+        (prefix, installed_packages_future.await.into_diagnostic()??)
+    };
+    if !no_install {
+        warn_mixed_platforms(&installed_packages, project.platform());
+    }
+
+    // Refuse to install an environment that violates the `run_constrained` pins of its packages.
+    // When the pypi packages are solved again they are checked after that solve.
+    let resolves_pypi = update_lock_file && project.has_pypi_dependencies();
+    if !no_install && !resolves_pypi {
+        lock_file::check_constraints(&lock_file, project.platform())?;
+    }
+
+    let python_status = if !no_install && filter.conda {
         update_prefix_conda(
             &prefix,
-            installed_packages_future.await.into_diagnostic()??,
-            &lock_file,
-            Platform::current(),
+            installed_packages,
+            &install_lock_file,
+            project.platform(),
+            project.shortcuts_enabled(),
+            project.post_link_script_policy(),
+            project.compile_pyc_enabled(),
         )
         .await?
+    } else if !no_install {
+        // The conda packages are left as they are, use the python interpreter that is installed.
+        let status = installed_python_status(&installed_packages, project.platform())?;
+        if filter.pypi
+            && project.has_pypi_dependencies()
+            && matches!(status, PythonStatus::DoesNotExist)
+        {
+            miette::bail!(
+                "the pypi packages can't be installed because the environment has no python interpreter, install the conda packages first"
+            );
+        }
+        status
     } else {
         // We don't know and it won't matter because we won't install pypi either
         PythonStatus::DoesNotExist
@@ -178,24 +369,105 @@ pub async fn get_up_to_date_prefix(
     if project.has_pypi_dependencies() {
         if update_lock_file {
             lock_file = lock_file::update_lock_file_for_pypi(project, lock_file).await?;
+            install_lock_file = lock_file::rewrite_lock_file_urls(&lock_file, project);
+            if !no_install {
+                lock_file::check_constraints(&lock_file, project.platform())?;
+            }
         }
 
-        if !no_install {
+        if !no_install && filter.pypi {
             // Then update the pypi packages.
             update_prefix_pypi(
                 &prefix,
-                Platform::current(),
+                project.platform(),
                 project.pypi_package_db()?,
-                &lock_file,
+                &install_lock_file,
                 &python_status,
             )
             .await?;
         }
     }
 
+    // Remove the build environments of the source distributions that are no longer locked.
+    match build_envs::prune_build_environments(project, &lock_file) {
+        Ok(pruned) => {
+            for path in pruned {
+                tracing::info!("removed the build environment {}", path.display());
+            }
+        }
+        Err(err) => tracing::warn!("failed to prune the build environments: {err:?}"),
+    }
+
+    if !no_install {
+        // Patch the installed packages with the local overlays of the project.
+        overlay::update_overlays(project, &prefix).await?;
+
+        // Point the scripts at the interpreters in the environment, also when it was moved.
+        shebang::update_shebangs(prefix.root(), project.shebang_style())?;
+
+        // Install the exposed tasks last, they may shadow an executable of a package.
+        let variables = task::TemplateVariables::from_lock_file(&lock_file, project.platform());
+        task::expose_tasks(project, &prefix, &variables)?;
+    }
+
+    Ok(prefix)
+}
+
+/// Returns the [`PythonStatus`] of an environment of which the conda packages are not updated.
+fn installed_python_status(
+    installed_packages: &[PrefixRecord],
+    platform: Platform,
+) -> miette::Result<PythonStatus> {
+    let Some(python) = installed_packages
+        .iter()
+        .map(|record| &record.repodata_record.package_record)
+        .find(|record| record.name.as_normalized() == "python")
+    else {
+        return Ok(PythonStatus::DoesNotExist);
+    };
+    let python_info = PythonInfo::from_version(&python.version, platform).into_diagnostic()?;
+    Ok(PythonStatus::Unchanged(python_info))
+}
+
+/// Installs the packages of `lock_file` for the platform of the project into a new environment at
+/// `path`, e.g. an ephemeral environment that is removed after use. The environment of the project
+/// is not touched.
+pub async fn install_new_prefix(
+    project: &Project,
+    lock_file: &CondaLock,
+    path: &Path,
+) -> miette::Result<Prefix> {
+    let prefix = Prefix::new(path)?;
+    let platform = project.platform();
+    lock_file::check_constraints(lock_file, platform)?;
+
+    let install_lock_file = lock_file::rewrite_lock_file_urls(lock_file, project);
+    let python_status = update_prefix_conda(
+        &prefix,
+        Vec::new(),
+        &install_lock_file,
+        platform,
+        false,
+        project.post_link_script_policy(),
+        project.compile_pyc_enabled(),
+    )
+    .await?;
+    if project.has_pypi_dependencies() {
+        update_prefix_pypi(
+            &prefix,
+            platform,
+            project.pypi_package_db()?,
+            &install_lock_file,
+            &python_status,
+        )
+        .await?;
+    }
+    overlay::update_overlays(project, &prefix).await?;
+    shebang::update_shebangs(prefix.root(), project.shebang_style())?;
     Ok(prefix)
 }
 
+#[tracing::instrument(skip_all, fields(platform = %platform))]
 pub async fn update_prefix_pypi(
     prefix: &Prefix,
     platform: Platform,
@@ -253,12 +525,20 @@ impl PythonStatus {
     }
 }
 
-/// Updates the environment to contain the packages from the specified lock-file
+/// Updates the environment to contain the packages from the specified lock-file. If `shortcuts` is
+/// true, shortcuts are created for the menu items defined by the installed packages and removed
+/// for the removed packages. The `post_link_scripts` policy determines whether the post-link
+/// scripts of the installed packages are executed. If `compile_pyc` is true, the python files of
+/// installed noarch python packages are compiled to bytecode.
+#[tracing::instrument(skip_all, fields(platform = %platform))]
 pub async fn update_prefix_conda(
     prefix: &Prefix,
     installed_packages: Vec<PrefixRecord>,
     lock_file: &CondaLock,
     platform: Platform,
+    shortcuts: bool,
+    post_link_scripts: PostLinkScriptPolicy,
+    compile_pyc: bool,
 ) -> miette::Result<PythonStatus> {
     // Construct a transaction to bring the environment up to date with the lock-file content
     let desired_conda_packages = lock_file
@@ -270,6 +550,17 @@ pub async fn update_prefix_conda(
 
     // Execute the transaction if there is work to do
     if !transaction.operations.is_empty() {
+        // Remove the shortcuts of packages that are about to be removed, the menu definitions are
+        // no longer available after the transaction. Without shortcuts enabled none were created.
+        if shortcuts {
+            let removed_records = transaction
+                .operations
+                .iter()
+                .filter_map(|op| op.record_to_remove())
+                .collect_vec();
+            menuinst::remove_shortcuts(prefix, &removed_records, platform)?;
+        }
+
         // Execute the operations that are returned by the solver.
         progress::await_in_progress(
             "updating environment",
@@ -282,6 +573,35 @@ pub async fn update_prefix_conda(
             ),
         )
         .await?;
+
+        // Determine the records of the newly installed packages.
+        let installed_names = transaction
+            .operations
+            .iter()
+            .filter_map(|op| op.record_to_install())
+            .map(|record| record.package_record.name.clone())
+            .collect::<HashSet<_>>();
+        let installed_records = prefix
+            .find_installed_packages(None)
+            .await?
+            .into_iter()
+            .filter(|record| installed_names.contains(&record.repodata_record.package_record.name))
+            .collect_vec();
+
+        // Compile the python files of the newly installed noarch python packages.
+        if let (true, Some(python_info)) = (compile_pyc, &transaction.python_info) {
+            install::compile_pyc(prefix.root(), &installed_records, &python_info.path)?;
+        }
+
+        // Run the post-link scripts of the newly installed packages.
+        let outputs =
+            install::run_post_link_scripts(prefix.root(), &installed_records, post_link_scripts)?;
+        install::report_post_link_outputs(&outputs);
+
+        // Create shortcuts for the newly installed packages.
+        if shortcuts {
+            menuinst::install_shortcuts(prefix, &installed_records, platform)?;
+        }
     }
 
     // Mark the location of the prefix
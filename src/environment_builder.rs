@@ -0,0 +1,168 @@
+//! A facade to create and query the environments of pixi projects from other Rust tools, without
+//! running the pixi executable. An environment can also be created from the contents of a manifest,
+//! in a temporary directory, e.g. as a fixture in the tests of a tool.
+//!
+//! ```no_run
+//! # async fn example() -> miette::Result<()> {
+//! let environment = pixi::EnvironmentBuilder::from_manifest_str(
+//!     r#"
+//!     [project]
+//!     name = "fixture"
+//!     channels = ["conda-forge"]
+//!     platforms = ["linux-64", "osx-arm64", "win-64"]
+//!
+//!     [dependencies]
+//!     python = "3.11.*"
+//!     "#,
+//! )
+//! .build()
+//! .await?;
+//! let output = environment.command("python").await?.arg("--version").output();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::cli::run::run_activation_async;
+use crate::consts::PROJECT_MANIFEST;
+use crate::environment::{get_filtered_prefix, InstallFilter, LockFileUsage};
+use crate::prefix::Prefix;
+use crate::Project;
+use miette::IntoDiagnostic;
+use rattler_conda_types::PrefixRecord;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+use tempfile::TempDir;
+
+/// Where the project of the environment comes from.
+enum ProjectSource {
+    Project(Project),
+    Manifest(String),
+}
+
+/// Creates the environment of a project, installing it if it is not up to date.
+pub struct EnvironmentBuilder {
+    source: ProjectSource,
+    environment: Option<String>,
+    lock_file_usage: LockFileUsage,
+    install_filter: InstallFilter,
+}
+
+impl EnvironmentBuilder {
+    /// Creates the environment of an existing project.
+    pub fn new(project: Project) -> Self {
+        Self::with_source(ProjectSource::Project(project))
+    }
+
+    /// Creates an ephemeral environment from the contents of a manifest. The project is created in
+    /// a temporary directory that is removed when the [`PixiEnvironment`] is dropped.
+    pub fn from_manifest_str(contents: impl Into<String>) -> Self {
+        Self::with_source(ProjectSource::Manifest(contents.into()))
+    }
+
+    fn with_source(source: ProjectSource) -> Self {
+        Self {
+            source,
+            environment: None,
+            lock_file_usage: LockFileUsage::Update,
+            install_filter: InstallFilter::default(),
+        }
+    }
+
+    /// Selects the environment of the project, one for every variant of the dependencies.
+    pub fn with_environment(mut self, name: impl Into<String>) -> Self {
+        self.environment = Some(name.into());
+        self
+    }
+
+    /// Sets whether the lock-file may be updated, defaults to [`LockFileUsage::Update`].
+    pub fn with_lock_file_usage(mut self, lock_file_usage: LockFileUsage) -> Self {
+        self.lock_file_usage = lock_file_usage;
+        self
+    }
+
+    /// Sets which groups of packages are installed, defaults to all of them.
+    pub fn with_install_filter(mut self, install_filter: InstallFilter) -> Self {
+        self.install_filter = install_filter;
+        self
+    }
+
+    /// Locks and installs the environment.
+    pub async fn build(self) -> miette::Result<PixiEnvironment> {
+        let (project, temp_dir) = match self.source {
+            ProjectSource::Project(project) => (project, None),
+            ProjectSource::Manifest(contents) => {
+                let temp_dir = tempfile::Builder::new()
+                    .prefix("pixi-environment-")
+                    .tempdir()
+                    .into_diagnostic()?;
+                let root = dunce::canonicalize(temp_dir.path()).into_diagnostic()?;
+                std::fs::write(root.join(PROJECT_MANIFEST), &contents).into_diagnostic()?;
+                (Project::from_str(&root, contents)?, Some(temp_dir))
+            }
+        };
+        let project = project.with_environment(self.environment.as_deref())?;
+
+        let prefix = get_filtered_prefix(
+            &project,
+            self.lock_file_usage,
+            false,
+            self.install_filter,
+            None,
+        )
+        .await?;
+
+        Ok(PixiEnvironment {
+            project,
+            prefix,
+            _temp_dir: temp_dir,
+        })
+    }
+}
+
+/// An installed environment of a project.
+pub struct PixiEnvironment {
+    project: Project,
+    prefix: Prefix,
+    /// The directory of an ephemeral project, removed when the environment is dropped
+    _temp_dir: Option<TempDir>,
+}
+
+impl PixiEnvironment {
+    /// Returns the project of the environment.
+    pub fn project(&self) -> &Project {
+        &self.project
+    }
+
+    /// Returns the directory the environment is installed in.
+    pub fn prefix(&self) -> &Path {
+        self.prefix.root()
+    }
+
+    /// Returns the conda packages that are installed in the environment.
+    pub async fn installed_packages(&self) -> miette::Result<Vec<PrefixRecord>> {
+        self.prefix.find_installed_packages(None).await
+    }
+
+    /// Returns the environment variables of the activated environment, including the variables
+    /// that the project sets. The variables of the current process are not included.
+    pub async fn activation_env(&self) -> miette::Result<HashMap<String, String>> {
+        let activation_env = run_activation_async(&self.project, self.prefix.clone()).await?;
+        Ok(activation_env
+            .into_iter()
+            .chain(self.project.get_metadata_env())
+            .collect())
+    }
+
+    /// Returns a command that runs `program` in the activated environment.
+    pub async fn command(
+        &self,
+        program: impl AsRef<OsStr>,
+    ) -> miette::Result<std::process::Command> {
+        let mut command = std::process::Command::new(program);
+        command
+            .envs(self.activation_env().await?)
+            .current_dir(self.project.root());
+        Ok(command)
+    }
+}
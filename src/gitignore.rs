@@ -0,0 +1,155 @@
+//! Management of the patterns that pixi adds to the `.gitignore` of a project. The patterns are
+//! kept in a block between two marker comments, so `pixi project gitignore sync` can update them
+//! when pixi starts to write other files, without touching the patterns of the user.
+
+use crate::consts;
+use std::path::{Path, PathBuf};
+
+/// The first line of the block of patterns that pixi manages.
+const BLOCK_START: &str = "# >>> pixi >>>";
+
+/// The last line of the block of patterns that pixi manages.
+const BLOCK_END: &str = "# <<< pixi <<<";
+
+/// The patterns that older versions of `pixi init` added, they are replaced by the block.
+const LEGACY_PATTERNS: &str = "# pixi environments\n.pixi\n";
+
+/// Returns the patterns that pixi manages. Everything in the `.pixi` directory is ignored, e.g.
+/// the environments, caches and logs, except for the configuration of the project and the lock
+/// files of the environments when `split-lock-files` is enabled.
+pub fn ignore_patterns(ignore_lock_file: bool) -> Vec<String> {
+    let mut patterns = vec![
+        format!("{}/*", consts::PIXI_DIR),
+        format!("!{}/{}", consts::PIXI_DIR, consts::CONFIG_FILE),
+    ];
+    if ignore_lock_file {
+        patterns.push(consts::PROJECT_LOCK_FILE.to_string());
+    } else {
+        patterns.push(format!("!{}/{}/", consts::PIXI_DIR, consts::LOCKS_DIR));
+    }
+    patterns
+}
+
+/// Returns whether the block of pixi in the content of a `.gitignore` ignores the lock file, or
+/// `None` if the content has no block.
+pub fn ignores_lock_file(content: &str) -> Option<bool> {
+    let (start, end) = find_block(content)?;
+    Some(
+        content[start..end]
+            .lines()
+            .any(|line| line.trim() == consts::PROJECT_LOCK_FILE),
+    )
+}
+
+/// Returns the byte range of the block of pixi, including the markers and the line ending of the
+/// end marker. A block whose end marker was removed ends at the first empty line, or at the end of
+/// the content.
+fn find_block(content: &str) -> Option<(usize, usize)> {
+    let start = content.find(BLOCK_START)?;
+    let end = match content[start..].find(BLOCK_END) {
+        Some(end) => start + end + BLOCK_END.len(),
+        None => content[start..]
+            .find("\n\n")
+            .map_or(content.len(), |end| start + end),
+    };
+    let end = if content[end..].starts_with('\n') {
+        end + 1
+    } else {
+        end
+    };
+    Some((start, end))
+}
+
+/// Returns the content of a `.gitignore` with the block of pixi set to `patterns`. An existing
+/// block is replaced, the patterns of older versions of pixi are replaced by the block, otherwise
+/// the block is appended.
+pub fn sync(content: &str, patterns: &[String]) -> String {
+    let block = format!("{BLOCK_START}\n{}\n{BLOCK_END}\n", patterns.join("\n"));
+    if let Some((start, end)) = find_block(content) {
+        return format!("{}{block}{}", &content[..start], &content[end..]);
+    }
+    if let Some(start) = content.find(LEGACY_PATTERNS) {
+        let end = start + LEGACY_PATTERNS.len();
+        return format!("{}{block}{}", &content[..start], &content[end..]);
+    }
+    let separator = match content {
+        "" => "",
+        content if content.ends_with("\n\n") => "",
+        content if content.ends_with('\n') => "\n",
+        _ => "\n\n",
+    };
+    format!("{content}{separator}{block}")
+}
+
+/// Updates the block of pixi in the `.gitignore` in `dir`, the file is created if it doesn't
+/// exist. Returns true if the file changed.
+pub fn sync_file(dir: &Path, ignore_lock_file: bool) -> std::io::Result<bool> {
+    let path = dir.join(".gitignore");
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let synced = sync(&content, &ignore_patterns(ignore_lock_file));
+    if synced == content {
+        return Ok(false);
+    }
+    std::fs::write(&path, synced)?;
+    Ok(true)
+}
+
+/// Returns the root of the git repository that contains `dir`, if there is one.
+pub fn find_git_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync() {
+        let patterns = ignore_patterns(false);
+        let block = "# >>> pixi >>>\n.pixi/*\n!.pixi/config.toml\n!.pixi/locks/\n# <<< pixi <<<\n";
+        assert_eq!(sync("", &patterns), block);
+        assert_eq!(sync("target/", &patterns), format!("target/\n\n{block}"));
+
+        // The patterns of older versions are replaced, the others are kept.
+        let legacy = "target/\n# pixi environments\n.pixi\n\n*.log\n";
+        let synced = sync(legacy, &patterns);
+        assert_eq!(synced, format!("target/\n{block}\n*.log\n"));
+        assert_eq!(ignores_lock_file(&synced), Some(false));
+
+        // An existing block is replaced in place.
+        let synced = sync(&synced, &ignore_patterns(true));
+        assert_eq!(
+            synced,
+            "target/\n# >>> pixi >>>\n.pixi/*\n!.pixi/config.toml\npixi.lock\n# <<< pixi <<<\n\n*.log\n"
+        );
+        assert_eq!(ignores_lock_file(&synced), Some(true));
+        assert_eq!(sync(&synced, &ignore_patterns(true)), synced);
+        assert_eq!(ignores_lock_file("target/\n"), None);
+
+        // A block without an end marker ends at the first empty line.
+        let unterminated = "target/\n# >>> pixi >>>\n.pixi/*\n\n*.log\n";
+        assert_eq!(ignores_lock_file(unterminated), Some(false));
+        assert_eq!(
+            sync(unterminated, &patterns),
+            format!("target/\n{block}\n*.log\n")
+        );
+        assert_eq!(sync("# >>> pixi >>>\n.pixi/*", &patterns), block);
+    }
+
+    #[test]
+    fn test_find_git_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("packages/project");
+        std::fs::create_dir_all(&project).unwrap();
+        assert_eq!(find_git_root(&project), None);
+
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        assert_eq!(find_git_root(&project), Some(dir.path().to_path_buf()));
+    }
+}
@@ -0,0 +1,157 @@
+//! Details of the machine pixi runs on that the conda platform doesn't capture: whether a linux
+//! machine uses glibc or musl (e.g. Alpine), and whether it runs under the Windows Subsystem for
+//! Linux.
+
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+/// A file that only exists on Alpine Linux.
+const ALPINE_RELEASE: &str = "/etc/alpine-release";
+
+/// The release of the running kernel, which names the WSL kernel on WSL.
+const KERNEL_OSRELEASE: &str = "/proc/sys/kernel/osrelease";
+
+/// The C standard library of a linux machine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LibcFlavor {
+    Glibc,
+    Musl,
+}
+
+impl LibcFlavor {
+    /// The family of the libc, as used by the `libc` system requirement and the `__glibc` and
+    /// `__musl` virtual packages.
+    pub fn family(&self) -> &'static str {
+        match self {
+            LibcFlavor::Glibc => "glibc",
+            LibcFlavor::Musl => "musl",
+        }
+    }
+}
+
+impl Display for LibcFlavor {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.family())
+    }
+}
+
+/// The Windows Subsystem for Linux this machine runs under.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Wsl {
+    /// 1 or 2, WSL 1 translates system calls while WSL 2 runs a real linux kernel.
+    pub version: u8,
+    /// The name of the distribution, e.g. `Ubuntu`.
+    pub distro: Option<String>,
+}
+
+impl Display for Wsl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WSL {}", self.version)?;
+        if let Some(distro) = &self.distro {
+            write!(f, " ({distro})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the libc of this machine, `None` if it isn't a linux machine. A machine uses musl if it
+/// has the dynamic loader of musl but not that of glibc, the musl loader alone is not enough
+/// because glibc distributions can install musl next to glibc.
+pub fn libc_flavor() -> Option<LibcFlavor> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    if Path::new(ALPINE_RELEASE).exists() {
+        return Some(LibcFlavor::Musl);
+    }
+    let loaders = ["/lib", "/lib64"]
+        .into_iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    Some(flavor_from_loaders(&loaders))
+}
+
+/// Determines the libc from the file names in the library directories, e.g.
+/// `ld-musl-x86_64.so.1` for musl and `ld-linux-x86-64.so.2` for glibc.
+fn flavor_from_loaders(file_names: &[String]) -> LibcFlavor {
+    let musl = file_names
+        .iter()
+        .any(|name| name.starts_with("ld-musl-") && name.ends_with(".so.1"));
+    let glibc = file_names
+        .iter()
+        .any(|name| name.starts_with("ld-linux") || name.starts_with("ld64.so"));
+    if musl && !glibc {
+        LibcFlavor::Musl
+    } else {
+        LibcFlavor::Glibc
+    }
+}
+
+/// Returns the WSL this machine runs under, `None` if it doesn't.
+pub fn wsl() -> Option<Wsl> {
+    if !cfg!(target_os = "linux") {
+        return None;
+    }
+    let osrelease = std::fs::read_to_string(KERNEL_OSRELEASE).ok()?;
+    let version = wsl_version(&osrelease)?;
+    Some(Wsl {
+        version,
+        distro: std::env::var("WSL_DISTRO_NAME")
+            .ok()
+            .filter(|name| !name.is_empty()),
+    })
+}
+
+/// Returns the version of WSL from the kernel release, e.g. `5.15.90.1-microsoft-standard-WSL2`
+/// for WSL 2 and `4.4.0-19041-Microsoft` for WSL 1.
+fn wsl_version(osrelease: &str) -> Option<u8> {
+    let osrelease = osrelease.trim().to_lowercase();
+    if !osrelease.contains("microsoft") {
+        return None;
+    }
+    if osrelease.contains("wsl2") || osrelease.contains("microsoft-standard") {
+        Some(2)
+    } else {
+        Some(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flavor_from_loaders() {
+        let names = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            flavor_from_loaders(&names(&["ld-musl-x86_64.so.1", "libc.musl-x86_64.so.1"])),
+            LibcFlavor::Musl
+        );
+        assert_eq!(
+            flavor_from_loaders(&names(&["ld-linux-x86-64.so.2", "ld-musl-x86_64.so.1"])),
+            LibcFlavor::Glibc
+        );
+        assert_eq!(
+            flavor_from_loaders(&names(&["ld-linux-aarch64.so.1"])),
+            LibcFlavor::Glibc
+        );
+        assert_eq!(flavor_from_loaders(&[]), LibcFlavor::Glibc);
+    }
+
+    #[test]
+    fn test_wsl_version() {
+        assert_eq!(wsl_version("5.15.90.1-microsoft-standard-WSL2\n"), Some(2));
+        assert_eq!(wsl_version("4.19.128-microsoft-standard"), Some(2));
+        assert_eq!(wsl_version("4.4.0-19041-Microsoft"), Some(1));
+        assert_eq!(wsl_version("6.5.0-14-generic"), None);
+    }
+}
@@ -1,32 +1,341 @@
-use crate::default_retry_policy;
 use crate::progress::{
     default_progress_style, finished_progress_style, global_multi_progress,
     ProgressBarMessageFormatter,
 };
+use crate::warnings::{self, WarningCode};
+use crate::{auth, concurrency, default_retry_policy, interrupt};
 use futures::future::ready;
 use futures::{stream, FutureExt, StreamExt, TryFutureExt, TryStreamExt};
 use itertools::Itertools;
 use miette::{IntoDiagnostic, WrapErr};
+use once_cell::sync::Lazy;
 use rattler::install::{
-    link_package, InstallDriver, InstallOptions, Transaction, TransactionOperation,
+    link_package, InstallDriver, InstallOptions, PythonInfo, Transaction, TransactionOperation,
 };
 use rattler::package_cache::PackageCache;
-use rattler_conda_types::{PrefixRecord, RepoDataRecord};
+use rattler_conda_types::package::{LinkJson, NoArchLinks, PackageFile, PathsJson};
+use rattler_conda_types::{PackageRecord, PrefixRecord, RepoDataRecord};
 use rattler_networking::AuthenticatedClient;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+
+/// The directory inside a prefix that holds the state of an in-progress transaction.
+const TRANSACTION_DIR: &str = ".pixi-transaction";
+
+/// Keeps track of an in-progress transaction so it can be rolled back when it fails or when pixi
+/// is interrupted while executing it.
+///
+/// Files of packages that are removed are moved into a backup directory instead of being deleted
+/// and the conda-meta files of the packages that are installed are recorded in a journal. Before a
+/// package is linked the paths it is going to occupy in the prefix are journaled as well, so the
+/// files of a package whose link failed halfway can be removed even though it has no conda-meta
+/// file. When the transaction succeeds the backup is discarded, otherwise the installed packages
+/// are removed again and the backup is restored.
+struct TransactionJournal {
+    prefix: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalContents {
+    /// The names of the conda-meta files of the packages that are installed by the transaction.
+    installing: Vec<String>,
+}
+
+impl TransactionJournal {
+    fn dir(prefix: &Path) -> PathBuf {
+        prefix.join(TRANSACTION_DIR)
+    }
+
+    fn journal_path(prefix: &Path) -> PathBuf {
+        Self::dir(prefix).join("journal.json")
+    }
+
+    /// Returns the directory that holds the paths of the packages that are being linked.
+    fn linking_dir(prefix: &Path) -> PathBuf {
+        Self::dir(prefix).join("linking")
+    }
+
+    /// Returns the directory in which the files of removed packages are stored.
+    fn backup_dir(&self) -> PathBuf {
+        Self::dir(&self.prefix).join("backup")
+    }
+
+    /// Starts a new transaction, recovering from a previously interrupted one first.
+    fn start(
+        prefix: &Path,
+        transaction: &Transaction<PrefixRecord, RepoDataRecord>,
+    ) -> miette::Result<Self> {
+        recover_interrupted_transaction(prefix)?;
+
+        let contents = JournalContents {
+            installing: transaction
+                .operations
+                .iter()
+                .filter_map(|op| op.record_to_install())
+                .map(|record| conda_meta_file_name(&record.package_record))
+                .collect(),
+        };
+        Self::write(prefix, &contents)?;
+
+        Ok(Self {
+            prefix: prefix.to_path_buf(),
+        })
+    }
+
+    /// Writes the journal of a transaction on the prefix.
+    fn write(prefix: &Path, contents: &JournalContents) -> miette::Result<()> {
+        std::fs::create_dir_all(Self::dir(prefix)).into_diagnostic()?;
+        std::fs::write(
+            Self::journal_path(prefix),
+            serde_json::to_string(contents).into_diagnostic()?,
+        )
+        .into_diagnostic()
+    }
+
+    /// Records the paths (relative to the prefix) that the extracted package in `package_dir` is
+    /// going to occupy, before it is linked. The paths are derived from the `info/paths.json` of
+    /// the package, taking into account where noarch python packages are installed.
+    fn record_linking(
+        prefix: &Path,
+        package_dir: &Path,
+        record: &PackageRecord,
+        python_info: Option<&PythonInfo>,
+    ) -> miette::Result<()> {
+        let paths_json = PathsJson::from_package_directory_with_deprecated_fallback(package_dir)
+            .into_diagnostic()
+            .wrap_err_with(|| {
+                format!(
+                    "failed to read the paths of the package at {}",
+                    package_dir.display()
+                )
+            })?;
+        let python_info = python_info.filter(|_| record.noarch.is_python());
+
+        let mut paths = paths_json
+            .paths
+            .iter()
+            .map(|entry| match python_info {
+                Some(python_info) => python_info
+                    .get_python_noarch_target_path(&entry.relative_path)
+                    .into_owned(),
+                None => entry.relative_path.clone(),
+            })
+            .collect_vec();
+
+        // The entry points of noarch python packages are not part of the paths of the package.
+        if let (Some(python_info), Ok(link_json)) =
+            (python_info, LinkJson::from_package_directory(package_dir))
+        {
+            if let NoArchLinks::Python(entry_points) = link_json.noarch {
+                for entry_point in entry_points.entry_points {
+                    if cfg!(windows) {
+                        paths.push(
+                            python_info
+                                .bin_dir
+                                .join(format!("{}-script.py", entry_point.command)),
+                        );
+                        paths.push(
+                            python_info
+                                .bin_dir
+                                .join(format!("{}.exe", entry_point.command)),
+                        );
+                    } else {
+                        paths.push(python_info.bin_dir.join(&entry_point.command));
+                    }
+                }
+            }
+        }
+
+        let linking_dir = Self::linking_dir(prefix);
+        std::fs::create_dir_all(&linking_dir).into_diagnostic()?;
+        std::fs::write(
+            linking_dir.join(conda_meta_file_name(record)),
+            serde_json::to_string(&paths).into_diagnostic()?,
+        )
+        .into_diagnostic()
+    }
+
+    /// Discards the backup after a successful transaction.
+    fn commit(self) -> miette::Result<()> {
+        std::fs::remove_dir_all(Self::dir(&self.prefix)).into_diagnostic()
+    }
+
+    /// Undoes the changes of a failed transaction.
+    fn rollback(self) -> miette::Result<()> {
+        rollback_transaction(&self.prefix)
+    }
+}
+
+/// Returns the name of the conda-meta file of a package.
+fn conda_meta_file_name(record: &PackageRecord) -> String {
+    format!(
+        "{}-{}-{}.json",
+        record.name.as_normalized(),
+        record.version,
+        record.build
+    )
+}
+
+/// Rolls back a transaction that was interrupted, e.g. because pixi was killed while updating the
+/// environment. This is a no-op if there is no interrupted transaction.
+pub fn recover_interrupted_transaction(prefix: &Path) -> miette::Result<()> {
+    if !TransactionJournal::journal_path(prefix).is_file() {
+        return Ok(());
+    }
+    tracing::warn!(
+        "a previous update of the environment at '{}' was interrupted, rolling back",
+        prefix.display()
+    );
+    rollback_transaction(prefix)
+}
+
+/// Removes the packages recorded in the journal and restores the backup of removed packages.
+fn rollback_transaction(prefix: &Path) -> miette::Result<()> {
+    let journal: JournalContents = serde_json::from_str(
+        &std::fs::read_to_string(TransactionJournal::journal_path(prefix)).into_diagnostic()?,
+    )
+    .into_diagnostic()?;
+
+    // Remove the files of the packages that were (partially) linked. A package whose link failed
+    // or was interrupted has no conda-meta file, so the paths that were journaled before linking
+    // it are removed as well.
+    for entry in std::fs::read_dir(TransactionJournal::linking_dir(prefix))
+        .into_iter()
+        .flatten()
+    {
+        let path = entry.into_diagnostic()?.path();
+        let paths: Vec<PathBuf> =
+            serde_json::from_str(&std::fs::read_to_string(&path).into_diagnostic()?)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to parse {}", path.display()))?;
+        for relative_path in paths.iter() {
+            let _ = std::fs::remove_file(prefix.join(relative_path));
+            for compiled in compiled_pyc_files(prefix, relative_path) {
+                let _ = std::fs::remove_file(prefix.join(compiled));
+            }
+        }
+    }
+    for file_name in journal.installing {
+        let conda_meta_path = prefix.join("conda-meta").join(file_name);
+        if let Ok(record) = PrefixRecord::from_path(&conda_meta_path) {
+            for path in record.paths_data.paths.iter() {
+                let _ = std::fs::remove_file(prefix.join(&path.relative_path));
+            }
+        }
+        let _ = std::fs::remove_file(conda_meta_path);
+    }
+
+    // Move the backed up files back into place.
+    let backup_dir = TransactionJournal::dir(prefix).join("backup");
+    let mut pending = vec![backup_dir.clone()];
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir).into_iter().flatten() {
+            let path = entry.into_diagnostic()?.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let relative_path = path
+                .strip_prefix(&backup_dir)
+                .expect("entries are always inside the backup directory");
+            let target = prefix.join(relative_path);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).into_diagnostic()?;
+            }
+            std::fs::rename(&path, &target)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to restore {}", target.display()))?;
+        }
+    }
+
+    std::fs::remove_dir_all(TransactionJournal::dir(prefix)).into_diagnostic()
+}
+
+/// Returns the name of the directory of a package in the package cache.
+fn package_cache_dir_name(record: &PackageRecord) -> String {
+    format!(
+        "{}-{}-{}",
+        record.name.as_normalized(),
+        record.version,
+        record.build
+    )
+}
+
+/// Keeps track of an interrupt of a transaction. Downloads are cancelled right away and packages
+/// that were not linked yet are skipped, but a package that is being linked is linked completely
+/// so the rollback knows which files to remove.
+struct Interruption<'a> {
+    token: &'a CancellationToken,
+    package_cache_dir: &'a Path,
+    /// The directories in the package cache of the downloads that were cancelled.
+    partial_downloads: Mutex<Vec<PathBuf>>,
+}
+
+impl Interruption<'_> {
+    /// Removes the partially downloaded and extracted packages from the package cache.
+    fn remove_partial_downloads(self) {
+        for dir in self.partial_downloads.into_inner().unwrap() {
+            if let Err(err) = std::fs::remove_dir_all(&dir) {
+                if err.kind() != ErrorKind::NotFound {
+                    tracing::warn!(
+                        "failed to remove the partial download {}: {err}",
+                        dir.display()
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Returns the package cache in `path`. The instance is shared by all installs of the process, so
+/// when several environments are installed at the same time a package that they all need is only
+/// downloaded and extracted once.
+fn shared_package_cache(path: PathBuf) -> PackageCache {
+    static PACKAGE_CACHES: Lazy<Mutex<HashMap<PathBuf, PackageCache>>> =
+        Lazy::new(Default::default);
+    PACKAGE_CACHES
+        .lock()
+        .unwrap()
+        .entry(path.clone())
+        .or_insert_with(|| PackageCache::new(path))
+        .clone()
+}
 
 /// Executes the transaction on the given environment.
+///
+/// The transaction only touches the packages that differ between the current and the desired
+/// state of the environment. If any of the operations fails or pixi is interrupted with Ctrl+C,
+/// the environment is rolled back to the state it was in before the transaction.
+#[tracing::instrument(name = "install", skip_all, fields(prefix = %target_prefix.display()))]
 pub async fn execute_transaction(
     transaction: &Transaction<PrefixRecord, RepoDataRecord>,
     target_prefix: PathBuf,
     cache_dir: PathBuf,
     download_client: AuthenticatedClient,
 ) -> miette::Result<()> {
+    // Record the transaction so it can be rolled back.
+    let journal = TransactionJournal::start(&target_prefix, transaction)?;
+    let backup_dir = journal.backup_dir();
+
     // Open the package cache
-    let package_cache = PackageCache::new(cache_dir.join("pkgs"));
+    let package_cache_dir = cache_dir.join("pkgs");
+    let package_cache = shared_package_cache(package_cache_dir.clone());
+
+    // Wind down instead of terminating when pixi is interrupted.
+    let interrupt_guard = interrupt::guard();
+    let interruption = Interruption {
+        token: interrupt_guard.token(),
+        package_cache_dir: &package_cache_dir,
+        partial_downloads: Mutex::default(),
+    };
 
     // Create an install driver which helps limit the number of concurrent filesystem operations
     let install_driver = InstallDriver::default();
@@ -106,10 +415,15 @@ pub async fn execute_transaction(
         })
         .map(|(_, op)| op);
 
+    // Limit the number of packages that are downloaded and linked at the same time.
+    let limits = concurrency::limits();
+    let download_semaphore = Semaphore::new(limits.downloads);
+    let link_semaphore = Semaphore::new(limits.link_jobs);
+
     // Perform all transactions operations in parallel.
     let result = stream::iter(sorted_operations.into_iter())
         .map(Ok)
-        .try_for_each_concurrent(50, |op| {
+        .try_for_each_concurrent(limits.downloads + limits.link_jobs, |op| {
             let target_prefix = target_prefix.clone();
             let download_client = download_client.clone();
             let package_cache = &package_cache;
@@ -117,9 +431,14 @@ pub async fn execute_transaction(
             let download_pb = download_pb.as_ref();
             let link_pb = &link_pb;
             let install_options = &install_options;
+            let backup_dir = &backup_dir;
+            let download_semaphore = &download_semaphore;
+            let link_semaphore = &link_semaphore;
+            let interruption = &interruption;
             async move {
                 execute_operation(
                     &target_prefix,
+                    backup_dir,
                     download_client,
                     package_cache,
                     install_driver,
@@ -127,6 +446,9 @@ pub async fn execute_transaction(
                     link_pb,
                     op,
                     install_options,
+                    download_semaphore,
+                    link_semaphore,
+                    interruption,
                 )
                 .await
             }
@@ -139,7 +461,25 @@ pub async fn execute_transaction(
     }
     link_pb.into_progress_bar().finish_and_clear();
 
-    result
+    if interrupt_guard.is_interrupted() {
+        let rolled_back = journal.rollback();
+        interruption.remove_partial_downloads();
+        rolled_back.wrap_err("failed to roll back the interrupted update of the environment")?;
+        miette::bail!(
+            "the update of the environment at '{}' was interrupted, the environment was restored to its previous state",
+            target_prefix.display()
+        );
+    }
+
+    match result {
+        Ok(()) => journal.commit(),
+        Err(err) => {
+            if let Err(rollback_err) = journal.rollback() {
+                tracing::error!("failed to roll back the environment: {rollback_err}");
+            }
+            Err(err)
+        }
+    }
 }
 
 /// Executes a single operation of a transaction on the environment.
@@ -147,6 +487,7 @@ pub async fn execute_transaction(
 #[allow(clippy::too_many_arguments)]
 async fn execute_operation(
     target_prefix: &Path,
+    backup_dir: &Path,
     download_client: AuthenticatedClient,
     package_cache: &PackageCache,
     install_driver: &InstallDriver,
@@ -154,7 +495,15 @@ async fn execute_operation(
     link_pb: &ProgressBarMessageFormatter,
     op: &TransactionOperation<PrefixRecord, RepoDataRecord>,
     install_options: &InstallOptions,
+    download_semaphore: &Semaphore,
+    link_semaphore: &Semaphore,
+    interruption: &Interruption<'_>,
 ) -> miette::Result<()> {
+    // Skip the operations that did not start before pixi was interrupted.
+    if interruption.token.is_cancelled() {
+        return Ok(());
+    }
+
     // Determine the package to install
     let install_record = op.record_to_install();
     let remove_record = op.record_to_remove();
@@ -172,7 +521,7 @@ async fn execute_operation(
                         .as_source(),
                     &remove_record.repodata_record.package_record.version
                 ),
-                remove_package_from_environment(target_prefix, remove_record),
+                remove_package_from_environment(target_prefix, backup_dir, remove_record),
             )
             .left_future()
     } else {
@@ -182,6 +531,15 @@ async fn execute_operation(
     // Create a future to download the package
     let cached_package_dir_fut = if let Some(install_record) = install_record {
         async {
+            let _permit = tokio::select! {
+                biased;
+                _ = interruption.token.cancelled() => return Ok(None),
+                permit = download_semaphore.acquire() => permit.into_diagnostic()?,
+            };
+            let cache_entry = interruption
+                .package_cache_dir
+                .join(package_cache_dir_name(&install_record.package_record));
+            let was_cached = cache_entry.is_dir();
             let task = if let Some(pb) = download_pb {
                 Some(
                     pb.start(install_record.package_record.name.as_source().to_string())
@@ -192,16 +550,27 @@ async fn execute_operation(
             };
 
             // Make sure the package is available in the package cache.
-            let result = package_cache
+            let fetch = package_cache
                 .get_or_fetch_from_url_with_retry(
                     &install_record.package_record,
-                    install_record.url.clone(),
+                    auth::authenticate_url(&install_record.url),
                     download_client.clone(),
                     default_retry_policy(),
                 )
                 .map_ok(|cache_dir| Some((install_record.clone(), cache_dir)))
-                .await
-                .into_diagnostic();
+                .instrument(tracing::info_span!(
+                    "download",
+                    package = install_record.package_record.name.as_source()
+                ));
+            let result = tokio::select! {
+                result = fetch => result.into_diagnostic(),
+                _ = interruption.token.cancelled() => {
+                    if !was_cached {
+                        interruption.partial_downloads.lock().unwrap().push(cache_entry);
+                    }
+                    Ok(None)
+                }
+            };
 
             // Increment the download progress bar.
             if let Some(task) = task {
@@ -224,6 +593,17 @@ async fn execute_operation(
 
     // If there is a package to install, do that now.
     if let Some((record, package_dir)) = install_package {
+        let _permit = tokio::select! {
+            biased;
+            _ = interruption.token.cancelled() => return Ok(()),
+            permit = link_semaphore.acquire() => permit.into_diagnostic()?,
+        };
+        TransactionJournal::record_linking(
+            target_prefix,
+            &package_dir,
+            &record.package_record,
+            install_options.python_info.as_ref(),
+        )?;
         link_pb
             .wrap(
                 record.package_record.name.as_source().to_string(),
@@ -249,6 +629,11 @@ async fn execute_operation(
 
 /// Install a package into the environment and write a `conda-meta` file that contains information
 /// about how the file was linked.
+#[tracing::instrument(
+    name = "link",
+    skip_all,
+    fields(package = repodata_record.package_record.name.as_source())
+)]
 async fn install_package_to_environment(
     target_prefix: &Path,
     package_dir: PathBuf,
@@ -290,15 +675,8 @@ async fn install_package_to_environment(
         std::fs::create_dir_all(&conda_meta_path)?;
 
         // Write the conda-meta information
-        let pkg_meta_path = conda_meta_path.join(format!(
-            "{}-{}-{}.json",
-            prefix_record
-                .repodata_record
-                .package_record
-                .name
-                .as_source(),
-            prefix_record.repodata_record.package_record.version,
-            prefix_record.repodata_record.package_record.build
+        let pkg_meta_path = conda_meta_path.join(conda_meta_file_name(
+            &prefix_record.repodata_record.package_record,
         ));
         prefix_record.write_to_path(pkg_meta_path, true)
     })
@@ -315,40 +693,345 @@ async fn install_package_to_environment(
     }
 }
 
-/// Completely remove the specified package from the environment.
+/// Moves a file from the prefix into the backup directory, preserving its relative path.
+async fn move_to_backup(
+    target_prefix: &Path,
+    backup_dir: &Path,
+    relative_path: &Path,
+) -> std::io::Result<()> {
+    let backup_path = backup_dir.join(relative_path);
+    if let Some(parent) = backup_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    tokio::fs::rename(target_prefix.join(relative_path), backup_path).await
+}
+
+/// Returns the paths (relative to the prefix) of the bytecode files compiled for a python file,
+/// e.g. `__pycache__/foo.cpython-311.pyc` for `foo.py`.
+fn compiled_pyc_files(target_prefix: &Path, relative_path: &Path) -> Vec<PathBuf> {
+    let (Some(parent), Some(stem)) = (relative_path.parent(), relative_path.file_stem()) else {
+        return Vec::new();
+    };
+    if relative_path.extension().map_or(true, |ext| ext != "py") {
+        return Vec::new();
+    }
+    let prefix = format!("{}.", stem.to_string_lossy());
+    let cache_dir = parent.join("__pycache__");
+    std::fs::read_dir(target_prefix.join(&cache_dir))
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with(&prefix) && name.ends_with(".pyc"))
+        .map(|name| cache_dir.join(name))
+        .collect()
+}
+
+/// Compiles the python files of the noarch python packages among `records` to bytecode using the
+/// python interpreter at `python` (relative to the prefix). The files are compiled in parallel
+/// using all available cores.
+pub fn compile_pyc(
+    target_prefix: &Path,
+    records: &[PrefixRecord],
+    python: &Path,
+) -> miette::Result<()> {
+    let files = records
+        .iter()
+        .filter(|record| record.repodata_record.package_record.noarch.is_python())
+        .flat_map(|record| record.files.iter())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "py"))
+        .map(|path| target_prefix.join(path).to_string_lossy().into_owned())
+        .join("\n");
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    // The files to compile are passed through stdin to avoid exceeding the maximum command line
+    // length.
+    let mut child = std::process::Command::new(target_prefix.join(python))
+        .args(["-Wi", "-m", "compileall", "-q", "-j", "0", "-i", "-"])
+        .current_dir(target_prefix)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .into_diagnostic()
+        .wrap_err("failed to start the python interpreter to compile python files")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        use std::io::Write;
+        stdin.write_all(files.as_bytes()).into_diagnostic()?;
+    }
+    let output = child.wait_with_output().into_diagnostic()?;
+
+    // Failing to compile a file is not fatal, python falls back to the source files.
+    if !output.status.success() {
+        tracing::warn!(
+            "failed to compile some python files:\n{}\n{}",
+            String::from_utf8_lossy(&output.stdout).trim(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Completely remove the specified package from the environment. The files of the package are
+/// moved into `backup_dir` so they can be restored if the transaction fails.
+#[tracing::instrument(
+    name = "unlink",
+    skip_all,
+    fields(package = package.repodata_record.package_record.name.as_source())
+)]
 async fn remove_package_from_environment(
     target_prefix: &Path,
+    backup_dir: &Path,
     package: &PrefixRecord,
 ) -> miette::Result<()> {
     // TODO: Take into account any clobbered files, they need to be restored.
     // TODO: Can we also delete empty directories?
 
-    // Remove all entries
-    for paths in package.paths_data.paths.iter() {
-        match tokio::fs::remove_file(target_prefix.join(&paths.relative_path)).await {
+    // Remove all entries, including the bytecode compiled for python files which is not
+    // recorded in the conda-meta file.
+    let compiled_files = package
+        .paths_data
+        .paths
+        .iter()
+        .flat_map(|paths| compiled_pyc_files(target_prefix, &paths.relative_path))
+        .collect_vec();
+    let relative_paths = package
+        .paths_data
+        .paths
+        .iter()
+        .map(|paths| &paths.relative_path)
+        .chain(compiled_files.iter());
+    for relative_path in relative_paths {
+        match move_to_backup(target_prefix, backup_dir, relative_path).await {
             Ok(_) => {}
             Err(e) if e.kind() == ErrorKind::NotFound => {
                 // Simply ignore if the file is already gone.
             }
             Err(e) => {
-                return Err(e).into_diagnostic().wrap_err(format!(
-                    "failed to delete {}",
-                    paths.relative_path.display()
-                ))
+                return Err(e)
+                    .into_diagnostic()
+                    .wrap_err(format!("failed to delete {}", relative_path.display()))
             }
         }
     }
 
     // Remove the conda-meta file
-    let conda_meta_path = target_prefix.join("conda-meta").join(format!(
+    let conda_meta_path = Path::new("conda-meta").join(format!(
         "{}-{}-{}.json",
         package.repodata_record.package_record.name.as_normalized(),
         package.repodata_record.package_record.version,
         package.repodata_record.package_record.build
     ));
-    tokio::fs::remove_file(conda_meta_path)
+    move_to_backup(target_prefix, backup_dir, &conda_meta_path)
         .await
         .into_diagnostic()?;
 
     Ok(())
 }
+
+/// Determines whether the post-link scripts of conda packages are executed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PostLinkScriptPolicy {
+    /// Execute post-link scripts.
+    Allow,
+    /// Execute post-link scripts but emit a warning for every script that is executed.
+    Warn,
+    /// Never execute post-link scripts, a warning is emitted for every script that is skipped.
+    #[default]
+    Deny,
+}
+
+/// The output of a post-link script that was executed.
+#[derive(Debug)]
+pub struct PostLinkOutput {
+    /// The name of the package the script belongs to.
+    pub package: String,
+
+    /// The combined stdout and stderr of the script.
+    pub output: String,
+}
+
+/// Returns the path (relative to the prefix) of the post-link script of a package, if any.
+fn post_link_script(record: &PrefixRecord) -> Option<&PathBuf> {
+    let name = record.repodata_record.package_record.name.as_normalized();
+    let (dir, file_name) = if cfg!(windows) {
+        ("Scripts", format!(".{name}-post-link.bat"))
+    } else {
+        ("bin", format!(".{name}-post-link.sh"))
+    };
+    record
+        .files
+        .iter()
+        .find(|path| path.parent() == Some(Path::new(dir)) && path.ends_with(&file_name))
+}
+
+/// Executes the post-link scripts of the given packages according to the `policy`.
+///
+/// Scripts are executed with a sanitized environment that only contains the variables conda
+/// documents for link scripts (`PREFIX`, `PKG_NAME`, `PKG_VERSION`, `PKG_BUILDNUM`) and a `PATH`
+/// that points into the prefix. The output of the executed scripts is returned so it can be
+/// reported to the user.
+pub fn run_post_link_scripts(
+    prefix: &Path,
+    records: &[PrefixRecord],
+    policy: PostLinkScriptPolicy,
+) -> miette::Result<Vec<PostLinkOutput>> {
+    let mut outputs = Vec::new();
+    for record in records {
+        let Some(script) = post_link_script(record) else {
+            continue;
+        };
+        let package_record = &record.repodata_record.package_record;
+        let package = package_record.name.as_source().to_string();
+
+        match policy {
+            PostLinkScriptPolicy::Deny => {
+                warnings::emit(
+                    WarningCode::PostLinkScript,
+                    format!("skipped the post-link script of {package}, the package might not work as expected"),
+                );
+                continue;
+            }
+            PostLinkScriptPolicy::Warn => {
+                warnings::emit(
+                    WarningCode::PostLinkScript,
+                    format!("executing the post-link script of {package}"),
+                );
+            }
+            PostLinkScriptPolicy::Allow => {}
+        }
+
+        let script_path = prefix.join(script);
+        let mut command = if cfg!(windows) {
+            let mut command = std::process::Command::new("cmd.exe");
+            command.arg("/d").arg("/c").arg(&script_path);
+            command
+        } else {
+            let mut command = std::process::Command::new("/bin/sh");
+            command.arg(&script_path);
+            command
+        };
+
+        let bin_dir = if cfg!(windows) {
+            prefix.join("Library").join("bin")
+        } else {
+            prefix.join("bin")
+        };
+        let system_path = if cfg!(windows) {
+            std::env::var_os("SystemRoot")
+                .map(|root| PathBuf::from(root).join("System32"))
+                .unwrap_or_else(|| PathBuf::from(r"C:\Windows\System32"))
+        } else {
+            PathBuf::from("/usr/bin:/bin")
+        };
+        let path =
+            std::env::join_paths([bin_dir.as_path(), system_path.as_path()]).into_diagnostic()?;
+
+        let result = command
+            .current_dir(prefix)
+            .env_clear()
+            .env("PATH", path)
+            .env("PREFIX", prefix)
+            .env("PKG_NAME", package_record.name.as_normalized())
+            .env("PKG_VERSION", package_record.version.to_string())
+            .env("PKG_BUILDNUM", package_record.build_number.to_string())
+            .output()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to execute the post-link script of {package}"))?;
+
+        // Conda link scripts communicate messages to the user through a `.messages.txt` file.
+        let messages_path = prefix.join(".messages.txt");
+        let messages = std::fs::read_to_string(&messages_path).unwrap_or_default();
+        let _ = std::fs::remove_file(&messages_path);
+
+        let output = [
+            String::from_utf8_lossy(&result.stdout),
+            String::from_utf8_lossy(&result.stderr),
+            messages.into(),
+        ]
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .join("\n");
+
+        if !result.status.success() {
+            miette::bail!(
+                "the post-link script of {package} failed with {}:\n{output}",
+                result.status
+            );
+        }
+
+        outputs.push(PostLinkOutput { package, output });
+    }
+    Ok(outputs)
+}
+
+/// Prints the output of executed post-link scripts to the user.
+pub fn report_post_link_outputs(outputs: &[PostLinkOutput]) {
+    for PostLinkOutput { package, output } in outputs.iter().filter(|o| !o.output.is_empty()) {
+        eprintln!(
+            "{}post-link script of {}:\n{}",
+            console::style(console::Emoji("ℹ ", "")).blue(),
+            console::style(package).bold(),
+            output
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{repodata_record, CONDA_FORGE};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_file_names_use_the_normalized_name() {
+        let mut record = repodata_record("pyyaml", "6.0.1", CONDA_FORGE).package_record;
+        record.name = rattler_conda_types::PackageName::from_str("PyYAML").unwrap();
+        assert_eq!(conda_meta_file_name(&record), "pyyaml-6.0.1-0.json");
+        assert_eq!(package_cache_dir_name(&record), "pyyaml-6.0.1-0");
+    }
+
+    #[test]
+    fn test_rollback_removes_partially_linked_package() {
+        let prefix = tempfile::tempdir().unwrap();
+        let prefix = prefix.path();
+        let record = repodata_record("foo", "1.0", CONDA_FORGE).package_record;
+        TransactionJournal::write(
+            prefix,
+            &JournalContents {
+                installing: vec![conda_meta_file_name(&record)],
+            },
+        )
+        .unwrap();
+
+        // The extracted package in the package cache.
+        let package_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(package_dir.path().join("info")).unwrap();
+        std::fs::write(
+            package_dir.path().join("info/paths.json"),
+            r#"{
+                "paths": [
+                    {"_path": "lib/foo-a.txt", "path_type": "hardlink"},
+                    {"_path": "lib/foo-b.txt", "path_type": "hardlink"}
+                ],
+                "paths_version": 1
+            }"#,
+        )
+        .unwrap();
+        TransactionJournal::record_linking(prefix, package_dir.path(), &record, None).unwrap();
+
+        // The link fails after the first file was linked, so no conda-meta file is written.
+        std::fs::create_dir_all(prefix.join("lib")).unwrap();
+        std::fs::write(prefix.join("lib/foo-a.txt"), "a").unwrap();
+        std::fs::write(prefix.join("lib/other.txt"), "other").unwrap();
+
+        rollback_transaction(prefix).unwrap();
+        assert!(!prefix.join("lib/foo-a.txt").exists());
+        assert!(prefix.join("lib/other.txt").exists());
+        assert!(!TransactionJournal::dir(prefix).exists());
+    }
+}
@@ -1,4 +1,7 @@
+use crate::auth;
+use crate::concurrency;
 use crate::environment::PythonStatus;
+use crate::interrupt;
 use crate::prefix::Prefix;
 use crate::progress;
 use crate::progress::ProgressBarMessageFormatter;
@@ -19,15 +22,21 @@ use rip::types::{
     WheelFilename,
 };
 use std::collections::HashSet;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Mutex;
 use std::time::Duration;
 use tokio::task::JoinError;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 /// The installer name for pypi packages installed by pixi.
 pub(crate) const PIXI_PYPI_INSTALLER: &str = env!("CARGO_PKG_NAME");
 
 /// Installs and/or remove python distributions.
+///
+/// If pixi is interrupted with Ctrl+C or any of the distributions fails to install, the
+/// distributions are restored to the state they were in before.
 pub async fn update_python_distributions(
     package_db: &PackageDb,
     prefix: &Prefix,
@@ -80,23 +89,68 @@ pub async fn update_python_distributions(
     let (package_stream, package_stream_pb) =
         stream_python_artifacts(package_db, python_distributions_to_install.clone());
 
-    // Remove python packages that need to be removed
-    if !python_distributions_to_remove.is_empty() {
-        let site_package_path = install_paths.site_packages();
+    // Wind down instead of terminating when pixi is interrupted, the downloads are cancelled and
+    // the wheels that are being unpacked are finished so they can be removed again.
+    let interrupt_guard = interrupt::guard();
+    let site_package_path = install_paths.site_packages().to_path_buf();
+    let rollback = PypiRollback::new(prefix.root(), &prefix.root().join(&site_package_path))?;
 
+    let result = async {
+        // Remove python packages that need to be removed
         for python_distribution in python_distributions_to_remove {
-            uninstall_pixi_installed_distribution(prefix, site_package_path, &python_distribution)?;
+            if interrupt_guard.is_interrupted() {
+                break;
+            }
+            rollback.backup(&prefix.root().join(&python_distribution.dist_info))?;
+            uninstall_pixi_installed_distribution(
+                prefix,
+                &site_package_path,
+                &python_distribution,
+            )?;
         }
-    }
 
-    // Install the individual python packages that we want
-    let package_install_pb = install_python_distributions(
-        prefix,
-        install_paths,
-        &prefix.root().join(python_info.path()),
-        package_stream,
-    )
-    .await?;
+        // Install the individual python packages that we want
+        let root = prefix.root().to_path_buf();
+        let python_executable_path = prefix.root().join(python_info.path());
+        let package_stream = package_stream
+            .map_ok(|(hash, extras, wheel)| (wheel.name().to_string(), (hash, extras, wheel)));
+        install_python_distributions(
+            package_stream,
+            interrupt_guard.token(),
+            &rollback,
+            move |(hash, extras, wheel)| {
+                unpack_wheel(
+                    &root,
+                    &install_paths,
+                    &python_executable_path,
+                    hash,
+                    extras,
+                    wheel,
+                )
+            },
+        )
+        .await
+    }
+    .await;
+
+    if interrupt_guard.is_interrupted() {
+        rollback
+            .rollback()
+            .wrap_err("failed to roll back the interrupted update of the python packages")?;
+        miette::bail!(
+            "the update of the python packages in '{}' was interrupted, they were restored to their previous state",
+            prefix.root().display()
+        );
+    }
+    let package_install_pb = match result {
+        Ok(pb) => pb,
+        Err(err) => {
+            if let Err(rollback_err) = rollback.rollback() {
+                tracing::warn!("failed to roll back the python packages: {rollback_err}");
+            }
+            return Err(err);
+        }
+    };
 
     // Clear any pending progress bar
     for pb in package_install_pb
@@ -109,12 +163,125 @@ pub async fn update_python_distributions(
     Ok(())
 }
 
-/// Concurrently installs python wheels as they become available.
-async fn install_python_distributions(
-    prefix: &Prefix,
-    install_paths: InstallPaths,
+/// Unpacks a wheel into the environment and returns the path of its `.dist-info` directory.
+fn unpack_wheel(
+    root: &Path,
+    install_paths: &InstallPaths,
     python_executable_path: &Path,
-    package_stream: impl Stream<Item = miette::Result<(Option<String>, HashSet<Extra>, Wheel)>> + Sized,
+    hash: Option<String>,
+    extras: HashSet<Extra>,
+    wheel: Wheel,
+) -> miette::Result<PathBuf> {
+    let unpacked_wheel = wheel
+        .unpack(
+            root,
+            install_paths,
+            python_executable_path,
+            &UnpackWheelOptions {
+                installer: Some(PIXI_PYPI_INSTALLER.into()),
+                extras: Some(extras),
+                ..Default::default()
+            },
+        )
+        .into_diagnostic()?;
+    if let Some(hash) = hash {
+        std::fs::write(unpacked_wheel.dist_info.join("HASH"), hash).into_diagnostic()?;
+    }
+    Ok(unpacked_wheel.dist_info)
+}
+
+/// The changes of an update of the python distributions, so they can be undone. The files of a
+/// distribution are copied to a backup directory in the environment before it is removed, and the
+/// distributions that are installed are recorded as they are unpacked.
+struct PypiRollback {
+    site_packages: PathBuf,
+    backup_dir: tempfile::TempDir,
+    /// Pairs of the path of a removed file and the path of its copy in the backup directory.
+    backups: Mutex<Vec<(PathBuf, PathBuf)>>,
+    /// The `.dist-info` directories of the installed distributions.
+    installed: Mutex<Vec<PathBuf>>,
+}
+
+impl PypiRollback {
+    fn new(prefix_root: &Path, site_packages: &Path) -> miette::Result<Self> {
+        Ok(Self {
+            site_packages: site_packages.to_path_buf(),
+            backup_dir: tempfile::Builder::new()
+                .prefix(".pixi-pypi-backup")
+                .tempdir_in(prefix_root)
+                .into_diagnostic()?,
+            backups: Mutex::default(),
+            installed: Mutex::default(),
+        })
+    }
+
+    /// Copies the files of the distribution with the `dist_info` directory, as listed in its
+    /// `RECORD`, to the backup directory.
+    fn backup(&self, dist_info: &Path) -> miette::Result<()> {
+        let record = std::fs::read_to_string(dist_info.join("RECORD"))
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read the RECORD of '{}'", dist_info.display()))?;
+        let mut backups = self.backups.lock().unwrap();
+        for path in record.lines().filter_map(record_path) {
+            let source = self.site_packages.join(path);
+            if !source.is_file() {
+                continue;
+            }
+            let backup = self.backup_dir.path().join(backups.len().to_string());
+            std::fs::copy(&source, &backup)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to back up '{}'", source.display()))?;
+            backups.push((source, backup));
+        }
+        Ok(())
+    }
+
+    /// Records a distribution that was installed, by its `.dist-info` directory.
+    fn installed(&self, dist_info: PathBuf) {
+        self.installed.lock().unwrap().push(dist_info);
+    }
+
+    /// Removes the installed distributions and restores the removed ones.
+    fn rollback(self) -> miette::Result<()> {
+        for dist_info in self.installed.into_inner().unwrap() {
+            let relative_dist_info = dist_info
+                .strip_prefix(&self.site_packages)
+                .unwrap_or(&dist_info);
+            let _ = std::fs::remove_file(dist_info.join("HASH"));
+            uninstall_distribution(&self.site_packages, relative_dist_info)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to remove '{}'", dist_info.display()))?;
+        }
+        for (target, backup) in self.backups.into_inner().unwrap() {
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).into_diagnostic()?;
+            }
+            std::fs::copy(&backup, &target)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to restore '{}'", target.display()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the path of a line of a `RECORD` file, the first field of the csv line.
+fn record_path(line: &str) -> Option<&str> {
+    let path = match line.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next()?,
+        None => line.split(',').next()?,
+    };
+    Some(path).filter(|path| !path.is_empty())
+}
+
+/// Concurrently installs python wheels as they become available, the items of the stream are
+/// pairs of the name of the package and what `unpack` needs to install it. Once `token` is
+/// cancelled no new wheels are installed.
+#[tracing::instrument(name = "install_pypi", skip_all)]
+async fn install_python_distributions<T: Send + 'static>(
+    package_stream: impl Stream<Item = miette::Result<(String, T)>> + Sized,
+    token: &CancellationToken,
+    rollback: &PypiRollback,
+    unpack: impl Fn(T) -> miette::Result<PathBuf> + Clone + Send + 'static,
 ) -> miette::Result<Option<ProgressBar>> {
     // Determine the number of packages that we are going to install
     let len = {
@@ -137,49 +304,32 @@ async fn install_python_distributions(
     // Concurrently unpack the wheels as they become available in the stream.
     let install_pb = pb.clone();
     package_stream
-        .try_for_each_concurrent(Some(20), move |(hash, extras, wheel)| {
-            let install_paths = install_paths.clone();
-            let root = prefix.root().to_path_buf();
-            let message_formatter = message_formatter.clone();
-            let pb = install_pb.clone();
-            let python_executable_path = python_executable_path.to_owned();
-            async move {
-                let pb_task = message_formatter.start(wheel.name().to_string()).await;
-                let unpack_result = tokio::task::spawn_blocking(move || {
-                    wheel
-                        .unpack(
-                            &root,
-                            &install_paths,
-                            &python_executable_path,
-                            &UnpackWheelOptions {
-                                installer: Some(PIXI_PYPI_INSTALLER.into()),
-                                extras: Some(extras),
-                                ..Default::default()
-                            },
-                        )
-                        .into_diagnostic()
-                        .and_then(|unpacked_wheel| {
-                            if let Some(hash) = hash {
-                                std::fs::write(unpacked_wheel.dist_info.join("HASH"), hash)
-                                    .into_diagnostic()
-                            } else {
-                                Ok(())
-                            }
-                        })
-                })
-                .map_err(JoinError::try_into_panic)
-                .await;
-
-                pb_task.finish().await;
-                pb.inc(1);
-
-                match unpack_result {
-                    Ok(unpack_result) => unpack_result,
-                    Err(Ok(panic)) => std::panic::resume_unwind(panic),
-                    Err(Err(e)) => Err(miette::miette!("{e}")),
+        .take_until(token.cancelled())
+        .try_for_each_concurrent(
+            Some(concurrency::limits().link_jobs),
+            move |(name, package)| {
+                let message_formatter = message_formatter.clone();
+                let pb = install_pb.clone();
+                let unpack = unpack.clone();
+                async move {
+                    let pb_task = message_formatter.start(name).await;
+                    let unpack_result = tokio::task::spawn_blocking(move || unpack(package))
+                        .map_err(JoinError::try_into_panic)
+                        .await;
+
+                    pb_task.finish().await;
+                    pb.inc(1);
+
+                    match unpack_result {
+                        Ok(unpack_result) => {
+                            unpack_result.map(|dist_info| rollback.installed(dist_info))
+                        }
+                        Err(Ok(panic)) => std::panic::resume_unwind(panic),
+                        Err(Err(e)) => Err(miette::miette!("{e}")),
+                    }
                 }
-            }
-        })
+            },
+        )
         .await?;
 
     // Update the progress bar
@@ -247,7 +397,7 @@ fn stream_python_artifacts<'a>(
                 // Reconstruct the ArtifactInfo from the data in the lockfile.
                 let artifact_info = ArtifactInfo {
                     filename: ArtifactName::Wheel(wheel_name),
-                    url: pip_package.url.clone(),
+                    url: auth::authenticate_url(&pip_package.url),
                     hashes: pip_package.hash.as_ref().map(|hash| ArtifactHashes {
                         sha256: hash.sha256().cloned(),
                     }),
@@ -263,7 +413,10 @@ fn stream_python_artifacts<'a>(
 
                 // TODO: Maybe we should have a cache of wheels separate from the package_db. Since a
                 //   wheel can just be identified by its hash or url.
-                let wheel: Wheel = package_db.get_wheel(&artifact_info, None).await?;
+                let wheel: Wheel = package_db
+                    .get_wheel(&artifact_info, None)
+                    .instrument(tracing::info_span!("download_wheel", package = filename))
+                    .await?;
 
                 // Update the progress bar
                 pb_task.finish().await;
@@ -290,7 +443,7 @@ fn stream_python_artifacts<'a>(
                 ))
             }
         })
-        .buffer_unordered(20)
+        .buffer_unordered(concurrency::limits().downloads)
         .right_stream();
 
     (download_stream, Some(pb))
@@ -517,3 +670,86 @@ fn does_installed_match_locked_package(
         (Some(locked_tags), Some(installed_tags)) => locked_tags == installed_tags,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a distribution with a module and a `.dist-info` directory to `site_packages` and
+    /// returns the path of its `.dist-info` directory.
+    fn write_distribution(site_packages: &Path, name: &str) -> PathBuf {
+        let dist_info = site_packages.join(format!("{name}-1.0.dist-info"));
+        std::fs::create_dir_all(site_packages.join(name)).unwrap();
+        std::fs::create_dir_all(&dist_info).unwrap();
+        std::fs::write(site_packages.join(name).join("__init__.py"), name).unwrap();
+        std::fs::write(
+            dist_info.join("METADATA"),
+            format!("Metadata-Version: 2.1\nName: {name}\nVersion: 1.0\n"),
+        )
+        .unwrap();
+        std::fs::write(dist_info.join("INSTALLER"), PIXI_PYPI_INSTALLER).unwrap();
+        std::fs::write(
+            dist_info.join("RECORD"),
+            format!(
+                "{name}/__init__.py,,\n{name}-1.0.dist-info/METADATA,,\n{name}-1.0.dist-info/INSTALLER,,\n{name}-1.0.dist-info/RECORD,,\n"
+            ),
+        )
+        .unwrap();
+        dist_info
+    }
+
+    #[test]
+    fn test_record_path() {
+        assert_eq!(
+            record_path("foo/__init__.py,sha256=abc,12"),
+            Some("foo/__init__.py")
+        );
+        assert_eq!(record_path("\"foo,bar.py\",,"), Some("foo,bar.py"));
+        assert_eq!(record_path(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_install_is_rolled_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let site_packages = dir.path().join("lib").join("site-packages");
+        let old_dist_info = write_distribution(&site_packages, "old");
+
+        // Remove a distribution like an update does.
+        let rollback = PypiRollback::new(dir.path(), &site_packages).unwrap();
+        rollback.backup(&old_dist_info).unwrap();
+        uninstall_distribution(&site_packages, Path::new("old-1.0.dist-info")).unwrap();
+        assert!(!site_packages.join("old").join("__init__.py").exists());
+
+        // Pixi is interrupted while the first new distribution is installed, the ones that were
+        // not started yet are skipped.
+        let token = CancellationToken::new();
+        let names = ["first", "second", "third"].map(String::from);
+        let package_stream =
+            stream::iter(names.map(|name| Ok((name.clone(), name)))).then(|item| async {
+                tokio::task::yield_now().await;
+                item
+            });
+        let unpack_token = token.clone();
+        let unpack_site_packages = site_packages.clone();
+        install_python_distributions(package_stream, &token, &rollback, move |name: String| {
+            let dist_info = write_distribution(&unpack_site_packages, &name);
+            unpack_token.cancel();
+            Ok(dist_info)
+        })
+        .await
+        .unwrap();
+        assert!(token.is_cancelled());
+        assert!(site_packages.join("first").join("__init__.py").is_file());
+
+        rollback.rollback().unwrap();
+        for name in ["first", "second", "third"] {
+            assert!(!site_packages.join(name).join("__init__.py").exists());
+            assert!(!site_packages.join(format!("{name}-1.0.dist-info")).exists());
+        }
+        assert_eq!(
+            std::fs::read_to_string(site_packages.join("old").join("__init__.py")).unwrap(),
+            "old"
+        );
+        assert!(old_dist_info.join("RECORD").is_file());
+    }
+}
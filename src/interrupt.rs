@@ -0,0 +1,160 @@
+//! Graceful handling of Ctrl+C. Work that leaves the machine in an inconsistent state when it is
+//! stopped halfway, e.g. updating the packages of an environment, holds an [`InterruptGuard`]. An
+//! interrupt cancels the guards so the work can wind down and undo its changes, without a guard an
+//! interrupt terminates pixi like it would without a handler.
+
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// The exit code of a process that was terminated by Ctrl+C.
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+#[derive(Default)]
+struct Guards {
+    /// Whether the interrupt listener runs, it is started by the first guard.
+    listening: bool,
+    next_id: u64,
+    tokens: Vec<(u64, CancellationToken)>,
+}
+
+/// The registry of the guards that are cancelled by an interrupt.
+#[derive(Default)]
+struct Interrupts {
+    guards: Mutex<Guards>,
+    /// Set once pixi was interrupted.
+    interrupted: AtomicBool,
+}
+
+/// The registry of the guards of the process, which the interrupt listener cancels.
+static INTERRUPTS: Lazy<Interrupts> = Lazy::new(Default::default);
+
+impl Interrupts {
+    /// Registers a new guard. Returns true as well if the listener still has to be started.
+    fn register(&self) -> (InterruptGuard<'_>, bool) {
+        let mut guards = self.guards.lock().unwrap();
+        let id = guards.next_id;
+        guards.next_id += 1;
+        let token = CancellationToken::new();
+        guards.tokens.push((id, token.clone()));
+        let start_listening = !std::mem::replace(&mut guards.listening, true);
+        let guard = InterruptGuard {
+            interrupts: self,
+            id,
+            token,
+        };
+        (guard, start_listening)
+    }
+
+    /// Handles an interrupt, returns false if there is no guard and pixi should exit.
+    fn interrupt(&self) -> bool {
+        self.interrupted.store(true, Ordering::Relaxed);
+        let guards = self.guards.lock().unwrap();
+        for (_, token) in guards.tokens.iter() {
+            token.cancel();
+        }
+        !guards.tokens.is_empty()
+    }
+
+    /// Returns true if there was an interrupt.
+    fn was_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Relaxed)
+    }
+}
+
+/// Cancels its token when pixi is interrupted while the guard is alive.
+pub struct InterruptGuard<'a> {
+    interrupts: &'a Interrupts,
+    id: u64,
+    token: CancellationToken,
+}
+
+impl InterruptGuard<'_> {
+    /// Returns the token that is cancelled when pixi is interrupted.
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Returns true if pixi was interrupted while the guard was alive.
+    pub fn is_interrupted(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+impl Drop for InterruptGuard<'_> {
+    fn drop(&mut self) {
+        self.interrupts
+            .guards
+            .lock()
+            .unwrap()
+            .tokens
+            .retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Returns a guard that keeps pixi alive on Ctrl+C and cancels its token instead. The owner of the
+/// guard is responsible for stopping its work, further interrupts are ignored until the guard is
+/// dropped. Must be called from within a tokio runtime.
+pub fn guard() -> InterruptGuard<'static> {
+    let (guard, start_listening) = INTERRUPTS.register();
+    if start_listening {
+        tokio::spawn(listen());
+    }
+    guard
+}
+
+/// Returns true if pixi was interrupted by Ctrl+C.
+pub fn was_interrupted() -> bool {
+    INTERRUPTS.was_interrupted()
+}
+
+/// Waits for interrupts for the rest of the program. Once the handler is registered the default
+/// behavior of Ctrl+C is replaced, so without guards the process is terminated here.
+async fn listen() {
+    while tokio::signal::ctrl_c().await.is_ok() {
+        if !INTERRUPTS.interrupt() {
+            crate::progress::global_multi_progress().clear().ok();
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interrupt_cancels_guards() {
+        let interrupts = Interrupts::default();
+        let (first, start_listening) = interrupts.register();
+        assert!(start_listening);
+        let (second, start_listening) = interrupts.register();
+        assert!(!start_listening);
+        assert!(!first.is_interrupted());
+
+        let second_id = second.id;
+        drop(second);
+        let registered = interrupts
+            .guards
+            .lock()
+            .unwrap()
+            .tokens
+            .iter()
+            .any(|(id, _)| *id == second_id);
+        assert!(!registered);
+
+        assert!(interrupts.interrupt());
+        assert!(first.is_interrupted());
+        assert!(interrupts.was_interrupted());
+
+        // A guard that is created afterwards is not interrupted.
+        let (third, _) = interrupts.register();
+        assert!(!third.is_interrupted());
+
+        // Without guards an interrupt terminates pixi.
+        drop(first);
+        drop(third);
+        assert!(!interrupts.interrupt());
+    }
+}
@@ -1,22 +1,54 @@
+pub mod activation_cache;
+pub mod alias;
+pub mod auth;
+pub mod banned;
+pub mod build_envs;
+pub mod build_fallback;
+pub mod channel_report;
+pub mod chrome_trace;
+pub mod ci;
 pub mod cli;
+pub mod concurrency;
 pub mod config;
 pub mod consts;
+pub mod drift;
+pub mod emulation;
+pub mod end_of_life;
 pub mod environment;
+pub mod environment_builder;
+pub mod gitignore;
+pub mod host;
 pub mod install;
 pub mod install_pypi;
+pub mod interrupt;
+pub mod local_channel;
 pub mod lock_file;
+pub mod menuinst;
+pub mod overlay;
 pub mod prefix;
+pub mod prerelease;
 pub mod progress;
 pub mod project;
 mod prompt;
+pub mod remote;
 pub mod repodata;
+pub mod shared_prefix;
+pub mod shebang;
+pub mod snapshot;
 pub mod task;
+#[cfg(test)]
+mod test_utils;
+pub mod token_expiry;
+pub mod trust;
 #[cfg(unix)]
 pub mod unix;
+pub mod unused_dependencies;
 pub mod util;
 pub mod utils;
 pub mod virtual_packages;
+pub mod warnings;
 
+pub use environment_builder::{EnvironmentBuilder, PixiEnvironment};
 use once_cell::sync::Lazy;
 pub use project::Project;
 use rattler_networking::retry_policies::ExponentialBackoff;
@@ -29,9 +61,17 @@ pub fn default_retry_policy() -> ExponentialBackoff {
     ExponentialBackoff::builder().build_with_max_retries(3)
 }
 
-/// Returns the default client to use for networking.
+/// Returns the default client to use for networking. The client uses the proxies and TLS settings
+/// of the global configuration.
 pub fn default_client() -> Client {
-    static CLIENT: Lazy<Client> = Lazy::new(Default::default);
+    static CLIENT: Lazy<Client> = Lazy::new(|| {
+        config::Config::load_global()
+            .and_then(|config| config.http_client())
+            .unwrap_or_else(|err| {
+                tracing::warn!("failed to apply the network configuration: {err:?}");
+                Client::default()
+            })
+    });
     CLIENT.clone()
 }
 
@@ -0,0 +1,257 @@
+//! Channels in a directory on the local file system. Such a directory only needs a
+//! `repodata.json` in every subdirectory to be used as a channel, e.g. with a `file://` url or a
+//! path in the `channels` of the manifest. The repodata is generated from the packages in the
+//! directory by [`index_channel`], and [`serve_channel`] makes the directory available to other
+//! machines over HTTP.
+
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::package::{IndexJson, PackageFile};
+use rattler_conda_types::{PackageRecord, Platform};
+use rattler_digest::{compute_file_digest, Md5, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The name of the file with the repodata of a subdirectory.
+const REPODATA_FILE: &str = "repodata.json";
+
+/// The number of packages that were indexed in a subdirectory of a channel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedSubdir {
+    pub subdir: String,
+    pub packages: usize,
+}
+
+/// Reads the record of a package from its archive.
+fn package_record(path: &Path) -> miette::Result<PackageRecord> {
+    let extracted = tempfile::tempdir().into_diagnostic()?;
+    rattler_package_streaming::fs::extract(path, extracted.path()).into_diagnostic()?;
+    let index_json = IndexJson::from_package_directory(extracted.path()).into_diagnostic()?;
+    let size = std::fs::metadata(path).into_diagnostic()?.len();
+    let sha256 = compute_file_digest::<Sha256>(path).into_diagnostic()?;
+    let md5 = compute_file_digest::<Md5>(path).into_diagnostic()?;
+    PackageRecord::from_index_json(index_json, Some(size), Some(sha256), Some(md5))
+        .into_diagnostic()
+}
+
+/// Writes the `repodata.json` of a subdirectory of a channel from the packages in it and returns
+/// the number of packages.
+fn index_subdir(dir: &Path, subdir: &str) -> miette::Result<usize> {
+    let mut packages = BTreeMap::new();
+    let mut conda_packages = BTreeMap::new();
+    for entry in std::fs::read_dir(dir).into_diagnostic()? {
+        let path = entry.into_diagnostic()?.path();
+        let Some(file_name) = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+        else {
+            continue;
+        };
+        let target = if file_name.ends_with(".conda") {
+            &mut conda_packages
+        } else if file_name.ends_with(".tar.bz2") {
+            &mut packages
+        } else {
+            continue;
+        };
+
+        let record = package_record(&path)
+            .wrap_err_with(|| format!("failed to read the package '{}'", path.display()))?;
+        if record.subdir != subdir {
+            tracing::warn!(
+                "skipping '{}', it is a package for {} and not for {subdir}",
+                path.display(),
+                record.subdir
+            );
+            continue;
+        }
+        target.insert(file_name, record);
+    }
+
+    let count = packages.len() + conda_packages.len();
+    let repodata = serde_json::json!({
+        "info": { "subdir": subdir },
+        "packages": packages,
+        "packages.conda": conda_packages,
+        "removed": [],
+        "repodata_version": 1,
+    });
+    let path = dir.join(REPODATA_FILE);
+    std::fs::write(
+        &path,
+        serde_json::to_string_pretty(&repodata).into_diagnostic()? + "\n",
+    )
+    .into_diagnostic()
+    .wrap_err_with(|| format!("failed to write '{}'", path.display()))?;
+    Ok(count)
+}
+
+/// Generates the `repodata.json` of every subdirectory of the channel in `dir` whose name is a
+/// platform. A `noarch` subdirectory is always created, because clients expect it to exist.
+pub fn index_channel(dir: &Path) -> miette::Result<Vec<IndexedSubdir>> {
+    if !dir.is_dir() {
+        miette::bail!("'{}' is not a directory", dir.display());
+    }
+    std::fs::create_dir_all(dir.join(Platform::NoArch.as_str())).into_diagnostic()?;
+
+    std::fs::read_dir(dir)
+        .into_diagnostic()?
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let subdir = entry.file_name().to_string_lossy().to_string();
+            Platform::from_str(&subdir).ok().map(|_| subdir)
+        })
+        .sorted()
+        .map(|subdir| {
+            let packages = index_subdir(&dir.join(&subdir), &subdir)?;
+            Ok(IndexedSubdir { subdir, packages })
+        })
+        .collect()
+}
+
+/// Returns the file in `root` that a request for `target` refers to, `None` if the target points
+/// outside of `root`.
+fn resolve_request_path(root: &Path, target: &str) -> Option<PathBuf> {
+    let path = target.split(['?', '#']).next()?;
+    let mut resolved = root.to_path_buf();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return None,
+            segment if segment.contains('\\') || segment.contains(':') => return None,
+            segment => resolved.push(segment),
+        }
+    }
+    Some(resolved)
+}
+
+/// Returns the content type of a file of a channel.
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Answers a single request for a file of the channel in `root`.
+fn handle_connection(root: &Path, mut stream: TcpStream) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers of the request are not needed
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    tracing::info!("{method} {target}");
+
+    let respond = |stream: &mut TcpStream, status: &str| {
+        write!(
+            stream,
+            "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )
+    };
+    if method != "GET" && method != "HEAD" {
+        return respond(&mut stream, "405 Method Not Allowed");
+    }
+    let Some(path) = resolve_request_path(root, target).filter(|path| path.is_file()) else {
+        return respond(&mut stream, "404 Not Found");
+    };
+
+    let mut file = File::open(&path)?;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        content_type(&path),
+        file.metadata()?.len()
+    )?;
+    if method == "GET" {
+        std::io::copy(&mut file, &mut stream)?;
+    }
+    stream.flush()
+}
+
+/// Serves the files of the channel in `dir` over HTTP until the process is stopped. Every
+/// connection is handled on its own thread.
+pub fn serve_channel(dir: &Path, listener: TcpListener) -> miette::Result<()> {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                tracing::warn!("failed to accept a connection: {err}");
+                continue;
+            }
+        };
+        let dir = dir.to_path_buf();
+        std::thread::spawn(move || {
+            if let Err(err) = handle_connection(&dir, stream) {
+                tracing::debug!("failed to answer a request: {err}");
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_request_path() {
+        let root = Path::new("/srv/channel");
+        assert_eq!(
+            resolve_request_path(root, "/linux-64/repodata.json?version=1"),
+            Some(root.join("linux-64").join("repodata.json"))
+        );
+        assert_eq!(
+            resolve_request_path(root, "/noarch/./foo-1.0-0.conda"),
+            Some(root.join("noarch").join("foo-1.0-0.conda"))
+        );
+        assert_eq!(resolve_request_path(root, "/../etc/passwd"), None);
+        assert_eq!(resolve_request_path(root, "/noarch/..%2F..\\secret"), None);
+    }
+
+    #[test]
+    fn test_index_empty_channel() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("linux-64")).unwrap();
+        std::fs::create_dir(dir.path().join("not-a-platform")).unwrap();
+
+        let indexed = index_channel(dir.path()).unwrap();
+        assert_eq!(
+            indexed,
+            vec![
+                IndexedSubdir {
+                    subdir: String::from("linux-64"),
+                    packages: 0
+                },
+                IndexedSubdir {
+                    subdir: String::from("noarch"),
+                    packages: 0
+                }
+            ]
+        );
+
+        let repodata: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(dir.path().join("noarch").join(REPODATA_FILE)).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(repodata["info"]["subdir"], "noarch");
+        assert!(!dir
+            .path()
+            .join("not-a-platform")
+            .join(REPODATA_FILE)
+            .exists());
+    }
+}
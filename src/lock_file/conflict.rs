@@ -0,0 +1,148 @@
+//! Traces the packages of a conflict that the solver reports back to the dependencies in the
+//! manifest. A conflict often involves a package that the user never added, this points at the
+//! dependencies that require it.
+
+use crate::Project;
+use itertools::Itertools;
+use miette::{LabeledSpan, Report};
+use rattler_conda_types::{PackageName, Platform, RepoDataRecord};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+/// Returns the names of the packages in `dependencies` that are mentioned in the message of the
+/// solver, and for the other mentioned packages which of the `dependencies` require them directly
+/// or transitively.
+fn trace_conflict<'a>(
+    message: &str,
+    dependencies: &'a [String],
+    records: impl IntoIterator<Item = (&'a str, Vec<String>)>,
+) -> (BTreeSet<&'a str>, HashMap<&'a str, BTreeSet<String>>) {
+    // The names of the packages that every package depends on, in any version.
+    let mut graph: HashMap<&str, HashSet<String>> = HashMap::new();
+    for (name, depends) in records {
+        graph.entry(name).or_default().extend(depends);
+    }
+
+    let mentioned: HashSet<&str> = message
+        .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .map(|word| word.trim_end_matches('.'))
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    let mut direct = BTreeSet::new();
+    let mut introduced: HashMap<&str, BTreeSet<String>> = HashMap::new();
+    for dependency in dependencies {
+        if mentioned.contains(dependency.as_str()) {
+            direct.insert(dependency.as_str());
+        }
+
+        // Find the mentioned packages that the dependency requires.
+        let mut visited = HashSet::from([dependency.as_str()]);
+        let mut queue = VecDeque::from([dependency.as_str()]);
+        while let Some(name) = queue.pop_front() {
+            for depend in graph.get(name).into_iter().flatten() {
+                let Some((&depend, _)) = graph.get_key_value(depend.as_str()) else {
+                    continue;
+                };
+                if !visited.insert(depend) {
+                    continue;
+                }
+                queue.push_back(depend);
+                if mentioned.contains(depend) && !dependencies.iter().any(|d| d == depend) {
+                    introduced
+                        .entry(dependency.as_str())
+                        .or_default()
+                        .insert(depend.to_string());
+                }
+            }
+        }
+    }
+    (direct, introduced)
+}
+
+/// Creates the error for a conflict of the solver, which labels the dependencies in the manifest
+/// that are part of the conflict or require a package that is.
+pub(crate) fn conflict_report(
+    project: &Project,
+    platform: Platform,
+    messages: &[String],
+    dependencies: &[PackageName],
+    available_packages: &[Vec<RepoDataRecord>],
+) -> Report {
+    let message = messages.join("\n");
+    let dependencies = dependencies
+        .iter()
+        .map(|name| name.as_normalized().to_string())
+        .collect_vec();
+    let records = available_packages.iter().flatten().map(|record| {
+        let package = &record.package_record;
+        (
+            package.name.as_normalized(),
+            package
+                .depends
+                .iter()
+                .filter_map(|depend| depend.split_whitespace().next())
+                .map(str::to_lowercase)
+                .collect_vec(),
+        )
+    });
+    let (direct, introduced) = trace_conflict(&message, &dependencies, records);
+
+    let spans = project.manifest.dependency_spans();
+    let mut labels = Vec::new();
+    for dependency in &dependencies {
+        let label = match (
+            direct.contains(dependency.as_str()),
+            introduced.get(dependency.as_str()),
+        ) {
+            (_, Some(required)) => format!("requires {}", required.iter().join(", ")),
+            (true, None) => String::from("part of the conflict"),
+            (false, None) => continue,
+        };
+        for span in spans.get(dependency).into_iter().flatten() {
+            labels.push(LabeledSpan::at(span.clone(), label.clone()));
+        }
+    }
+
+    if labels.is_empty() {
+        return miette::miette!("could not solve the dependencies for {platform}:\n{message}");
+    }
+    miette::miette!(
+        labels = labels,
+        help = "relax the version constraints of the labelled dependencies, or remove one of them",
+        "could not solve the dependencies for {platform}:\n{message}"
+    )
+    .with_source_code(project.manifest_named_source())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_conflict() {
+        let message = "The following packages are incompatible\n|-- libfoo >=2 cannot be installed because there are no viable options:\n    |-- libfoo 2.0 would require\n        |-- openssl >=3, which conflicts with the versions reported above.\n|-- bar 1.0 would require openssl <3.";
+        let dependencies = vec![
+            String::from("libfoo"),
+            String::from("bar"),
+            String::from("numpy"),
+        ];
+        let records = vec![
+            ("libfoo", vec![String::from("openssl")]),
+            ("bar", vec![String::from("libbar")]),
+            ("libbar", vec![String::from("openssl")]),
+            ("numpy", vec![String::from("python")]),
+            ("openssl", vec![]),
+        ];
+        let (direct, introduced) = trace_conflict(message, &dependencies, records);
+        assert_eq!(direct, BTreeSet::from(["bar", "libfoo"]));
+        assert_eq!(
+            introduced.get("bar"),
+            Some(&BTreeSet::from([String::from("openssl")]))
+        );
+        assert_eq!(
+            introduced.get("libfoo"),
+            Some(&BTreeSet::from([String::from("openssl")]))
+        );
+        assert!(!introduced.contains_key("numpy"));
+    }
+}
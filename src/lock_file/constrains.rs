@@ -0,0 +1,168 @@
+//! Checks the `run_constrained` pins of the locked conda packages (`constrains` in the lock file)
+//! against the whole environment. The conda solver respects them, but the pypi packages are solved
+//! separately and a lock file can be edited or merged by hand, so the final environment can still
+//! violate them.
+
+use crate::utils::spec_format::normalize_pypi_name;
+use itertools::Itertools;
+use rattler_conda_types::{MatchSpec, Platform, Version};
+use rattler_lock::{CondaLock, LockedDependencyKind};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A locked package of the environment, as seen by the constraint check.
+struct Package<'a> {
+    name: &'a str,
+    version: &'a str,
+    /// The build string of a conda package, `None` for pypi packages.
+    build: Option<&'a str>,
+    constrains: &'a [String],
+}
+
+/// A package whose version is not allowed by a `run_constrained` pin of another package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstraintViolation {
+    /// The package with the pin, e.g. `_openmp_mutex 4.5`.
+    pub constrained_by: String,
+    /// The pin, e.g. `openmp_impl 9999`.
+    pub constraint: String,
+    /// The package that violates the pin, e.g. `openmp_impl 4.0 (pypi)`.
+    pub package: String,
+}
+
+impl Display for ConstraintViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} requires '{}', but {} is locked",
+            self.constrained_by, self.constraint, self.package
+        )
+    }
+}
+
+/// Returns the locked packages of `platform` whose version violates a `run_constrained` pin of
+/// another locked package.
+pub fn find_constraint_violations(
+    lock_file: &CondaLock,
+    platform: Platform,
+) -> Vec<ConstraintViolation> {
+    let packages = lock_file
+        .package
+        .iter()
+        .filter(|package| package.platform == platform)
+        .map(|package| match &package.kind {
+            LockedDependencyKind::Conda(conda) => Package {
+                name: &package.name,
+                version: &package.version,
+                build: conda.build.as_deref(),
+                constrains: &conda.constrains,
+            },
+            LockedDependencyKind::Pypi(_) => Package {
+                name: &package.name,
+                version: &package.version,
+                build: None,
+                constrains: &[],
+            },
+        })
+        .collect_vec();
+    find_violations(&packages)
+}
+
+fn find_violations(packages: &[Package]) -> Vec<ConstraintViolation> {
+    let mut violations = Vec::new();
+    for constraining in packages {
+        for constraint in constraining.constrains {
+            let Ok(spec) = MatchSpec::from_str(constraint) else {
+                tracing::debug!("ignoring the invalid constraint '{constraint}'");
+                continue;
+            };
+            let Some(name) = spec
+                .name
+                .as_ref()
+                .map(|name| normalize_pypi_name(name.as_normalized()))
+            else {
+                continue;
+            };
+            for package in packages
+                .iter()
+                .filter(|package| normalize_pypi_name(package.name) == name)
+            {
+                let Ok(version) = Version::from_str(package.version) else {
+                    continue;
+                };
+                let version_matches = spec
+                    .version
+                    .as_ref()
+                    .map_or(true, |spec| spec.matches(&version));
+                let build_matches = match (&spec.build, package.build) {
+                    (Some(spec), Some(build)) => spec.matches(build),
+                    _ => true,
+                };
+                if !version_matches || !build_matches {
+                    violations.push(ConstraintViolation {
+                        constrained_by: format!("{} {}", constraining.name, constraining.version),
+                        constraint: constraint.clone(),
+                        package: match package.build {
+                            Some(_) => format!("{} {}", package.name, package.version),
+                            None => format!("{} {} (pypi)", package.name, package.version),
+                        },
+                    });
+                }
+            }
+        }
+    }
+    violations
+}
+
+/// Returns an error that explains the violated constraints, if there are any.
+pub fn check_constraints(lock_file: &CondaLock, platform: Platform) -> miette::Result<()> {
+    let violations = find_constraint_violations(lock_file, platform);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(miette::miette!(
+        help = "packages declare the versions of other packages they work with as `run_constrained`, update the lock file with `pixi update` or change the version of the pypi dependency",
+        "the locked environment for {platform} violates {} constraint{}:\n{}",
+        violations.len(),
+        if violations.len() == 1 { "" } else { "s" },
+        violations.iter().map(|violation| format!("  - {violation}")).join("\n")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_violations() {
+        let mutex_constrains = vec![String::from("openmp_impl 9999")];
+        let numpy_constrains = vec![String::from("scipy >=1.10")];
+        let packages = [
+            Package {
+                name: "_openmp_mutex",
+                version: "4.5",
+                build: Some("2_gnu"),
+                constrains: &mutex_constrains,
+            },
+            Package {
+                name: "numpy",
+                version: "1.26.2",
+                build: Some("py311h64a7726_0"),
+                constrains: &numpy_constrains,
+            },
+            Package {
+                name: "SciPy",
+                version: "1.9.3",
+                build: None,
+                constrains: &[],
+            },
+        ];
+
+        let violations = find_violations(&packages);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(
+            violations[0].to_string(),
+            "numpy 1.26.2 requires 'scipy >=1.10', but SciPy 1.9.3 (pypi) is locked"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use miette::{Context, IntoDiagnostic};
+use rattler_lock::CondaLock;
+use serde::Deserialize;
+use std::path::Path;
+
+/// The magic number at the start of every zstd frame.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The format the lock files of a project are written in. Lock files in any format can be read.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LockFileFormat {
+    /// Plain YAML, which can be reviewed and merged as text.
+    #[default]
+    Yaml,
+    /// YAML compressed with zstd, for projects whose lock files grow to many megabytes.
+    Zstd,
+}
+
+/// Reads a lock file in any of the [`LockFileFormat`]s.
+pub fn read_lock_file(path: &Path) -> miette::Result<CondaLock> {
+    let contents = std::fs::read(path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read the lock file '{}'", path.display()))?;
+    parse_lock_file(&contents)
+        .wrap_err_with(|| format!("failed to parse the lock file '{}'", path.display()))
+}
+
+/// Parses the contents of a lock file in any of the [`LockFileFormat`]s.
+pub(super) fn parse_lock_file(contents: &[u8]) -> miette::Result<CondaLock> {
+    if contents.starts_with(&ZSTD_MAGIC) {
+        let decompressed = zstd::decode_all(contents).into_diagnostic()?;
+        serde_yaml::from_slice(&decompressed).into_diagnostic()
+    } else {
+        serde_yaml::from_slice(contents).into_diagnostic()
+    }
+}
+
+/// Writes the lock file to `path` in the given format, creating its directory if needed, e.g.
+/// `.pixi/locks` for projects with `split-lock-files`.
+pub fn write_lock_file(
+    lock_file: &CondaLock,
+    path: &Path,
+    format: LockFileFormat,
+) -> miette::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to create '{}'", parent.display()))?;
+    }
+    match format {
+        LockFileFormat::Yaml => lock_file.to_path(path).into_diagnostic(),
+        LockFileFormat::Zstd => {
+            let compressed = compress_lock_file(lock_file)?;
+            std::fs::write(path, compressed)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to write the lock file '{}'", path.display()))
+        }
+    }
+}
+
+fn compress_lock_file(lock_file: &CondaLock) -> miette::Result<Vec<u8>> {
+    let yaml = serde_yaml::to_string(lock_file).into_diagnostic()?;
+    zstd::encode_all(yaml.as_bytes(), zstd::DEFAULT_COMPRESSION_LEVEL).into_diagnostic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rattler_lock::builder::LockFileBuilder;
+
+    #[test]
+    fn test_read_both_formats() {
+        let lock_file = LockFileBuilder::default().build().unwrap();
+        let yaml = serde_yaml::to_string(&lock_file).unwrap();
+        let compressed = compress_lock_file(&lock_file).unwrap();
+        assert!(compressed.starts_with(&ZSTD_MAGIC));
+
+        for contents in [yaml.as_bytes(), &compressed] {
+            let parsed = parse_lock_file(contents).unwrap();
+            assert_eq!(serde_yaml::to_string(&parsed).unwrap(), yaml);
+        }
+    }
+}
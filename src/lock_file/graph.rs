@@ -0,0 +1,402 @@
+use super::prune::{dependencies_of, PackageKey};
+use crate::project::SpecType;
+use crate::utils::spec_format::{self, normalize_pypi_name};
+use crate::Project;
+use itertools::Itertools;
+use rattler_conda_types::Platform;
+use rattler_lock::{CondaLock, LockedDependencyKind};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter, Write};
+
+/// What a node of the dependency graph is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeKind {
+    /// The project itself, the root of the graph.
+    Project,
+    Conda,
+    Pypi,
+}
+
+impl Display for NodeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            NodeKind::Project => "project",
+            NodeKind::Conda => "conda",
+            NodeKind::Pypi => "pypi",
+        })
+    }
+}
+
+/// Why a package depends on another one. Only the dependencies of the project can be host or build
+/// dependencies, the locked packages only have run dependencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyType {
+    Run,
+    Host,
+    Build,
+}
+
+impl From<SpecType> for DependencyType {
+    fn from(spec_type: SpecType) -> Self {
+        match spec_type {
+            SpecType::Run => DependencyType::Run,
+            SpecType::Host => DependencyType::Host,
+            SpecType::Build => DependencyType::Build,
+        }
+    }
+}
+
+impl Display for DependencyType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DependencyType::Run => "run",
+            DependencyType::Host => "host",
+            DependencyType::Build => "build",
+        })
+    }
+}
+
+/// A package in the dependency graph.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GraphNode {
+    /// The identifier of the node, the kind and the name, e.g. `conda:python` or `pypi:requests`.
+    pub id: String,
+    pub name: String,
+    /// The locked version, `None` for the project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub kind: NodeKind,
+}
+
+/// A dependency of a package on another one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GraphEdge {
+    /// The identifier of the package that has the dependency.
+    pub from: String,
+    /// The identifier of the package that is depended on.
+    pub to: String,
+    #[serde(rename = "type")]
+    pub dependency_type: DependencyType,
+    /// The kind of the package that is depended on.
+    pub kind: NodeKind,
+}
+
+/// The resolved dependency graph of a platform of the project, from the dependencies in the
+/// manifest to the locked packages and their dependencies.
+#[derive(Debug, Clone, Serialize)]
+pub struct DependencyGraph {
+    pub platform: Platform,
+    /// The identifier of the project node.
+    pub root: String,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+fn node_id(kind: NodeKind, name: &str) -> String {
+    format!("{kind}:{name}")
+}
+
+fn key_id(key: &PackageKey) -> String {
+    match key {
+        PackageKey::Conda(name) => node_id(NodeKind::Conda, name),
+        PackageKey::Pypi(name) => node_id(NodeKind::Pypi, name),
+    }
+}
+
+impl DependencyGraph {
+    /// Builds the dependency graph of the project for `platform` from the lock file.
+    pub fn from_project(project: &Project, lock_file: &CondaLock, platform: Platform) -> Self {
+        let conda = [SpecType::Run, SpecType::Host, SpecType::Build]
+            .into_iter()
+            .flat_map(|spec_type| {
+                project
+                    .dependencies(platform, spec_type)
+                    .into_keys()
+                    .map(move |name| {
+                        (
+                            PackageKey::Conda(name.as_normalized().to_string()),
+                            DependencyType::from(spec_type),
+                        )
+                    })
+            });
+        let pypi = project
+            .pypi_dependencies(platform)
+            .into_iter()
+            .map(|(name, requirement)| requirement.as_pep508(&name))
+            .map(|requirement| {
+                (
+                    PackageKey::Pypi(normalize_pypi_name(&requirement.name)),
+                    DependencyType::Run,
+                )
+            });
+        Self::from_lock_file(project.name(), conda.chain(pypi), lock_file, platform)
+    }
+
+    /// Builds the dependency graph of the packages of `platform` in the lock file, the `roots` are
+    /// the dependencies of the project.
+    fn from_lock_file(
+        project_name: &str,
+        roots: impl IntoIterator<Item = (PackageKey, DependencyType)>,
+        lock_file: &CondaLock,
+        platform: Platform,
+    ) -> Self {
+        let root = node_id(NodeKind::Project, project_name);
+        let mut nodes = vec![GraphNode {
+            id: root.clone(),
+            name: project_name.to_string(),
+            version: None,
+            kind: NodeKind::Project,
+        }];
+        let packages = lock_file
+            .get_packages_by_platform(platform)
+            .map(|package| (PackageKey::of(package), package))
+            .collect::<HashMap<_, _>>();
+        nodes.extend(
+            packages
+                .iter()
+                .map(|(key, package)| GraphNode {
+                    id: key_id(key),
+                    name: package.name.clone(),
+                    version: Some(package.version.clone()),
+                    kind: match package.kind {
+                        LockedDependencyKind::Conda(_) => NodeKind::Conda,
+                        LockedDependencyKind::Pypi(_) => NodeKind::Pypi,
+                    },
+                })
+                .sorted_by(|a, b| a.id.cmp(&b.id)),
+        );
+
+        // Dependencies that are not locked for the platform are not part of the graph, e.g.
+        // packages that are only required on other platforms.
+        let edge = |from: String, to: &PackageKey, dependency_type| {
+            packages.contains_key(to).then(|| GraphEdge {
+                from,
+                to: key_id(to),
+                dependency_type,
+                kind: match to {
+                    PackageKey::Conda(_) => NodeKind::Conda,
+                    PackageKey::Pypi(_) => NodeKind::Pypi,
+                },
+            })
+        };
+        let mut seen = HashSet::new();
+        let mut edges = roots
+            .into_iter()
+            .filter(|(key, _)| seen.insert(key.clone()))
+            .filter_map(|(key, dependency_type)| edge(root.clone(), &key, dependency_type))
+            .collect_vec();
+        for (key, package) in packages.iter().sorted_by_key(|(key, _)| key_id(key)) {
+            edges.extend(
+                dependencies_of(package)
+                    .iter()
+                    .unique()
+                    .sorted_by_key(|dependency| key_id(dependency))
+                    .filter_map(|dependency| edge(key_id(key), dependency, DependencyType::Run)),
+            );
+        }
+
+        Self {
+            platform,
+            root,
+            nodes,
+            edges,
+        }
+    }
+
+    /// Renders the graph in the DOT language of Graphviz. PyPI packages are blue, and the host and
+    /// build dependencies of the project are dashed and dotted edges.
+    pub fn to_dot(&self) -> String {
+        let mut dot = format!(
+            "digraph {} {{\n    rankdir=LR;\n",
+            dot_string(&format!("{} ({})", self.nodes[0].name, self.platform))
+        );
+        for node in &self.nodes {
+            let label = match &node.version {
+                Some(version) => format!("{}\n{version}", node.name),
+                None => node.name.clone(),
+            };
+            let attributes = match node.kind {
+                NodeKind::Project => ", shape=box, style=bold",
+                NodeKind::Conda => "",
+                NodeKind::Pypi => ", color=blue",
+            };
+            writeln!(
+                dot,
+                "    {} [label={}{attributes}];",
+                dot_string(&node.id),
+                dot_string(&label)
+            )
+            .expect("writing to a string cannot fail");
+        }
+        for edge in &self.edges {
+            let attributes = match edge.dependency_type {
+                DependencyType::Run => "",
+                DependencyType::Host => " [style=dashed, label=host]",
+                DependencyType::Build => " [style=dotted, label=build]",
+            };
+            writeln!(
+                dot,
+                "    {} -> {}{attributes};",
+                dot_string(&edge.from),
+                dot_string(&edge.to)
+            )
+            .expect("writing to a string cannot fail");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as a tree below the project. The dependencies of a package that was
+    /// already shown are not repeated, the package is marked with `(*)` instead.
+    pub fn to_tree(&self) -> String {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| (node.id.as_str(), node))
+            .collect::<HashMap<_, _>>();
+        let children = self
+            .edges
+            .iter()
+            .into_group_map_by(|edge| edge.from.as_str());
+
+        let mut tree = format!("{}\n", self.nodes[0].name);
+        write_subtree(
+            &self.root,
+            "",
+            &nodes,
+            &children,
+            &mut HashSet::new(),
+            &mut tree,
+        );
+        tree
+    }
+}
+
+/// Writes the dependencies of the node `id` to the tree, every line starts with `prefix`.
+fn write_subtree<'a>(
+    id: &str,
+    prefix: &str,
+    nodes: &HashMap<&str, &GraphNode>,
+    children: &HashMap<&str, Vec<&'a GraphEdge>>,
+    shown: &mut HashSet<&'a str>,
+    tree: &mut String,
+) {
+    let Some(edges) = children.get(id) else {
+        return;
+    };
+    for (index, edge) in edges.iter().enumerate() {
+        let is_last = index + 1 == edges.len();
+        let node = nodes[edge.to.as_str()];
+        tree.push_str(prefix);
+        tree.push_str(if is_last { "└── " } else { "├── " });
+        tree.push_str(&spec_format::locked_version(
+            &node.name,
+            node.version.as_deref(),
+            &node.kind.to_string(),
+        ));
+        if edge.dependency_type != DependencyType::Run {
+            write!(tree, " ({})", edge.dependency_type).expect("writing to a string cannot fail");
+        }
+
+        let has_children = children.contains_key(edge.to.as_str());
+        if !shown.insert(edge.to.as_str()) && has_children {
+            tree.push_str(" (*)\n");
+            continue;
+        }
+        tree.push('\n');
+        let prefix = format!("{prefix}{}", if is_last { "    " } else { "│   " });
+        write_subtree(&edge.to, &prefix, nodes, children, shown, tree);
+    }
+}
+
+/// Quotes a string for the DOT language.
+fn dot_string(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn sdl_graph() -> DependencyGraph {
+        let lock_file = crate::lock_file::read_lock_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/cpp-sdl/pixi.lock"),
+        )
+        .unwrap();
+        DependencyGraph::from_lock_file(
+            "sdl_example",
+            [
+                (PackageKey::Conda(String::from("sdl2")), DependencyType::Run),
+                (
+                    PackageKey::Conda(String::from("cmake")),
+                    DependencyType::Build,
+                ),
+            ],
+            &lock_file,
+            Platform::Linux64,
+        )
+    }
+
+    #[test]
+    fn test_dependency_graph() {
+        let graph = sdl_graph();
+        assert_eq!(graph.root, "project:sdl_example");
+        assert!(graph
+            .nodes
+            .iter()
+            .any(|node| node.id == "conda:sdl2" && node.kind == NodeKind::Conda));
+        assert_eq!(
+            graph
+                .edges
+                .iter()
+                .filter(|edge| edge.from == graph.root)
+                .map(|edge| (edge.to.as_str(), edge.dependency_type))
+                .collect_vec(),
+            vec![
+                ("conda:sdl2", DependencyType::Run),
+                ("conda:cmake", DependencyType::Build)
+            ]
+        );
+        // Every edge points at a node of the graph.
+        let ids = graph
+            .nodes
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect::<HashSet<_>>();
+        assert!(graph
+            .edges
+            .iter()
+            .all(|edge| ids.contains(edge.to.as_str())));
+    }
+
+    #[test]
+    fn test_render_dependency_graph() {
+        let graph = sdl_graph();
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph \"sdl_example (linux-64)\" {"));
+        assert!(
+            dot.contains("\"project:sdl_example\" -> \"conda:cmake\" [style=dotted, label=build];")
+        );
+
+        let tree = graph.to_tree();
+        let mut lines = tree.lines();
+        assert_eq!(lines.next(), Some("sdl_example"));
+        assert!(lines.next().unwrap().starts_with("├── sdl2 2.26.5"));
+        assert!(tree.contains("\n└── cmake 3.26.4 (build)\n"));
+    }
+
+    #[test]
+    fn test_dot_string() {
+        assert_eq!(dot_string("a \"b\"\nc"), "\"a \\\"b\\\"\\nc\"");
+    }
+}
@@ -0,0 +1,207 @@
+//! Solves the dependencies of a project with the lowest versions its direct dependencies allow, to
+//! test that the lower bounds of a library are correct.
+//!
+//! The solver always prefers the highest versions, so the lowest versions are found by pinning one
+//! direct dependency after the other to the lowest version of it that can still be solved together
+//! with the pins before it. The dependencies of the direct dependencies are solved as usual.
+
+use super::{
+    load_sparse_repo_data_async, resolve_conda_dependencies, solve_conda, solve_pypi,
+    ResolveReport, SolveStrategy,
+};
+use crate::{progress, Project};
+use indexmap::IndexMap;
+use indicatif::ProgressBar;
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use rattler_conda_types::{
+    GenericVirtualPackage, MatchSpec, NamelessMatchSpec, PackageName, Platform, RepoDataRecord,
+    Version,
+};
+use rattler_lock::{builder::LockFileBuilder, CondaLock};
+use rattler_repodata_gateway::sparse::SparseRepoData;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The maximum number of versions of a dependency that are tried, after that the version of the
+/// dependency is left to the solver.
+const MAX_ATTEMPTS: usize = 25;
+
+/// Returns the versions of `name` in `available_packages` that match `spec`, lowest first.
+fn matching_versions(
+    name: &PackageName,
+    spec: &NamelessMatchSpec,
+    available_packages: &[Vec<RepoDataRecord>],
+) -> Vec<Version> {
+    let spec = MatchSpec::from_nameless(spec.clone(), Some(name.clone()));
+    available_packages
+        .iter()
+        .flatten()
+        .filter(|record| spec.matches(&record.package_record))
+        .map(|record| Version::from(record.package_record.version.clone()))
+        .sorted()
+        .dedup()
+        .collect()
+}
+
+/// Returns a spec that selects exactly `version` of the package.
+fn exact_spec(name: &PackageName, version: &Version) -> MatchSpec {
+    MatchSpec::from_str(&format!("{} =={version}", name.as_normalized()))
+        .expect("a name and a version form a valid spec")
+}
+
+/// Returns a spec for every direct conda dependency of the project on `platform` that pins it to
+/// the lowest version that can be solved together with the other dependencies.
+pub async fn lowest_direct_specs(
+    project: &Project,
+    sparse_repo_data: Arc<[SparseRepoData]>,
+    platform: Platform,
+) -> miette::Result<Vec<MatchSpec>> {
+    let dependencies = project.all_dependencies(platform);
+    let mut available_packages = load_sparse_repo_data_async(
+        platform,
+        dependencies.keys().cloned().collect(),
+        vec![sparse_repo_data],
+    )
+    .await?;
+    project.retain_allowed_prereleases(&mut available_packages);
+    let virtual_packages = project.virtual_packages(platform)?;
+
+    let pb = progress::global_multi_progress().add(ProgressBar::new(dependencies.len() as u64));
+    pb.set_style(progress::default_progress_style());
+    pb.set_prefix(format!("lowest versions {platform}"));
+    pb.enable_steady_tick(Duration::from_millis(100));
+
+    // Every attempt is a solve, which is CPU intensive, so they run in a blocking task.
+    let pins_pb = pb.clone();
+    let pins = tokio::task::spawn_blocking(move || {
+        lowest_pins(
+            &dependencies,
+            &available_packages,
+            virtual_packages,
+            platform,
+            &pins_pb,
+        )
+    })
+    .await
+    .into_diagnostic()?;
+    pb.finish_and_clear();
+    Ok(pins)
+}
+
+/// Pins the `dependencies` one after the other to the lowest of their versions that can be solved
+/// together with the pins before it.
+fn lowest_pins(
+    dependencies: &IndexMap<PackageName, NamelessMatchSpec>,
+    available_packages: &[Vec<RepoDataRecord>],
+    virtual_packages: Vec<GenericVirtualPackage>,
+    platform: Platform,
+    pb: &ProgressBar,
+) -> Vec<MatchSpec> {
+    let specs = dependencies
+        .iter()
+        .map(|(name, spec)| MatchSpec::from_nameless(spec.clone(), Some(name.clone())))
+        .collect_vec();
+    let mut pins: Vec<MatchSpec> = Vec::new();
+    for (name, spec) in dependencies {
+        pb.set_message(name.as_source().to_string());
+        let pin = matching_versions(name, spec, available_packages)
+            .iter()
+            .take(MAX_ATTEMPTS)
+            .map(|version| exact_spec(name, version))
+            .find(|pin| {
+                let specs = specs
+                    .iter()
+                    .chain(pins.iter())
+                    .chain([pin])
+                    .cloned()
+                    .collect();
+                resolve_conda_dependencies(
+                    specs,
+                    virtual_packages.clone(),
+                    Vec::new(),
+                    available_packages,
+                )
+                .is_ok()
+            });
+        match pin {
+            Some(pin) => pins.push(pin),
+            None => tracing::warn!(
+                "none of the {MAX_ATTEMPTS} lowest versions of {} can be solved on {platform}, the solver chooses its version",
+                name.as_source()
+            ),
+        }
+        pb.inc(1);
+    }
+    pins
+}
+
+/// Solves the dependencies of the project for `platform` with the direct conda dependencies at the
+/// lowest versions that can be solved. Returns the lock file, which is not written to disk, and the
+/// specs of the lowest versions.
+pub async fn solve_lowest(
+    project: &Project,
+    platform: Platform,
+) -> miette::Result<(CondaLock, Vec<MatchSpec>)> {
+    let sparse_repo_data: Arc<[SparseRepoData]> = project.fetch_sparse_repodata().await?.into();
+    let pins = lowest_direct_specs(project, sparse_repo_data.clone(), platform).await?;
+
+    let empty_lock_file = LockFileBuilder::default().build().into_diagnostic()?;
+    let mut lock_file = solve_conda(
+        project,
+        &empty_lock_file,
+        Some(sparse_repo_data),
+        &[platform],
+        &pins,
+    )
+    .await?;
+    let mut report = ResolveReport::read(&lock_file).unwrap_or_default();
+    for inputs in report.platforms.values_mut() {
+        inputs.strategy = SolveStrategy::LowestDirect;
+    }
+    report.write(&mut lock_file);
+    if project.has_pypi_dependencies() {
+        tracing::warn!("the pypi dependencies are resolved to their highest versions");
+        lock_file = solve_pypi(project, lock_file, &[platform]).await?;
+    }
+    Ok((lock_file, pins))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{repodata_record, CONDA_FORGE};
+
+    fn record(name: &str, version: &str) -> RepoDataRecord {
+        repodata_record(name, version, CONDA_FORGE)
+    }
+
+    #[test]
+    fn test_matching_versions() {
+        let available = vec![
+            vec![record("numpy", "1.26.0"), record("numpy", "1.24.4")],
+            vec![
+                record("numpy", "1.22.0"),
+                record("numpy", "1.24.4"),
+                record("scipy", "1.0.0"),
+            ],
+        ];
+        let spec = NamelessMatchSpec::from_str(">=1.24").unwrap();
+        assert_eq!(
+            matching_versions(&PackageName::new_unchecked("numpy"), &spec, &available),
+            vec![
+                Version::from_str("1.24.4").unwrap(),
+                Version::from_str("1.26.0").unwrap()
+            ]
+        );
+        assert_eq!(
+            exact_spec(
+                &PackageName::new_unchecked("numpy"),
+                &Version::from_str("1.24.4").unwrap()
+            )
+            .to_string(),
+            "numpy ==1.24.4"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use super::ResolveReport;
+use crate::Project;
+use itertools::Itertools;
+use miette::Diagnostic;
+use rattler_conda_types::Platform;
+use rattler_lock::{CondaLock, LockedDependency, LockedDependencyKind};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// An error that occurs when merging lock files.
+#[derive(Debug, Error, Diagnostic)]
+pub enum MergeLockFileError {
+    #[error("'{}' was solved with different channels than the project", .0.display())]
+    #[diagnostic(help("solve the lock file again with the current channels of the project"))]
+    ChannelMismatch(PathBuf),
+
+    #[error("'{}' contains the platform '{platform}' which is not a platform of the project", .path.display())]
+    UnknownPlatform { path: PathBuf, platform: Platform },
+
+    #[error("'{}' and '{}' contain different packages for '{platform}'", .first.display(), .second.display())]
+    #[diagnostic(help(
+        "the platform was solved on multiple machines with different results, merge only one of them"
+    ))]
+    ConflictingPlatform {
+        platform: Platform,
+        first: PathBuf,
+        second: PathBuf,
+    },
+}
+
+/// Returns a sorted representation of the packages of a platform that can be compared between
+/// lock files.
+fn package_urls<'a>(packages: impl IntoIterator<Item = &'a LockedDependency>) -> Vec<String> {
+    packages
+        .into_iter()
+        .map(|package| match &package.kind {
+            LockedDependencyKind::Conda(conda) => conda.url.to_string(),
+            LockedDependencyKind::Pypi(pypi) => pypi.url.to_string(),
+        })
+        .sorted()
+        .collect()
+}
+
+/// Merges lock files that each contain a subset of the platforms of the project (e.g. created with
+/// `pixi lock --platform` on different machines) into `base`.
+///
+/// The packages of a platform contained in one of the `partial` lock files replace the packages of
+/// that platform in `base`, the other platforms are left untouched. An error is returned if a
+/// partial lock file was solved with other channels than those of the project, contains a
+/// platform that is not part of the project, or if two partial lock files contain different
+/// packages for the same platform.
+pub fn merge_lock_files(
+    project: &Project,
+    base: CondaLock,
+    partial: Vec<(PathBuf, CondaLock)>,
+) -> Result<CondaLock, MergeLockFileError> {
+    let channels = project
+        .channels()
+        .iter()
+        .map(|channel| rattler_lock::Channel::from(channel.base_url().to_string()))
+        .collect_vec();
+
+    // The inputs of the merged platforms are taken from the partial lock files as well.
+    let mut report = ResolveReport::read(&base).unwrap_or_default();
+
+    // Determine which lock file provides the packages of every platform.
+    let mut merged: HashMap<Platform, (PathBuf, Vec<LockedDependency>)> = HashMap::new();
+    for (path, lock_file) in partial {
+        if lock_file.metadata.channels.iter().ne(channels.iter()) {
+            return Err(MergeLockFileError::ChannelMismatch(path));
+        }
+        let partial_report = ResolveReport::read(&lock_file).unwrap_or_default();
+        for platform in lock_file.metadata.platforms.iter().copied() {
+            if !project.platforms().contains(&platform) {
+                return Err(MergeLockFileError::UnknownPlatform { path, platform });
+            }
+
+            let packages = lock_file
+                .package
+                .iter()
+                .filter(|package| package.platform == platform)
+                .cloned()
+                .collect_vec();
+            if let Some((first, existing)) = merged.get(&platform) {
+                if package_urls(existing) != package_urls(&packages) {
+                    return Err(MergeLockFileError::ConflictingPlatform {
+                        platform,
+                        first: first.clone(),
+                        second: path,
+                    });
+                }
+                continue;
+            }
+            merged.insert(platform, (path.clone(), packages));
+            match partial_report.platforms.get(platform.as_str()) {
+                Some(inputs) => report
+                    .platforms
+                    .insert(platform.to_string(), inputs.clone()),
+                None => report.platforms.remove(platform.as_str()),
+            };
+        }
+    }
+    report.platforms.retain(|platform, _| {
+        project
+            .platforms()
+            .iter()
+            .any(|project_platform| project_platform.as_str() == platform)
+    });
+
+    // Keep the packages of the platforms that were not merged.
+    let mut metadata = base.metadata;
+    metadata.channels = channels;
+    metadata.platforms = project.platforms().to_vec();
+    let package = base
+        .package
+        .into_iter()
+        .filter(|package| {
+            metadata.platforms.contains(&package.platform)
+                && !merged.contains_key(&package.platform)
+        })
+        .chain(
+            merged
+                .into_iter()
+                .sorted_by_key(|(platform, _)| platform.as_str())
+                .flat_map(|(_, (_, packages))| packages),
+        )
+        .collect();
+
+    let mut lock_file = CondaLock { metadata, package };
+    report.write(&mut lock_file);
+    Ok(lock_file)
+}
@@ -1,9 +1,20 @@
+mod conflict;
+mod constrains;
+mod format;
+mod graph;
+mod lowest;
+mod merge;
 mod package_identifier;
+mod provenance;
+mod prune;
 mod pypi;
 mod pypi_name_mapping;
+mod report;
 mod satisfiability;
+mod signing;
 
-use crate::{progress, Project};
+use crate::warnings::{self, WarningCode};
+use crate::{banned, build_fallback, concurrency, end_of_life, progress, Project};
 use futures::TryStreamExt;
 use futures::{stream, StreamExt};
 use indicatif::ProgressBar;
@@ -12,6 +23,7 @@ use miette::{Context, IntoDiagnostic};
 use rattler_conda_types::{
     GenericVirtualPackage, MatchSpec, PackageName, Platform, RepoDataRecord,
 };
+use rattler_digest::{compute_bytes_digest, Sha256};
 use rattler_lock::{
     builder::{
         CondaLockedDependencyBuilder, LockFileBuilder, LockedPackagesBuilder,
@@ -20,17 +32,40 @@ use rattler_lock::{
     CondaLock, LockedDependencyKind, PackageHashes,
 };
 use rattler_repodata_gateway::sparse::SparseRepoData;
-use rattler_solve::{resolvo, SolverImpl};
-use std::{sync::Arc, time::Duration};
-
+use rattler_solve::{resolvo, SolveError, SolverImpl};
+use std::{collections::HashSet, path::Path, sync::Arc, time::Duration};
+
+pub use constrains::{check_constraints, find_constraint_violations, ConstraintViolation};
+pub use format::{read_lock_file, LockFileFormat};
+pub use graph::{DependencyGraph, DependencyType, GraphEdge, GraphNode, NodeKind};
+pub use lowest::{lowest_direct_specs, solve_lowest};
+pub use merge::{merge_lock_files, MergeLockFileError};
+pub use provenance::{
+    check_locked_provenance, conda_channel_url, download_locked_artifacts, verify_artifacts,
+    ProvenanceFinding, ProvenanceIssue,
+};
+use prune::prune_lock_file_with_roots;
+pub use prune::{prune_lock_file, report_pruned_packages, PrunedPackage};
+pub use pypi_name_mapping::conda_pypi_name_mapping;
+pub use report::{PlatformResolveInputs, ResolveReport, SolveStrategy};
 pub use satisfiability::lock_file_satisfies_project;
+pub use signing::{
+    default_secret_key_path, read_signed_lock_file, sign_lock_file, signature_path,
+    SIGNING_KEY_PASSWORD_ENV,
+};
 
 /// Loads the lockfile for the specified project or returns a dummy one if none could be found.
+///
+/// When a project switches to `split-lock-files`, the lock file in the root of the project is used
+/// until the split lock file is written, so the locked versions are kept.
 pub async fn load_lock_file(project: &Project) -> miette::Result<CondaLock> {
     let lock_file_path = project.lock_file_path();
+    let root_lock_file_path = project.root_lock_file_path();
     tokio::task::spawn_blocking(move || {
         if lock_file_path.is_file() {
-            CondaLock::from_path(&lock_file_path).into_diagnostic()
+            read_lock_file(&lock_file_path)
+        } else if root_lock_file_path.is_file() {
+            read_lock_file(&root_lock_file_path)
         } else {
             LockFileBuilder::default().build().into_diagnostic()
         }
@@ -39,6 +74,63 @@ pub async fn load_lock_file(project: &Project) -> miette::Result<CondaLock> {
     .unwrap_or_else(|e| Err(e).into_diagnostic())
 }
 
+/// Returns a key to cache the environment of the project for `platform` in CI, e.g.
+/// `pixi-default-linux-64-0123456789abcdef`. The key changes whenever the lock file changes.
+pub fn cache_key(project: &Project, platform: Platform) -> miette::Result<String> {
+    let lock_file_path = [project.lock_file_path(), project.root_lock_file_path()]
+        .into_iter()
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            miette::miette!(
+                help = "run `pixi install` first",
+                "the project has no lock file"
+            )
+        })?;
+    let contents = std::fs::read(&lock_file_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            format!(
+                "failed to read the lock file '{}'",
+                lock_file_path.display()
+            )
+        })?;
+    let hash = format!("{:x}", compute_bytes_digest::<Sha256>(&contents));
+    Ok(format!(
+        "pixi-{}-{}-{}",
+        project.environment_name().unwrap_or("default"),
+        platform,
+        &hash[..16]
+    ))
+}
+
+/// Writes a lock file of the project to `path` in the format of the project.
+pub fn write_lock_file(
+    project: &Project,
+    lock_file: &CondaLock,
+    path: &Path,
+) -> miette::Result<()> {
+    format::write_lock_file(lock_file, path, project.lock_file_format())
+}
+
+/// Returns a copy of the lock-file with the urls of all locked packages rewritten according to the
+/// `url-rewrites` of the configuration of the project, and the packages that were built from
+/// source resolved to the local channel of the project. This is used at install time so the
+/// lock-file itself never contains the rewritten urls.
+pub fn rewrite_lock_file_urls(lock_file: &CondaLock, project: &Project) -> CondaLock {
+    let mut lock_file = build_fallback::resolve_lock_file_urls(project, lock_file);
+    let config = project.config();
+    if config.url_rewrites.is_empty() {
+        return lock_file;
+    }
+    for package in lock_file.package.iter_mut() {
+        match &mut package.kind {
+            LockedDependencyKind::Conda(conda) => conda.url = config.rewrite_url(&conda.url),
+            LockedDependencyKind::Pypi(pypi) => pypi.url = config.rewrite_url(&pypi.url),
+        }
+    }
+    lock_file
+}
+
 fn main_progress_bar(num_bars: u64, message: &'static str) -> ProgressBar {
     let multi_progress = progress::global_multi_progress();
     let top_level_progress = multi_progress.add(ProgressBar::new(num_bars));
@@ -73,15 +165,138 @@ pub async fn update_lock_file_conda(
     existing_lock_file: CondaLock,
     repodata: Option<Vec<SparseRepoData>>,
 ) -> miette::Result<CondaLock> {
-    let platforms = project.platforms();
+    let (conda_lock, member_locks) = solve_conda_with_members(
+        project,
+        &existing_lock_file,
+        repodata.map(Into::into),
+        project.platforms(),
+        &[],
+    )
+    .await?;
 
-    // Get the repodata for the project
-    let sparse_repo_data: Arc<[_]> = if let Some(sparse_repo_data) = repodata {
-        sparse_repo_data
-    } else {
-        project.fetch_sparse_repodata().await?
+    // The other environments of the solve group are locked at the same versions.
+    write_member_lock_files(&member_locks)?;
+
+    // Write the conda lock to disk
+    write_lock_file(project, &conda_lock, &project.lock_file_path())?;
+
+    end_of_life::warn_end_of_life_packages(project, &conda_lock).await;
+
+    Ok(conda_lock)
+}
+
+/// Solves the conda dependencies of the project for the given platforms. The returned lock file
+/// only contains these platforms, the packages in `existing_lock_file` are favored by the solver.
+/// The `additional_specs` further constrain the solution next to the dependencies of the project.
+///
+/// The environments of a solve group are solved together, the returned lock file only contains
+/// the packages that the selected environment needs. Use [`solve_conda_with_members`] to also
+/// update the lock files of the other environments of the group.
+pub async fn solve_conda(
+    project: &Project,
+    existing_lock_file: &CondaLock,
+    repodata: Option<Arc<[SparseRepoData]>>,
+    platforms: &[Platform],
+    additional_specs: &[MatchSpec],
+) -> miette::Result<CondaLock> {
+    let (lock_file, _) = solve_conda_with_members(
+        project,
+        existing_lock_file,
+        repodata,
+        platforms,
+        additional_specs,
+    )
+    .await?;
+    Ok(lock_file)
+}
+
+/// Solves like [`solve_conda`] and also returns the lock files of the other environments of the
+/// solve group of the project. These contain the existing lock file of the environment with the
+/// solved platforms replaced, write them with [`write_member_lock_files`].
+pub async fn solve_conda_with_members(
+    project: &Project,
+    existing_lock_file: &CondaLock,
+    repodata: Option<Arc<[SparseRepoData]>>,
+    platforms: &[Platform],
+    additional_specs: &[MatchSpec],
+) -> miette::Result<(CondaLock, Vec<(Project, CondaLock)>)> {
+    let members = project.solve_group_members()?;
+    let group_lock = solve_conda_group(
+        project,
+        &members,
+        existing_lock_file,
+        repodata,
+        platforms,
+        additional_specs,
+    )
+    .await?;
+    if members.is_empty() {
+        return Ok((group_lock, Vec::new()));
     }
-    .into();
+
+    let mut member_locks = Vec::with_capacity(members.len());
+    for member in members {
+        let member_lock = member_lock_file(&member, group_lock.clone()).await?;
+        member_locks.push((member, member_lock));
+    }
+
+    // The packages of the additional specs are kept, even when the dependencies of the project
+    // don't require them, e.g. the conda packages that replace pypi dependencies.
+    let roots = additional_specs
+        .iter()
+        .filter_map(|spec| spec.name.clone())
+        .collect_vec();
+    let (lock_file, _) = prune_lock_file_with_roots(project, group_lock, &roots);
+    Ok((lock_file, member_locks))
+}
+
+/// Returns the lock file of `member`, an environment of a solve group, after the group was solved
+/// for the platforms of `group_lock`. Only the packages the member needs are kept and the other
+/// platforms of its existing lock file are left untouched. The pypi packages of the member are
+/// kept until it is solved again.
+async fn member_lock_file(member: &Project, group_lock: CondaLock) -> miette::Result<CondaLock> {
+    let existing_member_lock = load_lock_file(member).await?;
+    let (mut member_lock, _) = prune_lock_file(member, group_lock);
+    let solved_platforms = member_lock.metadata.platforms.clone();
+    member_lock.package.extend(
+        existing_member_lock
+            .package
+            .iter()
+            .filter(|package| matches!(package.kind, LockedDependencyKind::Pypi(_)))
+            .filter(|package| solved_platforms.contains(&package.platform))
+            .cloned(),
+    );
+    Ok(merge_lock_files(
+        member,
+        existing_member_lock,
+        vec![(member.lock_file_path(), member_lock)],
+    )?)
+}
+
+/// Writes the lock files of the other environments of a solve group, as returned by
+/// [`solve_conda_with_members`].
+pub fn write_member_lock_files(member_locks: &[(Project, CondaLock)]) -> miette::Result<()> {
+    for (member, member_lock) in member_locks {
+        write_lock_file(member, member_lock, &member.lock_file_path())?;
+    }
+    Ok(())
+}
+
+/// Solves the union of the conda dependencies of the project and the other environments of its
+/// solve group, `members`, so the dependencies they share are resolved to the same versions.
+async fn solve_conda_group(
+    project: &Project,
+    members: &[Project],
+    existing_lock_file: &CondaLock,
+    repodata: Option<Arc<[SparseRepoData]>>,
+    platforms: &[Platform],
+    additional_specs: &[MatchSpec],
+) -> miette::Result<CondaLock> {
+    // Get the repodata for the project
+    let sparse_repo_data: Arc<[_]> = match repodata {
+        Some(sparse_repo_data) => sparse_repo_data,
+        None => project.fetch_sparse_repodata().await?.into(),
+    };
 
     // Construct a progress bar
     let _top_level_progress =
@@ -107,14 +322,15 @@ pub async fn update_lock_file_conda(
                     .unwrap(),
                 );
 
-                let existing_lock_file = &existing_lock_file;
                 let sparse_repo_data = sparse_repo_data.clone();
                 async move {
                     let result = resolve_platform(
                         project,
+                        members,
                         existing_lock_file,
                         sparse_repo_data.clone(),
                         *platform,
+                        additional_specs,
                         pb.clone(),
                     )
                     .await?;
@@ -132,7 +348,7 @@ pub async fn update_lock_file_conda(
                     Ok(result)
                 }
             })
-            .buffer_unordered(2)
+            .buffer_unordered(concurrency::limits().solves)
             .try_collect()
             .await;
 
@@ -145,21 +361,34 @@ pub async fn update_lock_file_conda(
     for locked_packages in result? {
         builder = builder.add_locked_packages(locked_packages);
     }
-    let conda_lock = builder.build().into_diagnostic()?;
+    let mut lock_file = builder.build().into_diagnostic()?;
+    banned::check_banned_packages(project, &lock_file, platforms)?;
+
+    // Record what influenced the solve, see `pixi lock --explain`.
+    ResolveReport::capture(project, platforms, SolveStrategy::Highest)?.write(&mut lock_file);
+    Ok(lock_file)
+}
+
+/// Updates the lock file for the pypi dependencies of the specified project.
+pub async fn update_lock_file_for_pypi(
+    project: &Project,
+    lock_for_conda: CondaLock,
+) -> miette::Result<CondaLock> {
+    let latest_lock = solve_pypi(project, lock_for_conda, project.platforms()).await?;
 
     // Write the conda lock to disk
-    conda_lock
-        .to_path(&project.lock_file_path())
-        .into_diagnostic()?;
+    write_lock_file(project, &latest_lock, &project.lock_file_path())?;
 
-    Ok(conda_lock)
+    Ok(latest_lock)
 }
 
-pub async fn update_lock_file_for_pypi(
+/// Solves the pypi dependencies of the project for the given platforms on top of the conda
+/// packages in `lock_for_conda`. Previously locked pypi packages are replaced.
+pub async fn solve_pypi(
     project: &Project,
     lock_for_conda: CondaLock,
+    platforms: &[Platform],
 ) -> miette::Result<CondaLock> {
-    let platforms = project.platforms();
     let _top_level_progress =
         main_progress_bar(platforms.len() as u64, "resolving pypi dependencies");
     let solve_bars = platform_solve_bars(platforms);
@@ -180,11 +409,13 @@ pub async fn update_lock_file_for_pypi(
                     .unwrap(),
                 );
 
+                let lock_for_conda = &lock_for_conda;
                 async move {
                     let locked_packages = LockedPackagesBuilder::new(*platform);
                     let result = resolve_pypi(
                         project,
-                        &records.into_diagnostic()?,
+                        lock_for_conda,
+                        records.into_diagnostic()?,
                         locked_packages,
                         *platform,
                         &pb,
@@ -219,45 +450,97 @@ pub async fn update_lock_file_for_pypi(
         .iter()
         .map(|channel| rattler_lock::Channel::from(channel.base_url().to_string()));
     let mut builder = LockFileBuilder::new(channels, platforms.iter().cloned(), vec![]);
-    for locked_packages in result? {
+    let mut conda_overrides = Vec::new();
+    for (locked_packages, conda_lock) in result? {
         builder = builder.add_locked_packages(locked_packages);
+        conda_overrides.extend(conda_lock);
     }
     let conda_lock_pypi_only = builder.build().into_diagnostic()?;
 
+    // The conda packages of the platforms that were solved again with the conda packages of the
+    // `[pypi-to-conda]` table replace the previously locked conda packages.
+    let overridden_platforms = conda_overrides
+        .iter()
+        .flat_map(|lock| lock.metadata.platforms.iter().copied())
+        .collect_vec();
+    let conda_packages = lock_for_conda
+        .package
+        .into_iter()
+        .filter(|p| !overridden_platforms.contains(&p.platform))
+        .chain(conda_overrides.into_iter().flat_map(|lock| lock.package))
+        .filter(|p| matches!(p.kind, LockedDependencyKind::Conda(_)));
+
     // TODO: think of a better way to do this
     // Seeing as we are not using the content-hash anyways this seems to be fine
-    let latest_lock = CondaLock {
+    let lock_file = CondaLock {
         metadata: lock_for_conda.metadata,
         package: conda_lock_pypi_only
             .package
             .into_iter()
-            .chain(
-                lock_for_conda
-                    .package
-                    .into_iter()
-                    .filter(|p| matches!(p.kind, LockedDependencyKind::Conda(_))),
-            )
+            .chain(conda_packages)
             .collect(),
     };
+    banned::check_banned_packages(project, &lock_file, platforms)?;
+    Ok(lock_file)
+}
 
-    // Write the conda lock to disk
-    latest_lock
-        .to_path(&project.lock_file_path())
-        .into_diagnostic()?;
-
-    Ok(latest_lock)
+/// Returns the conda overrides that are not in `existing` yet. Every override is added once, so
+/// resolving the pypi dependencies again ends when no new pypi package of the `[pypi-to-conda]`
+/// table is resolved.
+fn new_conda_overrides(overrides: Vec<MatchSpec>, existing: &[MatchSpec]) -> Vec<MatchSpec> {
+    overrides
+        .into_iter()
+        .filter(|spec| !existing.iter().any(|existing| existing.name == spec.name))
+        .collect()
 }
 
+/// Resolves the pypi dependencies of the project for a single platform. If the resolution contains
+/// packages of the `[pypi-to-conda]` table, their conda packages are added to the conda
+/// environment of the platform, the returned conda lock contains the conda packages of this
+/// platform that are solved again.
+#[tracing::instrument(name = "solve_pypi", skip_all, fields(platform = %platform))]
 async fn resolve_pypi(
     project: &Project,
-    records: &[RepoDataRecord],
+    lock_for_conda: &CondaLock,
+    mut records: Vec<RepoDataRecord>,
     mut locked_packages: LockedPackagesBuilder,
     platform: Platform,
     pb: &ProgressBar,
-) -> miette::Result<LockedPackagesBuilder> {
-    // Solve python packages
-    pb.set_message("resolving python");
-    let python_artifacts = pypi::resolve_dependencies(project, platform, records).await?;
+) -> miette::Result<(LockedPackagesBuilder, Option<CondaLock>)> {
+    let pypi_dependencies = project.pypi_dependencies(platform);
+    let mut conda_overrides: Vec<MatchSpec> = Vec::new();
+    let mut conda_lock = None;
+    let python_artifacts = loop {
+        // Solve python packages
+        pb.set_message("resolving python");
+        let python_artifacts = pypi::resolve_dependencies(project, platform, &records).await?;
+
+        // Solve the conda packages again if pypi packages should be replaced by conda packages.
+        let new_overrides = new_conda_overrides(
+            pypi::conda_overrides(
+                project.pypi_to_conda(),
+                &pypi_dependencies,
+                python_artifacts.iter().map(|artifact| &artifact.name),
+            )?,
+            &conda_overrides,
+        );
+        if new_overrides.is_empty() {
+            break python_artifacts;
+        }
+        tracing::info!(
+            "replacing pypi packages with the conda packages: {}",
+            new_overrides.iter().format(", ")
+        );
+        conda_overrides.extend(new_overrides);
+
+        pb.set_message("resolving conda");
+        let lock =
+            solve_conda(project, lock_for_conda, None, &[platform], &conda_overrides).await?;
+        records = lock
+            .get_conda_packages_by_platform(platform)
+            .into_diagnostic()?;
+        conda_lock = Some(lock);
+    };
 
     // Clear message
     pb.set_message("");
@@ -294,26 +577,55 @@ async fn resolve_pypi(
             build: None,
         };
 
+        if locked_package.hash.is_none() {
+            warnings::emit(
+                WarningCode::MissingProvenance,
+                format!(
+                    "the index has no sha256 for {} {}, its artifact can't be verified",
+                    locked_package.name, locked_package.version
+                ),
+            );
+        }
         locked_packages.add_locked_package(locked_package)
     }
-    Ok(locked_packages)
+    Ok((locked_packages, conda_lock))
 }
 
+#[tracing::instrument(name = "solve", skip_all, fields(platform = %platform))]
 async fn resolve_platform(
     project: &Project,
+    members: &[Project],
     existing_lock_file: &CondaLock,
     sparse_repo_data: Arc<[SparseRepoData]>,
     platform: Platform,
+    additional_specs: &[MatchSpec],
     pb: ProgressBar,
 ) -> miette::Result<LockedPackagesBuilder> {
-    let dependencies = project.all_dependencies(platform);
+    // The dependencies of the other environments of the solve group are solved together with
+    // those of the project, the specs of a package that several environments depend on are all
+    // used.
+    let dependencies = project
+        .all_dependencies(platform)
+        .into_iter()
+        .chain(
+            members
+                .iter()
+                .flat_map(|member| member.all_dependencies(platform)),
+        )
+        .collect_vec();
     let match_specs = dependencies
         .iter()
         .map(|(name, constraint)| MatchSpec::from_nameless(constraint.clone(), Some(name.clone())))
+        .chain(additional_specs.iter().cloned())
         .collect_vec();
 
     // Extract the package names from the dependencies
-    let package_names = dependencies.keys().cloned().collect_vec();
+    let package_names = dependencies
+        .iter()
+        .map(|(name, _)| name.clone())
+        .chain(additional_specs.iter().filter_map(|spec| spec.name.clone()))
+        .unique()
+        .collect_vec();
 
     // Get the virtual packages for this platform
     let virtual_packages = project.virtual_packages(platform)?;
@@ -327,54 +639,119 @@ async fn resolve_platform(
 
     // Get the repodata for the current platform and for NoArch
     pb.set_message("loading repodata");
-    let available_packages =
-        load_sparse_repo_data_async(platform, package_names.clone(), sparse_repo_data).await?;
+    let mut available_packages = load_sparse_repo_data_async(
+        platform,
+        package_names.clone(),
+        vec![sparse_repo_data.clone()],
+    )
+    .await?;
+
+    // Only the packages that opt in to pre-releases may come from the pre-release labels.
+    project.retain_allowed_prereleases(&mut available_packages);
+
+    // Build the dependencies that no channel provides from source, if the project opts in. The
+    // built packages can depend on more missing packages, those are built in the next round.
+    if let Some(build_fallback) = project.build_fallback() {
+        let mut built = HashSet::new();
+        loop {
+            let missing = build_fallback::missing_packages(
+                dependencies.iter().map(|(name, _)| name.clone()).unique(),
+                &available_packages,
+            )
+            .into_iter()
+            .filter(|name| !built.contains(name))
+            .collect_vec();
+            if missing.is_empty() {
+                break;
+            }
+            pb.set_message(format!(
+                "building {} from source",
+                missing.iter().map(|name| name.as_source()).join(", ")
+            ));
+            let local_repo_data =
+                build_fallback::build_missing_packages(project, build_fallback, platform, &missing)
+                    .await?;
+            built.extend(missing);
+
+            // Load the records again, so the dependencies of the built packages are included.
+            available_packages = load_sparse_repo_data_async(
+                platform,
+                package_names.clone(),
+                vec![sparse_repo_data.clone(), local_repo_data.into()],
+            )
+            .await?;
+            project.retain_allowed_prereleases(&mut available_packages);
+        }
+    }
 
     // Solve conda packages
     pb.set_message("resolving conda");
-    let mut records = resolve_conda_dependencies(
+    let mut records = match resolve_conda_dependencies(
         match_specs,
         virtual_packages,
         locked_packages,
-        available_packages,
-    )
-    .await?;
+        &available_packages,
+    ) {
+        Ok(records) => records,
+        Err(SolveError::Unsolvable(messages)) => {
+            return Err(conflict::conflict_report(
+                project,
+                platform,
+                &messages,
+                &package_names,
+                &available_packages,
+            ));
+        }
+        Err(err) => return Err(err).into_diagnostic(),
+    };
 
     // Add purl's for the conda packages that are also available as pypi packages
-    pypi::amend_pypi_purls(&mut records).await?;
+    pypi::amend_pypi_purls(project, &mut records).await?;
 
     // Update lock file
     let mut locked_packages = LockedPackagesBuilder::new(platform);
 
     // Add conda packages
     for record in records.iter() {
-        let locked_package = CondaLockedDependencyBuilder::try_from(record).into_diagnostic()?;
+        if record.package_record.sha256.is_none() {
+            warnings::emit(
+                WarningCode::MissingProvenance,
+                format!(
+                    "the repodata of {} has no sha256 for {} {}, its artifact can't be verified",
+                    record.channel,
+                    record.package_record.name.as_source(),
+                    record.package_record.version
+                ),
+            );
+        }
+        // The packages built from source refer to the local channel of the project.
+        let mut record = record.clone();
+        record.url = build_fallback::portable_url(project, &record.url);
+        let locked_package = CondaLockedDependencyBuilder::try_from(&record).into_diagnostic()?;
         locked_packages.add_locked_package(locked_package);
     }
 
     Ok(locked_packages)
 }
 
-/// Solves the conda package environment for the given input. This function is async because it
-/// spawns a background task for the solver. Since solving is a CPU intensive task we do not want to
-/// block the main task.
-async fn resolve_conda_dependencies(
+/// Solves the conda package environment for the given input.
+fn resolve_conda_dependencies(
     specs: Vec<MatchSpec>,
     virtual_packages: Vec<GenericVirtualPackage>,
     locked_packages: Vec<RepoDataRecord>,
-    available_packages: Vec<Vec<RepoDataRecord>>,
-) -> miette::Result<Vec<RepoDataRecord>> {
+    available_packages: &[Vec<RepoDataRecord>],
+) -> Result<Vec<RepoDataRecord>, SolveError> {
     // Construct a solver task that we can start solving.
     let task = rattler_solve::SolverTask {
         specs,
-        available_packages: &available_packages,
+        available_packages,
         locked_packages,
         pinned_packages: vec![],
         virtual_packages,
     };
 
     // Solve the task
-    resolvo::Solver.solve(task).into_diagnostic()
+    resolvo::Solver.solve(task)
 }
 
 /// Load the repodata records for the specified platform and package names in the background. This
@@ -382,12 +759,15 @@ async fn resolve_conda_dependencies(
 async fn load_sparse_repo_data_async(
     platform: Platform,
     package_names: Vec<PackageName>,
-    sparse_repo_data: Arc<[SparseRepoData]>,
+    sparse_repo_data: Vec<Arc<[SparseRepoData]>>,
 ) -> miette::Result<Vec<Vec<RepoDataRecord>>> {
     tokio::task::spawn_blocking(move || {
-        let platform_sparse_repo_data = sparse_repo_data.iter().filter(|sparse| {
-            sparse.subdir() == platform.as_str() || sparse.subdir() == Platform::NoArch.as_str()
-        });
+        let platform_sparse_repo_data = sparse_repo_data
+            .iter()
+            .flat_map(|sparse| sparse.iter())
+            .filter(|sparse| {
+                sparse.subdir() == platform.as_str() || sparse.subdir() == Platform::NoArch.as_str()
+            });
 
         // Load only records we need for this platform
         SparseRepoData::load_records_recursive(platform_sparse_repo_data, package_names, None)
@@ -407,3 +787,138 @@ async fn load_sparse_repo_data_async(
         )
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rattler_conda_types::VersionSpec;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn test_member_lock_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_contents = r#"
+        [project]
+        name = "foo"
+        channels = ["conda-forge"]
+        platforms = ["linux-64", "osx-64"]
+
+        [dependencies]
+        sdl2 = "*"
+
+        [feature.build.dependencies]
+        cmake = "*"
+
+        [environments]
+        prod = { features = [], solve-group = "prod" }
+        build = { features = ["build"], solve-group = "prod" }
+        "#;
+        let project = Project::from_str(dir.path(), file_contents)
+            .unwrap()
+            .with_environment(Some("prod"))
+            .unwrap();
+        let members = project.solve_group_members().unwrap();
+        let member = &members[0];
+        assert_eq!(member.environment_name(), Some("build"));
+
+        // The existing lock file of the member still locks `sdl2` for both platforms.
+        let sdl_lock_file = read_lock_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/cpp-sdl/pixi.lock"),
+        )
+        .unwrap();
+        write_lock_file(member, &sdl_lock_file, &member.lock_file_path()).unwrap();
+
+        // The group is solved again for linux-64 only.
+        let mut group_lock = sdl_lock_file.clone();
+        group_lock.metadata.platforms = vec![Platform::Linux64];
+        group_lock
+            .package
+            .retain(|package| package.platform == Platform::Linux64);
+        let member_lock = member_lock_file(member, group_lock).await.unwrap();
+        write_member_lock_files(&[(member.clone(), member_lock)]).unwrap();
+
+        let written = read_lock_file(&member.lock_file_path()).unwrap();
+        let is_locked = |name: &str, platform: Platform| {
+            written
+                .package
+                .iter()
+                .any(|package| package.name == name && package.platform == platform)
+        };
+        assert_eq!(
+            written.metadata.platforms,
+            [Platform::Linux64, Platform::Osx64]
+        );
+        // The solved platform only contains the packages the member needs, the other platform is
+        // left untouched.
+        assert!(is_locked("cmake", Platform::Linux64));
+        assert!(is_locked("sdl2", Platform::Linux64));
+        assert!(!is_locked("ninja", Platform::Linux64));
+        assert!(is_locked("ninja", Platform::Osx64));
+        assert!(!is_locked("cmake", Platform::Win64));
+    }
+
+    #[test]
+    fn test_conda_overrides() {
+        let project = Project::from_str(
+            Path::new(""),
+            r#"
+            [project]
+            name = "foo"
+            channels = ["conda-forge"]
+            platforms = ["linux-64"]
+
+            [pypi-dependencies]
+            lightning = "*"
+            Torch = ">=2.1"
+
+            [pypi-to-conda]
+            torch = "pytorch"
+            pillow = "pillow"
+            "#,
+        )
+        .unwrap();
+        let pypi_dependencies = project.pypi_dependencies(Platform::Linux64);
+        let overrides = |resolved: &[&str]| {
+            let resolved = resolved
+                .iter()
+                .map(|name| rip::types::NormalizedPackageName::from_str(name).unwrap())
+                .collect_vec();
+            pypi::conda_overrides(project.pypi_to_conda(), &pypi_dependencies, &resolved).unwrap()
+        };
+
+        // The first resolution replaces torch, the direct dependency keeps its version constraint.
+        let mut conda_overrides = Vec::new();
+        let new_overrides =
+            new_conda_overrides(overrides(&["lightning", "torch"]), &conda_overrides);
+        assert_eq!(new_overrides.len(), 1);
+        assert_eq!(
+            new_overrides[0].name,
+            Some(PackageName::new_unchecked("pytorch"))
+        );
+        assert_eq!(
+            new_overrides[0].version,
+            Some(VersionSpec::from_str(">=2.1").unwrap())
+        );
+        conda_overrides.extend(new_overrides);
+
+        // Solving with pytorch brings in pillow, which is replaced without a constraint.
+        let new_overrides = new_conda_overrides(
+            overrides(&["lightning", "torch", "pillow"]),
+            &conda_overrides,
+        );
+        assert_eq!(new_overrides.len(), 1);
+        assert_eq!(
+            new_overrides[0].name,
+            Some(PackageName::new_unchecked("pillow"))
+        );
+        assert_eq!(new_overrides[0].version, None);
+        conda_overrides.extend(new_overrides);
+
+        // The loop ends once the resolution contains no new packages of the table.
+        assert!(new_conda_overrides(
+            overrides(&["lightning", "torch", "pillow"]),
+            &conda_overrides
+        )
+        .is_empty());
+    }
+}
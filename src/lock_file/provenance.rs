@@ -0,0 +1,367 @@
+//! Checks the provenance of the locked packages. Every conda package is locked with the url of its
+//! artifact in the channel, the upload timestamp and the sha256 from the repodata, and every pypi
+//! package with the url of its artifact on the index and its sha256. The artifacts can be
+//! downloaded again and compared with those hashes, e.g. before an environment is installed on a
+//! machine that should only get exactly what was locked.
+
+use crate::config::Config;
+use crate::{auth, default_authenticated_client, progress};
+use futures::{stream, StreamExt, TryStreamExt};
+use indicatif::ProgressBar;
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use rattler_conda_types::Platform;
+use rattler_digest::digest::Digest;
+use rattler_digest::{Sha256, Sha256Hash};
+use rattler_lock::{CondaLock, LockedDependencyKind};
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use url::Url;
+
+/// What is wrong with the provenance of a locked package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProvenanceIssue {
+    /// The lock file has no sha256 for the artifact.
+    MissingHash,
+    /// The artifact of a conda package is not from a channel of the project.
+    UnknownChannel(String),
+    /// The downloaded artifact has a different sha256 than the locked one.
+    HashMismatch { expected: String, actual: String },
+    /// The artifact could not be downloaded.
+    DownloadFailed(String),
+}
+
+/// A locked package with a problem with its provenance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvenanceFinding {
+    /// The package, e.g. `numpy 1.26.2 (linux-64)`.
+    pub package: String,
+    pub issue: ProvenanceIssue,
+}
+
+impl Display for ProvenanceFinding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.package)?;
+        match &self.issue {
+            ProvenanceIssue::MissingHash => write!(f, "no sha256 is locked"),
+            ProvenanceIssue::UnknownChannel(channel) => {
+                write!(
+                    f,
+                    "locked from {channel}, which is not a channel of the project"
+                )
+            }
+            ProvenanceIssue::HashMismatch { expected, actual } => write!(
+                f,
+                "the artifact has sha256 {actual}, but {expected} is locked"
+            ),
+            ProvenanceIssue::DownloadFailed(err) => {
+                write!(f, "the artifact could not be downloaded: {err}")
+            }
+        }
+    }
+}
+
+/// The artifact of a locked package.
+struct LockedArtifact {
+    package: String,
+    url: Url,
+    sha256: Option<Sha256Hash>,
+    /// The channel of a conda package, `None` for pypi packages.
+    channel: Option<Url>,
+}
+
+/// Returns the url of the channel of a conda artifact, which is the url without the subdir and the
+/// file name, e.g. `https://conda.anaconda.org/conda-forge/` for
+/// `https://conda.anaconda.org/conda-forge/linux-64/zlib-1.3-0.conda`.
+pub fn conda_channel_url(url: &Url) -> Option<Url> {
+    let mut channel = url.clone();
+    channel.path_segments_mut().ok()?.pop().pop().push("");
+    Some(channel)
+}
+
+fn locked_artifacts(lock_file: &CondaLock, platform: Platform) -> Vec<LockedArtifact> {
+    lock_file
+        .get_packages_by_platform(platform)
+        .map(|package| {
+            let label = format!("{} {} ({platform})", package.name, package.version);
+            match &package.kind {
+                LockedDependencyKind::Conda(conda) => LockedArtifact {
+                    package: label,
+                    url: conda.url.clone(),
+                    sha256: conda.hash.sha256().cloned(),
+                    channel: conda_channel_url(&conda.url),
+                },
+                LockedDependencyKind::Pypi(pypi) => LockedArtifact {
+                    package: format!("{label} (pypi)"),
+                    url: pypi.url.clone(),
+                    sha256: pypi.hash.as_ref().and_then(|hash| hash.sha256()).cloned(),
+                    channel: None,
+                },
+            }
+        })
+        .collect()
+}
+
+/// Returns the packages of `platform` without a locked sha256, and the conda packages that are
+/// not from one of the `channels`.
+pub fn check_locked_provenance(
+    lock_file: &CondaLock,
+    platform: Platform,
+    channels: &[Url],
+) -> Vec<ProvenanceFinding> {
+    let mut findings = Vec::new();
+    for artifact in locked_artifacts(lock_file, platform) {
+        if artifact.sha256.is_none() {
+            findings.push(ProvenanceFinding {
+                package: artifact.package.clone(),
+                issue: ProvenanceIssue::MissingHash,
+            });
+        }
+        if let Some(channel) = artifact.channel {
+            if !channels.contains(&channel) {
+                findings.push(ProvenanceFinding {
+                    package: artifact.package,
+                    issue: ProvenanceIssue::UnknownChannel(channel.to_string()),
+                });
+            }
+        }
+    }
+    findings
+}
+
+/// Computes the sha256 of the artifact at `url`.
+async fn artifact_sha256(url: &Url) -> Result<Sha256Hash, String> {
+    if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| format!("invalid path '{url}'"))?;
+        return rattler_digest::compute_file_digest::<Sha256>(&path).map_err(|e| e.to_string());
+    }
+
+    let request = auth::authenticate_request(default_authenticated_client().get(url.clone()), url);
+    let mut response = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::default();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        hasher.update(&chunk);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Downloads the artifacts of the packages of `platform` and returns the ones whose sha256 differs
+/// from the locked one. The downloads go through the `url-rewrites` of the configuration.
+pub async fn verify_artifacts(
+    lock_file: &CondaLock,
+    platform: Platform,
+    config: &Config,
+) -> Vec<ProvenanceFinding> {
+    let artifacts = locked_artifacts(lock_file, platform)
+        .into_iter()
+        .filter(|artifact| artifact.sha256.is_some())
+        .collect_vec();
+
+    let pb = progress::global_multi_progress().add(ProgressBar::new(artifacts.len() as u64));
+    pb.set_style(progress::default_progress_style());
+    pb.set_prefix("verifying artifacts");
+    pb.enable_steady_tick(Duration::from_millis(100));
+
+    let findings = stream::iter(artifacts)
+        .map(|artifact| {
+            let pb = pb.clone();
+            async move {
+                let url = config.rewrite_url(&artifact.url);
+                let expected = artifact
+                    .sha256
+                    .expect("artifacts without a hash are skipped");
+                let issue = match artifact_sha256(&url).await {
+                    Ok(actual) if actual == expected => None,
+                    Ok(actual) => Some(ProvenanceIssue::HashMismatch {
+                        expected: format!("{expected:x}"),
+                        actual: format!("{actual:x}"),
+                    }),
+                    Err(err) => Some(ProvenanceIssue::DownloadFailed(err)),
+                };
+                pb.inc(1);
+                issue.map(|issue| ProvenanceFinding {
+                    package: artifact.package,
+                    issue,
+                })
+            }
+        })
+        .buffer_unordered(10)
+        .filter_map(|finding| async move { finding })
+        .collect::<Vec<_>>()
+        .await;
+    pb.finish_and_clear();
+
+    findings
+        .into_iter()
+        .sorted_by(|a, b| a.package.cmp(&b.package))
+        .collect()
+}
+
+/// Returns the path of an artifact relative to the directory it is downloaded to, the subdir and
+/// the file name for conda packages, e.g. `linux-64/zlib-1.3-0.conda`, and `pypi/` and the file
+/// name for pypi packages.
+fn artifact_path(artifact: &LockedArtifact) -> Option<PathBuf> {
+    let file_name = artifact.url.path_segments()?.last()?;
+    let dir = match artifact.channel {
+        Some(_) => artifact.url.path_segments()?.rev().nth(1)?,
+        None => "pypi",
+    };
+    Some(Path::new(dir).join(file_name))
+}
+
+/// Downloads the artifact at `url` to `path` and returns its sha256.
+async fn download_artifact(url: &Url, path: &Path) -> Result<Sha256Hash, String> {
+    if url.scheme() == "file" {
+        let source = url
+            .to_file_path()
+            .map_err(|_| format!("invalid path '{url}'"))?;
+        std::fs::copy(&source, path).map_err(|e| e.to_string())?;
+        return rattler_digest::compute_file_digest::<Sha256>(path).map_err(|e| e.to_string());
+    }
+
+    let request = auth::authenticate_request(default_authenticated_client().get(url.clone()), url);
+    let mut response = request
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    let mut file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut hasher = Sha256::default();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).map_err(|e| e.to_string())?;
+    }
+    Ok(hasher.finalize())
+}
+
+/// Downloads the artifacts of the packages of the `platforms` into `target_dir`, see
+/// [`artifact_path`] for the layout. Every artifact with a locked sha256 is checked against it.
+/// Returns the paths of the artifacts relative to `target_dir`.
+pub async fn download_locked_artifacts(
+    lock_file: &CondaLock,
+    platforms: &[Platform],
+    config: &Config,
+    target_dir: &Path,
+) -> miette::Result<Vec<PathBuf>> {
+    let artifacts = platforms
+        .iter()
+        .flat_map(|platform| locked_artifacts(lock_file, *platform))
+        .filter_map(|artifact| artifact_path(&artifact).map(|path| (path, artifact)))
+        .unique_by(|(path, _)| path.clone())
+        .collect_vec();
+
+    let pb = progress::global_multi_progress().add(ProgressBar::new(artifacts.len() as u64));
+    pb.set_style(progress::default_progress_style());
+    pb.set_prefix("downloading artifacts");
+    pb.enable_steady_tick(Duration::from_millis(100));
+
+    let result = stream::iter(artifacts)
+        .map(|(path, artifact)| {
+            let pb = pb.clone();
+            async move {
+                let destination = target_dir.join(&path);
+                if let Some(parent) = destination.parent() {
+                    std::fs::create_dir_all(parent).into_diagnostic()?;
+                }
+                let url = config.rewrite_url(&artifact.url);
+                let actual = download_artifact(&url, &destination).await.map_err(|err| {
+                    miette::miette!("failed to download {}: {err}", artifact.package)
+                })?;
+                if let Some(expected) = artifact.sha256 {
+                    if actual != expected {
+                        miette::bail!(
+                            "{}",
+                            ProvenanceFinding {
+                                package: artifact.package,
+                                issue: ProvenanceIssue::HashMismatch {
+                                    expected: format!("{expected:x}"),
+                                    actual: format!("{actual:x}"),
+                                },
+                            }
+                        );
+                    }
+                }
+                pb.inc(1);
+                Ok::<_, miette::Report>(path)
+            }
+        })
+        .buffer_unordered(10)
+        .try_collect::<Vec<_>>()
+        .await;
+    pb.finish_and_clear();
+
+    Ok(result?.into_iter().sorted().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conda_channel_url() {
+        let url =
+            Url::parse("https://conda.anaconda.org/conda-forge/linux-64/zlib-1.3-0.conda").unwrap();
+        assert_eq!(
+            conda_channel_url(&url).unwrap().as_str(),
+            "https://conda.anaconda.org/conda-forge/"
+        );
+        let url = Url::parse("https://repo.prefix.dev/my/channel/noarch/foo-1.0-0.conda").unwrap();
+        assert_eq!(
+            conda_channel_url(&url).unwrap().as_str(),
+            "https://repo.prefix.dev/my/channel/"
+        );
+    }
+
+    #[test]
+    fn test_artifact_path() {
+        let conda = LockedArtifact {
+            package: "zlib".to_string(),
+            url: Url::parse("https://conda.anaconda.org/conda-forge/linux-64/zlib-1.3-0.conda")
+                .unwrap(),
+            sha256: None,
+            channel: Some(Url::parse("https://conda.anaconda.org/conda-forge/").unwrap()),
+        };
+        assert_eq!(
+            artifact_path(&conda).unwrap(),
+            Path::new("linux-64/zlib-1.3-0.conda")
+        );
+        let pypi = LockedArtifact {
+            package: "flask".to_string(),
+            url: Url::parse(
+                "https://files.pythonhosted.org/packages/ab/cd/flask-3.0.0-py3-none-any.whl",
+            )
+            .unwrap(),
+            sha256: None,
+            channel: None,
+        };
+        assert_eq!(
+            artifact_path(&pypi).unwrap(),
+            Path::new("pypi/flask-3.0.0-py3-none-any.whl")
+        );
+    }
+
+    #[test]
+    fn test_check_locked_provenance() {
+        let lock_file = crate::lock_file::read_lock_file(
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/cpp-sdl/pixi.lock"),
+        )
+        .unwrap();
+        let conda_forge = Url::parse("https://conda.anaconda.org/conda-forge/").unwrap();
+        assert!(check_locked_provenance(&lock_file, Platform::Linux64, &[conda_forge]).is_empty());
+
+        let other = Url::parse("https://conda.anaconda.org/bioconda/").unwrap();
+        let findings = check_locked_provenance(&lock_file, Platform::Linux64, &[other]);
+        assert!(!findings.is_empty());
+        assert!(findings[0]
+            .to_string()
+            .ends_with("locked from https://conda.anaconda.org/conda-forge/, which is not a channel of the project"));
+    }
+}
@@ -0,0 +1,277 @@
+use crate::utils::spec_format::normalize_pypi_name;
+use crate::Project;
+use itertools::Itertools;
+use rattler_conda_types::{MatchSpec, PackageName, Platform};
+use rattler_lock::{CondaLock, LockedDependency, LockedDependencyKind};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// A locked package that was removed from the lock file because the project no longer needs it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrunedPackage {
+    pub name: String,
+    pub version: String,
+    pub platform: Platform,
+}
+
+/// Identifies a package in the dependency graph of the lock file. Conda and PyPI packages live in
+/// different namespaces.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) enum PackageKey {
+    Conda(String),
+    Pypi(String),
+}
+
+impl PackageKey {
+    pub(super) fn of(package: &LockedDependency) -> Self {
+        match package.kind {
+            LockedDependencyKind::Conda(_) => Self::Conda(package.name.to_lowercase()),
+            LockedDependencyKind::Pypi(_) => Self::Pypi(normalize_pypi_name(&package.name)),
+        }
+    }
+}
+
+/// Returns the names of the packages the locked `package` depends on.
+pub(super) fn dependencies_of(package: &LockedDependency) -> Vec<PackageKey> {
+    match &package.kind {
+        LockedDependencyKind::Conda(conda) => conda
+            .dependencies
+            .iter()
+            .filter_map(|spec| MatchSpec::from_str(spec).ok()?.name)
+            .map(|name| PackageKey::Conda(name.as_normalized().to_string()))
+            .collect(),
+        // Markers are not evaluated, that might keep a package that is not needed but never
+        // removes one that is.
+        LockedDependencyKind::Pypi(pypi) => pypi
+            .requires_dist
+            .iter()
+            .filter_map(|req| pep508_rs::Requirement::from_str(req).ok())
+            .map(|req| PackageKey::Pypi(normalize_pypi_name(&req.name)))
+            .collect(),
+    }
+}
+
+/// Removes everything from the lock file that the project no longer requires, i.e. the platforms
+/// that were removed from the manifest and the packages that can no longer be reached from the
+/// dependencies of the project. The remaining packages still satisfy the project so the lock file
+/// doesn't have to be solved again when only platforms or dependencies were removed.
+///
+/// Returns the pruned lock file and the packages that were removed from it.
+pub fn prune_lock_file(project: &Project, lock_file: CondaLock) -> (CondaLock, Vec<PrunedPackage>) {
+    prune_lock_file_with_roots(project, lock_file, &[])
+}
+
+/// Prunes the lock file like [`prune_lock_file`], the conda packages in `roots` and their
+/// dependencies are kept as well, even when the dependencies of the project don't require them.
+pub(super) fn prune_lock_file_with_roots(
+    project: &Project,
+    lock_file: CondaLock,
+    roots: &[PackageName],
+) -> (CondaLock, Vec<PrunedPackage>) {
+    let CondaLock {
+        mut metadata,
+        package,
+    } = lock_file;
+    metadata
+        .platforms
+        .retain(|platform| project.platforms().contains(platform));
+
+    // Determine the packages that are reachable from the dependencies of the project for every
+    // platform.
+    let mut required = HashSet::new();
+    for platform in metadata.platforms.iter().copied() {
+        let packages = package
+            .iter()
+            .filter(|package| package.platform == platform)
+            .map(|package| (PackageKey::of(package), package))
+            .collect::<HashMap<_, _>>();
+
+        let mut queue = project
+            .all_dependencies(platform)
+            .keys()
+            .chain(roots)
+            .map(|name| PackageKey::Conda(name.as_normalized().to_string()))
+            .chain(
+                project
+                    .pypi_dependencies(platform)
+                    .into_iter()
+                    .map(|(name, requirement)| requirement.as_pep508(&name))
+                    .map(|requirement| PackageKey::Pypi(normalize_pypi_name(&requirement.name))),
+            )
+            .collect_vec();
+        let mut seen = queue.iter().cloned().collect::<HashSet<_>>();
+        while let Some(key) = queue.pop() {
+            let Some(locked) = packages.get(&key) else {
+                continue;
+            };
+            required.insert((platform, key));
+            for dependency in dependencies_of(locked) {
+                if seen.insert(dependency.clone()) {
+                    queue.push(dependency);
+                }
+            }
+        }
+    }
+
+    let (kept, pruned): (Vec<_>, Vec<_>) = package
+        .into_iter()
+        .partition(|package| required.contains(&(package.platform, PackageKey::of(package))));
+    let pruned = pruned
+        .into_iter()
+        .map(|package| PrunedPackage {
+            name: package.name,
+            version: package.version,
+            platform: package.platform,
+        })
+        .collect();
+
+    (
+        CondaLock {
+            metadata,
+            package: kept,
+        },
+        pruned,
+    )
+}
+
+/// Prints the packages that were removed from the lock file, the platforms are combined for
+/// packages that were removed from multiple platforms.
+pub fn report_pruned_packages(pruned: &[PrunedPackage]) {
+    if pruned.is_empty() {
+        return;
+    }
+    let whitespace = console::Emoji("  ", "").to_string();
+    let packages = pruned
+        .iter()
+        .into_group_map_by(|package| (&package.name, &package.version))
+        .into_iter()
+        .sorted_by_key(|(key, _)| *key)
+        .map(|((name, version), packages)| {
+            format!(
+                "{} {version} ({})",
+                console::style(name).bold(),
+                packages.iter().map(|package| package.platform).join(", ")
+            )
+        })
+        .join(&format!("\n{whitespace} -  "));
+    eprintln!(
+        "{}Removed packages from the lock file that are no longer required:\n{whitespace} -  {packages}",
+        console::style(console::Emoji("✔ ", "")).green(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::manifest::Manifest;
+    use std::path::Path;
+
+    /// The lock file of the `cpp-sdl` example, which locks `cmake`, `cxx-compiler`, `ninja` and
+    /// `sdl2` for four platforms.
+    fn sdl_lock_file() -> CondaLock {
+        crate::lock_file::read_lock_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/cpp-sdl/pixi.lock"),
+        )
+        .unwrap()
+    }
+
+    fn project(dependencies: &str) -> Project {
+        let file_contents = format!(
+            r#"
+            [project]
+            name = "foo"
+            channels = ["conda-forge"]
+            platforms = ["linux-64"]
+
+            [dependencies]
+            {dependencies}
+            "#
+        );
+        Project::from_manifest(Manifest::from_str(Path::new(""), file_contents).unwrap())
+    }
+
+    fn locked_names(lock_file: &CondaLock) -> HashSet<&str> {
+        lock_file
+            .package
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_prune_lock_file() {
+        // Everything the project requires on its only platform is kept, the packages of the other
+        // platforms are removed with them.
+        let all_dependencies =
+            project("cmake = \"*\"\ncxx-compiler = \"*\"\nsdl2 = \"*\"\nninja = \"*\"");
+        let original = sdl_lock_file();
+        let (lock_file, pruned) = prune_lock_file(&all_dependencies, sdl_lock_file());
+        assert_eq!(lock_file.metadata.platforms, vec![Platform::Linux64]);
+        assert!(lock_file
+            .package
+            .iter()
+            .all(|package| package.platform == Platform::Linux64));
+        assert_eq!(
+            lock_file.package.len(),
+            original
+                .package
+                .iter()
+                .filter(|package| package.platform == Platform::Linux64)
+                .count()
+        );
+        assert_eq!(
+            pruned.len() + lock_file.package.len(),
+            original.package.len()
+        );
+        assert!(pruned
+            .iter()
+            .all(|package| package.platform != Platform::Linux64));
+
+        // Removing a dependency removes it and the packages only it required.
+        let without_cmake = project("cxx-compiler = \"*\"\nsdl2 = \"*\"\nninja = \"*\"");
+        let (lock_file, pruned) = prune_lock_file(&without_cmake, sdl_lock_file());
+        let names = locked_names(&lock_file);
+        assert!(!names.contains("cmake"));
+        assert!(!names.contains("libuv"));
+        assert!(names.contains("sdl2"));
+        assert!(names.contains("ninja"));
+        assert!(pruned.contains(&PrunedPackage {
+            name: String::from("cmake"),
+            version: lock_file_version(&original, "cmake"),
+            platform: Platform::Linux64,
+        }));
+
+        // Pruning a pruned lock file removes nothing.
+        let (_, pruned) = prune_lock_file(&without_cmake, lock_file);
+        assert!(pruned.is_empty());
+    }
+
+    /// Returns the locked version of a package on `linux-64`.
+    fn lock_file_version(lock_file: &CondaLock, name: &str) -> String {
+        lock_file
+            .package
+            .iter()
+            .find(|package| package.platform == Platform::Linux64 && package.name == name)
+            .unwrap()
+            .version
+            .clone()
+    }
+
+    #[test]
+    fn test_prune_lock_file_with_roots() {
+        let project = project("sdl2 = \"*\"");
+        let (lock_file, _) = prune_lock_file_with_roots(&project, sdl_lock_file(), &[]);
+        let names = locked_names(&lock_file);
+        assert!(names.contains("sdl2"));
+        assert!(!names.contains("cmake"));
+        assert!(!names.contains("libuv"));
+
+        // The roots and their dependencies are kept as well.
+        let cmake = PackageName::from_str("cmake").unwrap();
+        let (lock_file, _) = prune_lock_file_with_roots(&project, sdl_lock_file(), &[cmake]);
+        let names = locked_names(&lock_file);
+        assert!(names.contains("sdl2"));
+        assert!(names.contains("cmake"));
+        assert!(names.contains("libuv"));
+    }
+}
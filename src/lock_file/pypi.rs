@@ -1,16 +1,23 @@
 use crate::{
     consts::PROJECT_MANIFEST,
     lock_file::{package_identifier, pypi_name_mapping},
-    project::{manifest::LibCSystemRequirement, manifest::SystemRequirements},
+    project::{
+        manifest::LibCSystemRequirement, manifest::PyPiRequirement, manifest::SystemRequirements,
+    },
     virtual_packages::{default_glibc_version, default_mac_os_version},
     Project,
 };
+use indexmap::IndexMap;
 use itertools::Itertools;
 use miette::{Context, IntoDiagnostic};
 use pep508_rs::{MarkerEnvironment, StringVersion};
-use rattler_conda_types::{PackageRecord, Platform, RepoDataRecord, Version, VersionWithSource};
+use rattler_conda_types::{
+    MatchSpec, NamelessMatchSpec, PackageName, PackageRecord, Platform, RepoDataRecord, Version,
+    VersionSpec, VersionWithSource,
+};
 use rip::python_env::{WheelTag, WheelTags};
 use rip::resolve::{resolve, PinnedPackage, ResolveOptions, SDistResolution};
+use rip::types::NormalizedPackageName;
 use std::{collections::HashMap, str::FromStr, vec};
 
 /// Resolve python packages for the specified project.
@@ -61,13 +68,13 @@ pub async fn resolve_dependencies<'p>(
     // Determine the compatible tags
     let compatible_tags = project_platform_tags(
         platform,
-        project.system_requirements(),
+        &project.system_requirements(),
         python_record.as_ref(),
     );
 
     let requirements = dependencies
         .iter()
-        .map(|(name, req)| req.as_pep508(name))
+        .map(|(name, req)| resolver_requirement(name, req))
         .collect::<Vec<pep508_rs::Requirement>>();
 
     // Resolve the PyPi dependencies
@@ -94,15 +101,95 @@ pub async fn resolve_dependencies<'p>(
     Ok(result)
 }
 
-/// Amend the records with pypi purls if they are not present yet.
-pub async fn amend_pypi_purls(conda_packages: &mut [RepoDataRecord]) -> miette::Result<()> {
+/// Returns the requirement that is passed to the resolver. The resolver follows PEP 440 and only
+/// selects a pre-release if a specifier of the requirement mentions one, a requirement that allows
+/// pre-releases without mentioning one gets the lowest possible pre-release as its lower bound.
+fn resolver_requirement(
+    name: &rip::types::PackageName,
+    requirement: &PyPiRequirement,
+) -> pep508_rs::Requirement {
+    let mut pep508 = requirement.as_pep508(name);
+    let mentions_prerelease = requirement.version.as_ref().map_or(false, |specifiers| {
+        specifiers
+            .iter()
+            .any(|specifier| specifier.version().any_prerelease())
+    });
+    if requirement.allow_prerelease && !mentions_prerelease {
+        let lowest_prerelease =
+            pep440_rs::VersionSpecifier::from_str(">=0.dev0").expect("the specifier is valid");
+        let specifiers = requirement
+            .version
+            .iter()
+            .flat_map(|specifiers| specifiers.iter().cloned())
+            .chain(std::iter::once(lowest_prerelease))
+            .collect();
+        pep508.version_or_url = Some(pep508_rs::VersionOrUrl::VersionSpecifier(specifiers));
+    }
+    pep508
+}
+
+/// Amend the records with pypi purls if they are not present yet. The conda packages of the
+/// `[pypi-to-conda]` table provide the pypi packages they are mapped from.
+pub async fn amend_pypi_purls(
+    project: &Project,
+    conda_packages: &mut [RepoDataRecord],
+) -> miette::Result<()> {
     let conda_forge_mapping = pypi_name_mapping::conda_pypi_name_mapping().await?;
     for record in conda_packages.iter_mut() {
+        for (pypi_name, _) in project
+            .pypi_to_conda()
+            .iter()
+            .filter(|(_, conda_name)| **conda_name == record.package_record.name)
+        {
+            pypi_name_mapping::add_pypi_purl(record, &pypi_name.as_normalized().to_string());
+        }
         pypi_name_mapping::amend_pypi_purls(record, conda_forge_mapping)?;
     }
     Ok(())
 }
 
+/// Returns the specs of the conda packages that replace the resolved pypi packages according to
+/// the `[pypi-to-conda]` table of the project. The version constraint of a direct pypi dependency
+/// is kept for the conda package that replaces it, the conda packages of other pypi packages can
+/// have any version.
+pub fn conda_overrides<'a>(
+    pypi_to_conda: &IndexMap<rip::types::PackageName, PackageName>,
+    pypi_dependencies: &IndexMap<rip::types::PackageName, PyPiRequirement>,
+    resolved: impl IntoIterator<Item = &'a NormalizedPackageName>,
+) -> miette::Result<Vec<MatchSpec>> {
+    let resolved = resolved.into_iter().collect_vec();
+    pypi_to_conda
+        .iter()
+        .filter(|(pypi_name, _)| resolved.contains(&pypi_name.as_normalized()))
+        .unique_by(|(_, conda_name)| *conda_name)
+        .map(|(pypi_name, conda_name)| {
+            let version = pypi_dependencies
+                .iter()
+                .find(|(name, _)| name.as_normalized() == pypi_name.as_normalized())
+                .and_then(|(_, requirement)| requirement.version.as_ref())
+                .map(|version| {
+                    VersionSpec::from_str(&version.to_string())
+                        .into_diagnostic()
+                        .wrap_err_with(|| {
+                            format!(
+                                "the version '{version}' of the pypi dependency '{}' can't be used for the conda package '{}'",
+                                pypi_name.as_source_str(),
+                                conda_name.as_source()
+                            )
+                        })
+                })
+                .transpose()?;
+            Ok(MatchSpec::from_nameless(
+                NamelessMatchSpec {
+                    version,
+                    ..NamelessMatchSpec::default()
+                },
+                Some(conda_name.clone()),
+            ))
+        })
+        .collect()
+}
+
 /// Returns true if the specified record refers to a version/variant of python.
 pub fn is_python_record(record: &RepoDataRecord) -> bool {
     package_name_is_python(&record.package_record.name)
@@ -518,3 +605,29 @@ mod test {
         insta::assert_debug_snapshot!(tags);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolver_requirement() {
+        let requirements: IndexMap<rip::types::PackageName, PyPiRequirement> =
+            toml_edit::de::from_str(
+                r#"
+                foo = { version = ">=2", allow-prerelease = true }
+                bar = { version = ">=2.0rc1", allow-prerelease = true }
+                baz = ">=2"
+                "#,
+            )
+            .unwrap();
+        let resolved = requirements
+            .iter()
+            .map(|(name, requirement)| resolver_requirement(name, requirement).to_string())
+            .collect_vec();
+        // Only a requirement that allows pre-releases without mentioning one is extended.
+        assert!(resolved[0].starts_with("foo>=2") && resolved[0].ends_with(">=0.dev0"));
+        assert_eq!(resolved[1], "bar>=2.0rc1");
+        assert_eq!(resolved[2], "baz>=2");
+    }
+}
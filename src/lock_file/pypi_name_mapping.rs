@@ -64,6 +64,14 @@ pub fn amend_pypi_purls(
     Ok(())
 }
 
+/// Adds a purl for the specified PyPI package to the record, unless the record already has it.
+pub fn add_pypi_purl(record: &mut RepoDataRecord, pypi_name: &str) {
+    let purl = PackageUrl::new(String::from("pypi"), pypi_name).expect("valid pypi package url");
+    if !record.package_record.purls.contains(&purl) {
+        record.package_record.purls.push(purl);
+    }
+}
+
 /// Returns `true` if the specified record refers to a conda-forge package.
 pub fn is_conda_forge_record(record: &RepoDataRecord) -> bool {
     Url::from_str(&record.channel).map_or(false, |u| is_conda_forge_url(&u))
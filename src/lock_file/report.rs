@@ -0,0 +1,213 @@
+//! The resolve report records the inputs that influenced the solve of every platform of a lock
+//! file: the repodata that was used, the virtual packages, the version of pixi and the solver and
+//! the solve strategy. It is embedded in the header of the lock file so that two lock files that
+//! were solved on different machines, or at different times, can be compared with
+//! `pixi lock --explain`. It only contains values that are the same on every machine that solves
+//! with the same inputs, so solving again doesn't change the lock file.
+
+use crate::repodata::{repodata_fingerprints, RepodataFingerprint};
+use crate::Project;
+use rattler_conda_types::{GenericVirtualPackage, Platform};
+use rattler_lock::CondaLock;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// The key of the report in the custom metadata of the lock file.
+const REPORT_KEY: &str = "pixi-resolve-report";
+
+/// The solver that pixi uses to solve the conda dependencies.
+const SOLVER: &str = "resolvo";
+
+/// Which versions of the packages the solver prefers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SolveStrategy {
+    /// The highest versions that satisfy the dependencies, the default.
+    Highest,
+    /// The lowest versions of the direct dependencies, see `pixi run --with-lowest-deps`.
+    LowestDirect,
+}
+
+impl Display for SolveStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolveStrategy::Highest => write!(f, "highest"),
+            SolveStrategy::LowestDirect => write!(f, "lowest-direct"),
+        }
+    }
+}
+
+/// The inputs of the solve of a single platform.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PlatformResolveInputs {
+    /// The version of pixi that solved the platform.
+    pub pixi_version: String,
+    /// The solver that was used.
+    pub solver: String,
+    pub strategy: SolveStrategy,
+    /// The virtual packages that were available to the solver, e.g. `__glibc=2.17=0`.
+    pub virtual_packages: Vec<String>,
+    /// The repodata of the subdirectories of the platform, including `noarch`.
+    pub repodata: Vec<RepodataFingerprint>,
+}
+
+impl PlatformResolveInputs {
+    /// Captures the inputs of a solve of `platform` that just finished.
+    pub fn capture(
+        platform: Platform,
+        virtual_packages: &[GenericVirtualPackage],
+        strategy: SolveStrategy,
+    ) -> Self {
+        Self {
+            pixi_version: env!("CARGO_PKG_VERSION").to_string(),
+            solver: SOLVER.to_string(),
+            strategy,
+            virtual_packages: virtual_packages
+                .iter()
+                .map(|package| {
+                    format!(
+                        "{}={}={}",
+                        package.name.as_normalized(),
+                        package.version,
+                        package.build_string
+                    )
+                })
+                .collect(),
+            repodata: repodata_fingerprints(&[platform, Platform::NoArch]),
+        }
+    }
+}
+
+/// The inputs of the solve of every platform of a lock file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolveReport {
+    /// The inputs by the name of the platform.
+    pub platforms: BTreeMap<String, PlatformResolveInputs>,
+}
+
+impl ResolveReport {
+    /// Captures the inputs of the solves of `platforms` that just finished.
+    pub fn capture(
+        project: &Project,
+        platforms: &[Platform],
+        strategy: SolveStrategy,
+    ) -> miette::Result<Self> {
+        let mut report = Self::default();
+        for platform in platforms.iter().copied() {
+            let virtual_packages = project.virtual_packages(platform)?;
+            report.platforms.insert(
+                platform.to_string(),
+                PlatformResolveInputs::capture(platform, &virtual_packages, strategy),
+            );
+        }
+        Ok(report)
+    }
+
+    /// Reads the report from the header of a lock file. Returns `None` if the lock file was created
+    /// by an older version of pixi, or if the report is invalid.
+    pub fn read(lock_file: &CondaLock) -> Option<Self> {
+        let report = lock_file
+            .metadata
+            .custom_metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get(REPORT_KEY))?;
+        match serde_json::from_str(report) {
+            Ok(report) => Some(report),
+            Err(err) => {
+                tracing::warn!("ignoring the invalid resolve report in the lock file: {err}");
+                None
+            }
+        }
+    }
+
+    /// Writes the report into the header of a lock file.
+    pub fn write(&self, lock_file: &mut CondaLock) {
+        let report = serde_json::to_string(self).expect("the report can always be serialized");
+        lock_file
+            .metadata
+            .custom_metadata
+            .get_or_insert_with(Default::default)
+            .insert(REPORT_KEY.to_string(), report);
+    }
+}
+
+impl Display for ResolveReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (index, (platform, inputs)) in self.platforms.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "{}", console::style(platform).bold())?;
+            writeln!(f, "  pixi version:     {}", inputs.pixi_version)?;
+            writeln!(f, "  solver:           {}", inputs.solver)?;
+            writeln!(f, "  strategy:         {}", inputs.strategy)?;
+            if inputs.virtual_packages.is_empty() {
+                writeln!(f, "  virtual packages: none")?;
+            } else {
+                writeln!(f, "  virtual packages:")?;
+                for package in &inputs.virtual_packages {
+                    writeln!(f, "    {package}")?;
+                }
+            }
+            if inputs.repodata.is_empty() {
+                writeln!(f, "  repodata:         unknown")?;
+            } else {
+                writeln!(f, "  repodata:")?;
+                for repodata in &inputs.repodata {
+                    writeln!(f, "    {} ({})", repodata.url, repodata.fingerprint)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rattler_lock::builder::LockFileBuilder;
+
+    fn inputs(strategy: SolveStrategy) -> PlatformResolveInputs {
+        PlatformResolveInputs {
+            pixi_version: String::from("0.9.1"),
+            solver: SOLVER.to_string(),
+            strategy,
+            virtual_packages: vec![String::from("__glibc=2.17=0")],
+            repodata: vec![RepodataFingerprint {
+                url: String::from("https://conda.anaconda.org/conda-forge/linux-64/"),
+                fingerprint: String::from("blake2:0123456789abcdef"),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_report() {
+        let mut lock_file = LockFileBuilder::default().build().unwrap();
+        assert_eq!(ResolveReport::read(&lock_file), None);
+
+        let mut report = ResolveReport::default();
+        report.platforms.insert(
+            String::from("linux-64"),
+            inputs(SolveStrategy::LowestDirect),
+        );
+        report.write(&mut lock_file);
+        assert_eq!(ResolveReport::read(&lock_file), Some(report));
+    }
+
+    #[test]
+    fn test_display_report() {
+        let mut report = ResolveReport::default();
+        report
+            .platforms
+            .insert(String::from("linux-64"), inputs(SolveStrategy::Highest));
+        let printed = console::strip_ansi_codes(&report.to_string()).to_string();
+        assert!(printed.starts_with("linux-64\n"));
+        assert!(printed.contains("  strategy:         highest\n"));
+        assert!(printed.contains("    __glibc=2.17=0\n"));
+        assert!(printed.contains(
+            "https://conda.anaconda.org/conda-forge/linux-64/ (blake2:0123456789abcdef)"
+        ));
+    }
+}
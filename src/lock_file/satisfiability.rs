@@ -1,6 +1,7 @@
 use super::package_identifier;
 use crate::project::{DependencyKind, DependencyName};
 use crate::{
+    banned,
     lock_file::pypi::{determine_marker_environment, is_python_record},
     Project,
 };
@@ -41,6 +42,11 @@ pub fn lock_file_satisfies_project(
         return Ok(false);
     }
 
+    // A lock file with a banned package has to be solved again, which fails with the reason.
+    if !banned::find_banned_packages(project, lock_file, platforms).is_empty() {
+        return Ok(false);
+    }
+
     // For each platform,
     for platform in platforms.iter().cloned() {
         // Check if all dependencies exist in the lock-file.
@@ -0,0 +1,220 @@
+//! Signing of lock files with [minisign](https://jedisct1.github.io/minisign/) keys. `pixi lock
+//! --sign` writes the signature next to the lock file, e.g. `pixi.lock.minisig`. When the
+//! configuration sets `require-signed-lock`, an environment is only installed from a lock file
+//! with a valid signature of one of the trusted keys, so a production deployment installs exactly
+//! what was locked and reviewed.
+
+use super::format::parse_lock_file;
+use miette::{Context, IntoDiagnostic};
+use minisign::{PublicKey, PublicKeyBox, SecretKey, SecretKeyBox, SignatureBox};
+use rattler_lock::CondaLock;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+/// The environment variable with the password of an encrypted secret key, without it the password
+/// is asked for.
+pub const SIGNING_KEY_PASSWORD_ENV: &str = "PIXI_SIGNING_KEY_PASSWORD";
+
+/// Returns the path of the signature of a lock file.
+pub fn signature_path(lock_file_path: &Path) -> PathBuf {
+    let mut file_name = lock_file_path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".minisig");
+    lock_file_path.with_file_name(file_name)
+}
+
+/// Returns the default location of the secret key, the one that `minisign -G` creates.
+pub fn default_secret_key_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".minisign").join("minisign.key"))
+}
+
+/// Signs the lock file with the secret key at `secret_key_path` and writes the signature next to
+/// it. Returns the path of the signature.
+pub fn sign_lock_file(lock_file_path: &Path, secret_key_path: &Path) -> miette::Result<PathBuf> {
+    let secret_key = std::fs::read_to_string(secret_key_path)
+        .into_diagnostic()
+        .and_then(|contents| SecretKeyBox::from_string(&contents).into_diagnostic())
+        .and_then(|key| {
+            key.into_secret_key(std::env::var(SIGNING_KEY_PASSWORD_ENV).ok())
+                .into_diagnostic()
+        })
+        .wrap_err_with(|| {
+            format!(
+                "failed to read the secret key '{}'",
+                secret_key_path.display()
+            )
+        })?;
+
+    let contents = std::fs::read(lock_file_path).into_diagnostic()?;
+    let signature = sign(&secret_key, &contents)?;
+    let path = signature_path(lock_file_path);
+    std::fs::write(&path, signature.into_string())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write '{}'", path.display()))?;
+    Ok(path)
+}
+
+/// Reads the lock file, verifies that it is signed by one of the `trusted_keys` and parses it. The
+/// file is read once and the signature is verified over the same contents that are parsed, so the
+/// lock file can't be replaced between the verification and its use.
+pub fn read_signed_lock_file(
+    lock_file_path: &Path,
+    trusted_keys: &[String],
+) -> miette::Result<CondaLock> {
+    let contents = std::fs::read(lock_file_path)
+        .into_diagnostic()
+        .wrap_err_with(|| {
+            format!(
+                "failed to read the lock file '{}'",
+                lock_file_path.display()
+            )
+        })?;
+    verify_lock_file(lock_file_path, &contents, trusted_keys)?;
+    parse_lock_file(&contents).wrap_err_with(|| {
+        format!(
+            "failed to parse the lock file '{}'",
+            lock_file_path.display()
+        )
+    })
+}
+
+/// Verifies that the `contents` of the lock file are signed by one of the `trusted_keys`, which are
+/// either public keys (`RWQ...`) or paths of public key files.
+fn verify_lock_file(
+    lock_file_path: &Path,
+    contents: &[u8],
+    trusted_keys: &[String],
+) -> miette::Result<()> {
+    if trusted_keys.is_empty() {
+        miette::bail!(
+            help = "add the public keys that sign the lock files to `lock-signing-keys` in the global configuration",
+            "a signed lock file is required, but no key to verify it is trusted"
+        );
+    }
+    let keys = trusted_keys
+        .iter()
+        .map(|key| parse_public_key(key))
+        .collect::<miette::Result<Vec<_>>>()?;
+
+    let path = signature_path(lock_file_path);
+    let signature = match std::fs::read_to_string(&path) {
+        Ok(signature) => signature,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => miette::bail!(
+            help = "sign the lock file with `pixi lock --sign`",
+            "'{}' is not signed, but a signed lock file is required",
+            lock_file_path.display()
+        ),
+        Err(err) => return Err(err).into_diagnostic(),
+    };
+    if !verify(&keys, &signature, contents) {
+        miette::bail!(
+            help = "the lock file was modified after it was signed, or it was signed with a key that is not trusted. Review the changes and sign it again with `pixi lock --sign`",
+            "the signature '{}' does not match the lock file",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Parses a public key, either the key itself or the path of a file that contains it.
+fn parse_public_key(key: &str) -> miette::Result<PublicKey> {
+    let path = Path::new(key);
+    let public_key = if path.is_file() {
+        std::fs::read_to_string(path)
+            .into_diagnostic()
+            .and_then(|contents| PublicKeyBox::from_string(&contents).into_diagnostic())
+            .and_then(|key| key.into_public_key().into_diagnostic())
+    } else {
+        PublicKey::from_base64(key.trim()).into_diagnostic()
+    };
+    public_key.wrap_err_with(|| format!("'{key}' is not a minisign public key or the path of one"))
+}
+
+fn sign(secret_key: &SecretKey, contents: &[u8]) -> miette::Result<SignatureBox> {
+    minisign::sign(None, secret_key, Cursor::new(contents), None, None).into_diagnostic()
+}
+
+/// Returns true if the signature is valid for the contents and was created by one of the keys.
+fn verify(keys: &[PublicKey], signature: &str, contents: &[u8]) -> bool {
+    let Ok(signature) = SignatureBox::from_string(signature) else {
+        return false;
+    };
+    keys.iter().any(|key| {
+        minisign::verify(key, &signature, Cursor::new(contents), true, false, false).is_ok()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minisign::KeyPair;
+
+    #[test]
+    fn test_signature_path() {
+        assert_eq!(
+            signature_path(Path::new("/project/pixi.lock")),
+            Path::new("/project/pixi.lock.minisig")
+        );
+        assert_eq!(
+            signature_path(Path::new("/project/pixi.test.lock")),
+            Path::new("/project/pixi.test.lock.minisig")
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = KeyPair::generate_unencrypted_keypair().unwrap();
+        let other = KeyPair::generate_unencrypted_keypair().unwrap();
+        let contents = b"version: 1\nmetadata: {}\npackage: []\n";
+        let signature = sign(&signer.sk, contents).unwrap().into_string();
+
+        assert!(verify(&[signer.pk.clone()], &signature, contents));
+        assert!(verify(
+            &[other.pk.clone(), signer.pk.clone()],
+            &signature,
+            contents
+        ));
+        assert!(!verify(&[other.pk], &signature, contents));
+        assert!(!verify(
+            &[signer.pk.clone()],
+            &signature,
+            b"version: 1\nmetadata: {}\npackage: [evil]\n"
+        ));
+        assert!(!verify(&[signer.pk], "not a signature", contents));
+    }
+
+    #[test]
+    fn test_verify_lock_file() {
+        let signer = KeyPair::generate_unencrypted_keypair().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let lock_file_path = dir.path().join("pixi.lock");
+        let trusted_keys = [signer.pk.to_base64()];
+
+        // Without a signature or trusted keys the lock file is rejected.
+        assert!(verify_lock_file(&lock_file_path, b"version: 1\n", &trusted_keys).is_err());
+        let signature = sign(&signer.sk, b"version: 1\n").unwrap().into_string();
+        std::fs::write(signature_path(&lock_file_path), signature).unwrap();
+        assert!(verify_lock_file(&lock_file_path, b"version: 1\n", &[]).is_err());
+        verify_lock_file(&lock_file_path, b"version: 1\n", &trusted_keys).unwrap();
+        assert!(verify_lock_file(&lock_file_path, b"version: 2\n", &trusted_keys).is_err());
+    }
+
+    #[test]
+    fn test_read_signed_lock_file() {
+        let signer = KeyPair::generate_unencrypted_keypair().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let lock_file_path = dir.path().join("pixi.lock");
+        let contents =
+            std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/cpp-sdl/pixi.lock"))
+                .unwrap();
+        std::fs::write(&lock_file_path, &contents).unwrap();
+        let signature = sign(&signer.sk, &contents).unwrap().into_string();
+        std::fs::write(signature_path(&lock_file_path), signature).unwrap();
+        let trusted_keys = [signer.pk.to_base64()];
+
+        let lock_file = read_signed_lock_file(&lock_file_path, &trusted_keys).unwrap();
+        assert!(!lock_file.package.is_empty());
+
+        std::fs::write(&lock_file_path, [contents.as_slice(), b"\n"].concat()).unwrap();
+        assert!(read_signed_lock_file(&lock_file_path, &trusted_keys).is_err());
+    }
+}
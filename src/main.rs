@@ -1,9 +1,12 @@
-use pixi::cli;
+use pixi::{cli, interrupt};
 
 #[tokio::main]
 pub async fn main() {
     if let Err(err) = cli::execute().await {
         eprintln!("{err:?}");
+        if interrupt::was_interrupted() {
+            std::process::exit(interrupt::INTERRUPTED_EXIT_CODE);
+        }
         std::process::exit(1);
     }
 }
@@ -0,0 +1,331 @@
+//! Support for the menu definitions (`Menu/*.json`) that conda packages can ship to create desktop
+//! or start-menu shortcuts. This implements the subset of the
+//! [menuinst](https://github.com/conda/menuinst) v2 schema that pixi needs to create simple
+//! launchers for the packages in an environment.
+
+use crate::prefix::Prefix;
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use rattler_conda_types::{Platform, PrefixRecord};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// The directory inside a prefix that contains the menu definitions of the installed packages.
+const MENU_DIR: &str = "Menu";
+
+/// The top-level structure of a menu definition file.
+#[derive(Debug, Deserialize)]
+pub struct MenuDefinition {
+    /// The name of the menu that groups the items together.
+    pub menu_name: String,
+
+    /// The shortcuts defined by this menu.
+    pub menu_items: Vec<MenuItem>,
+}
+
+/// A single shortcut defined in a menu definition file.
+#[derive(Debug, Deserialize)]
+pub struct MenuItem {
+    /// The name of the shortcut.
+    pub name: String,
+
+    /// An optional description of the shortcut.
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// The command to execute, the first element is the executable.
+    pub command: Vec<String>,
+
+    /// An optional path to the icon of the shortcut.
+    #[serde(default)]
+    pub icon: Option<String>,
+
+    /// Whether the command should be executed in a terminal.
+    #[serde(default)]
+    pub terminal: bool,
+
+    /// The platforms for which this item should be created. Keys are `linux`, `osx` and `win`.
+    #[serde(default)]
+    pub platforms: serde_json::Map<String, serde_json::Value>,
+}
+
+impl MenuItem {
+    /// Returns true if this menu item should be created on the given platform.
+    fn supports_platform(&self, platform: Platform) -> bool {
+        let key = if platform.is_linux() {
+            "linux"
+        } else if platform.is_osx() {
+            "osx"
+        } else if platform.is_windows() {
+            "win"
+        } else {
+            return false;
+        };
+        self.platforms.contains_key(key)
+    }
+}
+
+/// Returns the paths (relative to the prefix) of the menu definition files of a package.
+fn menu_files(record: &PrefixRecord) -> impl Iterator<Item = &PathBuf> + '_ {
+    record.files.iter().filter(|path| {
+        path.starts_with(MENU_DIR) && path.extension().map_or(false, |ext| ext == "json")
+    })
+}
+
+/// Replaces the `{{ PLACEHOLDER }}` variables supported by menuinst.
+fn render(template: &str, prefix: &Path, platform: Platform) -> String {
+    let (bin_dir, python, icon_ext) = if platform.is_windows() {
+        (
+            prefix.join("Library").join("bin"),
+            prefix.join("python.exe"),
+            "ico",
+        )
+    } else if platform.is_osx() {
+        (
+            prefix.join("bin"),
+            prefix.join("bin").join("python"),
+            "icns",
+        )
+    } else {
+        (prefix.join("bin"), prefix.join("bin").join("python"), "png")
+    };
+    let scripts_dir = if platform.is_windows() {
+        prefix.join("Scripts")
+    } else {
+        prefix.join("bin")
+    };
+    let distribution_name = prefix
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let home = dirs::home_dir().unwrap_or_default();
+
+    let variables = [
+        ("PREFIX", prefix.to_path_buf()),
+        ("BASE_PREFIX", prefix.to_path_buf()),
+        ("BIN_DIR", bin_dir),
+        ("SCRIPTS_DIR", scripts_dir),
+        ("PYTHON", python),
+        ("MENU_DIR", prefix.join(MENU_DIR)),
+        ("HOME", home),
+    ];
+
+    let mut result = template.to_string();
+    for (name, value) in variables {
+        result = result.replace(
+            &format!("{{{{ {name} }}}}"),
+            value.to_string_lossy().as_ref(),
+        );
+    }
+    result
+        .replace("{{ ICON_EXT }}", icon_ext)
+        .replace("{{ DISTRIBUTION_NAME }}", &distribution_name)
+}
+
+/// Returns the directory in which shortcuts are placed for the current platform.
+fn shortcut_dir(platform: Platform) -> miette::Result<PathBuf> {
+    let dir = if platform.is_linux() {
+        dirs::data_dir().map(|dir| dir.join("applications"))
+    } else if platform.is_osx() {
+        dirs::home_dir().map(|dir| dir.join("Applications"))
+    } else {
+        dirs::data_dir().map(|dir| {
+            dir.join("Microsoft")
+                .join("Windows")
+                .join("Start Menu")
+                .join("Programs")
+        })
+    };
+    dir.ok_or_else(|| miette::miette!("could not determine the directory to place shortcuts in"))
+}
+
+/// Returns the path of the shortcut file for a menu item. The name of the file includes a short
+/// hash of the prefix so the same package installed in different environments does not clash.
+fn shortcut_path(
+    dir: &Path,
+    prefix: &Path,
+    menu_name: &str,
+    item: &MenuItem,
+    platform: Platform,
+) -> PathBuf {
+    let digest = rattler_digest::compute_bytes_digest::<rattler_digest::Sha256>(
+        prefix.to_string_lossy().as_bytes(),
+    );
+    let hash = format!("{digest:x}");
+    let name = format!("{}-{}-{}", menu_name, item.name, &hash[..8])
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let extension = if platform.is_linux() {
+        "desktop"
+    } else if platform.is_osx() {
+        "command"
+    } else {
+        "bat"
+    };
+    dir.join(format!("{name}.{extension}"))
+}
+
+/// Returns the contents of the shortcut file for a menu item.
+fn shortcut_contents(item: &MenuItem, prefix: &Path, platform: Platform) -> String {
+    let command = item
+        .command
+        .iter()
+        .map(|arg| render(arg, prefix, platform))
+        .collect_vec();
+    if platform.is_linux() {
+        let mut contents = format!(
+            "[Desktop Entry]\nType=Application\nEncoding=UTF-8\nName={}\nExec={}\nTerminal={}\n",
+            item.name,
+            command.iter().map(|arg| shlex::quote(arg)).join(" "),
+            item.terminal
+        );
+        if let Some(description) = &item.description {
+            contents.push_str(&format!("Comment={description}\n"));
+        }
+        if let Some(icon) = &item.icon {
+            contents.push_str(&format!("Icon={}\n", render(icon, prefix, platform)));
+        }
+        contents
+    } else if platform.is_osx() {
+        format!(
+            "#!/bin/sh\nexec {}\n",
+            command.iter().map(|arg| shlex::quote(arg)).join(" ")
+        )
+    } else {
+        format!(
+            "@echo off\r\n{}\r\n",
+            command.iter().map(|arg| format!("\"{arg}\"")).join(" ")
+        )
+    }
+}
+
+/// Reads all the menu definitions of a package installed in the given prefix. A menu definition
+/// that can't be read or parsed is skipped with a warning, a broken shortcut of one package should
+/// not fail the installation of the environment.
+fn read_menu_definitions(prefix: &Prefix, record: &PrefixRecord) -> Vec<MenuDefinition> {
+    menu_files(record)
+        .filter_map(|path| {
+            let menu = std::fs::read_to_string(prefix.root().join(path))
+                .into_diagnostic()
+                .and_then(|contents| serde_json::from_str(&contents).into_diagnostic());
+            match menu {
+                Ok(menu) => Some(menu),
+                Err(e) => {
+                    tracing::warn!(
+                        "skipping menu definition '{}' of {}: {e}",
+                        path.display(),
+                        record.repodata_record.package_record.name.as_source()
+                    );
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Creates the shortcuts for all the menu items defined by the given packages. Returns the paths
+/// of the shortcuts that were created.
+pub fn install_shortcuts(
+    prefix: &Prefix,
+    records: &[PrefixRecord],
+    platform: Platform,
+) -> miette::Result<Vec<PathBuf>> {
+    let mut created = Vec::new();
+    for record in records {
+        for menu in read_menu_definitions(prefix, record) {
+            for item in menu
+                .menu_items
+                .iter()
+                .filter(|item| item.supports_platform(platform))
+            {
+                let dir = shortcut_dir(platform)?;
+                std::fs::create_dir_all(&dir).into_diagnostic()?;
+                let path = shortcut_path(&dir, prefix.root(), &menu.menu_name, item, platform);
+                std::fs::write(&path, shortcut_contents(item, prefix.root(), platform))
+                    .into_diagnostic()?;
+
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+                        .into_diagnostic()?;
+                }
+
+                tracing::info!("created shortcut {}", path.display());
+                created.push(path);
+            }
+        }
+    }
+    Ok(created)
+}
+
+/// Removes the shortcuts created for the menu items defined by the given packages. This must be
+/// called before the packages are removed from the prefix because the menu definitions are read
+/// from the prefix.
+pub fn remove_shortcuts(
+    prefix: &Prefix,
+    records: &[&PrefixRecord],
+    platform: Platform,
+) -> miette::Result<()> {
+    for record in records {
+        for menu in read_menu_definitions(prefix, record) {
+            for item in menu
+                .menu_items
+                .iter()
+                .filter(|item| item.supports_platform(platform))
+            {
+                let path = shortcut_path(
+                    &shortcut_dir(platform)?,
+                    prefix.root(),
+                    &menu.menu_name,
+                    item,
+                    platform,
+                );
+                match std::fs::remove_file(&path) {
+                    Ok(_) => tracing::info!("removed shortcut {}", path.display()),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e).into_diagnostic(),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_and_desktop_entry() {
+        let menu: MenuDefinition = serde_json::from_str(
+            r#"{
+                "menu_name": "foo",
+                "menu_items": [{
+                    "name": "Foo",
+                    "description": "Runs foo",
+                    "command": ["{{ PREFIX }}/bin/foo", "--gui"],
+                    "icon": "{{ MENU_DIR }}/foo.{{ ICON_EXT }}",
+                    "platforms": { "linux": {} }
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let item = &menu.menu_items[0];
+        assert!(item.supports_platform(Platform::Linux64));
+        assert!(!item.supports_platform(Platform::Win64));
+
+        let contents = shortcut_contents(item, Path::new("/tmp/env"), Platform::Linux64);
+        assert!(contents.contains("Exec=/tmp/env/bin/foo --gui\n"));
+        assert!(contents.contains("Icon=/tmp/env/Menu/foo.png\n"));
+        assert!(contents.contains("Comment=Runs foo\n"));
+    }
+}
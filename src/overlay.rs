@@ -0,0 +1,546 @@
+//! Local patches of installed conda packages. The `[overlays]` table of the manifest maps a package
+//! to a directory in the project whose files are copied over the files of the package after it is
+//! linked into the environment, e.g. to hotfix a third-party package until the fix is released.
+//!
+//! The overlay directory is laid out like the environment, so `overlays/numpy/lib/python3.12/...`
+//! replaces the file at `lib/python3.12/...`. The files that are replaced are moved into a backup
+//! directory in `conda-meta` and the applied overlays are recorded, so an overlay that is removed
+//! from the manifest is undone and an overlay is applied again when its package is reinstalled.
+
+use crate::{prefix::Prefix, Project};
+use indexmap::IndexMap;
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::{PackageName, PackageRecord, VersionSpec};
+use rattler_digest::{compute_file_digest, Sha256};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The directory in `conda-meta` with the state of the overlays and the backups of the files they
+/// replaced.
+const OVERLAY_DIR: &str = "pixi-overlays";
+
+/// The file in [`OVERLAY_DIR`] that records the applied overlays.
+const OVERLAY_STATE_FILE: &str = "state.json";
+
+/// The directory in [`OVERLAY_DIR`] with the original files of the packages.
+const OVERLAY_BACKUP_DIR: &str = "backup";
+
+/// The files that are copied over an installed package.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    /// The directory with the files, relative to the root of the project.
+    pub path: PathBuf,
+
+    /// The versions of the package the overlay applies to, the overlay is skipped for other
+    /// versions.
+    pub version: Option<VersionSpec>,
+}
+
+#[serde_as]
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OverlayTable {
+    path: PathBuf,
+    #[serde(default)]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    version: Option<VersionSpec>,
+}
+
+impl<'de> Deserialize<'de> for Overlay {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        serde_untagged::UntaggedEnumVisitor::new()
+            .string(|path| {
+                Ok(Overlay {
+                    path: PathBuf::from(path),
+                    version: None,
+                })
+            })
+            .map(|map| {
+                map.deserialize().map(|table: OverlayTable| Overlay {
+                    path: table.path,
+                    version: table.version,
+                })
+            })
+            .expecting("a path or a table with `path` and an optional `version`")
+            .deserialize(deserializer)
+    }
+}
+
+/// An overlay that is applied to an installed package.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct AppliedOverlay {
+    /// The version of the package the overlay was applied to.
+    version: String,
+
+    /// The build string of the package the overlay was applied to.
+    build: String,
+
+    /// The files that were written, relative to the environment, with the sha256 of their content.
+    files: BTreeMap<PathBuf, String>,
+
+    /// The files that existed before and of which a backup was made.
+    #[serde(default)]
+    backed_up: BTreeSet<PathBuf>,
+}
+
+impl AppliedOverlay {
+    /// Returns true if the overlay was applied to this exact package.
+    fn applies_to(&self, package: Option<&PackageRecord>) -> bool {
+        package.map_or(false, |package| {
+            package.version.to_string() == self.version && package.build == self.build
+        })
+    }
+
+    /// Returns true if the files in the environment still have the content of the overlay.
+    fn is_intact(&self, prefix: &Path) -> bool {
+        self.files
+            .iter()
+            .all(|(file, hash)| file_hash(&prefix.join(file)).ok().as_ref() == Some(hash))
+    }
+}
+
+/// The overlays that are applied to an environment, by package name.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+struct OverlayState {
+    packages: BTreeMap<String, AppliedOverlay>,
+}
+
+/// Returns the sha256 of the content of a file as a hex string.
+fn file_hash(path: &Path) -> std::io::Result<String> {
+    Ok(format!("{:x}", compute_file_digest::<Sha256>(path)?))
+}
+
+/// Recursively collects the files in `dir`, relative to `base`, in a deterministic order.
+fn collect_files(base: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(base, &entry, files)?;
+        } else {
+            files.push(
+                entry
+                    .strip_prefix(base)
+                    .expect("the entry is in the base directory")
+                    .to_path_buf(),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Returns the overlay that should be applied to `package` with the files in `source`.
+fn desired_overlay(source: &Path, package: &PackageRecord) -> std::io::Result<AppliedOverlay> {
+    let mut paths = Vec::new();
+    collect_files(source, source, &mut paths)?;
+    let mut files = BTreeMap::new();
+    for path in paths {
+        let hash = file_hash(&source.join(&path))?;
+        files.insert(path, hash);
+    }
+    Ok(AppliedOverlay {
+        version: package.version.to_string(),
+        build: package.build.clone(),
+        files,
+        backed_up: BTreeSet::new(),
+    })
+}
+
+/// Copies the files of the `overlay` from `source` into the environment at `prefix`. Existing
+/// files are moved into `backup` instead of being overwritten, because they are hard linked from
+/// the package cache. Every file is recorded in `applied` before it is touched, so an overlay that
+/// fails halfway can still be undone.
+fn apply_overlay(
+    prefix: &Path,
+    source: &Path,
+    backup: &Path,
+    overlay: &AppliedOverlay,
+    applied: &mut AppliedOverlay,
+) -> std::io::Result<()> {
+    for (file, hash) in &overlay.files {
+        let destination = prefix.join(file);
+        if destination.symlink_metadata().is_ok() {
+            let backup_path = backup.join(file);
+            if let Some(parent) = backup_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&destination, &backup_path)?;
+            applied.backed_up.insert(file.clone());
+        } else if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        applied.files.insert(file.clone(), hash.clone());
+        fs::copy(source.join(file), &destination)?;
+    }
+    Ok(())
+}
+
+/// Undoes an overlay. If the package it was applied to is still `installed` the original files are
+/// restored. Otherwise the files of the package were replaced when it was reinstalled or removed,
+/// and only the files that the overlay added are removed.
+fn remove_overlay(
+    prefix: &Path,
+    backup: &Path,
+    overlay: &AppliedOverlay,
+    installed: bool,
+) -> std::io::Result<()> {
+    for (file, hash) in &overlay.files {
+        let destination = prefix.join(file);
+        let backed_up = overlay.backed_up.contains(file);
+        if installed {
+            // A file whose backup is gone was already restored by an earlier attempt.
+            let backup_path = backup.join(file);
+            if backed_up && backup_path.symlink_metadata().is_err() {
+                continue;
+            }
+            if destination.symlink_metadata().is_ok() {
+                fs::remove_file(&destination)?;
+            }
+            if backed_up {
+                fs::rename(backup_path, &destination)?;
+            }
+        } else if !backed_up && file_hash(&destination).ok().as_ref() == Some(hash) {
+            fs::remove_file(&destination)?;
+        }
+    }
+    match fs::remove_dir_all(backup) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Applies the `overlays` with directories relative to `root` to the environment at `prefix` with
+/// the `installed` packages, and undoes the overlays that no longer apply.
+pub fn apply_overlays(
+    prefix: &Path,
+    root: &Path,
+    overlays: &IndexMap<PackageName, Overlay>,
+    installed: &[PackageRecord],
+) -> miette::Result<()> {
+    let overlay_dir = prefix.join("conda-meta").join(OVERLAY_DIR);
+    let previous = fs::read_to_string(overlay_dir.join(OVERLAY_STATE_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str::<OverlayState>(&content).ok())
+        .unwrap_or_default();
+    let find_package = |name: &str| {
+        installed
+            .iter()
+            .find(|package| package.name.as_normalized() == name)
+    };
+    let backup_dir = |name: &str| overlay_dir.join(OVERLAY_BACKUP_DIR).join(name);
+
+    // Determine the overlays that should be applied
+    let mut desired = BTreeMap::new();
+    for (name, overlay) in overlays {
+        let Some(package) = find_package(name.as_normalized()) else {
+            tracing::warn!(
+                "the overlay of {} is not applied because the package is not installed",
+                name.as_source()
+            );
+            continue;
+        };
+        if let Some(version) = &overlay.version {
+            if !version.matches(&package.version) {
+                tracing::warn!(
+                    "the overlay of {} is not applied because it is for version {version}, but {} is installed",
+                    name.as_source(),
+                    package.version
+                );
+                continue;
+            }
+        }
+        let source = root.join(&overlay.path);
+        if !source.is_dir() {
+            miette::bail!(
+                help = "the path of an overlay is a directory relative to the project",
+                "the overlay of {} at '{}' does not exist",
+                name.as_source(),
+                source.display()
+            );
+        }
+        let overlay = desired_overlay(&source, package)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read the overlay at '{}'", source.display()))?;
+        desired.insert(name.as_normalized().to_string(), (source, overlay));
+    }
+
+    // Keep the overlays that are still applied, undo the others
+    let mut state = OverlayState::default();
+    for (name, applied) in &previous.packages {
+        let installed = applied.applies_to(find_package(name));
+        let unchanged = desired
+            .get(name)
+            .map_or(false, |(_, overlay)| overlay.files == applied.files);
+        if installed && unchanged && applied.is_intact(prefix) {
+            desired.remove(name);
+            state.packages.insert(name.clone(), applied.clone());
+            continue;
+        }
+        remove_overlay(prefix, &backup_dir(name), applied, installed)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to undo the overlay of {name}"))?;
+    }
+
+    // Apply the new and changed overlays
+    for (name, (source, overlay)) in desired {
+        let mut applied = AppliedOverlay {
+            files: BTreeMap::new(),
+            backed_up: BTreeSet::new(),
+            ..overlay.clone()
+        };
+        let result = apply_overlay(prefix, &source, &backup_dir(&name), &overlay, &mut applied);
+        state.packages.insert(name.clone(), applied);
+        if let Err(err) = result {
+            // Record the files that were replaced, so the next run restores them from the backup
+            // instead of moving the copy of the overlay over it.
+            write_state(&overlay_dir, &state)?;
+            return Err(err)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to apply the overlay of {name}"));
+        }
+        tracing::info!(
+            "applied the overlay of {name} ({} files)",
+            overlay.files.len()
+        );
+    }
+
+    if state == previous {
+        return Ok(());
+    }
+    write_state(&overlay_dir, &state)
+}
+
+/// Writes the state of the overlays to `overlay_dir`, or removes the directory if no overlays are
+/// applied.
+fn write_state(overlay_dir: &Path, state: &OverlayState) -> miette::Result<()> {
+    if state.packages.is_empty() {
+        return match fs::remove_dir_all(overlay_dir) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err).into_diagnostic(),
+            _ => Ok(()),
+        };
+    }
+    let state_path = overlay_dir.join(OVERLAY_STATE_FILE);
+    fs::create_dir_all(overlay_dir).into_diagnostic()?;
+    fs::write(
+        &state_path,
+        serde_json::to_string_pretty(state).expect("the state can be serialized"),
+    )
+    .into_diagnostic()
+    .wrap_err_with(|| format!("failed to write '{}'", state_path.display()))
+}
+
+/// Returns a line for every overlay with the content of its files, which identifies the overlays
+/// in the hash of a shared environment. Environments with different overlays can't share a prefix.
+pub fn overlay_fingerprints(
+    root: &Path,
+    overlays: &IndexMap<PackageName, Overlay>,
+) -> miette::Result<Vec<String>> {
+    let mut fingerprints = Vec::new();
+    for (name, overlay) in overlays {
+        let source = root.join(&overlay.path);
+        let mut files = Vec::new();
+        if source.is_dir() {
+            collect_files(&source, &source, &mut files)
+                .into_diagnostic()
+                .wrap_err_with(|| {
+                    format!("failed to read the overlay at '{}'", source.display())
+                })?;
+        }
+        let mut fingerprint = format!(
+            "{} {}",
+            name.as_normalized(),
+            overlay
+                .version
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_default()
+        );
+        for file in files {
+            let hash = file_hash(&source.join(&file)).into_diagnostic()?;
+            fingerprint.push_str(&format!(" {}={hash}", file.display()));
+        }
+        fingerprints.push(fingerprint);
+    }
+    Ok(fingerprints)
+}
+
+/// Applies the overlays of the project to the environment at `prefix`, after its packages are
+/// installed.
+pub async fn update_overlays(project: &Project, prefix: &Prefix) -> miette::Result<()> {
+    let overlays = project.overlays();
+    if overlays.is_empty() && !prefix.root().join("conda-meta").join(OVERLAY_DIR).exists() {
+        return Ok(());
+    }
+    let installed = prefix
+        .find_installed_packages(None)
+        .await?
+        .into_iter()
+        .map(|record| record.repodata_record.package_record)
+        .collect::<Vec<_>>();
+    apply_overlays(prefix.root(), project.root(), overlays, &installed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rattler_conda_types::Version;
+    use std::str::FromStr;
+
+    fn overlays(version: Option<&str>) -> IndexMap<PackageName, Overlay> {
+        IndexMap::from([(
+            PackageName::new_unchecked("foo"),
+            Overlay {
+                path: PathBuf::from("overlays/foo"),
+                version: version.map(|v| VersionSpec::from_str(v).unwrap()),
+            },
+        )])
+    }
+
+    #[test]
+    fn test_deserialize_overlay() {
+        let overlays: IndexMap<PackageName, Overlay> = toml_edit::de::from_str(
+            r#"
+            numpy = "overlays/numpy"
+            scipy = { path = "overlays/scipy", version = "==1.11.4" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            overlays[&PackageName::new_unchecked("numpy")].path,
+            Path::new("overlays/numpy")
+        );
+        let scipy = &overlays[&PackageName::new_unchecked("scipy")];
+        assert_eq!(scipy.version.as_ref().unwrap().to_string(), "==1.11.4");
+
+        assert!(toml_edit::de::from_str::<IndexMap<PackageName, Overlay>>(
+            r#"numpy = { dir = "overlays/numpy" }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_apply_overlays() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        let prefix = dir.path().join("env");
+        let source = root.join("overlays/foo/lib/foo");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(prefix.join("lib/foo")).unwrap();
+        fs::create_dir_all(prefix.join("conda-meta")).unwrap();
+        fs::write(source.join("core.py"), "fixed").unwrap();
+        fs::write(source.join("extra.py"), "added").unwrap();
+        fs::write(prefix.join("lib/foo/core.py"), "broken").unwrap();
+        let installed = [PackageRecord::new(
+            PackageName::new_unchecked("foo"),
+            Version::from_str("1.0").unwrap(),
+            String::from("0"),
+        )];
+
+        // The files are replaced and the original is kept
+        apply_overlays(&prefix, &root, &overlays(None), &installed).unwrap();
+        assert_eq!(
+            fs::read_to_string(prefix.join("lib/foo/core.py")).unwrap(),
+            "fixed"
+        );
+        assert_eq!(
+            fs::read_to_string(prefix.join("lib/foo/extra.py")).unwrap(),
+            "added"
+        );
+
+        // Applying again changes nothing, a changed overlay is applied again
+        apply_overlays(&prefix, &root, &overlays(None), &installed).unwrap();
+        fs::write(source.join("core.py"), "fixed again").unwrap();
+        apply_overlays(&prefix, &root, &overlays(None), &installed).unwrap();
+        assert_eq!(
+            fs::read_to_string(prefix.join("lib/foo/core.py")).unwrap(),
+            "fixed again"
+        );
+
+        // An overlay for another version is undone
+        apply_overlays(&prefix, &root, &overlays(Some(">=2")), &installed).unwrap();
+        assert_eq!(
+            fs::read_to_string(prefix.join("lib/foo/core.py")).unwrap(),
+            "broken"
+        );
+        assert!(!prefix.join("lib/foo/extra.py").exists());
+        assert!(!prefix.join("conda-meta").join(OVERLAY_DIR).exists());
+
+        // A reinstalled package gets the overlay again
+        apply_overlays(&prefix, &root, &overlays(None), &installed).unwrap();
+        fs::remove_file(prefix.join("lib/foo/core.py")).unwrap();
+        fs::write(prefix.join("lib/foo/core.py"), "broken").unwrap();
+        apply_overlays(&prefix, &root, &overlays(None), &installed).unwrap();
+        assert_eq!(
+            fs::read_to_string(prefix.join("lib/foo/core.py")).unwrap(),
+            "fixed again"
+        );
+    }
+
+    #[test]
+    fn test_apply_overlays_partial_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        let prefix = dir.path().join("env");
+        let source = root.join("overlays/foo/lib/foo");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        fs::create_dir_all(prefix.join("lib/foo")).unwrap();
+        fs::create_dir_all(prefix.join("conda-meta")).unwrap();
+        fs::write(source.join("core.py"), "fixed").unwrap();
+        fs::write(source.join("sub/extra.py"), "added").unwrap();
+        fs::write(prefix.join("lib/foo/core.py"), "broken").unwrap();
+        // A file where the overlay needs a directory, so the second file can't be written
+        fs::write(prefix.join("lib/foo/sub"), "").unwrap();
+        let installed = [PackageRecord::new(
+            PackageName::new_unchecked("foo"),
+            Version::from_str("1.0").unwrap(),
+            String::from("0"),
+        )];
+
+        assert!(apply_overlays(&prefix, &root, &overlays(None), &installed).is_err());
+        assert_eq!(
+            fs::read_to_string(prefix.join("lib/foo/core.py")).unwrap(),
+            "fixed"
+        );
+
+        // The next run undoes the partial overlay instead of losing the original file
+        fs::remove_file(prefix.join("lib/foo/sub")).unwrap();
+        apply_overlays(&prefix, &root, &overlays(None), &installed).unwrap();
+        assert_eq!(
+            fs::read_to_string(prefix.join("lib/foo/sub/extra.py")).unwrap(),
+            "added"
+        );
+        apply_overlays(&prefix, &root, &IndexMap::new(), &installed).unwrap();
+        assert_eq!(
+            fs::read_to_string(prefix.join("lib/foo/core.py")).unwrap(),
+            "broken"
+        );
+    }
+
+    #[test]
+    fn test_overlay_fingerprints() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("overlays/foo/lib");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("core.py"), "fixed").unwrap();
+
+        let first = overlay_fingerprints(dir.path(), &overlays(None)).unwrap();
+        assert_eq!(first.len(), 1);
+        assert!(first[0].starts_with("foo  lib/core.py="));
+        fs::write(source.join("core.py"), "fixed again").unwrap();
+        assert_ne!(
+            overlay_fingerprints(dir.path(), &overlays(None)).unwrap(),
+            first
+        );
+        assert!(overlay_fingerprints(dir.path(), &IndexMap::new())
+            .unwrap()
+            .is_empty());
+    }
+}
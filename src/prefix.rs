@@ -39,7 +39,8 @@ impl Prefix {
         {
             let entry = entry.into_diagnostic()?;
             let path = entry.path();
-            if path.ends_with(".json") {
+            // Only json files are package records, skip e.g. the directories pixi keeps its state in
+            if !path.is_file() || path.extension().map_or(true, |ext| ext != "json") {
                 continue;
             }
 
@@ -0,0 +1,161 @@
+//! Opt-in to pre-release versions of single packages.
+//!
+//! Conda channels publish release candidates under a label, e.g. `conda-forge/label/dev`. When a
+//! project lists packages in `prerelease-packages`, the pre-release labels of its channels are
+//! fetched as well, but only the records of those packages are kept from them, so the rest of the
+//! environment is solved exactly as without the opt-in.
+
+use crate::Project;
+use rattler_conda_types::{Channel, ChannelConfig, PackageName, RepoDataRecord};
+
+/// The label of the channels that is used when `prerelease-labels` is not set.
+pub const DEFAULT_PRERELEASE_LABEL: &str = "dev";
+
+/// Returns true if the channel is a label of another channel, e.g. `conda-forge/label/dev`.
+pub fn is_label_channel(channel: &Channel) -> bool {
+    channel.base_url().path().contains("/label/")
+}
+
+/// Returns the channels of the `labels` of every channel. Local channels and channels that are a
+/// label themselves have no labels.
+pub fn label_channels(channels: &[Channel], labels: &[String]) -> Vec<Channel> {
+    channels
+        .iter()
+        .filter(|channel| channel.base_url().scheme() != "file" && !is_label_channel(channel))
+        .flat_map(|channel| {
+            labels.iter().filter_map(move |label| {
+                let url = format!("{}label/{label}", channel.base_url());
+                Channel::from_str(&url, &ChannelConfig::default())
+                    .map_err(|err| tracing::warn!("invalid pre-release label '{label}': {err}"))
+                    .ok()
+            })
+        })
+        .collect()
+}
+
+/// Removes the records that come from one of the `label_channels` unless they are one of the
+/// `allowed` packages.
+pub fn retain_allowed_records(
+    available_packages: &mut [Vec<RepoDataRecord>],
+    label_channels: &[Channel],
+    allowed: &[PackageName],
+) {
+    if label_channels.is_empty() {
+        return;
+    }
+    let label_urls = label_channels
+        .iter()
+        .map(|channel| channel.canonical_name())
+        .collect::<Vec<_>>();
+    let is_label_record = |record: &RepoDataRecord| {
+        let channel = record.channel.trim_end_matches('/');
+        label_urls
+            .iter()
+            .any(|url| url.trim_end_matches('/') == channel)
+    };
+    for records in available_packages.iter_mut() {
+        records.retain(|record| {
+            !is_label_record(record) || allowed.contains(&record.package_record.name)
+        });
+    }
+}
+
+impl Project {
+    /// Returns the conda packages of which pre-release versions can be selected.
+    pub fn prerelease_packages(&self) -> &[PackageName] {
+        &self.manifest.parsed.project.prerelease_packages
+    }
+
+    /// Returns the labels of the channels that contain the pre-release versions.
+    pub fn prerelease_labels(&self) -> Vec<String> {
+        self.manifest
+            .parsed
+            .project
+            .prerelease_labels
+            .clone()
+            .unwrap_or_else(|| vec![String::from(DEFAULT_PRERELEASE_LABEL)])
+    }
+
+    /// Returns the channels that contain the pre-release versions, empty if the project has no
+    /// `prerelease-packages`.
+    pub fn prerelease_channels(&self) -> Vec<Channel> {
+        if self.prerelease_packages().is_empty() {
+            return Vec::new();
+        }
+        label_channels(self.channels(), &self.prerelease_labels())
+    }
+
+    /// Removes the records of the pre-release channels that are not one of the
+    /// `prerelease-packages` of the project.
+    pub fn retain_allowed_prereleases(&self, available_packages: &mut [Vec<RepoDataRecord>]) {
+        retain_allowed_records(
+            available_packages,
+            &self.prerelease_channels(),
+            self.prerelease_packages(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::repodata_record;
+
+    fn record(name: &str, channel: &str) -> RepoDataRecord {
+        repodata_record(name, "2.0.0rc1", channel)
+    }
+
+    #[test]
+    fn test_label_channels() {
+        let channels = [
+            Channel::from_str("conda-forge", &ChannelConfig::default()).unwrap(),
+            Channel::from_str("conda-forge/label/python_rc", &ChannelConfig::default()).unwrap(),
+        ];
+        let labels = label_channels(&channels, &[String::from("dev")]);
+        assert_eq!(labels.len(), 1);
+        assert_eq!(
+            labels[0].canonical_name(),
+            "https://conda.anaconda.org/conda-forge/label/dev/"
+        );
+        assert!(is_label_channel(&labels[0]));
+        assert!(!is_label_channel(&channels[0]));
+    }
+
+    #[test]
+    fn test_retain_allowed_records() {
+        let label = Channel::from_str("conda-forge/label/dev", &ChannelConfig::default()).unwrap();
+        let mut available = vec![
+            vec![record("numpy", "https://conda.anaconda.org/conda-forge/")],
+            vec![
+                record("numpy", "https://conda.anaconda.org/conda-forge/label/dev/"),
+                record(
+                    "python",
+                    "https://conda.anaconda.org/conda-forge/label/dev/",
+                ),
+            ],
+        ];
+        retain_allowed_records(
+            &mut available,
+            &[label],
+            &[PackageName::new_unchecked("numpy")],
+        );
+        let names = available
+            .iter()
+            .flatten()
+            .map(|record| (record.package_record.name.as_normalized(), &record.channel))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                (
+                    "numpy",
+                    &String::from("https://conda.anaconda.org/conda-forge/")
+                ),
+                (
+                    "numpy",
+                    &String::from("https://conda.anaconda.org/conda-forge/label/dev/")
+                ),
+            ]
+        );
+    }
+}
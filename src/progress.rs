@@ -1,3 +1,4 @@
+use crate::ci;
 use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressState};
 use once_cell::sync::Lazy;
 use std::borrow::Cow;
@@ -12,10 +13,16 @@ use tokio::sync::mpsc::{channel, Sender};
 /// Although you can always create an instance yourself any logging will interrupt pending
 /// progressbars. To fix this issue, logging has been configured in such a way to it will not
 /// interfere if you use the [`indicatif::MultiProgress`] returning by this function.
+///
+/// On CI the progress bars are hidden.
 pub fn global_multi_progress() -> MultiProgress {
     static GLOBAL_MP: Lazy<MultiProgress> = Lazy::new(|| {
         let mp = MultiProgress::new();
-        mp.set_draw_target(ProgressDrawTarget::stderr_with_hz(20));
+        if ci::is_ci() {
+            mp.set_draw_target(ProgressDrawTarget::hidden());
+        } else {
+            mp.set_draw_target(ProgressDrawTarget::stderr_with_hz(20));
+        }
         mp
     });
     GLOBAL_MP.clone()
@@ -5,3 +5,10 @@ use serde::Deserialize;
 pub struct Activation {
     pub scripts: Option<Vec<String>>,
 }
+
+/// The scripts that run when an environment is deactivated, i.e. when `pixi shell` exits.
+#[derive(Default, Clone, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Deactivation {
+    pub scripts: Option<Vec<String>>,
+}
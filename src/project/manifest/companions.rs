@@ -0,0 +1,207 @@
+use super::feature::FeatureName;
+use super::target::TargetSelector;
+use super::variants::RawManifest;
+use crate::utils::spanned::PixiSpanned;
+use indexmap::IndexMap;
+use miette::{LabeledSpan, NamedSource, Report};
+use rattler_conda_types::{MatchSpec, NamelessMatchSpec, PackageName, Platform};
+use std::str::FromStr;
+
+/// The companion packages of the dependencies of the project, which model optional capabilities
+/// of a package that are shipped as separate packages, e.g. the encoders of `ffmpeg`.
+///
+/// A dependency selects its companions with the `with` key, e.g.
+/// `ffmpeg = { version = "6.*", with = ["x264", "svt-av1 >=1.8"] }`. The companions are added to
+/// the dependencies of the same kind when an environment is solved that selects the feature and
+/// the target the dependency is declared in.
+#[derive(Debug, Clone, Default)]
+pub struct Companions {
+    /// The companions by the location of the dependency that pulls them in and its normalized
+    /// name.
+    companions: IndexMap<Location, IndexMap<String, Vec<(PackageName, NamelessMatchSpec)>>>,
+}
+
+/// Where a dependency is declared: its feature and its target, `None` for the dependencies that
+/// apply to all platforms.
+type Location = (FeatureName, Option<TargetSelector>);
+
+impl Companions {
+    /// Returns the companions of the package with the given normalized name that is declared in
+    /// the given feature and target.
+    pub fn of(
+        &self,
+        feature: &FeatureName,
+        target: Option<&TargetSelector>,
+        package: &str,
+    ) -> &[(PackageName, NamelessMatchSpec)] {
+        self.companions
+            .get(&(feature.clone(), target.cloned()))
+            .and_then(|companions| companions.get(package))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns true if no dependency has companions.
+    pub fn is_empty(&self) -> bool {
+        self.companions.is_empty()
+    }
+
+    /// Adds the companions of the default feature of another project, e.g. of the root of the
+    /// workspace. The other features of that project are never part of the environments of this
+    /// one.
+    pub fn extend_default(&mut self, other: &Companions) {
+        let default_targets = other
+            .companions
+            .iter()
+            .filter(|((feature, _), _)| *feature == FeatureName::Default);
+        for (location, packages) in default_targets {
+            let target = self.companions.entry(location.clone()).or_default();
+            for (package, companions) in packages {
+                let entry = target.entry(package.clone()).or_default();
+                for (name, spec) in companions {
+                    if !entry.iter().any(|(other, _)| other == name) {
+                        entry.push((name.clone(), spec.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads the companions of the dependencies from the manifest. Returns `None` if the contents
+    /// are not valid TOML, the parser of the manifest reports that error.
+    pub fn from_toml_str(contents: &str) -> Option<Result<Self, Report>> {
+        let manifest: RawManifest = toml_edit::de::from_str(contents).ok()?;
+        let mut selected = Vec::new();
+        for (feature, selector, target) in manifest.located_targets() {
+            let feature = feature.map_or(FeatureName::Default, |name| {
+                FeatureName::Named(name.to_owned())
+            });
+            // The parser of the manifest reports invalid target selectors.
+            let Ok(selector) = selector
+                .map(|selector| Platform::from_str(selector).map(TargetSelector::Platform))
+                .transpose()
+            else {
+                continue;
+            };
+            for (name, dependency) in target.dependencies() {
+                if !dependency.with.is_empty() {
+                    selected.push((
+                        (feature.clone(), selector.clone()),
+                        name.as_str(),
+                        dependency.with.as_slice(),
+                    ));
+                }
+            }
+        }
+
+        Some(Self::from_selected(selected).map_err(|(span, message)| {
+            miette::miette!(
+                labels = vec![LabeledSpan::at(span, message)],
+                help = "the companions of a dependency are match specs of other packages, e.g. `with = [\"x264\", \"svt-av1 >=1.8\"]`",
+                "invalid dependency companion"
+            )
+            .with_source_code(NamedSource::new(
+                crate::consts::PROJECT_MANIFEST,
+                contents.to_owned(),
+            ))
+        }))
+    }
+
+    fn from_selected(
+        selected: Vec<(Location, &str, &[PixiSpanned<String>])>,
+    ) -> Result<Self, (std::ops::Range<usize>, String)> {
+        let mut companions = Self::default();
+        for (location, package, with) in selected {
+            let package = PackageName::from_str(package)
+                .map(|name| name.as_normalized().to_owned())
+                .unwrap_or_else(|_| package.to_lowercase());
+            let target = companions.companions.entry(location).or_default();
+            for companion in with.iter() {
+                let span = companion.span().unwrap_or_default();
+                let spec = MatchSpec::from_str(companion.get_ref())
+                    .map_err(|err| (span.clone(), err.to_string()))?;
+                let (Some(name), spec) = spec.into_nameless() else {
+                    return Err((span, String::from("the companion has no package name")));
+                };
+                if name.as_normalized() == package {
+                    return Err((span, format!("'{package}' can't be its own companion")));
+                }
+
+                // A package that pulls in a companion more than once uses the last spec.
+                let entry = target.entry(package.clone()).or_default();
+                entry.retain(|(other, _)| other != &name);
+                entry.push((name, spec));
+            }
+        }
+        Ok(companions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_companions() {
+        let contents = r#"
+        [project]
+        name = "foo"
+        channels = []
+        platforms = ["linux-64"]
+
+        [dependencies]
+        python = "3.11.*"
+        ffmpeg = { version = "6.*", with = ["x264", "svt-av1 >=1.8"] }
+
+        [target.linux-64.dependencies]
+        gdal = { with = ["libgdal-arrow-parquet"] }
+
+        [feature.gpu.dependencies]
+        opencv = { version = "4.*", with = ["libopencv-cuda"] }
+        "#;
+        let companions = Companions::from_toml_str(contents).unwrap().unwrap();
+        let gpu = FeatureName::Named(String::from("gpu"));
+        let linux = TargetSelector::Platform(Platform::Linux64);
+        let names = |feature: &FeatureName, target: Option<&TargetSelector>, package: &str| {
+            companions
+                .of(feature, target, package)
+                .iter()
+                .map(|(name, spec)| match &spec.version {
+                    Some(version) => format!("{} {version}", name.as_source()),
+                    None => name.as_source().to_owned(),
+                })
+                .collect::<Vec<_>>()
+        };
+        let default = FeatureName::Default;
+        assert_eq!(names(&default, None, "ffmpeg"), ["x264", "svt-av1 >=1.8"]);
+        assert_eq!(
+            names(&default, Some(&linux), "gdal"),
+            ["libgdal-arrow-parquet"]
+        );
+        assert_eq!(names(&gpu, None, "opencv"), ["libopencv-cuda"]);
+        assert!(names(&default, None, "python").is_empty());
+
+        // The companions only apply to the feature and target they are declared in.
+        assert!(names(&default, None, "gdal").is_empty());
+        assert!(names(&default, None, "opencv").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_companions() {
+        let manifest = |dependencies: &str| {
+            format!("[project]\nname = \"foo\"\n\n[dependencies]\n{dependencies}")
+        };
+        for dependencies in [
+            r#"ffmpeg = { version = "6.*", with = ["ffmpeg"] }"#,
+            r#"ffmpeg = { version = "6.*", with = ["*"] }"#,
+            r#"ffmpeg = { version = "6.*", with = ["x264 >=>1"] }"#,
+        ] {
+            assert!(
+                Companions::from_toml_str(&manifest(dependencies))
+                    .unwrap()
+                    .is_err(),
+                "{dependencies}"
+            );
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::utils::spanned::PixiSpanned;
+use serde::Deserialize;
 
 /// The name of an environment. This is either a string or default for the default environment.
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Hash)]
@@ -36,3 +37,58 @@ pub struct Environment {
     /// dependencies of the environment that share the same solve-group will be solved together.
     pub solve_group: Option<String>,
 }
+
+/// An environment in the `[environments]` table, either a list of features or a table with the
+/// features and optionally the solve-group of the environment.
+#[derive(Debug, Clone)]
+pub struct TomlEnvironment {
+    pub features: Vec<String>,
+    /// Only set in the table form, see [`Environment::solve_group`].
+    pub solve_group: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for TomlEnvironment {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields, rename_all = "kebab-case")]
+        struct FeaturesAndSolveGroup {
+            features: Vec<String>,
+            #[serde(default)]
+            solve_group: Option<String>,
+        }
+
+        serde_untagged::UntaggedEnumVisitor::new()
+            .seq(|seq| {
+                Ok(TomlEnvironment {
+                    features: seq.deserialize()?,
+                    solve_group: None,
+                })
+            })
+            .map(|map| {
+                let FeaturesAndSolveGroup {
+                    features,
+                    solve_group,
+                } = map.deserialize()?;
+                Ok(TomlEnvironment {
+                    features,
+                    solve_group,
+                })
+            })
+            .expecting("a list of features or a mapping with `features` and `solve-group`")
+            .deserialize(deserializer)
+    }
+}
+
+impl TomlEnvironment {
+    /// Converts the environment into an [`Environment`] with the given name.
+    pub fn into_environment(self, name: String) -> Environment {
+        Environment {
+            name: EnvironmentName::Named(name),
+            features: self.features.into(),
+            solve_group: self.solve_group,
+        }
+    }
+}
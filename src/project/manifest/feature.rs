@@ -1,9 +1,15 @@
-use super::SystemRequirements;
-use crate::project::manifest::target::Targets;
+use super::{Activation, Deactivation, PyPiRequirement, SystemRequirements};
+use crate::project::manifest::target::{Target, TargetSelector, Targets};
+use crate::project::SpecType;
+use crate::task::Task;
+use crate::utils::interpolate::interpolate_env;
 use crate::utils::spanned::PixiSpanned;
-use rattler_conda_types::{Channel, Platform};
+use indexmap::IndexMap;
+use rattler_conda_types::{Channel, ChannelConfig, NamelessMatchSpec, PackageName, Platform};
 use serde::de::Error;
 use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, PickFirst};
+use std::collections::HashMap;
 
 /// The name of a feature. This is either a string or default for the default feature.
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Ord, Hash)]
@@ -36,6 +42,48 @@ impl FeatureName {
     }
 }
 
+/// A channel of a feature with an optional priority. When an environment combines multiple
+/// features, channels with a higher priority are used before channels with a lower one. Channels
+/// without a priority have a priority of 0, like the channels of the project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrioritizedChannel {
+    pub channel: Channel,
+    pub priority: Option<i32>,
+}
+
+impl<'de> Deserialize<'de> for PrioritizedChannel {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[serde_as]
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct ChannelAndPriority {
+            #[serde_as(as = "super::serde::ChannelStr")]
+            channel: Channel,
+            priority: Option<i32>,
+        }
+
+        serde_untagged::UntaggedEnumVisitor::new()
+            .string(|channel| {
+                let channel = interpolate_env(channel).map_err(Error::custom)?;
+                Channel::from_str(channel, &ChannelConfig::default())
+                    .map(|channel| PrioritizedChannel {
+                        channel,
+                        priority: None,
+                    })
+                    .map_err(Error::custom)
+            })
+            .map(|map| {
+                let ChannelAndPriority { channel, priority } = map.deserialize()?;
+                Ok(PrioritizedChannel { channel, priority })
+            })
+            .expecting("a channel or a mapping with `channel` and `priority`")
+            .deserialize(deserializer)
+    }
+}
+
 /// A feature describes a set of functionalities. It allows us to group functionality and its
 /// dependencies together.
 ///
@@ -56,7 +104,7 @@ pub struct Feature {
     ///
     /// This value is `None` if this feature does not specify any channels and the default
     /// channels from the project should be used.
-    pub channels: Option<Vec<Channel>>,
+    pub channels: Option<Vec<PrioritizedChannel>>,
 
     /// Additional system requirements
     pub system_requirements: SystemRequirements,
@@ -64,3 +112,82 @@ pub struct Feature {
     /// Target specific configuration.
     pub targets: Targets,
 }
+
+/// A feature in the `[feature.<name>]` table of the manifest.
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct TomlFeature {
+    /// The channels the feature adds to the environments that use it.
+    #[serde(default)]
+    pub channels: Option<Vec<PrioritizedChannel>>,
+
+    /// The system requirements the feature adds to the environments that use it.
+    #[serde(
+        default,
+        deserialize_with = "super::system_requirements::deserialize_system_requirements"
+    )]
+    pub system_requirements: SystemRequirements,
+
+    /// Target specific configuration of the feature
+    #[serde(default)]
+    pub target: IndexMap<PixiSpanned<TargetSelector>, Target>,
+
+    // The keys of the default target are copied from the Target deserialize implementation, see
+    // the deserialization of the manifest for why this is not flattened.
+    #[serde(default)]
+    #[serde_as(as = "IndexMap<_, PickFirst<(DisplayFromStr, _)>>")]
+    pub dependencies: IndexMap<PackageName, NamelessMatchSpec>,
+
+    #[serde(default)]
+    #[serde_as(as = "Option<IndexMap<_, PickFirst<(DisplayFromStr, _)>>>")]
+    pub host_dependencies: Option<IndexMap<PackageName, NamelessMatchSpec>>,
+
+    #[serde(default)]
+    #[serde_as(as = "Option<IndexMap<_, PickFirst<(DisplayFromStr, _)>>>")]
+    pub build_dependencies: Option<IndexMap<PackageName, NamelessMatchSpec>>,
+
+    #[serde(default)]
+    pub pypi_dependencies: Option<IndexMap<rip::types::PackageName, PyPiRequirement>>,
+
+    /// Additional information to activate the environments that use the feature.
+    #[serde(default)]
+    pub activation: Option<Activation>,
+
+    /// The scripts that run when the environments that use the feature are deactivated.
+    #[serde(default)]
+    pub deactivation: Option<Deactivation>,
+
+    /// The tasks the feature adds to the environments that use it.
+    #[serde(default)]
+    pub tasks: HashMap<String, Task>,
+}
+
+impl TomlFeature {
+    /// Converts the feature into a [`Feature`] with the given name.
+    pub fn into_feature(self, name: FeatureName) -> Feature {
+        let mut dependencies = HashMap::from_iter([(SpecType::Run, self.dependencies)]);
+        if let Some(host_deps) = self.host_dependencies {
+            dependencies.insert(SpecType::Host, host_deps);
+        }
+        if let Some(build_deps) = self.build_dependencies {
+            dependencies.insert(SpecType::Build, build_deps);
+        }
+
+        let default_target = Target {
+            dependencies,
+            pypi_dependencies: self.pypi_dependencies,
+            activation: self.activation,
+            deactivation: self.deactivation,
+            tasks: self.tasks,
+        };
+
+        Feature {
+            name,
+            platforms: None,
+            channels: self.channels,
+            system_requirements: self.system_requirements,
+            targets: Targets::from_default_and_user_defined(default_target, self.target),
+        }
+    }
+}
@@ -0,0 +1,506 @@
+//! A manifest can be split into several files with `include = ["tasks.toml", "deps/cuda.toml"]`
+//! at the top of `pixi.toml`, e.g. to keep the tasks or the dependencies of a platform apart in a
+//! large project. An included file has the tables of a manifest that describe what is installed
+//! and run: the dependency tables, `system-requirements`, `activation`, `deactivation`, `tasks`,
+//! `target` and `feature`. The files are merged key by key, a key in `pixi.toml` takes precedence
+//! over the same key in an included file, and a key in a later included file over the same key in
+//! an earlier one.
+
+use super::activation::{Activation, Deactivation};
+use super::feature::{Feature, FeatureName, TomlFeature};
+use super::python::PyPiRequirement;
+use super::system_requirements::SystemRequirements;
+use super::target::{Target, TargetSelector, Targets};
+use super::variants::RawManifest;
+use super::{create_unsupported_platform_report, ProjectManifest, SpecType};
+use crate::consts;
+use crate::task::Task;
+use crate::utils::spanned::PixiSpanned;
+use indexmap::IndexMap;
+use miette::{Context, IntoDiagnostic, LabeledSpan, NamedSource};
+use rattler_conda_types::{NamelessMatchSpec, PackageName};
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, PickFirst};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The tables of an included file.
+#[derive(Debug)]
+pub struct IncludedManifest {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The name of the file in the `include` array, errors point at it.
+    name: String,
+    contents: String,
+    system_requirements: SystemRequirements,
+    default_target: Target,
+    target: IndexMap<PixiSpanned<TargetSelector>, Target>,
+    feature: IndexMap<FeatureName, TomlFeature>,
+}
+
+impl IncludedManifest {
+    /// Reads the included file `name`, relative to the `root` of the project.
+    fn from_path(root: &Path, name: &str) -> miette::Result<Self> {
+        let path = root.join(name);
+        let contents = std::fs::read_to_string(&path).into_diagnostic()?;
+        let source = || NamedSource::new(name, contents.clone());
+
+        // The keys are copied from the deserialization of the manifest, see there why the default
+        // target is not flattened.
+        #[serde_as]
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields, rename_all = "kebab-case")]
+        struct TomlIncludedManifest {
+            #[serde(
+                default,
+                deserialize_with = "super::system_requirements::deserialize_system_requirements"
+            )]
+            system_requirements: SystemRequirements,
+            #[serde(default)]
+            target: IndexMap<PixiSpanned<TargetSelector>, Target>,
+            #[serde(default)]
+            #[serde_as(as = "IndexMap<_, PickFirst<(DisplayFromStr, _)>>")]
+            dependencies: IndexMap<PackageName, NamelessMatchSpec>,
+            #[serde(default)]
+            #[serde_as(as = "Option<IndexMap<_, PickFirst<(DisplayFromStr, _)>>>")]
+            host_dependencies: Option<IndexMap<PackageName, NamelessMatchSpec>>,
+            #[serde(default)]
+            #[serde_as(as = "Option<IndexMap<_, PickFirst<(DisplayFromStr, _)>>>")]
+            build_dependencies: Option<IndexMap<PackageName, NamelessMatchSpec>>,
+            #[serde(default)]
+            pypi_dependencies: Option<IndexMap<rip::types::PackageName, PyPiRequirement>>,
+            #[serde(default)]
+            activation: Option<Activation>,
+            #[serde(default)]
+            deactivation: Option<Deactivation>,
+            #[serde(default)]
+            tasks: HashMap<String, Task>,
+            #[serde(default)]
+            feature: IndexMap<FeatureName, TomlFeature>,
+        }
+
+        let included: TomlIncludedManifest = match toml_edit::de::from_str(&contents) {
+            Ok(included) => included,
+            Err(err) => {
+                let Some(span) = err.span() else {
+                    return Err(err)
+                        .into_diagnostic()
+                        .wrap_err_with(|| format!("failed to parse the included file '{name}'"));
+                };
+                return Err(miette::miette!(
+                    labels = vec![LabeledSpan::at(span, err.message())],
+                    help = "an included file can only have the dependency tables, `system-requirements`, `activation`, `deactivation`, `tasks`, `target` and `feature`",
+                    "failed to parse the included file '{name}'"
+                )
+                .with_source_code(source()));
+            }
+        };
+
+        // Variants and companions are only read from the manifest itself.
+        if let Ok(raw) = toml_edit::de::from_str::<RawManifest>(&contents) {
+            for target in raw.targets_with_features() {
+                for (_, dependency) in target.dependencies() {
+                    let span = dependency
+                        .variant
+                        .as_ref()
+                        .or(dependency.with.first())
+                        .and_then(PixiSpanned::span);
+                    if let Some(span) = span {
+                        return Err(miette::miette!(
+                            labels = vec![LabeledSpan::at(span, "not allowed here")],
+                            help = format!("move the dependency to `{}`", consts::PROJECT_MANIFEST),
+                            "the `variant` and `with` of a dependency can't be in an included file"
+                        )
+                        .with_source_code(source()));
+                    }
+                }
+            }
+        }
+
+        let mut dependencies = HashMap::from_iter([(SpecType::Run, included.dependencies)]);
+        if let Some(host_deps) = included.host_dependencies {
+            dependencies.insert(SpecType::Host, host_deps);
+        }
+        if let Some(build_deps) = included.build_dependencies {
+            dependencies.insert(SpecType::Build, build_deps);
+        }
+
+        Ok(Self {
+            path,
+            name: name.to_string(),
+            contents,
+            system_requirements: included.system_requirements,
+            default_target: Target {
+                dependencies,
+                pypi_dependencies: included.pypi_dependencies,
+                activation: included.activation,
+                deactivation: included.deactivation,
+                tasks: included.tasks,
+            },
+            target: included.target,
+            feature: included.feature,
+        })
+    }
+}
+
+/// Reads the files of the `include` array of a manifest. `source` is the manifest itself, a file
+/// that doesn't exist is reported there.
+pub fn read_includes(
+    root: &Path,
+    include: &[PixiSpanned<String>],
+    source: &str,
+) -> miette::Result<Vec<IncludedManifest>> {
+    include
+        .iter()
+        .map(|name| {
+            if !root.join(name.get_ref()).is_file() {
+                return Err(miette::miette!(
+                    labels = vec![LabeledSpan::at(
+                        name.span().unwrap_or_default(),
+                        "file not found"
+                    )],
+                    "the included file '{}' does not exist",
+                    name.get_ref()
+                )
+                .with_source_code(NamedSource::new(
+                    consts::PROJECT_MANIFEST,
+                    source.to_owned(),
+                )));
+            }
+            IncludedManifest::from_path(root, name.get_ref())
+        })
+        .collect()
+}
+
+/// Merges the included files into the manifest. They are merged from the last to the first, every
+/// file only adds the keys that the manifest or a later file doesn't define.
+pub fn merge_includes(
+    manifest: &mut ProjectManifest,
+    includes: Vec<IncludedManifest>,
+) -> miette::Result<()> {
+    for included in includes.into_iter().rev() {
+        let source = (included.name.as_str(), included.contents.as_str());
+        check_target_platforms(manifest, &FeatureName::Default, &included.target, source)?;
+        let default_feature = manifest.default_feature_mut();
+        fill_system_requirements(
+            &mut default_feature.system_requirements,
+            included.system_requirements,
+        );
+        fill_targets(
+            &mut default_feature.targets,
+            included.default_target,
+            included.target,
+        );
+
+        for (name, feature) in included.feature {
+            manifest
+                .features
+                .entry(name.clone())
+                .or_insert_with(|| Feature {
+                    name: name.clone(),
+                    platforms: None,
+                    channels: None,
+                    system_requirements: SystemRequirements::default(),
+                    targets: Targets::from_default_and_user_defined(
+                        Target::default(),
+                        IndexMap::new(),
+                    ),
+                });
+            check_target_platforms(manifest, &name, &feature.target, source)?;
+
+            let merged = manifest
+                .features
+                .get_mut(&name)
+                .expect("the feature was inserted");
+            if merged.channels.is_none() {
+                merged.channels = feature.channels;
+            }
+            fill_system_requirements(&mut merged.system_requirements, feature.system_requirements);
+
+            let mut dependencies = HashMap::from_iter([(SpecType::Run, feature.dependencies)]);
+            if let Some(host_deps) = feature.host_dependencies {
+                dependencies.insert(SpecType::Host, host_deps);
+            }
+            if let Some(build_deps) = feature.build_dependencies {
+                dependencies.insert(SpecType::Build, build_deps);
+            }
+            let default_target = Target {
+                dependencies,
+                pypi_dependencies: feature.pypi_dependencies,
+                activation: feature.activation,
+                deactivation: feature.deactivation,
+                tasks: feature.tasks,
+            };
+            fill_targets(&mut merged.targets, default_target, feature.target);
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the targets of a feature in an included file are platforms of the feature. Like
+/// the validation of the manifest, but the errors point at the included file.
+fn check_target_platforms(
+    manifest: &ProjectManifest,
+    feature: &FeatureName,
+    targets: &IndexMap<PixiSpanned<TargetSelector>, Target>,
+    (file_name, contents): (&str, &str),
+) -> miette::Result<()> {
+    let feature = &manifest.features[feature];
+    let platforms = feature
+        .platforms
+        .as_ref()
+        .unwrap_or(&manifest.project.platforms);
+    // A workspace member inherits the platforms of the workspace root.
+    if platforms.as_ref().is_empty() {
+        return Ok(());
+    }
+    for selector in targets.keys() {
+        match selector.get_ref() {
+            TargetSelector::Platform(platform) => {
+                if !platforms.as_ref().contains(platform) {
+                    return Err(create_unsupported_platform_report(
+                        NamedSource::new(file_name, contents.to_owned()),
+                        selector.span().unwrap_or_default(),
+                        platform,
+                        feature,
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds the targets of an included file to the targets of a feature.
+fn fill_targets(
+    targets: &mut Targets,
+    default_target: Target,
+    user_defined: IndexMap<PixiSpanned<TargetSelector>, Target>,
+) {
+    fill_target(targets.default_mut(), default_target);
+    for (selector, target) in user_defined {
+        fill_target(
+            targets.for_opt_target_or_default_mut(Some(selector.get_ref())),
+            target,
+        );
+    }
+}
+
+/// Adds the dependencies, tasks and activation of `from` that `target` doesn't define.
+fn fill_target(target: &mut Target, from: Target) {
+    for (kind, dependencies) in from.dependencies {
+        let existing = target.dependencies.entry(kind).or_default();
+        for (name, spec) in dependencies {
+            existing.entry(name).or_insert(spec);
+        }
+    }
+    if let Some(pypi_dependencies) = from.pypi_dependencies {
+        let existing = target.pypi_dependencies.get_or_insert_with(IndexMap::new);
+        for (name, requirement) in pypi_dependencies {
+            existing.entry(name).or_insert(requirement);
+        }
+    }
+    if target.activation.is_none() {
+        target.activation = from.activation;
+    }
+    if target.deactivation.is_none() {
+        target.deactivation = from.deactivation;
+    }
+    for (name, task) in from.tasks {
+        target.tasks.entry(name).or_insert(task);
+    }
+}
+
+/// Adds the system requirements of `from` that `requirements` doesn't define.
+fn fill_system_requirements(requirements: &mut SystemRequirements, from: SystemRequirements) {
+    requirements.windows = requirements.windows.or(from.windows);
+    requirements.unix = requirements.unix.or(from.unix);
+    requirements.macos = requirements.macos.take().or(from.macos);
+    requirements.linux = requirements.linux.take().or(from.linux);
+    requirements.cuda = requirements.cuda.take().or(from.cuda);
+    requirements.libc = requirements.libc.take().or(from.libc);
+    requirements.archspec = requirements.archspec.take().or(from.archspec);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Manifest;
+    use super::*;
+    use rattler_conda_types::Platform;
+
+    fn write(root: &Path, path: &str, contents: &str) {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_merge_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "pixi.toml",
+            r#"include = ["tasks.toml", "deps/cuda.toml"]
+
+[project]
+name = "foo"
+channels = ["conda-forge"]
+platforms = ["linux-64", "osx-arm64"]
+
+[tasks]
+test = "pytest -x"
+
+[dependencies]
+python = "3.11.*"
+"#,
+        );
+        write(
+            root,
+            "tasks.toml",
+            r#"
+[tasks]
+test = "pytest"
+build = "make"
+lint = "ruff check"
+"#,
+        );
+        write(
+            root,
+            "deps/cuda.toml",
+            r#"
+[tasks]
+build = "make cuda"
+
+[target.linux-64.dependencies]
+cuda-toolkit = "12.*"
+
+[feature.cuda.system-requirements]
+cuda = "12"
+
+[feature.cuda.dependencies]
+pytorch = "2.*"
+"#,
+        );
+
+        let manifest = Manifest::from_path(root.join("pixi.toml")).unwrap();
+        assert_eq!(
+            manifest.included_files,
+            [root.join("tasks.toml"), root.join("deps/cuda.toml")]
+        );
+
+        // The manifest takes precedence over the includes, a later include over an earlier one.
+        let tasks = &manifest.default_feature().targets.default().tasks;
+        let command = |name: &str| tasks[name].as_single_command().unwrap().into_owned();
+        assert_eq!(command("test"), "pytest -x");
+        assert_eq!(command("build"), "make cuda");
+        assert_eq!(command("lint"), "ruff check");
+
+        let linux = manifest
+            .default_feature()
+            .targets
+            .for_target(&TargetSelector::Platform(Platform::Linux64))
+            .unwrap();
+        assert!(linux
+            .run_dependencies()
+            .unwrap()
+            .contains_key(&PackageName::new_unchecked("cuda-toolkit")));
+
+        let cuda = &manifest.parsed.features[&FeatureName::Named(String::from("cuda"))];
+        assert!(cuda.system_requirements.cuda.is_some());
+        assert!(cuda
+            .targets
+            .default()
+            .run_dependencies()
+            .unwrap()
+            .contains_key(&PackageName::new_unchecked("pytorch")));
+    }
+
+    #[test]
+    fn test_remove_included_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(
+            root,
+            "pixi.toml",
+            r#"include = ["extra.toml"]
+
+[project]
+name = "foo"
+channels = ["conda-forge"]
+platforms = ["linux-64"]
+
+[tasks]
+lint = "ruff check"
+"#,
+        );
+        write(
+            root,
+            "extra.toml",
+            r#"
+[tasks]
+test = "pytest"
+
+[dependencies]
+python = "3.11.*"
+
+[target.linux-64.pypi-dependencies]
+requests = "*"
+"#,
+        );
+
+        let mut manifest = Manifest::from_path(root.join("pixi.toml")).unwrap();
+        let err = manifest.remove_task("test", None).unwrap_err();
+        assert!(err.to_string().contains("included file 'extra.toml'"));
+        assert!(manifest
+            .remove_dependency(&PackageName::new_unchecked("python"), SpecType::Run, None)
+            .is_err());
+        assert!(manifest
+            .remove_pypi_dependency(&"requests".parse().unwrap(), Some(Platform::Linux64))
+            .is_err());
+        assert!(manifest.tasks(None).contains_key("test"));
+
+        // The keys of the manifest itself can still be removed.
+        manifest.remove_task("lint", None).unwrap();
+        assert!(!manifest.tasks(None).contains_key("lint"));
+    }
+
+    #[test]
+    fn test_invalid_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let manifest = r#"include = ["extra.toml"]
+
+[project]
+name = "foo"
+channels = []
+platforms = ["linux-64"]
+"#;
+        write(root, "pixi.toml", manifest);
+        assert!(Manifest::from_path(root.join("pixi.toml"))
+            .unwrap_err()
+            .to_string()
+            .contains("the included file 'extra.toml' does not exist"));
+
+        // Only the tables of the dependencies and tasks can be included, and only for the
+        // platforms of the project.
+        for (contents, error) in [
+            (
+                "[project]\nname = \"bar\"\n",
+                "failed to parse the included file 'extra.toml'",
+            ),
+            (
+                "[target.win-64.dependencies]\nfoo = \"*\"\n",
+                "targeting a platform that this project does not support",
+            ),
+            (
+                "[dependencies]\nfoo = { version = \"*\", variant = \"a|b\" }\n",
+                "the `variant` and `with` of a dependency can't be in an included file",
+            ),
+        ] {
+            write(root, "extra.toml", contents);
+            let err = Manifest::from_path(root.join("pixi.toml")).unwrap_err();
+            assert_eq!(err.to_string(), error);
+        }
+    }
+}
@@ -1,8 +1,12 @@
-use crate::utils::spanned::PixiSpanned;
-use rattler_conda_types::{Channel, Platform, Version};
-use serde::Deserialize;
+use crate::{
+    install::PostLinkScriptPolicy, lock_file::LockFileFormat, shebang::ShebangStyle,
+    utils::spanned::PixiSpanned, warnings::WarningCode,
+};
+use rattler_conda_types::{Channel, PackageName, Platform, Version};
+use serde::{Deserialize, Deserializer};
 use serde_with::{serde_as, DisplayFromStr};
 use std::path::PathBuf;
+use std::str::FromStr;
 use url::Url;
 
 /// Describes the contents of the `[package]` section of the project manifest.
@@ -24,13 +28,15 @@ pub struct ProjectMetadata {
     #[serde(default)]
     pub authors: Vec<String>,
 
-    /// The channels used by the project
+    /// The channels used by the project, a workspace member inherits them from the root
     #[serde_as(deserialize_as = "Vec<super::serde::ChannelStr>")]
+    #[serde(default)]
     pub channels: Vec<Channel>,
 
-    /// The platforms this project supports
+    /// The platforms this project supports, a workspace member inherits them from the root
     // TODO: This is actually slightly different from the rattler_conda_types::Platform because it
     //     should not include noarch.
+    #[serde(default, deserialize_with = "deserialize_platforms")]
     pub platforms: PixiSpanned<Vec<Platform>>,
 
     /// The license as a valid SPDX string (e.g. MIT AND Apache-2.0)
@@ -50,4 +56,88 @@ pub struct ProjectMetadata {
 
     /// URL of the project documentation
     pub documentation: Option<Url>,
+
+    /// Whether to create desktop or start-menu shortcuts for packages that ship menu definitions
+    #[serde(default)]
+    pub shortcuts: bool,
+
+    /// Whether the post-link scripts of conda packages are executed
+    #[serde(default)]
+    pub post_link_scripts: PostLinkScriptPolicy,
+
+    /// Whether `.pyc` files are compiled for noarch python packages (defaults to true)
+    pub compile_pyc: Option<bool>,
+
+    /// How the shebangs of the scripts in the environment are written
+    #[serde(default)]
+    pub shebang: ShebangStyle,
+
+    /// The conda packages of which pre-release versions can be selected from the pre-release
+    /// labels of the channels
+    #[serde(default)]
+    pub prerelease_packages: Vec<PackageName>,
+
+    /// The labels of the channels that contain pre-release versions (defaults to `dev`)
+    pub prerelease_labels: Option<Vec<String>>,
+
+    /// Whether the `etc/conda/activate.d` scripts of the installed packages are run when the
+    /// environment is activated (defaults to true)
+    pub package_activation_scripts: Option<bool>,
+
+    /// The task that is executed by `pixi start` or `pixi run` without arguments
+    pub default_task: Option<PixiSpanned<String>>,
+
+    /// Whether unknown keys in the manifest are an error instead of a warning
+    #[serde(default)]
+    pub strict: bool,
+
+    /// The codes of the warnings that are not shown for this project
+    #[serde(default)]
+    pub allow_warnings: Vec<WarningCode>,
+
+    /// Whether every environment is locked in a separate file in `.pixi/locks`
+    #[serde(default)]
+    pub split_lock_files: bool,
+
+    /// The format the lock files are written in
+    #[serde(default)]
+    pub lock_file_format: LockFileFormat,
+
+    /// The environment variables of the host that reach the tasks when they run in a clean
+    /// environment, e.g. `SSH_AUTH_SOCK` or `AWS_*`
+    #[serde(default)]
+    pub passthrough_env: Vec<String>,
+}
+
+/// Parses a platform of the manifest. Platforms like `linux-64-musl` are rejected with a hint,
+/// conda channels publish packages built against musl in the regular linux subdirectories where
+/// they depend on the `__musl` virtual package, so musl is selected with the `libc` system
+/// requirement instead.
+pub fn parse_platform(platform: &str) -> Result<Platform, String> {
+    if let Some(base) = platform.strip_suffix("-musl") {
+        if let Ok(base) = Platform::from_str(base) {
+            if base.is_linux() {
+                return Err(format!(
+                    "'{platform}' is not a platform of conda channels, packages built against musl are published for '{base}' and depend on the `__musl` virtual package. Use '{base}' and add `libc = {{ family = \"musl\", version = \"1.2\" }}` to [system-requirements]"
+                ));
+            }
+        }
+    }
+    Platform::from_str(platform).map_err(|err| err.to_string())
+}
+
+fn deserialize_platforms<'de, D>(deserializer: D) -> Result<PixiSpanned<Vec<Platform>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let platforms = PixiSpanned::<Vec<String>>::deserialize(deserializer)?;
+    let value = platforms
+        .value
+        .iter()
+        .map(|platform| parse_platform(platform).map_err(serde::de::Error::custom))
+        .collect::<Result<_, _>>()?;
+    Ok(PixiSpanned {
+        span: platforms.span,
+        value,
+    })
 }
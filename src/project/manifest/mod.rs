@@ -1,30 +1,47 @@
 mod activation;
+mod companions;
 mod environment;
 mod error;
 mod feature;
+mod include;
 mod metadata;
+mod pipeline;
 mod python;
 mod serde;
+mod strict;
 mod system_requirements;
 mod target;
+mod variants;
+mod workspace;
 
 use crate::{
+    alias::Alias,
+    banned::BannedDependency,
+    build_fallback::BuildFallback,
     consts,
+    end_of_life::EndOfLife,
+    overlay::Overlay,
     project::{manifest::target::Targets, SpecType},
     task::Task,
-    utils::spanned::PixiSpanned,
+    utils::{interpolate::interpolate_env, spanned::PixiSpanned, spec_format},
+    warnings::{self, WarningCode},
 };
 use ::serde::{Deserialize, Deserializer};
-pub use activation::Activation;
+pub use activation::{Activation, Deactivation};
+pub use companions::Companions;
+use environment::TomlEnvironment;
 pub use environment::{Environment, EnvironmentName};
-pub use feature::{Feature, FeatureName};
+use feature::TomlFeature;
+pub use feature::{Feature, FeatureName, PrioritizedChannel};
 use indexmap::IndexMap;
 use itertools::Itertools;
-pub use metadata::ProjectMetadata;
+pub use metadata::{parse_platform, ProjectMetadata};
 use miette::{Context, IntoDiagnostic, LabeledSpan, NamedSource, Report};
+pub use pipeline::{Pipeline, Stage, MATRIX_ENVIRONMENT_KEY};
 pub use python::PyPiRequirement;
 use rattler_conda_types::{
-    Channel, ChannelConfig, MatchSpec, NamelessMatchSpec, PackageName, Platform, Version,
+    Channel, ChannelConfig, MatchSpec, NamelessMatchSpec, PackageName, ParseChannelError, Platform,
+    Version,
 };
 use serde_with::{serde_as, DisplayFromStr, PickFirst};
 use std::{
@@ -33,9 +50,13 @@ use std::{
     path::{Path, PathBuf},
     str::FromStr,
 };
-pub use system_requirements::{LibCFamilyAndVersion, LibCSystemRequirement, SystemRequirements};
+pub use system_requirements::{
+    LibCFamilyAndVersion, LibCSystemRequirement, SystemRequirements, SystemRequirementsPreset,
+};
 pub use target::{Target, TargetSelector};
 use toml_edit::{value, Array, Document, Item, Table, TomlError, Value};
+pub use variants::Variants;
+pub use workspace::WorkspaceMetadata;
 
 /// Handles the project's manifest file.
 /// This struct is responsible for reading, parsing, editing, and saving the manifest.
@@ -56,6 +77,21 @@ pub struct Manifest {
 
     /// The parsed manifest
     pub parsed: ProjectManifest,
+
+    /// The variants of the dependencies, every variant is a separate environment
+    pub variants: Variants,
+
+    /// The companion packages that dependencies pull in
+    pub companions: Companions,
+
+    /// The paths of the files in the `include` array of the manifest
+    pub included_files: Vec<PathBuf>,
+
+    /// The channels of the environments whose features add channels, by environment
+    environment_channels: HashMap<String, Vec<Channel>>,
+
+    /// The configuration that channel names are resolved with
+    channel_config: ChannelConfig,
 }
 
 impl Manifest {
@@ -71,11 +107,30 @@ impl Manifest {
 
     /// Create a new manifest from a string
     pub fn from_str(root: &Path, contents: impl Into<String>) -> miette::Result<Self> {
+        Self::from_str_with_strictness(root, contents, false)
+    }
+
+    /// Create a new manifest from a string. Unknown keys in the manifest are an error if `strict`
+    /// is true or `strict = true` is set in the manifest, otherwise a warning is shown for them.
+    pub fn from_str_with_strictness(
+        root: &Path,
+        contents: impl Into<String>,
+        strict: bool,
+    ) -> miette::Result<Self> {
         let contents = contents.into();
-        let (manifest, document) = match ProjectManifest::from_toml_str(&contents)
+        let key_check = strict::check_keys(&contents).unwrap_or_default();
+        let strict = strict || key_check.strict;
+        let (mut manifest, document) = match ProjectManifest::from_toml_str(&contents)
             .and_then(|manifest| contents.parse::<Document>().map(|doc| (manifest, doc)))
         {
             Ok(result) => result,
+            Err(_) if !key_check.unknown_keys.is_empty() => {
+                // A typo in a key is the most likely cause, point at all of them at once.
+                return Err(strict::unknown_keys_report(
+                    &key_check.unknown_keys,
+                    NamedSource::new(consts::PROJECT_MANIFEST, contents),
+                ));
+            }
             Err(e) => {
                 if let Some(span) = e.span() {
                     return Err(miette::miette!(
@@ -89,11 +144,52 @@ impl Manifest {
             }
         };
 
+        if strict && !key_check.unknown_keys.is_empty() {
+            return Err(strict::unknown_keys_report(
+                &key_check.unknown_keys,
+                NamedSource::new(consts::PROJECT_MANIFEST, contents),
+            ));
+        }
+        warnings::allow(manifest.project.allow_warnings.iter().copied());
+        for key in &key_check.unknown_keys {
+            warnings::emit(
+                WarningCode::UnknownManifestKey,
+                format!("{}, it is ignored", key.message()),
+            );
+        }
+
+        resolve_relative_channels(&mut manifest, &document, root)?;
+
+        // Merge the included files before anything reads the tables they add to.
+        let includes = include::read_includes(root, &manifest.include, &contents)?;
+        let included_files = includes
+            .iter()
+            .map(|included| included.path.clone())
+            .collect();
+        include::merge_includes(&mut manifest, includes)?;
+
+        let variants = match Variants::from_toml_str(&contents) {
+            Some(variants) => variants?,
+            None => Variants::default(),
+        };
+        let companions = match Companions::from_toml_str(&contents) {
+            Some(companions) => companions?,
+            None => Companions::default(),
+        };
+
         // Validate the contents of the manifest
         manifest.validate(
             NamedSource::new(consts::PROJECT_MANIFEST, contents.to_owned()),
             root,
         )?;
+        if !variants.names().is_empty() && manifest.environments.len() > 1 {
+            miette::bail!(
+                help = "use either the `variant` of dependencies or the [environments] table to define the environments",
+                "the environments of the [environments] table can't be combined with dependency variants"
+            );
+        }
+        let environment_channels = manifest.environment_channels()?;
+        manifest.check_solve_groups(&environment_channels)?;
 
         // Notify the user that pypi-dependencies are still experimental
         if manifest
@@ -105,7 +201,7 @@ impl Manifest {
             match std::env::var("PIXI_BETA_WARNING_OFF") {
                 Ok(var) if var == *"true" => {}
                 _ => {
-                    tracing::warn!("BETA feature `[pypi-dependencies]` enabled!\n\nPlease report any and all issues here:\n\n\thttps://github.com/prefix-dev/pixi.\n\nTurn this warning off by setting the environment variable `PIXI_BETA_WARNING_OFF` to `true`.\n");
+                    warnings::emit(WarningCode::BetaFeature, "BETA feature `[pypi-dependencies]` enabled!\n\nPlease report any and all issues here:\n\n\thttps://github.com/prefix-dev/pixi.\n\nTurn this warning off by setting the environment variable `PIXI_BETA_WARNING_OFF` to `true`, or with `allow-warnings = [\"beta-feature\"]` in the [project] table.\n");
                 }
             }
         }
@@ -115,9 +211,62 @@ impl Manifest {
             contents,
             document,
             parsed: manifest,
+            variants,
+            companions,
+            included_files,
+            environment_channels,
+            channel_config: ChannelConfig::default(),
         })
     }
 
+    /// Returns the names of the environments of the project, either one for every variant of the
+    /// dependencies or `default` and the environments of the `[environments]` table. The first one
+    /// is the default environment. Empty if the project only has the default environment.
+    pub fn environment_names(&self) -> Vec<String> {
+        if !self.variants.names().is_empty() {
+            return self.variants.names().to_vec();
+        }
+        if self.parsed.environments.len() <= 1 {
+            return Vec::new();
+        }
+        let named = self
+            .parsed
+            .environments
+            .keys()
+            .filter_map(|name| name.name())
+            .filter(|name| *name != "default")
+            .map(ToOwned::to_owned);
+        std::iter::once(String::from("default"))
+            .chain(named)
+            .collect()
+    }
+
+    /// Returns the channels of the environment with the given name if its features add channels.
+    pub fn environment_channels(&self, name: &str) -> Option<&[Channel]> {
+        self.environment_channels.get(name).map(Vec::as_slice)
+    }
+
+    /// Resolves the channel names of the manifest with the channel alias of the configuration,
+    /// the manifest is parsed before the configuration is known.
+    pub fn apply_channel_config(&mut self, channel_config: &ChannelConfig) -> miette::Result<()> {
+        for channel in self.parsed.project.channels.iter_mut() {
+            apply_channel_alias(channel, channel_config);
+        }
+        for channels in self
+            .parsed
+            .features
+            .values_mut()
+            .filter_map(|feature| feature.channels.as_mut())
+        {
+            for prioritized in channels.iter_mut() {
+                apply_channel_alias(&mut prioritized.channel, channel_config);
+            }
+        }
+        self.environment_channels = self.parsed.environment_channels()?;
+        self.channel_config = channel_config.clone();
+        Ok(())
+    }
+
     /// Save the manifest to the file and update the contents
     pub fn save(&mut self) -> miette::Result<()> {
         self.contents = self.document.to_string();
@@ -125,6 +274,12 @@ impl Manifest {
         Ok(())
     }
 
+    /// Returns the locations of the conda dependencies in the manifest by their lowercase name,
+    /// for every table that lists them.
+    pub fn dependency_spans(&self) -> HashMap<String, Vec<Range<usize>>> {
+        strict::dependency_spans(&self.contents)
+    }
+
     /// Returns a hashmap of the tasks that should run only the given platform. If the platform is
     /// `None`, only the default targets tasks are returned.
     pub fn tasks(&self, platform: Option<Platform>) -> HashMap<&str, &Task> {
@@ -176,6 +331,7 @@ impl Manifest {
         self.tasks(platform)
             .get(name.as_ref())
             .ok_or_else(|| miette::miette!("task {} does not exist", name.as_ref()))?;
+        self.check_not_included(platform, "tasks", name.as_ref())?;
 
         // Get the task table either from the target platform or the default tasks.
         let tasks_table = ensure_toml_target_table(&mut self.document, platform, "tasks")?;
@@ -266,7 +422,10 @@ impl Manifest {
         };
 
         // Store (or replace) in the document
-        dependency_table.insert(name.as_source(), Item::Value(spec.to_string().into()));
+        dependency_table.insert(
+            name.as_source(),
+            Item::Value(spec_format::manifest_conda_spec(&spec).into()),
+        );
 
         // Add the dependency to the manifest as well
         self.default_feature_mut()
@@ -291,7 +450,8 @@ impl Manifest {
             ensure_toml_target_table(&mut self.document, platform, consts::PYPI_DEPENDENCIES)?;
 
         // Add the pypi dependency to the table
-        dependency_table.insert(name.as_str(), (*requirement).clone().into());
+        let requirement = spec_format::normalize_pypi_requirement(requirement);
+        dependency_table.insert(name.as_str(), requirement.clone().into());
 
         // Add the dependency to the manifest as well
         self.default_feature_mut()
@@ -299,8 +459,63 @@ impl Manifest {
             .for_opt_target_or_default_mut(platform.map(TargetSelector::from).as_ref())
             .pypi_dependencies
             .get_or_insert_with(Default::default)
-            .insert(name.clone(), requirement.clone());
+            .insert(name.clone(), requirement);
+
+        Ok(())
+    }
+
+    /// Allows pre-releases of the pypi dependency `name` in every target that defines it. Returns
+    /// false if `name` is not a pypi dependency of the project.
+    pub fn allow_pypi_prerelease(
+        &mut self,
+        name: &rip::types::PackageName,
+    ) -> miette::Result<bool> {
+        let requirements = self
+            .default_feature()
+            .targets
+            .iter()
+            .filter_map(|(target, selector)| {
+                let requirement = target.pypi_dependencies.as_ref()?.get(name)?;
+                let platform = selector.map(|selector| match selector {
+                    TargetSelector::Platform(platform) => *platform,
+                });
+                Some((platform, requirement.clone()))
+            })
+            .collect_vec();
+        for (platform, mut requirement) in requirements.iter().cloned() {
+            requirement.allow_prerelease = true;
+            self.add_pypi_dependency(name, &requirement, platform)?;
+        }
+        Ok(!requirements.is_empty())
+    }
+
+    /// Adds conda packages to the `prerelease-packages` of the project.
+    pub fn add_prerelease_packages(
+        &mut self,
+        names: impl IntoIterator<Item = PackageName>,
+    ) -> miette::Result<()> {
+        let mut added = Vec::new();
+        for name in names {
+            if !self.parsed.project.prerelease_packages.contains(&name) {
+                self.parsed.project.prerelease_packages.push(name.clone());
+                added.push(name);
+            }
+        }
 
+        let project = &mut self.document["project"];
+        if project.is_none() {
+            *project = Item::Table(Table::new());
+        }
+        let packages = &mut project["prerelease-packages"];
+        if packages.is_none() {
+            *packages = Item::Value(Value::Array(Array::new()))
+        }
+        let packages = packages
+            .as_array_mut()
+            .ok_or_else(|| miette::miette!("malformed prerelease-packages array"))?;
+        for name in added {
+            packages.push(name.as_source());
+        }
         Ok(())
     }
 
@@ -311,6 +526,7 @@ impl Manifest {
         spec_type: SpecType,
         platform: Option<Platform>,
     ) -> miette::Result<(PackageName, NamelessMatchSpec)> {
+        self.check_not_included(platform, spec_type.name(), dep.as_normalized())?;
         get_toml_target_table(&mut self.document, platform, spec_type.name())?
             .remove(dep.as_normalized())
             .ok_or_else(|| {
@@ -341,6 +557,7 @@ impl Manifest {
         dep: &rip::types::PackageName,
         platform: Option<Platform>,
     ) -> miette::Result<(rip::types::PackageName, PyPiRequirement)> {
+        self.check_not_included(platform, consts::PYPI_DEPENDENCIES, dep.as_str())?;
         get_toml_target_table(&mut self.document, platform, consts::PYPI_DEPENDENCIES)?
             .remove(dep.as_str())
             .ok_or_else(|| {
@@ -370,6 +587,50 @@ impl Manifest {
             .expect("dependency should exist"))
     }
 
+    /// Returns an error if `key` of the table `table_name` of the target of `platform` is not in
+    /// the manifest itself but in an included file. Pixi only edits the manifest, the included
+    /// file has to be edited by hand.
+    fn check_not_included(
+        &self,
+        platform: Option<Platform>,
+        table_name: &str,
+        key: &str,
+    ) -> miette::Result<()> {
+        let defines = |document: &Document| {
+            let target = match platform {
+                Some(platform) => document
+                    .get("target")
+                    .and_then(|target| target.get(platform.as_str())),
+                None => Some(document.as_item()),
+            };
+            target
+                .and_then(|target| target.get(table_name))
+                .and_then(|table| table.get(key))
+                .is_some()
+        };
+        if defines(&self.document) {
+            return Ok(());
+        }
+
+        // A later included file takes precedence over an earlier one.
+        let included = self.included_files.iter().rev().find(|path| {
+            std::fs::read_to_string(path)
+                .ok()
+                .and_then(|contents| contents.parse::<Document>().ok())
+                .map_or(false, |document| defines(&document))
+        });
+        if let Some(path) = included {
+            let root = self.root();
+            let name = path.strip_prefix(&root).unwrap_or(path).display();
+            miette::bail!(
+                help = format!("edit '{name}' instead"),
+                "'{key}' is defined in the included file '{name}', pixi can only change {}",
+                consts::PROJECT_MANIFEST
+            );
+        }
+        Ok(())
+    }
+
     /// Returns true if any of the features has pypi dependencies defined.
     ///
     /// This also returns true if the `pypi-dependencies` key is defined but empty.
@@ -381,6 +642,14 @@ impl Manifest {
             .any(|f| f.pypi_dependencies.is_some())
     }
 
+    /// Returns the root directory of the project.
+    fn root(&self) -> PathBuf {
+        self.path
+            .parent()
+            .expect("the manifest is always in a directory")
+            .to_path_buf()
+    }
+
     /// Returns a mutable reference to the channels array.
     fn channels_array_mut(&mut self) -> miette::Result<&mut Array> {
         let project = &mut self.document["project"];
@@ -403,10 +672,11 @@ impl Manifest {
         &mut self,
         channels: impl IntoIterator<Item = impl AsRef<str>>,
     ) -> miette::Result<()> {
+        let root = self.root();
         let mut stored_channels = Vec::new();
         for channel in channels {
             self.parsed.project.channels.push(
-                Channel::from_str(channel.as_ref(), &ChannelConfig::default()).into_diagnostic()?,
+                parse_channel(channel.as_ref(), &root, &self.channel_config).into_diagnostic()?,
             );
             stored_channels.push(channel.as_ref().to_owned());
         }
@@ -416,6 +686,8 @@ impl Manifest {
             channels_array.push(channel);
         }
 
+        // The environments with feature channels also use the channels of the project.
+        self.environment_channels = self.parsed.environment_channels()?;
         Ok(())
     }
 
@@ -429,7 +701,8 @@ impl Manifest {
         for channel in channels {
             // Parse the channel to be removed
             let channel_to_remove =
-                Channel::from_str(channel.as_ref(), &ChannelConfig::default()).into_diagnostic()?;
+                parse_channel(channel.as_ref(), &self.root(), &self.channel_config)
+                    .into_diagnostic()?;
 
             // Remove the channel if it exists
             if let Some(pos) = self
@@ -449,6 +722,7 @@ impl Manifest {
         let channels_array = self.channels_array_mut()?;
         channels_array.retain(|x| !removed_channels.contains(&x.as_str().unwrap().to_string()));
 
+        self.environment_channels = self.parsed.environment_channels()?;
         Ok(())
     }
 
@@ -461,11 +735,20 @@ impl Manifest {
         Ok(())
     }
 
-    /// Set the project version
+    /// Set the project version, the comments around the old version are kept.
     pub fn set_version(&mut self, version: &String) -> miette::Result<()> {
+        let parsed = Version::from_str(version)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("'{version}' is not a valid version"))?;
+
         // Update in both the manifest and the toml
-        self.parsed.project.version = Some(Version::from_str(version).unwrap());
-        self.document["project"]["version"] = value(version);
+        self.parsed.project.version = Some(parsed);
+        let item = &mut self.document["project"]["version"];
+        let decor = item.as_value().map(|value| value.decor().clone());
+        *item = value(version);
+        if let (Some(decor), Some(value)) = (decor, item.as_value_mut()) {
+            *value.decor_mut() = decor;
+        }
 
         Ok(())
     }
@@ -539,6 +822,111 @@ pub fn ensure_toml_target_table<'a>(
         })
 }
 
+/// Returns true if a channel is a path relative to the project, e.g. `./channel`.
+pub fn is_relative_channel_path(channel: &str) -> bool {
+    matches!(channel, "." | "..")
+        || ["./", "../", ".\\", "..\\"]
+            .iter()
+            .any(|prefix| channel.starts_with(prefix))
+}
+
+/// Parses a channel of the manifest, a path relative to the project is resolved against the root
+/// of the project instead of the current directory.
+pub fn parse_channel(
+    channel: &str,
+    root: &Path,
+    channel_config: &ChannelConfig,
+) -> Result<Channel, ParseChannelError> {
+    if is_relative_channel_path(channel) {
+        let path = root.join(channel);
+        let path = dunce::canonicalize(&path).unwrap_or(path);
+        Channel::from_str(path.to_string_lossy(), channel_config)
+    } else {
+        Channel::from_str(channel, channel_config)
+    }
+}
+
+/// Resolves a channel that was resolved with the default channel alias again with the alias of
+/// `channel_config`. Channels with a url of another server and local channels are kept as they are.
+fn apply_channel_alias(channel: &mut Channel, channel_config: &ChannelConfig) {
+    let default_config = ChannelConfig::default();
+    let Ok(default) = Channel::from_str(channel.name(), &default_config) else {
+        return;
+    };
+    if default.base_url() != channel.base_url() {
+        return;
+    }
+    if let Ok(aliased) = Channel::from_str(channel.name(), channel_config) {
+        *channel = aliased;
+    }
+}
+
+/// Resolves the channels of the project and of the features that are relative paths against the
+/// root of the project, the channels are parsed without knowing it. Environment variables in the
+/// channels are interpolated first, like they are when the channels are parsed.
+fn resolve_relative_channels(
+    manifest: &mut ProjectManifest,
+    document: &Document,
+    root: &Path,
+) -> miette::Result<()> {
+    if let Some(channels) = document
+        .get("project")
+        .and_then(|project| project.get("channels"))
+        .and_then(|channels| channels.as_array())
+    {
+        for (channel, value) in manifest.project.channels.iter_mut().zip(channels.iter()) {
+            let source = value
+                .as_str()
+                .map(interpolate_env)
+                .transpose()
+                .into_diagnostic()?;
+            match source.as_deref() {
+                Some(source) if is_relative_channel_path(source) => {
+                    *channel =
+                        parse_channel(source, root, &ChannelConfig::default()).into_diagnostic()?;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // The channels of a feature are either a string or a table with a `channel` key.
+    for feature in manifest.features.values_mut() {
+        let (Some(name), Some(channels)) = (feature.name.name(), feature.channels.as_mut()) else {
+            continue;
+        };
+        let Some(values) = document
+            .get("feature")
+            .and_then(|features| features.get(name))
+            .and_then(|feature| feature.get("channels"))
+            .and_then(|channels| channels.as_array())
+        else {
+            continue;
+        };
+        for (prioritized, value) in channels.iter_mut().zip(values.iter()) {
+            let source = value
+                .as_str()
+                .or_else(|| {
+                    value
+                        .as_inline_table()
+                        .and_then(|table| table.get("channel"))
+                        .and_then(|channel| channel.as_str())
+                })
+                .map(interpolate_env)
+                .transpose()
+                .into_diagnostic()?;
+            match source.as_deref() {
+                Some(source) if is_relative_channel_path(source) => {
+                    prioritized.channel =
+                        parse_channel(source, root, &ChannelConfig::default()).into_diagnostic()?;
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Retrieve a mutable reference to a target table `table_name`
 /// for a specific platform.
 fn get_toml_target_table<'a>(
@@ -582,6 +970,33 @@ pub struct ProjectManifest {
 
     /// All the environments defined in the project.
     pub environments: IndexMap<EnvironmentName, Environment>,
+
+    /// The pipelines of the project by name.
+    pub pipelines: IndexMap<String, Pipeline>,
+
+    /// PyPI packages that are installed with the conda package they map to instead of with pip.
+    pub pypi_to_conda: IndexMap<rip::types::PackageName, PackageName>,
+
+    /// The versions of packages that the project considers end-of-life.
+    pub end_of_life: IndexMap<PackageName, EndOfLife>,
+
+    /// The packages that the project must not depend on, directly or indirectly.
+    pub banned_dependencies: IndexMap<PackageName, BannedDependency>,
+
+    /// How to build the dependencies that the channels don't provide, if they are built at all.
+    pub build_fallback: Option<BuildFallback>,
+
+    /// The local files that are copied over installed packages.
+    pub overlays: IndexMap<PackageName, Overlay>,
+
+    /// Names for pixi commands, they are expanded before the command line is parsed.
+    pub alias: IndexMap<String, Alias>,
+
+    /// The members of the workspace if the project is the root of one.
+    pub workspace: Option<WorkspaceMetadata>,
+
+    /// The files that are merged into the manifest, relative to the root of the project.
+    pub include: Vec<PixiSpanned<String>>,
 }
 
 impl ProjectManifest {
@@ -617,6 +1032,146 @@ impl ProjectManifest {
             .or_else(|| envs.get(&EnvironmentName::Default))
             .expect("default environment should always exist")
     }
+
+    /// Returns the environment with the given name, `default` is the default environment.
+    pub fn environment(&self, name: &str) -> Option<&Environment> {
+        if name == "default" {
+            return Some(self.default_environment());
+        }
+        self.environments
+            .get(&EnvironmentName::Named(name.to_string()))
+    }
+
+    /// Returns the feature with the given name, `default` is the default feature.
+    pub fn feature(&self, name: &str) -> Option<&Feature> {
+        if name == "default" {
+            return Some(self.default_feature());
+        }
+        self.features.get(&FeatureName::Named(name.to_string()))
+    }
+
+    /// Returns the system requirements of the environment, the union of the system requirements
+    /// of its features. Returns an error if the features require a different libc family.
+    pub fn environment_system_requirements(
+        &self,
+        environment: &Environment,
+    ) -> Result<SystemRequirements, String> {
+        self.environment_features(environment)
+            .into_iter()
+            .try_fold(SystemRequirements::default(), |requirements, feature| {
+                requirements.union(&feature.system_requirements)
+            })
+    }
+
+    /// Returns the features that make up the environment, the default feature first followed by
+    /// the features in the order in which the environment lists them.
+    pub fn environment_features(&self, environment: &Environment) -> Vec<&Feature> {
+        std::iter::once(self.default_feature())
+            .chain(
+                environment
+                    .features
+                    .get_ref()
+                    .iter()
+                    .filter_map(|name| self.features.get(&FeatureName::Named(name.clone()))),
+            )
+            .collect()
+    }
+
+    /// Returns the channels of the environments of the `[environments]` table whose features add
+    /// channels, by the name of the environment. The channels of the features come first, in the
+    /// order in which the environment lists its features, followed by the channels of the project.
+    /// Channels with a higher priority are moved before channels with a lower one, which is 0 if
+    /// no priority is given.
+    ///
+    /// An error is returned if an environment uses a feature that is not defined, or if two of its
+    /// features give the same channel a different priority.
+    pub fn environment_channels(&self) -> miette::Result<HashMap<String, Vec<Channel>>> {
+        let mut environment_channels = HashMap::new();
+        for environment in self.environments.values() {
+            let Some(name) = environment.name.name() else {
+                continue;
+            };
+
+            let mut prioritized = Vec::new();
+            let mut explicit: Vec<(&Channel, i32, &str)> = Vec::new();
+            for feature_name in environment.features.get_ref() {
+                let Some(feature) = self.features.get(&FeatureName::Named(feature_name.clone()))
+                else {
+                    miette::bail!(
+                        help = format!("add a [feature.{feature_name}] table or remove it from the environment"),
+                        "the environment '{name}' uses the feature '{feature_name}', which is not defined"
+                    );
+                };
+                for PrioritizedChannel { channel, priority } in feature.channels.iter().flatten() {
+                    if let Some(priority) = *priority {
+                        if let Some((_, other_priority, other_feature)) =
+                            explicit.iter().find(|(other, other_priority, _)| {
+                                *other == channel && *other_priority != priority
+                            })
+                        {
+                            miette::bail!(
+                                help = "give the channel the same priority in both features",
+                                "the features '{other_feature}' and '{feature_name}' of the environment '{name}' give the channel '{}' a different priority, {other_priority} and {priority}",
+                                channel.canonical_name()
+                            );
+                        }
+                        explicit.push((channel, priority, feature_name));
+                    }
+                    prioritized.push((channel, priority.unwrap_or(0)));
+                }
+            }
+            if prioritized.is_empty() {
+                continue;
+            }
+
+            // The sort is stable, so channels with the same priority keep their order.
+            prioritized.extend(self.project.channels.iter().map(|channel| (channel, 0)));
+            prioritized.sort_by_key(|(_, priority)| std::cmp::Reverse(*priority));
+            let mut channels: Vec<Channel> = Vec::new();
+            for (channel, _) in prioritized {
+                if !channels.contains(channel) {
+                    channels.push(channel.clone());
+                }
+            }
+            environment_channels.insert(name.to_string(), channels);
+        }
+        Ok(environment_channels)
+    }
+
+    /// Checks that the environments of every solve group use the same channels, they are solved
+    /// together.
+    fn check_solve_groups(
+        &self,
+        environment_channels: &HashMap<String, Vec<Channel>>,
+    ) -> miette::Result<()> {
+        let channels_of = |environment: &Environment| {
+            environment
+                .name
+                .name()
+                .and_then(|name| environment_channels.get(name))
+                .unwrap_or(&self.project.channels)
+        };
+        for (group, environments) in self
+            .environments
+            .values()
+            .filter_map(|environment| Some((environment.solve_group.as_deref()?, environment)))
+            .into_group_map()
+        {
+            let first = environments[0];
+            if let Some(other) = environments
+                .into_iter()
+                .find(|environment| channels_of(*environment) != channels_of(first))
+            {
+                miette::bail!(
+                    help = "give the environments of the solve group the same channels, or put them in different solve groups",
+                    "the environments '{}' and '{}' of the solve group '{group}' use different channels",
+                    first.name.name().unwrap_or("default"),
+                    other.name.name().unwrap_or("default")
+                );
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'de> Deserialize<'de> for ProjectManifest {
@@ -628,8 +1183,15 @@ impl<'de> Deserialize<'de> for ProjectManifest {
         #[derive(Deserialize)]
         #[serde(deny_unknown_fields, rename_all = "kebab-case")]
         pub struct TomlProjectManifest {
-            project: ProjectMetadata,
+            /// Files with more tables of the manifest
             #[serde(default)]
+            include: Vec<PixiSpanned<String>>,
+
+            project: ProjectMetadata,
+            #[serde(
+                default,
+                deserialize_with = "system_requirements::deserialize_system_requirements"
+            )]
             system_requirements: SystemRequirements,
             #[serde(default)]
             target: IndexMap<PixiSpanned<TargetSelector>, Target>,
@@ -661,9 +1223,53 @@ impl<'de> Deserialize<'de> for ProjectManifest {
             #[serde(default)]
             activation: Option<Activation>,
 
+            /// The scripts that run when the environment is deactivated.
+            #[serde(default)]
+            deactivation: Option<Deactivation>,
+
             /// Target specific tasks to run in the environment
             #[serde(default)]
             tasks: HashMap<String, Task>,
+
+            /// Workflows that run tasks in multiple stages
+            #[serde(default)]
+            pipelines: IndexMap<String, Pipeline>,
+
+            /// PyPI packages that are replaced by conda packages
+            #[serde(default)]
+            pypi_to_conda: IndexMap<rip::types::PackageName, PackageName>,
+
+            /// Package versions that are end-of-life
+            #[serde(default)]
+            end_of_life: IndexMap<PackageName, EndOfLife>,
+
+            /// Packages that may not be part of a solution
+            #[serde(default)]
+            banned_dependencies: IndexMap<PackageName, BannedDependency>,
+
+            /// Building missing packages from source
+            #[serde(default)]
+            build_fallback: Option<BuildFallback>,
+
+            /// Local files that are copied over installed packages
+            #[serde(default)]
+            overlays: IndexMap<PackageName, Overlay>,
+
+            /// Names for pixi commands
+            #[serde(default)]
+            alias: IndexMap<String, Alias>,
+
+            /// Features that environments combine
+            #[serde(default)]
+            feature: IndexMap<FeatureName, TomlFeature>,
+
+            /// Environments that combine features
+            #[serde(default)]
+            environments: IndexMap<String, TomlEnvironment>,
+
+            /// The member projects of the workspace
+            #[serde(default)]
+            workspace: Option<WorkspaceMetadata>,
         }
 
         let toml_manifest = TomlProjectManifest::deserialize(deserializer)?;
@@ -680,6 +1286,7 @@ impl<'de> Deserialize<'de> for ProjectManifest {
             dependencies,
             pypi_dependencies: toml_manifest.pypi_dependencies,
             activation: toml_manifest.activation,
+            deactivation: toml_manifest.deactivation,
             tasks: toml_manifest.tasks,
         };
 
@@ -705,10 +1312,41 @@ impl<'de> Deserialize<'de> for ProjectManifest {
             solve_group: None,
         };
 
+        let features = std::iter::once((FeatureName::Default, default_feature))
+            .chain(
+                toml_manifest
+                    .feature
+                    .into_iter()
+                    .map(|(name, feature)| (name.clone(), feature.into_feature(name))),
+            )
+            .collect();
+        let environments = std::iter::once((EnvironmentName::Default, default_environment))
+            .chain(
+                toml_manifest
+                    .environments
+                    .into_iter()
+                    .map(|(name, environment)| {
+                        (
+                            EnvironmentName::Named(name.clone()),
+                            environment.into_environment(name),
+                        )
+                    }),
+            )
+            .collect();
+
         Ok(Self {
             project: toml_manifest.project,
-            features: IndexMap::from_iter([(FeatureName::Default, default_feature)]),
-            environments: IndexMap::from_iter([(EnvironmentName::Default, default_environment)]),
+            features,
+            environments,
+            pipelines: toml_manifest.pipelines,
+            pypi_to_conda: toml_manifest.pypi_to_conda,
+            end_of_life: toml_manifest.end_of_life,
+            banned_dependencies: toml_manifest.banned_dependencies,
+            build_fallback: toml_manifest.build_fallback,
+            overlays: toml_manifest.overlays,
+            alias: toml_manifest.alias,
+            workspace: toml_manifest.workspace,
+            include: toml_manifest.include,
         })
     }
 }
@@ -722,6 +1360,10 @@ impl ProjectManifest {
                 .platforms
                 .as_ref()
                 .unwrap_or(&self.project.platforms);
+            // A workspace member inherits the platforms of the workspace root.
+            if platforms.as_ref().is_empty() {
+                continue;
+            }
             for target_sel in feature.targets.user_defined_selectors() {
                 match target_sel {
                     TargetSelector::Platform(p) => {
@@ -738,6 +1380,45 @@ impl ProjectManifest {
             }
         }
 
+        // Check that the system requirements of the features of every environment can be combined.
+        for environment in self.environments.values() {
+            if let Err(err) = self.environment_system_requirements(environment) {
+                miette::bail!(
+                    help = "give the features of the environment the same libc family",
+                    "invalid system requirements of the environment '{}': {err}",
+                    environment.name.name().unwrap_or("default")
+                );
+            }
+        }
+
+        // Check that the default task is defined, it may only be defined for some platforms.
+        if let Some(default_task) = &self.project.default_task {
+            if !self
+                .default_feature()
+                .targets
+                .targets()
+                .any(|target| target.tasks.contains_key(default_task.get_ref()))
+            {
+                return Err(miette::miette!(
+                    labels = vec![LabeledSpan::at(
+                        default_task.span().unwrap_or_default(),
+                        "unknown task"
+                    )],
+                    help = "add the task to the [tasks] table",
+                    "the default task '{}' is not defined",
+                    default_task.get_ref()
+                )
+                .with_source_code(source));
+            }
+        }
+
+        // Check that the stages of every pipeline form a DAG.
+        for (name, pipeline) in &self.pipelines {
+            if let Err(err) = pipeline.stage_order() {
+                miette::bail!("invalid pipeline '{name}': {err}");
+            }
+        }
+
         // parse the SPDX license expression to make sure that it is a valid expression.
         if let Some(spdx_expr) = &self.project.license {
             spdx::Expression::parse(spdx_expr)
@@ -1007,6 +1688,42 @@ mod test {
             .join("\n"))
     }
 
+    #[test]
+    fn test_default_task() {
+        let contents = format!(
+            "{}\n[target.linux-64.tasks]\ndev = \"python -m http.server\"",
+            PROJECT_BOILERPLATE.replace(
+                "platforms = []",
+                "platforms = [\"linux-64\"]\ndefault-task = \"dev\""
+            )
+        );
+        let manifest = Manifest::from_str(Path::new(""), &contents).unwrap();
+        assert_eq!(
+            manifest
+                .parsed
+                .project
+                .default_task
+                .as_ref()
+                .map(|task| task.get_ref().as_str()),
+            Some("dev")
+        );
+
+        let contents = contents.replace("default-task = \"dev\"", "default-task = \"start\"");
+        assert!(Manifest::from_str(Path::new(""), &contents).is_err());
+    }
+
+    #[test]
+    fn test_strict_unknown_keys() {
+        let contents = format!(
+            "{PROJECT_BOILERPLATE}\n[pypi-dependencies]\nrequests = {{ version = \"*\", extra = [\"socks\"] }}"
+        );
+        assert!(Manifest::from_str(Path::new(""), &contents).is_ok());
+        assert!(Manifest::from_str_with_strictness(Path::new(""), &contents, true).is_err());
+
+        let contents = contents.replace("platforms = []", "platforms = []\nstrict = true");
+        assert!(Manifest::from_str(Path::new(""), &contents).is_err());
+    }
+
     #[test]
     fn test_activation_scripts() {
         let contents = r#"
@@ -1067,6 +1784,114 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_deactivation_scripts() {
+        let contents = r#"
+            [project]
+            name = "foo"
+            channels = []
+            platforms = ["win-64", "linux-64"]
+
+            [deactivation]
+            scripts = ["teardown.sh"]
+
+            [target.win-64.deactivation]
+            scripts = ["teardown.bat"]
+            "#;
+
+        let manifest = Manifest::from_str(Path::new(""), contents).unwrap();
+        let targets = &manifest.default_feature().targets;
+        assert_eq!(
+            targets.default().deactivation.as_ref().unwrap().scripts,
+            Some(vec![String::from("teardown.sh")])
+        );
+        assert_eq!(
+            targets
+                .for_target(&TargetSelector::Platform(Platform::Win64))
+                .unwrap()
+                .deactivation
+                .as_ref()
+                .unwrap()
+                .scripts,
+            Some(vec![String::from("teardown.bat")])
+        );
+        assert!(Manifest::from_str_with_strictness(Path::new(""), contents, true).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_feature_channels() {
+        let unknown_feature = format!(
+            r#"{PROJECT_BOILERPLATE}
+            [environments]
+            nightly = ["nightly"]
+            "#
+        );
+        let err = Manifest::from_str(Path::new(""), &unknown_feature).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("the feature 'nightly', which is not defined"));
+
+        let conflicting_priorities = format!(
+            r#"{PROJECT_BOILERPLATE}
+            [feature.nightly]
+            channels = [{{ channel = "pytorch-nightly", priority = 10 }}]
+
+            [feature.gpu]
+            channels = [{{ channel = "pytorch-nightly", priority = 1 }}]
+
+            [environments]
+            gpu-nightly = ["nightly", "gpu"]
+            "#
+        );
+        let err = Manifest::from_str(Path::new(""), &conflicting_priorities).unwrap_err();
+        assert!(err.to_string().contains("a different priority, 10 and 1"));
+
+        let with_variants = format!(
+            r#"{PROJECT_BOILERPLATE}
+            [dependencies]
+            pytorch = {{ version = "2.*", variant = "cuda12|cpu" }}
+
+            [feature.nightly]
+            channels = ["pytorch-nightly"]
+
+            [environments]
+            nightly = ["nightly"]
+            "#
+        );
+        assert!(Manifest::from_str(Path::new(""), &with_variants).is_err());
+        assert!(toml_edit::de::from_str::<ProjectManifest>(&format!(
+            "{PROJECT_BOILERPLATE}\n[feature.default]\nchannels = []"
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_musl_platforms() {
+        let contents = PROJECT_BOILERPLATE.replace(
+            "platforms = []",
+            "platforms = [\"linux-64-musl\"]\n[system-requirements]\nlibc = { family = \"musl\", version = \"1.2\" }",
+        );
+        let err = ProjectManifest::from_toml_str(&contents)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("packages built against musl are published for 'linux-64'"));
+
+        let contents = contents.replace("linux-64-musl", "linux-64");
+        let manifest = ProjectManifest::from_toml_str(&contents).unwrap();
+        assert_eq!(manifest.project.platforms.value, vec![Platform::Linux64]);
+        assert_eq!(
+            manifest
+                .default_feature()
+                .system_requirements
+                .libc
+                .clone()
+                .unwrap()
+                .family_and_version()
+                .0,
+            "musl"
+        );
+    }
+
     #[test]
     fn test_target_specific_tasks() {
         let contents = format!(
@@ -1131,6 +1956,200 @@ mod test {
             .join("\n"));
     }
 
+    #[test]
+    fn test_pypi_to_conda() {
+        let contents = format!(
+            r#"
+            {PROJECT_BOILERPLATE}
+            [pypi-to-conda]
+            torch = "pytorch"
+            Pillow = "pillow"
+            "#
+        );
+
+        let manifest =
+            toml_edit::de::from_str::<ProjectManifest>(&contents).expect("parsing should succeed!");
+        assert_eq!(
+            manifest
+                .pypi_to_conda
+                .iter()
+                .map(|(pypi, conda)| format!("{} = {}", pypi.as_source_str(), conda.as_source()))
+                .collect_vec(),
+            vec!["torch = pytorch", "Pillow = pillow"]
+        );
+    }
+
+    #[test]
+    fn test_end_of_life() {
+        let contents = format!(
+            r#"
+            {PROJECT_BOILERPLATE}
+            [end-of-life]
+            python = {{ versions = "<3.8", message = "python 3.7 is no longer supported" }}
+            "#
+        );
+
+        let manifest =
+            toml_edit::de::from_str::<ProjectManifest>(&contents).expect("parsing should succeed!");
+        let python = &manifest.end_of_life[&PackageName::new_unchecked("python")];
+        assert_eq!(python.versions.to_string(), "<3.8");
+        assert_eq!(
+            python.message.as_deref(),
+            Some("python 3.7 is no longer supported")
+        );
+
+        let contents = format!(
+            r#"
+            {PROJECT_BOILERPLATE}
+            [end-of-life]
+            python = {{ version = "<3.8" }}
+            "#
+        );
+        assert!(toml_edit::de::from_str::<ProjectManifest>(&contents).is_err());
+    }
+
+    #[test]
+    fn test_banned_dependencies() {
+        let contents = format!(
+            r#"
+            {PROJECT_BOILERPLATE}
+            [banned-dependencies]
+            openssl = {{ version = "<3", reason = "end-of-life" }}
+            "#
+        );
+
+        let manifest = Manifest::from_str_with_strictness(Path::new(""), &contents, true)
+            .expect("parsing should succeed!");
+        let openssl = &manifest.parsed.banned_dependencies[&PackageName::new_unchecked("openssl")];
+        assert_eq!(openssl.versions.as_ref().unwrap().to_string(), "<3");
+        assert_eq!(openssl.reason.as_deref(), Some("end-of-life"));
+
+        let contents = format!(
+            r#"
+            {PROJECT_BOILERPLATE}
+            [banned-dependencies]
+            openssl = {{ versions = "<3" }}
+            "#
+        );
+        assert!(toml_edit::de::from_str::<ProjectManifest>(&contents).is_err());
+    }
+
+    #[test]
+    fn test_relative_channels() {
+        let contents = r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            channels = ["conda-forge", "./channel", "../shared/channel"]
+            platforms = ["linux-64"]
+            "#;
+        let root = Path::new("/projects/foo");
+        let manifest = Manifest::from_str(root, contents).unwrap();
+        let channels = manifest
+            .parsed
+            .project
+            .channels
+            .iter()
+            .map(|channel| channel.base_url().to_string())
+            .collect_vec();
+        assert_eq!(channels[0], "https://conda.anaconda.org/conda-forge/");
+        assert_eq!(
+            channels[1],
+            url::Url::from_directory_path(root.join("channel"))
+                .unwrap()
+                .to_string()
+        );
+        assert!(is_relative_channel_path("../shared/channel"));
+        assert!(!is_relative_channel_path("/srv/channel"));
+        assert!(!is_relative_channel_path("conda-forge"));
+    }
+
+    #[test]
+    fn test_interpolated_channels() {
+        std::env::set_var("PIXI_TEST_CHANNEL_MIRROR", "https://mirror.example.com");
+        let contents = r#"
+            [project]
+            name = "foo"
+            channels = ["${env:PIXI_TEST_CHANNEL_MIRROR}/conda-forge", "${env:PIXI_TEST_UNSET_CHANNEL:-bioconda}"]
+            platforms = ["linux-64"]
+
+            [feature.nightly]
+            channels = [{ channel = "${env:PIXI_TEST_CHANNEL_MIRROR}/pytorch-nightly", priority = 1 }]
+            "#;
+        let manifest = Manifest::from_str(Path::new(""), contents).unwrap();
+        let channels = manifest
+            .parsed
+            .project
+            .channels
+            .iter()
+            .map(|channel| channel.base_url().to_string())
+            .collect_vec();
+        assert_eq!(
+            channels,
+            [
+                "https://mirror.example.com/conda-forge/",
+                "https://conda.anaconda.org/bioconda/"
+            ]
+        );
+        let nightly = &manifest.parsed.features[&FeatureName::Named(String::from("nightly"))];
+        assert_eq!(
+            nightly.channels.as_ref().unwrap()[0]
+                .channel
+                .base_url()
+                .as_str(),
+            "https://mirror.example.com/pytorch-nightly/"
+        );
+
+        let err = ProjectManifest::from_toml_str(&contents.replace(":-bioconda", "")).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("the environment variable 'PIXI_TEST_UNSET_CHANNEL' is not set"));
+    }
+
+    #[test]
+    fn test_build_fallback() {
+        let manifest = toml_edit::de::from_str::<ProjectManifest>(PROJECT_BOILERPLATE).unwrap();
+        assert!(manifest.build_fallback.is_none());
+
+        let contents = format!(
+            r#"
+            {PROJECT_BOILERPLATE}
+            [build-fallback]
+            recipes = {{ libfoo = "recipes/libfoo" }}
+            "#
+        );
+        let manifest =
+            toml_edit::de::from_str::<ProjectManifest>(&contents).expect("parsing should succeed!");
+        let build_fallback = manifest.build_fallback.unwrap();
+        assert_eq!(
+            build_fallback.recipes[&PackageName::new_unchecked("libfoo")],
+            "recipes/libfoo"
+        );
+        assert!(build_fallback.conda_forge_feedstocks);
+    }
+
+    #[test]
+    fn test_overlays() {
+        let contents = format!(
+            r#"
+            {PROJECT_BOILERPLATE}
+            [overlays]
+            numpy = "overlays/numpy"
+            scipy = {{ path = "overlays/scipy", version = "==1.11.4" }}
+            "#
+        );
+        let manifest =
+            toml_edit::de::from_str::<ProjectManifest>(&contents).expect("parsing should succeed!");
+        assert_eq!(
+            manifest
+                .overlays
+                .iter()
+                .map(|(name, overlay)| format!("{} = {}", name.as_source(), overlay.path.display()))
+                .collect_vec(),
+            vec!["numpy = overlays/numpy", "scipy = overlays/scipy"]
+        );
+    }
+
     fn test_remove(file_contents: &str, name: &str, kind: SpecType, platform: Option<Platform>) {
         let mut manifest = Manifest::from_str(Path::new(""), file_contents).unwrap();
 
@@ -1340,6 +2359,24 @@ ypackage = {version = ">=1.2.3"}
             manifest.parsed.project.version.as_ref().unwrap().clone(),
             Version::from_str("1.2.3").unwrap()
         );
+
+        assert!(manifest.set_version(&String::from(">=1.0")).is_err());
+    }
+
+    #[test]
+    fn test_set_version_keeps_formatting() {
+        let file_contents = r#"[project]
+name = "foo"
+version = "0.1.0" # bumped by the release workflow
+channels = []
+platforms = ["linux-64"]
+"#;
+        let mut manifest = Manifest::from_str(Path::new(""), file_contents).unwrap();
+        manifest.set_version(&String::from("0.2.0")).unwrap();
+        assert_eq!(
+            manifest.document.to_string(),
+            file_contents.replace("0.1.0", "0.2.0")
+        );
     }
 
     #[test]
@@ -1494,4 +2531,48 @@ ypackage = {version = ">=1.2.3"}
 
         assert_eq!(manifest.parsed.project.channels, vec![]);
     }
+
+    #[test]
+    fn test_apply_channel_config() {
+        let file_contents = r#"
+            [project]
+            name = "foo"
+            channels = ["conda-forge", "https://example.com/channel"]
+            platforms = ["linux-64"]
+
+            [feature.bio]
+            channels = ["bioconda"]
+
+            [environments]
+            bio = ["bio"]
+        "#;
+        let mut manifest = Manifest::from_str(Path::new(""), file_contents).unwrap();
+        let channel_config = ChannelConfig {
+            channel_alias: url::Url::parse("https://mirror.example.com/conda/").unwrap(),
+        };
+        manifest.apply_channel_config(&channel_config).unwrap();
+
+        let urls = |channels: &[Channel]| {
+            channels
+                .iter()
+                .map(|channel| channel.base_url().to_string())
+                .collect_vec()
+        };
+        assert_eq!(
+            urls(&manifest.parsed.project.channels),
+            vec![
+                "https://mirror.example.com/conda/conda-forge/",
+                "https://example.com/channel/"
+            ]
+        );
+        assert!(urls(manifest.environment_channels("bio").unwrap())
+            .contains(&String::from("https://mirror.example.com/conda/bioconda/")));
+
+        // Channels that are added later are resolved with the alias as well
+        manifest.remove_channels(["conda-forge"]).unwrap();
+        assert_eq!(
+            urls(&manifest.parsed.project.channels),
+            vec!["https://example.com/channel/"]
+        );
+    }
 }
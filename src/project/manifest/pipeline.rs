@@ -0,0 +1,181 @@
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools;
+use serde::Deserialize;
+use std::path::PathBuf;
+
+/// The key of a matrix that selects the environment of a stage instead of setting a variable.
+pub const MATRIX_ENVIRONMENT_KEY: &str = "environment";
+
+/// A named workflow of the project that runs tasks in multiple stages, e.g. `lint`, `test` and
+/// `package`. The stages form a DAG through their `depends-on` keys.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Pipeline {
+    /// A description of the pipeline that is shown when listing the pipelines.
+    pub description: Option<String>,
+
+    /// The stages of the pipeline by name.
+    pub stages: IndexMap<String, Stage>,
+}
+
+/// A stage of a pipeline, it runs a task for every combination of its matrix.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Stage {
+    /// The task (or command) that is run, in the same format as the arguments of `pixi run`.
+    pub task: String,
+
+    /// The stages that must succeed before this stage is run.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+
+    /// The environment the task is run in, defaults to the default environment of the project.
+    pub environment: Option<String>,
+
+    /// The task is run for every combination of the values. The values are available to the task
+    /// as `PIXI_MATRIX_<KEY>` variables, the `environment` key selects the environment instead.
+    #[serde(default)]
+    pub matrix: IndexMap<String, Vec<String>>,
+
+    /// Files or directories (relative to the project root) that are kept after the stage succeeded.
+    #[serde(default)]
+    pub artifacts: Vec<PathBuf>,
+}
+
+impl Pipeline {
+    /// Returns the names of the stages in an order in which every stage comes after the stages it
+    /// depends on. Stages without a dependency between them keep the order of the manifest.
+    ///
+    /// Fails if a stage depends on an unknown stage, the stages depend on each other, or the matrix
+    /// of a stage has a key without values.
+    pub fn stage_order(&self) -> Result<Vec<&str>, String> {
+        for (name, stage) in &self.stages {
+            if let Some((key, _)) = stage.matrix.iter().find(|(_, values)| values.is_empty()) {
+                return Err(format!(
+                    "the matrix of the stage '{name}' has no values for '{key}'"
+                ));
+            }
+            if let Some(dependency) = stage
+                .depends_on
+                .iter()
+                .find(|dependency| !self.stages.contains_key(dependency.as_str()))
+            {
+                return Err(format!(
+                    "the stage '{name}' depends on the unknown stage '{dependency}'"
+                ));
+            }
+        }
+
+        let mut order = IndexSet::new();
+        while order.len() < self.stages.len() {
+            let next = self.stages.iter().find(|(name, stage)| {
+                !order.contains(name.as_str())
+                    && stage
+                        .depends_on
+                        .iter()
+                        .all(|dependency| order.contains(dependency.as_str()))
+            });
+            match next {
+                Some((name, _)) => {
+                    order.insert(name.as_str());
+                }
+                None => {
+                    return Err(format!(
+                        "the stages {} depend on each other",
+                        self.stages
+                            .keys()
+                            .filter(|name| !order.contains(name.as_str()))
+                            .map(|name| format!("'{name}'"))
+                            .join(", ")
+                    ))
+                }
+            }
+        }
+        Ok(order.into_iter().collect())
+    }
+}
+
+impl Stage {
+    /// Returns every combination of the values of the matrix, a stage without a matrix has a
+    /// single empty combination.
+    pub fn matrix_combinations(&self) -> Vec<IndexMap<&str, &str>> {
+        self.matrix
+            .iter()
+            .map(|(key, values)| {
+                values
+                    .iter()
+                    .map(move |value| (key.as_str(), value.as_str()))
+            })
+            .multi_cartesian_product()
+            .map(IndexMap::from_iter)
+            .pad_using(1, |_| IndexMap::new())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pipeline(contents: &str) -> Pipeline {
+        toml_edit::de::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn test_stage_order() {
+        let pipeline = pipeline(
+            r#"
+            [stages.package]
+            task = "build"
+            depends-on = ["test", "lint"]
+
+            [stages.test]
+            task = "test"
+            matrix = { environment = ["cuda12", "cpu"], python = ["3.10", "3.11"] }
+
+            [stages.lint]
+            task = "lint"
+            "#,
+        );
+        assert_eq!(pipeline.stage_order().unwrap(), ["test", "lint", "package"]);
+
+        let combinations = pipeline.stages["test"].matrix_combinations();
+        assert_eq!(combinations.len(), 4);
+        assert_eq!(
+            combinations[1],
+            IndexMap::from_iter([("environment", "cuda12"), ("python", "3.11")])
+        );
+        assert_eq!(
+            pipeline.stages["lint"].matrix_combinations(),
+            [IndexMap::<&str, &str>::new()]
+        );
+    }
+
+    #[test]
+    fn test_invalid_stage_order() {
+        let unknown = pipeline(
+            r#"
+            [stages.test]
+            task = "test"
+            depends-on = ["build"]
+            "#,
+        );
+        assert!(unknown.stage_order().is_err());
+
+        let cycle = pipeline(
+            r#"
+            [stages.a]
+            task = "a"
+            depends-on = ["b"]
+
+            [stages.b]
+            task = "b"
+            depends-on = ["a"]
+            "#,
+        );
+        assert_eq!(
+            cycle.stage_order().unwrap_err(),
+            "the stages 'a', 'b' depend on each other"
+        );
+    }
+}
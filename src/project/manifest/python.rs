@@ -12,6 +12,8 @@ use toml_edit::Item;
 pub struct PyPiRequirement {
     pub(crate) version: Option<pep440_rs::VersionSpecifiers>,
     pub(crate) extras: Option<Vec<String>>,
+    /// Whether pre-release versions of the package can be selected
+    pub(crate) allow_prerelease: bool,
 }
 
 /// The type of parse error that occurred when parsing match spec.
@@ -37,8 +39,8 @@ impl fmt::Display for PyPiRequirement {
 impl From<PyPiRequirement> for Item {
     /// PyPiRequirement to a toml_edit item, to put in the manifest file.
     fn from(val: PyPiRequirement) -> Item {
-        if val.extras.is_some() {
-            // If extras is defined use an inline table
+        if val.extras.is_some() || val.allow_prerelease {
+            // If extras or allow-prerelease is defined use an inline table
             let mut table = toml_edit::Table::new().into_inline_table();
 
             // First add the version
@@ -55,10 +57,15 @@ impl From<PyPiRequirement> for Item {
                 );
             }
             // Add extras as an array.
-            table.insert(
-                "extras",
-                toml_edit::Value::Array(toml_edit::Array::from_iter(val.extras.unwrap())),
-            );
+            if let Some(extras) = val.extras {
+                table.insert(
+                    "extras",
+                    toml_edit::Value::Array(toml_edit::Array::from_iter(extras)),
+                );
+            }
+            if val.allow_prerelease {
+                table.insert("allow-prerelease", toml_edit::Value::from(true));
+            }
             Item::Value(toml_edit::Value::InlineTable(table))
         } else {
             // Without extras use the string representation.
@@ -84,6 +91,7 @@ impl FromStr for PyPiRequirement {
             Ok(Self {
                 version: None,
                 extras: None,
+                allow_prerelease: false,
             })
         } else if s.starts_with(|c: char| c.is_ascii_digit()) {
             Err(ParsePyPiRequirementError::MissingOperator(s.to_string()))
@@ -95,6 +103,7 @@ impl FromStr for PyPiRequirement {
                         .map_err(ParsePyPiRequirementError::Pep440Error)?,
                 ),
                 extras: None,
+                allow_prerelease: false,
             })
         }
     }
@@ -114,6 +123,7 @@ impl From<pep508_rs::Requirement> for PyPiRequirement {
         PyPiRequirement {
             version,
             extras: req.extras,
+            allow_prerelease: false,
         }
     }
 }
@@ -159,6 +169,8 @@ impl<'de> Deserialize<'de> for PyPiRequirement {
                 struct RawPyPiRequirement {
                     version: Option<String>,
                     extras: Option<Vec<String>>,
+                    #[serde(default, rename = "allow-prerelease")]
+                    allow_prerelease: bool,
                 }
                 let raw_requirement =
                     RawPyPiRequirement::deserialize(de::value::MapAccessDeserializer::new(map))?;
@@ -176,6 +188,7 @@ impl<'de> Deserialize<'de> for PyPiRequirement {
                 Ok(PyPiRequirement {
                     version,
                     extras: raw_requirement.extras,
+                    allow_prerelease: raw_requirement.allow_prerelease,
                 })
             }
         }
@@ -211,7 +224,8 @@ mod test {
             requirement.first().unwrap().1,
             &PyPiRequirement {
                 version: Some(pep440_rs::VersionSpecifiers::from_str(">=3.12").unwrap()),
-                extras: None
+                extras: None,
+                allow_prerelease: false
             }
         );
         let requirement: IndexMap<rip::types::PackageName, PyPiRequirement> =
@@ -220,7 +234,8 @@ mod test {
             requirement.first().unwrap().1,
             &PyPiRequirement {
                 version: Some(pep440_rs::VersionSpecifiers::from_str("==3.12.0").unwrap()),
-                extras: None
+                extras: None,
+                allow_prerelease: false
             }
         );
 
@@ -230,7 +245,8 @@ mod test {
             requirement.first().unwrap().1,
             &PyPiRequirement {
                 version: Some(pep440_rs::VersionSpecifiers::from_str("~=2.1.3").unwrap()),
-                extras: None
+                extras: None,
+                allow_prerelease: false
             }
         );
 
@@ -240,7 +256,8 @@ mod test {
             requirement.first().unwrap().1,
             &PyPiRequirement {
                 version: None,
-                extras: None
+                extras: None,
+                allow_prerelease: false
             }
         );
     }
@@ -257,7 +274,8 @@ mod test {
             requirement.first().unwrap().1,
             &PyPiRequirement {
                 version: Some(pep440_rs::VersionSpecifiers::from_str(">=3.12").unwrap()),
-                extras: Some(vec!("bar".to_string()))
+                extras: Some(vec!("bar".to_string())),
+                allow_prerelease: false
             }
         );
 
@@ -274,8 +292,22 @@ mod test {
             requirement.first().unwrap().1,
             &PyPiRequirement {
                 version: Some(pep440_rs::VersionSpecifiers::from_str(">=3.12,<3.13.0").unwrap()),
-                extras: Some(vec!("bar".to_string(), "foo".to_string()))
+                extras: Some(vec!("bar".to_string(), "foo".to_string())),
+                allow_prerelease: false
             }
         );
     }
+
+    #[test]
+    fn test_allow_prerelease() {
+        let requirement: IndexMap<rip::types::PackageName, PyPiRequirement> =
+            toml_edit::de::from_str(r#"foo = { version=">=2", allow-prerelease = true }"#).unwrap();
+        let (name, requirement) = requirement.first().unwrap();
+        assert!(requirement.allow_prerelease);
+        assert_eq!(
+            requirement.to_string(),
+            "{ version = \">=2\", allow-prerelease = true }"
+        );
+        assert_eq!(requirement.as_pep508(name).to_string(), "foo>=2");
+    }
 }
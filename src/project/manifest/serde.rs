@@ -1,3 +1,4 @@
+use crate::utils::interpolate::interpolate_env;
 use rattler_conda_types::{Channel, ChannelConfig};
 use serde::de::Error;
 use serde::{Deserialize, Deserializer};
@@ -11,6 +12,7 @@ impl<'de> serde_with::DeserializeAs<'de, Channel> for ChannelStr {
         D: Deserializer<'de>,
     {
         let channel_str = Cow::<str>::deserialize(deserializer)?;
+        let channel_str = interpolate_env(&channel_str).map_err(D::Error::custom)?;
         let channel_config = ChannelConfig::default();
         Channel::from_str(channel_str, &channel_config).map_err(D::Error::custom)
     }
@@ -0,0 +1,575 @@
+use crate::utils::spanned::PixiSpanned;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use miette::{LabeledSpan, NamedSource, Report};
+use serde::de::{Deserialize, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// The keys that are allowed in a table of the manifest.
+enum Schema {
+    /// A table with a fixed set of keys.
+    Table(&'static [(&'static str, Schema)]),
+    /// A table with arbitrary keys (e.g. package names) whose values all follow the same schema.
+    Map(&'static Schema),
+    /// A value that is not checked.
+    Any,
+}
+
+const ACTIVATION: Schema = Schema::Table(&[("scripts", Schema::Any)]);
+
+const SYSTEM_REQUIREMENTS: Schema = Schema::Table(&[
+    ("windows", Schema::Any),
+    ("unix", Schema::Any),
+    ("macos", Schema::Any),
+    ("linux", Schema::Any),
+    ("cuda", Schema::Any),
+    (
+        "libc",
+        Schema::Table(&[("family", Schema::Any), ("version", Schema::Any)]),
+    ),
+    ("archspec", Schema::Any),
+]);
+
+const CONDA_DEPENDENCIES: Schema = Schema::Map(&Schema::Table(&[
+    ("version", Schema::Any),
+    ("build", Schema::Any),
+    ("build_number", Schema::Any),
+    ("file_name", Schema::Any),
+    ("channel", Schema::Any),
+    ("subdir", Schema::Any),
+    ("namespace", Schema::Any),
+    ("md5", Schema::Any),
+    ("sha256", Schema::Any),
+    ("variant", Schema::Any),
+    ("with", Schema::Any),
+]));
+
+const PYPI_DEPENDENCIES: Schema = Schema::Map(&Schema::Table(&[
+    ("version", Schema::Any),
+    ("extras", Schema::Any),
+    ("allow-prerelease", Schema::Any),
+]));
+
+const TASKS: Schema = Schema::Map(&Schema::Table(&[
+    ("cmd", Schema::Any),
+    ("depends_on", Schema::Any),
+    ("cwd", Schema::Any),
+    ("inputs", Schema::Any),
+    ("outputs", Schema::Any),
+    ("cache-env", Schema::Any),
+    ("cache_env", Schema::Any),
+    ("interactive", Schema::Any),
+    ("expose", Schema::Any),
+    ("skip-if", Schema::Any),
+    ("only-if", Schema::Any),
+]));
+
+const PIPELINES: Schema = Schema::Map(&Schema::Table(&[
+    ("description", Schema::Any),
+    (
+        "stages",
+        Schema::Map(&Schema::Table(&[
+            ("task", Schema::Any),
+            ("depends-on", Schema::Any),
+            ("environment", Schema::Any),
+            ("matrix", Schema::Any),
+            ("artifacts", Schema::Any),
+        ])),
+    ),
+]));
+
+const TARGET: Schema = Schema::Table(&[
+    ("dependencies", CONDA_DEPENDENCIES),
+    ("host-dependencies", CONDA_DEPENDENCIES),
+    ("build-dependencies", CONDA_DEPENDENCIES),
+    ("pypi-dependencies", PYPI_DEPENDENCIES),
+    ("activation", ACTIVATION),
+    ("deactivation", ACTIVATION),
+    ("tasks", TASKS),
+]);
+
+const MANIFEST: Schema = Schema::Table(&[
+    (
+        "project",
+        Schema::Table(&[
+            ("name", Schema::Any),
+            ("version", Schema::Any),
+            ("description", Schema::Any),
+            ("authors", Schema::Any),
+            ("channels", Schema::Any),
+            ("platforms", Schema::Any),
+            ("license", Schema::Any),
+            ("license-file", Schema::Any),
+            ("readme", Schema::Any),
+            ("homepage", Schema::Any),
+            ("repository", Schema::Any),
+            ("documentation", Schema::Any),
+            ("shortcuts", Schema::Any),
+            ("post-link-scripts", Schema::Any),
+            ("compile-pyc", Schema::Any),
+            ("shebang", Schema::Any),
+            ("prerelease-packages", Schema::Any),
+            ("prerelease-labels", Schema::Any),
+            ("package-activation-scripts", Schema::Any),
+            ("default-task", Schema::Any),
+            ("strict", Schema::Any),
+            ("allow-warnings", Schema::Any),
+            ("split-lock-files", Schema::Any),
+            ("lock-file-format", Schema::Any),
+            ("passthrough-env", Schema::Any),
+        ]),
+    ),
+    ("system-requirements", SYSTEM_REQUIREMENTS),
+    ("target", Schema::Map(&TARGET)),
+    ("dependencies", CONDA_DEPENDENCIES),
+    ("host-dependencies", CONDA_DEPENDENCIES),
+    ("build-dependencies", CONDA_DEPENDENCIES),
+    ("pypi-dependencies", PYPI_DEPENDENCIES),
+    ("activation", ACTIVATION),
+    ("deactivation", ACTIVATION),
+    ("tasks", TASKS),
+    ("pipelines", PIPELINES),
+    ("pypi-to-conda", Schema::Any),
+    (
+        "end-of-life",
+        Schema::Map(&Schema::Table(&[
+            ("versions", Schema::Any),
+            ("message", Schema::Any),
+        ])),
+    ),
+    (
+        "banned-dependencies",
+        Schema::Map(&Schema::Table(&[
+            ("version", Schema::Any),
+            ("reason", Schema::Any),
+        ])),
+    ),
+    (
+        "build-fallback",
+        Schema::Table(&[
+            ("recipes", Schema::Any),
+            ("conda-forge-feedstocks", Schema::Any),
+        ]),
+    ),
+    ("overlays", Schema::Any),
+    ("alias", Schema::Any),
+    (
+        "feature",
+        Schema::Map(&Schema::Table(&[
+            ("channels", Schema::Any),
+            ("system-requirements", SYSTEM_REQUIREMENTS),
+            ("target", Schema::Map(&TARGET)),
+            ("dependencies", CONDA_DEPENDENCIES),
+            ("host-dependencies", CONDA_DEPENDENCIES),
+            ("build-dependencies", CONDA_DEPENDENCIES),
+            ("pypi-dependencies", PYPI_DEPENDENCIES),
+            ("activation", ACTIVATION),
+            ("deactivation", ACTIVATION),
+            ("tasks", TASKS),
+        ])),
+    ),
+    ("environments", Schema::Any),
+    ("workspace", Schema::Table(&[("members", Schema::Any)])),
+    ("include", Schema::Any),
+]);
+
+/// The structure of a TOML document with the locations of the keys.
+enum RawValue {
+    Table(IndexMap<PixiSpanned<String>, RawValue>),
+    Bool(bool),
+    Other,
+}
+
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawValueVisitor;
+
+        impl<'de> Visitor<'de> for RawValueVisitor {
+            type Value = RawValue;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("any TOML value")
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+                Ok(RawValue::Bool(v))
+            }
+
+            fn visit_i64<E>(self, _v: i64) -> Result<Self::Value, E> {
+                Ok(RawValue::Other)
+            }
+
+            fn visit_u64<E>(self, _v: u64) -> Result<Self::Value, E> {
+                Ok(RawValue::Other)
+            }
+
+            fn visit_f64<E>(self, _v: f64) -> Result<Self::Value, E> {
+                Ok(RawValue::Other)
+            }
+
+            fn visit_str<E>(self, _v: &str) -> Result<Self::Value, E> {
+                Ok(RawValue::Other)
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                // Arrays don't contain tables that are checked.
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(RawValue::Other)
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut table = IndexMap::new();
+                while let Some((key, value)) = map.next_entry()? {
+                    table.insert(key, value);
+                }
+                Ok(RawValue::Table(table))
+            }
+        }
+
+        deserializer.deserialize_any(RawValueVisitor)
+    }
+}
+
+/// A key in the manifest that pixi doesn't know and would otherwise ignore or reject with a less
+/// helpful error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownKey {
+    /// The dotted path of the table that contains the key.
+    pub table: String,
+    /// The unknown key.
+    pub key: String,
+    /// The location of the key in the manifest.
+    pub span: Option<Range<usize>>,
+    /// A known key that was probably meant.
+    pub suggestion: Option<&'static str>,
+}
+
+/// The result of checking the keys of a manifest.
+#[derive(Debug, Default)]
+pub struct KeyCheck {
+    /// Whether `strict = true` is set in the `[project]` table.
+    pub strict: bool,
+    /// All keys that are not known to pixi.
+    pub unknown_keys: Vec<UnknownKey>,
+}
+
+/// Finds the keys in the manifest that pixi does not know. Returns `None` if the contents are not
+/// valid TOML, the parser of the manifest reports that error.
+pub fn check_keys(contents: &str) -> Option<KeyCheck> {
+    let document: RawValue = toml_edit::de::from_str(contents).ok()?;
+    let strict = match &document {
+        RawValue::Table(table) => match table.get("project") {
+            Some(RawValue::Table(project)) => {
+                matches!(project.get("strict"), Some(RawValue::Bool(true)))
+            }
+            _ => false,
+        },
+        _ => false,
+    };
+
+    let mut unknown_keys = Vec::new();
+    check_value(&document, &MANIFEST, "", &mut unknown_keys);
+    Some(KeyCheck {
+        strict,
+        unknown_keys,
+    })
+}
+
+fn check_value(value: &RawValue, schema: &Schema, path: &str, unknown_keys: &mut Vec<UnknownKey>) {
+    let RawValue::Table(table) = value else {
+        // Tasks and dependencies can also be strings.
+        return;
+    };
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{path}.{key}")
+        }
+    };
+
+    match schema {
+        Schema::Table(fields) => {
+            for (key, value) in table {
+                match fields
+                    .iter()
+                    .find(|(name, _)| *name == key.get_ref().as_str())
+                {
+                    Some((name, schema)) => check_value(value, schema, &join(name), unknown_keys),
+                    None => unknown_keys.push(UnknownKey {
+                        table: path.to_string(),
+                        key: key.get_ref().clone(),
+                        span: key.span(),
+                        suggestion: fields
+                            .iter()
+                            .map(|(name, _)| (strsim::jaro(name, key.get_ref()), *name))
+                            .filter(|(score, _)| *score >= 0.8)
+                            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+                            .map(|(_, name)| name),
+                    }),
+                }
+            }
+        }
+        Schema::Map(schema) => {
+            for (key, value) in table {
+                check_value(value, schema, &join(key.get_ref()), unknown_keys);
+            }
+        }
+        Schema::Any => {}
+    }
+}
+
+/// The tables that contain conda dependencies, at the top level and in every target.
+const CONDA_DEPENDENCY_TABLES: [&str; 3] =
+    ["dependencies", "host-dependencies", "build-dependencies"];
+
+/// Returns the locations of the conda dependencies in the manifest by their lowercase name. A
+/// dependency can be listed in multiple tables, e.g. for multiple targets.
+pub fn dependency_spans(contents: &str) -> HashMap<String, Vec<Range<usize>>> {
+    let mut spans: HashMap<String, Vec<Range<usize>>> = HashMap::new();
+    let Ok(RawValue::Table(document)) = toml_edit::de::from_str::<RawValue>(contents) else {
+        return spans;
+    };
+    let mut tables = vec![&document];
+    if let Some(RawValue::Table(targets)) = document.get("target") {
+        tables.extend(targets.values().filter_map(|target| match target {
+            RawValue::Table(target) => Some(target),
+            _ => None,
+        }));
+    }
+    for table in tables {
+        for name in CONDA_DEPENDENCY_TABLES {
+            let Some(RawValue::Table(dependencies)) = table.get(name) else {
+                continue;
+            };
+            for key in dependencies.keys() {
+                if let Some(span) = key.span() {
+                    spans
+                        .entry(key.get_ref().to_lowercase())
+                        .or_default()
+                        .push(span);
+                }
+            }
+        }
+    }
+    spans
+}
+
+impl UnknownKey {
+    /// Returns a message that describes the key.
+    pub fn message(&self) -> String {
+        let location = if self.table.is_empty() {
+            String::from("the manifest")
+        } else {
+            format!("'{}'", self.table)
+        };
+        match self.suggestion {
+            Some(suggestion) => format!(
+                "unknown key '{}' in {location}, did you mean '{suggestion}'?",
+                self.key
+            ),
+            None => format!("unknown key '{}' in {location}", self.key),
+        }
+    }
+}
+
+/// Creates an error that points at all the unknown keys in the manifest.
+pub fn unknown_keys_report(unknown_keys: &[UnknownKey], source: NamedSource) -> Report {
+    let labels = unknown_keys
+        .iter()
+        .map(|key| LabeledSpan::at(key.span.clone().unwrap_or_default(), key.message()))
+        .collect_vec();
+    miette::miette!(
+        labels = labels,
+        help = "remove the keys or correct their spelling, see https://pixi.sh/configuration/",
+        "the manifest contains {} unknown key{}",
+        unknown_keys.len(),
+        if unknown_keys.len() == 1 { "" } else { "s" }
+    )
+    .with_source_code(source)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::project::manifest::{Manifest, PyPiRequirement};
+    use crate::project::SpecType;
+    use crate::task::{Alias, CmdArgs, Condition, Execute, Task};
+    use rattler_conda_types::{MatchSpec, PackageName, Platform};
+    use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_check_keys() {
+        let contents = r#"
+        [project]
+        name = "foo"
+        channels = []
+        platforms = []
+        strict = true
+
+        [dependencies]
+        python = { verison = "3.11" }
+
+        [tasks]
+        build = "make"
+        test = { cmd = "pytest", depends-on = ["build"] }
+
+        [target.linux-64.pypi-dependencies]
+        requests = { version = "*", extra = ["socks"] }
+        "#;
+
+        let check = check_keys(contents).unwrap();
+        assert!(check.strict);
+        let keys = check
+            .unknown_keys
+            .iter()
+            .map(|key| (key.table.as_str(), key.key.as_str(), key.suggestion))
+            .collect_vec();
+        assert_eq!(
+            keys,
+            vec![
+                ("dependencies.python", "verison", Some("version")),
+                ("tasks.test", "depends-on", Some("depends_on")),
+                (
+                    "target.linux-64.pypi-dependencies.requests",
+                    "extra",
+                    Some("extras")
+                ),
+            ]
+        );
+        let span = check.unknown_keys[1].span.clone().unwrap();
+        assert_eq!(&contents[span], "depends-on");
+    }
+
+    #[test]
+    fn test_check_keys_not_strict() {
+        let check = check_keys(
+            r#"
+            [project]
+            name = "foo"
+            channels = []
+            platforms = []
+            "#,
+        )
+        .unwrap();
+        assert!(!check.strict);
+        assert!(check.unknown_keys.is_empty());
+    }
+
+    #[test]
+    fn test_dependency_spans() {
+        let contents = r#"
+            [project]
+            name = "foo"
+            channels = []
+            platforms = []
+
+            [dependencies]
+            Numpy = "*"
+
+            [target.linux-64.host-dependencies]
+            numpy = "1.26.*"
+            "#;
+        let spans = dependency_spans(contents);
+        assert_eq!(spans.len(), 1);
+        let spans = &spans["numpy"];
+        assert_eq!(spans.len(), 2);
+        assert_eq!(&contents[spans[0].clone()], "Numpy");
+        assert_eq!(&contents[spans[1].clone()], "numpy");
+    }
+
+    #[test]
+    fn test_written_keys_are_known() {
+        let mut manifest = Manifest::from_str(
+            Path::new(""),
+            r#"
+            [project]
+            name = "foo"
+            version = "0.1.0"
+            channels = []
+            platforms = ["linux-64"]
+            strict = true
+            "#,
+        )
+        .unwrap();
+
+        // Every key that pixi writes to the manifest.
+        manifest.set_version(&String::from("0.2.0")).unwrap();
+        manifest
+            .set_description(&String::from("A project"))
+            .unwrap();
+        manifest.add_channels(["conda-forge"]).unwrap();
+        manifest.add_platforms([Platform::Win64].iter()).unwrap();
+        manifest
+            .add_prerelease_packages([PackageName::from_str("numpy").unwrap()])
+            .unwrap();
+        for (spec_type, platform) in [
+            (SpecType::Run, None),
+            (SpecType::Host, Some(Platform::Linux64)),
+            (SpecType::Build, Some(Platform::Win64)),
+        ] {
+            manifest
+                .add_dependency(
+                    &MatchSpec::from_str("python >=3.11 py*").unwrap(),
+                    spec_type,
+                    platform,
+                )
+                .unwrap();
+        }
+        let requirement = PyPiRequirement {
+            version: Some(">=2.31".parse().unwrap()),
+            extras: Some(vec![String::from("socks")]),
+            allow_prerelease: true,
+        };
+        for platform in [None, Some(Platform::Linux64)] {
+            manifest
+                .add_pypi_dependency(&"requests".parse().unwrap(), &requirement, platform)
+                .unwrap();
+        }
+        let condition = Condition::from_str("platform == 'linux-64'").unwrap();
+        let task = Execute {
+            cmd: CmdArgs::Multiple(vec![String::from("pytest"), String::from("tests")]),
+            depends_on: vec![String::from("build")],
+            cwd: Some(PathBuf::from("tests")),
+            inputs: vec![PathBuf::from("src")],
+            outputs: vec![PathBuf::from("report.xml")],
+            cache_env: vec![String::from("CI")],
+            interactive: true,
+            expose: true,
+            skip_if: Some(condition.clone()),
+            only_if: Some(condition),
+        };
+        manifest
+            .add_task("build", Task::Plain(String::from("make")), None)
+            .unwrap();
+        manifest
+            .add_task("test", Task::Execute(task), Some(Platform::Linux64))
+            .unwrap();
+        manifest
+            .add_task(
+                "all",
+                Task::Alias(Alias {
+                    depends_on: vec![String::from("build")],
+                }),
+                None,
+            )
+            .unwrap();
+
+        let contents = manifest.document.to_string();
+        let check = check_keys(&contents).unwrap();
+        assert!(check.strict);
+        assert_eq!(check.unknown_keys, vec![], "{contents}");
+        Manifest::from_str_with_strictness(Path::new(""), contents, true).unwrap();
+    }
+}
@@ -1,7 +1,9 @@
+use itertools::Itertools;
 use rattler_conda_types::Version;
 use rattler_virtual_packages::{Archspec, Cuda, LibC, Linux, Osx, VirtualPackage};
-use serde::Deserialize;
+use serde::{de::Visitor, Deserialize, Deserializer};
 use serde_with::{serde_as, DisplayFromStr};
+use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
 /// Describes the minimal system requirements to be able to run a certain environment.
@@ -61,6 +63,157 @@ impl SystemRequirements {
 
         result
     }
+
+    /// Combines the requirements of two features, the highest version of every requirement is
+    /// used and the other requirements of `other` take precedence. Returns an error if the
+    /// features require a different libc family.
+    pub fn union(&self, other: &Self) -> Result<Self, String> {
+        let libc = match (&self.libc, &other.libc) {
+            (Some(libc), Some(other_libc)) => {
+                let (family, version) = libc.family_and_version();
+                let (other_family, other_version) = other_libc.family_and_version();
+                if family != other_family {
+                    return Err(format!(
+                        "the libc families '{family}' and '{other_family}' can't be combined"
+                    ));
+                }
+                Some(if other_version > version {
+                    other_libc.clone()
+                } else {
+                    libc.clone()
+                })
+            }
+            (libc, other_libc) => other_libc.clone().or_else(|| libc.clone()),
+        };
+
+        Ok(Self {
+            windows: other.windows.or(self.windows),
+            unix: other.unix.or(self.unix),
+            macos: self.macos.clone().max(other.macos.clone()),
+            linux: self.linux.clone().max(other.linux.clone()),
+            cuda: self.cuda.clone().max(other.cuda.clone()),
+            libc,
+            archspec: other.archspec.clone().or_else(|| self.archspec.clone()),
+        })
+    }
+}
+
+/// Built-in presets of system requirements that expand to concrete linux and glibc versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemRequirementsPreset {
+    /// Systems from the CentOS 6 era, equivalent to `manylinux2010`.
+    LinuxLegacy,
+    /// Systems from the Ubuntu 20.04 / Debian 11 era.
+    LinuxModern,
+    /// The `manylinux2010` platform (CentOS 6).
+    Manylinux2010,
+    /// The `manylinux2014` platform (CentOS 7).
+    Manylinux2014,
+    /// The `manylinux_2_28` platform (AlmaLinux 8).
+    Manylinux228,
+}
+
+impl SystemRequirementsPreset {
+    /// All the presets, in the order they are listed in error messages.
+    const ALL: [SystemRequirementsPreset; 5] = [
+        SystemRequirementsPreset::LinuxLegacy,
+        SystemRequirementsPreset::LinuxModern,
+        SystemRequirementsPreset::Manylinux2010,
+        SystemRequirementsPreset::Manylinux2014,
+        SystemRequirementsPreset::Manylinux228,
+    ];
+
+    /// Returns the name of the preset as used in the manifest.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SystemRequirementsPreset::LinuxLegacy => "linux-legacy",
+            SystemRequirementsPreset::LinuxModern => "linux-modern",
+            SystemRequirementsPreset::Manylinux2010 => "manylinux2010",
+            SystemRequirementsPreset::Manylinux2014 => "manylinux2014",
+            SystemRequirementsPreset::Manylinux228 => "manylinux_2_28",
+        }
+    }
+
+    /// Returns the minimal linux kernel and glibc versions of the preset.
+    fn linux_and_glibc(&self) -> (&'static str, &'static str) {
+        match self {
+            SystemRequirementsPreset::LinuxLegacy | SystemRequirementsPreset::Manylinux2010 => {
+                ("2.6.32", "2.12")
+            }
+            SystemRequirementsPreset::Manylinux2014 => ("3.10", "2.17"),
+            SystemRequirementsPreset::Manylinux228 => ("4.18", "2.28"),
+            SystemRequirementsPreset::LinuxModern => ("5.4", "2.31"),
+        }
+    }
+
+    /// Expands the preset into concrete system requirements.
+    pub fn system_requirements(&self) -> SystemRequirements {
+        let (linux, glibc) = self.linux_and_glibc();
+        SystemRequirements {
+            unix: Some(true),
+            linux: Some(Version::from_str(linux).expect("preset versions are valid")),
+            libc: Some(LibCSystemRequirement::GlibC(
+                Version::from_str(glibc).expect("preset versions are valid"),
+            )),
+            ..SystemRequirements::default()
+        }
+    }
+}
+
+impl Display for SystemRequirementsPreset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SystemRequirementsPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.as_str() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown system-requirements preset `{s}`, expected one of {}",
+                    Self::ALL.iter().map(|p| format!("`{p}`")).join(", ")
+                )
+            })
+    }
+}
+
+/// Deserializes the `system-requirements` of a manifest which is either a table or the name of a
+/// [`SystemRequirementsPreset`].
+///
+/// This uses a hand-written visitor instead of an untagged enum so errors in the table still
+/// point at the offending key.
+pub(super) fn deserialize_system_requirements<'de, D>(
+    deserializer: D,
+) -> Result<SystemRequirements, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct SystemRequirementsVisitor;
+
+    impl<'de> Visitor<'de> for SystemRequirementsVisitor {
+        type Value = SystemRequirements;
+
+        fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+            f.write_str("a table of system requirements or the name of a preset")
+        }
+
+        fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            SystemRequirementsPreset::from_str(v)
+                .map(|preset| preset.system_requirements())
+                .map_err(E::custom)
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            SystemRequirements::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+        }
+    }
+
+    deserializer.deserialize_any(SystemRequirementsVisitor)
 }
 
 #[derive(Debug, Clone)]
@@ -185,6 +338,52 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_system_requirements_preset() {
+        #[derive(Deserialize)]
+        struct Manifest {
+            #[serde(
+                rename = "system-requirements",
+                deserialize_with = "deserialize_system_requirements"
+            )]
+            system_requirements: SystemRequirements,
+        }
+
+        let manifest: Manifest =
+            toml_edit::de::from_str(r#"system-requirements = "manylinux2014""#).unwrap();
+        assert_eq!(
+            manifest.system_requirements.virtual_packages(),
+            vec![
+                VirtualPackage::Unix,
+                VirtualPackage::Linux(Linux {
+                    version: Version::from_str("3.10").unwrap(),
+                }),
+                VirtualPackage::LibC(LibC {
+                    version: Version::from_str("2.17").unwrap(),
+                    family: "glibc".to_string(),
+                }),
+            ]
+        );
+
+        let manifest: Manifest = toml_edit::de::from_str(
+            r#"
+        [system-requirements]
+        linux = "5.11"
+        "#,
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.system_requirements.linux,
+            Some(Version::from_str("5.11").unwrap())
+        );
+
+        let error = toml_edit::de::from_str::<Manifest>(r#"system-requirements = "linux-ancient""#)
+            .map(|_| ())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("unknown system-requirements preset `linux-ancient`"));
+    }
+
     #[test]
     fn test_system_requirements_failing_edge_cases() {
         #[derive(Deserialize)]
@@ -234,4 +433,40 @@ mod test {
             assert_snapshot!(name, &error, file_content);
         }
     }
+
+    #[test]
+    fn test_union() {
+        let requirements =
+            |content: &str| -> SystemRequirements { toml_edit::de::from_str(content).unwrap() };
+        let default = requirements(
+            r#"
+            linux = "4.18"
+            cuda = "11.8"
+            libc = "2.17"
+            "#,
+        );
+        let gpu = requirements(
+            r#"
+            cuda = "12.0"
+            libc = { family = "glibc", version = "2.12" }
+            archspec = "x86_64_v3"
+            "#,
+        );
+
+        let union = default.union(&gpu).unwrap();
+        assert_eq!(union.linux, Some(Version::from_str("4.18").unwrap()));
+        assert_eq!(union.cuda, Some(Version::from_str("12.0").unwrap()));
+        assert_eq!(union.archspec.as_deref(), Some("x86_64_v3"));
+        let (family, version) = union.libc.as_ref().unwrap().family_and_version();
+        assert_eq!(
+            (family, version.to_string()),
+            ("glibc", String::from("2.17"))
+        );
+
+        let musl = requirements(r#"libc = { family = "musl", version = "1.2" }"#);
+        assert_eq!(
+            default.union(&musl).unwrap_err(),
+            "the libc families 'glibc' and 'musl' can't be combined"
+        );
+    }
 }
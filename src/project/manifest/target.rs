@@ -1,4 +1,4 @@
-use crate::project::manifest::activation::Activation;
+use crate::project::manifest::activation::{Activation, Deactivation};
 use crate::utils::spanned::PixiSpanned;
 use crate::{
     project::{manifest::error::SpecIsMissing, manifest::PyPiRequirement, SpecType},
@@ -26,6 +26,9 @@ pub struct Target {
     /// Additional information to activate an environment.
     pub activation: Option<Activation>,
 
+    /// The scripts that run when the environment is deactivated.
+    pub deactivation: Option<Deactivation>,
+
     /// Target specific tasks to run in the environment
     pub tasks: HashMap<String, Task>,
 }
@@ -137,6 +140,10 @@ impl<'de> Deserialize<'de> for Target {
             #[serde(default)]
             activation: Option<Activation>,
 
+            /// The scripts that run when the environment is deactivated.
+            #[serde(default)]
+            deactivation: Option<Deactivation>,
+
             /// Target specific tasks to run in the environment
             #[serde(default)]
             tasks: HashMap<String, Task>,
@@ -156,6 +163,7 @@ impl<'de> Deserialize<'de> for Target {
             dependencies,
             pypi_dependencies: target.pypi_dependencies,
             activation: target.activation,
+            deactivation: target.deactivation,
             tasks: target.tasks,
         })
     }
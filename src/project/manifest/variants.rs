@@ -0,0 +1,335 @@
+use crate::utils::spanned::PixiSpanned;
+use indexmap::IndexMap;
+use itertools::Itertools;
+use miette::{LabeledSpan, NamedSource, Report};
+use serde::de::{Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
+use std::collections::HashSet;
+
+/// The variants of the dependencies of the project, e.g. `cuda12` and `cpu`. Every variant is
+/// locked and installed as a separate environment of the project.
+///
+/// A dependency selects the variants it is built for with the `variant` key, e.g.
+/// `pytorch = { version = "2.*", variant = "cuda12|cpu" }`. In the environment of a variant the
+/// build string of the dependency has to contain the name of the variant.
+#[derive(Debug, Clone, Default)]
+pub struct Variants {
+    /// The names of the variants, the first one is the default.
+    names: Vec<String>,
+
+    /// The normalized names of the packages that have a variant selector.
+    packages: HashSet<String>,
+}
+
+impl Variants {
+    /// Returns the names of the variants, the first one is the default.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Returns the variant that is used when none is selected.
+    pub fn default_variant(&self) -> Option<&str> {
+        self.names.first().map(String::as_str)
+    }
+
+    /// Returns true if the package with the given normalized name has a variant selector.
+    pub fn has_selector(&self, package: &str) -> bool {
+        self.packages.contains(package)
+    }
+
+    /// Adds the variant selectors of another project, e.g. of the same workspace. Both projects
+    /// have to select the same variants in the same order, unless one of them has none.
+    pub fn merge(&mut self, other: &Variants) -> miette::Result<()> {
+        if other.names.is_empty() {
+            return Ok(());
+        }
+        if self.names.is_empty() {
+            self.names = other.names.clone();
+        } else if self.names != other.names {
+            miette::bail!(
+                help = "all dependencies with a `variant` must select the same variants, e.g. `variant = \"cuda12|cpu\"`",
+                "expected the variants '{}', found '{}'",
+                self.names.iter().join("|"),
+                other.names.iter().join("|")
+            );
+        }
+        self.packages.extend(other.packages.iter().cloned());
+        Ok(())
+    }
+
+    /// Reads the variant selectors of the dependencies from the manifest. Returns `None` if the
+    /// contents are not valid TOML, the parser of the manifest reports that error.
+    pub fn from_toml_str(contents: &str) -> Option<Result<Self, Report>> {
+        let manifest: RawManifest = toml_edit::de::from_str(contents).ok()?;
+        let mut selectors = Vec::new();
+        for target in manifest.targets() {
+            for (name, dependency) in target.dependencies() {
+                if let Some(variant) = &dependency.variant {
+                    selectors.push((name.to_lowercase(), dependency.has_build, variant));
+                }
+            }
+        }
+
+        Some(
+            Self::from_selectors(&selectors).map_err(|(span, message)| {
+                miette::miette!(
+                    labels = vec![LabeledSpan::at(span, message)],
+                    help = "all dependencies with a `variant` must select the same variants, e.g. `variant = \"cuda12|cpu\"`",
+                    "invalid dependency variant"
+                )
+                .with_source_code(NamedSource::new(
+                    crate::consts::PROJECT_MANIFEST,
+                    contents.to_owned(),
+                ))
+            }),
+        )
+    }
+
+    fn from_selectors(
+        selectors: &[(String, bool, &PixiSpanned<String>)],
+    ) -> Result<Self, (std::ops::Range<usize>, String)> {
+        let mut variants = Self::default();
+        for (package, has_build, selector) in selectors {
+            let span = selector.span().unwrap_or_default();
+            if *has_build {
+                return Err((
+                    span,
+                    format!("'{package}' selects a variant and a build, only one is allowed"),
+                ));
+            }
+            let names = selector
+                .get_ref()
+                .split('|')
+                .map(str::trim)
+                .map(ToOwned::to_owned)
+                .collect_vec();
+            if let Some(name) = names.iter().find(|name| {
+                name.is_empty()
+                    || !name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            }) {
+                return Err((span, format!("'{name}' is not a valid variant name")));
+            }
+            if names.iter().duplicates().next().is_some() {
+                return Err((span, String::from("the variants are not unique")));
+            }
+
+            if variants.names.is_empty() {
+                variants.names = names;
+            } else if names.iter().sorted().ne(variants.names.iter().sorted()) {
+                return Err((
+                    span,
+                    format!(
+                        "expected the variants '{}'",
+                        variants.names.iter().join("|")
+                    ),
+                ));
+            }
+            variants.packages.insert(package.clone());
+        }
+        Ok(variants)
+    }
+}
+
+/// The tables of the manifest that contain dependencies.
+#[derive(Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct RawTarget {
+    #[serde(default)]
+    dependencies: IndexMap<String, RawDependency>,
+    #[serde(default)]
+    host_dependencies: IndexMap<String, RawDependency>,
+    #[serde(default)]
+    build_dependencies: IndexMap<String, RawDependency>,
+}
+
+impl RawTarget {
+    pub(super) fn dependencies(&self) -> impl Iterator<Item = (&String, &RawDependency)> {
+        self.dependencies
+            .iter()
+            .chain(self.host_dependencies.iter())
+            .chain(self.build_dependencies.iter())
+    }
+}
+
+/// Only the dependency tables of the manifest, all other keys are ignored. A feature has the same
+/// dependency tables as the manifest itself.
+#[derive(Default)]
+pub(super) struct RawManifest {
+    root: RawTarget,
+    target: IndexMap<String, RawTarget>,
+    feature: IndexMap<String, RawManifest>,
+}
+
+impl RawManifest {
+    /// Returns the tables at the root of the manifest and those of the `target` table.
+    pub(super) fn targets(&self) -> impl Iterator<Item = &RawTarget> {
+        std::iter::once(&self.root).chain(self.target.values())
+    }
+
+    /// Returns the targets of the manifest and those of its features.
+    pub(super) fn targets_with_features(&self) -> Vec<&RawTarget> {
+        self.targets()
+            .chain(self.feature.values().flat_map(RawManifest::targets))
+            .collect()
+    }
+
+    /// Returns the targets of the manifest and those of its features, with the name of their
+    /// feature, `None` for the default feature, and the key of their target, `None` for the tables
+    /// that apply to all platforms.
+    pub(super) fn located_targets(&self) -> Vec<(Option<&str>, Option<&str>, &RawTarget)> {
+        let features = std::iter::once((None, self)).chain(
+            self.feature
+                .iter()
+                .map(|(name, feature)| (Some(name.as_str()), feature)),
+        );
+        let mut targets = Vec::new();
+        for (feature, manifest) in features {
+            targets.push((feature, None, &manifest.root));
+            targets.extend(
+                manifest
+                    .target
+                    .iter()
+                    .map(|(selector, target)| (feature, Some(selector.as_str()), target)),
+            );
+        }
+        targets
+    }
+}
+
+impl<'de> Deserialize<'de> for RawManifest {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // `flatten` would lose the locations of the values, so the root target is read by hand.
+        #[derive(serde::Deserialize)]
+        #[serde(rename_all = "kebab-case")]
+        struct Manifest {
+            #[serde(default)]
+            dependencies: IndexMap<String, RawDependency>,
+            #[serde(default)]
+            host_dependencies: IndexMap<String, RawDependency>,
+            #[serde(default)]
+            build_dependencies: IndexMap<String, RawDependency>,
+            #[serde(default)]
+            target: IndexMap<String, RawTarget>,
+            #[serde(default)]
+            feature: IndexMap<String, RawManifest>,
+        }
+
+        let manifest = Manifest::deserialize(deserializer)?;
+        Ok(Self {
+            root: RawTarget {
+                dependencies: manifest.dependencies,
+                host_dependencies: manifest.host_dependencies,
+                build_dependencies: manifest.build_dependencies,
+            },
+            target: manifest.target,
+            feature: manifest.feature,
+        })
+    }
+}
+
+/// The parts of a dependency that are not part of its match spec: its variants and companions.
+#[derive(Default)]
+pub(super) struct RawDependency {
+    pub(super) variant: Option<PixiSpanned<String>>,
+    has_build: bool,
+    pub(super) with: Vec<PixiSpanned<String>>,
+}
+
+impl<'de> Deserialize<'de> for RawDependency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawDependencyVisitor;
+
+        impl<'de> Visitor<'de> for RawDependencyVisitor {
+            type Value = RawDependency;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a version string or a table")
+            }
+
+            fn visit_str<E>(self, _v: &str) -> Result<Self::Value, E> {
+                Ok(RawDependency::default())
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut dependency = RawDependency::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "variant" => dependency.variant = Some(map.next_value()?),
+                        "with" => dependency.with = map.next_value()?,
+                        "build" => {
+                            dependency.has_build = true;
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(dependency)
+            }
+        }
+
+        deserializer.deserialize_any(RawDependencyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_variants() {
+        let contents = r#"
+        [project]
+        name = "foo"
+        channels = []
+        platforms = []
+
+        [dependencies]
+        python = "3.11.*"
+        pytorch = { version = "2.*", variant = "cuda12|cpu" }
+
+        [target.linux-64.dependencies]
+        torchvision = { variant = "cpu | cuda12" }
+        "#;
+        let variants = Variants::from_toml_str(contents).unwrap().unwrap();
+        assert_eq!(variants.names(), ["cuda12", "cpu"]);
+        assert_eq!(variants.default_variant(), Some("cuda12"));
+        assert!(variants.has_selector("pytorch"));
+        assert!(variants.has_selector("torchvision"));
+        assert!(!variants.has_selector("python"));
+    }
+
+    #[test]
+    fn test_invalid_variants() {
+        let manifest = |dependencies: &str| {
+            format!("[project]\nname = \"foo\"\n\n[dependencies]\n{dependencies}")
+        };
+        for dependencies in [
+            r#"pytorch = { variant = "cuda12|cpu" }
+               torchvision = { variant = "rocm|cpu" }"#,
+            r#"pytorch = { variant = "cuda12|cpu", build = "*cuda*" }"#,
+            r#"pytorch = { variant = "cuda 12|cpu" }"#,
+            r#"pytorch = { variant = "cpu|cpu" }"#,
+        ] {
+            assert!(Variants::from_toml_str(&manifest(dependencies))
+                .unwrap()
+                .is_err());
+        }
+        assert!(Variants::from_toml_str(&manifest(r#"pytorch = "2.*""#))
+            .unwrap()
+            .unwrap()
+            .names()
+            .is_empty());
+    }
+}
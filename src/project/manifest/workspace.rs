@@ -0,0 +1,11 @@
+use serde::Deserialize;
+
+/// The `[workspace]` table of a manifest, which makes the project the root of a workspace of
+/// member projects, e.g. the libraries and applications of a monorepo.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct WorkspaceMetadata {
+    /// The directories of the members relative to the root, where `*` and `?` match any name in a
+    /// directory, e.g. `libs/*`.
+    pub members: Vec<String>,
+}
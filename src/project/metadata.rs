@@ -1,5 +1,6 @@
-use crate::Project;
+use crate::{emulation, Project};
 use itertools::Itertools;
+use rattler_conda_types::Platform;
 use std::collections::HashMap;
 
 // Setting a base prefix for the pixi package
@@ -8,7 +9,7 @@ const ENV_PREFIX: &str = "PIXI_PACKAGE_";
 impl Project {
     /// Returns environment variables and their values that should be injected when running a command.
     pub fn get_metadata_env(&self) -> HashMap<String, String> {
-        HashMap::from_iter([
+        let mut env = HashMap::from_iter([
             (
                 format!("{ENV_PREFIX}ROOT"),
                 self.root().to_string_lossy().into_owned(),
@@ -31,6 +32,21 @@ impl Project {
                     }),
             ),
             ("PIXI_PROMPT".to_string(), format!("({}) ", self.name())),
-        ])
+        ]);
+        if let Some(environment) = self.environment_name() {
+            env.insert(format!("{ENV_PREFIX}ENVIRONMENT"), environment.to_string());
+            env.insert(
+                "PIXI_PROMPT".to_string(),
+                format!("({}:{environment}) ", self.name()),
+            );
+        }
+        if self.is_emulated() {
+            env.insert(format!("{ENV_PREFIX}PLATFORM"), self.platform().to_string());
+            env.extend(emulation::emulation_env(
+                Platform::current(),
+                self.platform(),
+            ));
+        }
+        env
     }
 }
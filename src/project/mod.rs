@@ -1,11 +1,14 @@
 pub mod manifest;
 pub mod metadata;
+mod workspace;
 
 use indexmap::IndexMap;
 use itertools::Itertools;
 use miette::{IntoDiagnostic, NamedSource, WrapErr};
 use once_cell::sync::OnceCell;
-use rattler_conda_types::{Channel, MatchSpec, NamelessMatchSpec, PackageName, Platform, Version};
+use rattler_conda_types::{
+    Channel, MatchSpec, NamelessMatchSpec, PackageName, Platform, StringMatcher, Version,
+};
 use rattler_virtual_packages::VirtualPackage;
 use rip::{index::PackageDb, normalize_index_url};
 use std::collections::HashMap;
@@ -18,15 +21,28 @@ use std::{
 };
 
 use crate::{
+    auth,
+    banned::BannedDependency,
+    build_fallback::BuildFallback,
+    config::Config,
     consts::{self, PROJECT_MANIFEST},
-    default_client,
-    task::Task,
+    emulation,
+    end_of_life::EndOfLife,
+    install::PostLinkScriptPolicy,
+    lock_file::LockFileFormat,
+    overlay::Overlay,
+    shebang::ShebangStyle,
+    task::{self, Task},
     virtual_packages::non_relevant_virtual_packages_for_platform,
+    warnings::{self, WarningCode},
+};
+use manifest::{
+    Environment, Feature, Manifest, Pipeline, PyPiRequirement, SystemRequirements, Target,
 };
-use manifest::{Manifest, PyPiRequirement, SystemRequirements};
 use rip::types::NormalizedPackageName;
 use std::fmt::{Display, Formatter};
 use url::Url;
+pub use workspace::Workspace;
 
 /// The dependency types we support
 #[derive(Debug, Copy, Clone)]
@@ -76,6 +92,18 @@ pub struct Project {
     package_db: OnceCell<Arc<PackageDb>>,
     /// The manifest for the project
     pub(crate) manifest: Manifest,
+    /// The user configuration that applies to this project
+    config: Config,
+    /// The selected environment, `None` if the dependencies of the project have no variants
+    environment: Option<String>,
+    /// The platform the environment is installed for, either the platform of this machine or one
+    /// that runs through emulation
+    platform: Platform,
+    /// Whether the tasks only get the environment variables of the host that the project passes
+    /// through
+    clean_env: bool,
+    /// The workspace the project is the root or a member of
+    workspace: Option<Arc<Workspace>>,
 }
 
 impl Project {
@@ -84,7 +112,12 @@ impl Project {
         Self {
             root: Default::default(),
             package_db: Default::default(),
+            environment: manifest.variants.default_variant().map(ToOwned::to_owned),
+            platform: Platform::current(),
             manifest,
+            config: Default::default(),
+            clean_env: false,
+            workspace: None,
         }
     }
 
@@ -110,12 +143,15 @@ impl Project {
         // Determine the parent directory of the manifest file
         let full_path = dunce::canonicalize(manifest_path).into_diagnostic()?;
         if full_path.file_name().and_then(OsStr::to_str) != Some(PROJECT_MANIFEST) {
-            miette::bail!("the manifest-path must point to a {PROJECT_MANIFEST} file");
+            miette::bail!(
+                "the manifest-path must point to a {PROJECT_MANIFEST} file or a directory containing it"
+            );
         }
 
         let root = full_path
             .parent()
             .ok_or_else(|| miette::miette!("can not find parent of {}", manifest_path.display()))?;
+        tracing::info!("using the manifest at {}", full_path.display());
 
         // Load the TOML document
         let manifest = fs::read_to_string(manifest_path)
@@ -129,22 +165,263 @@ impl Project {
                 )
             });
 
+        // A member of a workspace inherits from the root and shares its lock file and environment.
+        let mut manifest = manifest?;
+        let workspace = Workspace::discover(root, &manifest)?;
+        if let Some(workspace) = &workspace {
+            workspace.inherit(&mut manifest)?;
+        }
+        if manifest.parsed.project.platforms.as_ref().is_empty() {
+            miette::bail!(
+                help = "add the platforms the project supports to the [project] table, e.g. `platforms = [\"linux-64\"]`",
+                "the project '{}' has no platforms",
+                manifest.parsed.project.name
+            );
+        }
+        // An empty list of channels is allowed, leaving them out only in a workspace member.
+        if manifest.parsed.project.channels.is_empty()
+            && manifest
+                .document
+                .get("project")
+                .and_then(|project| project.get("channels"))
+                .is_none()
+        {
+            miette::bail!(
+                help = "add the channels of the project to the [project] table, e.g. `channels = [\"conda-forge\"]`",
+                "the project '{}' has no channels",
+                manifest.parsed.project.name
+            );
+        }
+
+        let mut project = Self::from_manifest_in_root(
+            manifest,
+            root,
+            workspace.as_ref().map_or(root, |workspace| &workspace.root),
+        )?;
+        project.workspace = workspace.map(Arc::new);
+        Ok(project)
+    }
+
+    /// Constructs a project in the `root` directory from the contents of a manifest, without reading
+    /// the manifest from disk. The manifest is written to the root when the project is saved, the
+    /// lock-file and the environments are stored in the root as well.
+    pub fn from_str(root: &Path, contents: impl Into<String>) -> miette::Result<Self> {
+        let manifest = Manifest::from_str(root, contents)?;
+        Self::from_manifest_in_root(manifest, root, root)
+    }
+
+    /// Constructs a project in the `root` directory with the configuration that applies to it,
+    /// which is read from the root of its workspace.
+    fn from_manifest_in_root(
+        mut manifest: Manifest,
+        root: &Path,
+        workspace_root: &Path,
+    ) -> miette::Result<Self> {
+        let config = Config::load(workspace_root)?;
+        manifest.apply_channel_config(&config.channel_config())?;
         Ok(Self {
             root: root.to_owned(),
             package_db: Default::default(),
-            manifest: manifest?,
+            environment: manifest.variants.default_variant().map(ToOwned::to_owned),
+            platform: emulation::select_platform(
+                Platform::current(),
+                manifest.parsed.project.platforms.get_ref(),
+                config.platform_preference(),
+            ),
+            manifest,
+            config,
+            clean_env: false,
+            workspace: None,
         })
     }
 
     /// Loads a project manifest file or discovers it in the current directory or any of the parent
+    /// directories.
+    ///
+    /// The `manifest_path` can point to either the manifest file or a directory containing it. If
+    /// no path is given the `PIXI_PROJECT_MANIFEST` environment variable is used before falling
+    /// back to discovery.
     pub fn load_or_else_discover(manifest_path: Option<&Path>) -> miette::Result<Self> {
-        let project = match manifest_path {
-            Some(path) => Project::load(path)?,
+        let env_manifest_path = std::env::var_os(consts::PROJECT_MANIFEST_ENV)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from);
+        let project = match manifest_path.or(env_manifest_path.as_deref()) {
+            Some(path) => Project::load(&resolve_manifest_path(path))?,
             None => Project::discover()?,
         };
         Ok(project)
     }
 
+    /// Selects the environment with the given name, the default environment is used if `name` is
+    /// `None`. Every variant of the dependencies of the project and every environment of the
+    /// `[environments]` table is an environment.
+    pub fn with_environment(mut self, name: Option<&str>) -> miette::Result<Self> {
+        let Some(name) = name else {
+            return Ok(self);
+        };
+        if !self
+            .environments()
+            .iter()
+            .any(|environment| environment == name)
+        {
+            let help = if self.environments().is_empty() {
+                String::from("environments are defined by the `variant` of dependencies, e.g. `pytorch = { version = \"2.*\", variant = \"cuda12|cpu\" }`, or in the [environments] table")
+            } else {
+                format!(
+                    "the environments of the project are {}",
+                    self.environments()
+                        .iter()
+                        .map(|name| format!("'{name}'"))
+                        .join(", ")
+                )
+            };
+            miette::bail!(
+                help = help,
+                "the project has no environment called '{name}'"
+            );
+        }
+        // The default environment of a project without variants has no name.
+        self.environment = (name != "default"
+            || self.manifest.variants.default_variant().is_some())
+        .then(|| name.to_string());
+        Ok(self)
+    }
+
+    /// Selects the platform the environment is installed for, the platform that is determined from
+    /// the configuration is used if `platform` is `None`. The platform must either be the platform
+    /// of this machine or one that it runs through emulation, e.g. `osx-64` on Apple Silicon.
+    pub fn with_platform(mut self, platform: Option<Platform>) -> miette::Result<Self> {
+        if let Some(platform) = platform {
+            emulation::verify_platform(Platform::current(), platform)?;
+            self.platform = platform;
+        }
+        Ok(self)
+    }
+
+    /// Selects whether the tasks run in a clean environment, where they only get the environment
+    /// variables of the host that are listed in the `passthrough-env` of the project.
+    pub fn with_clean_env(mut self, clean_env: bool) -> Self {
+        self.clean_env = clean_env;
+        self
+    }
+
+    /// Returns true if the tasks run in a clean environment.
+    pub fn clean_env(&self) -> bool {
+        self.clean_env
+    }
+
+    /// Returns the environment variables of the host that reach the tasks, all of them unless the
+    /// tasks run in a clean environment.
+    pub fn host_env(&self) -> Vec<(String, String)> {
+        task::host_variables(
+            self.clean_env,
+            &self.manifest.parsed.project.passthrough_env,
+        )
+    }
+
+    /// Returns the platform the environment is installed for.
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    /// Returns true if the environment is installed for a platform that runs through emulation.
+    pub fn is_emulated(&self) -> bool {
+        self.platform != Platform::current()
+    }
+
+    /// Returns the names of the environments of the project, one for every variant of the
+    /// dependencies or `default` and the environments of the `[environments]` table. The first one
+    /// is the default environment. Empty if the project only has the default environment.
+    pub fn environments(&self) -> Vec<String> {
+        self.manifest.environment_names()
+    }
+
+    /// Returns the name of the selected environment, `None` if the project has no variants.
+    pub fn environment_name(&self) -> Option<&str> {
+        self.environment.as_deref()
+    }
+
+    /// Returns the environment of the `[environments]` table with the given name, `default` is the
+    /// default environment.
+    pub fn environment(&self, name: &str) -> Option<&Environment> {
+        self.manifest.parsed.environment(name)
+    }
+
+    /// Returns the feature with the given name, `default` is the default feature.
+    pub fn feature(&self, name: &str) -> Option<&Feature> {
+        self.manifest.parsed.feature(name)
+    }
+
+    /// Returns the other environments of the solve group of the selected environment. The
+    /// environments of a solve group are solved together, so the dependencies they share are locked
+    /// at the same versions.
+    pub fn solve_group_members(&self) -> miette::Result<Vec<Project>> {
+        let name = self.environment_name().unwrap_or("default");
+        let Some(group) = self
+            .environment(name)
+            .and_then(|environment| environment.solve_group.as_deref())
+        else {
+            return Ok(Vec::new());
+        };
+        self.manifest
+            .parsed
+            .environments
+            .values()
+            .filter(|environment| environment.solve_group.as_deref() == Some(group))
+            .filter_map(|environment| environment.name.name())
+            .filter(|member| *member != name)
+            .map(|member| self.clone().with_environment(Some(member)))
+            .collect()
+    }
+
+    /// Returns the features that make up the selected environment, the default feature first. The
+    /// environments of dependency variants only consist of the default feature.
+    fn features(&self) -> Vec<&Feature> {
+        match self
+            .manifest
+            .parsed
+            .environment(self.environment_name().unwrap_or("default"))
+        {
+            Some(environment) => self.manifest.parsed.environment_features(environment),
+            None => vec![self.manifest.default_feature()],
+        }
+    }
+
+    /// Returns the features whose tasks and activation scripts the selected environment uses: those
+    /// of the environment, after the default feature of the workspace root for a member of a
+    /// workspace.
+    fn task_features(&self) -> Vec<&Feature> {
+        let root_feature = self
+            .workspace
+            .as_deref()
+            .filter(|workspace| workspace.root != self.root)
+            .map(|workspace| workspace.manifest.default_feature());
+        root_feature.into_iter().chain(self.features()).collect()
+    }
+
+    /// Returns the features whose dependencies the selected environment uses. All projects of a
+    /// workspace share one lock file, so the default features of the root and of all other members
+    /// are combined with the features of the environment, which come last.
+    fn dependency_features(&self) -> Vec<&Feature> {
+        let Some(workspace) = self.workspace.as_deref() else {
+            return self.features();
+        };
+        std::iter::once(&workspace.manifest)
+            .chain(workspace.members.iter())
+            .filter(|manifest| manifest.path != self.manifest.path)
+            .map(|manifest| manifest.default_feature())
+            .chain(self.features())
+            .collect()
+    }
+
+    /// Returns the name of the selected environment if it is not the default environment. The
+    /// default environment uses the regular lock file and prefix.
+    fn non_default_environment(&self) -> Option<&str> {
+        self.environment
+            .as_deref()
+            .filter(|name| Some(*name) != self.manifest.variants.default_variant())
+    }
+
     /// Returns the name of the project
     pub fn name(&self) -> &str {
         &self.manifest.parsed.project.name
@@ -160,19 +437,84 @@ impl Project {
         &self.manifest.parsed.project.description
     }
 
+    /// Returns true if shortcuts should be created for packages that define them
+    pub fn shortcuts_enabled(&self) -> bool {
+        self.manifest.parsed.project.shortcuts
+    }
+
+    /// Returns the policy that determines whether post-link scripts are executed
+    pub fn post_link_script_policy(&self) -> PostLinkScriptPolicy {
+        self.manifest.parsed.project.post_link_scripts
+    }
+
+    /// Returns true if `.pyc` files should be compiled for noarch python packages
+    pub fn compile_pyc_enabled(&self) -> bool {
+        self.manifest.parsed.project.compile_pyc.unwrap_or(true)
+    }
+
+    /// Returns how the shebangs of the scripts in the environment are written
+    pub fn shebang_style(&self) -> ShebangStyle {
+        self.manifest.parsed.project.shebang
+    }
+
+    /// Returns true if the activation scripts of the installed packages are run on activation
+    pub fn package_activation_scripts_enabled(&self) -> bool {
+        self.manifest
+            .parsed
+            .project
+            .package_activation_scripts
+            .unwrap_or(true)
+    }
+
+    /// Returns the name of the task that is executed by `pixi start`
+    pub fn default_task(&self) -> Option<&str> {
+        self.manifest
+            .parsed
+            .project
+            .default_task
+            .as_ref()
+            .map(|task| task.get_ref().as_str())
+    }
+
+    /// Returns the pipelines of the project that are executed by `pixi run pipeline`
+    pub fn pipelines(&self) -> &IndexMap<String, Pipeline> {
+        &self.manifest.parsed.pipelines
+    }
+
+    /// Returns the user configuration that applies to this project
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     /// Returns the root directory of the project
     pub fn root(&self) -> &Path {
         &self.root
     }
 
-    /// Returns the pixi directory
+    /// Returns the root directory of the workspace of the project, which holds the lock file and
+    /// the environments. This is the root of the project if it is not part of a workspace.
+    pub fn workspace_root(&self) -> &Path {
+        self.workspace
+            .as_deref()
+            .map_or(&self.root, |workspace| &workspace.root)
+    }
+
+    /// Returns the workspace the project is the root or a member of.
+    pub fn workspace(&self) -> Option<&Workspace> {
+        self.workspace.as_deref()
+    }
+
+    /// Returns the pixi directory, the one of the workspace root for a member of a workspace
     pub fn pixi_dir(&self) -> PathBuf {
-        self.root.join(consts::PIXI_DIR)
+        self.workspace_root().join(consts::PIXI_DIR)
     }
 
     /// Returns the environment directory
     pub fn environment_dir(&self) -> PathBuf {
-        self.pixi_dir().join(consts::ENVIRONMENT_DIR)
+        match self.non_default_environment() {
+            Some(name) => self.pixi_dir().join(consts::ENVIRONMENTS_DIR).join(name),
+            None => self.pixi_dir().join(consts::ENVIRONMENT_DIR),
+        }
     }
 
     /// Returns the path to the manifest file.
@@ -180,9 +522,57 @@ impl Project {
         self.manifest.path.clone()
     }
 
-    /// Returns the path to the lock file of the project
+    /// Returns the files that the manifest includes, as pairs of their path relative to the root of
+    /// the project and their full path. Fails if a file is outside of the project, it can't be
+    /// copied along with the manifest.
+    pub fn included_files(&self) -> miette::Result<Vec<(PathBuf, PathBuf)>> {
+        self.manifest
+            .included_files
+            .iter()
+            .map(|path| {
+                let relative = path.strip_prefix(&self.root).ok().filter(|relative| {
+                    relative
+                        .components()
+                        .all(|component| matches!(component, std::path::Component::Normal(_)))
+                });
+                match relative {
+                    Some(relative) => Ok((relative.to_path_buf(), path.clone())),
+                    None => Err(miette::miette!(
+                        help = "move the file into the directory of the project",
+                        "the included file '{}' is outside of the project",
+                        path.display()
+                    )),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the path to the lock file of the project. Every environment except the default one
+    /// is locked in a separate lock file, `pixi.<environment>.lock`. With `split-lock-files` every
+    /// environment is locked in `.pixi/locks/<environment>.lock` instead.
     pub fn lock_file_path(&self) -> PathBuf {
-        self.root.join(consts::PROJECT_LOCK_FILE)
+        if self.manifest.parsed.project.split_lock_files {
+            let name = self.environment_name().unwrap_or("default");
+            return self
+                .pixi_dir()
+                .join(consts::LOCKS_DIR)
+                .join(format!("{name}.lock"));
+        }
+        self.root_lock_file_path()
+    }
+
+    /// Returns the format the lock files of the project are written in.
+    pub fn lock_file_format(&self) -> LockFileFormat {
+        self.manifest.parsed.project.lock_file_format
+    }
+
+    /// Returns the path to the lock file of the environment in the root of the project, or of its
+    /// workspace, which is where the environment is locked without `split-lock-files`.
+    pub fn root_lock_file_path(&self) -> PathBuf {
+        match self.non_default_environment() {
+            Some(name) => self.workspace_root().join(format!("pixi.{name}.lock")),
+            None => self.workspace_root().join(consts::PROJECT_LOCK_FILE),
+        }
     }
 
     /// Save back changes
@@ -190,9 +580,12 @@ impl Project {
         self.manifest.save()
     }
 
-    /// Returns the channels used by this project
+    /// Returns the channels used by the selected environment of this project, which are the
+    /// channels of the project unless the features of the environment add channels.
     pub fn channels(&self) -> &[Channel] {
-        &self.manifest.parsed.project.channels
+        self.manifest
+            .environment_channels(self.environment_name().unwrap_or("default"))
+            .unwrap_or(&self.manifest.parsed.project.channels)
     }
 
     /// Returns the platforms this project targets
@@ -200,26 +593,39 @@ impl Project {
         self.manifest.parsed.project.platforms.as_ref().as_slice()
     }
 
-    /// Get the tasks of this project
+    /// Get the tasks of the selected environment of this project, the tasks of a feature take
+    /// precedence over those of the features before it.
     pub fn tasks(&self, platform: Option<Platform>) -> HashMap<&str, &Task> {
-        self.manifest.tasks(platform)
+        self.task_features()
+            .into_iter()
+            .flat_map(|feature| {
+                feature
+                    .targets
+                    .resolve(platform)
+                    .collect_vec()
+                    .into_iter()
+                    .rev()
+            })
+            .flat_map(|target| target.tasks.iter())
+            .map(|(name, task)| (name.as_str(), task))
+            .collect()
     }
 
     /// Get the task with the specified `name` or `None` if no such task exists. If `platform` is
     /// specified then the task will first be looked up in the target specific tasks for the given
     /// platform.
     pub fn task_opt(&self, name: &str, platform: Option<Platform>) -> Option<&Task> {
-        self.manifest.tasks(platform).get(name).copied()
+        self.tasks(platform).get(name).copied()
     }
 
     /// Returns all tasks defined in the project for the given platform
     pub fn task_names(&self, platform: Option<Platform>) -> Vec<&str> {
-        self.manifest.tasks(platform).keys().copied().collect_vec()
+        self.tasks(platform).keys().copied().collect_vec()
     }
 
     /// Returns names of the tasks that depend on the given task.
     pub fn task_names_depending_on(&self, name: impl AsRef<str>) -> Vec<&str> {
-        let mut tasks = self.manifest.tasks(Some(Platform::current()));
+        let mut tasks = self.tasks(Some(self.platform()));
         let task = tasks.remove(name.as_ref());
         if task.is_some() {
             tasks
@@ -232,22 +638,74 @@ impl Project {
         }
     }
 
-    /// Returns the dependencies of the project.
+    /// Returns the dependencies of the selected environment of the project, which combines the
+    /// dependencies of its features.
     pub fn dependencies(
         &self,
         platform: Platform,
         kind: SpecType,
     ) -> IndexMap<PackageName, NamelessMatchSpec> {
-        self.manifest
-            .default_feature()
-            .targets
-            .resolve(Some(platform))
-            .collect_vec()
+        self.features_dependencies(&self.dependency_features(), platform, kind)
+    }
+
+    /// Returns the union of the dependencies of the given features, e.g. the default feature and a
+    /// `test` feature. The dependencies of a feature take precedence over those of the features
+    /// before it.
+    pub fn features_dependencies(
+        &self,
+        features: &[&Feature],
+        platform: Platform,
+        kind: SpecType,
+    ) -> IndexMap<PackageName, NamelessMatchSpec> {
+        let mut dependencies: IndexMap<_, _> = feature_targets(features, platform)
             .into_iter()
-            .rev() // We rev this so that the most specific target is last.
             .flat_map(|t| t.dependencies.get(&kind).into_iter().flatten())
-            .map(|(name, spec)| (name.clone(), spec.clone()))
-            .collect()
+            .map(|(name, spec)| (name.clone(), self.apply_variant(name, spec.clone())))
+            .collect();
+
+        // The companions of the dependencies are added unless they are dependencies themselves,
+        // only those of the selected features and of the targets that apply to the platform.
+        let companions = features
+            .iter()
+            .flat_map(|feature| {
+                feature
+                    .targets
+                    .iter()
+                    .filter(|(_, selector)| {
+                        selector.map_or(true, |selector| selector.matches(platform))
+                    })
+                    .flat_map(move |(target, selector)| {
+                        target
+                            .dependencies
+                            .get(&kind)
+                            .into_iter()
+                            .flatten()
+                            .flat_map(move |(name, _)| {
+                                self.manifest.companions.of(
+                                    &feature.name,
+                                    selector,
+                                    name.as_normalized(),
+                                )
+                            })
+                    })
+            })
+            .cloned()
+            .collect_vec();
+        for (name, spec) in companions {
+            dependencies.entry(name).or_insert(spec);
+        }
+        dependencies
+    }
+
+    /// Restricts the build of a dependency with a variant selector to builds of the variant of the
+    /// selected environment, e.g. `*cuda12*`.
+    fn apply_variant(&self, name: &PackageName, mut spec: NamelessMatchSpec) -> NamelessMatchSpec {
+        if let Some(environment) = &self.environment {
+            if self.manifest.variants.has_selector(name.as_normalized()) {
+                spec.build = StringMatcher::from_str(&format!("*{environment}*")).ok();
+            }
+        }
+        spec
     }
 
     /// Returns all dependencies of the project. These are the run, host, build dependency sets combined.
@@ -262,27 +720,58 @@ impl Project {
         &self,
         platform: Platform,
     ) -> IndexMap<rip::types::PackageName, PyPiRequirement> {
-        self.manifest
-            .default_feature()
-            .targets
-            .resolve(Some(platform))
-            .collect_vec()
+        feature_targets(&self.dependency_features(), platform)
             .into_iter()
-            .rev() // We rev this so that the most specific target is last.
             .flat_map(|t| t.pypi_dependencies.iter().flatten())
             .map(|(name, spec)| (name.clone(), spec.clone()))
             .collect()
     }
 
+    /// Returns the PyPI packages that are installed with a conda package instead, as specified in
+    /// the `[pypi-to-conda]` table of the manifest.
+    pub fn pypi_to_conda(&self) -> &IndexMap<rip::types::PackageName, PackageName> {
+        &self.manifest.parsed.pypi_to_conda
+    }
+
+    /// Returns the package versions that the manifest marks as end-of-life.
+    pub fn end_of_life(&self) -> &IndexMap<PackageName, EndOfLife> {
+        &self.manifest.parsed.end_of_life
+    }
+
+    /// Returns the packages that the project must not depend on, as specified in the
+    /// `[banned-dependencies]` table of the manifest.
+    pub fn banned_dependencies(&self) -> &IndexMap<PackageName, BannedDependency> {
+        &self.manifest.parsed.banned_dependencies
+    }
+
+    /// Returns how the dependencies that the channels don't provide are built from source, `None`
+    /// if the manifest has no `[build-fallback]` table.
+    pub fn build_fallback(&self) -> Option<&BuildFallback> {
+        self.manifest.parsed.build_fallback.as_ref()
+    }
+
+    /// Returns the local files that are copied over installed packages, as specified in the
+    /// `[overlays]` table of the manifest.
+    pub fn overlays(&self) -> &IndexMap<PackageName, Overlay> {
+        &self.manifest.parsed.overlays
+    }
+
     /// Returns true if the project contains any pypi dependencies
     pub fn has_pypi_dependencies(&self) -> bool {
         self.manifest.has_pypi_dependencies()
+            || self.workspace.as_deref().map_or(false, |workspace| {
+                std::iter::once(&workspace.manifest)
+                    .chain(workspace.members.iter())
+                    .any(Manifest::has_pypi_dependencies)
+            })
     }
 
-    /// Returns the Python index URLs to use for this project.
+    /// Returns the Python index URLs to use for this project. The urls don't contain credentials,
+    /// they are sent by the client of the package database so they don't end up in the lock file.
     pub fn pypi_index_urls(&self) -> Vec<Url> {
-        let index_url = normalize_index_url(Url::parse("https://pypi.org/simple/").unwrap());
-        vec![index_url]
+        vec![normalize_index_url(
+            Url::parse("https://pypi.org/simple/").unwrap(),
+        )]
     }
 
     /// Returns the package database used for caching python metadata, wheels and more. See the
@@ -291,9 +780,10 @@ impl Project {
         Ok(self
             .package_db
             .get_or_try_init(|| {
+                let index_urls = self.pypi_index_urls();
                 PackageDb::new(
-                    default_client(),
-                    &self.pypi_index_urls(),
+                    auth::client_for_url(&index_urls[0]),
+                    &index_urls,
                     &rattler::default_cache_dir()
                         .map_err(|_| {
                             miette::miette!("could not determine default cache directory")
@@ -308,45 +798,97 @@ impl Project {
 
     /// Returns the all specified activation scripts that are used in the current platform.
     pub fn activation_scripts(&self, platform: Platform) -> miette::Result<Vec<PathBuf>> {
-        let feature = self.manifest.default_feature();
-
-        // Select the platform-specific activation scripts that is most specific
-        let activation = feature
-            .targets
-            .resolve(Some(platform))
-            .filter_map(|target| target.activation.as_ref())
-            .next();
+        // Select the most specific platform-specific activation scripts of every feature
+        let scripts = self
+            .task_features()
+            .into_iter()
+            .filter_map(|feature| {
+                feature
+                    .targets
+                    .resolve(Some(platform))
+                    .filter_map(|target| target.activation.as_ref())
+                    .next()
+                    .and_then(|activation| activation.scripts.as_ref())
+                    .map(|scripts| (self.feature_root(feature), scripts))
+            })
+            .flat_map(|(root, scripts)| scripts.iter().map(move |script| (root, script)))
+            .collect_vec();
+        Ok(self.existing_scripts(&scripts, "activation"))
+    }
 
-        // Get the activation scripts
-        let all_scripts = activation
+    /// Returns the deactivation scripts that are used in the current platform, they run when
+    /// `pixi shell` exits.
+    pub fn deactivation_scripts(&self, platform: Platform) -> miette::Result<Vec<PathBuf>> {
+        let scripts = self
+            .task_features()
             .into_iter()
-            .flat_map(|activation| activation.scripts.iter().flatten())
+            .filter_map(|feature| {
+                feature
+                    .targets
+                    .resolve(Some(platform))
+                    .filter_map(|target| target.deactivation.as_ref())
+                    .next()
+                    .and_then(|deactivation| deactivation.scripts.as_ref())
+                    .map(|scripts| (self.feature_root(feature), scripts))
+            })
+            .flat_map(|(root, scripts)| scripts.iter().map(move |script| (root, script)))
             .collect_vec();
+        Ok(self.existing_scripts(&scripts, "deactivation"))
+    }
 
-        // Check if scripts exist
+    /// Returns the directory the paths of a feature are relative to, the root of the workspace for
+    /// the default feature of the workspace root and the project root otherwise.
+    fn feature_root(&self, feature: &Feature) -> &Path {
+        match self.workspace.as_deref() {
+            Some(workspace) if std::ptr::eq(feature, workspace.manifest.default_feature()) => {
+                &workspace.root
+            }
+            _ => &self.root,
+        }
+    }
+
+    /// Returns the full paths of the scripts relative to the directory they are defined in, warns
+    /// about the scripts that don't exist.
+    fn existing_scripts(&self, scripts: &[(&Path, &String)], kind: &str) -> Vec<PathBuf> {
         let mut full_paths = Vec::new();
         let mut missing_scripts = Vec::new();
-        for script_name in &all_scripts {
-            let script_path = self.root().join(script_name);
+        for (root, script_name) in scripts.iter().copied() {
+            let script_path = root.join(script_name);
             if script_path.exists() {
                 full_paths.push(script_path);
-                tracing::debug!("Found activation script: {:?}", script_name);
+                tracing::debug!("Found {kind} script: {:?}", script_name);
             } else {
                 missing_scripts.push(script_name);
             }
         }
 
         if !missing_scripts.is_empty() {
-            tracing::warn!("can't find activation scripts: {:?}", missing_scripts);
+            warnings::emit(
+                WarningCode::MissingActivationScript,
+                format!("can't find {kind} scripts: {:?}", missing_scripts),
+            );
         }
 
-        Ok(full_paths)
+        full_paths
     }
 
-    /// Get the system requirements defined under the `system-requirements` section of the project manifest.
-    /// They will act as the description of a reference machine which is minimally needed for this package to be run.
-    pub fn system_requirements(&self) -> &SystemRequirements {
-        &self.manifest.default_feature().system_requirements
+    /// Get the system requirements defined under the `system-requirements` section of the project
+    /// manifest, combined with those of the features of the selected environment. They will act as
+    /// the description of a reference machine which is minimally needed for this package to be run.
+    pub fn system_requirements(&self) -> SystemRequirements {
+        match self
+            .manifest
+            .parsed
+            .environment(self.environment_name().unwrap_or("default"))
+        {
+            // Features with conflicting requirements are reported when the manifest is loaded.
+            Some(environment) => self
+                .manifest
+                .parsed
+                .environment_system_requirements(environment)
+                .unwrap_or_default(),
+            None => self.manifest.default_feature().system_requirements.clone(),
+        }
     }
 
     /// Get the system requirements defined under the `system-requirements` section of the project manifest.
@@ -364,13 +906,69 @@ impl Project {
     }
 }
 
-/// Iterates over the current directory and all its parent directories and returns the first
-/// directory path that contains the [`consts::PROJECT_MANIFEST`].
+/// Returns the targets of the features that apply to the platform. A target comes after the
+/// targets it overrides: the targets of a feature come after those of the features before it, and
+/// the most specific target of a feature is last.
+fn feature_targets<'a>(features: &[&'a Feature], platform: Platform) -> Vec<&'a Target> {
+    features
+        .iter()
+        .copied()
+        .flat_map(|feature| {
+            feature
+                .targets
+                .resolve(Some(platform))
+                .collect_vec()
+                .into_iter()
+                .rev()
+        })
+        .collect()
+}
+
+/// Resolves the path of the manifest, a directory is resolved to the manifest file inside it.
+fn resolve_manifest_path(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join(PROJECT_MANIFEST)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Iterates over the current directory and its parent directories and returns the first directory
+/// path that contains the [`consts::PROJECT_MANIFEST`]. Only the current directory is searched if
+/// the `PIXI_NO_PARENT_DISCOVERY` environment variable is set to `1` or `true`.
 pub fn find_project_root() -> Option<PathBuf> {
     let current_dir = env::current_dir().ok()?;
-    std::iter::successors(Some(current_dir.as_path()), |prev| prev.parent())
-        .find(|dir| dir.join(consts::PROJECT_MANIFEST).is_file())
-        .map(Path::to_path_buf)
+    let search_parents = !env::var(consts::NO_PARENT_DISCOVERY_ENV)
+        .is_ok_and(|value| matches!(value.as_str(), "1" | "true"));
+    find_project_root_from(&current_dir, search_parents)
+}
+
+/// Returns the first directory of `dir` and its parents that contains the manifest. The search
+/// stops at a directory with a [`consts::PROJECT_ROOT_MARKER`] or at the root of a git repository,
+/// so the manifest of an unrelated project higher up in the filesystem is never picked up.
+fn find_project_root_from(dir: &Path, search_parents: bool) -> Option<PathBuf> {
+    for dir in dir.ancestors() {
+        if dir.join(consts::PROJECT_MANIFEST).is_file() {
+            return Some(dir.to_path_buf());
+        }
+        if !search_parents {
+            tracing::debug!(
+                "{} is set, not looking for {} in the parent directories",
+                consts::NO_PARENT_DISCOVERY_ENV,
+                consts::PROJECT_MANIFEST
+            );
+            return None;
+        }
+        if dir.join(consts::PROJECT_ROOT_MARKER).exists() || dir.join(".git").exists() {
+            tracing::debug!(
+                "stopped looking for {} at the project boundary {}",
+                consts::PROJECT_MANIFEST,
+                dir.display()
+            );
+            return None;
+        }
+    }
+    None
 }
 
 #[cfg(test)]
@@ -380,6 +978,53 @@ mod tests {
     use rattler_virtual_packages::{LibC, VirtualPackage};
     use std::str::FromStr;
 
+    #[test]
+    fn test_find_project_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::write(root.join(PROJECT_MANIFEST), "").unwrap();
+        let nested = root.join("repo/src/module");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(
+            find_project_root_from(&nested, true),
+            Some(root.to_path_buf())
+        );
+        assert_eq!(find_project_root_from(&nested, false), None);
+        assert_eq!(
+            find_project_root_from(root, false),
+            Some(root.to_path_buf())
+        );
+
+        // The search stops at the marker and at the root of a git repository.
+        std::fs::create_dir(root.join("repo/.git")).unwrap();
+        assert_eq!(find_project_root_from(&nested, true), None);
+        std::fs::remove_dir(root.join("repo/.git")).unwrap();
+        std::fs::write(root.join("repo/src").join(consts::PROJECT_ROOT_MARKER), "").unwrap();
+        assert_eq!(find_project_root_from(&nested, true), None);
+    }
+
+    #[test]
+    fn test_resolve_manifest_path() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            resolve_manifest_path(dir.path()),
+            dir.path().join(PROJECT_MANIFEST)
+        );
+        let manifest = dir.path().join(PROJECT_MANIFEST);
+        assert_eq!(resolve_manifest_path(&manifest), manifest);
+    }
+
+    #[test]
+    fn test_from_str() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = Project::from_str(dir.path(), PROJECT_BOILERPLATE).unwrap();
+        assert_eq!(project.name(), "foo");
+        assert_eq!(project.root(), dir.path());
+        assert_eq!(project.manifest_path(), dir.path().join(PROJECT_MANIFEST));
+        assert!(!project.manifest_path().exists());
+    }
+
     const PROJECT_BOILERPLATE: &str = r#"
         [project]
         name = "foo"
@@ -457,6 +1102,522 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_dependency_variants() {
+        let file_contents = r#"
+        [dependencies]
+        python = "3.11.*"
+        pytorch = { version = "2.*", variant = "cuda12|cpu" }
+        "#;
+        let manifest = Manifest::from_str(
+            Path::new(""),
+            format!("{PROJECT_BOILERPLATE}\n{file_contents}").as_str(),
+        )
+        .unwrap();
+        let project = Project::from_manifest(manifest);
+        assert_eq!(project.environments(), ["cuda12", "cpu"]);
+        assert_eq!(project.environment_name(), Some("cuda12"));
+        assert_eq!(
+            project.lock_file_path(),
+            Path::new(consts::PROJECT_LOCK_FILE)
+        );
+
+        let build = |project: &Project, name: &str| {
+            project.dependencies(Platform::Linux64, SpecType::Run)
+                [&PackageName::new_unchecked(name)]
+                .build
+                .as_ref()
+                .map(ToString::to_string)
+        };
+        assert_eq!(build(&project, "pytorch").as_deref(), Some("*cuda12*"));
+        assert_eq!(build(&project, "python"), None);
+
+        let project = project.with_environment(Some("cpu")).unwrap();
+        assert_eq!(build(&project, "pytorch").as_deref(), Some("*cpu*"));
+        assert_eq!(project.lock_file_path(), Path::new("pixi.cpu.lock"));
+        assert_eq!(
+            project.environment_dir(),
+            Path::new(consts::PIXI_DIR).join("envs").join("cpu")
+        );
+        assert!(project.with_environment(Some("rocm")).is_err());
+    }
+
+    #[test]
+    fn test_feature_channels() {
+        let file_contents = r#"
+        [project]
+        name = "foo"
+        channels = ["conda-forge"]
+        platforms = ["linux-64"]
+
+        [feature.nightly]
+        channels = ["conda-forge/label/dev", { channel = "pytorch-nightly", priority = 10 }]
+
+        [feature.bio]
+        channels = ["bioconda"]
+
+        [environments]
+        nightly = ["nightly"]
+        bio-nightly = { features = ["bio", "nightly"] }
+        "#;
+        let manifest = Manifest::from_str(Path::new(""), file_contents).unwrap();
+        let project = Project::from_manifest(manifest);
+        let channel_names =
+            |project: &Project| project.channels().iter().map(Channel::name).collect_vec();
+
+        assert_eq!(
+            project.environments(),
+            ["default", "nightly", "bio-nightly"]
+        );
+        assert_eq!(project.environment_name(), None);
+        assert_eq!(channel_names(&project), ["conda-forge"]);
+
+        let nightly = project.clone().with_environment(Some("nightly")).unwrap();
+        assert_eq!(
+            channel_names(&nightly),
+            ["pytorch-nightly", "conda-forge/label/dev", "conda-forge"]
+        );
+        assert_eq!(nightly.lock_file_path(), Path::new("pixi.nightly.lock"));
+
+        let bio_nightly = project
+            .clone()
+            .with_environment(Some("bio-nightly"))
+            .unwrap();
+        assert_eq!(
+            channel_names(&bio_nightly),
+            [
+                "pytorch-nightly",
+                "bioconda",
+                "conda-forge/label/dev",
+                "conda-forge"
+            ]
+        );
+
+        let default = project.with_environment(Some("default")).unwrap();
+        assert_eq!(default.environment_name(), None);
+        assert_eq!(
+            default.lock_file_path(),
+            Path::new(consts::PROJECT_LOCK_FILE)
+        );
+    }
+
+    #[test]
+    fn test_feature_dependencies_and_tasks() {
+        let file_contents = r#"
+        [project]
+        name = "foo"
+        channels = ["conda-forge"]
+        platforms = ["linux-64", "win-64"]
+
+        [dependencies]
+        python = "3.11.*"
+
+        [tasks]
+        start = "python main.py"
+
+        [feature.test.dependencies]
+        pytest = "*"
+        python = "3.12.*"
+
+        [feature.test.target.win-64.dependencies]
+        pywin32 = "*"
+
+        [feature.test.tasks]
+        test = "pytest"
+
+        [feature.lint.pypi-dependencies]
+        ruff = "*"
+
+        [feature.lint.tasks]
+        start = "ruff check ."
+
+        [environments]
+        test = ["test"]
+        lint = { features = ["lint"] }
+        "#;
+        let manifest = Manifest::from_str(Path::new(""), file_contents).unwrap();
+        let project = Project::from_manifest(manifest);
+        let dependency_names = |project: &Project, platform| {
+            project
+                .all_dependencies(platform)
+                .keys()
+                .map(PackageName::as_source)
+                .map(ToOwned::to_owned)
+                .collect_vec()
+        };
+
+        assert_eq!(
+            project.environment("test").unwrap().features.get_ref(),
+            &["test"]
+        );
+        assert!(project.environment("docs").is_none());
+        assert_eq!(dependency_names(&project, Platform::Win64), ["python"]);
+        assert_eq!(project.task_names(None), ["start"]);
+
+        let test = project.clone().with_environment(Some("test")).unwrap();
+        assert_eq!(
+            dependency_names(&test, Platform::Linux64),
+            ["python", "pytest"]
+        );
+        assert_eq!(
+            dependency_names(&test, Platform::Win64),
+            ["python", "pytest", "pywin32"]
+        );
+        // The dependencies of a feature take precedence over those of the default feature.
+        assert_eq!(
+            test.all_dependencies(Platform::Linux64)[&PackageName::from_str("python").unwrap()]
+                .version
+                .as_ref()
+                .map(ToString::to_string)
+                .as_deref(),
+            Some("3.12.*")
+        );
+        assert_eq!(
+            test.task_names(None).into_iter().sorted().collect_vec(),
+            ["start", "test"]
+        );
+        assert_eq!(test.environment_dir(), Path::new(".pixi/envs/test"));
+
+        let lint = project.with_environment(Some("lint")).unwrap();
+        let pypi_dependencies = lint.pypi_dependencies(Platform::Linux64);
+        assert_eq!(pypi_dependencies.len(), 1);
+        assert!(pypi_dependencies.contains_key(&rip::types::PackageName::from_str("ruff").unwrap()));
+        assert_eq!(
+            lint.task_opt("start", None)
+                .unwrap()
+                .as_single_command()
+                .as_deref(),
+            Some("ruff check .")
+        );
+    }
+
+    #[test]
+    fn test_feature_groups() {
+        let file_contents = r#"
+        [project]
+        name = "foo"
+        channels = ["conda-forge"]
+        platforms = ["linux-64"]
+
+        [dependencies]
+        python = "3.11.*"
+
+        [system-requirements]
+        cuda = "11.8"
+
+        [feature.test.dependencies]
+        pytest = "*"
+
+        [feature.docs.dependencies]
+        mkdocs = "*"
+
+        [feature.gpu.system-requirements]
+        cuda = "12.0"
+        libc = "2.28"
+
+        [environments]
+        gpu = ["gpu"]
+        "#;
+        let manifest = Manifest::from_str(Path::new(""), file_contents).unwrap();
+        let project = Project::from_manifest(manifest);
+
+        let features = ["default", "test", "docs"]
+            .into_iter()
+            .map(|name| project.feature(name).unwrap())
+            .collect_vec();
+        assert_eq!(
+            project
+                .features_dependencies(&features, Platform::Linux64, SpecType::Run)
+                .keys()
+                .map(PackageName::as_source)
+                .collect_vec(),
+            ["python", "pytest", "mkdocs"]
+        );
+        assert!(project.feature("lint").is_none());
+
+        assert_eq!(
+            project.system_requirements().cuda,
+            Some(Version::from_str("11.8").unwrap())
+        );
+        let gpu = project.with_environment(Some("gpu")).unwrap();
+        let requirements = gpu.system_requirements();
+        assert_eq!(requirements.cuda, Some(Version::from_str("12.0").unwrap()));
+        assert!(requirements.libc.is_some());
+
+        let conflicting = r#"
+        [project]
+        name = "foo"
+        channels = ["conda-forge"]
+        platforms = ["linux-64"]
+
+        [system-requirements]
+        libc = "2.17"
+
+        [feature.alpine.system-requirements]
+        libc = { family = "musl", version = "1.2" }
+
+        [environments]
+        alpine = ["alpine"]
+        "#;
+        let err = Manifest::from_str(Path::new(""), conflicting).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("invalid system requirements of the environment 'alpine'"));
+    }
+
+    #[test]
+    fn test_dependency_companions() {
+        let file_contents = r#"
+        [project]
+        name = "foo"
+        channels = ["conda-forge"]
+        platforms = ["linux-64", "win-64"]
+
+        [dependencies]
+        ffmpeg = { version = "6.*", with = ["x264", "svt-av1 >=1.8"] }
+        svt-av1 = "2.*"
+        gdal = "3.*"
+
+        [target.linux-64.dependencies]
+        gdal = { version = "3.*", with = ["libgdal-arrow-parquet"] }
+
+        [feature.gpu.dependencies]
+        ffmpeg = { version = "6.*", with = ["nv-codec-headers"] }
+
+        [environments]
+        gpu = ["gpu"]
+        "#;
+        let manifest = Manifest::from_str(Path::new(""), file_contents).unwrap();
+        let project = Project::from_manifest(manifest);
+        let names = |project: &Project, platform: Platform| {
+            project
+                .dependencies(platform, SpecType::Run)
+                .keys()
+                .map(|name| name.as_source().to_owned())
+                .sorted()
+                .collect_vec()
+        };
+
+        // The companions of a target or a feature only apply when it is selected.
+        assert_eq!(
+            names(&project, Platform::Win64),
+            ["ffmpeg", "gdal", "svt-av1", "x264"]
+        );
+        assert_eq!(
+            names(
+                &project.clone().with_environment(Some("gpu")).unwrap(),
+                Platform::Linux64
+            ),
+            [
+                "ffmpeg",
+                "gdal",
+                "libgdal-arrow-parquet",
+                "nv-codec-headers",
+                "svt-av1",
+                "x264"
+            ]
+        );
+        let dependencies = project.dependencies(Platform::Linux64, SpecType::Run);
+        assert_eq!(
+            dependencies
+                .iter()
+                .map(|(name, spec)| format!(
+                    "{} {}",
+                    name.as_source(),
+                    spec.version
+                        .as_ref()
+                        .map_or(String::from("*"), ToString::to_string)
+                ))
+                .collect_vec(),
+            [
+                "ffmpeg 6.*",
+                "svt-av1 2.*",
+                "gdal 3.*",
+                "x264 *",
+                "libgdal-arrow-parquet *"
+            ]
+        );
+        assert!(project
+            .dependencies(Platform::Linux64, SpecType::Host)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_workspace_member() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dunce::canonicalize(dir.path()).unwrap();
+        let write = |path: &str, contents: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write(
+            "pixi.toml",
+            r#"
+            [project]
+            name = "monorepo"
+            channels = ["conda-forge"]
+            platforms = ["linux-64", "osx-arm64", "win-64"]
+
+            [workspace]
+            members = ["libs/*"]
+
+            [dependencies]
+            python = "3.11.*"
+
+            [tasks]
+            lint = "ruff check ."
+            test = "pytest"
+            "#,
+        );
+        write(
+            "libs/core/pixi.toml",
+            r#"
+            [project]
+            name = "core"
+
+            [dependencies]
+            numpy = "1.26.*"
+
+            [tasks]
+            test = "pytest tests"
+            "#,
+        );
+        write(
+            "libs/web/pixi.toml",
+            "[project]\nname = \"web\"\n\n[dependencies]\nflask = \"*\"\n",
+        );
+
+        let project = Project::load_or_else_discover(Some(&root.join("libs/core"))).unwrap();
+        assert_eq!(project.root(), root.join("libs/core"));
+        assert_eq!(project.workspace_root(), root);
+        assert_eq!(project.platforms().len(), 3);
+        assert_eq!(project.channels().len(), 1);
+        assert_eq!(
+            project.lock_file_path(),
+            root.join(consts::PROJECT_LOCK_FILE)
+        );
+        assert_eq!(project.environment_dir(), root.join(".pixi").join("env"));
+
+        // The tasks of the root are combined with those of the member, which take precedence.
+        let tasks = project.tasks(None);
+        assert_eq!(
+            tasks.keys().copied().sorted().collect_vec(),
+            ["lint", "test"]
+        );
+        assert_eq!(
+            tasks["test"].as_single_command().as_deref(),
+            Some("pytest tests")
+        );
+
+        // The dependencies of all projects of the workspace are locked together.
+        let dependencies = project.dependencies(Platform::Linux64, SpecType::Run);
+        assert_eq!(
+            dependencies
+                .keys()
+                .map(|name| name.as_source())
+                .sorted()
+                .collect_vec(),
+            ["flask", "numpy", "python"]
+        );
+        let root_project = Project::load_or_else_discover(Some(&root)).unwrap();
+        assert_eq!(
+            root_project
+                .dependencies(Platform::Linux64, SpecType::Run)
+                .len(),
+            3
+        );
+        assert_eq!(
+            root_project.tasks(None)["test"]
+                .as_single_command()
+                .as_deref(),
+            Some("pytest")
+        );
+
+        // Only a member of a workspace can leave out the channels.
+        let other = tempfile::tempdir().unwrap();
+        std::fs::write(
+            other.path().join(consts::PROJECT_MANIFEST),
+            "[project]\nname = \"other\"\nplatforms = [\"linux-64\"]\n",
+        )
+        .unwrap();
+        let err = Project::load_or_else_discover(Some(other.path())).unwrap_err();
+        assert!(err.to_string().contains("has no channels"));
+    }
+
+    #[test]
+    fn test_solve_group_members() {
+        let file_contents = r#"
+        [project]
+        name = "foo"
+        channels = ["conda-forge"]
+        platforms = ["linux-64"]
+
+        [feature.test.dependencies]
+        pytest = "*"
+
+        [feature.docs.dependencies]
+        mkdocs = "*"
+
+        [environments]
+        prod = { features = [], solve-group = "prod" }
+        test = { features = ["test"], solve-group = "prod" }
+        docs = ["docs"]
+        "#;
+        let manifest = Manifest::from_str(Path::new(""), file_contents).unwrap();
+        let project = Project::from_manifest(manifest);
+        let member_names = |name: &str| {
+            project
+                .clone()
+                .with_environment(Some(name))
+                .unwrap()
+                .solve_group_members()
+                .unwrap()
+                .iter()
+                .map(|member| member.environment_name().unwrap().to_owned())
+                .collect_vec()
+        };
+
+        assert_eq!(member_names("prod"), ["test"]);
+        assert_eq!(member_names("test"), ["prod"]);
+        assert!(member_names("docs").is_empty());
+        assert!(member_names("default").is_empty());
+
+        let different_channels =
+            format!("{file_contents}\n[feature.docs]\nchannels = [\"bioconda\"]\n").replace(
+                "docs = [\"docs\"]",
+                "docs = { features = [\"docs\"], solve-group = \"prod\" }",
+            );
+        let err = Manifest::from_str(Path::new(""), different_channels).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("of the solve group 'prod' use different channels"));
+    }
+
+    #[test]
+    fn test_split_lock_files() {
+        let file_contents = r#"
+        [dependencies]
+        pytorch = { version = "2.*", variant = "cuda12|cpu" }
+        "#;
+        let manifest = Manifest::from_str(
+            Path::new(""),
+            format!("{PROJECT_BOILERPLATE}\nsplit-lock-files = true\n{file_contents}").as_str(),
+        )
+        .unwrap();
+        let project = Project::from_manifest(manifest);
+        let locks_dir = Path::new(consts::PIXI_DIR).join(consts::LOCKS_DIR);
+        assert_eq!(project.lock_file_path(), locks_dir.join("cuda12.lock"));
+        assert_eq!(
+            project.root_lock_file_path(),
+            Path::new(consts::PROJECT_LOCK_FILE)
+        );
+
+        let project = project.with_environment(Some("cpu")).unwrap();
+        assert_eq!(project.lock_file_path(), locks_dir.join("cpu.lock"));
+        assert_eq!(project.root_lock_file_path(), Path::new("pixi.cpu.lock"));
+    }
+
     #[test]
     fn test_dependency_target_sets() {
         let file_contents = r#"
@@ -0,0 +1,396 @@
+//! Workspaces combine the projects of a monorepo. The root of a workspace is a project with a
+//! `[workspace]` table that lists its members, e.g. `members = ["libs/*", "apps/*"]`. All projects
+//! of a workspace share the lock file and the environment in the root, which are solved for the
+//! dependencies of all of them, and the members inherit the channels and platforms of the root.
+
+use super::manifest::Manifest;
+use crate::consts::{self, PROJECT_MANIFEST};
+use crate::utils::glob::glob_matches;
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// The workspace a project belongs to.
+#[derive(Debug)]
+pub struct Workspace {
+    /// The directory of the manifest with the `[workspace]` table.
+    pub root: PathBuf,
+    /// The manifest of the root of the workspace.
+    pub manifest: Manifest,
+    /// The manifests of the members, in the order of the `members` patterns.
+    pub members: Vec<Manifest>,
+}
+
+impl Workspace {
+    /// Finds the workspace of the project in `project_root`: the project itself if its manifest has
+    /// a `[workspace]` table, otherwise the closest parent directory with a workspace. Like the
+    /// discovery of the project, the search stops at the root of a git repository. Returns `None`
+    /// if the project is not part of a workspace.
+    pub fn discover(project_root: &Path, manifest: &Manifest) -> miette::Result<Option<Self>> {
+        if manifest.parsed.workspace.is_some() {
+            return Self::load(project_root, manifest.clone()).map(Some);
+        }
+        if project_root.join(".git").exists()
+            || project_root.join(consts::PROJECT_ROOT_MARKER).exists()
+        {
+            return Ok(None);
+        }
+
+        for dir in project_root.ancestors().skip(1) {
+            let manifest_path = dir.join(PROJECT_MANIFEST);
+            if manifest_path.is_file() && declares_workspace(&manifest_path) {
+                let root_manifest = Manifest::from_path(&manifest_path).wrap_err_with(|| {
+                    format!("failed to parse the workspace manifest {}", dir.display())
+                })?;
+                let members = &root_manifest
+                    .parsed
+                    .workspace
+                    .as_ref()
+                    .expect("the manifest declares a workspace")
+                    .members;
+                let relative_path = project_root
+                    .strip_prefix(dir)
+                    .expect("the directory is an ancestor of the project");
+                if !members
+                    .iter()
+                    .any(|pattern| member_matches(pattern, relative_path))
+                {
+                    // Workspaces don't nest, the project is not part of any workspace.
+                    return Ok(None);
+                }
+                return Self::load(dir, root_manifest).map(Some);
+            }
+            if dir.join(".git").exists() || dir.join(consts::PROJECT_ROOT_MARKER).exists() {
+                break;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Loads the members of the workspace in `root`.
+    fn load(root: &Path, manifest: Manifest) -> miette::Result<Self> {
+        let patterns = manifest
+            .parsed
+            .workspace
+            .as_ref()
+            .map(|workspace| workspace.members.as_slice())
+            .unwrap_or_default();
+        let members = find_members(root, patterns)
+            .into_iter()
+            .map(|dir| {
+                Manifest::from_path(dir.join(PROJECT_MANIFEST)).wrap_err_with(|| {
+                    format!("failed to parse the workspace member {}", dir.display())
+                })
+            })
+            .collect::<miette::Result<Vec<_>>>()?;
+        check_environment_names(std::iter::once(&manifest).chain(&members))?;
+        Ok(Self {
+            root: root.to_path_buf(),
+            manifest,
+            members,
+        })
+    }
+
+    /// Copies the channels and platforms of the root of the workspace into the manifest of a
+    /// member that doesn't specify them. A member that specifies them must use the same as the
+    /// root, otherwise the shared lock file would change depending on where pixi runs. For the same
+    /// reason the variants of the dependencies of all projects of the workspace are combined, as
+    /// are the companions of their default features.
+    pub fn inherit(&self, member: &mut Manifest) -> miette::Result<()> {
+        let root = &self.manifest.parsed.project;
+        let project = &mut member.parsed.project;
+        if project.channels.is_empty() {
+            project.channels = root.channels.clone();
+        } else if project.channels != root.channels {
+            miette::bail!(
+                help = "remove the channels from the member to inherit those of the workspace",
+                "the channels of the workspace member '{}' differ from those of the workspace root",
+                project.name
+            );
+        }
+        if project.platforms.as_ref().is_empty() {
+            project.platforms.value = root.platforms.as_ref().clone();
+        } else if project.platforms.as_ref() != root.platforms.as_ref() {
+            miette::bail!(
+                help = "remove the platforms from the member to inherit those of the workspace",
+                "the platforms of the workspace member '{}' differ from those of the workspace root",
+                project.name
+            );
+        }
+
+        for other in std::iter::once(&self.manifest)
+            .chain(&self.members)
+            .filter(|other| other.path != member.path)
+        {
+            member.variants.merge(&other.variants).wrap_err_with(|| {
+                format!(
+                    "the variants of {} differ from those of {}",
+                    other.path.display(),
+                    member.path.display()
+                )
+            })?;
+            member.companions.extend_default(&other.companions);
+        }
+        if !member.variants.names().is_empty() && member.parsed.environments.len() > 1 {
+            miette::bail!(
+                help = "use either the `variant` of dependencies or the [environments] table to define the environments",
+                "the environments of the [environments] table of '{}' can't be combined with the dependency variants of the workspace",
+                member.parsed.project.name
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Checks that no two projects of a workspace define an environment with the same name. The
+/// environments of all projects are stored in the root of the workspace, so they would overwrite
+/// each other's lock file and prefix.
+fn check_environment_names<'a>(
+    manifests: impl IntoIterator<Item = &'a Manifest>,
+) -> miette::Result<()> {
+    let mut defined_by: HashMap<&str, &Path> = HashMap::new();
+    for manifest in manifests {
+        let names = manifest
+            .parsed
+            .environments
+            .keys()
+            .filter_map(|name| name.name())
+            .filter(|name| *name != "default");
+        for name in names {
+            if let Some(other) = defined_by.insert(name, &manifest.path) {
+                miette::bail!(
+                    help = "the environments of a workspace share its lock files and prefixes, rename one of them",
+                    "the environment '{name}' is defined by both {} and {}",
+                    other.display(),
+                    manifest.path.display()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns true if the manifest has a `[workspace]` table, without parsing the whole manifest.
+fn declares_workspace(manifest_path: &Path) -> bool {
+    std::fs::read_to_string(manifest_path)
+        .into_diagnostic()
+        .and_then(|contents| contents.parse::<toml_edit::Document>().into_diagnostic())
+        .map(|document| document.contains_key("workspace"))
+        .unwrap_or(false)
+}
+
+/// Returns true if the path of a directory relative to the root of the workspace matches a
+/// `members` pattern, every component of the path has to match a component of the pattern.
+fn member_matches(pattern: &str, relative_path: &Path) -> bool {
+    let components = relative_path
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect_vec();
+    let pattern = pattern
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect_vec();
+    !components.is_empty()
+        && pattern.len() == components.len()
+        && pattern
+            .iter()
+            .zip(components)
+            .all(|(pattern, name)| glob_matches(pattern, name))
+}
+
+/// Returns the directories with a manifest in `root` that match one of the `patterns`, sorted and
+/// without duplicates.
+fn find_members(root: &Path, patterns: &[String]) -> Vec<PathBuf> {
+    let mut members = Vec::new();
+    for pattern in patterns {
+        let mut dirs = vec![root.to_path_buf()];
+        for part in pattern
+            .split('/')
+            .filter(|part| !part.is_empty() && *part != ".")
+        {
+            dirs = dirs
+                .into_iter()
+                .flat_map(|dir| std::fs::read_dir(dir).into_iter().flatten().flatten())
+                .filter(|entry| entry.file_type().map_or(false, |kind| kind.is_dir()))
+                .filter(|entry| {
+                    entry
+                        .file_name()
+                        .to_str()
+                        .map_or(false, |name| glob_matches(part, name))
+                })
+                .map(|entry| entry.path())
+                .sorted()
+                .collect();
+        }
+        members.extend(
+            dirs.into_iter()
+                .filter(|dir| dir != root && dir.join(PROJECT_MANIFEST).is_file()),
+        );
+    }
+    members.into_iter().unique().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::manifest::FeatureName;
+
+    #[test]
+    fn test_member_matches() {
+        assert!(member_matches("libs/*", Path::new("libs/core")));
+        assert!(member_matches("./apps/web", Path::new("apps/web")));
+        assert!(member_matches("apps/web-?", Path::new("apps/web-1")));
+        assert!(!member_matches("libs/*", Path::new("libs/core/tests")));
+        assert!(!member_matches("libs/*", Path::new("apps/web")));
+        assert!(!member_matches("*", Path::new("")));
+    }
+
+    #[test]
+    fn test_discover() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let write = |path: &str, contents: &str| {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        write(
+            "pixi.toml",
+            r#"
+            [project]
+            name = "monorepo"
+            channels = ["conda-forge"]
+            platforms = ["linux-64", "osx-arm64"]
+
+            [workspace]
+            members = ["libs/*", "apps/web"]
+
+            [dependencies]
+            python = "3.11.*"
+            "#,
+        );
+        let member = "[project]\nname = \"member\"\n";
+        write("libs/core/pixi.toml", member);
+        write("libs/utils/pixi.toml", member);
+        write("libs/docs/README.md", "");
+        write("apps/web/pixi.toml", member);
+        write("apps/cli/pixi.toml", member);
+
+        let manifest_of = |path: &str| Manifest::from_path(root.join(path)).unwrap();
+        let workspace =
+            Workspace::discover(&root.join("libs/core"), &manifest_of("libs/core/pixi.toml"))
+                .unwrap()
+                .unwrap();
+        assert_eq!(workspace.root, root);
+        assert_eq!(
+            workspace
+                .members
+                .iter()
+                .map(|member| member.path.strip_prefix(root).unwrap().to_path_buf())
+                .collect_vec(),
+            [
+                Path::new("libs/core/pixi.toml"),
+                Path::new("libs/utils/pixi.toml"),
+                Path::new("apps/web/pixi.toml")
+            ]
+        );
+
+        // The root discovers itself, a project that is not a member has no workspace.
+        assert!(Workspace::discover(root, &manifest_of("pixi.toml"))
+            .unwrap()
+            .is_some());
+        assert!(
+            Workspace::discover(&root.join("apps/cli"), &manifest_of("apps/cli/pixi.toml"))
+                .unwrap()
+                .is_none()
+        );
+
+        // Members inherit the channels and platforms, but can't use others.
+        let mut member = manifest_of("libs/core/pixi.toml");
+        workspace.inherit(&mut member).unwrap();
+        assert_eq!(member.parsed.project.platforms.as_ref().len(), 2);
+        assert_eq!(member.parsed.project.channels.len(), 1);
+        let mut other = Manifest::from_str(
+            &root.join("apps/cli"),
+            "[project]\nname = \"cli\"\nplatforms = [\"win-64\"]\n",
+        )
+        .unwrap();
+        assert!(workspace.inherit(&mut other).is_err());
+    }
+
+    #[test]
+    fn test_inherit_variants_and_companions() {
+        let root = Path::new("/monorepo");
+        let manifest =
+            |path: &str, contents: &str| Manifest::from_str(&root.join(path), contents).unwrap();
+        let workspace = Workspace {
+            root: root.to_path_buf(),
+            manifest: manifest(
+                "",
+                r#"
+                [project]
+                name = "monorepo"
+                channels = ["conda-forge"]
+                platforms = ["linux-64"]
+
+                [workspace]
+                members = ["libs/*"]
+
+                [dependencies]
+                pytorch = { version = "2.*", variant = "cuda12|cpu" }
+                "#,
+            ),
+            members: vec![manifest(
+                "libs/video",
+                "[project]\nname = \"video\"\n\n[dependencies]\nffmpeg = { version = \"6.*\", with = [\"x264\"] }\n",
+            )],
+        };
+
+        // The root gets the companions of the members and the members the variants of the root.
+        let mut root_manifest = workspace.manifest.clone();
+        workspace.inherit(&mut root_manifest).unwrap();
+        assert_eq!(
+            root_manifest
+                .companions
+                .of(&FeatureName::Default, None, "ffmpeg")
+                .len(),
+            1
+        );
+        let mut member = workspace.members[0].clone();
+        workspace.inherit(&mut member).unwrap();
+        assert_eq!(member.variants.names(), ["cuda12", "cpu"]);
+        assert!(member.variants.has_selector("pytorch"));
+
+        // Projects that select other variants can't be combined.
+        let mut other = manifest(
+            "libs/gpu",
+            "[project]\nname = \"gpu\"\n\n[dependencies]\njax = { variant = \"cpu|cuda12\" }\n",
+        );
+        assert!(workspace.inherit(&mut other).is_err());
+    }
+
+    #[test]
+    fn test_check_environment_names() {
+        let manifest = |path: &str, environments: &str| {
+            Manifest::from_str(
+                Path::new(path),
+                format!(
+                    "[project]\nname = \"foo\"\nchannels = []\nplatforms = [\"linux-64\"]\n\n[feature.test.dependencies]\npytest = \"*\"\n\n[environments]\n{environments}"
+                ),
+            )
+            .unwrap()
+        };
+        let root = manifest("/monorepo", "lint = [\"test\"]\n");
+        let core = manifest("/monorepo/libs/core", "test = [\"test\"]\n");
+        let web = manifest("/monorepo/libs/web", "test = [\"test\"]\n");
+        assert!(check_environment_names([&root, &core]).is_ok());
+        let err = check_environment_names([&root, &core, &web]).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("the environment 'test' is defined by both"));
+    }
+}
@@ -0,0 +1,256 @@
+//! Running tasks of a project on another machine over SSH.
+//!
+//! The manifest, the files it includes and the lock file of the project are copied to the remote host and pixi on that
+//! host installs the locked environment and runs the task. The sources of the project are not
+//! copied, they are expected to be available on the remote host already, e.g. through a shared
+//! file system or a clone of the repository.
+
+use crate::consts;
+use crate::Project;
+use miette::{Context, IntoDiagnostic};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The exit code of a shell when a command is not found.
+const COMMAND_NOT_FOUND: i32 = 127;
+
+/// Explains how to install pixi on a remote host.
+const INSTALL_PIXI_HELP: &str =
+    "install pixi on the host with `curl -fsSL https://pixi.sh/install.sh | bash`";
+
+/// A host that runs the tasks of a project over SSH.
+#[derive(Debug, Clone)]
+pub struct RemoteHost {
+    /// The destination that is passed to `ssh`, e.g. `user@server` or a host of `~/.ssh/config`.
+    destination: String,
+
+    /// The directory of the project on the remote host, relative paths are relative to the home
+    /// directory of the remote user.
+    project_dir: String,
+}
+
+impl RemoteHost {
+    /// Constructs a remote host for the project. Without a `project_dir` the project is expected at
+    /// the same location relative to the home directory as on this machine.
+    pub fn new(
+        destination: impl Into<String>,
+        project_dir: Option<String>,
+        project: &Project,
+    ) -> miette::Result<Self> {
+        let project_dir = match project_dir {
+            Some(project_dir) => project_dir,
+            None => dirs::home_dir()
+                .and_then(|home| default_project_dir(&home, project.root()))
+                .ok_or_else(|| {
+                    miette::miette!(
+                        help = "specify the directory of the project on the remote host with `--remote-dir`",
+                        "the project is not in the home directory, cannot determine its location on the remote host"
+                    )
+                })?,
+        };
+        Ok(Self {
+            destination: destination.into(),
+            project_dir,
+        })
+    }
+
+    /// Returns the destination that is passed to `ssh`.
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+
+    /// Makes sure pixi is available on the remote host and copies the manifest, the files it
+    /// includes and the lock file of the project to it.
+    pub fn sync(&self, project: &Project) -> miette::Result<()> {
+        let status = self
+            .ssh(
+                &format!(
+                    "{}command -v pixi >/dev/null 2>&1 || exit {COMMAND_NOT_FOUND}; mkdir -p {}",
+                    path_prefix(),
+                    shlex::quote(&self.project_dir)
+                ),
+                false,
+            )
+            .status()
+            .into_diagnostic()
+            .wrap_err("failed to run `ssh`")?;
+        match status.code() {
+            Some(0) => {}
+            Some(COMMAND_NOT_FOUND) => {
+                return Err(miette::miette!(
+                    help = INSTALL_PIXI_HELP,
+                    "pixi is not installed on '{}'",
+                    self.destination
+                ));
+            }
+            _ => miette::bail!("failed to connect to '{}'", self.destination),
+        }
+
+        let lock_file_path = project.lock_file_path();
+        if !lock_file_path.is_file() {
+            miette::bail!("the project has no lock file, run `pixi lock` first");
+        }
+        let mut files = project.included_files()?;
+        for path in [project.manifest_path(), lock_file_path] {
+            // The lock file of a workspace member is in the root of the workspace, on the remote
+            // host the member is locked on its own.
+            let relative = match path.strip_prefix(project.root()) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => PathBuf::from(path.file_name().unwrap_or_default()),
+            };
+            files.push((relative, path));
+        }
+        for (relative, path) in files {
+            self.copy_file(&path, &relative)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the task on the remote host with the locked environment and returns its exit code. The
+    /// output of the task is streamed to this process.
+    pub fn run(
+        &self,
+        task_args: &[String],
+        environment: Option<&str>,
+        pty: bool,
+    ) -> miette::Result<i32> {
+        let status = self
+            .ssh(
+                &run_script(&self.project_dir, task_args, environment, pty),
+                pty,
+            )
+            .stdin(Stdio::inherit())
+            .status()
+            .into_diagnostic()
+            .wrap_err("failed to run `ssh`")?;
+        Ok(status.code().unwrap_or(1))
+    }
+
+    /// Copies a file of the project to `relative`, a path relative to the directory of the project
+    /// on the remote host.
+    fn copy_file(&self, path: &Path, relative: &Path) -> miette::Result<()> {
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let file = File::open(path)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to read '{}'", path.display()))?;
+        let destination = format!("{}/{relative}", self.project_dir);
+        let status = self
+            .ssh(
+                &format!(
+                    "mkdir -p \"$(dirname {destination})\" && cat > {destination}",
+                    destination = shlex::quote(&destination)
+                ),
+                false,
+            )
+            .stdin(file)
+            .status()
+            .into_diagnostic()
+            .wrap_err("failed to run `ssh`")?;
+        if !status.success() {
+            miette::bail!(
+                "failed to copy '{relative}' to '{}:{}'",
+                self.destination,
+                self.project_dir
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns a command that runs the script with the shell of the remote user, `tty` forces the
+    /// allocation of a terminal on the remote host.
+    fn ssh(&self, script: &str, tty: bool) -> Command {
+        let mut command = Command::new("ssh");
+        if tty {
+            command.arg("-t");
+        }
+        command
+            .arg(&self.destination)
+            .arg("--")
+            .arg(script)
+            .stdin(Stdio::null());
+        command
+    }
+}
+
+/// Returns the directory of the project relative to the home directory, if the project is in it.
+fn default_project_dir(home: &Path, project_root: &Path) -> Option<String> {
+    let relative = project_root.strip_prefix(home).ok()?;
+    let components = relative
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    Some(if components.is_empty() {
+        String::from(".")
+    } else {
+        components.join("/")
+    })
+}
+
+/// Non-interactive SSH sessions don't read the profile of the user, so the default install location
+/// of pixi is added to the `PATH`.
+fn path_prefix() -> String {
+    format!(
+        "PATH=\"$HOME/{}/bin:$PATH\"; export PATH; ",
+        consts::PIXI_DIR
+    )
+}
+
+/// Returns the script that runs the task on the remote host. The lock file is copied from this
+/// machine so it is used as is.
+fn run_script(
+    project_dir: &str,
+    task_args: &[String],
+    environment: Option<&str>,
+    pty: bool,
+) -> String {
+    let mut script = format!(
+        "{}cd {} && exec pixi run --frozen",
+        path_prefix(),
+        shlex::quote(project_dir)
+    );
+    if let Some(environment) = environment {
+        script.push_str(&format!(" --environment {}", shlex::quote(environment)));
+    }
+    if pty {
+        script.push_str(" --pty");
+    }
+    if !task_args.is_empty() {
+        script.push_str(" --");
+        for arg in task_args {
+            script.push(' ');
+            script.push_str(&shlex::quote(arg));
+        }
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_project_dir() {
+        let home = Path::new("/home/user");
+        assert_eq!(
+            default_project_dir(home, Path::new("/home/user/projects/foo")).as_deref(),
+            Some("projects/foo")
+        );
+        assert_eq!(default_project_dir(home, home).as_deref(), Some("."));
+        assert_eq!(default_project_dir(home, Path::new("/opt/foo")), None);
+    }
+
+    #[test]
+    fn test_run_script() {
+        let args = vec![String::from("train"), String::from("--epochs 10")];
+        assert_eq!(
+            run_script("projects/my project", &args, Some("cuda12"), false),
+            "PATH=\"$HOME/.pixi/bin:$PATH\"; export PATH; cd 'projects/my project' && exec pixi run \
+             --frozen --environment cuda12 -- train '--epochs 10'"
+        );
+        assert_eq!(
+            run_script("foo", &[], None, true),
+            "PATH=\"$HOME/.pixi/bin:$PATH\"; export PATH; cd foo && exec pixi run --frozen --pty"
+        );
+    }
+}
@@ -1,17 +1,103 @@
-use crate::{default_authenticated_client, progress, project::Project};
+use crate::{
+    auth, build_fallback, concurrency, prerelease, progress, project::Project, token_expiry,
+};
 use futures::{stream, StreamExt, TryStreamExt};
 use indicatif::ProgressBar;
 use miette::{Context, IntoDiagnostic};
+use once_cell::sync::Lazy;
 use rattler_conda_types::{Channel, Platform};
 use rattler_networking::AuthenticatedClient;
 use rattler_repodata_gateway::{fetch, sparse::SparseRepoData};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
 use std::{path::Path, time::Duration};
 
+/// Identifies the repodata of a channel subdirectory that was used by pixi, so differences between
+/// lock files solved on different machines can be traced back to differences in the repodata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepodataFingerprint {
+    /// The url of the subdirectory, e.g. `https://conda.anaconda.org/conda-forge/linux-64/`.
+    pub url: String,
+    /// The identity of the repodata on the server: the blake2 hash of its content, or its `ETag`
+    /// or `Last-Modified` header, e.g. `blake2:0123456789abcdef`. It is the same on every machine
+    /// that fetched the same repodata.
+    pub fingerprint: String,
+}
+
+/// The fingerprints of the repodata that was loaded by this process, by url.
+static REPODATA_FINGERPRINTS: Lazy<Mutex<BTreeMap<String, RepodataFingerprint>>> =
+    Lazy::new(Default::default);
+
+/// Records the fingerprint of the repodata of `url` from the state of its cache, which holds the
+/// hash of the content and the headers the server sent. Nothing is recorded if the server sent
+/// neither.
+fn record_fingerprint(url: &str, cache_state: &fetch::RepoDataState) {
+    let Some(fingerprint) = serde_json::to_value(cache_state)
+        .ok()
+        .and_then(|state| server_identity(&state))
+    else {
+        return;
+    };
+    REPODATA_FINGERPRINTS.lock().unwrap().insert(
+        url.to_string(),
+        RepodataFingerprint {
+            url: url.to_string(),
+            fingerprint,
+        },
+    );
+}
+
+/// Returns the identity of the repodata from the serialized state of its cache, the hash of the
+/// content is preferred over the headers because servers behind a CDN don't agree on them.
+fn server_identity(state: &serde_json::Value) -> Option<String> {
+    let field = |key: &str| {
+        state
+            .get(key)
+            .and_then(|value| value.as_str())
+            .filter(|value| !value.is_empty())
+    };
+    if let Some(hash) = field("blake2_hash") {
+        Some(format!("blake2:{}", &hash[..hash.len().min(16)]))
+    } else if let Some(etag) = field("etag") {
+        Some(format!("etag:{}", etag.trim_matches('"')))
+    } else {
+        field("mod").map(|modified| format!("last-modified:{modified}"))
+    }
+}
+
+/// Returns the fingerprints of the repodata of the given subdirectories that this process loaded.
+pub fn repodata_fingerprints(subdirs: &[Platform]) -> Vec<RepodataFingerprint> {
+    REPODATA_FINGERPRINTS
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|fingerprint| {
+            subdirs.iter().any(|subdir| {
+                fingerprint
+                    .url
+                    .trim_end_matches('/')
+                    .ends_with(subdir.as_str())
+            })
+        })
+        .cloned()
+        .collect()
+}
+
 impl Project {
     pub async fn fetch_sparse_repodata(&self) -> miette::Result<Vec<SparseRepoData>> {
-        let channels = self.channels();
+        let mut channels = self.channels().to_vec();
         let platforms = self.platforms();
-        fetch_sparse_repodata(channels, platforms).await
+
+        // The labels with the pre-release versions of the packages that opt in to them.
+        channels.extend(self.prerelease_channels());
+        let mut sparse_repo_data = fetch_sparse_repodata(&channels, platforms).await?;
+
+        // The packages that were built from source are available in the local channel.
+        if self.build_fallback().is_some() {
+            sparse_repo_data.extend(build_fallback::load_local_repodata(self, platforms)?);
+        }
+        Ok(sparse_repo_data)
     }
 }
 
@@ -23,6 +109,9 @@ pub async fn fetch_sparse_repodata(
         return Ok(vec![]);
     }
 
+    // Warn about expired tokens up front, before the requests fail with `401 Unauthorized`.
+    token_expiry::warn_expiring_tokens(channels.iter().map(|channel| channel.base_url()));
+
     // Determine all the repodata that requires fetching.
     let mut fetch_targets = Vec::with_capacity(channels.len() * target_platforms.len());
     for channel in channels {
@@ -49,7 +138,6 @@ pub async fn fetch_sparse_repodata(
     let repodata_cache_path = rattler::default_cache_dir()
         .map_err(|_| miette::miette!("could not determine default cache directory"))?
         .join("repodata");
-    let repodata_download_client = default_authenticated_client();
     let multi_progress = progress::global_multi_progress();
     let mut progress_bars = Vec::new();
 
@@ -66,17 +154,20 @@ pub async fn fetch_sparse_repodata(
 
             // Spawn a future that downloads the repodata in the background
             let repodata_cache = repodata_cache_path.clone();
-            let download_client = repodata_download_client.clone();
+            let download_client = auth::authenticated_client_for_url(&channel.base_url());
             let top_level_progress = top_level_progress.clone();
 
             async move {
+                // Labels of a channel only contain the platforms they have packages for.
+                let allow_not_found =
+                    platform != Platform::NoArch || prerelease::is_label_channel(&channel);
                 let result = fetch_repo_data_records_with_progress(
                     channel,
                     platform,
                     &repodata_cache,
                     download_client,
                     progress_bar.clone(),
-                    platform != Platform::NoArch,
+                    allow_not_found,
                 )
                 .await;
 
@@ -85,7 +176,7 @@ pub async fn fetch_sparse_repodata(
                 result
             }
         })
-        .buffered(20)
+        .buffered(concurrency::limits().downloads)
         .filter_map(|result| async move { result.transpose() })
         .try_collect::<Vec<_>>()
         .await;
@@ -101,6 +192,11 @@ pub async fn fetch_sparse_repodata(
 
 /// Given a channel and platform, download and cache the `repodata.json` for it. This function
 /// reports its progress via a CLI progressbar.
+#[tracing::instrument(
+    name = "fetch_repodata",
+    skip_all,
+    fields(channel = %friendly_channel_name(&channel), platform = %platform)
+)]
 async fn fetch_repo_data_records_with_progress(
     channel: Channel,
     platform: Platform,
@@ -112,7 +208,7 @@ async fn fetch_repo_data_records_with_progress(
     // Download the repodata.json
     let download_progress_progress_bar = progress_bar.clone();
     let result = fetch::fetch_repo_data(
-        channel.platform_url(platform),
+        auth::authenticate_url(&channel.platform_url(platform)),
         client,
         repodata_cache.to_path_buf(),
         Default::default(),
@@ -146,6 +242,7 @@ async fn fetch_repo_data_records_with_progress(
     // Deserialize the data. This is a hefty blocking operation so we spawn it as a tokio blocking
     // task.
     let repo_data_json_path = result.repo_data_json_path.clone();
+    record_fingerprint(channel.platform_url(platform).as_str(), &result.cache_state);
     match tokio::task::spawn_blocking(move || {
         SparseRepoData::new(channel, platform.to_string(), repo_data_json_path, None)
     })
@@ -187,3 +284,34 @@ pub fn friendly_channel_name(channel: &Channel) -> String {
         .map(String::from)
         .unwrap_or_else(|| channel.canonical_name())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_identity() {
+        let state = serde_json::json!({
+            "url": "https://conda.anaconda.org/conda-forge/linux-64/repodata.json",
+            "etag": "\"abc\"",
+            "mod": "Tue, 02 Jan 2024 03:00:00 GMT",
+            "mtime_ns": 1704164400000000000u64,
+            "size": 1234,
+            "blake2_hash": "0123456789abcdef0123456789abcdef",
+        });
+        assert_eq!(
+            server_identity(&state).as_deref(),
+            Some("blake2:0123456789abcdef")
+        );
+
+        // The local modification time and size of the cache are never used
+        let state = serde_json::json!({ "etag": "\"abc\"", "mtime_ns": 1, "size": 2 });
+        assert_eq!(server_identity(&state).as_deref(), Some("etag:abc"));
+        let state = serde_json::json!({ "mod": "Tue, 02 Jan 2024 03:00:00 GMT" });
+        assert_eq!(
+            server_identity(&state).as_deref(),
+            Some("last-modified:Tue, 02 Jan 2024 03:00:00 GMT")
+        );
+        assert_eq!(server_identity(&serde_json::json!({ "size": 2 })), None);
+    }
+}
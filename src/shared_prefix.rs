@@ -0,0 +1,270 @@
+//! Sharing of prefixes between environments that resolve to identical lock content.
+//!
+//! When enabled through the `shared-environments` configuration, the packages of an environment
+//! are not installed in `.pixi/env` of the project but in a content addressed store in
+//! `~/.pixi/shared-envs/<hash>/env`, where the hash is computed from the locked packages. The
+//! `.pixi/env` directory of the project becomes a symlink to the prefix in the store, so projects
+//! with identical lock content share a single prefix.
+//!
+//! Each entry in the store keeps a reference file for every environment that links to it in its
+//! `refs` directory. When an environment switches to another entry, its reference is removed and
+//! entries without any valid references left are deleted.
+
+use crate::consts;
+use itertools::Itertools;
+use miette::IntoDiagnostic;
+use rattler_conda_types::Platform;
+use rattler_digest::{compute_bytes_digest, Sha256};
+use rattler_lock::{CondaLock, LockedDependencyKind};
+use std::path::{Path, PathBuf};
+
+/// The directory inside `~/.pixi` that contains the shared prefixes.
+const SHARED_ENVS_DIR: &str = "shared-envs";
+
+/// The directory inside a store entry that contains the reference files.
+const REFS_DIR: &str = "refs";
+
+/// Computes the hash that identifies the content of the environment for the given platform.
+///
+/// The hash covers the locked conda packages, the requested PyPI requirements and the overlays
+/// that are copied over the packages. The PyPI packages are locked after the conda packages are
+/// installed, so the requirements are used instead of the locked PyPI packages.
+pub fn lock_content_hash(
+    lock_file: &CondaLock,
+    platform: Platform,
+    pypi_requirements: impl IntoIterator<Item = String>,
+    overlays: impl IntoIterator<Item = String>,
+) -> String {
+    let conda_packages = lock_file
+        .package
+        .iter()
+        .filter(|package| package.platform == platform)
+        .filter_map(|package| match &package.kind {
+            LockedDependencyKind::Conda(conda) => Some(format!("conda {}", conda.url)),
+            LockedDependencyKind::Pypi(_) => None,
+        });
+    let pypi_requirements = pypi_requirements
+        .into_iter()
+        .map(|requirement| format!("pypi {requirement}"));
+    let overlays = overlays
+        .into_iter()
+        .map(|overlay| format!("overlay {overlay}"));
+    let content = conda_packages
+        .chain(pypi_requirements)
+        .chain(overlays)
+        .sorted()
+        .join("\n");
+    let digest = compute_bytes_digest::<Sha256>(format!("{platform}\n{content}").as_bytes());
+    format!("{digest:x}")[..16].to_string()
+}
+
+/// The content addressed store of shared prefixes.
+pub struct SharedPrefixStore {
+    root: PathBuf,
+}
+
+impl SharedPrefixStore {
+    /// Opens the store in `~/.pixi/shared-envs`.
+    pub fn open() -> miette::Result<Self> {
+        let root = dirs::home_dir()
+            .ok_or_else(|| miette::miette!("could not determine the home directory"))?
+            .join(consts::PIXI_DIR)
+            .join(SHARED_ENVS_DIR);
+        Ok(Self::new(root))
+    }
+
+    /// Constructs a store located at the given directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the directory of the store entry with the given hash.
+    fn entry_dir(&self, hash: &str) -> PathBuf {
+        self.root.join(hash)
+    }
+
+    /// Returns the path of the reference file of `link` inside the given entry.
+    fn ref_path(entry_dir: &Path, link: &Path) -> PathBuf {
+        let digest = compute_bytes_digest::<Sha256>(link.to_string_lossy().as_bytes());
+        entry_dir.join(REFS_DIR).join(format!("{digest:x}"))
+    }
+
+    /// Makes `link` point to the prefix of the store entry with the given hash and returns the
+    /// path of that prefix. The entry is created if it does not exist yet. The reference of `link`
+    /// to a previous entry is released.
+    pub fn acquire(&self, hash: &str, link: &Path) -> miette::Result<PathBuf> {
+        let entry_dir = self.entry_dir(hash);
+        let prefix = entry_dir.join(consts::ENVIRONMENT_DIR);
+        std::fs::create_dir_all(&prefix).into_diagnostic()?;
+        std::fs::create_dir_all(entry_dir.join(REFS_DIR)).into_diagnostic()?;
+
+        // Register the reference before touching the link so the entry is never without a
+        // reference while it is in use.
+        let link = absolute(link)?;
+        std::fs::write(
+            Self::ref_path(&entry_dir, &link),
+            link.to_string_lossy().as_bytes(),
+        )
+        .into_diagnostic()?;
+
+        match std::fs::read_link(&link) {
+            Ok(target) if target == prefix => return Ok(prefix),
+            Ok(target) => {
+                remove_symlink(&link)?;
+                if let Some(previous_entry) = target.parent() {
+                    self.release(previous_entry, &link)?;
+                }
+            }
+            Err(_) if link.is_dir() => {
+                // An unshared environment from before sharing was enabled.
+                tracing::info!(
+                    "replacing environment at {} with a shared prefix",
+                    link.display()
+                );
+                std::fs::remove_dir_all(&link).into_diagnostic()?;
+            }
+            Err(_) => {}
+        }
+
+        if let Some(parent) = link.parent() {
+            std::fs::create_dir_all(parent).into_diagnostic()?;
+        }
+        create_symlink(&prefix, &link)?;
+        Ok(prefix)
+    }
+
+    /// Removes `link` if it points to a prefix in the store and releases its reference. This is
+    /// used when sharing is disabled again so the environment is not installed into the store.
+    pub fn detach(&self, link: &Path) -> miette::Result<()> {
+        let link = absolute(link)?;
+        let Ok(target) = std::fs::read_link(&link) else {
+            return Ok(());
+        };
+        let Some(entry_dir) = target.parent().filter(|dir| dir.starts_with(&self.root)) else {
+            return Ok(());
+        };
+        remove_symlink(&link)?;
+        self.release(entry_dir, &link)
+    }
+
+    /// Removes the reference of `link` from the given entry and deletes the entry if no valid
+    /// references remain.
+    fn release(&self, entry_dir: &Path, link: &Path) -> miette::Result<()> {
+        if !entry_dir.starts_with(&self.root) {
+            return Ok(());
+        }
+        match std::fs::remove_file(Self::ref_path(entry_dir, link)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e).into_diagnostic(),
+            _ => {}
+        }
+        if self.valid_references(entry_dir)? == 0 {
+            tracing::info!("removing unused shared prefix {}", entry_dir.display());
+            std::fs::remove_dir_all(entry_dir).into_diagnostic()?;
+        }
+        Ok(())
+    }
+
+    /// Counts the references of an entry that still point to it. References of links that were
+    /// removed or point elsewhere (e.g. because the project was deleted) are cleaned up.
+    fn valid_references(&self, entry_dir: &Path) -> miette::Result<usize> {
+        let prefix = entry_dir.join(consts::ENVIRONMENT_DIR);
+        let mut count = 0;
+        for entry in std::fs::read_dir(entry_dir.join(REFS_DIR))
+            .into_iter()
+            .flatten()
+        {
+            let ref_file = entry.into_diagnostic()?.path();
+            let link = std::fs::read_to_string(&ref_file).into_diagnostic()?;
+            if std::fs::read_link(link).map_or(false, |target| target == prefix) {
+                count += 1;
+            } else {
+                std::fs::remove_file(&ref_file).into_diagnostic()?;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Removes all entries of the store that are no longer referenced. Returns the directories of
+    /// the removed entries.
+    pub fn prune(&self) -> miette::Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+        for entry in std::fs::read_dir(&self.root).into_iter().flatten() {
+            let entry_dir = entry.into_diagnostic()?.path();
+            if entry_dir.is_dir() && self.valid_references(&entry_dir)? == 0 {
+                std::fs::remove_dir_all(&entry_dir).into_diagnostic()?;
+                removed.push(entry_dir);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Makes the path absolute without resolving symlinks.
+fn absolute(path: &Path) -> miette::Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(std::env::current_dir().into_diagnostic()?.join(path))
+    }
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path) -> miette::Result<()> {
+    std::os::unix::fs::symlink(target, link).into_diagnostic()
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path) -> miette::Result<()> {
+    std::os::windows::fs::symlink_dir(target, link).into_diagnostic()
+}
+
+#[cfg(unix)]
+fn remove_symlink(link: &Path) -> miette::Result<()> {
+    std::fs::remove_file(link).into_diagnostic()
+}
+
+#[cfg(windows)]
+fn remove_symlink(link: &Path) -> miette::Result<()> {
+    // Directory symlinks are removed like directories on Windows.
+    std::fs::remove_dir(link).into_diagnostic()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_prefix_reference_counting() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = SharedPrefixStore::new(dir.path().join("store"));
+        let link_a = dir.path().join("a").join("env");
+        let link_b = dir.path().join("b").join("env");
+
+        // Both environments share the same prefix.
+        let prefix = store.acquire("1234", &link_a).unwrap();
+        assert_eq!(store.acquire("1234", &link_b).unwrap(), prefix);
+        assert_eq!(std::fs::read_link(&link_a).unwrap(), prefix);
+        assert_eq!(std::fs::read_link(&link_b).unwrap(), prefix);
+
+        // The entry is kept while it is still referenced.
+        store.acquire("5678", &link_a).unwrap();
+        assert!(prefix.is_dir());
+
+        // And removed once the last reference is gone.
+        store.acquire("5678", &link_b).unwrap();
+        assert!(!prefix.exists());
+        assert!(store.prune().unwrap().is_empty());
+
+        // Detaching releases the reference but keeps entries that are still in use.
+        store.acquire("5678", &link_a).unwrap();
+        store.detach(&link_b).unwrap();
+        assert!(!link_b.exists());
+        assert!(dir.path().join("store").join("5678").is_dir());
+        store.acquire("5678", &link_b).unwrap();
+
+        // Entries of removed environments are pruned.
+        std::fs::remove_file(&link_a).unwrap();
+        std::fs::remove_file(&link_b).unwrap();
+        assert_eq!(store.prune().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,350 @@
+//! Rewrites the shebangs of the scripts in the `bin` directory of an environment. Entry-point
+//! scripts are installed with the absolute path of the interpreter in the environment, which stops
+//! working when the environment is moved, e.g. when the project directory is renamed or the
+//! environment is copied into a container image. After every install the scripts are pointed at
+//! the current location of the environment, or at `/usr/bin/env <interpreter>` so they use the
+//! interpreter that is first on the `PATH` of the activated environment.
+
+use crate::consts;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// The file in the `.pixi` directory of the environment that records where the environment was when
+/// the shebangs were last written, and in which style. It is not stored in `conda-meta`, where every
+/// json file is read as the record of an installed package.
+const SHEBANG_STATE_FILE: &str = "shebangs.json";
+
+/// The maximum length of a shebang line that is supported by Linux.
+const MAX_SHEBANG_LENGTH: usize = 127;
+
+/// How the shebangs of the scripts in an environment are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShebangStyle {
+    /// The absolute path of the interpreter in the environment, e.g.
+    /// `#!/path/to/project/.pixi/env/bin/python3.12`.
+    #[default]
+    Absolute,
+    /// The interpreter is looked up on the `PATH`, e.g. `#!/usr/bin/env python3.12`.
+    Env,
+}
+
+/// Where the environment was when its shebangs were last written, and in which style.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ShebangState {
+    prefix: PathBuf,
+    style: ShebangStyle,
+}
+
+/// A parsed shebang line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Shebang {
+    interpreter: String,
+    args: String,
+}
+
+impl Shebang {
+    /// Parses the first line of a script, without the line ending.
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.strip_prefix("#!")?.trim();
+        let (interpreter, args) = line.split_once([' ', '\t']).unwrap_or((line, ""));
+        if interpreter.is_empty() {
+            return None;
+        }
+        Some(Self {
+            interpreter: interpreter.to_string(),
+            args: args.trim().to_string(),
+        })
+    }
+
+    /// Returns the path of the interpreter relative to the environment if it is in `prefix`,
+    /// in `old_prefix`, or (for `/usr/bin/env` shebangs that were written by pixi) in the `bin`
+    /// directory of `prefix`.
+    fn interpreter_in_prefix(
+        &self,
+        prefix: &Path,
+        old_prefix: Option<&Path>,
+        env_written: bool,
+    ) -> Option<PathBuf> {
+        let interpreter = Path::new(&self.interpreter);
+        if let Ok(relative) = interpreter.strip_prefix(prefix) {
+            return Some(relative.to_path_buf());
+        }
+        if let Some(relative) = old_prefix.and_then(|old| interpreter.strip_prefix(old).ok()) {
+            return Some(relative.to_path_buf());
+        }
+        if env_written && self.interpreter == "/usr/bin/env" && !self.args.contains(' ') {
+            let relative = Path::new("bin").join(&self.args);
+            if prefix.join(&relative).is_file() {
+                return Some(relative);
+            }
+        }
+        None
+    }
+
+    /// Returns the shebang line for an interpreter at `relative` in `prefix`.
+    fn render(prefix: &Path, relative: &Path, args: &str, style: ShebangStyle) -> String {
+        let absolute = if args.is_empty() {
+            format!("#!{}", prefix.join(relative).display())
+        } else {
+            format!("#!{} {args}", prefix.join(relative).display())
+        };
+        let name = relative.file_name().map(|name| name.to_string_lossy());
+        match (style, name) {
+            // `env` passes all arguments as a single one on Linux, so shebangs with arguments
+            // keep the absolute path.
+            (ShebangStyle::Env, Some(name)) if args.is_empty() => format!("#!/usr/bin/env {name}"),
+            (ShebangStyle::Absolute, Some(name))
+                if absolute.len() > MAX_SHEBANG_LENGTH && args.is_empty() =>
+            {
+                tracing::debug!(
+                    "the shebang '{absolute}' is too long, using /usr/bin/env {name} instead"
+                );
+                format!("#!/usr/bin/env {name}")
+            }
+            _ => absolute,
+        }
+    }
+}
+
+/// Returns the first line of a file if it starts with `#!`.
+fn read_shebang_line(path: &Path) -> std::io::Result<Option<String>> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let mut magic = [0u8; 2];
+    if reader.read_exact(&mut magic).is_err() || magic != *b"#!" {
+        return Ok(None);
+    }
+    let mut line = Vec::new();
+    reader.take(4096).read_until(b'\n', &mut line)?;
+    Ok(String::from_utf8(line)
+        .ok()
+        .map(|line| format!("#!{}", line.trim_end_matches(['\n', '\r']))))
+}
+
+/// Replaces the first line of the script at `path` with `shebang`. The script is replaced instead
+/// of modified in place, so files that are hard linked from the package cache are not changed.
+fn replace_shebang_line(path: &Path, shebang: &str) -> std::io::Result<()> {
+    let content = fs::read(path)?;
+    let rest = content
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map_or(&[][..], |end| &content[end..]);
+    let permissions = fs::metadata(path)?.permissions();
+    let mut new_content = shebang.as_bytes().to_vec();
+    new_content.extend_from_slice(rest);
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let temp = tempfile::NamedTempFile::new_in(dir)?;
+    fs::write(temp.path(), new_content)?;
+    fs::set_permissions(temp.path(), permissions)?;
+    temp.persist(path).map_err(|err| err.error)?;
+    Ok(())
+}
+
+/// Rewrites the shebangs of the scripts in the `bin` directory of `prefix` that refer to an
+/// interpreter in the environment, including those written when the environment was at
+/// `old_prefix`. `env_written` indicates whether `/usr/bin/env` shebangs were written by pixi
+/// before. Returns the number of scripts that were changed.
+fn rewrite_shebangs(
+    prefix: &Path,
+    old_prefix: Option<&Path>,
+    env_written: bool,
+    style: ShebangStyle,
+) -> std::io::Result<usize> {
+    let mut changed = 0;
+    let Ok(entries) = fs::read_dir(prefix.join("bin")) else {
+        return Ok(0);
+    };
+    for entry in entries {
+        let entry = entry?;
+        // Symlinks point at files that are handled themselves
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let path = entry.path();
+        let Some(line) = read_shebang_line(&path)? else {
+            continue;
+        };
+        let Some(shebang) = Shebang::parse(&line) else {
+            continue;
+        };
+        let Some(relative) = shebang.interpreter_in_prefix(prefix, old_prefix, env_written) else {
+            continue;
+        };
+        let args = if shebang.interpreter == "/usr/bin/env" {
+            ""
+        } else {
+            shebang.args.as_str()
+        };
+        let new_line = Shebang::render(prefix, &relative, args, style);
+        if new_line != line {
+            replace_shebang_line(&path, &new_line)?;
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// Writes the shebangs of the scripts in the environment at `prefix` in the given `style`,
+/// pointing the scripts at the current location of the environment if it was moved.
+pub fn update_shebangs(prefix: &Path, style: ShebangStyle) -> miette::Result<()> {
+    if cfg!(windows) {
+        // Scripts on Windows are started by launchers instead of shebangs
+        return Ok(());
+    }
+
+    let state_dir = prefix.join(consts::PIXI_DIR);
+    let state_path = state_dir.join(SHEBANG_STATE_FILE);
+    let previous = fs::read_to_string(&state_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<ShebangState>(&content).ok());
+    let old_prefix = previous
+        .as_ref()
+        .map(|state| state.prefix.as_path())
+        .filter(|old| *old != prefix);
+    if let Some(old_prefix) = old_prefix {
+        tracing::info!(
+            "the environment was moved from '{}', updating the scripts",
+            old_prefix.display()
+        );
+    }
+    let env_written = previous
+        .as_ref()
+        .map_or(false, |state| state.style == ShebangStyle::Env);
+
+    let changed = rewrite_shebangs(prefix, old_prefix, env_written, style)
+        .map_err(|err| miette::miette!("failed to update the scripts of the environment: {err}"))?;
+    if changed > 0 {
+        tracing::info!("updated the shebang of {changed} scripts");
+    }
+
+    let state = ShebangState {
+        prefix: prefix.to_path_buf(),
+        style,
+    };
+    if previous.as_ref() != Some(&state) && prefix.join("conda-meta").is_dir() {
+        fs::create_dir_all(&state_dir)
+            .and_then(|_| {
+                fs::write(
+                    &state_path,
+                    serde_json::to_string(&state).expect("the state can be serialized"),
+                )
+            })
+            .map_err(|err| miette::miette!("failed to write '{}': {err}", state_path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn write_script(prefix: &Path, name: &str, content: &str) -> PathBuf {
+        let path = prefix.join("bin").join(name);
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_shebang() {
+        assert_eq!(
+            Shebang::parse("#!/env/bin/python3.12 -E"),
+            Some(Shebang {
+                interpreter: String::from("/env/bin/python3.12"),
+                args: String::from("-E"),
+            })
+        );
+        assert_eq!(Shebang::parse("#!"), None);
+        assert_eq!(Shebang::parse("import sys"), None);
+    }
+
+    #[test]
+    fn test_update_shebangs() {
+        let dir = tempfile::tempdir().unwrap();
+        let old_prefix = dir.path().join("old");
+        let prefix = dir.path().join("env");
+        fs::create_dir_all(prefix.join("bin")).unwrap();
+        fs::create_dir_all(prefix.join("conda-meta")).unwrap();
+        fs::create_dir_all(prefix.join(consts::PIXI_DIR)).unwrap();
+        fs::write(prefix.join("bin").join("python3.12"), "").unwrap();
+        fs::write(
+            prefix.join(consts::PIXI_DIR).join(SHEBANG_STATE_FILE),
+            serde_json::to_string(&ShebangState {
+                prefix: old_prefix.clone(),
+                style: ShebangStyle::Absolute,
+            })
+            .unwrap(),
+        )
+        .unwrap();
+
+        let moved = write_script(
+            &prefix,
+            "black",
+            &format!("#!{}/bin/python3.12\nimport black\n", old_prefix.display()),
+        );
+        let with_args = write_script(
+            &prefix,
+            "tool",
+            &format!("#!{}/bin/python3.12 -E\nimport tool\n", prefix.display()),
+        );
+        let system = write_script(&prefix, "system", "#!/bin/sh\necho hi\n");
+
+        // The moved environment is fixed
+        update_shebangs(&prefix, ShebangStyle::Absolute).unwrap();
+        assert_eq!(
+            fs::read_to_string(&moved).unwrap(),
+            format!("#!{}/bin/python3.12\nimport black\n", prefix.display())
+        );
+        assert_eq!(
+            fs::metadata(&moved).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+
+        // Scripts without arguments use env
+        update_shebangs(&prefix, ShebangStyle::Env).unwrap();
+        assert_eq!(
+            fs::read_to_string(&moved).unwrap(),
+            "#!/usr/bin/env python3.12\nimport black\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&with_args).unwrap(),
+            format!("#!{}/bin/python3.12 -E\nimport tool\n", prefix.display())
+        );
+        assert_eq!(fs::read_to_string(&system).unwrap(), "#!/bin/sh\necho hi\n");
+
+        // And the absolute paths are restored
+        update_shebangs(&prefix, ShebangStyle::Absolute).unwrap();
+        assert_eq!(
+            fs::read_to_string(&moved).unwrap(),
+            format!("#!{}/bin/python3.12\nimport black\n", prefix.display())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_shebangs_twice_keeps_records_readable() {
+        let dir = tempfile::tempdir().unwrap();
+        let prefix = dir.path().join("env");
+        fs::create_dir_all(prefix.join("bin")).unwrap();
+        fs::create_dir_all(prefix.join("conda-meta")).unwrap();
+        write_script(&prefix, "tool", "#!/bin/sh\necho hi\n");
+
+        // Installing twice reads the state of the first install, and the records of the
+        // environment can still be read afterwards.
+        update_shebangs(&prefix, ShebangStyle::Absolute).unwrap();
+        update_shebangs(&prefix, ShebangStyle::Env).unwrap();
+        assert!(prefix
+            .join(consts::PIXI_DIR)
+            .join(SHEBANG_STATE_FILE)
+            .is_file());
+        let records = crate::prefix::Prefix::new(&prefix)
+            .unwrap()
+            .find_installed_packages(None)
+            .await
+            .unwrap();
+        assert!(records.is_empty());
+    }
+}
@@ -0,0 +1,300 @@
+//! Snapshots of the state of an environment of a project. A snapshot stores the manifest, the files
+//! it includes, the lock file and the packages that were installed in `.pixi/snapshots/<name>`.
+//! Restoring a snapshot writes the manifest, its included files and the lock file back and reinstalls the environment from the lock file, the
+//! packages are linked from the package cache so no copy of the environment is needed.
+
+use crate::consts::SNAPSHOTS_DIR;
+use crate::environment::{get_up_to_date_prefix, LockFileUsage};
+use crate::prefix::Prefix;
+use crate::Project;
+use chrono::{DateTime, Local, Utc};
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The file in the directory of a snapshot that describes it.
+const SNAPSHOT_FILE: &str = "snapshot.json";
+/// The files in the directory of a snapshot that store the manifest and lock file.
+const MANIFEST_FILE: &str = "manifest.toml";
+const LOCK_FILE: &str = "lock";
+/// The directory in the directory of a snapshot that stores the files the manifest includes, by
+/// their path relative to the root of the project.
+const INCLUDE_DIR: &str = "include";
+
+/// Describes a snapshot of an environment.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Snapshot {
+    pub name: String,
+    /// When the snapshot was created, in RFC 3339 format.
+    pub created: String,
+    /// The environment of the project, `None` for the default environment.
+    pub environment: Option<String>,
+    /// The path of the lock file relative to the root of the project.
+    pub lock_file: PathBuf,
+    /// The paths of the files the manifest includes relative to the root of the project.
+    #[serde(default)]
+    pub included_files: Vec<PathBuf>,
+    /// The conda packages that were installed, as `name version build`.
+    pub packages: Vec<String>,
+}
+
+impl Snapshot {
+    /// Returns the creation time in the local timezone.
+    pub fn created_local(&self) -> Option<DateTime<Local>> {
+        DateTime::parse_from_rfc3339(&self.created)
+            .ok()
+            .map(|created| created.with_timezone(&Local))
+    }
+}
+
+/// Returns the directory that stores the snapshots of a project.
+fn snapshots_dir(project: &Project) -> PathBuf {
+    project.pixi_dir().join(SNAPSHOTS_DIR)
+}
+
+/// Returns the directory of a snapshot, after checking that the name can be used as a directory.
+fn snapshot_dir(project: &Project, name: &str) -> miette::Result<PathBuf> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        miette::bail!(
+            help = "use letters, digits, '-', '_' and '.'",
+            "'{name}' is not a valid snapshot name"
+        );
+    }
+    Ok(snapshots_dir(project).join(name))
+}
+
+fn read_snapshot(dir: &Path) -> miette::Result<Snapshot> {
+    let path = dir.join(SNAPSHOT_FILE);
+    let contents = std::fs::read_to_string(&path)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to read '{}'", path.display()))?;
+    serde_json::from_str(&contents)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to parse '{}'", path.display()))
+}
+
+/// Creates a snapshot of the selected environment of the project. An existing snapshot with the
+/// same name is only replaced with `force`.
+pub async fn create_snapshot(
+    project: &Project,
+    name: &str,
+    force: bool,
+) -> miette::Result<Snapshot> {
+    let dir = snapshot_dir(project, name)?;
+    if dir.exists() {
+        if !force {
+            miette::bail!(
+                help = "use --force to replace it",
+                "the snapshot '{name}' already exists"
+            );
+        }
+        std::fs::remove_dir_all(&dir).into_diagnostic()?;
+    }
+
+    let lock_file_path = project.lock_file_path();
+    if !lock_file_path.is_file() {
+        miette::bail!(
+            help = "run `pixi install` first",
+            "the project has no lock file to snapshot"
+        );
+    }
+
+    let included_files = project.included_files()?;
+
+    let packages = if project.environment_dir().is_dir() {
+        Prefix::new(project.environment_dir())?
+            .find_installed_packages(None)
+            .await?
+            .iter()
+            .map(|record| {
+                let package = &record.repodata_record.package_record;
+                format!(
+                    "{} {} {}",
+                    package.name.as_normalized(),
+                    package.version,
+                    package.build
+                )
+            })
+            .sorted()
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let snapshot = Snapshot {
+        name: name.to_string(),
+        created: Utc::now().to_rfc3339(),
+        environment: project.environment_name().map(str::to_string),
+        lock_file: lock_file_path
+            .strip_prefix(project.root())
+            .unwrap_or(&lock_file_path)
+            .to_path_buf(),
+        included_files: included_files
+            .iter()
+            .map(|(relative, _)| relative.clone())
+            .collect(),
+        packages,
+    };
+
+    std::fs::create_dir_all(&dir).into_diagnostic()?;
+    std::fs::copy(project.manifest_path(), dir.join(MANIFEST_FILE)).into_diagnostic()?;
+    std::fs::copy(&lock_file_path, dir.join(LOCK_FILE)).into_diagnostic()?;
+    for (relative, path) in &included_files {
+        copy_creating_parent(path, &dir.join(INCLUDE_DIR).join(relative))?;
+    }
+    std::fs::write(
+        dir.join(SNAPSHOT_FILE),
+        serde_json::to_string_pretty(&snapshot).into_diagnostic()?,
+    )
+    .into_diagnostic()?;
+    Ok(snapshot)
+}
+
+/// Returns the snapshots of the project, oldest first.
+pub fn list_snapshots(project: &Project) -> miette::Result<Vec<Snapshot>> {
+    let dir = snapshots_dir(project);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in std::fs::read_dir(&dir).into_diagnostic()? {
+        let entry = entry.into_diagnostic()?;
+        if entry.path().join(SNAPSHOT_FILE).is_file() {
+            snapshots.push(read_snapshot(&entry.path())?);
+        }
+    }
+    snapshots.sort_by(|a, b| (&a.created, &a.name).cmp(&(&b.created, &b.name)));
+    Ok(snapshots)
+}
+
+/// Restores the manifest and lock file of a snapshot and reinstalls the environment from the lock
+/// file. Returns the snapshot and the project as it was restored.
+pub async fn restore_snapshot(
+    project: &Project,
+    name: &str,
+    no_install: bool,
+) -> miette::Result<(Snapshot, Project)> {
+    let dir = snapshot_dir(project, name)?;
+    if !dir.join(SNAPSHOT_FILE).is_file() {
+        miette::bail!(
+            help = "run `pixi snapshot list` to see the snapshots",
+            "the snapshot '{name}' does not exist"
+        );
+    }
+    let snapshot = read_snapshot(&dir)?;
+
+    let manifest_path = project.manifest_path();
+    std::fs::copy(dir.join(MANIFEST_FILE), &manifest_path).into_diagnostic()?;
+    for relative in &snapshot.included_files {
+        copy_creating_parent(
+            &dir.join(INCLUDE_DIR).join(relative),
+            &project.root().join(relative),
+        )?;
+    }
+    copy_creating_parent(
+        &dir.join(LOCK_FILE),
+        &project.root().join(&snapshot.lock_file),
+    )?;
+
+    let project = Project::load_or_else_discover(Some(&manifest_path))?
+        .with_environment(snapshot.environment.as_deref())?;
+    if !no_install {
+        get_up_to_date_prefix(&project, LockFileUsage::Frozen, false, None).await?;
+    }
+    Ok((snapshot, project))
+}
+
+/// Copies the file `from` to `to`, the parent directory of `to` is created if it doesn't exist.
+fn copy_creating_parent(from: &Path, to: &Path) -> miette::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent).into_diagnostic()?;
+    }
+    std::fs::copy(from, to)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to copy '{}'", from.display()))?;
+    Ok(())
+}
+
+/// Removes a snapshot.
+pub fn remove_snapshot(project: &Project, name: &str) -> miette::Result<()> {
+    let dir = snapshot_dir(project, name)?;
+    if !dir.is_dir() {
+        miette::bail!("the snapshot '{name}' does not exist");
+    }
+    std::fs::remove_dir_all(&dir).into_diagnostic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::PROJECT_MANIFEST;
+
+    #[tokio::test]
+    async fn test_create_and_list_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = r#"
+            [project]
+            name = "foo"
+            channels = []
+            platforms = ["linux-64"]
+            "#;
+        std::fs::write(dir.path().join(PROJECT_MANIFEST), manifest).unwrap();
+        let project = Project::from_str(dir.path(), manifest).unwrap();
+
+        // A snapshot needs a lock file.
+        assert!(create_snapshot(&project, "before", false).await.is_err());
+        std::fs::write(project.lock_file_path(), "version: 1\n").unwrap();
+
+        let snapshot = create_snapshot(&project, "before", false).await.unwrap();
+        assert_eq!(snapshot.lock_file, PathBuf::from("pixi.lock"));
+        assert!(snapshot.packages.is_empty());
+        assert!(create_snapshot(&project, "before", false).await.is_err());
+        create_snapshot(&project, "before", true).await.unwrap();
+        assert!(create_snapshot(&project, "../escape", false).await.is_err());
+
+        let snapshots = list_snapshots(&project).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].name, "before");
+        assert!(snapshots[0].created_local().is_some());
+
+        remove_snapshot(&project, "before").unwrap();
+        assert!(list_snapshots(&project).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_included_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = r#"
+            include = ["tasks/test.toml"]
+
+            [project]
+            name = "foo"
+            channels = []
+            platforms = ["linux-64"]
+            "#;
+        std::fs::write(dir.path().join(PROJECT_MANIFEST), manifest).unwrap();
+        let included = dir.path().join("tasks/test.toml");
+        std::fs::create_dir_all(included.parent().unwrap()).unwrap();
+        std::fs::write(&included, "[tasks]\ntest = \"pytest\"\n").unwrap();
+        let project = Project::from_str(dir.path(), manifest).unwrap();
+        std::fs::write(project.lock_file_path(), "version: 1\n").unwrap();
+
+        let snapshot = create_snapshot(&project, "before", false).await.unwrap();
+        assert_eq!(snapshot.included_files, [PathBuf::from("tasks/test.toml")]);
+
+        // Restoring the snapshot restores the included file.
+        std::fs::write(&included, "[tasks]\ntest = \"pytest -x\"\n").unwrap();
+        restore_snapshot(&project, "before", true).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&included).unwrap(),
+            "[tasks]\ntest = \"pytest\"\n"
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Caching of task results.
+//!
+//! Tasks that declare `inputs`, `outputs` or `cache-env` are only executed if something that
+//! influences their result changed since the last successful run. The cache key of a task is a hash
+//! of its command, working directory, the contents of its inputs, the values of the environment
+//! variables listed in `cache-env` and the lock-file of the project. The key of the last
+//! successful run is stored in `.pixi/task-cache`.
+//!
+//! Paths are hashed relative to the root of the project, so the same task results in the same key
+//! on every machine. This allows sharing the outputs of tasks through a remote cache (see
+//! [`super::remote_cache`]).
+
+use crate::consts;
+use rattler_digest::{compute_bytes_digest, compute_file_digest, Sha256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// The directory inside `.pixi` that stores the cache keys of the tasks.
+const TASK_CACHE_DIR: &str = "task-cache";
+
+/// Everything that determines the result of a task.
+pub struct CacheKeyInputs<'a> {
+    /// The full command that is executed, including additional arguments.
+    pub command: &'a str,
+
+    /// The root directory of the project.
+    pub project_root: &'a Path,
+
+    /// The directory the command is executed in.
+    pub cwd: &'a Path,
+
+    /// Files or directories whose contents influence the result, relative to `cwd`.
+    pub inputs: &'a [PathBuf],
+
+    /// The names of the environment variables whose values influence the result.
+    pub cache_env: &'a [String],
+
+    /// The environment the command is executed with.
+    pub env: &'a HashMap<String, String>,
+
+    /// The lock-file of the project, changes to the environment invalidate the cache.
+    pub lock_file: &'a Path,
+}
+
+/// Recursively collects all the files in the given path in a deterministic order.
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if path.is_dir() {
+        let mut entries = std::fs::read_dir(path)?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+        for entry in entries {
+            collect_files(&entry, files)?;
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+/// Formats a path with forward slashes so it hashes the same on all platforms.
+fn portable_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Computes the cache key of a task.
+pub fn compute_cache_key(inputs: &CacheKeyInputs) -> std::io::Result<String> {
+    let mut content = String::new();
+    let _ = writeln!(content, "command: {}", inputs.command);
+    let cwd = inputs
+        .cwd
+        .strip_prefix(inputs.project_root)
+        .unwrap_or(inputs.cwd);
+    let _ = writeln!(content, "cwd: {}", portable_path(cwd));
+
+    for input in inputs.inputs {
+        let mut files = Vec::new();
+        collect_files(&inputs.cwd.join(input), &mut files)?;
+        for file in files {
+            let digest = compute_file_digest::<Sha256>(&file)?;
+            let relative = file.strip_prefix(inputs.cwd).unwrap_or(&file);
+            let _ = writeln!(content, "input: {} {digest:x}", portable_path(relative));
+        }
+    }
+
+    for name in inputs.cache_env {
+        match inputs.env.get(name) {
+            Some(value) => {
+                let _ = writeln!(content, "env: {name}={value}");
+            }
+            None => {
+                let _ = writeln!(content, "env: {name} (unset)");
+            }
+        }
+    }
+
+    if inputs.lock_file.is_file() {
+        let digest = compute_file_digest::<Sha256>(inputs.lock_file)?;
+        let _ = writeln!(content, "lock-file: {digest:x}");
+    }
+
+    Ok(format!(
+        "{:x}",
+        compute_bytes_digest::<Sha256>(content.as_bytes())
+    ))
+}
+
+/// The contents of a cache file.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// The cache key of the last successful run.
+    key: String,
+}
+
+/// Stores the cache keys of the last successful runs of the tasks of a project.
+pub struct TaskCache {
+    dir: PathBuf,
+}
+
+impl TaskCache {
+    /// Constructs the cache for the project located at `project_root`.
+    pub fn new(project_root: &Path) -> Self {
+        Self {
+            dir: project_root.join(consts::PIXI_DIR).join(TASK_CACHE_DIR),
+        }
+    }
+
+    /// Returns the path of the cache file of a task.
+    fn entry_path(&self, task_name: &str) -> PathBuf {
+        let file_name = task_name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        self.dir.join(format!("{file_name}.json"))
+    }
+
+    /// Returns true if the last successful run of the task had the same cache key and all the
+    /// `outputs` (relative to `cwd`) still exist.
+    pub fn is_up_to_date(
+        &self,
+        task_name: &str,
+        key: &str,
+        cwd: &Path,
+        outputs: &[PathBuf],
+    ) -> bool {
+        let Ok(contents) = std::fs::read_to_string(self.entry_path(task_name)) else {
+            return false;
+        };
+        let Ok(entry) = serde_json::from_str::<CacheEntry>(&contents) else {
+            return false;
+        };
+        entry.key == key && outputs.iter().all(|output| cwd.join(output).exists())
+    }
+
+    /// Records the cache key of a successful run of the task.
+    pub fn store(&self, task_name: &str, key: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let entry = CacheEntry {
+            key: key.to_string(),
+        };
+        std::fs::write(
+            self.entry_path(task_name),
+            serde_json::to_string(&entry).map_err(std::io::Error::from)?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_includes_env_and_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src").join("main.c"), "int main() {}").unwrap();
+
+        let inputs = [PathBuf::from("src")];
+        let cache_env = [String::from("CC"), String::from("CFLAGS")];
+        let mut env = HashMap::from([(String::from("CC"), String::from("gcc"))]);
+        let key = |env: &HashMap<String, String>| {
+            compute_cache_key(&CacheKeyInputs {
+                command: "make",
+                project_root: dir.path(),
+                cwd: dir.path(),
+                inputs: &inputs,
+                cache_env: &cache_env,
+                env,
+                lock_file: &dir.path().join("pixi.lock"),
+            })
+            .unwrap()
+        };
+
+        let initial = key(&env);
+        assert_eq!(initial, key(&env));
+
+        // Variables that are not part of `cache-env` do not influence the key.
+        env.insert(String::from("HOME"), String::from("/home/user"));
+        assert_eq!(initial, key(&env));
+
+        // Changing the toolchain invalidates the cache.
+        env.insert(String::from("CC"), String::from("clang"));
+        let clang = key(&env);
+        assert_ne!(initial, clang);
+        env.insert(String::from("CFLAGS"), String::from("-O2"));
+        assert_ne!(clang, key(&env));
+
+        // And so does changing the inputs.
+        let with_flags = key(&env);
+        std::fs::write(
+            dir.path().join("src").join("main.c"),
+            "int main() { return 1; }",
+        )
+        .unwrap();
+        assert_ne!(with_flags, key(&env));
+    }
+
+    #[test]
+    fn test_cache_key_is_independent_of_project_location() {
+        let key = |root: &Path| {
+            std::fs::create_dir_all(root.join("docs")).unwrap();
+            std::fs::write(root.join("docs").join("index.md"), "# Docs").unwrap();
+            compute_cache_key(&CacheKeyInputs {
+                command: "mkdocs build",
+                project_root: root,
+                cwd: &root.join("docs"),
+                inputs: &[PathBuf::from("index.md")],
+                cache_env: &[],
+                env: &HashMap::new(),
+                lock_file: &root.join("pixi.lock"),
+            })
+            .unwrap()
+        };
+
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        assert_eq!(key(first.path()), key(second.path()));
+    }
+
+    #[test]
+    fn test_task_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = TaskCache::new(dir.path());
+        let outputs = [PathBuf::from("out")];
+        assert!(!cache.is_up_to_date("build", "abc", dir.path(), &outputs));
+
+        cache.store("build", "abc").unwrap();
+        assert!(!cache.is_up_to_date("build", "abc", dir.path(), &outputs));
+
+        std::fs::write(dir.path().join("out"), "").unwrap();
+        assert!(cache.is_up_to_date("build", "abc", dir.path(), &outputs));
+        assert!(!cache.is_up_to_date("build", "def", dir.path(), &outputs));
+    }
+}
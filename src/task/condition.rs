@@ -0,0 +1,343 @@
+//! Conditions that decide whether a task runs, e.g. `platform == 'linux-64' && env.CI == 'true'`.
+//!
+//! A condition compares the `platform` or an environment variable (`env.NAME`) with a string with
+//! `==` or `!=`, checks whether a file exists with `exists('path')`, or checks whether an
+//! environment variable is set and not empty (`env.NAME`). These can be combined with `&&`, `||`,
+//! `!` and parentheses.
+
+use rattler_conda_types::Platform;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// An error that occurred while parsing a condition.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("invalid condition '{condition}': {reason}")]
+pub struct ParseConditionError {
+    condition: String,
+    reason: String,
+}
+
+/// The values a condition is evaluated against.
+pub struct ConditionContext<'a> {
+    /// The platform the task runs on.
+    pub platform: Platform,
+    /// The environment variables of the task.
+    pub env: &'a HashMap<String, String>,
+    /// The directory that paths in `exists` are relative to.
+    pub root: &'a Path,
+}
+
+/// A parsed condition of a task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    /// Returns true if the condition holds in the given context.
+    pub fn evaluate(&self, context: &ConditionContext) -> bool {
+        self.expr.evaluate(context)
+    }
+}
+
+impl FromStr for Condition {
+    type Err = ParseConditionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |reason: String| ParseConditionError {
+            condition: s.to_string(),
+            reason,
+        };
+        let tokens = tokenize(s).map_err(error)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let expr = parser.parse_or().map_err(error)?;
+        if let Some(token) = parser.peek() {
+            return Err(error(format!("unexpected {token}")));
+        }
+        Ok(Self {
+            source: s.to_string(),
+            expr,
+        })
+    }
+}
+
+impl Display for Condition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    Platform,
+    Env(String),
+    Literal(String),
+}
+
+impl Operand {
+    fn value(&self, context: &ConditionContext) -> String {
+        match self {
+            Operand::Platform => context.platform.to_string(),
+            Operand::Env(name) => context.env.get(name).cloned().unwrap_or_default(),
+            Operand::Literal(value) => value.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Equals(Operand, Operand),
+    NotEquals(Operand, Operand),
+    Exists(String),
+    IsSet(Operand),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn evaluate(&self, context: &ConditionContext) -> bool {
+        match self {
+            Expr::Equals(lhs, rhs) => lhs.value(context) == rhs.value(context),
+            Expr::NotEquals(lhs, rhs) => lhs.value(context) != rhs.value(context),
+            Expr::Exists(path) => context.root.join(path).exists(),
+            Expr::IsSet(operand) => !operand.value(context).is_empty(),
+            Expr::Not(expr) => !expr.evaluate(context),
+            Expr::And(lhs, rhs) => lhs.evaluate(context) && rhs.evaluate(context),
+            Expr::Or(lhs, rhs) => lhs.evaluate(context) || rhs.evaluate(context),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Eq,
+    NotEq,
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(ident) => write!(f, "'{ident}'"),
+            Token::Str(value) => write!(f, "string '{value}'"),
+            Token::Eq => f.write_str("'=='"),
+            Token::NotEq => f.write_str("'!='"),
+            Token::And => f.write_str("'&&'"),
+            Token::Or => f.write_str("'||'"),
+            Token::Not => f.write_str("'!'"),
+            Token::LParen => f.write_str("'('"),
+            Token::RParen => f.write_str("')'"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Eq,
+            '!' if chars.next_if_eq(&'=').is_some() => Token::NotEq,
+            '!' => Token::Not,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '\'' | '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some(c) => value.push(c),
+                        None => return Err(String::from("unterminated string")),
+                    }
+                }
+                Token::Str(value)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = String::from(c);
+                while let Some(c) =
+                    chars.next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.'))
+                {
+                    ident.push(c);
+                }
+                Token::Ident(ident)
+            }
+            c => return Err(format!("unexpected character '{c}'")),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token) -> Result<(), String> {
+        match self.next() {
+            Some(next) if &next == token => Ok(()),
+            Some(next) => Err(format!("expected {token}, found {next}")),
+            None => Err(format!("expected {token}, found the end of the condition")),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.eat(&Token::Or) {
+            expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_unary()?;
+        while self.eat(&Token::And) {
+            expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.eat(&Token::Not) {
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        if self.eat(&Token::LParen) {
+            let expr = self.parse_or()?;
+            self.expect(&Token::RParen)?;
+            return Ok(expr);
+        }
+        if self.peek() == Some(&Token::Ident(String::from("exists"))) {
+            self.pos += 1;
+            self.expect(&Token::LParen)?;
+            let path = match self.next() {
+                Some(Token::Str(path)) => path,
+                _ => return Err(String::from("`exists` expects a path in quotes")),
+            };
+            self.expect(&Token::RParen)?;
+            return Ok(Expr::Exists(path));
+        }
+
+        let lhs = self.parse_operand()?;
+        if self.eat(&Token::Eq) {
+            Ok(Expr::Equals(lhs, self.parse_operand()?))
+        } else if self.eat(&Token::NotEq) {
+            Ok(Expr::NotEquals(lhs, self.parse_operand()?))
+        } else {
+            Ok(Expr::IsSet(lhs))
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, String> {
+        match self.next() {
+            Some(Token::Str(value)) => Ok(Operand::Literal(value)),
+            Some(Token::Ident(ident)) if ident == "platform" => Ok(Operand::Platform),
+            Some(Token::Ident(ident)) => match ident.strip_prefix("env.") {
+                Some(name) if !name.is_empty() => Ok(Operand::Env(name.to_string())),
+                _ => Err(format!(
+                    "unknown value '{ident}', expected `platform`, `env.<NAME>` or a string in quotes"
+                )),
+            },
+            Some(token) => Err(format!("unexpected {token}")),
+            None => Err(String::from("unexpected end of the condition")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(condition: &str, env: &[(&str, &str)], root: &Path) -> bool {
+        let env = env
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        Condition::from_str(condition)
+            .unwrap()
+            .evaluate(&ConditionContext {
+                platform: Platform::Linux64,
+                env: &env,
+                root,
+            })
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("setup.py"), "").unwrap();
+        let root = dir.path();
+
+        assert!(evaluate("platform == 'linux-64'", &[], root));
+        assert!(!evaluate("platform != \"linux-64\"", &[], root));
+        assert!(evaluate(
+            "platform == 'linux-64' && env.CI == 'true'",
+            &[("CI", "true")],
+            root
+        ));
+        assert!(!evaluate(
+            "platform == 'linux-64' && env.CI == 'true'",
+            &[],
+            root
+        ));
+        assert!(evaluate("env.CI || exists('setup.py')", &[], root));
+        assert!(!evaluate("!exists('setup.py')", &[], root));
+        assert!(evaluate(
+            "!(platform == 'osx-arm64' || platform == 'win-64') && !env.CI",
+            &[("CI", "")],
+            root
+        ));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for condition in [
+            "",
+            "platform ==",
+            "platform = 'linux-64'",
+            "os == 'linux'",
+            "(platform == 'linux-64'",
+            "exists(setup.py)",
+            "env.CI == 'true",
+            "env.CI env.HOME",
+        ] {
+            assert!(
+                Condition::from_str(condition).is_err(),
+                "'{condition}' should not parse"
+            );
+        }
+    }
+}
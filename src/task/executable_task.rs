@@ -1,11 +1,14 @@
 use crate::{
-    task::{quote_arguments, CmdArgs, Custom, Task},
+    task::{
+        compute_cache_key, quote_arguments, CacheKeyInputs, CmdArgs, ConditionContext, Custom,
+        InvalidTemplate, Task, TemplateVariables,
+    },
     Project,
 };
 use deno_task_shell::{
     execute_with_pipes, parser::SequentialList, pipe, ShellPipeWriter, ShellState,
 };
-use miette::Diagnostic;
+use miette::{Diagnostic, IntoDiagnostic};
 use rattler_conda_types::Platform;
 use std::{
     borrow::Cow,
@@ -13,6 +16,7 @@ use std::{
     env,
     fmt::{Display, Formatter},
     path::PathBuf,
+    sync::Arc,
 };
 use thiserror::Error;
 use tokio::task::JoinHandle;
@@ -50,6 +54,8 @@ pub enum TaskExecutionError {
     InvalidWorkingDirectory(#[from] InvalidWorkingDirectory),
     #[error(transparent)]
     FailedToParseShellScript(#[from] FailedToParseShellScript),
+    #[error(transparent)]
+    InvalidTemplate(#[from] InvalidTemplate),
 }
 
 /// A task that contains enough information to be able to execute it. The lifetime [`'p`] refers to
@@ -61,6 +67,7 @@ pub struct ExecutableTask<'p> {
     pub(super) task: Cow<'p, Task>,
     pub(super) additional_args: Vec<String>,
     pub(super) platform: Option<Platform>,
+    pub(super) variables: Arc<TemplateVariables>,
 }
 
 impl<'p> ExecutableTask<'p> {
@@ -84,6 +91,12 @@ impl<'p> ExecutableTask<'p> {
         self.project
     }
 
+    /// Sets the values of the variables that can be used in the command of this task and the tasks
+    /// it depends on.
+    pub fn with_variables(self, variables: Arc<TemplateVariables>) -> Self {
+        Self { variables, ..self }
+    }
+
     /// Parses command line arguments into an [`ExecutableTask`].
     pub fn from_cmd_args(
         project: &'p Project,
@@ -102,6 +115,7 @@ impl<'p> ExecutableTask<'p> {
                     task: Cow::Borrowed(task),
                     additional_args: args,
                     platform,
+                    variables: Default::default(),
                 };
             }
         }
@@ -119,26 +133,39 @@ impl<'p> ExecutableTask<'p> {
             ),
             additional_args: vec![],
             platform,
+            variables: Default::default(),
         }
     }
 
-    /// Returns a [`SequentialList`] which can be executed by deno task shell. Returns `None` if the
-    /// command is not executable like in the case of an alias.
-    pub fn as_deno_script(&self) -> Result<Option<SequentialList>, FailedToParseShellScript> {
+    /// Returns the command of the task including the additional arguments as a single string, with
+    /// the variables in it replaced by their values. Returns `None` if the command is not
+    /// executable like in the case of an alias.
+    pub fn full_command(&self) -> Result<Option<String>, InvalidTemplate> {
         // Convert the task into an executable string
         let Some(task) = self.task.as_single_command() else {
             return Ok(None);
         };
+        let task = self.variables.render(&task)?;
 
         // Append the command line arguments
         let cli_args = quote_arguments(self.additional_args.iter().map(|arg| arg.as_str()));
-        let full_script = format!("{task} {cli_args}");
+        Ok(Some(format!("{task} {cli_args}").trim().to_string()))
+    }
+
+    /// Returns a [`SequentialList`] which can be executed by deno task shell. Returns `None` if the
+    /// command is not executable like in the case of an alias.
+    pub fn as_deno_script(&self) -> Result<Option<SequentialList>, TaskExecutionError> {
+        let Some(full_script) = self.full_command()? else {
+            return Ok(None);
+        };
 
         // Parse the shell command
-        deno_task_shell::parser::parse(full_script.trim())
-            .map_err(|e| FailedToParseShellScript {
-                script: full_script,
-                error: e.to_string(),
+        deno_task_shell::parser::parse(&full_script)
+            .map_err(|e| {
+                TaskExecutionError::from(FailedToParseShellScript {
+                    script: full_script,
+                    error: e.to_string(),
+                })
             })
             .map(Some)
     }
@@ -160,6 +187,41 @@ impl<'p> ExecutableTask<'p> {
         })
     }
 
+    /// Computes the cache key of this task. Returns `None` if the results of the task are not
+    /// cached.
+    pub fn cache_key(
+        &self,
+        command_env: &HashMap<String, String>,
+    ) -> miette::Result<Option<String>> {
+        let Some(execute) = self.task.as_execute().filter(|execute| execute.is_cached()) else {
+            return Ok(None);
+        };
+        let command = self.full_command().into_diagnostic()?.unwrap_or_default();
+        let cwd = self.working_directory().into_diagnostic()?;
+        compute_cache_key(&CacheKeyInputs {
+            command: &command,
+            project_root: self.project.root(),
+            cwd: &cwd,
+            inputs: &execute.inputs,
+            cache_env: &execute.cache_env,
+            env: command_env,
+            lock_file: &self.project.lock_file_path(),
+        })
+        .into_diagnostic()
+        .map(Some)
+    }
+
+    /// Returns true if the `skip-if` and `only-if` conditions of this task allow it to run with the
+    /// environment variables in `command_env`. Paths in the conditions are relative to the root of
+    /// the project.
+    pub fn conditions_hold(&self, command_env: &HashMap<String, String>) -> bool {
+        self.task.conditions_hold(&ConditionContext {
+            platform: self.platform.unwrap_or_else(Platform::current),
+            env: command_env,
+            root: self.project.root(),
+        })
+    }
+
     /// Returns an object that implements [`Display`] which outputs the command of the wrapped task.
     pub fn display_command(&self) -> impl Display + '_ {
         ExecutableTaskConsoleDisplay { task: self }
@@ -204,7 +266,12 @@ struct ExecutableTaskConsoleDisplay<'p, 't> {
 
 impl<'p, 't> Display for ExecutableTaskConsoleDisplay<'p, 't> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let command = self.task.task.as_single_command();
+        let command = self.task.task.as_single_command().map(|command| {
+            match self.task.variables.render(&command) {
+                Ok(rendered) => Cow::Owned(rendered),
+                Err(_) => command,
+            }
+        });
         write!(
             f,
             "{}",
@@ -378,4 +445,42 @@ mod tests {
 
         assert_eq!(task.task().as_single_command().unwrap(), r#""echo bla""#);
     }
+
+    #[test]
+    fn test_conditions_hold() {
+        let file_content = r#"
+        [project]
+        name = "pixi"
+        channels = ["conda-forge"]
+        platforms = ["linux-64", "win-64"]
+        [tasks]
+        ci = {cmd="echo ci", only-if="platform == 'linux-64' && env.CI == 'true'"}
+        unix = {cmd="echo unix", skip-if="platform == 'win-64'"}
+    "#;
+        let manifest = Manifest::from_str(Path::new(""), file_content.to_string()).unwrap();
+        let project = Project::from_manifest(manifest);
+        let ci_env = HashMap::from([(String::from("CI"), String::from("true"))]);
+
+        let task = |name: &str, platform| {
+            ExecutableTask::from_cmd_args(&project, vec![name.to_string()], Some(platform))
+        };
+        assert!(task("ci", Platform::Linux64).conditions_hold(&ci_env));
+        assert!(!task("ci", Platform::Linux64).conditions_hold(&HashMap::new()));
+        assert!(!task("ci", Platform::Win64).conditions_hold(&ci_env));
+        assert!(task("unix", Platform::Linux64).conditions_hold(&HashMap::new()));
+        assert!(!task("unix", Platform::Win64).conditions_hold(&HashMap::new()));
+    }
+
+    #[test]
+    fn test_invalid_condition() {
+        let file_content = r#"
+        [project]
+        name = "pixi"
+        channels = ["conda-forge"]
+        platforms = ["linux-64"]
+        [tasks]
+        ci = {cmd="echo ci", only-if="os == 'linux'"}
+    "#;
+        assert!(Manifest::from_str(Path::new(""), file_content.to_string()).is_err());
+    }
 }
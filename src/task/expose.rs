@@ -0,0 +1,248 @@
+//! Tasks with `expose = true` are installed as executables in the `bin` directory of the prefix
+//! (`Scripts` on Windows), so other tools and shells can call them without `pixi run`. Every
+//! executable activates the environment and runs the command of the task with the arguments it
+//! was called with, the variables in the command are replaced like `pixi run` does.
+
+use crate::consts;
+use crate::prefix::Prefix;
+use crate::task::{Task, TemplateVariables};
+use crate::warnings::{self, WarningCode};
+use crate::{trust, Project};
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use rattler_shell::activation::{ActivationVariables, Activator, PathModificationBehavior};
+use rattler_shell::shell::{Shell, ShellEnum, ShellScript};
+use std::path::{Path, PathBuf};
+
+/// The file in the `.pixi` directory of the environment that records the exposed tasks, so the
+/// executables of tasks that are no longer exposed are removed.
+const EXPOSED_TASKS_FILE: &str = "exposed-tasks.json";
+
+/// Installs the exposed tasks of the project as executables in the prefix, and removes the
+/// executables of tasks that are no longer exposed. The `variables` are those of the locked
+/// packages of the environment.
+pub fn expose_tasks(
+    project: &Project,
+    prefix: &Prefix,
+    variables: &TemplateVariables,
+) -> miette::Result<()> {
+    let platform = project.platform();
+    let shell: ShellEnum = if platform.is_windows() {
+        rattler_shell::shell::CmdExe.into()
+    } else {
+        rattler_shell::shell::Bash.into()
+    };
+    let bin_dir = prefix.root().join(if platform.is_windows() {
+        "Scripts"
+    } else {
+        "bin"
+    });
+    let state_dir = prefix.root().join(consts::PIXI_DIR);
+    let record_path = state_dir.join(EXPOSED_TASKS_FILE);
+
+    let previously_exposed = read_exposed_tasks(&record_path);
+    let tasks = project
+        .tasks(Some(platform))
+        .into_iter()
+        .filter(|(_, task)| task.is_exposed())
+        .sorted_by_key(|(name, _)| *name)
+        .collect_vec();
+
+    // Remove the executables of the tasks that are no longer exposed.
+    for name in &previously_exposed {
+        if tasks.iter().all(|(exposed, _)| exposed != name) {
+            let path = executable_path(&bin_dir, &shell, name);
+            if path.is_file() {
+                std::fs::remove_file(&path)
+                    .into_diagnostic()
+                    .wrap_err_with(|| format!("failed to remove '{}'", path.display()))?;
+            }
+        }
+    }
+
+    let mut exposed = Vec::new();
+    if !tasks.is_empty() {
+        let activation_script = activation_script(project, prefix, &shell)?;
+        std::fs::create_dir_all(&bin_dir).into_diagnostic()?;
+        for (name, task) in tasks {
+            let path = executable_path(&bin_dir, &shell, name);
+
+            // Never overwrite an executable that was installed by a package.
+            if path.exists() && !previously_exposed.iter().any(|previous| previous == name) {
+                warnings::emit(
+                    WarningCode::ExposedTaskShadowed,
+                    format!(
+                        "the task '{name}' is not exposed because '{}' already exists in the environment",
+                        path.display()
+                    ),
+                );
+                continue;
+            }
+
+            let script = executable_script(project, task, &shell, &activation_script, variables)?;
+            std::fs::write(&path, script)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to write '{}'", path.display()))?;
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+                    .into_diagnostic()?;
+            }
+            exposed.push(name.to_string());
+        }
+    }
+
+    if exposed.is_empty() {
+        if record_path.is_file() {
+            std::fs::remove_file(&record_path).into_diagnostic()?;
+        }
+    } else {
+        std::fs::create_dir_all(&state_dir).into_diagnostic()?;
+        std::fs::write(
+            &record_path,
+            serde_json::to_string(&exposed).into_diagnostic()?,
+        )
+        .into_diagnostic()?;
+    }
+    Ok(())
+}
+
+/// Returns the names of the tasks that were exposed by a previous installation.
+fn read_exposed_tasks(path: &Path) -> Vec<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Returns the path of the executable of a task.
+fn executable_path(bin_dir: &Path, shell: &ShellEnum, name: &str) -> PathBuf {
+    match shell {
+        ShellEnum::CmdExe(_) => bin_dir.join(format!("{name}.bat")),
+        _ => bin_dir.join(name),
+    }
+}
+
+/// Constructs the script that activates the environment, including the activation scripts of the
+/// project and the environment variables that `pixi run` sets.
+fn activation_script(
+    project: &Project,
+    prefix: &Prefix,
+    shell: &ShellEnum,
+) -> miette::Result<String> {
+    // The executables run the activation scripts of the project without pixi, so they are only
+    // created when the scripts are trusted.
+    trust::ensure_trusted(project, &trust::project_scripts(project)?)?;
+    let project_scripts = project.activation_scripts(project.platform())?;
+
+    let mut activator =
+        Activator::from_path(prefix.root(), shell.clone(), project.platform()).into_diagnostic()?;
+    if !project.package_activation_scripts_enabled() {
+        activator.activation_scripts.clear();
+    }
+    activator.activation_scripts.extend(project_scripts);
+    let activation = activator
+        .activation(ActivationVariables {
+            conda_prefix: None,
+            path: None,
+            path_modification_behavior: PathModificationBehavior::Prepend,
+        })
+        .into_diagnostic()?;
+
+    let mut script = ShellScript::new(shell.clone(), project.platform());
+    for (key, value) in project.get_metadata_env().into_iter().sorted() {
+        script.set_env_var(&key, &value);
+    }
+
+    let header = match shell {
+        ShellEnum::CmdExe(_) => "@echo off\n",
+        // The activation script is written for bash
+        _ => "#!/usr/bin/env bash\n",
+    };
+    Ok(format!("{header}{}{}", activation.script, script.contents))
+}
+
+/// Constructs the executable of a task: the activation followed by the command of the task, which
+/// runs in the working directory of the task if it has one.
+fn executable_script(
+    project: &Project,
+    task: &Task,
+    shell: &ShellEnum,
+    activation_script: &str,
+    variables: &TemplateVariables,
+) -> miette::Result<String> {
+    let Some(command) = task.as_single_command() else {
+        miette::bail!("only tasks with a command can be exposed");
+    };
+    let command = variables.render(&command).into_diagnostic()?;
+
+    let mut script = activation_script.to_string();
+    if let Some(cwd) = task.working_directory() {
+        let cwd = project.root().join(cwd);
+        match shell {
+            ShellEnum::CmdExe(_) => script.push_str(&format!("cd /d \"{}\"\n", cwd.display())),
+            _ => script.push_str(&format!("cd \"{}\" || exit 1\n", cwd.display())),
+        }
+    }
+    let catch_all_arg = match shell {
+        ShellEnum::CmdExe(_) => "%*",
+        _ => "\"$@\"",
+    };
+    shell
+        .run_command(&mut script, [command.as_str(), catch_all_arg])
+        .expect("should never fail");
+    Ok(script)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_expose_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = |expose: bool| {
+            format!(
+                r#"
+                [project]
+                name = "foo"
+                channels = []
+                platforms = ["linux-64", "linux-aarch64", "osx-64", "osx-arm64"]
+
+                [tasks]
+                mycli = {{ cmd = "python{{{{ deps.python.version }}}} -m mypkg", expose = {expose} }}
+                build = "make"
+                "#
+            )
+        };
+        let prefix = Prefix::new(dir.path().join("env")).unwrap();
+        std::fs::create_dir_all(prefix.root().join("conda-meta")).unwrap();
+        let executable = prefix.root().join("bin").join("mycli");
+
+        let variables = TemplateVariables::from_versions([("python", "3.12")]);
+
+        let project = Project::from_str(dir.path(), manifest(true)).unwrap();
+        expose_tasks(&project, &prefix, &variables).unwrap();
+        let script = std::fs::read_to_string(&executable).unwrap();
+        assert!(script.starts_with("#!/usr/bin/env bash\n"));
+        assert!(script.contains("python3.12 -m mypkg \"$@\""));
+        assert!(!prefix.root().join("bin").join("build").exists());
+        assert!(prefix
+            .root()
+            .join(consts::PIXI_DIR)
+            .join(EXPOSED_TASKS_FILE)
+            .is_file());
+        assert!(std::fs::read_dir(prefix.root().join("conda-meta"))
+            .unwrap()
+            .next()
+            .is_none());
+
+        // The executable is removed once the task is no longer exposed.
+        let project = Project::from_str(dir.path(), manifest(false)).unwrap();
+        expose_tasks(&project, &prefix, &variables).unwrap();
+        assert!(!executable.exists());
+    }
+}
@@ -1,17 +1,33 @@
 use itertools::Itertools;
 use serde::Deserialize;
-use serde_with::{formats::PreferMany, serde_as, OneOrMany};
+use serde_with::{formats::PreferMany, serde_as, DisplayFromStr, OneOrMany};
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
 use std::path::{Path, PathBuf};
 
+mod cache;
+mod condition;
 mod executable_task;
+mod expose;
+mod passthrough;
+mod process_group;
+mod remote_cache;
+mod template;
 mod traverse;
 
+pub use cache::{compute_cache_key, CacheKeyInputs, TaskCache};
+pub use condition::{Condition, ConditionContext, ParseConditionError};
+
+pub use expose::expose_tasks;
+pub use passthrough::host_variables;
+
 pub use executable_task::{
     ExecutableTask, FailedToParseShellScript, InvalidWorkingDirectory, RunOutput,
     TaskExecutionError,
 };
+pub use process_group::{ProcessGroup, Signals};
+pub use remote_cache::RemoteTaskCache;
+pub use template::{InvalidTemplate, TemplateVariables};
 pub use traverse::TraversalError;
 
 /// Represents different types of scripts
@@ -98,6 +114,32 @@ impl Task {
         }
     }
 
+    /// Returns true if this task should be executed in a pseudo-terminal.
+    pub fn is_interactive(&self) -> bool {
+        matches!(self, Task::Execute(exe) if exe.interactive)
+    }
+
+    /// Returns true if this task is installed as an executable in the environment.
+    pub fn is_exposed(&self) -> bool {
+        matches!(self, Task::Execute(exe) if exe.expose)
+    }
+
+    /// Returns true if the `skip-if` and `only-if` conditions of this task allow it to run.
+    pub fn conditions_hold(&self, context: &ConditionContext) -> bool {
+        match self {
+            Task::Execute(exe) => {
+                exe.only_if
+                    .as_ref()
+                    .map_or(true, |condition| condition.evaluate(context))
+                    && !exe
+                        .skip_if
+                        .as_ref()
+                        .map_or(false, |condition| condition.evaluate(context))
+            }
+            _ => true,
+        }
+    }
+
     /// True if this task is a custom task instead of something defined in a project.
     pub fn is_custom(&self) -> bool {
         matches!(self, Task::Custom(_))
@@ -120,6 +162,47 @@ pub struct Execute {
 
     /// The working directory for the command relative to the root of the project.
     pub cwd: Option<PathBuf>,
+
+    /// Files or directories (relative to the working directory) whose contents are part of the
+    /// cache key of the task.
+    #[serde(default)]
+    pub inputs: Vec<PathBuf>,
+
+    /// Files or directories (relative to the working directory) that are produced by the task.
+    /// The task is skipped if its cache key did not change and all outputs exist.
+    #[serde(default)]
+    pub outputs: Vec<PathBuf>,
+
+    /// The names of the environment variables whose values are part of the cache key of the task.
+    #[serde(default, rename = "cache-env", alias = "cache_env")]
+    pub cache_env: Vec<String>,
+
+    /// Whether the task is executed in a pseudo-terminal, for tools that need a terminal to behave
+    /// correctly (e.g. interactive shells or progress bars).
+    #[serde(default)]
+    pub interactive: bool,
+
+    /// Whether the task is installed as an executable with the name of the task in the `bin`
+    /// directory of the environment, so it can be called without `pixi run`.
+    #[serde(default)]
+    pub expose: bool,
+
+    /// The task and the tasks it depends on are skipped if this condition holds.
+    #[serde(default, rename = "skip-if")]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub skip_if: Option<Condition>,
+
+    /// The task and the tasks it depends on are only run if this condition holds.
+    #[serde(default, rename = "only-if")]
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    pub only_if: Option<Condition>,
+}
+
+impl Execute {
+    /// Returns true if the results of this task are cached.
+    pub fn is_cached(&self) -> bool {
+        !self.inputs.is_empty() || !self.outputs.is_empty() || !self.cache_env.is_empty()
+    }
 }
 
 impl From<Execute> for Task {
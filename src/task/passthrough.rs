@@ -0,0 +1,65 @@
+//! The environment variables of the host that reach the tasks when they run in a clean
+//! environment, with `pixi run --clean-env`. Only the variables that the `passthrough-env` of the
+//! project allows are passed on, next to the few that processes need to work at all, so the tasks
+//! don't depend on how the machine that runs them happens to be configured.
+
+use crate::utils::glob::glob_matches;
+
+/// The variables that are always passed on, processes fail in surprising ways without them.
+#[cfg(unix)]
+const ESSENTIAL_VARIABLES: &[&str] = &["PATH", "TERM", "TMPDIR"];
+
+/// The variables that are always passed on, processes fail in surprising ways without them.
+#[cfg(not(unix))]
+const ESSENTIAL_VARIABLES: &[&str] = &[
+    "PATH",
+    "PATHEXT",
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "TEMP",
+    "TMP",
+];
+
+/// Returns the environment variables of the host that reach the tasks. Without `clean_env` that
+/// are all of them, otherwise only the essential ones and those that match one of the `patterns`.
+pub fn host_variables(clean_env: bool, patterns: &[String]) -> Vec<(String, String)> {
+    std::env::vars()
+        .filter(|(name, _)| !clean_env || is_passed_through(name, patterns))
+        .collect()
+}
+
+/// Returns true if the variable is essential or matches one of the `patterns`, e.g. `AWS_*`. The
+/// names of variables are case insensitive on Windows.
+pub fn is_passed_through(name: &str, patterns: &[String]) -> bool {
+    let name = normalize(name);
+    ESSENTIAL_VARIABLES
+        .iter()
+        .copied()
+        .chain(patterns.iter().map(String::as_str))
+        .any(|pattern| glob_matches(&normalize(pattern), &name))
+}
+
+fn normalize(name: &str) -> String {
+    if cfg!(windows) {
+        name.to_uppercase()
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_passed_through() {
+        let patterns = vec![String::from("SSH_AUTH_SOCK"), String::from("AWS_*")];
+        assert!(is_passed_through("PATH", &[]));
+        assert!(is_passed_through("SSH_AUTH_SOCK", &patterns));
+        assert!(is_passed_through("AWS_SECRET_ACCESS_KEY", &patterns));
+        assert!(!is_passed_through("GITHUB_TOKEN", &patterns));
+        assert_eq!(is_passed_through("aws_profile", &patterns), cfg!(windows));
+    }
+}
@@ -0,0 +1,285 @@
+//! Management of the processes spawned by tasks.
+//!
+//! On unix `pixi run` moves itself into a new process group before it executes tasks, so that all
+//! the processes spawned by the tasks end up in that group. This only happens when pixi runs in a
+//! terminal, without one (e.g. in CI or a pipeline) the process groups are left to the caller. If
+//! the terminal is controlled by the group pixi was started in, the new group becomes the
+//! foreground group of the terminal. Signals that are sent to pixi (`SIGINT`, `SIGTERM`, `SIGHUP`)
+//! are forwarded to the whole group, and when a task is cancelled any process left behind in the
+//! group is killed after pixi leaves it again.
+//!
+//! On Windows pixi assigns itself to a job object that kills all processes in the job when pixi
+//! exits. Ctrl+C is delivered to every process attached to the console by Windows itself, so it is
+//! not forwarded.
+
+use std::cell::Cell;
+
+/// The process group (or job object on Windows) that contains the processes spawned by tasks.
+/// Dropping it restores the original process group of pixi.
+pub struct ProcessGroup {
+    /// Set when a signal was forwarded to the group, in which case the processes that remain in
+    /// the group are killed when the group is dropped.
+    cancelled: Cell<bool>,
+
+    #[cfg(unix)]
+    inner: unix::ProcessGroup,
+}
+
+impl ProcessGroup {
+    /// Moves pixi into a new process group (or job object). Failures are not fatal, tasks are
+    /// still executed but signals are not forwarded.
+    pub fn enter() -> Self {
+        #[cfg(windows)]
+        if let Err(err) = windows::assign_current_process_to_job() {
+            tracing::debug!("failed to create a job object: {err}");
+        }
+
+        Self {
+            cancelled: Cell::new(false),
+            #[cfg(unix)]
+            inner: unix::ProcessGroup::enter(),
+        }
+    }
+
+    /// Waits for a signal that is sent to pixi and forwards it to the processes in the group.
+    /// Returns the number of the signal. This function is cancel-safe.
+    pub async fn forward_signal(&self, signals: &mut Signals) -> i32 {
+        let signal = signals.recv().await;
+
+        #[cfg(unix)]
+        self.inner.forward(signal);
+
+        self.cancelled.set(true);
+        signal
+    }
+}
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        self.inner.leave(self.cancelled.get());
+    }
+}
+
+/// The signals that pixi listens for while a task is running. Once created the default handlers
+/// of these signals are replaced for the remainder of the program, so pixi itself is not
+/// terminated by them.
+pub struct Signals {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+}
+
+impl Signals {
+    /// Starts listening for signals.
+    pub fn new() -> std::io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Self {
+                interrupt: signal(SignalKind::interrupt())?,
+                terminate: signal(SignalKind::terminate())?,
+                hangup: signal(SignalKind::hangup())?,
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(Self {})
+        }
+    }
+
+    /// Waits for the next signal and returns its number.
+    async fn recv(&mut self) -> i32 {
+        #[cfg(unix)]
+        {
+            tokio::select! {
+                _ = self.interrupt.recv() => libc::SIGINT,
+                _ = self.terminate.recv() => libc::SIGTERM,
+                _ = self.hangup.recv() => libc::SIGHUP,
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            while tokio::signal::ctrl_c().await.is_err() {}
+            2
+        }
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use nix::sys::signal::{self, killpg, SigHandler, Signal};
+    use nix::unistd::{getpgrp, getpid, setpgid, tcgetpgrp, tcsetpgrp, Pid};
+    use std::io::IsTerminal;
+
+    pub struct ProcessGroup {
+        /// The process group pixi was started in.
+        original: Pid,
+
+        /// The process group that contains the processes of the tasks, `None` if pixi could not
+        /// be moved into a group of its own.
+        group: Option<Pid>,
+
+        /// True if the foreground group of the terminal was changed to `group`.
+        foreground: bool,
+    }
+
+    impl ProcessGroup {
+        pub fn enter() -> Self {
+            let original = getpgrp();
+            let pid = getpid();
+
+            // If pixi already leads its own group (e.g. when started as a job of an interactive
+            // shell) there is no need to create a new one.
+            if original == pid {
+                return Self {
+                    original,
+                    group: Some(pid),
+                    foreground: false,
+                };
+            }
+
+            // Without a terminal the caller manages the processes, e.g. a CI runner that kills
+            // the process group of the job, so pixi stays in the group it was started in.
+            if !std::io::stdin().is_terminal() {
+                return Self {
+                    original,
+                    group: None,
+                    foreground: false,
+                };
+            }
+
+            if let Err(err) = setpgid(Pid::from_raw(0), Pid::from_raw(0)) {
+                tracing::debug!("failed to create a process group: {err}");
+                return Self {
+                    original,
+                    group: None,
+                    foreground: false,
+                };
+            }
+
+            // Hand the terminal to the new group if the original group controlled it, otherwise
+            // the processes of the task would be stopped when they read from the terminal.
+            let foreground = tcgetpgrp(std::io::stdin()).map_or(false, |fg| fg == original)
+                && set_foreground(pid);
+
+            Self {
+                original,
+                group: Some(pid),
+                foreground,
+            }
+        }
+
+        /// Forwards a signal to the group.
+        pub fn forward(&self, signal: i32) {
+            let Some(group) = self.group else {
+                return;
+            };
+            let Ok(signal) = Signal::try_from(signal) else {
+                return;
+            };
+
+            // The terminal already delivers SIGINT to all processes in its foreground group,
+            // forwarding it would deliver it twice.
+            if signal == Signal::SIGINT
+                && tcgetpgrp(std::io::stdin()).map_or(false, |fg| fg == group)
+            {
+                return;
+            }
+
+            if let Err(err) = killpg(group, signal) {
+                tracing::debug!("failed to forward {signal} to the task processes: {err}");
+            }
+        }
+
+        /// Moves pixi back into its original group. If the task was cancelled, the processes that
+        /// are left in the group are killed.
+        pub fn leave(&self, cancelled: bool) {
+            let Some(group) = self.group else {
+                return;
+            };
+            if self.foreground {
+                set_foreground(self.original);
+            }
+            if group == self.original {
+                // pixi leads the group itself, so the remaining processes can only be asked to
+                // terminate.
+                if cancelled {
+                    let _ = killpg(group, Signal::SIGTERM);
+                }
+                return;
+            }
+            if let Err(err) = setpgid(Pid::from_raw(0), self.original) {
+                tracing::debug!("failed to restore the process group: {err}");
+                return;
+            }
+            if cancelled {
+                let _ = killpg(group, Signal::SIGKILL);
+            }
+        }
+    }
+
+    /// Makes `group` the foreground process group of the terminal. Returns true on success.
+    fn set_foreground(group: Pid) -> bool {
+        // A process that is not in the foreground group receives SIGTTOU when it changes the
+        // foreground group, so it is ignored while doing so.
+        // SAFETY: no other handler for SIGTTOU is installed by pixi.
+        let previous = unsafe { signal::signal(Signal::SIGTTOU, SigHandler::SigIgn) };
+        let result = tcsetpgrp(std::io::stdin(), group);
+        if let Ok(previous) = previous {
+            // SAFETY: restores the handler that was installed before.
+            let _ = unsafe { signal::signal(Signal::SIGTTOU, previous) };
+        }
+        match result {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::debug!("failed to change the foreground process group: {err}");
+                false
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use windows_sys::Win32::System::{
+        JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+            SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+            JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        },
+        Threading::GetCurrentProcess,
+    };
+
+    /// Assigns pixi to a job object that kills all its processes when the last handle to it is
+    /// closed. The handle is intentionally never closed, closing it would kill pixi itself, so
+    /// the system closes it when pixi exits.
+    pub fn assign_current_process_to_job() -> std::io::Result<()> {
+        // SAFETY: all pointers passed to the functions are valid for the duration of the calls.
+        unsafe {
+            let job = CreateJobObjectW(std::ptr::null(), std::ptr::null());
+            if job == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = std::mem::zeroed();
+            info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+            if SetInformationJobObject(
+                job,
+                JobObjectExtendedLimitInformation,
+                &info as *const _ as *const _,
+                std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            ) == 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+            if AssignProcessToJobObject(job, GetCurrentProcess()) == 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! A remote cache for the outputs of tasks.
+//!
+//! When a `task-cache` is configured (see [`crate::config::RemoteTaskCacheConfig`]) the outputs of
+//! a task are looked up in the cache by the cache key of the task before it is executed. If they
+//! are found they are extracted into the working directory of the task instead of running it.
+//! Machines that are allowed to upload (e.g. CI) store the outputs of the tasks they execute.
+//!
+//! The outputs are stored as an uncompressed tarball at `<url>/<platform>/<cache key>.tar`, so any
+//! HTTP server that supports `GET` and `PUT` can be used, including S3 compatible object stores.
+
+use crate::{auth, config::RemoteTaskCacheConfig, default_authenticated_client};
+use miette::{Context, IntoDiagnostic};
+use rattler_conda_types::Platform;
+use rattler_networking::AuthenticatedClient;
+use reqwest::{Method, StatusCode};
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// A remote cache for the outputs of tasks.
+pub struct RemoteTaskCache {
+    url: Url,
+    upload: bool,
+    client: AuthenticatedClient,
+}
+
+impl RemoteTaskCache {
+    /// Constructs the remote cache from its configuration.
+    pub fn new(config: &RemoteTaskCacheConfig) -> Self {
+        // Make sure the url is treated as a directory when joining.
+        let mut url = config.url.clone();
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+        Self {
+            url,
+            upload: config.upload,
+            client: default_authenticated_client(),
+        }
+    }
+
+    /// Returns true if outputs should be uploaded to the cache.
+    pub fn upload_enabled(&self) -> bool {
+        self.upload
+    }
+
+    /// Returns the url of the outputs of the task with the given cache key.
+    fn artifact_url(&self, key: &str) -> miette::Result<Url> {
+        self.url
+            .join(&format!("{}/{key}.tar", Platform::current()))
+            .into_diagnostic()
+    }
+
+    /// Downloads the outputs of the task with the given cache key and extracts them into `cwd`.
+    /// Returns `false` if the cache does not contain the outputs.
+    pub async fn download(&self, key: &str, cwd: &Path) -> miette::Result<bool> {
+        let url = self.artifact_url(key)?;
+        let response = auth::authenticate_request(self.client.get(url.clone()), &url)
+            .send()
+            .await
+            .into_diagnostic()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        let bytes = response
+            .error_for_status()
+            .into_diagnostic()?
+            .bytes()
+            .await
+            .into_diagnostic()?;
+
+        let cwd = cwd.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            tar::Archive::new(std::io::Cursor::new(bytes)).unpack(&cwd)
+        })
+        .await
+        .into_diagnostic()?
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to extract the outputs downloaded from {url}"))?;
+        Ok(true)
+    }
+
+    /// Uploads the `outputs` (relative to `cwd`) of the task with the given cache key.
+    pub async fn upload(&self, key: &str, cwd: &Path, outputs: &[PathBuf]) -> miette::Result<()> {
+        let url = self.artifact_url(key)?;
+        let cwd = cwd.to_path_buf();
+        let outputs = outputs.to_vec();
+        let archive = tokio::task::spawn_blocking(move || archive_outputs(&cwd, &outputs))
+            .await
+            .into_diagnostic()?
+            .into_diagnostic()
+            .wrap_err("failed to archive the outputs of the task")?;
+
+        auth::authenticate_request(self.client.request(Method::PUT, url.clone()), &url)
+            .header("Content-Type", "application/x-tar")
+            .body(archive)
+            .send()
+            .await
+            .into_diagnostic()?
+            .error_for_status()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("failed to upload the outputs to {url}"))?;
+        Ok(())
+    }
+}
+
+/// Creates an uncompressed tarball that contains the `outputs` with paths relative to `cwd`.
+fn archive_outputs(cwd: &Path, outputs: &[PathBuf]) -> std::io::Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.follow_symlinks(false);
+    for output in outputs {
+        let path = cwd.join(output);
+        if path.is_dir() {
+            builder.append_dir_all(output, &path)?;
+        } else {
+            builder.append_path_with_name(&path, output)?;
+        }
+    }
+    builder.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_outputs() {
+        let source = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(source.path().join("site").join("css")).unwrap();
+        std::fs::write(source.path().join("site").join("index.html"), "<html/>").unwrap();
+        std::fs::write(source.path().join("site").join("css").join("a.css"), "").unwrap();
+        std::fs::write(source.path().join("app"), "binary").unwrap();
+
+        let archive = archive_outputs(
+            source.path(),
+            &[PathBuf::from("site"), PathBuf::from("app")],
+        )
+        .unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        tar::Archive::new(std::io::Cursor::new(archive))
+            .unpack(target.path())
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(target.path().join("site").join("index.html")).unwrap(),
+            "<html/>"
+        );
+        assert!(target
+            .path()
+            .join("site")
+            .join("css")
+            .join("a.css")
+            .is_file());
+        assert_eq!(
+            std::fs::read_to_string(target.path().join("app")).unwrap(),
+            "binary"
+        );
+    }
+
+    #[test]
+    fn test_artifact_url() {
+        let cache = RemoteTaskCache::new(&RemoteTaskCacheConfig {
+            url: Url::parse("https://cache.example.com/pixi").unwrap(),
+            upload: false,
+        });
+        assert_eq!(
+            cache.artifact_url("abc").unwrap().as_str(),
+            format!(
+                "https://cache.example.com/pixi/{}/abc.tar",
+                Platform::current()
+            )
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Variables that can be used in the command of a task, e.g. `{{ deps.python.version }}` is
+//! replaced by the version of python that is locked for the platform the task runs on.
+
+use crate::utils::spec_format::normalize_pypi_name;
+use rattler_conda_types::Platform;
+use rattler_lock::{CondaLock, LockedDependencyKind};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// An error that occurred while replacing the variables in a command.
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+#[error("invalid variable '{{{{ {variable} }}}}' in '{command}': {reason}")]
+pub struct InvalidTemplate {
+    pub command: String,
+    pub variable: String,
+    pub reason: String,
+}
+
+/// The values of the variables that can be used in the commands of tasks.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateVariables {
+    /// The versions of the locked packages by their normalized name.
+    versions: HashMap<String, String>,
+}
+
+impl TemplateVariables {
+    /// Creates the variables from the packages in the lock file for `platform`. When a conda and a
+    /// PyPI package have the same name, the conda package is used.
+    pub fn from_lock_file(lock_file: &CondaLock, platform: Platform) -> Self {
+        let mut versions = HashMap::new();
+        let (conda, pypi): (Vec<_>, Vec<_>) = lock_file
+            .get_packages_by_platform(platform)
+            .partition(|package| matches!(package.kind, LockedDependencyKind::Conda(_)));
+        for package in conda.into_iter().chain(pypi) {
+            versions
+                .entry(normalize_pypi_name(&package.name))
+                .or_insert_with(|| package.version.clone());
+        }
+        Self { versions }
+    }
+
+    /// Creates the variables from the versions of packages by their name.
+    pub fn from_versions<'a>(versions: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        Self {
+            versions: versions
+                .into_iter()
+                .map(|(name, version)| (normalize_pypi_name(name), version.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Returns the version of the package in a `deps.<package>.version` variable.
+    fn resolve(&self, variable: &str) -> Result<&str, String> {
+        let mut parts = variable.split('.');
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some("deps"), Some(name), Some("version"), None) if !name.is_empty() => self
+                .versions
+                .get(&normalize_pypi_name(name))
+                .map(String::as_str)
+                .ok_or_else(|| format!("'{name}' is not a package in the environment of the task")),
+            _ => Err(String::from(
+                "only `deps.<package>.version` variables are supported",
+            )),
+        }
+    }
+
+    /// Replaces every `{{ deps.<package>.version }}` in `command` with the version of the package.
+    /// Other text between double braces is left as is, because commands like `docker inspect
+    /// --format '{{.Id}}'` use them for their own templates.
+    pub fn render(&self, command: &str) -> Result<String, InvalidTemplate> {
+        let mut rendered = String::with_capacity(command.len());
+        let mut rest = command;
+        while let Some(start) = rest.find("{{") {
+            let after = &rest[start + 2..];
+            let variable = after
+                .find("}}")
+                .map(|end| (after[..end].trim(), end))
+                .filter(|(variable, _)| variable.starts_with("deps."));
+            let Some((variable, end)) = variable else {
+                rendered.push_str(&rest[..start + 2]);
+                rest = after;
+                continue;
+            };
+            let value = self.resolve(variable).map_err(|reason| InvalidTemplate {
+                command: command.to_string(),
+                variable: variable.to_string(),
+                reason,
+            })?;
+            rendered.push_str(&rest[..start]);
+            rendered.push_str(value);
+            rest = &after[end + 2..];
+        }
+        rendered.push_str(rest);
+        Ok(rendered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_render() {
+        let variables = TemplateVariables::from_versions([
+            ("python", "3.12.1"),
+            ("typing_extensions", "4.9.0"),
+        ]);
+        assert_eq!(
+            variables
+                .render("docker build -t app:py{{ deps.python.version }} .")
+                .unwrap(),
+            "docker build -t app:py3.12.1 ."
+        );
+        assert_eq!(
+            variables
+                .render("echo {{deps.typing-extensions.version}}")
+                .unwrap(),
+            "echo 4.9.0"
+        );
+        for command in [
+            "echo no variables",
+            "docker inspect --format '{{.Id}}' app",
+            "echo {{ deps.python.version",
+        ] {
+            assert_eq!(variables.render(command).unwrap(), command);
+        }
+
+        for command in [
+            "echo {{ deps.numpy.version }}",
+            "echo {{ deps.python.build }}",
+            "echo {{ deps.python }}",
+        ] {
+            assert!(
+                variables.render(command).is_err(),
+                "'{command}' should fail"
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_lock_file() {
+        let lock_file = crate::lock_file::read_lock_file(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/cpp-sdl/pixi.lock"),
+        )
+        .unwrap();
+        let variables = TemplateVariables::from_lock_file(&lock_file, Platform::Linux64);
+        let sdl2 = lock_file
+            .get_packages_by_platform(Platform::Linux64)
+            .find(|package| package.name == "sdl2")
+            .unwrap();
+        assert_eq!(
+            variables.render("{{ deps.sdl2.version }}").unwrap(),
+            sdl2.version
+        );
+    }
+}
@@ -108,6 +108,7 @@ impl<'p> ExecutableTask<'p> {
                         task: Cow::Borrowed(dependency),
                         additional_args: Vec::new(),
                         platform: task.platform,
+                        variables: task.variables.clone(),
                     },
                     visited,
                     visit,
@@ -0,0 +1,23 @@
+//! Fixtures that are shared by the tests of several modules.
+
+use rattler_conda_types::{PackageName, PackageRecord, RepoDataRecord, Version};
+use std::str::FromStr;
+
+/// The url of the conda-forge channel.
+pub const CONDA_FORGE: &str = "https://conda.anaconda.org/conda-forge/";
+
+/// Returns a `linux-64` record of the package `name` at `version` in the channel with the url
+/// `channel`, with the build string `0` and without dependencies.
+pub fn repodata_record(name: &str, version: &str, channel: &str) -> RepoDataRecord {
+    let file_name = format!("{name}-{version}-0.conda");
+    RepoDataRecord {
+        url: url::Url::parse(&format!("{channel}linux-64/{file_name}")).unwrap(),
+        channel: channel.to_string(),
+        file_name,
+        package_record: PackageRecord::new(
+            PackageName::new_unchecked(name),
+            Version::from_str(version).unwrap(),
+            String::from("0"),
+        ),
+    }
+}
@@ -0,0 +1,329 @@
+//! Expiry and rotation of the tokens that `pixi auth login` stores. The keyring only stores the
+//! credentials, so pixi keeps the metadata of a token next to it in `~/.pixi/auth-metadata.json`:
+//! when the token expires and, for services that support it, the url that issues a new token.
+//!
+//! Pixi warns when a token that is used for a channel expired or expires soon, so an expired token
+//! is noticed before requests start to fail with `401 Unauthorized` halfway through a CI run.
+//! `pixi auth rotate` replaces the token, either with a new token that is passed to it or by
+//! requesting one from the rotation url.
+
+use crate::auth::{self, AuthenticationSource};
+use crate::consts;
+use crate::warnings::{self, WarningCode};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use miette::{Context, IntoDiagnostic};
+use rattler_networking::Authentication;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// The name of the file in `~/.pixi` that stores the metadata of the tokens.
+const METADATA_FILE: &str = "auth-metadata.json";
+
+/// Pixi warns about tokens that expire within this many days.
+const EXPIRY_WARNING_DAYS: i64 = 7;
+
+/// The metadata of the token that is stored for a host.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenMetadata {
+    /// When the token expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// The url that issues a new token, see [`rotate_token`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate_url: Option<Url>,
+}
+
+/// Whether a token is still valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    /// The token does not expire soon, or its expiry is unknown.
+    Valid,
+    /// The token expires within [`EXPIRY_WARNING_DAYS`], after the given duration.
+    ExpiresSoon(Duration),
+    /// The token expired.
+    Expired,
+}
+
+impl TokenMetadata {
+    /// Returns whether the token is still valid at `now`.
+    pub fn expiry(&self, now: DateTime<Utc>) -> Expiry {
+        match self.expires_at {
+            Some(expires_at) if expires_at <= now => Expiry::Expired,
+            Some(expires_at) if expires_at - now <= Duration::days(EXPIRY_WARNING_DAYS) => {
+                Expiry::ExpiresSoon(expires_at - now)
+            }
+            _ => Expiry::Valid,
+        }
+    }
+}
+
+/// The metadata of the stored tokens, by the host under which `pixi auth login` stored them.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TokenMetadataStore {
+    hosts: BTreeMap<String, TokenMetadata>,
+}
+
+impl TokenMetadataStore {
+    /// Loads the metadata from `path`, there is none if the file does not exist.
+    pub fn from_path(path: &Path) -> miette::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to parse '{}'", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).into_diagnostic(),
+        }
+    }
+
+    /// Loads the metadata from `~/.pixi/auth-metadata.json`.
+    pub fn load() -> miette::Result<Self> {
+        Self::from_path(&metadata_store_path()?)
+    }
+
+    /// Writes the metadata to `path`.
+    pub fn write(&self, path: &Path) -> miette::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).into_diagnostic()?;
+        }
+        std::fs::write(
+            path,
+            serde_json::to_string_pretty(self).into_diagnostic()? + "\n",
+        )
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write '{}'", path.display()))
+    }
+
+    /// Writes the metadata to `~/.pixi/auth-metadata.json`.
+    pub fn save(&self) -> miette::Result<()> {
+        self.write(&metadata_store_path()?)
+    }
+
+    /// Returns the metadata stored under exactly `host`, e.g. `*.prefix.dev`.
+    pub fn get(&self, host: &str) -> Option<&TokenMetadata> {
+        self.hosts.get(host)
+    }
+
+    /// Returns the metadata of the token that is used for requests to `host`, looked up like the
+    /// keyring does: the host itself and the wildcard of its parent domain. Also returns the host
+    /// under which the metadata is stored.
+    pub fn lookup(&self, host: &str) -> Option<(&str, &TokenMetadata)> {
+        let wildcard = host
+            .split_once('.')
+            .map(|(_, parent)| format!("*.{parent}"));
+        std::iter::once(host.to_string())
+            .chain(wildcard)
+            .find_map(|host| self.hosts.get_key_value(&host))
+            .map(|(host, metadata)| (host.as_str(), metadata))
+    }
+
+    /// Stores the metadata for `host`. Metadata without any information is removed instead.
+    pub fn set(&mut self, host: String, metadata: TokenMetadata) {
+        if metadata == TokenMetadata::default() {
+            self.hosts.remove(&host);
+        } else {
+            self.hosts.insert(host, metadata);
+        }
+    }
+
+    /// Forgets the metadata of `host`, returns false if there was none.
+    pub fn remove(&mut self, host: &str) -> bool {
+        self.hosts.remove(host).is_some()
+    }
+}
+
+/// Returns the path of the file that stores the metadata of the tokens.
+fn metadata_store_path() -> miette::Result<PathBuf> {
+    dirs::home_dir()
+        .map(|home| home.join(consts::PIXI_DIR).join(METADATA_FILE))
+        .ok_or_else(|| miette::miette!("could not determine the home directory"))
+}
+
+/// Parses the expiry of a token, either a RFC 3339 timestamp (`2024-06-30T12:00:00Z`) or a date
+/// (`2024-06-30`), which means the start of that day in UTC.
+pub fn parse_expiry(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|timestamp| Utc.from_utc_datetime(&timestamp))
+        .ok_or_else(|| format!("'{value}' is not a date (YYYY-MM-DD) or a RFC 3339 timestamp"))
+}
+
+/// Formats the remaining time of a token, e.g. `3 days` or `5 hours`.
+fn format_remaining(remaining: Duration) -> String {
+    match (remaining.num_days(), remaining.num_hours()) {
+        (1, _) => String::from("1 day"),
+        (days, _) if days > 1 => format!("{days} days"),
+        (_, 1) => String::from("1 hour"),
+        (_, hours) if hours > 1 => format!("{hours} hours"),
+        _ => String::from("less than an hour"),
+    }
+}
+
+/// Returns the warning for the token of `host`, if it expired or expires soon.
+pub fn expiry_warning(host: &str, metadata: &TokenMetadata, now: DateTime<Utc>) -> Option<String> {
+    let expires_at = metadata.expires_at?.format("%Y-%m-%d %H:%M UTC");
+    match metadata.expiry(now) {
+        Expiry::Valid => None,
+        Expiry::ExpiresSoon(remaining) => Some(format!(
+            "the token for {host} expires in {} ({expires_at}), run `pixi auth rotate {host}` to replace it",
+            format_remaining(remaining)
+        )),
+        Expiry::Expired => Some(format!(
+            "the token for {host} expired on {expires_at}, run `pixi auth rotate {host}` to replace it"
+        )),
+    }
+}
+
+/// Warns about the tokens for the hosts of `urls` that expired or expire soon. Only tokens that are
+/// actually used, i.e. that are not overridden by credentials from the environment, are reported.
+pub fn warn_expiring_tokens<'a>(urls: impl IntoIterator<Item = &'a Url>) {
+    let store = match TokenMetadataStore::load() {
+        Ok(store) => store,
+        Err(err) => {
+            tracing::debug!("failed to read the metadata of the stored tokens: {err}");
+            return;
+        }
+    };
+    if store.hosts.is_empty() {
+        return;
+    }
+
+    let now = Utc::now();
+    let mut reported = Vec::new();
+    for host in urls.into_iter().filter_map(|url| url.host_str()) {
+        let Some((stored_host, metadata)) = store.lookup(host) else {
+            continue;
+        };
+        if reported.contains(&stored_host) {
+            continue;
+        }
+        reported.push(stored_host);
+        let Some(warning) = expiry_warning(stored_host, metadata, now) else {
+            continue;
+        };
+        if matches!(
+            auth::resolve_authentication(host),
+            Some((_, AuthenticationSource::Keyring))
+        ) {
+            warnings::emit(WarningCode::TokenExpiry, warning);
+        }
+    }
+}
+
+/// The response of a rotation url.
+#[derive(Debug, Deserialize)]
+pub struct RotatedToken {
+    /// The new token.
+    pub token: String,
+    /// When the new token expires.
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Requests a new token from the rotation url of a service. The current token is sent as a bearer
+/// token in a `POST` request, and the service responds with a JSON object with the new `token` and
+/// optionally its `expires_at` as a RFC 3339 timestamp. The service is expected to revoke the
+/// current token.
+pub async fn rotate_token(rotate_url: &Url, auth: &Authentication) -> miette::Result<RotatedToken> {
+    let current_token = match auth {
+        Authentication::BearerToken(token) | Authentication::CondaToken(token) => token,
+        Authentication::BasicHTTP { .. } => {
+            miette::bail!("only tokens can be rotated, not basic HTTP credentials")
+        }
+    };
+    crate::default_client()
+        .post(rotate_url.clone())
+        .bearer_auth(current_token)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to request a new token from {rotate_url}"))?
+        .json()
+        .await
+        .into_diagnostic()
+        .wrap_err_with(|| format!("{rotate_url} did not respond with a new token"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(expires_at: Option<DateTime<Utc>>) -> TokenMetadata {
+        TokenMetadata {
+            expires_at,
+            rotate_url: None,
+        }
+    }
+
+    #[test]
+    fn test_expiry() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap();
+        assert_eq!(metadata(None).expiry(now), Expiry::Valid);
+        assert_eq!(
+            metadata(Some(now + Duration::days(30))).expiry(now),
+            Expiry::Valid
+        );
+        assert_eq!(
+            metadata(Some(now + Duration::days(3))).expiry(now),
+            Expiry::ExpiresSoon(Duration::days(3))
+        );
+        assert_eq!(metadata(Some(now)).expiry(now), Expiry::Expired);
+
+        assert_eq!(
+            expiry_warning(
+                "repo.prefix.dev",
+                &metadata(Some(now + Duration::days(3))),
+                now
+            )
+            .unwrap(),
+            "the token for repo.prefix.dev expires in 3 days (2024-06-04 12:00 UTC), run `pixi auth rotate repo.prefix.dev` to replace it"
+        );
+        assert!(expiry_warning("repo.prefix.dev", &metadata(None), now).is_none());
+        assert_eq!(format_remaining(Duration::minutes(90)), "1 hour");
+        assert_eq!(format_remaining(Duration::minutes(5)), "less than an hour");
+    }
+
+    #[test]
+    fn test_parse_expiry() {
+        assert_eq!(
+            parse_expiry("2024-06-30").unwrap(),
+            Utc.with_ymd_and_hms(2024, 6, 30, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            parse_expiry("2024-06-30T12:00:00+02:00").unwrap(),
+            Utc.with_ymd_and_hms(2024, 6, 30, 10, 0, 0).unwrap()
+        );
+        assert!(parse_expiry("next week").is_err());
+    }
+
+    #[test]
+    fn test_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(METADATA_FILE);
+        let expires_at = Utc.with_ymd_and_hms(2024, 6, 30, 0, 0, 0).unwrap();
+
+        let mut store = TokenMetadataStore::from_path(&path).unwrap();
+        store.set(String::from("*.prefix.dev"), metadata(Some(expires_at)));
+        store.set(String::from("example.com"), TokenMetadata::default());
+        store.write(&path).unwrap();
+
+        let store = TokenMetadataStore::from_path(&path).unwrap();
+        assert_eq!(
+            store.lookup("repo.prefix.dev"),
+            Some(("*.prefix.dev", &metadata(Some(expires_at))))
+        );
+        assert_eq!(store.lookup("example.com"), None);
+
+        let rotated: RotatedToken =
+            serde_json::from_str(r#"{ "token": "new", "expires_at": "2024-06-30T00:00:00Z" }"#)
+                .unwrap();
+        assert_eq!(rotated.token, "new");
+        assert_eq!(rotated.expires_at, Some(expires_at));
+    }
+}
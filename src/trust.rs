@@ -0,0 +1,301 @@
+//! The activation scripts of a project run arbitrary code whenever the environment is activated, so
+//! a freshly cloned project has to be trusted before they run for the first time, like
+//! `direnv allow`. The decisions are stored per project, keyed by a hash of the path of its
+//! manifest, together with a digest of the activation scripts. When a script changes, the project
+//! has to be trusted again. The deactivation scripts, which run when `pixi shell` exits, are
+//! trusted together with the activation scripts.
+
+use crate::config::Config;
+use crate::{ci, consts, Project};
+use itertools::Itertools;
+use miette::{Context, IntoDiagnostic};
+use rattler_digest::{compute_bytes_digest, Sha256};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The name of the file in `~/.pixi` that stores the trust decisions.
+const TRUST_FILE: &str = "trusted-projects.json";
+
+/// The decision of the user about a project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustDecision {
+    /// The manifest of the project.
+    pub manifest_path: PathBuf,
+    /// The digest of the activation scripts the decision was made for.
+    pub digest: String,
+    /// Whether the scripts are allowed to run.
+    pub trusted: bool,
+}
+
+/// Whether the activation scripts of a project are allowed to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustState {
+    /// The user trusted the current activation scripts.
+    Trusted,
+    /// The user denied the current activation scripts.
+    Denied,
+    /// The user made a decision for activation scripts that have changed since.
+    Changed,
+    /// The user never made a decision for the project.
+    Unknown,
+}
+
+/// The trust decisions of all projects, by the key of the project.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TrustStore {
+    projects: BTreeMap<String, TrustDecision>,
+}
+
+impl TrustStore {
+    /// Loads the decisions from `path`, there are none if the file does not exist.
+    pub fn from_path(path: &Path) -> miette::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to parse '{}'", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).into_diagnostic(),
+        }
+    }
+
+    /// Loads the decisions from `~/.pixi/trusted-projects.json`.
+    pub fn load() -> miette::Result<Self> {
+        Self::from_path(&trust_store_path()?)
+    }
+
+    /// Writes the decisions to `path`.
+    pub fn write(&self, path: &Path) -> miette::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).into_diagnostic()?;
+        }
+        std::fs::write(
+            path,
+            serde_json::to_string_pretty(self).into_diagnostic()? + "\n",
+        )
+        .into_diagnostic()
+        .wrap_err_with(|| format!("failed to write '{}'", path.display()))
+    }
+
+    /// Writes the decisions to `~/.pixi/trusted-projects.json`.
+    pub fn save(&self) -> miette::Result<()> {
+        self.write(&trust_store_path()?)
+    }
+
+    /// Returns whether activation scripts with `digest` are allowed to run for the project.
+    pub fn state(&self, key: &str, digest: &str) -> TrustState {
+        match self.projects.get(key) {
+            Some(decision) if decision.digest != digest => TrustState::Changed,
+            Some(decision) if decision.trusted => TrustState::Trusted,
+            Some(_) => TrustState::Denied,
+            None => TrustState::Unknown,
+        }
+    }
+
+    /// Records the decision for a project.
+    pub fn decide(&mut self, key: String, decision: TrustDecision) {
+        self.projects.insert(key, decision);
+    }
+
+    /// Forgets the decision for a project, returns false if there was none.
+    pub fn forget(&mut self, key: &str) -> bool {
+        self.projects.remove(key).is_some()
+    }
+
+    /// Returns the decisions of all projects.
+    pub fn decisions(&self) -> impl Iterator<Item = &TrustDecision> {
+        self.projects.values()
+    }
+}
+
+/// Returns the path of the file that stores the trust decisions.
+fn trust_store_path() -> miette::Result<PathBuf> {
+    dirs::home_dir()
+        .map(|home| home.join(consts::PIXI_DIR).join(TRUST_FILE))
+        .ok_or_else(|| miette::miette!("could not determine the home directory"))
+}
+
+/// Returns the key of a project in the trust store, a hash of the path of its manifest.
+pub fn project_key(project: &Project) -> String {
+    let path = project.manifest_path();
+    let path = path.canonicalize().unwrap_or(path);
+    format!(
+        "{:x}",
+        compute_bytes_digest::<Sha256>(path.to_string_lossy().as_bytes())
+    )
+}
+
+/// Reads the contents of the activation scripts.
+fn read_scripts(scripts: &[PathBuf]) -> miette::Result<Vec<(PathBuf, Vec<u8>)>> {
+    scripts
+        .iter()
+        .map(|script| {
+            std::fs::read(script)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("failed to read '{}'", script.display()))
+                .map(|contents| (script.clone(), contents))
+        })
+        .collect()
+}
+
+/// Returns the digest of the paths and contents of activation scripts.
+fn scripts_digest(scripts: &[(PathBuf, Vec<u8>)]) -> String {
+    let mut contents = Vec::new();
+    for (script, script_contents) in scripts {
+        contents.extend(script.to_string_lossy().as_bytes());
+        contents.push(0);
+        contents.extend(script_contents);
+        contents.push(0);
+    }
+    format!("{:x}", compute_bytes_digest::<Sha256>(&contents))
+}
+
+/// Returns the scripts of the project that have to be trusted: the activation scripts followed by
+/// the deactivation scripts.
+pub fn project_scripts(project: &Project) -> miette::Result<Vec<PathBuf>> {
+    let mut scripts = project.activation_scripts(project.platform())?;
+    scripts.extend(project.deactivation_scripts(project.platform())?);
+    Ok(scripts)
+}
+
+/// Records that the current activation scripts of the project are trusted, or denied.
+pub fn decide(project: &Project, trusted: bool) -> miette::Result<()> {
+    let mut store = TrustStore::load()?;
+    store.decide(
+        project_key(project),
+        TrustDecision {
+            manifest_path: project.manifest_path(),
+            digest: scripts_digest(&read_scripts(&project_scripts(project)?)?),
+            trusted,
+        },
+    );
+    store.save()
+}
+
+/// Returns an error if the activation `scripts` of the project are not trusted. The user is asked
+/// for a decision when it is missing or the scripts changed and the terminal is interactive.
+pub fn ensure_trusted(project: &Project, scripts: &[PathBuf]) -> miette::Result<()> {
+    ensure_trusted_contents(project, &read_scripts(scripts)?)
+}
+
+/// Reads the scripts of the project that have to be trusted and returns their contents if they are
+/// trusted. The contents are read once, so the returned contents are the ones that were trusted
+/// even if a script changes afterwards.
+pub fn read_trusted_scripts(project: &Project) -> miette::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let scripts = read_scripts(&project_scripts(project)?)?;
+    ensure_trusted_contents(project, &scripts)?;
+    Ok(scripts)
+}
+
+/// Like [`ensure_trusted`] for the paths and contents of scripts that were already read.
+fn ensure_trusted_contents(
+    project: &Project,
+    scripts: &[(PathBuf, Vec<u8>)],
+) -> miette::Result<()> {
+    if scripts.is_empty() || Config::load_global()?.trust_all_projects() {
+        return Ok(());
+    }
+    let digest = scripts_digest(scripts);
+    let mut store = TrustStore::load()?;
+    let state = store.state(&project_key(project), &digest);
+    match state {
+        TrustState::Trusted => return Ok(()),
+        TrustState::Denied => miette::bail!(
+            help = "review the activation scripts and run `pixi trust` if they are safe",
+            "the activation scripts of {} are not trusted",
+            project.name()
+        ),
+        TrustState::Changed | TrustState::Unknown => {}
+    }
+
+    let scripts = scripts
+        .iter()
+        .map(|(script, _)| {
+            format!(
+                "  - {}",
+                script
+                    .strip_prefix(project.root())
+                    .unwrap_or(script)
+                    .display()
+            )
+        })
+        .join("\n");
+    let reason = if state == TrustState::Changed {
+        "changed activation scripts"
+    } else {
+        "activation scripts"
+    };
+
+    let term = console::Term::stderr();
+    if !ci::is_interactive() {
+        miette::bail!(
+            help = "review the activation scripts and run `pixi trust` if they are safe",
+            "{} has {reason} that run arbitrary code, they have to be trusted first:\n{scripts}",
+            project.name()
+        );
+    }
+    eprintln!(
+        "{} {} has {reason} that run arbitrary code:\n{scripts}",
+        console::style("!").yellow().bold(),
+        console::style(project.name()).bold()
+    );
+    eprint!("{} [y/N]: ", console::style("Do you trust them?").bold());
+    let answer = term.read_line().into_diagnostic()?;
+    let trusted = answer.trim().to_lowercase().starts_with('y');
+    store.decide(
+        project_key(project),
+        TrustDecision {
+            manifest_path: project.manifest_path(),
+            digest,
+            trusted,
+        },
+    );
+    store.save()?;
+    if !trusted {
+        miette::bail!(
+            help = "run `pixi trust` if you change your mind",
+            "the activation scripts of {} are not trusted",
+            project.name()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trust_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TRUST_FILE);
+        let mut store = TrustStore::from_path(&path).unwrap();
+        assert_eq!(store.state("project", "abc"), TrustState::Unknown);
+
+        store.decide(
+            String::from("project"),
+            TrustDecision {
+                manifest_path: PathBuf::from("/project/pixi.toml"),
+                digest: String::from("abc"),
+                trusted: true,
+            },
+        );
+        store.write(&path).unwrap();
+
+        let mut store = TrustStore::from_path(&path).unwrap();
+        assert_eq!(store.state("project", "abc"), TrustState::Trusted);
+        assert_eq!(store.state("project", "def"), TrustState::Changed);
+
+        store.decide(
+            String::from("project"),
+            TrustDecision {
+                manifest_path: PathBuf::from("/project/pixi.toml"),
+                digest: String::from("def"),
+                trusted: false,
+            },
+        );
+        assert_eq!(store.state("project", "def"), TrustState::Denied);
+        assert!(store.forget("project"));
+        assert_eq!(store.state("project", "def"), TrustState::Unknown);
+    }
+}
@@ -0,0 +1,466 @@
+//! Finds the declared dependencies of a project that nothing in the project appears to use. The
+//! files of the project are scanned by [`Analyzer`]s, which find the names that a kind of file
+//! refers to, e.g. the modules that python files import. A dependency is used if its name is
+//! referred to, or if the files it installed provide a name that is referred to.
+
+use crate::prefix::Prefix;
+use crate::utils::spec_format::normalize_pypi_name;
+use crate::Project;
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use rattler_conda_types::{MatchSpec, PrefixRecord};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The directories that are never scanned, next to hidden directories.
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "__pycache__"];
+
+/// A name that a file of the project refers to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Reference {
+    /// A top-level python module, e.g. `yaml` for `import yaml.loader`
+    PythonModule(String),
+    /// A CMake package, e.g. `ZLIB` for `find_package(ZLIB)`
+    CMakePackage(String),
+    /// An executable, e.g. one that a task runs
+    Executable(String),
+}
+
+impl Reference {
+    /// Returns the referenced name, normalized so it can be compared with package names.
+    fn normalized_name(&self) -> String {
+        let name = match self {
+            Reference::PythonModule(name)
+            | Reference::CMakePackage(name)
+            | Reference::Executable(name) => name,
+        };
+        normalize_pypi_name(name)
+    }
+}
+
+/// Finds the references in a kind of file of the project.
+pub trait Analyzer {
+    /// Returns true if the analyzer understands the file.
+    fn matches(&self, path: &Path) -> bool;
+
+    /// Returns the references in the contents of the file.
+    fn references(&self, contents: &str) -> Vec<Reference>;
+}
+
+/// Finds the modules that python files import.
+pub struct PythonAnalyzer;
+
+impl Analyzer for PythonAnalyzer {
+    fn matches(&self, path: &Path) -> bool {
+        matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("py" | "pyi" | "pyx")
+        )
+    }
+
+    fn references(&self, contents: &str) -> Vec<Reference> {
+        static FROM_IMPORT: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"(?m)^\s*from\s+([A-Za-z_]\w*)").unwrap());
+        static IMPORT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^\s*import\s+(.+)$").unwrap());
+
+        let from_imports = FROM_IMPORT
+            .captures_iter(contents)
+            .map(|c| c[1].to_string());
+        let imports = IMPORT.captures_iter(contents).flat_map(|c| {
+            c[1].split(',')
+                .filter_map(|module| {
+                    let module = module.trim().split(['.', ' ']).next()?;
+                    (!module.is_empty()).then(|| module.to_string())
+                })
+                .collect_vec()
+        });
+
+        // Python files also need the interpreter.
+        from_imports
+            .chain(imports)
+            .map(Reference::PythonModule)
+            .chain([Reference::Executable(String::from("python"))])
+            .collect()
+    }
+}
+
+/// Finds the packages that CMake files look for.
+pub struct CMakeAnalyzer;
+
+impl Analyzer for CMakeAnalyzer {
+    fn matches(&self, path: &Path) -> bool {
+        path.file_name().and_then(|name| name.to_str()) == Some("CMakeLists.txt")
+            || path.extension().and_then(|ext| ext.to_str()) == Some("cmake")
+    }
+
+    fn references(&self, contents: &str) -> Vec<Reference> {
+        static FIND_PACKAGE: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"(?i)find_package\s*\(\s*([A-Za-z0-9_.+-]+)").unwrap());
+        FIND_PACKAGE
+            .captures_iter(contents)
+            .map(|c| Reference::CMakePackage(c[1].to_string()))
+            .chain([Reference::Executable(String::from("cmake"))])
+            .collect()
+    }
+}
+
+/// Returns the analyzers that are used by default.
+pub fn default_analyzers() -> Vec<Box<dyn Analyzer>> {
+    vec![Box::new(PythonAnalyzer), Box::new(CMakeAnalyzer)]
+}
+
+/// A declared dependency that nothing appears to use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnusedDependency {
+    pub name: String,
+    /// The table in which the dependency is declared
+    pub table: &'static str,
+}
+
+/// Returns the dependencies of the project, for the platform of the project, that nothing in the
+/// project appears to use. The installed environment is used to find the names that the packages
+/// provide, without it only the names of the packages are compared with the references.
+pub async fn find_unused_dependencies(
+    project: &Project,
+    analyzers: &[Box<dyn Analyzer>],
+) -> miette::Result<Vec<UnusedDependency>> {
+    let platform = project.platform();
+    let mut references = HashSet::new();
+    scan_dir(project.root(), analyzers, &mut references)?;
+    references.extend(
+        project
+            .tasks(Some(platform))
+            .values()
+            .filter_map(|task| task.as_single_command())
+            .flat_map(|command| command_executables(&command)),
+    );
+    let referenced_names = references
+        .iter()
+        .map(Reference::normalized_name)
+        .collect::<HashSet<_>>();
+
+    let prefix = Prefix::new(project.environment_dir())?;
+    let installed = prefix.find_installed_packages(None).await?;
+    if installed.is_empty() {
+        tracing::warn!(
+            "the environment is not installed, only the names of the dependencies are compared with what the project uses"
+        );
+    }
+    let installed = installed
+        .iter()
+        .map(|record| {
+            (
+                record.repodata_record.package_record.name.as_normalized(),
+                record,
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut unused = Vec::new();
+    for name in project.all_dependencies(platform).keys() {
+        let used = name_is_referenced(name.as_normalized(), &referenced_names)
+            || installed.get(name.as_normalized()).map_or(false, |record| {
+                conda_provides(record, &installed)
+                    .iter()
+                    .any(|provided| referenced_names.contains(&provided.normalized_name()))
+            });
+        if !used {
+            unused.push(UnusedDependency {
+                name: name.as_source().to_string(),
+                table: "dependencies",
+            });
+        }
+    }
+
+    let pypi_provided = pypi_provides(prefix.root());
+    for name in project.pypi_dependencies(platform).keys() {
+        let normalized = normalize_pypi_name(&name.as_normalized().to_string());
+        let used = name_is_referenced(&normalized, &referenced_names)
+            || pypi_provided.get(&normalized).map_or(false, |provided| {
+                provided
+                    .iter()
+                    .any(|p| referenced_names.contains(&p.normalized_name()))
+            });
+        if !used {
+            unused.push(UnusedDependency {
+                name: name.as_source_str().to_string(),
+                table: "pypi-dependencies",
+            });
+        }
+    }
+
+    Ok(unused)
+}
+
+/// Returns true if a reference has the name of the package, also without a `python-` or `py`
+/// prefix, which is common for the conda packages of python modules.
+fn name_is_referenced(package_name: &str, referenced_names: &HashSet<String>) -> bool {
+    let name = normalize_pypi_name(package_name);
+    [
+        Some(name.as_str()),
+        name.strip_prefix("python-"),
+        name.strip_prefix("py"),
+    ]
+    .into_iter()
+    .flatten()
+    .any(|name| !name.is_empty() && referenced_names.contains(name))
+}
+
+/// Scans the files in `dir` and its subdirectories, except for hidden and skipped directories.
+fn scan_dir(
+    dir: &Path,
+    analyzers: &[Box<dyn Analyzer>],
+    references: &mut HashSet<Reference>,
+) -> miette::Result<()> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            if !file_name.starts_with('.') && !SKIPPED_DIRS.contains(&file_name.as_str()) {
+                scan_dir(&path, analyzers, references)?;
+            }
+            continue;
+        }
+        let matching = analyzers.iter().filter(|a| a.matches(&path)).collect_vec();
+        if matching.is_empty() {
+            continue;
+        }
+        // Files that are not text can't refer to anything.
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        for analyzer in matching {
+            references.extend(analyzer.references(&contents));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the executables that a task command runs, the first word of every command in it.
+fn command_executables(command: &str) -> Vec<Reference> {
+    let Some(words) = shlex::split(command) else {
+        return vec![];
+    };
+    let mut executables = Vec::new();
+    let mut command_start = true;
+    for word in words {
+        if ["&&", "||", ";", "|"].contains(&word.as_str()) {
+            command_start = true;
+        } else if command_start && !word.contains('=') {
+            let name = Path::new(&word)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or(word);
+            executables.push(Reference::Executable(name));
+            command_start = false;
+        }
+    }
+    executables
+}
+
+/// Returns what the files of an installed conda package provide. Packages without files, like
+/// meta-packages, provide what their direct dependencies provide.
+fn conda_provides(
+    record: &PrefixRecord,
+    installed: &HashMap<&str, &PrefixRecord>,
+) -> Vec<Reference> {
+    if !record.files.is_empty() {
+        return provided_by_files(&record.files);
+    }
+    record
+        .repodata_record
+        .package_record
+        .depends
+        .iter()
+        .filter_map(|spec| MatchSpec::from_str(spec).ok()?.name)
+        .filter_map(|name| installed.get(name.as_normalized()))
+        .flat_map(|dependency| provided_by_files(&dependency.files))
+        .collect()
+}
+
+/// Returns the python modules, executables and CMake packages that the files of a conda package
+/// provide. The paths are relative to the prefix.
+fn provided_by_files(files: &[PathBuf]) -> Vec<Reference> {
+    let mut provided = Vec::new();
+    for file in files {
+        let components = file
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect_vec();
+        let components = components.iter().map(String::as_str).collect_vec();
+        if let Some(index) = components.iter().position(|c| *c == "site-packages") {
+            if let Some(module) = components.get(index + 1).and_then(|c| python_module(c)) {
+                provided.push(Reference::PythonModule(module));
+            }
+        } else if let ["bin" | "Scripts", exe] | ["Library", "bin", exe] = components.as_slice() {
+            provided.push(Reference::Executable(executable_name(exe)));
+        } else if let Some(index) = components
+            .windows(2)
+            .position(|w| matches!(w, ["lib" | "share", "cmake"]))
+        {
+            // Either `lib/cmake/<Name>/<Name>Config.cmake` or `lib/cmake/<Name>Config.cmake`
+            match &components[index + 2..] {
+                [dir, _, ..] => provided.push(Reference::CMakePackage(dir.to_string())),
+                [file] => {
+                    let name = file.trim_end_matches(".cmake");
+                    if let Some(name) = name
+                        .strip_suffix("Config")
+                        .or_else(|| name.strip_suffix("-config"))
+                    {
+                        provided.push(Reference::CMakePackage(name.to_string()));
+                    }
+                }
+                [] => {}
+            }
+        }
+    }
+    provided.into_iter().unique().collect()
+}
+
+/// Returns the top-level python module of a file or directory in `site-packages`.
+fn python_module(name: &str) -> Option<String> {
+    if name.ends_with(".dist-info")
+        || name.ends_with(".egg-info")
+        || name.ends_with(".pth")
+        || name == "__pycache__"
+    {
+        return None;
+    }
+    let module = name.split('.').next()?;
+    (!module.is_empty()).then(|| module.to_string())
+}
+
+/// Returns the name of an executable without the extension of Windows executables.
+fn executable_name(file_name: &str) -> String {
+    [".exe", ".bat", ".cmd"]
+        .iter()
+        .find_map(|ext| file_name.strip_suffix(ext))
+        .unwrap_or(file_name)
+        .to_string()
+}
+
+/// Returns what the installed pypi packages in the environment provide by their normalized name,
+/// determined from the `RECORD` files of the installed distributions.
+fn pypi_provides(prefix: &Path) -> HashMap<String, Vec<Reference>> {
+    let site_packages_dirs = std::fs::read_dir(prefix.join("lib"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("python"))
+        .map(|entry| entry.path().join("site-packages"))
+        .chain([prefix.join("Lib").join("site-packages")]);
+
+    let mut provides = HashMap::new();
+    for dist_info in site_packages_dirs
+        .flat_map(|dir| std::fs::read_dir(dir).into_iter().flatten().flatten())
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".dist-info"))
+    {
+        let dir_name = dist_info.file_name().to_string_lossy().to_string();
+        let Some(name) = dir_name.split('-').next() else {
+            continue;
+        };
+        let Ok(record) = std::fs::read_to_string(dist_info.path().join("RECORD")) else {
+            continue;
+        };
+        let provided = record
+            .lines()
+            .filter_map(|line| line.split(',').next())
+            .filter_map(|path| {
+                if path.starts_with("..") {
+                    let file_name = Path::new(path).file_name()?.to_string_lossy().to_string();
+                    Some(Reference::Executable(executable_name(&file_name)))
+                } else {
+                    python_module(path.split('/').next()?).map(Reference::PythonModule)
+                }
+            })
+            .unique()
+            .collect_vec();
+        provides.insert(normalize_pypi_name(name), provided);
+    }
+    provides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_python_analyzer() {
+        let references = PythonAnalyzer.references(
+            "import os, numpy as np\nfrom yaml.loader import SafeLoader\nfrom . import sibling\n    import sklearn.linear_model\n",
+        );
+        assert_eq!(
+            references,
+            vec![
+                Reference::PythonModule(String::from("yaml")),
+                Reference::PythonModule(String::from("os")),
+                Reference::PythonModule(String::from("numpy")),
+                Reference::PythonModule(String::from("sklearn")),
+                Reference::Executable(String::from("python")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cmake_analyzer() {
+        assert!(CMakeAnalyzer.matches(Path::new("src/CMakeLists.txt")));
+        assert_eq!(
+            CMakeAnalyzer.references("find_package(ZLIB REQUIRED)\nFIND_PACKAGE( fmt )"),
+            vec![
+                Reference::CMakePackage(String::from("ZLIB")),
+                Reference::CMakePackage(String::from("fmt")),
+                Reference::Executable(String::from("cmake")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_executables() {
+        assert_eq!(
+            command_executables("RUST_LOG=info ./bin/serve --port 80 && pytest -x | tee log"),
+            vec![
+                Reference::Executable(String::from("serve")),
+                Reference::Executable(String::from("pytest")),
+                Reference::Executable(String::from("tee")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_provided_by_files() {
+        let files = [
+            "lib/python3.11/site-packages/yaml/__init__.py",
+            "lib/python3.11/site-packages/_yaml.cpython-311-x86_64-linux-gnu.so",
+            "lib/python3.11/site-packages/PyYAML-6.0.1.dist-info/METADATA",
+            "bin/pytest",
+            "Library/bin/cmake.exe",
+            "lib/cmake/ZLIB/ZLIBConfig.cmake",
+            "share/cmake/fmt-config.cmake",
+        ]
+        .map(PathBuf::from);
+        assert_eq!(
+            provided_by_files(&files),
+            vec![
+                Reference::PythonModule(String::from("yaml")),
+                Reference::PythonModule(String::from("_yaml")),
+                Reference::Executable(String::from("pytest")),
+                Reference::Executable(String::from("cmake")),
+                Reference::CMakePackage(String::from("ZLIB")),
+                Reference::CMakePackage(String::from("fmt")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_name_is_referenced() {
+        let referenced = HashSet::from([String::from("yaml"), String::from("requests")]);
+        assert!(name_is_referenced("pyyaml", &referenced));
+        assert!(name_is_referenced("python-requests", &referenced));
+        assert!(!name_is_referenced("numpy", &referenced));
+    }
+}
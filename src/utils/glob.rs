@@ -0,0 +1,53 @@
+//! Matching of names against simple glob patterns, e.g. the environment variables that are passed
+//! through to tasks (`AWS_*`) or the members of a workspace (`libs/*`).
+
+/// Matches a name against a pattern where `*` matches any number of characters and `?` matches
+/// exactly one.
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` in the pattern and of the name when it was reached, to
+    // backtrack to when the rest doesn't match.
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("HOME", "HOME"));
+        assert!(!glob_matches("HOME", "HOMEBREW_PREFIX"));
+        assert!(glob_matches("AWS_*", "AWS_PROFILE"));
+        assert!(glob_matches("AWS_*", "AWS_"));
+        assert!(!glob_matches("AWS_*", "AWS"));
+        assert!(glob_matches("*_TOKEN", "GITHUB_TOKEN"));
+        assert!(glob_matches("*_PROXY*", "HTTPS_PROXY_URL"));
+        assert!(glob_matches("LC_?", "LC_A"));
+        assert!(!glob_matches("LC_?", "LC_ALL"));
+        assert!(glob_matches("*", "ANYTHING"));
+    }
+}
@@ -0,0 +1,123 @@
+//! Interpolation of environment variables in values of the manifest, e.g.
+//! `${env:CONDA_MIRROR}/conda-forge` or `${env:CONDA_MIRROR:-https://conda.anaconda.org}/conda-forge`,
+//! so a manifest can point at site specific locations. The values are interpolated when the
+//! manifest is loaded.
+
+use std::borrow::Cow;
+use thiserror::Error;
+
+/// An error that occurs when a value can't be interpolated.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum InterpolationError {
+    #[error("the environment variable '{0}' is not set, set it or add a default with `${{env:{0}:-default}}`")]
+    MissingVariable(String),
+
+    #[error("'{0}' is not closed with a '}}'")]
+    Unterminated(String),
+
+    #[error("'{0}' is not a valid interpolation, use `${{env:NAME}}` or `${{env:NAME:-default}}`")]
+    Invalid(String),
+}
+
+/// Replaces the `${env:NAME}` and `${env:NAME:-default}` references in `value` with the value of
+/// the environment variable. The default is used if the variable is not set or empty, a variable
+/// without a default that is not set is an error.
+pub fn interpolate_env(value: &str) -> Result<Cow<'_, str>, InterpolationError> {
+    interpolate_with(value, |name| std::env::var(name).ok())
+}
+
+fn interpolate_with(
+    value: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Cow<'_, str>, InterpolationError> {
+    if !value.contains("${") {
+        return Ok(Cow::Borrowed(value));
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let reference = &rest[start..];
+        let Some(end) = reference.find('}') else {
+            return Err(InterpolationError::Unterminated(reference.to_string()));
+        };
+        let invalid = || InterpolationError::Invalid(reference[..=end].to_string());
+        let inner = reference[2..end].strip_prefix("env:").ok_or_else(invalid)?;
+        let (name, default) = match inner.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (inner, None),
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(invalid());
+        }
+        match (lookup(name), default) {
+            (Some(variable), Some(default)) if variable.is_empty() => result.push_str(default),
+            (Some(variable), _) => result.push_str(&variable),
+            (None, Some(default)) => result.push_str(default),
+            (None, None) => return Err(InterpolationError::MissingVariable(name.to_string())),
+        }
+        rest = &reference[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(Cow::Owned(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "MIRROR" => Some(String::from("https://mirror.example.com")),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(
+            interpolate_with("conda-forge", lookup).unwrap(),
+            "conda-forge"
+        );
+        assert_eq!(
+            interpolate_with("${env:MIRROR}/conda-forge", lookup).unwrap(),
+            "https://mirror.example.com/conda-forge"
+        );
+        assert_eq!(
+            interpolate_with(
+                "${env:OTHER:-https://conda.anaconda.org}/conda-forge",
+                lookup
+            )
+            .unwrap(),
+            "https://conda.anaconda.org/conda-forge"
+        );
+        assert_eq!(
+            interpolate_with("${env:EMPTY:-default}-${env:EMPTY}", lookup).unwrap(),
+            "default-"
+        );
+        assert_eq!(
+            interpolate_with("${env:MIRROR:-unused}", lookup).unwrap(),
+            "https://mirror.example.com"
+        );
+    }
+
+    #[test]
+    fn test_interpolate_errors() {
+        assert_eq!(
+            interpolate_with("${env:OTHER}/conda-forge", lookup).unwrap_err(),
+            InterpolationError::MissingVariable(String::from("OTHER"))
+        );
+        assert_eq!(
+            interpolate_with("${env:MIRROR/conda-forge", lookup).unwrap_err(),
+            InterpolationError::Unterminated(String::from("${env:MIRROR/conda-forge"))
+        );
+        assert_eq!(
+            interpolate_with("${MIRROR}/conda-forge", lookup).unwrap_err(),
+            InterpolationError::Invalid(String::from("${MIRROR}"))
+        );
+        assert!(interpolate_with("${env:}", lookup).is_err());
+        assert!(interpolate_with("${env:MIR ROR}", lookup).is_err());
+    }
+}
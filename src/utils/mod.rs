@@ -1 +1,4 @@
+pub mod glob;
+pub mod interpolate;
 pub mod spanned;
+pub mod spec_format;
@@ -0,0 +1,172 @@
+//! The canonical rendering of specs and locked packages, so all commands display them the same
+//! way, and the normalization of specs before they are written to the manifest.
+
+use crate::project::manifest::PyPiRequirement;
+use itertools::Itertools;
+use rattler_conda_types::{MatchSpec, NamelessMatchSpec, PackageName, Platform};
+use rattler_lock::{LockedDependency, LockedDependencyKind};
+
+/// Renders a conda spec, e.g. `numpy >=1.26,<2`.
+pub fn conda_spec(name: &PackageName, spec: &NamelessMatchSpec) -> String {
+    MatchSpec::from_nameless(spec.clone(), Some(name.clone())).to_string()
+}
+
+/// Renders a PyPI requirement as PEP 508, e.g. `requests[socks]>=2.31`.
+pub fn pypi_requirement(name: &rip::types::PackageName, requirement: &PyPiRequirement) -> String {
+    requirement.as_pep508(name).to_string()
+}
+
+/// Renders a conda spec as it is written in the manifest, a spec without any constraint is `*`.
+pub fn manifest_conda_spec(spec: &NamelessMatchSpec) -> String {
+    let rendered = spec.to_string();
+    if rendered.trim().is_empty() {
+        String::from("*")
+    } else {
+        rendered
+    }
+}
+
+/// Normalizes a PyPI requirement before it is written to the manifest. The extras are normalized
+/// as described in PEP 685, deduplicated and sorted, the version is kept as specified.
+pub fn normalize_pypi_requirement(requirement: &PyPiRequirement) -> PyPiRequirement {
+    let extras = requirement.extras.as_ref().and_then(|extras| {
+        let extras = extras
+            .iter()
+            .map(|extra| normalize_extra(extra))
+            .filter(|extra| !extra.is_empty())
+            .sorted()
+            .dedup()
+            .collect_vec();
+        (!extras.is_empty()).then_some(extras)
+    });
+    PyPiRequirement {
+        version: requirement.version.clone(),
+        extras,
+        allow_prerelease: requirement.allow_prerelease,
+    }
+}
+
+/// Normalizes the name of a PyPI package as described in PEP 503: lowercase with runs of `-`, `_`
+/// and `.` replaced by a single `-`. Conda names are compared with PyPI names in this form too.
+pub fn normalize_pypi_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut in_separator = false;
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !in_separator {
+                normalized.push('-');
+            }
+            in_separator = true;
+        } else {
+            normalized.extend(c.to_lowercase());
+            in_separator = false;
+        }
+    }
+    normalized
+}
+
+/// Normalizes the name of an extra, PEP 685 normalizes them like the names of packages.
+fn normalize_extra(extra: &str) -> String {
+    normalize_pypi_name(extra.trim().trim_matches(['-', '_', '.']))
+}
+
+/// Returns the kind of a locked package, `conda` or `pypi`.
+pub fn package_kind(package: &LockedDependency) -> &'static str {
+    match package.kind {
+        LockedDependencyKind::Conda(_) => "conda",
+        LockedDependencyKind::Pypi(_) => "pypi",
+    }
+}
+
+/// Returns the name of a locked package in the form it is compared in, conda names are lowercase
+/// and PyPI names are normalized as described in PEP 503.
+pub fn locked_name(package: &LockedDependency) -> String {
+    match package.kind {
+        LockedDependencyKind::Conda(_) => package.name.to_lowercase(),
+        LockedDependencyKind::Pypi(_) => normalize_pypi_name(&package.name),
+    }
+}
+
+/// Renders a locked package with its version, PyPI packages are marked as such, e.g.
+/// `numpy 1.26.2` or `requests 2.31.0 (pypi)`.
+pub fn locked_version(name: &str, version: Option<&str>, kind: &str) -> String {
+    let mut rendered = name.to_string();
+    if let Some(version) = version {
+        rendered.push(' ');
+        rendered.push_str(version);
+    }
+    if kind == "pypi" {
+        rendered.push_str(" (pypi)");
+    }
+    rendered
+}
+
+/// Renders a locked package with its kind and platform, e.g. `numpy (conda, linux-64)`.
+pub fn locked_package(name: &str, kind: &str, platform: Platform) -> String {
+    format!("{} ({kind}, {platform})", console::style(name).bold())
+}
+
+/// Renders the change of a locked version, `from` is `None` for an added package and `to` for a
+/// removed package.
+pub fn version_change(from: Option<&str>, to: Option<&str>) -> String {
+    format!(
+        "{} -> {}",
+        from.unwrap_or("<none>"),
+        console::style(to.unwrap_or("<removed>")).green()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_normalize_pypi_name() {
+        assert_eq!(
+            normalize_pypi_name("Typing_Extensions"),
+            "typing-extensions"
+        );
+        assert_eq!(normalize_pypi_name("zope.interface"), "zope-interface");
+        assert_eq!(normalize_pypi_name("foo--_.bar"), "foo-bar");
+        assert_eq!(normalize_pypi_name("numpy"), "numpy");
+    }
+
+    #[test]
+    fn test_conda_spec() {
+        let name = PackageName::new_unchecked("numpy");
+        let spec = NamelessMatchSpec::from_str(">=1.26,<2").unwrap();
+        assert_eq!(conda_spec(&name, &spec), "numpy >=1.26,<2");
+        assert_eq!(manifest_conda_spec(&spec), ">=1.26,<2");
+    }
+
+    #[test]
+    fn test_pypi_requirement() {
+        let requirement =
+            pep508_rs::Requirement::from_str("Requests[SOCKS,use_chardet_on_py3,socks]>=2.31")
+                .unwrap();
+        let name = rip::types::PackageName::from_str(&requirement.name).unwrap();
+        let requirement = normalize_pypi_requirement(&PyPiRequirement::from(requirement));
+        assert_eq!(
+            requirement.extras.as_deref(),
+            Some(["socks".to_string(), "use-chardet-on-py3".to_string()].as_slice())
+        );
+        assert_eq!(
+            pypi_requirement(&name, &requirement),
+            "Requests[socks,use-chardet-on-py3]>=2.31"
+        );
+    }
+
+    #[test]
+    fn test_locked_version() {
+        assert_eq!(
+            locked_version("numpy", Some("1.26.2"), "conda"),
+            "numpy 1.26.2"
+        );
+        assert_eq!(
+            locked_version("requests", Some("2.31.0"), "pypi"),
+            "requests 2.31.0 (pypi)"
+        );
+        assert_eq!(locked_version("foo", None, "project"), "foo");
+    }
+}
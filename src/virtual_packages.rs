@@ -1,3 +1,4 @@
+use crate::host::{libc_flavor, LibcFlavor};
 use crate::Project;
 use miette::IntoDiagnostic;
 use rattler_conda_types::{GenericVirtualPackage, Platform, Version};
@@ -114,10 +115,17 @@ impl Project {
         // Get the system requirements from the project manifest
         let system_requirements = self.virtual_packages_for_platform(platform);
 
+        // A libc requirement of another family, e.g. musl, replaces the default glibc instead of
+        // being required next to it.
+        let requires_libc = system_requirements
+            .iter()
+            .any(|requirement| matches!(requirement, VirtualPackage::LibC(_)));
+
         // Combine the requirements, allowing the system requirements to overwrite the reference
         // virtual packages.
         let combined_packages = get_minimal_virtual_packages(platform)
             .into_iter()
+            .filter(|vpkg| !(requires_libc && matches!(vpkg, VirtualPackage::LibC(_))))
             .chain(system_requirements)
             .map(GenericVirtualPackage::from)
             .map(|vpkg| (vpkg.name.clone(), vpkg))
@@ -127,11 +135,13 @@ impl Project {
     }
 }
 
-/// Verifies if the current platform satisfies the minimal virtual package requirements.
+/// Verifies if the current platform satisfies the minimal virtual package requirements of the
+/// platform the environment of the project is installed for. When that platform runs through
+/// emulation the architecture of the machine is not checked.
 pub fn verify_current_platform_has_required_virtual_packages(
     project: &Project,
 ) -> miette::Result<()> {
-    let current_platform = Platform::current();
+    let platform = project.platform();
 
     let system_virtual_packages = VirtualPackage::current()
         .into_diagnostic()?
@@ -140,7 +150,10 @@ pub fn verify_current_platform_has_required_virtual_packages(
         .map(GenericVirtualPackage::from)
         .map(|vpkg| (vpkg.name.clone(), vpkg))
         .collect::<HashMap<_, _>>();
-    let required_pkgs = project.virtual_packages(current_platform)?;
+    let required_pkgs = project
+        .virtual_packages(platform)?
+        .into_iter()
+        .filter(|vpkg| !(project.is_emulated() && vpkg.name.as_normalized() == "__archspec"));
 
     // Check for every local minimum package if it is available and on the correct version.
     for req_pkg in required_pkgs {
@@ -156,6 +169,10 @@ pub fn verify_current_platform_has_required_virtual_packages(
                 [system-requirements]\n\
                 {} = \"{}\"", req_pkg.name.as_source(), req_pkg.version, local_vpkg.version, req_pkg.name.as_normalized().strip_prefix("__").unwrap_or(local_vpkg.name.as_normalized()), local_vpkg.version);
             }
+        } else if let Some((message, help)) =
+            libc_mismatch(req_pkg.name.as_normalized(), libc_flavor())
+        {
+            miette::bail!(help = help, "{message}")
         } else {
             miette::bail!("The platform you are running on should at least have the virtual package {} on version {}, build_string: {}", req_pkg.name.as_source(), req_pkg.version, req_pkg.build_string)
         }
@@ -164,10 +181,34 @@ pub fn verify_current_platform_has_required_virtual_packages(
     Ok(())
 }
 
+/// Returns why a missing libc virtual package can't be satisfied by the libc of this machine, and
+/// how to resolve it, if the project requires a libc of another family, e.g. a glibc project on
+/// Alpine.
+fn libc_mismatch(required: &str, host: Option<LibcFlavor>) -> Option<(String, String)> {
+    let host = host?;
+    let required = required.strip_prefix("__")?;
+    if required == host.family() || !matches!(required, "glibc" | "musl") {
+        return None;
+    }
+    let message = format!(
+        "The project requires {required}, but this machine uses {host}. Packages built against {required} cannot run on it."
+    );
+    let help = match host {
+        LibcFlavor::Musl => String::from(
+            "if the channels of the project provide packages built against musl, set in your pixi.toml:\n[system-requirements]\nlibc = { family = \"musl\", version = \"1.2\" }",
+        ),
+        LibcFlavor::Glibc => String::from(
+            "the project only runs on machines that use musl, e.g. in an Alpine container",
+        ),
+    };
+    Some((message, help))
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::host::LibcFlavor;
     use crate::virtual_packages::{
-        get_minimal_virtual_packages, non_relevant_virtual_packages_for_platform,
+        get_minimal_virtual_packages, libc_mismatch, non_relevant_virtual_packages_for_platform,
     };
     use insta::assert_debug_snapshot;
     use rattler_conda_types::Platform;
@@ -254,4 +295,15 @@ mod tests {
             _ => false,
         }));
     }
+
+    #[test]
+    fn test_libc_mismatch() {
+        let (message, help) = libc_mismatch("__glibc", Some(LibcFlavor::Musl)).unwrap();
+        assert!(message.contains("this machine uses musl"));
+        assert!(help.contains("family = \"musl\""));
+        assert!(libc_mismatch("__musl", Some(LibcFlavor::Glibc)).is_some());
+        assert_eq!(libc_mismatch("__glibc", Some(LibcFlavor::Glibc)), None);
+        assert_eq!(libc_mismatch("__cuda", Some(LibcFlavor::Musl)), None);
+        assert_eq!(libc_mismatch("__glibc", None), None);
+    }
 }
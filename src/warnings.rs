@@ -0,0 +1,169 @@
+//! The registry of the non-fatal warnings that pixi emits. Every warning has a code, the codes that
+//! a project allows in its manifest are not shown, and `--deny-warnings` turns any other warning
+//! into an error at the end of the command.
+
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+
+/// The code of a kind of warning, used to allow the warning in the manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarningCode {
+    /// The manifest contains a key that pixi doesn't know.
+    UnknownManifestKey,
+    /// The project uses a feature that is still in beta.
+    BetaFeature,
+    /// An activation script of the project doesn't exist.
+    MissingActivationScript,
+    /// An activation script of the project has the wrong extension for the platform.
+    ActivationScriptExtension,
+    /// The environment contains packages of another platform.
+    MixedPlatforms,
+    /// The lock file is used while it is not up to date with the manifest.
+    OutdatedLockFile,
+    /// A post-link script of a package was executed or skipped.
+    PostLinkScript,
+    /// A locked package has a version that is end-of-life or deprecated.
+    EndOfLife,
+    /// An exposed task would overwrite an executable of the environment.
+    ExposedTaskShadowed,
+    /// A locked package has no sha256, so its artifact can't be verified.
+    MissingProvenance,
+    /// A stored token for a channel expired or expires soon.
+    TokenExpiry,
+}
+
+impl WarningCode {
+    /// Returns the code as it is written in the manifest.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WarningCode::UnknownManifestKey => "unknown-manifest-key",
+            WarningCode::BetaFeature => "beta-feature",
+            WarningCode::MissingActivationScript => "missing-activation-script",
+            WarningCode::ActivationScriptExtension => "activation-script-extension",
+            WarningCode::MixedPlatforms => "mixed-platforms",
+            WarningCode::OutdatedLockFile => "outdated-lock-file",
+            WarningCode::PostLinkScript => "post-link-script",
+            WarningCode::EndOfLife => "end-of-life",
+            WarningCode::ExposedTaskShadowed => "exposed-task-shadowed",
+            WarningCode::MissingProvenance => "missing-provenance",
+            WarningCode::TokenExpiry => "token-expiry",
+        }
+    }
+}
+
+impl Display for WarningCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A warning that was emitted during the current command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct Registry {
+    allowed: HashSet<WarningCode>,
+    emitted: Vec<Warning>,
+}
+
+static REGISTRY: Lazy<Mutex<Registry>> = Lazy::new(Default::default);
+
+/// Allows the warnings with the given codes, they are only logged at the debug level from now on.
+pub fn allow(codes: impl IntoIterator<Item = WarningCode>) {
+    REGISTRY.lock().unwrap().allowed.extend(codes);
+}
+
+/// Emits a warning, unless its code is allowed.
+pub fn emit(code: WarningCode, message: impl Into<String>) {
+    let message = message.into();
+    let mut registry = REGISTRY.lock().unwrap();
+    if registry.allowed.contains(&code) {
+        tracing::debug!("{message} [{code}]");
+        return;
+    }
+    tracing::warn!("{message} [{code}]");
+    registry.emitted.push(Warning { code, message });
+}
+
+/// Returns the warnings that were emitted so far.
+pub fn emitted() -> Vec<Warning> {
+    REGISTRY.lock().unwrap().emitted.clone()
+}
+
+/// Returns an error if any warning was emitted, used for `--deny-warnings`.
+pub fn deny_warnings() -> miette::Result<()> {
+    deny(&emitted())
+}
+
+fn deny(warnings: &[Warning]) -> miette::Result<()> {
+    if warnings.is_empty() {
+        return Ok(());
+    }
+    let codes = warnings.iter().map(|w| w.code).unique().collect_vec();
+    Err(miette::miette!(
+        help = format!(
+            "fix the warnings, or allow them in the manifest with `allow-warnings = [{}]` in the [project] table",
+            codes.iter().map(|code| format!("\"{code}\"")).join(", ")
+        ),
+        "{} warning(s) were emitted while warnings are denied:\n{}",
+        warnings.len(),
+        warnings
+            .iter()
+            .map(|w| format!("  [{}] {}", w.code, w.message))
+            .join("\n")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deny() {
+        assert!(deny(&[]).is_ok());
+        let warnings = [
+            Warning {
+                code: WarningCode::MissingActivationScript,
+                message: String::from("can't find activation scripts: [\"env.sh\"]"),
+            },
+            Warning {
+                code: WarningCode::MissingActivationScript,
+                message: String::from("can't find activation scripts: [\"other.sh\"]"),
+            },
+        ];
+        let err = deny(&warnings).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("[missing-activation-script] can't find"));
+        assert!(err
+            .help()
+            .unwrap()
+            .to_string()
+            .contains("allow-warnings = [\"missing-activation-script\"]"));
+    }
+
+    #[test]
+    fn test_warning_code_from_manifest() {
+        let codes: Vec<WarningCode> =
+            serde_json::from_str(r#"["beta-feature", "outdated-lock-file"]"#).unwrap();
+        assert_eq!(
+            codes,
+            vec![WarningCode::BetaFeature, WarningCode::OutdatedLockFile]
+        );
+        for code in codes {
+            assert_eq!(
+                serde_json::from_str::<WarningCode>(&format!("\"{code}\"")).unwrap(),
+                code
+            );
+        }
+    }
+}
@@ -191,6 +191,10 @@ impl PixiControl {
                 path: self.project_path().to_path_buf(),
                 channels: None,
                 platforms: Vec::new(),
+                interactive: false,
+                template: None,
+                imports: Vec::new(),
+                ignore_lock_file: false,
             },
         }
     }
@@ -203,6 +207,10 @@ impl PixiControl {
                 path: self.project_path().to_path_buf(),
                 channels: None,
                 platforms,
+                interactive: false,
+                template: None,
+                imports: Vec::new(),
+                ignore_lock_file: false,
             },
         }
     }
@@ -220,6 +228,8 @@ impl PixiControl {
                 no_lockfile_update: false,
                 platform: Default::default(),
                 pypi: false,
+                interactive: false,
+                from_prefix: false,
             },
         }
     }
@@ -238,7 +248,7 @@ impl PixiControl {
     /// Run a command
     pub async fn run(&self, mut args: run::Args) -> miette::Result<RunOutput> {
         args.manifest_path = args.manifest_path.or_else(|| Some(self.manifest_path()));
-        let project = self.project()?;
+        let project = self.project()?.with_clean_env(args.clean_env);
         let task = ExecutableTask::from_cmd_args(&project, args.task, Some(Platform::current()));
         let task_env = get_task_env(&project, args.lock_file_usage.into()).await?;
 
@@ -279,6 +289,11 @@ impl PixiControl {
         InstallBuilder {
             args: Args {
                 manifest_path: Some(self.manifest_path()),
+                environment: None,
+                all: false,
+                platform: None,
+                only: vec![],
+                skip: vec![],
                 lock_file_usage: LockFileUsageArgs {
                     frozen: false,
                     locked: false,
@@ -314,6 +329,9 @@ impl TasksControl<'_> {
                 depends_on: None,
                 platform,
                 cwd: None,
+                expose: false,
+                skip_if: None,
+                only_if: None,
             },
         }
     }
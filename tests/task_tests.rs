@@ -189,3 +189,45 @@ async fn test_cwd() {
         .await
         .is_err());
 }
+
+#[tokio::test]
+async fn test_clean_env() {
+    let pixi = PixiControl::new().unwrap();
+    pixi.init().without_channels().await.unwrap();
+    let manifest = fs::read_to_string(pixi.manifest_path()).unwrap();
+    fs::write(
+        pixi.manifest_path(),
+        manifest.replacen(
+            "[project]\n",
+            "[project]\npassthrough-env = [\"PIXI_TEST_CLEAN_ENV_ALLOWED\"]\n",
+            1,
+        ),
+    )
+    .unwrap();
+
+    // The variables are only set for a pixi child process, the environment of the test process is
+    // shared with the other tests.
+    let run = |clean_env: bool| {
+        let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_pixi"));
+        command
+            .arg("run")
+            .arg("--manifest-path")
+            .arg(pixi.manifest_path())
+            .args(clean_env.then_some("--clean-env"))
+            .arg("echo $PIXI_TEST_CLEAN_ENV_ALLOWED-$PIXI_TEST_CLEAN_ENV_SECRET")
+            .env("PIXI_TEST_CLEAN_ENV_ALLOWED", "allowed")
+            .env("PIXI_TEST_CLEAN_ENV_SECRET", "secret");
+        async move { command.output().await }
+    };
+
+    // Only the variables of the `passthrough-env` reach the task
+    let output = run(true).await.unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "allowed-");
+
+    let output = run(false).await.unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "allowed-secret"
+    );
+}